@@ -0,0 +1,87 @@
+//! Benchmark suite for the corrector against the full built-in registry.
+//!
+//! Run with `cargo bench --bench corrector`. With
+//! `--features bench-internals` it first prints the slowest rules for each
+//! scenario, to tell which rule a regression comes from.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, RuleContext, RuleRegistry};
+use std::hint::black_box;
+use std::sync::Arc;
+
+/// Builds every built-in rule group, as for a command whose executable isn't
+/// known.
+fn registry(config: &Config, context: &Arc<RuleContext>) -> RuleRegistry {
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(config, context), None);
+    registry
+}
+
+fn git_failure() -> Command {
+    Command::new(
+        "git pus",
+        "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush",
+        1,
+    )
+}
+
+fn unmatched() -> Command {
+    Command::new("frobnicate --all", "frobnicate: something went wrong", 1)
+}
+
+/// A failed `npm install` with a 50KB log, whose error is on the last line.
+fn long_output() -> Command {
+    let mut output = String::new();
+    while output.len() < 50 * 1024 {
+        output.push_str("npm WARN deprecated some-package@1.0.0: this version is no longer supported\n");
+    }
+    output.push_str("npm ERR! Error: EACCES: permission denied, mkdir '/usr/lib/node_modules'\n");
+    Command::new("npm install -g typescript", output, 243)
+}
+
+/// A named command to correct.
+type Scenario = (&'static str, fn() -> Command);
+
+const SCENARIOS: &[Scenario] = &[
+    ("git failure", git_failure),
+    ("unmatched command", unmatched),
+    ("50KB output", long_output),
+];
+
+/// Number of slowest rules printed per scenario.
+#[cfg(feature = "bench-internals")]
+const SLOWEST: usize = 5;
+
+#[cfg(feature = "bench-internals")]
+fn print_rule_timings(corrector: &Corrector) {
+    for (scenario, command) in SCENARIOS {
+        let command = command();
+        // Patterns are compiled on first use, which would dwarf the rest
+        corrector.get_corrections(&command);
+        println!("slowest rules for {}:", scenario);
+        for (rule, time) in corrector.rule_timings(&command).iter().take(SLOWEST) {
+            println!("  {:<40} {:>10.3?}", rule, time);
+        }
+    }
+}
+
+fn bench_corrector(c: &mut Criterion) {
+    let config = Config::default();
+    let context = Arc::new(RuleContext::default());
+    let corrector = Corrector::new(registry(&config, &context));
+
+    #[cfg(feature = "bench-internals")]
+    print_rule_timings(&corrector);
+
+    let mut group = c.benchmark_group("full registry");
+    for (scenario, command) in SCENARIOS {
+        let command = command();
+        group.bench_function(*scenario, |b| b.iter(|| corrector.get_corrections(black_box(&command))));
+    }
+    group.bench_function("registry construction", |b| b.iter(|| registry(&config, &context)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_corrector);
+criterion_main!(benches);
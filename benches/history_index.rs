@@ -0,0 +1,63 @@
+//! Benchmark for building and querying the shell history index.
+//!
+//! Run with `cargo bench --bench history_index`.
+
+use fasterthefuck::shell::{BashShell, HistoryIndex, Shell};
+use std::time::{Duration, Instant};
+
+const ENTRIES: usize = 100_000;
+const LOOKUPS: u32 = 10_000;
+
+/// Writes a synthetic history file with a realistic mix of repeated commands.
+fn write_history() -> std::path::PathBuf {
+    let executables = ["git", "cargo", "ls", "cd", "docker", "kubectl", "vim", "make"];
+    let lines: Vec<String> = (0..ENTRIES)
+        .map(|i| format!("{} arg{} --flag{}", executables[i % executables.len()], i % 997, i % 13))
+        .collect();
+
+    let path = std::env::temp_dir().join(format!("ftf_bench_history_{}", std::process::id()));
+    std::fs::write(&path, lines.join("\n")).expect("failed to write history fixture");
+    path
+}
+
+fn report(label: &str, total: Duration, iterations: u32) {
+    println!(
+        "{:<32} total {:>10.3?}  per-op {:>10.3?}",
+        label,
+        total,
+        total / iterations
+    );
+}
+
+fn main() {
+    let path = write_history();
+    let mut shell = BashShell::new().expect("failed to initialize shell");
+    shell
+        .set_env("HISTFILE".to_string(), path.display().to_string())
+        .expect("failed to set HISTFILE");
+
+    let start = Instant::now();
+    let index = HistoryIndex::build(&shell);
+    report("build (100k entries)", start.elapsed(), 1);
+    assert_eq!(index.len(), ENTRIES);
+
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        std::hint::black_box(index.frequency("git arg1 --flag1"));
+    }
+    report("frequency", start.elapsed(), LOOKUPS);
+
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        std::hint::black_box(index.most_recent(1000));
+    }
+    report("most_recent(1000)", start.elapsed(), LOOKUPS);
+
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        std::hint::black_box(index.commands_starting_with("docker").next());
+    }
+    report("commands_starting_with", start.elapsed(), LOOKUPS);
+
+    let _ = std::fs::remove_file(&path);
+}
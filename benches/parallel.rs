@@ -0,0 +1,52 @@
+//! Benchmark for matching 10, 100 and 1000 rules one after another and on
+//! the thread pool.
+//!
+//! Run with `cargo bench --bench parallel`. Without the `parallel` feature
+//! both are serial.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use fasterthefuck::{Command, Corrector, RegexRuleBuilder, RuleRegistry};
+use std::hint::black_box;
+
+/// A corrector with `count` rules that each scan the output for a pattern
+/// only the last line has.
+fn corrector(count: usize, threshold: usize) -> Corrector {
+    let mut registry = RuleRegistry::new();
+    for i in 0..count {
+        registry.add_rule(
+            RegexRuleBuilder::new(format!("rule_{}", i))
+                .match_output_regex(&format!(r"error E{}\b", i))
+                .unwrap()
+                .replace_with(|original, _| vec![format!("{} --fix", original)])
+                .build()
+                .unwrap(),
+        );
+    }
+    let mut corrector = Corrector::new(registry).without_prefilter();
+    corrector.set_parallel_threshold(threshold);
+    corrector
+}
+
+fn command() -> Command {
+    let output = "warning: unused variable\n".repeat(200) + "error E7: mismatched types\n";
+    Command::new("cargo build", output, 101)
+}
+
+fn bench_parallel(c: &mut Criterion) {
+    let command = command();
+    let mut group = c.benchmark_group("rule matching");
+    for count in [10, 100, 1000] {
+        let serial = corrector(count, usize::MAX);
+        let parallel = corrector(count, 0);
+        group.bench_with_input(BenchmarkId::new("serial", count), &command, |b, command| {
+            b.iter(|| serial.get_corrections(black_box(command)))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", count), &command, |b, command| {
+            b.iter(|| parallel.get_corrections(black_box(command)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parallel);
+criterion_main!(benches);
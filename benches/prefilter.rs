@@ -0,0 +1,42 @@
+//! Benchmark for matching every built-in rule against a command with a
+//! 50KB output, with and without the substring pre-filter.
+//!
+//! Run with `cargo bench --bench prefilter`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, RuleContext, RuleRegistry};
+use std::hint::black_box;
+use std::sync::Arc;
+
+fn corrector() -> Corrector {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    Corrector::new(registry)
+}
+
+/// A failed `npm install` with a long log that no rule's substring appears in
+/// until the last line.
+fn long_output() -> Command {
+    let mut output = String::new();
+    while output.len() < 50 * 1024 {
+        output.push_str("npm WARN deprecated some-package@1.0.0: this version is no longer supported\n");
+    }
+    output.push_str("npm ERR! Error: EACCES: permission denied, mkdir '/usr/lib/node_modules'\n");
+    Command::new("npm install -g typescript", output, 243)
+}
+
+fn bench_prefilter(c: &mut Criterion) {
+    let command = long_output();
+    let filtered = corrector();
+    let unfiltered = corrector().without_prefilter();
+
+    let mut group = c.benchmark_group("50KB output");
+    group.bench_function("with pre-filter", |b| b.iter(|| filtered.get_corrections(black_box(&command))));
+    group.bench_function("without pre-filter", |b| b.iter(|| unfiltered.get_corrections(black_box(&command))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_prefilter);
+criterion_main!(benches);
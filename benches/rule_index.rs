@@ -0,0 +1,41 @@
+//! Benchmark for correcting a command against 500 rules, with and without
+//! the leading-token index.
+//!
+//! Run with `cargo bench --bench rule_index`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fasterthefuck::{Command, Corrector, RegexRuleBuilder, RuleRegistry};
+use std::hint::black_box;
+
+/// 500 rules for 50 executables, 10 rules each.
+fn corrector() -> Corrector {
+    let mut registry = RuleRegistry::new();
+    for tool in 0..50 {
+        for subcommand in 0..10 {
+            registry.add_rule(
+                RegexRuleBuilder::new(format!("tool{}_sub{}", tool, subcommand))
+                    .match_command_regex(&format!(r"^tool{}\s+sub{}\b", tool, subcommand))
+                    .unwrap()
+                    .match_output_regex(r"error: (\w+)")
+                    .unwrap()
+                    .replace_simple(format!("tool{} sub{} --fix", tool, subcommand))
+                    .unwrap(),
+            );
+        }
+    }
+    Corrector::new(registry)
+}
+
+fn bench_rule_index(c: &mut Criterion) {
+    let command = Command::new("tool7 sub3 --all", "error: something went wrong", 1);
+    let indexed = corrector();
+    let unindexed = corrector().without_index();
+
+    let mut group = c.benchmark_group("500 rules");
+    group.bench_function("indexed", |b| b.iter(|| indexed.get_corrections(black_box(&command))));
+    group.bench_function("unindexed", |b| b.iter(|| unindexed.get_corrections(black_box(&command))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_rule_index);
+criterion_main!(benches);
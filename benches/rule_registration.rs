@@ -0,0 +1,42 @@
+//! Benchmark for building every built-in rule group repeatedly, as a
+//! long-lived process that rebuilds its registry would.
+//!
+//! Run with `cargo bench --bench rule_registration`.
+
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Config, RuleContext, RuleRegistry};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const REGISTRATIONS: u32 = 100;
+
+/// Builds every built-in rule group into a registry.
+fn register_all(config: &Config, context: &Arc<RuleContext>) -> RuleRegistry {
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(config, context), None);
+    registry
+}
+
+fn report(label: &str, total: Duration, iterations: u32) {
+    println!(
+        "{:<32} total {:>10.3?}  per-op {:>10.3?}",
+        label,
+        total,
+        total / iterations
+    );
+}
+
+fn main() {
+    let config = Config::default();
+    let context = Arc::new(RuleContext::default());
+
+    let start = Instant::now();
+    let rules = register_all(&config, &context).len();
+    report(&format!("first registration ({} rules)", rules), start.elapsed(), 1);
+
+    let start = Instant::now();
+    for _ in 0..REGISTRATIONS {
+        std::hint::black_box(register_all(&config, &context));
+    }
+    report("register all x100", start.elapsed(), REGISTRATIONS);
+}
@@ -0,0 +1,60 @@
+//! Example ftf rule compiled to WASM: corrects `dokcer` to `docker`.
+//!
+//! Build it and install it where ftf looks for WASM rules:
+//!
+//! ```sh
+//! cargo build --release --target wasm32-unknown-unknown
+//! cp target/wasm32-unknown-unknown/release/ftf_wasm_plugin_example.wasm \
+//!    ~/.config/fasterthefuck/wasm/docker_typo.wasm
+//! ```
+//!
+//! See the `plugins::wasm` module of ftf for the ABI.
+
+use serde_json::{json, Value};
+
+/// The input buffer the host writes the command into, reused by every call.
+static mut INPUT: Vec<u8> = Vec::new();
+
+/// The corrections of the last call, length-prefixed, kept alive until the
+/// next one.
+static mut OUTPUT: Vec<u8> = Vec::new();
+
+/// Gets a buffer of `len` bytes for the host to write a command into.
+#[no_mangle]
+pub extern "C" fn alloc(len: i32) -> *mut u8 {
+    // SAFETY: WASM is single-threaded and the host calls one export at a time
+    let input = unsafe { &mut *std::ptr::addr_of_mut!(INPUT) };
+    *input = vec![0; len as usize];
+    input.as_mut_ptr()
+}
+
+/// Gets the corrected script for the command JSON at `ptr`, if it's a typo.
+fn correct(ptr: *const u8, len: i32) -> Option<String> {
+    // SAFETY: the host wrote `len` bytes of JSON at `ptr`, from `alloc`
+    let input = unsafe { std::slice::from_raw_parts(ptr, len as usize) };
+    let command: Value = serde_json::from_slice(input).ok()?;
+    let script = command["script"].as_str()?;
+    let rest = script.strip_prefix("dokcer")?;
+    Some(format!("docker{}", rest))
+}
+
+#[no_mangle]
+pub extern "C" fn matches(ptr: *const u8, len: i32) -> i32 {
+    i32::from(correct(ptr, len).is_some())
+}
+
+#[no_mangle]
+pub extern "C" fn corrections(ptr: *const u8, len: i32) -> *const u8 {
+    let corrections = match correct(ptr, len) {
+        Some(script) => json!([{ "script": script, "priority": 900 }]),
+        None => json!([]),
+    };
+    let json = corrections.to_string();
+
+    // SAFETY: as in `alloc`
+    let output = unsafe { &mut *std::ptr::addr_of_mut!(OUTPUT) };
+    output.clear();
+    output.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    output.extend_from_slice(json.as_bytes());
+    output.as_ptr()
+}
@@ -0,0 +1,69 @@
+#ifndef FASTERTHEFUCK_H
+#define FASTERTHEFUCK_H
+
+/* Generated by cbindgen from src/lib.rs; don't edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The call succeeded.
+#define FTF_OK 0
+
+// An argument was null where it can't be, or wasn't UTF-8.
+#define FTF_ERROR_ARGUMENT -1
+
+// The config couldn't be loaded.
+#define FTF_ERROR_CONFIG -2
+
+// The result couldn't be encoded or allocated.
+#define FTF_ERROR_RESULT -3
+
+// ftf panicked; it's safe to keep calling it.
+#define FTF_ERROR_PANIC -4
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Loads the config used by later calls from the TOML file at `path`, or
+// from `~/.config/fasterthefuck/config.toml` if `path` is null, and throws
+// away the rules built so far. A missing file gives the default config.
+//
+// Returns `FTF_OK`, `FTF_ERROR_ARGUMENT` if `path` isn't UTF-8, or
+// `FTF_ERROR_CONFIG` if the file can't be read or parsed, in which case the
+// previous config is kept.
+//
+// # Safety
+//
+// `path` must be null or point to a NUL-terminated string.
+int ftf_init_with_config(const char *path);
+
+// Corrects `script`, which failed with `output` and `exit_code`, in the
+// current directory. On success `*out_json` is a JSON array of corrections,
+// best first and empty if there are none, to be freed with `ftf_free`;
+// otherwise it's null.
+//
+// Returns `FTF_OK`, `FTF_ERROR_ARGUMENT` if a pointer is null or a string
+// isn't UTF-8, `FTF_ERROR_RESULT` if the corrections can't be returned, or
+// `FTF_ERROR_PANIC`.
+//
+// # Safety
+//
+// `script` and `output` must point to NUL-terminated strings, and
+// `out_json` to writable memory for a pointer.
+int ftf_correct(const char *script, const char *output, int exit_code, char **out_json);
+
+// Frees a string returned by ftf. Null is ignored.
+//
+// # Safety
+//
+// `ptr` must be null or a string returned by ftf that hasn't been freed.
+void ftf_free(char *ptr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FASTERTHEFUCK_H */
@@ -0,0 +1,334 @@
+//! C interface, for integrations that aren't written in Rust.
+//!
+//! This crate builds `libfasterthefuck_ffi`, a shared library exporting:
+//!
+//! - `ftf_init_with_config(path)`: loads the config at `path`, or the
+//!   default one if `path` is null, for the calls that follow.
+//! - `ftf_correct(script, output, exit_code, out_json)`: corrects a failed
+//!   command as `ftf` would in the current directory, and stores a JSON array
+//!   of corrections, best first, in `*out_json`:
+//!
+//!   ```text
+//!   [{"script":"git push","priority":500,"side_effect":null,"rollbacks":[],"rule":"git_not_command"}]
+//!   ```
+//!
+//! - `ftf_free(ptr)`: frees a string returned by `ftf_correct`.
+//!
+//! Every function returns `FTF_OK` or a negative `FTF_ERROR_*` code. Strings
+//! are NUL-terminated UTF-8, and returned ones are allocated with `malloc`.
+//! The declarations are in `include/fasterthefuck.h`, generated in this
+//! directory with cbindgen:
+//!
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/fasterthefuck.h src/lib.rs
+//! ```
+//!
+//! The config and learned priorities are loaded once, and the rules built
+//! once per directory, and shared by every call, which may come from any
+//! thread. Like `ftf serve`, the rules are rebuilt when the directory
+//! changes or they're older than [`REGISTRY_MAX_AGE`], to notice newly
+//! installed programs; `ftf_init_with_config` throws them away. A panic
+//! never crosses the boundary; it's reported as `FTF_ERROR_PANIC`.
+
+use fasterthefuck::daemon::{self, Request};
+use fasterthefuck::learning::AdaptivePriorities;
+use fasterthefuck::rpc::REGISTRY_MAX_AGE;
+use fasterthefuck::{stats, BashShell, Config, Corrector, RuleContext};
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+/// The call succeeded.
+pub const FTF_OK: c_int = 0;
+/// An argument was null where it can't be, or wasn't UTF-8.
+pub const FTF_ERROR_ARGUMENT: c_int = -1;
+/// The config couldn't be loaded.
+pub const FTF_ERROR_CONFIG: c_int = -2;
+/// The result couldn't be encoded or allocated.
+pub const FTF_ERROR_RESULT: c_int = -3;
+/// ftf panicked; it's safe to keep calling it.
+pub const FTF_ERROR_PANIC: c_int = -4;
+
+/// What every call shares, set up by `ftf_init_with_config` or the first
+/// correction.
+static HANDLE: Mutex<Option<Handle>> = Mutex::new(None);
+
+/// The loaded config and learned priorities, and the rules last built.
+struct Handle {
+    config: Arc<Config>,
+    learned: AdaptivePriorities,
+    rules: Option<Rules>,
+}
+
+/// Rules built for one directory.
+struct Rules {
+    cwd: PathBuf,
+    built: Instant,
+    corrector: Arc<Corrector>,
+}
+
+impl Handle {
+    /// Creates a handle for `config`, reading the learned priorities from
+    /// the correction log.
+    fn new(config: Config) -> Self {
+        let learned = stats::default_log_path()
+            .and_then(|path| AdaptivePriorities::load(&path).ok())
+            .unwrap_or_default();
+        Self {
+            config: Arc::new(config),
+            learned,
+            rules: None,
+        }
+    }
+
+    /// Gets the rules for `cwd`, building them if needed.
+    fn corrector(&mut self, cwd: PathBuf) -> Arc<Corrector> {
+        let stale = self
+            .rules
+            .as_ref()
+            .is_none_or(|rules| rules.cwd != cwd || rules.built.elapsed() > REGISTRY_MAX_AGE);
+        if stale {
+            let context = Arc::new(RuleContext::new(Box::new(BashShell::in_dir(cwd.clone()))));
+            let corrector =
+                Corrector::for_any_command(self.config.factories(&context), &self.config, &self.learned, &context);
+            self.rules = Some(Rules {
+                cwd,
+                built: Instant::now(),
+                corrector: Arc::new(corrector),
+            });
+        }
+        Arc::clone(&self.rules.as_ref().expect("rules were just built").corrector)
+    }
+}
+
+/// Runs `f`, turning a panic into `FTF_ERROR_PANIC`.
+fn guard(f: impl FnOnce() -> c_int) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(FTF_ERROR_PANIC)
+}
+
+/// Locks the shared handle, whatever a panicking call left in it.
+fn handle() -> MutexGuard<'static, Option<Handle>> {
+    HANDLE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Gets the shared config and its rules for `cwd`, loading the default
+/// config if there's none yet. The lock isn't held while correcting, so
+/// calls from several threads run at once.
+fn rules_for(cwd: PathBuf) -> (Arc<Config>, Arc<Corrector>) {
+    let mut handle = handle();
+    let handle = handle.get_or_insert_with(|| Handle::new(Config::load_default().unwrap_or_default()));
+    (Arc::clone(&handle.config), handle.corrector(cwd))
+}
+
+/// Reads a C string argument.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn argument<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    CStr::from_ptr(ptr).to_str().ok()
+}
+
+/// Copies `text` into a NUL-terminated string allocated with `malloc`.
+fn malloc_string(text: &str) -> Option<*mut c_char> {
+    if text.contains('\0') {
+        return None;
+    }
+    // SAFETY: the allocation is checked, and has room for the text and the NUL
+    unsafe {
+        let ptr = libc::malloc(text.len() + 1).cast::<c_char>();
+        if ptr.is_null() {
+            return None;
+        }
+        std::ptr::copy_nonoverlapping(text.as_ptr().cast::<c_char>(), ptr, text.len());
+        *ptr.add(text.len()) = 0;
+        Some(ptr)
+    }
+}
+
+/// Loads the config used by later calls from the TOML file at `path`, or
+/// from `~/.config/fasterthefuck/config.toml` if `path` is null, and throws
+/// away the rules built so far. A missing file gives the default config.
+///
+/// Returns `FTF_OK`, `FTF_ERROR_ARGUMENT` if `path` isn't UTF-8, or
+/// `FTF_ERROR_CONFIG` if the file can't be read or parsed, in which case the
+/// previous config is kept.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ftf_init_with_config(path: *const c_char) -> c_int {
+    guard(|| {
+        let loaded = if path.is_null() {
+            Config::load_default()
+        } else {
+            match argument(path) {
+                Some(path) => Config::load_from_file(Path::new(path)),
+                None => return FTF_ERROR_ARGUMENT,
+            }
+        };
+        match loaded {
+            Ok(loaded) => {
+                *handle() = Some(Handle::new(loaded));
+                FTF_OK
+            }
+            Err(_) => FTF_ERROR_CONFIG,
+        }
+    })
+}
+
+/// Corrects `script`, which failed with `output` and `exit_code`, in the
+/// current directory. On success `*out_json` is a JSON array of corrections,
+/// best first and empty if there are none, to be freed with `ftf_free`;
+/// otherwise it's null.
+///
+/// Returns `FTF_OK`, `FTF_ERROR_ARGUMENT` if a pointer is null or a string
+/// isn't UTF-8, `FTF_ERROR_RESULT` if the corrections can't be returned, or
+/// `FTF_ERROR_PANIC`.
+///
+/// # Safety
+///
+/// `script` and `output` must point to NUL-terminated strings, and
+/// `out_json` to writable memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ftf_correct(
+    script: *const c_char,
+    output: *const c_char,
+    exit_code: c_int,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    if out_json.is_null() {
+        return FTF_ERROR_ARGUMENT;
+    }
+    *out_json = std::ptr::null_mut();
+
+    guard(|| {
+        let (Some(script), Some(output)) = (argument(script), argument(output)) else {
+            return FTF_ERROR_ARGUMENT;
+        };
+        let Ok(cwd) = std::env::current_dir() else {
+            return FTF_ERROR_ARGUMENT;
+        };
+
+        let (config, corrector) = rules_for(cwd.clone());
+        let corrections = daemon::correct_with(&corrector, &config, &Request::new(script, output, exit_code, cwd));
+        match serde_json::to_string(&corrections).ok().as_deref().and_then(malloc_string) {
+            Some(json) => {
+                *out_json = json;
+                FTF_OK
+            }
+            None => FTF_ERROR_RESULT,
+        }
+    })
+}
+
+/// Frees a string returned by ftf. Null is ignored.
+///
+/// # Safety
+///
+/// `ptr` must be null or a string returned by ftf that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ftf_free(ptr: *mut c_char) {
+    libc::free(ptr.cast());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fasterthefuck::CorrectedCommand;
+    use std::ffi::CString;
+
+    /// Calls `ftf_correct` and decodes what it returns.
+    fn correct(script: &str, output: &str, exit_code: c_int) -> (c_int, Option<Vec<CorrectedCommand>>) {
+        let script = CString::new(script).unwrap();
+        let output = CString::new(output).unwrap();
+        let mut json = std::ptr::null_mut();
+        // SAFETY: valid strings and out pointer, and the result is freed once
+        unsafe {
+            let status = ftf_correct(script.as_ptr(), output.as_ptr(), exit_code, &mut json);
+            if json.is_null() {
+                return (status, None);
+            }
+            let corrections = serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            ftf_free(json);
+            (status, Some(corrections))
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file = std::env::temp_dir().join(format!("ftf_ffi_{}", std::process::id()));
+        std::fs::write(&file, "[rules.git_not_command]\npriority = 5\n").unwrap();
+        let path = CString::new(file.to_str().unwrap()).unwrap();
+        // SAFETY: a valid path
+        assert_eq!(unsafe { ftf_init_with_config(path.as_ptr()) }, FTF_OK);
+
+        let (status, corrections) = correct(
+            "git pus",
+            "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush",
+            1,
+        );
+        assert_eq!(status, FTF_OK);
+        let corrections = corrections.unwrap();
+        assert_eq!(corrections[0].script, "git push");
+        assert_eq!(corrections[0].priority, 5);
+
+        let (status, corrections) = correct("true", "", 0);
+        assert_eq!(status, FTF_OK);
+        assert_eq!(corrections, Some(Vec::new()));
+
+        // The rules are built once for the directory, and again after init
+        let cwd = std::env::current_dir().unwrap();
+        let (_, built) = rules_for(cwd.clone());
+        assert!(Arc::ptr_eq(&built, &rules_for(cwd.clone()).1));
+        // SAFETY: a valid path
+        assert_eq!(unsafe { ftf_init_with_config(path.as_ptr()) }, FTF_OK);
+        assert!(!Arc::ptr_eq(&built, &rules_for(cwd).1));
+
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut json = std::ptr::null_mut();
+        let output = CString::new("").unwrap();
+        let invalid = [0xffu8, 0];
+        // SAFETY: null and non-UTF-8 strings are what's being checked
+        unsafe {
+            assert_eq!(ftf_correct(std::ptr::null(), output.as_ptr(), 1, &mut json), FTF_ERROR_ARGUMENT);
+            assert!(json.is_null());
+            assert_eq!(
+                ftf_correct(invalid.as_ptr().cast(), output.as_ptr(), 1, &mut json),
+                FTF_ERROR_ARGUMENT
+            );
+            assert_eq!(
+                ftf_correct(output.as_ptr(), output.as_ptr(), 1, std::ptr::null_mut()),
+                FTF_ERROR_ARGUMENT
+            );
+            assert_eq!(ftf_init_with_config(invalid.as_ptr().cast()), FTF_ERROR_ARGUMENT);
+            ftf_free(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_bad_config_is_reported() {
+        let file = std::env::temp_dir().join(format!("ftf_ffi_bad_{}", std::process::id()));
+        std::fs::write(&file, "[global\n").unwrap();
+        let path = CString::new(file.to_str().unwrap()).unwrap();
+        // SAFETY: a valid path
+        assert_eq!(unsafe { ftf_init_with_config(path.as_ptr()) }, FTF_ERROR_CONFIG);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_panic_stays_inside() {
+        assert_eq!(guard(|| panic!("rule bug")), FTF_ERROR_PANIC);
+        assert_eq!(guard(|| FTF_OK), FTF_OK);
+    }
+}
@@ -30,6 +30,22 @@ fi
 _FTF_LAST_COMMAND=""
 _FTF_LAST_EXIT_CODE=0
 _FTF_LAST_OUTPUT=""
+# Set while the last command hasn't been written to the session cache yet
+_FTF_UNRECORDED=""
+
+# Session cache: the last few commands of this terminal, read by rules that
+# need the command *before* the failed one (one directory per command).
+# Only kept under XDG_RUNTIME_DIR: in a shared TMPDIR another user could
+# plant commands in it.
+_FTF_SESSION_CAPACITY=10
+if [[ -z "$FTF_SESSION_DIR" && -n "$XDG_RUNTIME_DIR" ]]; then
+    _ftf_tty="$(tty 2>/dev/null)"
+    _ftf_tty="${_ftf_tty#/}"
+    FTF_SESSION_DIR="$XDG_RUNTIME_DIR/fasterthefuck/${_ftf_tty//[^a-zA-Z0-9]/_}"
+    unset _ftf_tty
+fi
+export FTF_SESSION_DIR
+_FTF_SESSION_SEQ=0
 
 # Function to capture command execution details
 _fasterthefuck_capture() {
@@ -44,7 +60,38 @@ _fasterthefuck_capture() {
     # Store for later use
     _FTF_LAST_COMMAND="$command_line"
     _FTF_LAST_EXIT_CODE=$exit_code
+    _FTF_UNRECORDED=1
+
+    return $exit_code
+}
+
+# Record the last command into the session cache, once.
+# The hook doesn't capture output: the output file is always empty unless
+# something else filled _FTF_LAST_OUTPUT. The last command itself is kept
+# for `ftf`.
+_fasterthefuck_record() {
+    local exit_code=$?
+    [[ -z "$_FTF_UNRECORDED" || -z "$_FTF_LAST_COMMAND" ]] && return $exit_code
+    # The DEBUG trap runs before the command, so only now is its status known
+    _FTF_LAST_EXIT_CODE=$exit_code
+
+    [[ -n "$FTF_SESSION_DIR" ]] || return $exit_code
+    mkdir -p -m 700 "$FTF_SESSION_DIR" 2>/dev/null || return $exit_code
+    [[ -O "$FTF_SESSION_DIR" ]] || return $exit_code
+    if [[ $_FTF_SESSION_SEQ -eq 0 ]]; then
+        _FTF_SESSION_SEQ=$(ls "$FTF_SESSION_DIR" 2>/dev/null | grep -E '^[0-9]+$' | sort -n | tail -n 1)
+        _FTF_SESSION_SEQ=${_FTF_SESSION_SEQ:-0}
+    fi
+    _FTF_SESSION_SEQ=$((_FTF_SESSION_SEQ + 1))
 
+    local entry="$FTF_SESSION_DIR/$_FTF_SESSION_SEQ"
+    mkdir -p "$entry" &&
+        printf '%s' "$_FTF_LAST_COMMAND" > "$entry/command" &&
+        printf '%s' "$_FTF_LAST_OUTPUT" > "$entry/output" &&
+        printf '%s' "$exit_code" > "$entry/exit_code"
+    rm -rf "$FTF_SESSION_DIR/$((_FTF_SESSION_SEQ - _FTF_SESSION_CAPACITY))" 2>/dev/null
+
+    _FTF_UNRECORDED=""
     return $exit_code
 }
 
@@ -113,18 +160,59 @@ EOF
         return 1
     fi
 
-    # TODO: Actually invoke the binary with the command details
-    # For now, just demonstrate the integration
-    echo "fasterthefuck: Would correct '$cmd_to_correct'"
-    echo "  (Exit code: $exit_code)"
-    [[ -n "$output" ]] && echo "  (Output: $output)"
-    echo ""
-    echo "Note: Full binary integration coming in Phase 2.3"
+    # Never let the corrector wait for an answer nobody can give (CI, pipes)
+    local -a interaction=()
+    if [[ ! -t 0 || ! -t 2 || "$TERM" == "dumb" || -n "$CI" ]]; then
+        interaction=(--no-interaction)
+    fi
+
+    # Ask the corrector for a fix. Lines starting with "# ftf-effect: " name
+    # side effects only this shell can perform, run before the correction.
+    local corrected
+    corrected="$(command "$FTF_CMD" "${interaction[@]}" --command "$cmd_to_correct" --output "$output" --exit-code "$exit_code")" || return $?
+
+    local script="" line
+    local -a effects=()
+    while IFS= read -r line; do
+        if [[ "$line" == "# ftf-effect: "* ]]; then
+            effects+=("${line#"# ftf-effect: "}")
+        else
+            script+="${script:+$'\n'}$line"
+        fi
+    done <<< "$corrected"
+
+    # Before the script, so a retry following an install in it looks the
+    # command up again instead of using the stale cached path
+    local effect
+    for effect in "${effects[@]}"; do
+        case "$effect" in
+            rehash)
+                if [[ -n "$ZSH_VERSION" ]]; then rehash; else hash -r; fi
+                ;;
+        esac
+    done
+
+    eval "$script"
+    local status=$?
+
+    # Record the correction itself, so up-arrow runs it again
+    if [[ -n "$script" ]]; then
+        if [[ -n "$ZSH_VERSION" ]]; then
+            print -s -- "$script"
+        else
+            history -s -- "$script"
+        fi
+    fi
+
+    return $status
 }
 
+export -f ftf
+export FTF_CMD
+
 # Set up command capture using DEBUG trap
-# This captures every command executed
+# This captures every command executed, so it's set up last
 trap '_fasterthefuck_capture' DEBUG
 
-export -f ftf
-export FTF_CMD
+# Record each finished command into the session cache before the prompt
+PROMPT_COMMAND="_fasterthefuck_record${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
@@ -0,0 +1,42 @@
+//! Runs every built-in rule over arbitrary failures and fails on any panic
+//! or on a correction that would change nothing.
+//!
+//! The input is split on its first two NUL bytes into the script, the output
+//! and the exit code, each decoded lossily like ftf decodes what it reads.
+//! Run with `cargo fuzz run corrector` from the repository root.
+
+#![no_main]
+
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, RuleContext, RuleRegistry};
+use libfuzzer_sys::fuzz_target;
+use std::sync::{Arc, OnceLock};
+
+fn corrector() -> &'static Corrector {
+    static CORRECTOR: OnceLock<Corrector> = OnceLock::new();
+    CORRECTOR.get_or_init(|| {
+        let context = Arc::new(RuleContext::default());
+        let mut registry = RuleRegistry::new();
+        registry.add_factories(builtin_factories(&Config::default(), &context), None);
+        Corrector::new(registry)
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut parts = data.splitn(3, |byte| *byte == 0);
+    let script = String::from_utf8_lossy(parts.next().unwrap_or_default());
+    let output = String::from_utf8_lossy(parts.next().unwrap_or_default());
+    let exit_code = parts
+        .next()
+        .map_or(1, |code| code.iter().fold(0i32, |code, byte| code.wrapping_mul(31) ^ *byte as i32));
+    let command = Command::new(script, output, exit_code);
+
+    let set = corrector().correct(&command);
+    assert!(set.failures.is_empty(), "{:?} panicked: {:?}", command, set.failures);
+    for correction in &set.corrections {
+        assert!(!correction.script.trim().is_empty(), "empty correction for {:?}", command);
+        if correction.side_effect.is_none() {
+            assert_ne!(correction.script, command.script, "{:?} left {:?} unchanged", correction.rule, command);
+        }
+    }
+});
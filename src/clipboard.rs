@@ -0,0 +1,174 @@
+//! Copying a correction to the system clipboard, for `--copy`.
+//!
+//! The clipboard is reached through the first tool found of wl-copy (on
+//! Wayland), xclip (on X11), pbcopy (macOS) and clip.exe (Windows and WSL).
+//! Over SSH those would fill the remote host's clipboard, so there, and
+//! wherever none is installed, the text is sent to the terminal in an OSC 52
+//! escape sequence, which terminals that support it put on the clipboard of
+//! the machine they run on.
+
+use crate::{Error, Result, Shell};
+use std::fmt;
+use std::io::Write;
+use std::process::{Command as StdCommand, Stdio};
+
+/// A way of putting text on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    WlCopy,
+    Xclip,
+    Pbcopy,
+    ClipExe,
+    /// The OSC 52 escape sequence, written to the terminal
+    Osc52,
+}
+
+/// The tools tried, in order, with the environment variable each needs set.
+const TOOLS: [(Backend, Option<&str>); 4] = [
+    (Backend::WlCopy, Some("WAYLAND_DISPLAY")),
+    (Backend::Xclip, Some("DISPLAY")),
+    (Backend::Pbcopy, None),
+    (Backend::ClipExe, None),
+];
+
+impl Backend {
+    /// Gets the command that reads the text to copy on stdin, or `None` for
+    /// OSC 52.
+    pub fn command(self) -> Option<&'static [&'static str]> {
+        match self {
+            Backend::WlCopy => Some(&["wl-copy"]),
+            Backend::Xclip => Some(&["xclip", "-selection", "clipboard"]),
+            Backend::Pbcopy => Some(&["pbcopy"]),
+            Backend::ClipExe => Some(&["clip.exe"]),
+            Backend::Osc52 => None,
+        }
+    }
+
+    /// Picks the backend for the session `shell` runs in: OSC 52 over SSH,
+    /// otherwise the first tool installed, and OSC 52 if there's none.
+    pub fn probe(shell: &dyn Shell) -> Self {
+        if shell.env("SSH_CONNECTION").or_else(|| shell.env("SSH_TTY")).is_some() {
+            return Backend::Osc52;
+        }
+        TOOLS
+            .into_iter()
+            .filter(|(_, needs)| needs.is_none_or(|key| shell.env(key).is_some_and(|value| !value.is_empty())))
+            .map(|(backend, _)| backend)
+            .find(|backend| {
+                let program = backend.command().map(|command| command[0]).unwrap_or_default();
+                shell.command_exists(program).unwrap_or(false)
+            })
+            .unwrap_or(Backend::Osc52)
+    }
+
+    /// Puts `text` on the clipboard. OSC 52 is written to `terminal`.
+    pub fn copy(self, text: &str, terminal: &mut dyn Write) -> Result<()> {
+        let Some(command) = self.command() else {
+            terminal.write_all(osc52(text).as_bytes())?;
+            return Ok(terminal.flush()?);
+        };
+
+        let mut child = StdCommand::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // Dropped once written, so the tool sees the end of its input
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::Other(format!("{} exited with {}", command[0], status)))
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.command() {
+            Some(command) => f.write_str(command[0]),
+            None => f.write_str("OSC 52"),
+        }
+    }
+}
+
+/// Frames `text` in the OSC 52 sequence that sets the clipboard:
+/// `ESC ] 52 ; c ; <base64> BEL`.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+/// Encodes `bytes` as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| group | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64("git push → ✓".as_bytes()), "Z2l0IHB1c2gg4oaSIOKckw==");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+    }
+
+    #[test]
+    fn test_osc52_framing() {
+        assert_eq!(osc52("git push"), "\x1b]52;c;Z2l0IHB1c2g=\x07");
+        assert_eq!(osc52(""), "\x1b]52;c;\x07");
+
+        let mut terminal = Vec::new();
+        Backend::Osc52.copy("ls", &mut terminal).unwrap();
+        assert_eq!(terminal, b"\x1b]52;c;bHM=\x07");
+    }
+
+    #[test]
+    fn test_probe_order() {
+        let all = || {
+            MockShell::new()
+                .with_output("wl-copy", "", 0)
+                .with_output("xclip", "", 0)
+                .with_output("pbcopy", "", 0)
+                .with_output("clip.exe", "", 0)
+        };
+        let wayland = all().with_env("WAYLAND_DISPLAY", "wayland-0").with_env("DISPLAY", ":0");
+        assert_eq!(Backend::probe(&wayland), Backend::WlCopy);
+        assert_eq!(Backend::probe(&all().with_env("DISPLAY", ":0")), Backend::Xclip);
+        // Without a display the X11 and Wayland tools can't work
+        assert_eq!(Backend::probe(&all()), Backend::Pbcopy);
+        let wsl = MockShell::new().with_output("clip.exe", "", 0).with_env("DISPLAY", ":0");
+        assert_eq!(Backend::probe(&wsl), Backend::ClipExe);
+        assert_eq!(Backend::probe(&MockShell::new().with_env("DISPLAY", ":0")), Backend::Osc52);
+    }
+
+    #[test]
+    fn test_probe_prefers_osc52_over_ssh() {
+        let shell = MockShell::new().with_output("xclip", "", 0).with_env("DISPLAY", ":0");
+        assert_eq!(Backend::probe(&shell.with_env("SSH_CONNECTION", "10.0.0.2 51000 10.0.0.1 22")), Backend::Osc52);
+        let shell = MockShell::new().with_output("pbcopy", "", 0).with_env("SSH_TTY", "/dev/pts/3");
+        assert_eq!(Backend::probe(&shell), Backend::Osc52);
+    }
+}
@@ -0,0 +1,3 @@
+//! Compatibility with other command correctors' configuration.
+
+pub mod thefuck;
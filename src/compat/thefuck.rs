@@ -0,0 +1,674 @@
+//! Importing thefuck's configuration.
+//!
+//! thefuck reads `~/.config/thefuck/settings.py`, a Python file of
+//! assignments:
+//!
+//! ```text
+//! rules = DEFAULT_RULES + ['git_push']
+//! exclude_rules = ['git_pull']
+//! priority = {'sudo': 100}
+//! require_confirmation = True
+//! ```
+//!
+//! and `THEFUCK_*` environment variables, which take precedence:
+//!
+//! ```text
+//! export THEFUCK_EXCLUDE_RULES='git_pull:git_push'
+//! export THEFUCK_PRIORITY='sudo=100:git_push=500'
+//! ```
+//!
+//! [`Settings`] reads both without running any Python, line by line, and
+//! [`import`] turns them into an ftf [`Config`]. thefuck rules are mapped to
+//! the ftf rules that correct the same mistakes through [`ALIASES`]; rules
+//! and settings ftf has no equivalent for are reported rather than dropped
+//! silently.
+
+use crate::config::RuleConfig;
+use crate::{fuzzy, Config, Error, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+
+/// thefuck rules and the ftf rules that correct the same mistakes, by
+/// thefuck name.
+pub const ALIASES: &[(&str, &[&str])] = &[
+    ("apt_get_search", &["apt_get_search"]),
+    ("aws_cli", &["aws_invalid_choice"]),
+    ("az_cli", &["az_not_in_group"]),
+    ("cd_correction", &["cd_correction"]),
+    ("chmod_x", &["chmod_execute"]),
+    ("cp_omitting_directory", &["cp_recursive"]),
+    ("git_branch_0flag", &["git_branch_0flag"]),
+    ("git_branch_delete", &["git_branch_delete"]),
+    ("git_branch_exists", &["git_branch_exists"]),
+    ("git_commit_amend", &["git_commit_amend"]),
+    ("git_not_command", &["git_not_command"]),
+    ("git_pull", &["git_pull_rebase"]),
+    ("git_pull_uncommitted_changes", &["git_stash"]),
+    ("git_push", &["git_push_set_upstream"]),
+    ("git_push_force", &["git_push_force"]),
+    ("git_stash", &["git_stash"]),
+    ("history", &["history_search"]),
+    ("mkdir_p", &["mkdir_p"]),
+    ("rm_dir", &["rm_recursive"]),
+    ("ssh_known_hosts", &["ssh_host_key_changed"]),
+    ("sudo", &["sudo_permission_denied", "sudo_apt", "systemctl_sudo", "journalctl_sudo"]),
+    ("systemctl", &["systemctl_swapped_args"]),
+    ("terraform_init", &["terraform_init"]),
+    ("tmux", &["tmux_unknown_command"]),
+];
+
+/// Name thefuck's settings use for its built-in rule list.
+const DEFAULT_RULES: &str = "DEFAULT_RULES";
+
+/// Minimum similarity for an ftf rule to be suggested for an unmapped one.
+const MIN_SUGGESTION_SIMILARITY: f64 = 0.5;
+
+/// Gets the ftf rules for a thefuck rule, empty if there are none.
+pub fn ftf_rules(thefuck_rule: &str) -> &'static [&'static str] {
+    ALIASES
+        .iter()
+        .find(|(name, _)| *name == thefuck_rule)
+        .map_or(&[], |(_, rules)| rules)
+}
+
+/// Gets where thefuck reads its settings: `$XDG_CONFIG_HOME/thefuck` or
+/// `~/.config/thefuck`, on every platform.
+pub fn default_settings_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("thefuck").join("settings.py"))
+}
+
+/// The rules a `rules` setting enables.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleList {
+    /// Whether thefuck's default rules are included
+    pub defaults: bool,
+    /// The rules listed by name
+    pub names: Vec<String>,
+}
+
+/// The parts of thefuck's settings ftf can import.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    /// Enabled rules, or `None` for thefuck's defaults
+    pub rules: Option<RuleList>,
+    /// Disabled rules
+    pub exclude_rules: Vec<String>,
+    /// Rule priorities; lower comes first, as in ftf
+    pub priority: Vec<(String, i32)>,
+    /// Whether corrections are confirmed before they run
+    pub require_confirmation: Option<bool>,
+    /// Whether debug output is shown
+    pub debug: Option<bool>,
+    /// History entries scanned by history rules
+    pub history_limit: Option<usize>,
+    /// Settings without an ftf equivalent, in the order they were found
+    pub ignored: Vec<String>,
+}
+
+impl Settings {
+    /// Parses a settings.py. Lines exporting `THEFUCK_*` variables are read
+    /// as environment variables, so a shell profile can be imported too.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut settings = Self::default();
+        for (line_number, statement) in statements(source) {
+            let env = statement.strip_prefix("export ").unwrap_or(&statement).trim_start();
+            let result = match env.split_once('=') {
+                Some((name, value)) if name.starts_with("THEFUCK_") => settings.set_env(name, &unquote(value)),
+                _ => settings.set_python(&statement),
+            };
+            result.map_err(|e| Error::config(format!("thefuck settings line {}: {}", line_number, e)))?;
+        }
+        Ok(settings)
+    }
+
+    /// Applies `THEFUCK_*` environment variables over the settings, as
+    /// thefuck does. Other variables are ignored.
+    pub fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+        for (name, value) in vars.into_iter().filter(|(name, _)| name.starts_with("THEFUCK_")) {
+            self.set_env(&name, &value)
+                .map_err(|e| Error::config(format!("{}: {}", name, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if nothing was set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Sets one `THEFUCK_*` variable.
+    fn set_env(&mut self, name: &str, value: &str) -> std::result::Result<(), String> {
+        let list = || value.split(':').map(str::trim).filter(|item| !item.is_empty());
+        match name {
+            "THEFUCK_RULES" => {
+                let mut rules = RuleList::default();
+                for item in list() {
+                    match item {
+                        DEFAULT_RULES => rules.defaults = true,
+                        name => rules.names.push(name.to_string()),
+                    }
+                }
+                self.rules = Some(rules);
+            }
+            "THEFUCK_EXCLUDE_RULES" => self.exclude_rules = list().map(str::to_string).collect(),
+            "THEFUCK_PRIORITY" => {
+                self.priority = list()
+                    .map(|item| {
+                        let (rule, priority) = item
+                            .split_once('=')
+                            .ok_or(format!("expected rule=priority, got '{}'", item))?;
+                        let priority = priority.trim().parse().map_err(|_| format!("invalid priority '{}'", priority))?;
+                        Ok((rule.trim().to_string(), priority))
+                    })
+                    .collect::<std::result::Result<_, String>>()?;
+            }
+            "THEFUCK_REQUIRE_CONFIRMATION" => self.require_confirmation = Some(value.eq_ignore_ascii_case("true")),
+            "THEFUCK_DEBUG" => self.debug = Some(value.eq_ignore_ascii_case("true")),
+            "THEFUCK_HISTORY_LIMIT" => {
+                self.history_limit = Some(value.trim().parse().map_err(|_| format!("invalid number '{}'", value))?)
+            }
+            _ => self.ignore(&name["THEFUCK_".len()..].to_ascii_lowercase()),
+        }
+        Ok(())
+    }
+
+    /// Sets one Python assignment. Anything else, such as an import, is
+    /// skipped.
+    fn set_python(&mut self, statement: &str) -> std::result::Result<(), String> {
+        let Some((name, value)) = statement.split_once('=') else {
+            return Ok(());
+        };
+        let name = name.trim();
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || value.starts_with('=') {
+            return Ok(());
+        }
+        let tokens = tokenize(value)?;
+        match name {
+            "rules" => self.rules = Some(rule_list(&tokens)?),
+            "exclude_rules" => {
+                let list = rule_list(&tokens)?;
+                if list.defaults {
+                    return Err("exclude_rules can't include DEFAULT_RULES".to_string());
+                }
+                self.exclude_rules = list.names;
+            }
+            "priority" => self.priority = priorities(&tokens)?,
+            "require_confirmation" => self.require_confirmation = Some(boolean(&tokens)?),
+            "debug" => self.debug = Some(boolean(&tokens)?),
+            "history_limit" => match tokens.as_slice() {
+                [Token::Int(limit)] if *limit >= 0 => self.history_limit = Some(*limit as usize),
+                _ => return Err("history_limit must be a number".to_string()),
+            },
+            name => self.ignore(name),
+        }
+        Ok(())
+    }
+
+    fn ignore(&mut self, name: &str) {
+        if !self.ignored.iter().any(|ignored| ignored == name) {
+            self.ignored.push(name.to_string());
+        }
+    }
+}
+
+/// Splits `source` into statements with their first line number, joining
+/// the lines of brackets left open and dropping comments.
+fn statements(source: &str) -> Vec<(usize, String)> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    let mut depth = 0i32;
+
+    for (index, line) in source.lines().enumerate() {
+        let mut quote = None;
+        let mut code = String::new();
+        for c in line.chars() {
+            match (quote, c) {
+                (None, '#') => break,
+                (None, '\'' | '"') => quote = Some(c),
+                (Some(open), _) if c == open => quote = None,
+                (None, '[' | '{' | '(') => depth += 1,
+                (None, ']' | '}' | ')') => depth -= 1,
+                _ => {}
+            }
+            code.push(c);
+        }
+        if current.is_empty() {
+            start = index + 1;
+        }
+        current.push_str(code.trim());
+        current.push(' ');
+        if depth <= 0 {
+            let statement = current.trim();
+            if !statement.is_empty() {
+                statements.push((start, statement.to_string()));
+            }
+            current.clear();
+            depth = 0;
+        }
+    }
+    if !current.trim().is_empty() {
+        statements.push((start, current.trim().to_string()));
+    }
+    statements
+}
+
+/// Strips the quotes a shell would around a value.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['\'', '"'] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)) {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Name(String),
+    Int(i64),
+    Punct(char),
+}
+
+/// Splits a Python value into literals, names and punctuation.
+fn tokenize(value: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '\'' | '"' => {
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') => literal.extend(chars.next()),
+                        Some(other) => literal.push(other),
+                        None => return Err(format!("unterminated string {}{}", c, literal)),
+                    }
+                }
+                tokens.push(Token::Str(literal));
+            }
+            c if c.is_ascii_digit() || (c == '-' && chars.peek().is_some_and(char::is_ascii_digit)) => {
+                let mut number = c.to_string();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                tokens.push(Token::Int(number.parse().map_err(|_| format!("invalid number {}", number))?));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(next) = chars.next_if(|next| next.is_ascii_alphanumeric() || *next == '_') {
+                    name.push(next);
+                }
+                tokens.push(Token::Name(name));
+            }
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads a list of rule names, possibly added to `DEFAULT_RULES`.
+fn rule_list(tokens: &[Token]) -> std::result::Result<RuleList, String> {
+    let mut list = RuleList::default();
+    for token in tokens {
+        match token {
+            Token::Name(name) if name == DEFAULT_RULES => list.defaults = true,
+            Token::Str(name) => list.names.push(name.clone()),
+            Token::Punct('[' | ']' | '(' | ')' | ',' | '+') => {}
+            other => return Err(format!("unsupported value in rule list: {:?}", other)),
+        }
+    }
+    Ok(list)
+}
+
+/// Reads a dict of rule names to priorities.
+fn priorities(tokens: &[Token]) -> std::result::Result<Vec<(String, i32)>, String> {
+    let mut priorities = Vec::new();
+    let mut rest = tokens;
+    loop {
+        match rest {
+            [Token::Punct('{' | '}' | ','), tail @ ..] => rest = tail,
+            [Token::Str(rule), Token::Punct(':'), Token::Int(priority), tail @ ..] => {
+                let priority = i32::try_from(*priority).map_err(|_| format!("priority {} is out of range", priority))?;
+                priorities.push((rule.clone(), priority));
+                rest = tail;
+            }
+            [] => return Ok(priorities),
+            [other, ..] => return Err(format!("unsupported value in priority: {:?}", other)),
+        }
+    }
+}
+
+fn boolean(tokens: &[Token]) -> std::result::Result<bool, String> {
+    match tokens {
+        [Token::Name(name)] if name == "True" => Ok(true),
+        [Token::Name(name)] if name == "False" => Ok(false),
+        _ => Err("expected True or False".to_string()),
+    }
+}
+
+/// A thefuck rule without an ftf equivalent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unmapped {
+    /// The thefuck rule
+    pub name: String,
+    /// ftf rules with similar names, most similar first
+    pub similar: Vec<String>,
+}
+
+impl fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "thefuck rule '{}' has no ftf equivalent", self.name)?;
+        if !self.similar.is_empty() {
+            write!(f, "; similar ftf rules: {}", self.similar.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// An ftf config made from thefuck's settings.
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// The equivalent config
+    pub config: Config,
+    /// thefuck rules the config couldn't include, in the order they were found
+    pub unmapped: Vec<Unmapped>,
+    /// thefuck settings the config couldn't include
+    pub ignored: Vec<String>,
+    /// ftf rules disabled because thefuck's settings list their rules
+    /// without the defaults, and nothing listed maps to them
+    pub unlisted: usize,
+}
+
+impl Import {
+    /// Gets what couldn't be imported, one message per line.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<String> = self.unmapped.iter().map(Unmapped::to_string).collect();
+        warnings.extend(
+            self.ignored
+                .iter()
+                .map(|setting| format!("thefuck setting '{}' has no ftf equivalent", setting)),
+        );
+        if self.unlisted > 0 {
+            warnings.push(format!(
+                "{} ftf rules are disabled because thefuck's rules don't include DEFAULT_RULES",
+                self.unlisted
+            ));
+        }
+        warnings
+    }
+
+    /// Renders the config as TOML, with only the settings that differ from
+    /// ftf's defaults and the rules in name order.
+    pub fn to_toml(&self) -> String {
+        let mut table = toml::Table::new();
+
+        let global = toml::Table::try_from(&self.config.global).unwrap_or_default();
+        let defaults = toml::Table::try_from(crate::config::GlobalConfig::default()).unwrap_or_default();
+        let changed: toml::Table = global
+            .into_iter()
+            .filter(|(key, value)| defaults.get(key) != Some(value))
+            .collect();
+        if !changed.is_empty() {
+            table.insert("global".to_string(), toml::Value::Table(changed));
+        }
+
+        let rules: BTreeMap<&String, &RuleConfig> = self.config.rules.iter().collect();
+        if let Some(rules) = toml::Table::try_from(rules).ok().filter(|rules| !rules.is_empty()) {
+            table.insert("rules".to_string(), toml::Value::Table(rules));
+        }
+
+        format!(
+            "# Imported from thefuck's settings by `ftf import-thefuck`\n\n{}",
+            toml::to_string_pretty(&table).unwrap_or_default()
+        )
+    }
+}
+
+/// Makes an ftf config equivalent to `settings`. `known` lists every ftf
+/// rule, to disable the ones an explicit thefuck rule list leaves out and to
+/// suggest equivalents for unmapped rules.
+pub fn import(settings: &Settings, known: &[&str]) -> Import {
+    let mut rules: HashMap<String, RuleConfig> = HashMap::new();
+    let mut unmapped: Vec<Unmapped> = Vec::new();
+    let mut map = |name: &str| {
+        let mapped = ftf_rules(name);
+        if mapped.is_empty() && !unmapped.iter().any(|unmapped| unmapped.name == name) {
+            unmapped.push(Unmapped {
+                name: name.to_string(),
+                similar: similar_rules(name, known),
+            });
+        }
+        mapped
+    };
+
+    let mut unlisted = 0;
+    if let Some(list) = &settings.rules {
+        let listed: Vec<&str> = list.names.iter().flat_map(|name| map(name)).copied().collect();
+        for name in &listed {
+            rule(&mut rules, name).enabled = true;
+        }
+        if !list.defaults {
+            for name in known.iter().filter(|name| !listed.contains(name)) {
+                rule(&mut rules, name).enabled = false;
+                unlisted += 1;
+            }
+        }
+    }
+    for name in &settings.exclude_rules {
+        for name in map(name) {
+            rule(&mut rules, name).enabled = false;
+        }
+    }
+    for (name, priority) in &settings.priority {
+        for name in map(name) {
+            rule(&mut rules, name).priority = Some(*priority);
+        }
+    }
+
+    let mut config = Config {
+        rules,
+        ..Config::default()
+    };
+    if let Some(confirm) = settings.require_confirmation {
+        config.global.interactive = confirm;
+    }
+    if let Some(debug) = settings.debug {
+        config.global.debug = debug;
+    }
+    if let Some(limit) = settings.history_limit {
+        config.global.history_scan_limit = limit;
+    }
+
+    Import {
+        config,
+        unmapped,
+        ignored: settings.ignored.clone(),
+        unlisted,
+    }
+}
+
+/// Gets the config of rule `name`, adding it if it has none.
+fn rule<'a>(rules: &'a mut HashMap<String, RuleConfig>, name: &str) -> &'a mut RuleConfig {
+    rules.entry(name.to_string()).or_insert_with(|| RuleConfig {
+        enabled: true,
+        priority: None,
+        options: HashMap::new(),
+    })
+}
+
+/// Gets up to three ftf rules named like `name`, most similar first.
+fn similar_rules(name: &str, known: &[&str]) -> Vec<String> {
+    let mut scored: Vec<(&str, f64)> = known
+        .iter()
+        .map(|rule| (*rule, fuzzy::similarity(name, rule)))
+        .filter(|(_, score)| *score >= MIN_SUGGESTION_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.into_iter().take(3).map(|(rule, _)| rule.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::builtin_factories;
+    use crate::RuleContext;
+    use std::sync::Arc;
+
+    const SETTINGS: &str = include_str!("../../tests/fixtures/thefuck/settings.py");
+    const ONLY_LISTED: &str = include_str!("../../tests/fixtures/thefuck/only_listed.py");
+    const ENV: &str = include_str!("../../tests/fixtures/thefuck/env.sh");
+
+    fn known_rules() -> Vec<&'static str> {
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        let factories = builtin_factories(&config, &context);
+        factories
+            .iter()
+            .flat_map(|factory| factory.rules())
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    #[test]
+    fn test_aliases_name_ftf_rules() {
+        let known = known_rules();
+        for (thefuck, rules) in ALIASES {
+            for rule in *rules {
+                assert!(known.contains(rule), "{} maps to unknown rule {}", thefuck, rule);
+            }
+        }
+        assert!(ALIASES.windows(2).all(|pair| pair[0].0 < pair[1].0), "aliases aren't sorted");
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = Settings::parse(SETTINGS).unwrap();
+
+        assert_eq!(
+            settings.rules,
+            Some(RuleList {
+                defaults: true,
+                names: vec!["git_push_force".to_string()],
+            })
+        );
+        assert_eq!(settings.exclude_rules, vec!["git_pull", "sudo", "fix_file"]);
+        assert_eq!(
+            settings.priority,
+            vec![("git_push".to_string(), 100), ("no_command".to_string(), 9999)]
+        );
+        assert_eq!(settings.require_confirmation, Some(false));
+        assert_eq!(settings.debug, None);
+        assert_eq!(settings.history_limit, Some(2000));
+        assert_eq!(settings.ignored, vec!["wait_command", "no_colors"]);
+    }
+
+    #[test]
+    fn test_parse_env_style() {
+        let settings = Settings::parse(ENV).unwrap();
+
+        assert_eq!(settings.exclude_rules, vec!["git_pull", "tmux"]);
+        assert_eq!(settings.priority, vec![("sudo".to_string(), 50)]);
+        assert_eq!(settings.require_confirmation, Some(true));
+        assert_eq!(settings.ignored, vec!["wait_slow_command"]);
+    }
+
+    #[test]
+    fn test_environment_overrides_file() {
+        let mut settings = Settings::parse(SETTINGS).unwrap();
+        settings
+            .apply_env([
+                ("THEFUCK_EXCLUDE_RULES".to_string(), "mkdir_p".to_string()),
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ])
+            .unwrap();
+
+        assert_eq!(settings.exclude_rules, vec!["mkdir_p"]);
+        assert_eq!(settings.history_limit, Some(2000));
+    }
+
+    #[test]
+    fn test_import_maps_rules() {
+        let import = import(&Settings::parse(SETTINGS).unwrap(), &known_rules());
+        let config = &import.config;
+
+        assert!(!config.is_rule_enabled("git_pull_rebase"));
+        for rule in ftf_rules("sudo") {
+            assert!(!config.is_rule_enabled(rule));
+        }
+        assert!(config.is_rule_enabled("git_push_force"));
+        assert!(config.is_rule_enabled("mkdir_p"));
+        assert_eq!(config.get_rule_priority("git_push_set_upstream"), Some(100));
+        assert!(!config.global.interactive);
+        assert_eq!(config.global.history_scan_limit, 2000);
+        assert_eq!(import.unlisted, 0);
+
+        let unmapped: Vec<&str> = import.unmapped.iter().map(|unmapped| unmapped.name.as_str()).collect();
+        assert_eq!(unmapped, vec!["fix_file", "no_command"]);
+    }
+
+    #[test]
+    fn test_explicit_rule_list_disables_the_rest() {
+        let known = known_rules();
+        let import = import(&Settings::parse(ONLY_LISTED).unwrap(), &known);
+
+        assert!(import.config.is_rule_enabled("git_not_command"));
+        assert!(import.config.is_rule_enabled("mkdir_p"));
+        assert!(!import.config.is_rule_enabled("kubectl_did_you_mean"));
+        assert_eq!(import.unlisted, known.len() - 2);
+    }
+
+    #[test]
+    fn test_unmapped_rules_get_suggestions() {
+        let import = import(&Settings::parse("exclude_rules = ['apt_get', 'zzz']").unwrap(), &known_rules());
+
+        assert!(import.unmapped[0].similar.contains(&"apt_get_search".to_string()));
+        assert!(import.unmapped[1].similar.is_empty());
+        assert_eq!(
+            import.warnings()[1],
+            "thefuck rule 'zzz' has no ftf equivalent".to_string()
+        );
+        assert!(import.warnings()[0].starts_with("thefuck rule 'apt_get' has no ftf equivalent; similar ftf rules: "));
+    }
+
+    #[test]
+    fn test_toml_round_trips() {
+        let import = import(&Settings::parse(SETTINGS).unwrap(), &known_rules());
+        let toml = import.to_toml();
+
+        let config: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(config, import.config);
+        assert!(toml.contains("[rules.git_pull_rebase]\nenabled = false\n"), "{}", toml);
+        // Defaults aren't repeated
+        assert!(!toml.contains("max_output_bytes"), "{}", toml);
+        assert!(!toml.contains("options"), "{}", toml);
+    }
+
+    #[test]
+    fn test_malformed_setting_names_line() {
+        let error = Settings::parse("# settings\n\npriority = {'sudo': high}\n").unwrap_err();
+        assert!(error.to_string().contains("line 3"), "{}", error);
+
+        assert!(Settings::parse("history_limit = 'lots'").is_err());
+    }
+
+    #[test]
+    fn test_multiline_values_and_comments() {
+        let settings = Settings::parse(
+            "import os\nrules = [\n    'git_push',  # push upstream\n    \"mkdir_p\",\n]\nif os.name == 'nt':\n    pass\n",
+        )
+        .unwrap();
+
+        assert_eq!(settings.rules.unwrap().names, vec!["git_push", "mkdir_p"]);
+        assert!(settings.ignored.is_empty());
+    }
+}
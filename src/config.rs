@@ -1,16 +1,30 @@
-/// Configuration system for fasterthefuck.
-///
-/// Supports customization via TOML config files:
-/// - Enable/disable specific rules
-/// - Override rule priorities
-/// - Global settings
-
+//! Configuration system for fasterthefuck.
+//!
+//! Supports customization via TOML config files:
+//! - Enable/disable specific rules
+//! - Override rule priorities
+//! - Global settings
+//!
+//! A config that loads can still be wrong: a misspelled rule name is
+//! ignored like any other unknown rule. [`Config::validate`] finds such
+//! mistakes, and `ftf config validate` reports them.
+
+use crate::rules::{builtin_factories, PatternLimits, RuleFactory};
+use crate::plugins::{self, packs};
+use crate::{fuzzy, Error, Result, RuleContext};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Placeholder left in corrections whose value the user has to supply.
+/// Corrections containing it always need confirmation.
+pub const VALUE_PLACEHOLDER: &str = "<value>";
 
 /// Global configuration for fasterthefuck
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Config {
     /// Global settings
     #[serde(default)]
@@ -19,6 +33,27 @@ pub struct Config {
     /// Per-rule settings
     #[serde(default)]
     pub rules: HashMap<String, RuleConfig>,
+
+    /// Values suggested for environment variables a command reports as unset
+    #[serde(default)]
+    pub env_defaults: HashMap<String, String>,
+
+    /// Shared rule packs downloaded by `ftf rules sync`, by name
+    #[serde(default)]
+    pub rule_packs: HashMap<String, RulePackConfig>,
+
+    /// Opt-in usage metrics
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Colors of the correction selector, by element: original, number,
+    /// changed, rule, warning, reason and removed
+    #[serde(default)]
+    pub theme: HashMap<String, String>,
+
+    /// Overrides of user-facing messages, by key (see locales/en.toml)
+    #[serde(default)]
+    pub messages: HashMap<String, String>,
 }
 
 /// Global configuration options
@@ -28,9 +63,97 @@ pub struct GlobalConfig {
     #[serde(default = "default_true")]
     pub interactive: bool,
 
-    /// Show debug information
+    /// Show debug information: log at DEBUG level unless `log_level` says
+    /// otherwise
     #[serde(default)]
     pub debug: bool,
+
+    /// Color the correction selector when stderr is a terminal and
+    /// `NO_COLOR` is unset
+    #[serde(default = "default_true")]
+    pub color: bool,
+
+    /// Show under each candidate in the selector why its rule matched
+    #[serde(default = "default_true")]
+    pub show_reasons: bool,
+
+    /// Unchanged tokens shown on either side of the changes in a diff, with
+    /// --diff or in the selector's diff view
+    #[serde(default = "default_diff_context")]
+    pub diff_context: usize,
+
+    /// Level of the log written with --log-file: "error", "warn", "info",
+    /// "debug", "trace" or "off" (defaults to "info", or "debug" with `debug`)
+    #[serde(default)]
+    pub log_level: Option<String>,
+
+    /// Maximum number of recent history entries scanned by history-based rules
+    #[serde(default = "default_history_scan_limit")]
+    pub history_scan_limit: usize,
+
+    /// Record every invocation to the correction log used by `ftf stats`,
+    /// including accepted corrections in plaintext
+    #[serde(default)]
+    pub log_corrections: bool,
+
+    /// Minutes during which a rejected correction is not suggested again
+    /// for the same command (0 disables suppression)
+    #[serde(default = "default_rejection_window_minutes")]
+    pub rejection_window_minutes: u64,
+
+    /// Offer similar recent history entries when no rule matches
+    #[serde(default)]
+    pub history_fallback: bool,
+
+    /// Corrections containing any of these substrings are never selected
+    /// automatically and always need an explicit choice
+    #[serde(default = "default_dangerous_patterns")]
+    pub dangerous_patterns: Vec<String>,
+
+    /// Added to the priority of corrections from rules meant for another
+    /// kind of project (e.g. npm rules in a Rust crate)
+    #[serde(default = "default_project_penalty")]
+    pub project_penalty: i32,
+
+    /// Milliseconds rules may spend checking that their corrections are
+    /// likely to succeed (0 disables the checks)
+    #[serde(default = "default_verify_budget_ms")]
+    pub verify_budget_ms: u64,
+
+    /// Privilege escalation tool used in corrections instead of sudo, e.g.
+    /// "doas" (detected from sudo, doas and run0 if unset)
+    #[serde(default)]
+    pub privilege_tool: Option<String>,
+
+    /// Show a desktop notification when a correction run with --execute
+    /// takes at least this many seconds (0 disables notifications)
+    #[serde(default = "default_notify_after_secs")]
+    pub notify_after_secs: u64,
+
+    /// Bytes of a command's output kept for matching; longer outputs keep
+    /// their first and last halves
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+
+    /// Largest compiled size, in bytes, of a pattern from a custom rule or
+    /// plugin; larger patterns are rejected
+    #[serde(default = "default_max_pattern_bytes")]
+    pub max_pattern_bytes: usize,
+
+    /// Milliseconds a plugin rule may run before it's stopped and offers
+    /// nothing
+    #[serde(default = "default_plugin_timeout_ms")]
+    pub plugin_timeout_ms: u64,
+
+    /// Fuel a WASM plugin may spend on each call, with the `wasm-plugins`
+    /// feature
+    #[serde(default = "default_wasm_fuel")]
+    pub wasm_fuel: u64,
+
+    /// Don't warn about mistakes in the config, such as unknown rule names,
+    /// on every invocation (`ftf config validate` still reports them)
+    #[serde(default)]
+    pub quiet_config_warnings: bool,
 }
 
 /// Configuration for a specific rule
@@ -41,27 +164,208 @@ pub struct RuleConfig {
     pub enabled: bool,
 
     /// Override the rule's priority (lower = higher priority)
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<i32>,
+
+    /// Rule-specific settings, e.g. the command a rule should suggest
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub options: HashMap<String, String>,
+}
+
+/// A shared rule pack: a TOML file of rules fetched from a URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RulePackConfig {
+    /// Where `ftf rules sync` downloads the pack from: an https:// or
+    /// file:// URL
+    pub url: String,
+
+    /// Load the pack's rules
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// SHA-256 of the pack's file, as `sha256sum` prints it. A download or
+    /// cached copy with another hash is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Opt-in usage metrics: which rules fire and whether their corrections are
+/// accepted, never the commands themselves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Send metrics; nothing is recorded unless this is set and there's an
+    /// endpoint
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// HTTP endpoint batches of metrics are POSTed to as JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    /// Invocations recorded before a batch is sent
+    #[serde(default = "default_telemetry_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            batch_size: default_telemetry_batch_size(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// Gets the endpoint metrics are sent to, or `None` if telemetry is off.
+    pub fn active_endpoint(&self) -> Option<&str> {
+        self.endpoint.as_deref().filter(|_| self.enabled)
+    }
+}
+
+fn default_telemetry_batch_size() -> usize {
+    20
+}
+
+fn default_diff_context() -> usize {
+    1
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_history_scan_limit() -> usize {
+    1000
+}
+
+fn default_rejection_window_minutes() -> u64 {
+    5
+}
+
+fn default_project_penalty() -> i32 {
+    1000
+}
+
+fn default_verify_budget_ms() -> u64 {
+    50
+}
+
+fn default_notify_after_secs() -> u64 {
+    30
+}
+
+fn default_max_output_bytes() -> usize {
+    crate::output::DEFAULT_MAX_OUTPUT_BYTES
+}
+
+fn default_max_pattern_bytes() -> usize {
+    crate::rules::builders::regex::DEFAULT_MAX_COMPILED_PATTERN_BYTES
+}
+
+fn default_plugin_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_wasm_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_dangerous_patterns() -> Vec<String> {
+    vec![
+        "terraform apply".to_string(),
+        "terraform destroy".to_string(),
+        "curl -k".to_string(),
+    ]
+}
+
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
             interactive: true,
             debug: false,
+            color: true,
+            show_reasons: true,
+            diff_context: default_diff_context(),
+            log_level: None,
+            history_scan_limit: default_history_scan_limit(),
+            log_corrections: false,
+            rejection_window_minutes: default_rejection_window_minutes(),
+            history_fallback: false,
+            dangerous_patterns: default_dangerous_patterns(),
+            project_penalty: default_project_penalty(),
+            verify_budget_ms: default_verify_budget_ms(),
+            privilege_tool: None,
+            notify_after_secs: default_notify_after_secs(),
+            max_output_bytes: default_max_output_bytes(),
+            max_pattern_bytes: default_max_pattern_bytes(),
+            plugin_timeout_ms: default_plugin_timeout_ms(),
+            wasm_fuel: default_wasm_fuel(),
+            quiet_config_warnings: false,
         }
     }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            global: GlobalConfig::default(),
-            rules: HashMap::new(),
+/// Lowest similarity for a rule name to be suggested for an unknown one.
+const MIN_SUGGESTION_SIMILARITY: f64 = 0.75;
+
+/// How much a [`ConfigDiagnostic`] matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The config works, but likely not as meant
+    Warning,
+    /// Part of the config can't work
+    Error,
+}
+
+/// A mistake found in a config that loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigDiagnostic {
+    /// `[rules.<name>]` names no rule; `suggestion` is the most similar one
+    UnknownRule { name: String, suggestion: Option<String> },
+    /// `[rules.<name>]` names a group of rules, not a rule
+    GroupNotRule { name: String },
+    /// `[rules.<name>]` sets a priority but disables the rule
+    PriorityOnDisabledRule { name: String },
+    /// A rule of a rule pack has the name of a built-in rule, so both get
+    /// its `[rules.<name>]` settings
+    ShadowedRule { name: String, pack: String },
+    /// A rule of a rule pack can't be built, as when its pattern is invalid
+    InvalidCustomRule { pack: String, error: String },
+}
+
+impl ConfigDiagnostic {
+    /// Gets how much the diagnostic matters.
+    pub fn severity(&self) -> Severity {
+        match self {
+            ConfigDiagnostic::InvalidCustomRule { .. } => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigDiagnostic::UnknownRule { name, suggestion: Some(suggestion) } => {
+                write!(f, "[rules.{}] names no rule; did you mean {}?", name, suggestion)
+            }
+            ConfigDiagnostic::UnknownRule { name, suggestion: None } => {
+                write!(f, "[rules.{}] names no rule; 'ftf rules list' shows them all", name)
+            }
+            ConfigDiagnostic::GroupNotRule { name } => {
+                write!(f, "[rules.{}] names a group of rules; settings under [rules] apply to single rules", name)
+            }
+            ConfigDiagnostic::PriorityOnDisabledRule { name } => {
+                write!(f, "[rules.{}] sets a priority, which does nothing as it disables the rule", name)
+            }
+            ConfigDiagnostic::ShadowedRule { name, pack } => write!(
+                f,
+                "rule pack '{}' has a rule named {} like a built-in one; [rules.{}] applies to both",
+                pack, name, name
+            ),
+            ConfigDiagnostic::InvalidCustomRule { pack, error } => write!(f, "rule pack '{}': {}", pack, error),
         }
     }
 }
@@ -73,41 +377,40 @@ impl Config {
     }
 
     /// Loads config from file. Returns empty config if file doesn't exist.
-    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Errors name the file, and for malformed TOML the line and column.
+    pub fn load_from_file(path: &Path) -> Result<Self> {
         if !path.exists() {
             return Ok(Self::default());
         }
 
-        let contents = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&contents)?;
-        Ok(config)
+        let contents = std::fs::read_to_string(path).map_err(|e| Error::config_io(path, e))?;
+        toml::from_str(&contents).map_err(|e| Error::config_parse(path, &contents, &e))
     }
 
     /// Loads config from standard location: ~/.config/fasterthefuck/config.toml
-    pub fn load_default() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn load_default() -> Result<Self> {
         let config_path = Self::default_config_path()?;
         Self::load_from_file(&config_path)
     }
 
     /// Gets the default config file path
-    pub fn default_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    pub fn default_config_path() -> Result<PathBuf> {
         let config_dir = dirs::config_dir()
-            .ok_or("Could not determine config directory")?
+            .ok_or_else(|| Error::config("Could not determine config directory"))?
             .join("fasterthefuck");
 
         Ok(config_dir.join("config.toml"))
     }
 
     /// Saves config to file
-    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
+            std::fs::create_dir_all(parent).map_err(|e| Error::config_io(parent, e))?;
         }
 
-        let contents = toml::to_string_pretty(self)?;
-        std::fs::write(path, contents)?;
-        Ok(())
+        let contents = toml::to_string_pretty(self).map_err(|e| Error::config(e.to_string()))?;
+        std::fs::write(path, contents).map_err(|e| Error::config_io(path, e))
     }
 
     /// Checks if a rule is enabled (default: true if not specified)
@@ -123,6 +426,120 @@ impl Config {
         self.rules.get(rule_name).and_then(|config| config.priority)
     }
 
+    /// Checks if a correction needs explicit confirmation before it is used
+    pub fn requires_confirmation(&self, script: &str) -> bool {
+        script.contains(VALUE_PLACEHOLDER)
+            || self
+                .global
+                .dangerous_patterns
+                .iter()
+                .any(|pattern| script.contains(pattern.as_str()))
+    }
+
+    /// Gets the configured value for an environment variable (returns None if not set)
+    pub fn env_default(&self, name: &str) -> Option<&str> {
+        self.env_defaults.get(name).map(String::as_str)
+    }
+
+    /// Gets a rule-specific option (returns None if not set)
+    pub fn rule_option(&self, rule_name: &str, key: &str) -> Option<&str> {
+        self.rules
+            .get(rule_name)
+            .and_then(|config| config.options.get(key))
+            .map(String::as_str)
+    }
+
+    /// Finds mistakes in the rule settings: `[rules]` tables naming no rule
+    /// in `known_rules` or a group instead of a rule, and priorities set on
+    /// disabled rules. Diagnostics are in the order of the rule names.
+    pub fn validate(&self, known_rules: &[&str]) -> Vec<ConfigDiagnostic> {
+        let context = Arc::new(RuleContext::default());
+        let groups: Vec<&str> = builtin_factories(self, &context).iter().map(RuleFactory::category).collect();
+
+        let mut names: Vec<&String> = self.rules.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let rule = &self.rules[name];
+                let diagnostic = if groups.contains(&name.as_str()) && !known_rules.contains(&name.as_str()) {
+                    ConfigDiagnostic::GroupNotRule { name: name.clone() }
+                } else if !known_rules.contains(&name.as_str()) {
+                    ConfigDiagnostic::UnknownRule {
+                        name: name.clone(),
+                        suggestion: suggest(name, known_rules).map(str::to_string),
+                    }
+                } else if !rule.enabled && rule.priority.is_some() {
+                    ConfigDiagnostic::PriorityOnDisabledRule { name: name.clone() }
+                } else {
+                    return None;
+                };
+                Some(diagnostic)
+            })
+            .collect()
+    }
+
+    /// Finds mistakes in the rule packs synced to `dir`: rules that can't
+    /// be built, and rules with the name of one of `known_rules`. Packs are
+    /// in the order of their names.
+    pub fn validate_rule_packs(&self, known_rules: &[&str], dir: &Path) -> Vec<ConfigDiagnostic> {
+        let mut names: Vec<&String> = self.rule_packs.keys().filter(|name| self.rule_packs[*name].enabled).collect();
+        names.sort();
+        let limits = self.pattern_limits();
+        let mut diagnostics = Vec::new();
+        for pack in names {
+            let rules = match packs::inspect(dir, pack, &limits) {
+                Ok(rules) => rules,
+                Err(e) => {
+                    diagnostics.push(ConfigDiagnostic::InvalidCustomRule {
+                        pack: pack.clone(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            for (name, error) in rules {
+                if let Some(error) = error {
+                    diagnostics.push(ConfigDiagnostic::InvalidCustomRule {
+                        pack: pack.clone(),
+                        error: error.to_string(),
+                    });
+                } else if known_rules.contains(&name.as_str()) {
+                    diagnostics.push(ConfigDiagnostic::ShadowedRule { name, pack: pack.clone() });
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Gets the limits patterns from custom rules and plugins must stay
+    /// within
+    pub fn pattern_limits(&self) -> PatternLimits {
+        PatternLimits {
+            max_compiled_bytes: self.global.max_pattern_bytes,
+            ..PatternLimits::default()
+        }
+    }
+
+    /// Gets the rule factories ftf corrects with: the built-in rule groups
+    /// and the plugins, including WASM ones with the `wasm-plugins` feature.
+    pub fn factories<'a>(&'a self, context: &'a Arc<RuleContext>) -> Vec<RuleFactory<'a>> {
+        let mut factories = builtin_factories(self, context);
+        let timeout = Duration::from_millis(self.global.plugin_timeout_ms);
+        factories.extend(plugins::default_dir().map(|dir| plugins::factory(dir, timeout)));
+        #[cfg(feature = "wasm-plugins")]
+        factories.extend(plugins::wasm::default_dir().map(|dir| plugins::wasm::factory(dir, self.global.wasm_fuel)));
+        if let Some(dir) = packs::default_dir() {
+            let mut enabled: Vec<(&String, &RulePackConfig)> =
+                self.rule_packs.iter().filter(|(_, pack)| pack.enabled).collect();
+            enabled.sort_by_key(|(name, _)| *name);
+            for (name, pack) in enabled {
+                factories.push(packs::factory(dir.clone(), name.clone(), pack.clone(), self.pattern_limits()));
+            }
+        }
+        factories
+    }
+
     /// Gets example config with documentation
     pub fn example() -> String {
         r#"# FastertTheFuck Configuration
@@ -134,6 +551,61 @@ interactive = true
 # Show debug information
 debug = false
 
+# Color the correction selector in a terminal (NO_COLOR and --color win)
+color = true
+
+# Show under each candidate in the selector why its rule matched
+show_reasons = true
+
+# Unchanged words shown around the changes with --diff, or when the
+# selector's diff view is toggled with d
+diff_context = 1
+
+# Level of the log written with --log-file: error, warn, info, debug, trace
+# or off (defaults to info, or debug when debug = true)
+# log_level = "debug"
+
+# Number of recent shell history entries scanned by history-based rules
+history_scan_limit = 1000
+
+# Record corrections to a local log so `ftf stats` can report on them.
+# Accepted corrections are stored in plaintext, like shell history.
+log_corrections = false
+
+# Minutes during which a correction you rejected is not suggested again
+# for the same command (0 disables this)
+rejection_window_minutes = 5
+
+# When no rule matches, offer your most similar recent commands instead
+# (only in interactive mode)
+history_fallback = false
+
+# Corrections containing any of these are never picked automatically
+dangerous_patterns = ["terraform apply", "terraform destroy", "curl -k"]
+
+# Corrections for another kind of project (npm rules in a Rust crate, ...)
+# are ranked lower by this much
+project_penalty = 1000
+
+# Bytes of a failed command's output kept for matching (default 1 MiB).
+# Longer outputs keep their beginning and end, where errors usually are
+max_output_bytes = 1048576
+
+# Largest compiled size of a custom rule's pattern, in bytes. Larger patterns
+# could make matching slow, and are rejected
+max_pattern_bytes = 262144
+
+# Milliseconds a plugin rule in ~/.config/fasterthefuck/rules/ may run
+plugin_timeout_ms = 1000
+
+# Fuel a WASM rule in ~/.config/fasterthefuck/wasm/ may spend on each call,
+# with the wasm-plugins feature
+wasm_fuel = 100000000
+
+# Don't warn on every run about mistakes such as a misspelled rule name
+# below; `ftf config validate` still lists them
+quiet_config_warnings = false
+
 # Override rules by name
 [rules.git_branch_delete]
 # Disable this rule
@@ -146,11 +618,74 @@ priority = 300
 [rules.mkdir_p]
 enabled = true
 priority = 150
+
+# Some rules take extra settings
+[rules.kubectl_unauthorized.options]
+reauth_command = "aws eks update-kubeconfig --name my-cluster"
+
+[rules.aws_region_missing.options]
+region = "eu-west-1"
+
+[rules.gcloud_project_unset.options]
+project = "my-project"
+
+# Rule packs shared by a team, downloaded by `ftf rules sync` and loaded from
+# the copy kept in ~/.config/fasterthefuck/packs/, never fetched while
+# correcting. Downloads and the copy must have the pinned SHA-256
+[rule_packs.team]
+url = "https://git.example.com/team/ftf-rules/raw/main/rules.toml"
+sha256 = "5f0c8b1d6e2a4b3c9d7e8f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c"
+enabled = true
+
+# Opt-in usage metrics for a platform team: the rule that fired, whether its
+# correction was accepted and a latency bucket, never the command. Batches
+# are spooled locally and sent in the background; `ftf telemetry` shows,
+# flushes or purges them
+[telemetry]
+enabled = false
+# endpoint = "https://metrics.example.com/v1/ftf"
+batch_size = 20
+
+# Colors of the correction selector: space-separated names from bold, dim,
+# italic, underline, black, red, green, yellow, blue, magenta, cyan, white
+# and their bright_ variants, or none
+[theme]
+original = "dim"
+number = "bold"
+changed = "bold green"
+rule = "bright_black"
+warning = "yellow"
+reason = "dim"
+removed = "red"
+group = "bold"
+
+# Overrides of ftf's messages, by key; the rest follow LANG, from a locale
+# file such as ~/.config/fasterthefuck/locales/ja.toml or the shipped ones
+[messages]
+select_prompt = "Select correction (1-{count}, d toggles diffs, cN copies): "
+
+# Values suggested when a command reports an environment variable as unset
+# (anything else gets a <value> placeholder you fill in)
+[env_defaults]
+EDITOR = "vim"
+AWS_PROFILE = "default"
 "#
         .to_string()
     }
 }
 
+/// Gets the name in `known` most like `name`, if one is similar enough to
+/// be a likely fix for a typo.
+pub fn suggest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (fuzzy::similarity(name, candidate), *candidate))
+        .filter(|(similarity, _)| *similarity >= MIN_SUGGESTION_SIMILARITY)
+        // The first of equally similar names wins
+        .reduce(|best, next| if next.0 > best.0 { next } else { best })
+        .map(|(_, candidate)| candidate)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,7 +695,33 @@ mod tests {
         let config = Config::default();
         assert!(config.global.interactive);
         assert!(!config.global.debug);
+        assert_eq!(config.global.history_scan_limit, 1000);
+        assert!(!config.global.log_corrections);
+        assert_eq!(config.global.rejection_window_minutes, 5);
+        assert!(!config.global.history_fallback);
+        assert_eq!(config.global.project_penalty, 1000);
+        assert_eq!(config.global.verify_budget_ms, 50);
+        assert_eq!(config.global.notify_after_secs, 30);
+        assert_eq!(config.global.plugin_timeout_ms, 1000);
+        assert_eq!(config.global.wasm_fuel, 100_000_000);
         assert!(config.rules.is_empty());
+        assert!(!config.telemetry.enabled);
+        assert!(config.global.color);
+        assert!(config.global.show_reasons);
+        assert_eq!(config.global.diff_context, 1);
+        assert!(config.theme.is_empty());
+    }
+
+    #[test]
+    fn test_telemetry_needs_enabled_and_endpoint() {
+        let config: Config = toml::from_str("[telemetry]\nendpoint = \"https://metrics.example.com\"\n").unwrap();
+        assert_eq!(config.telemetry.active_endpoint(), None);
+        let config: Config = toml::from_str("[telemetry]\nenabled = true\n").unwrap();
+        assert_eq!(config.telemetry.active_endpoint(), None);
+        let config: Config =
+            toml::from_str("[telemetry]\nenabled = true\nendpoint = \"https://metrics.example.com\"\n").unwrap();
+        assert_eq!(config.telemetry.active_endpoint(), Some("https://metrics.example.com"));
+        assert_eq!(config.telemetry.batch_size, 20);
     }
 
     #[test]
@@ -178,6 +739,7 @@ mod tests {
             RuleConfig {
                 enabled: false,
                 priority: None,
+                options: HashMap::new(),
             },
         );
 
@@ -193,6 +755,7 @@ mod tests {
             RuleConfig {
                 enabled: true,
                 priority: Some(150),
+                options: HashMap::new(),
             },
         );
 
@@ -216,15 +779,230 @@ priority = 200
 
         let config: Config = toml::from_str(toml_str).expect("Failed to parse TOML");
         assert!(config.global.interactive);
+        assert_eq!(config.global.history_scan_limit, 1000);
         assert!(!config.is_rule_enabled("git_branch_delete"));
         assert_eq!(config.get_rule_priority("mkdir_p"), Some(200));
     }
 
+    #[test]
+    fn test_history_scan_limit_override() {
+        let config: Config = toml::from_str("[global]\nhistory_scan_limit = 50\n")
+            .expect("Failed to parse TOML");
+        assert_eq!(config.global.history_scan_limit, 50);
+    }
+
+    #[test]
+    fn test_privilege_tool_override() {
+        let config: Config = toml::from_str("[global]\nprivilege_tool = \"doas\"\n")
+            .expect("Failed to parse TOML");
+        assert_eq!(config.global.privilege_tool.as_deref(), Some("doas"));
+        assert_eq!(Config::default().global.privilege_tool, None);
+    }
+
+    #[test]
+    fn test_pattern_limits_override() {
+        let config: Config = toml::from_str("[global]\nmax_pattern_bytes = 1024\n")
+            .expect("Failed to parse TOML");
+        assert_eq!(config.pattern_limits().max_compiled_bytes, 1024);
+        assert_eq!(Config::default().pattern_limits(), PatternLimits::default());
+    }
+
+    #[test]
+    fn test_requires_confirmation() {
+        let config = Config::default();
+        assert!(config.requires_confirmation("terraform plan -out=tfplan && terraform apply tfplan"));
+        assert!(!config.requires_confirmation("terraform init && terraform plan"));
+
+        let config: Config = toml::from_str("[global]\ndangerous_patterns = []\n")
+            .expect("Failed to parse TOML");
+        assert!(!config.requires_confirmation("terraform apply"));
+        assert!(config.requires_confirmation("DATABASE_URL=<value> ./manage.py migrate"));
+    }
+
+    #[test]
+    fn test_env_defaults() {
+        let config: Config = toml::from_str("[env_defaults]\nEDITOR = \"nvim\"\nAWS_PROFILE = \"dev\"\n")
+            .expect("Failed to parse TOML");
+
+        assert_eq!(config.env_default("EDITOR"), Some("nvim"));
+        assert_eq!(config.env_default("AWS_PROFILE"), Some("dev"));
+        assert_eq!(config.env_default("DATABASE_URL"), None);
+        assert!(Config::default().env_defaults.is_empty());
+    }
+
+    #[test]
+    fn test_rule_options() {
+        let config: Config = toml::from_str(
+            "[rules.kubectl_unauthorized.options]\nreauth_command = \"gcloud auth login\"\n",
+        )
+        .expect("Failed to parse TOML");
+
+        assert!(config.is_rule_enabled("kubectl_unauthorized"));
+        assert_eq!(
+            config.rule_option("kubectl_unauthorized", "reauth_command"),
+            Some("gcloud auth login")
+        );
+        assert_eq!(config.rule_option("kubectl_unauthorized", "other"), None);
+        assert_eq!(config.rule_option("mkdir_p", "reauth_command"), None);
+    }
+
     #[test]
     fn test_config_example_valid() {
         let example = Config::example();
-        let config: Result<Config, _> = toml::from_str(&example);
+        let config: std::result::Result<Config, _> = toml::from_str(&example);
         assert!(config.is_ok(), "Example config should be valid TOML");
+        let config = config.unwrap();
+        assert!(crate::render::Theme::from_config(&config.theme).is_ok());
+        assert!(crate::messages::Messages::unknown_keys(&config.messages).is_empty());
+    }
+
+    #[test]
+    fn test_malformed_config_names_file_and_line() {
+        let path = std::env::temp_dir().join(format!("ftf_malformed_config_{}.toml", std::process::id()));
+        std::fs::write(&path, "[global]\nhistory_fallback = true\n\n[rules.git_push\nenabled = false\n").unwrap();
+        let error = Config::load_from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(error, Error::ConfigParse { line: 4, .. }), "{:?}", error);
+        let message = error.to_string();
+        assert!(message.contains(&path.display().to_string()), "{}", message);
+        assert!(message.contains("at line 4"), "{}", message);
+
+        let error = Config::load_from_file(&std::env::temp_dir()).unwrap_err();
+        assert!(matches!(error, Error::ConfigIo { .. }), "{:?}", error);
+    }
+
+    fn builtin_rules() -> Vec<&'static str> {
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        let factories = builtin_factories(&config, &context);
+        factories.iter().flat_map(|factory| factory.rules()).map(|(name, _)| *name).collect()
+    }
+
+    #[test]
+    fn test_suggestions_fix_typos_only() {
+        let known = builtin_rules();
+        assert_eq!(suggest("git_push_setupstream", &known), Some("git_push_set_upstream"));
+        assert_eq!(suggest("mkdirp", &known), Some("mkdir_p"));
+        assert_eq!(suggest("git_branch_delete", &known), Some("git_branch_delete"));
+        assert_eq!(suggest("deploy_typo", &known), None);
+        assert_eq!(suggest("ab", &["ac", "ab_"]), None);
+        // The first of equally similar names wins
+        assert_eq!(suggest("rule_b", &["rule_a", "rule_c"]), Some("rule_a"));
+    }
+
+    #[test]
+    fn test_validate_rule_names() {
+        let config: Config = toml::from_str(
+            r#"
+            [rules.git_push_setupstream]
+            priority = 100
+
+            [rules.git]
+            enabled = false
+
+            [rules.mkdir_p]
+            enabled = false
+            priority = 150
+
+            [rules.git_not_command]
+            enabled = false
+
+            [rules.deploy_typo]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+
+        let diagnostics = config.validate(&builtin_rules());
+        assert_eq!(
+            diagnostics,
+            [
+                ConfigDiagnostic::UnknownRule {
+                    name: "deploy_typo".to_string(),
+                    suggestion: None,
+                },
+                ConfigDiagnostic::GroupNotRule { name: "git".to_string() },
+                ConfigDiagnostic::UnknownRule {
+                    name: "git_push_setupstream".to_string(),
+                    suggestion: Some("git_push_set_upstream".to_string()),
+                },
+                ConfigDiagnostic::PriorityOnDisabledRule { name: "mkdir_p".to_string() },
+            ]
+        );
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity() == Severity::Warning));
+        assert!(Config::default().validate(&builtin_rules()).is_empty());
+    }
+
+    #[test]
+    fn test_example_config_has_no_diagnostics() {
+        let config: Config = toml::from_str(&Config::example()).unwrap();
+        assert_eq!(config.validate(&builtin_rules()), []);
+    }
+
+    #[test]
+    fn test_validate_rule_packs() {
+        let dir = std::env::temp_dir().join(format!("ftf_validate_packs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pack = |url: &str| RulePackConfig {
+            url: url.to_string(),
+            enabled: true,
+            sha256: None,
+        };
+        std::fs::write(
+            dir.join("team.toml"),
+            "[[rules]]\nname = \"git_push_force\"\ncommand = '^git push'\nreplace = \"git push --force-with-lease\"\n\n\
+             [[rules]]\nname = \"broken\"\ncommand = '^deplyo ('\nreplace = \"deploy\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("garbled.toml"), "[[rules]\n").unwrap();
+        let mut config = Config::default();
+        config.rule_packs.insert("team".to_string(), pack("file:///team.toml"));
+        config.rule_packs.insert("garbled".to_string(), pack("file:///garbled.toml"));
+        config.rule_packs.insert("unsynced".to_string(), pack("file:///unsynced.toml"));
+
+        let diagnostics = config.validate_rule_packs(&builtin_rules(), &dir);
+        let shown: Vec<_> = diagnostics.iter().map(|d| (d.severity(), d.to_string())).collect();
+        assert_eq!(shown.len(), 3, "{:?}", shown);
+        assert_eq!(shown[0].0, Severity::Error);
+        assert!(shown[0].1.starts_with("rule pack 'garbled': Rule error: invalid rule pack: "), "{}", shown[0].1);
+        assert_eq!(shown[1].0, Severity::Warning);
+        assert_eq!(
+            shown[1].1,
+            "rule pack 'team' has a rule named git_push_force like a built-in one; \
+             [rules.git_push_force] applies to both"
+        );
+        assert_eq!(shown[2].0, Severity::Error);
+        assert!(
+            shown[2].1.starts_with("rule pack 'team': rule 'broken' ([[rules]] table 2): invalid command pattern:"),
+            "{}",
+            shown[2].1
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_diagnostics_render() {
+        let unknown = |suggestion: Option<&str>| ConfigDiagnostic::UnknownRule {
+            name: "git_push_setupstream".to_string(),
+            suggestion: suggestion.map(str::to_string),
+        };
+        assert_eq!(
+            unknown(Some("git_push_set_upstream")).to_string(),
+            "[rules.git_push_setupstream] names no rule; did you mean git_push_set_upstream?"
+        );
+        assert_eq!(
+            unknown(None).to_string(),
+            "[rules.git_push_setupstream] names no rule; 'ftf rules list' shows them all"
+        );
+        assert_eq!(
+            ConfigDiagnostic::GroupNotRule { name: "git".to_string() }.to_string(),
+            "[rules.git] names a group of rules; settings under [rules] apply to single rules"
+        );
+        assert_eq!(
+            ConfigDiagnostic::PriorityOnDisabledRule { name: "mkdir_p".to_string() }.to_string(),
+            "[rules.mkdir_p] sets a priority, which does nothing as it disables the rule"
+        );
     }
 
     #[test]
@@ -0,0 +1,566 @@
+//! Shared context for rules that need more than the failed command.
+//!
+//! Rules that depend on the environment (shell history, the session cache, ...)
+//! are constructed with an `Arc<RuleContext>`. Expensive data is loaded lazily
+//! on first access, so invocations that never reach such rules pay nothing.
+//!
+//! The context also knows which kind of project the command ran in, so
+//! corrections for unrelated toolchains can be ranked lower.
+//!
+//! Anything else several rules need (branch lists, directory listings, ...)
+//! goes through [`RuleContext::cached`], so it is computed at most once per
+//! invocation even when rules are evaluated on different threads.
+
+use crate::privilege;
+use crate::replay::{ProbeLog, Probes, RecordingShell};
+use crate::shell::{HistoryIndex, SessionContext, Shell, PROBE_TIMEOUT};
+use serde::{Deserialize, Serialize};
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A cached value, shared between the rules that request it.
+type CacheSlot = Arc<OnceLock<Arc<dyn Any + Send + Sync>>>;
+
+/// A kind of project, recognised by marker files in the working directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectType {
+    /// `Cargo.toml`
+    Rust,
+    /// `package.json`
+    Node,
+    /// `pyproject.toml`, `requirements.txt`, `setup.py` or `Pipfile`
+    Python,
+    /// `go.mod`
+    Go,
+    /// `Dockerfile` or a compose file
+    Docker,
+    /// Any `*.tf` file
+    Terraform,
+    /// `.git`
+    Git,
+}
+
+impl ProjectType {
+    /// Every project type; the default relevance of a rule.
+    pub const ALL: &'static [ProjectType] = &[
+        ProjectType::Rust,
+        ProjectType::Node,
+        ProjectType::Python,
+        ProjectType::Go,
+        ProjectType::Docker,
+        ProjectType::Terraform,
+        ProjectType::Git,
+    ];
+
+    /// Marker files and the project type each one indicates.
+    const MARKERS: &'static [(&'static str, ProjectType)] = &[
+        ("Cargo.toml", ProjectType::Rust),
+        ("package.json", ProjectType::Node),
+        ("pyproject.toml", ProjectType::Python),
+        ("requirements.txt", ProjectType::Python),
+        ("setup.py", ProjectType::Python),
+        ("Pipfile", ProjectType::Python),
+        ("go.mod", ProjectType::Go),
+        ("Dockerfile", ProjectType::Docker),
+        ("compose.yaml", ProjectType::Docker),
+        ("docker-compose.yml", ProjectType::Docker),
+        ("docker-compose.yaml", ProjectType::Docker),
+        (".git", ProjectType::Git),
+    ];
+
+    /// Detects the project types of `cwd`.
+    ///
+    /// Inside a git repository, markers in every directory up to the
+    /// repository root count, so `src/` of a Rust crate is still a Rust
+    /// project. Outside one, only `cwd` itself is checked.
+    pub fn detect(cwd: &Path) -> HashSet<ProjectType> {
+        let mut found = HashSet::new();
+        for dir in cwd.ancestors() {
+            found.extend(Self::markers_in(dir));
+            if found.contains(&ProjectType::Git) {
+                return found;
+            }
+        }
+        Self::markers_in(cwd)
+    }
+
+    /// Gets the project types whose markers are directly inside `dir`.
+    fn markers_in(dir: &Path) -> HashSet<ProjectType> {
+        let mut found: HashSet<ProjectType> = Self::MARKERS
+            .iter()
+            .filter(|(marker, _)| dir.join(marker).exists())
+            .map(|(_, project)| *project)
+            .collect();
+
+        let has_terraform = std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.path().extension().is_some_and(|ext| ext == "tf"))
+        });
+        if has_terraform {
+            found.insert(ProjectType::Terraform);
+        }
+        found
+    }
+}
+
+/// Environment data shared by context-aware rules.
+pub struct RuleContext {
+    shell: Option<Box<dyn Shell>>,
+    history: OnceLock<HistoryIndex>,
+    session: OnceLock<SessionContext>,
+    projects: OnceLock<HashSet<ProjectType>>,
+    cache: Mutex<HashMap<(String, TypeId), CacheSlot>>,
+    probes: Option<ProbeLog>,
+}
+
+impl RuleContext {
+    /// Creates a context backed by a shell. History is read on first use.
+    pub fn new(shell: Box<dyn Shell>) -> Self {
+        Self {
+            shell: Some(shell),
+            history: OnceLock::new(),
+            session: OnceLock::new(),
+            projects: OnceLock::new(),
+            cache: Mutex::default(),
+            probes: None,
+        }
+    }
+
+    /// Creates a context from pre-loaded history (most recent first).
+    pub fn from_history(history: Vec<String>) -> Self {
+        Self {
+            shell: None,
+            history: OnceLock::from(HistoryIndex::from_entries(history)),
+            session: OnceLock::new(),
+            projects: OnceLock::new(),
+            cache: Mutex::default(),
+            probes: None,
+        }
+    }
+
+    /// Uses a pre-loaded session cache instead of reading it from disk.
+    pub fn with_session(self, session: SessionContext) -> Self {
+        Self {
+            session: OnceLock::from(session),
+            ..self
+        }
+    }
+
+    /// Uses pre-loaded history (most recent first) instead of reading the
+    /// shell's.
+    pub fn with_history(self, history: Vec<String>) -> Self {
+        Self {
+            history: OnceLock::from(HistoryIndex::from_entries(history)),
+            ..self
+        }
+    }
+
+    /// Uses known project types instead of detecting them from the shell's
+    /// working directory.
+    pub fn with_project_types(self, projects: HashSet<ProjectType>) -> Self {
+        Self {
+            projects: OnceLock::from(projects),
+            ..self
+        }
+    }
+
+    /// Records every probe rules run through the shell from now on, such as
+    /// `git branch` or a `PATH` lookup, for [`RuleContext::recorded_probes`].
+    pub fn recording_probes(self) -> Self {
+        let log = ProbeLog::default();
+        let shell = self.shell.map(|shell| Box::new(RecordingShell::new(shell, log.clone())) as Box<dyn Shell>);
+        Self {
+            shell,
+            probes: Some(log),
+            ..self
+        }
+    }
+
+    /// Gets the probes recorded since [`RuleContext::recording_probes`], or
+    /// `None` when they aren't being recorded.
+    pub fn recorded_probes(&self) -> Option<Probes> {
+        self.probes.as_ref().map(ProbeLog::probes)
+    }
+
+    /// Gets the shell backing this context, if any.
+    pub fn shell(&self) -> Option<&dyn Shell> {
+        self.shell.as_deref()
+    }
+
+    /// Gets the indexed shell history, building it on first use.
+    pub fn history(&self) -> &HistoryIndex {
+        self.history.get_or_init(|| match self.shell() {
+            Some(shell) => HistoryIndex::build(shell),
+            None => HistoryIndex::default(),
+        })
+    }
+
+    /// Gets the commands recorded by the shell hook, loading them on first use.
+    pub fn session(&self) -> &SessionContext {
+        self.session.get_or_init(|| {
+            self.shell()
+                .and_then(|shell| SessionContext::session_dir(|key| shell.env(key)))
+                .map(|dir| SessionContext::load(&dir))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Gets the value cached under `key`, computing it with `f` on first use.
+    ///
+    /// Concurrent callers with the same key wait for the first one, so `f`
+    /// runs at most once per invocation. Values of different types never
+    /// share a slot, even under the same key.
+    pub fn cached<T, F>(&self, key: &str, f: F) -> Arc<T>
+    where
+        T: Send + Sync + 'static,
+        F: FnOnce() -> T,
+    {
+        // Only hold the map lock long enough to find the slot, so slow
+        // computations for different keys don't block each other
+        let slot = {
+            let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            Arc::clone(cache.entry((key.to_string(), TypeId::of::<T>())).or_default())
+        };
+        let value = slot.get_or_init(|| Arc::new(f()) as Arc<dyn Any + Send + Sync>);
+        Arc::clone(value)
+            .downcast::<T>()
+            .unwrap_or_else(|_| unreachable!("cache slots are keyed by type"))
+    }
+
+    /// Returns true if `binary` is on the shell's `PATH`, or `None` without a
+    /// shell.
+    ///
+    /// Lookup errors count as missing.
+    pub fn command_exists(&self, binary: &str) -> Option<bool> {
+        let shell = self.shell()?;
+        let exists = self.cached(&format!("command_exists:{}", binary), || {
+            shell.command_exists(binary).unwrap_or(false)
+        });
+        Some(*exists)
+    }
+
+    /// Gets the privilege escalation tool the shell can run, or `sudo`
+    /// without a shell.
+    pub fn escalation_command(&self) -> Arc<String> {
+        self.cached("escalation_command", || match self.shell() {
+            Some(shell) => privilege::escalation_command(shell),
+            None => privilege::DEFAULT_TOOL.to_string(),
+        })
+    }
+
+    /// Gets the local git branches, or nothing outside a repository or
+    /// without a shell.
+    pub fn git_branches(&self) -> Arc<Vec<String>> {
+        self.cached("git_branches", || {
+            let Some(shell) = self.shell() else {
+                return Vec::new();
+            };
+            match shell.execute_with_timeout("git branch --format='%(refname:short)'", PROBE_TIMEOUT) {
+                Ok(output) if output.success => output
+                    .stdout
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                _ => Vec::new(),
+            }
+        })
+    }
+
+    /// Gets the sorted names of the entries in `path`, or nothing if it
+    /// can't be read.
+    ///
+    /// Relative paths are resolved against the shell's working directory.
+    pub fn dir_entries(&self, path: &Path) -> Arc<Vec<String>> {
+        let path = match self.shell().and_then(|shell| shell.cwd().ok()) {
+            Some(cwd) => cwd.join(path),
+            None => path.to_path_buf(),
+        };
+        self.cached(&format!("dir_entries:{}", path.display()), || {
+            let mut names: Vec<String> = std::fs::read_dir(&path)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            names.sort();
+            names
+        })
+    }
+
+    /// Gets the sorted, de-duplicated names of the executables on the
+    /// shell's `PATH`.
+    pub fn path_executables(&self) -> Arc<Vec<String>> {
+        self.cached("path_executables", || {
+            use std::os::unix::fs::PermissionsExt;
+
+            let Some(path) = self.shell().and_then(|shell| shell.env("PATH")) else {
+                return Vec::new();
+            };
+            let mut names: Vec<String> = std::env::split_paths(&path)
+                .filter_map(|dir| std::fs::read_dir(dir).ok())
+                .flat_map(|entries| entries.flatten())
+                .filter(|entry| {
+                    entry.metadata().is_ok_and(|metadata| {
+                        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+                    })
+                })
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+        })
+    }
+
+    /// Gets the project types of the shell's working directory, detecting
+    /// them on first use. Empty without a shell.
+    pub fn project_types(&self) -> &HashSet<ProjectType> {
+        self.projects.get_or_init(|| {
+            self.shell()
+                .and_then(|shell| shell.cwd().ok())
+                .map(|cwd| ProjectType::detect(&cwd))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Returns true if the history index has already been built.
+    pub fn history_loaded(&self) -> bool {
+        self.history.get().is_some()
+    }
+}
+
+impl Default for RuleContext {
+    fn default() -> Self {
+        Self::from_history(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_from_history() {
+        let context = RuleContext::from_history(vec!["git status".to_string()]);
+        assert_eq!(context.history().most_recent(10), &["git status"]);
+        assert_eq!(context.history().frequency("git status"), 1);
+        assert!(context.shell().is_none());
+    }
+
+    #[test]
+    fn test_context_history_is_lazy() {
+        let shell = crate::shell::BashShell::new().unwrap();
+        let context = RuleContext::new(Box::new(shell));
+        assert!(!context.history_loaded());
+
+        let _ = context.history();
+        assert!(context.history_loaded());
+    }
+
+    #[test]
+    fn test_context_default_is_empty() {
+        let context = RuleContext::default();
+        assert!(context.history().is_empty());
+        assert!(context.session().is_empty());
+    }
+
+    #[test]
+    fn test_context_command_exists() {
+        let shell = crate::testing::MockShell::new().with_output("podman --version", "", 0);
+        let context = RuleContext::new(Box::new(shell));
+        assert_eq!(context.command_exists("podman"), Some(true));
+        assert_eq!(context.command_exists("docker"), Some(false));
+
+        assert_eq!(RuleContext::default().command_exists("podman"), None);
+    }
+
+    /// Creates an empty directory for a detection test.
+    fn project_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_project_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_detect_rust_project() {
+        let dir = project_dir("rust");
+        std::fs::write(dir.join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(dir.join("Dockerfile"), "FROM rust\n").unwrap();
+
+        let detected = ProjectType::detect(&dir);
+        assert_eq!(detected, HashSet::from([ProjectType::Rust, ProjectType::Docker]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_node_and_terraform_projects() {
+        let dir = project_dir("node");
+        std::fs::write(dir.join("package.json"), "{}").unwrap();
+        std::fs::write(dir.join("main.tf"), "").unwrap();
+
+        let detected = ProjectType::detect(&dir);
+        assert_eq!(detected, HashSet::from([ProjectType::Node, ProjectType::Terraform]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_up_to_repository_root() {
+        let dir = project_dir("repo");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("src/app")).unwrap();
+        std::fs::write(dir.join("pyproject.toml"), "").unwrap();
+
+        let detected = ProjectType::detect(&dir.join("src/app"));
+        assert_eq!(detected, HashSet::from([ProjectType::Python, ProjectType::Git]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_outside_repository_checks_only_cwd() {
+        let dir = project_dir("plain");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("go.mod"), "module example.com/x\n").unwrap();
+
+        assert!(ProjectType::detect(&dir.join("sub")).is_empty());
+        assert_eq!(ProjectType::detect(&dir), HashSet::from([ProjectType::Go]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_context_project_types_from_shell_cwd() {
+        let dir = project_dir("context");
+        std::fs::write(dir.join("Cargo.toml"), "[package]\n").unwrap();
+
+        let shell = crate::testing::MockShell::new().with_cwd(dir.clone());
+        let context = RuleContext::new(Box::new(shell));
+        assert_eq!(context.project_types(), &HashSet::from([ProjectType::Rust]));
+
+        // Cached for the rest of the invocation
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(context.project_types(), &HashSet::from([ProjectType::Rust]));
+
+        assert!(RuleContext::default().project_types().is_empty());
+    }
+
+    #[test]
+    fn test_cached_runs_once_under_parallel_access() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let context = RuleContext::default();
+        let calls = AtomicUsize::new(0);
+
+        let values: Vec<Arc<Vec<String>>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..32)
+                .map(|_| {
+                    scope.spawn(|| {
+                        context.cached("git_branches", || {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            std::thread::sleep(std::time::Duration::from_millis(20));
+                            vec!["main".to_string()]
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(values.iter().all(|value| Arc::ptr_eq(value, &values[0])));
+    }
+
+    #[test]
+    fn test_cached_keys_and_types_are_separate() {
+        let context = RuleContext::default();
+
+        assert_eq!(*context.cached("a", || 1u32), 1);
+        assert_eq!(*context.cached("a", || 2u32), 1);
+        assert_eq!(*context.cached("b", || 3u32), 3);
+        assert_eq!(*context.cached("a", || "text"), "text");
+    }
+
+    #[test]
+    fn test_with_history_replaces_the_shells() {
+        let shell = crate::testing::MockShell::new().with_history(["make"]);
+        let context = RuleContext::new(Box::new(shell)).with_history(vec!["cargo test".to_string()]);
+        assert_eq!(context.history().most_recent(10), &["cargo test"]);
+        assert!(context.shell().is_some());
+    }
+
+    #[test]
+    fn test_recording_probes() {
+        let shell = crate::testing::MockShell::new()
+            .with_output("git branch --format='%(refname:short)'", "main\nfeature\n", 0)
+            .with_env("PATH", "/nonexistent")
+            .with_command("sudo", true);
+        let context = RuleContext::new(Box::new(shell)).recording_probes();
+        assert_eq!(*context.git_branches(), vec!["main", "feature"]);
+        assert_eq!(context.command_exists("sudo"), Some(true));
+        context.path_executables();
+
+        let probes = context.recorded_probes().unwrap();
+        assert_eq!(probes.runs.len(), 1);
+        assert_eq!(probes.runs[0].command, "git branch --format='%(refname:short)'");
+        assert_eq!(probes.command_exists.get("sudo"), Some(&true));
+        assert_eq!(probes.env.get("PATH").map(String::as_str), Some("/nonexistent"));
+        assert_eq!(RuleContext::default().recorded_probes(), None);
+    }
+
+    #[test]
+    fn test_git_branches() {
+        let shell = crate::testing::MockShell::new()
+            .with_output("git branch --format='%(refname:short)'", "main\nfeature/login\n", 0);
+        let context = RuleContext::new(Box::new(shell));
+
+        assert_eq!(*context.git_branches(), vec!["main", "feature/login"]);
+        assert!(RuleContext::default().git_branches().is_empty());
+    }
+
+    #[test]
+    fn test_dir_entries_relative_to_cwd() {
+        let dir = project_dir("entries");
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+
+        let shell = crate::testing::MockShell::new().with_cwd(dir.clone());
+        let context = RuleContext::new(Box::new(shell));
+        assert_eq!(*context.dir_entries(Path::new(".")), vec!["a", "b.txt"]);
+        assert_eq!(*context.dir_entries(&dir), vec!["a", "b.txt"]);
+        assert!(context.dir_entries(Path::new("missing")).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_path_executables() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = project_dir("path");
+        for (name, mode) in [("ftf-tool", 0o755), ("notes.txt", 0o644)] {
+            std::fs::write(dir.join(name), "").unwrap();
+            std::fs::set_permissions(dir.join(name), std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let path = std::env::join_paths([&dir, &dir.join("missing")]).unwrap();
+        let shell = crate::testing::MockShell::new().with_env("PATH", path.to_str().unwrap());
+        let context = RuleContext::new(Box::new(shell));
+        assert_eq!(*context.path_executables(), vec!["ftf-tool"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_context_with_session() {
+        use crate::shell::SessionEntry;
+
+        let context = RuleContext::default()
+            .with_session(SessionContext::from_entries(vec![SessionEntry::new("ls", "", 0)]));
+        assert_eq!(context.session().entries()[0].command, "ls");
+    }
+}
@@ -0,0 +1,187 @@
+//! Recorded failures and the corrections expected for them.
+//!
+//! A corpus file is TOML with one `[[case]]` table per failure:
+//!
+//! ```toml
+//! [[case]]
+//! script = "git comit -m 'fix'"
+//! output = """
+//! git: 'comit' is not a git command. See 'git --help'.
+//!
+//! The most similar command is
+//! \tcommit
+//! """
+//! exit_code = 1
+//! expected_top = "git commit -m 'fix'"
+//! must_not_suggest = ["git comit -m 'fix'"]
+//! ```
+//!
+//! `tests/corpus/` holds the cases every build is checked against, so tool
+//! versions that word their errors differently don't silently break a rule.
+//! `ftf corpus record` appends the failure being corrected, with the
+//! correction that was accepted, to a local file whose cases can be
+//! contributed there.
+
+use crate::{Command, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A failed command and what should be suggested for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusCase {
+    /// The command that failed
+    pub script: String,
+    /// What it printed
+    pub output: String,
+    /// Its exit code
+    pub exit_code: i32,
+    /// The correction that should be suggested first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_top: Option<String>,
+    /// Text some suggested correction should contain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_contains: Option<String>,
+    /// Corrections that should not be suggested at all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub must_not_suggest: Vec<String>,
+}
+
+/// The cases of a corpus file, as laid out in it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CorpusFile {
+    #[serde(default, rename = "case")]
+    cases: Vec<CorpusCase>,
+}
+
+impl CorpusCase {
+    /// Creates a case expecting `accepted` to be suggested first.
+    pub fn new(script: impl Into<String>, output: impl Into<String>, exit_code: i32, accepted: Option<String>) -> Self {
+        Self {
+            script: script.into(),
+            output: output.into(),
+            exit_code,
+            expected_top: accepted,
+            ..Self::default()
+        }
+    }
+
+    /// Gets the failed command.
+    pub fn command(&self) -> Command {
+        Command::new(&self.script, &self.output, self.exit_code)
+    }
+
+    /// Checks `corrections`, best first, against what the case expects.
+    ///
+    /// Returns a description of the first expectation that doesn't hold.
+    pub fn check(&self, corrections: &[&str]) -> std::result::Result<(), String> {
+        if let Some(expected) = &self.expected_top {
+            if corrections.first() != Some(&expected.as_str()) {
+                return Err(format!("expected {:?} first, got {:?}", expected, corrections));
+            }
+        }
+        if let Some(expected) = &self.expected_contains {
+            if !corrections.iter().any(|correction| correction.contains(expected.as_str())) {
+                return Err(format!("expected a correction containing {:?}, got {:?}", expected, corrections));
+            }
+        }
+        match self.must_not_suggest.iter().find(|unwanted| corrections.contains(&unwanted.as_str())) {
+            Some(unwanted) => Err(format!("{:?} was suggested: {:?}", unwanted, corrections)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses the cases of a corpus file.
+pub fn parse(contents: &str) -> Result<Vec<CorpusCase>> {
+    let file: CorpusFile = toml::from_str(contents).map_err(|e| Error::Other(e.to_string()))?;
+    Ok(file.cases)
+}
+
+/// Reads the cases of every `.toml` file in `dir`, in file name order, each
+/// with the name of its file.
+pub fn read_dir(dir: &Path) -> Result<Vec<(String, CorpusCase)>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .collect();
+    paths.sort();
+
+    let mut cases = Vec::new();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let parsed = parse(&std::fs::read_to_string(&path)?)
+            .map_err(|e| Error::Other(format!("{}: {}", path.display(), e.user_message())))?;
+        cases.extend(parsed.into_iter().map(|case| (name.clone(), case)));
+    }
+    Ok(cases)
+}
+
+/// Gets the default local corpus: <data dir>/fasterthefuck/corpus.toml
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("fasterthefuck").join("corpus.toml"))
+}
+
+/// Appends `case` to the corpus file at `path`, creating the file and its
+/// parent directory.
+pub fn append(path: &Path, case: &CorpusCase) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = CorpusFile { cases: vec![case.clone()] };
+    let table = toml::to_string(&file).map_err(|e| Error::Other(e.to_string()))?;
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", table)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CASE: &str = "[[case]]\nscript = \"git pus\"\noutput = \"\"\"\ngit: 'pus' is not a git command.\n\"\"\"\n\
+                        exit_code = 1\nexpected_top = \"git push\"\nmust_not_suggest = [\"git pull\"]\n";
+
+    #[test]
+    fn test_parse() {
+        let cases = parse(CASE).unwrap();
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].output, "git: 'pus' is not a git command.\n");
+        assert_eq!(cases[0].expected_top.as_deref(), Some("git push"));
+        assert_eq!(cases[0].expected_contains, None);
+        assert!(parse("[[case]]\nscript = \"ls\"\n").is_err());
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let case = &parse(CASE).unwrap()[0];
+        assert_eq!(case.check(&["git push", "git pull --rebase"]), Ok(()));
+        assert!(case.check(&["git pull --rebase", "git push"]).unwrap_err().contains("expected \"git push\" first"));
+        assert!(case.check(&["git push", "git pull"]).unwrap_err().contains("\"git pull\" was suggested"));
+        assert!(case.check(&[]).is_err());
+
+        let contains = CorpusCase {
+            expected_contains: Some("-u origin".to_string()),
+            ..CorpusCase::new("git push", "", 128, None)
+        };
+        assert_eq!(contains.check(&["git push --force", "git push -u origin main"]), Ok(()));
+        assert!(contains.check(&["git push --force"]).is_err());
+    }
+
+    #[test]
+    fn test_append_round_trips() {
+        let path = std::env::temp_dir().join(format!("ftf_corpus_{}", std::process::id())).join("corpus.toml");
+        let _ = std::fs::remove_file(&path);
+        let output = "git: 'pus' is not a git command.\n\n\tpush\n";
+        let first = CorpusCase::new("git pus", output, 1, Some("git push".into()));
+        let second = CorpusCase::new("apt install vim", "E: Could not open lock file", 100, None);
+
+        append(&path, &first).unwrap();
+        append(&path, &second).unwrap();
+        assert_eq!(parse(&std::fs::read_to_string(&path).unwrap()).unwrap(), vec![first, second]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
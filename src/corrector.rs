@@ -1,7 +1,37 @@
-/// Rule evaluation and command correction engine with parallel processing.
+//! Rule evaluation and command correction engine with parallel processing.
+//!
+//! Rules are matched on rayon's thread pool with the `parallel` feature,
+//! which is on by default, and one after another without it.
+//!
+//! Each correction is logged as a `correct` span, holding a `rule` span for
+//! every rule tried with whether it matched, why it was skipped and how long
+//! it took. Nothing is logged unless the application installs a `tracing`
+//! subscriber.
+//!
+//! [`Corrector::stream`] reports each rule as it finishes, for showing
+//! progress while slow rules run, and [`Corrector::correct`] gives the
+//! corrections as a [`CorrectionSet`], with how many rules were tried.
+//!
+//! A rule that panics, such as a custom rule indexing past its captures, is
+//! skipped and recorded as a [`RuleFailure`], and the other rules go on.
+//!
+//! Fallback rules ([`Rule::is_fallback`]) make a second tier: they are only
+//! tried when none of the others corrected the command, so a generic
+//! suggestion like `man git` never sits among real corrections.
 
-use crate::{Command, CorrectedCommand, Rule};
+use crate::learning::AdaptivePriorities;
+use crate::prefilter::{Candidates, Prefilter};
+use crate::rules::{RuleFactory, RuleIndex};
+use crate::{Command, Config, CorrectedCommand, ProjectType, Rule, RuleContext, Verdict};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{field, Span};
 
 // Re-export RuleRegistry from rules module for convenience
 pub use crate::rules::RuleRegistry;
@@ -9,46 +39,536 @@ pub use crate::rules::RuleRegistry;
 /// The command correction engine.
 pub struct Corrector {
     rules: Vec<Box<dyn Rule>>,
+    prefilter: Option<Prefilter>,
+    index: Option<RuleIndex>,
+    projects: HashSet<ProjectType>,
+    project_penalty: i32,
+    context: Option<Arc<RuleContext>>,
+    verify_budget: Duration,
+    parallel_threshold: usize,
+}
+
+/// Added to the priority of corrections a rule's verify check demoted.
+pub const DEMOTE_PENALTY: i32 = 1000;
+
+/// What [`Corrector::stream`] reports while it corrects a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorrectionEvent {
+    /// A rule finished, whether or not it matched: `done` of the `total`
+    /// rules that could match are finished
+    RuleDone { rule: String, done: usize, total: usize },
+    /// A correction from the rule about to be reported done, unranked
+    Correction(CorrectedCommand),
+    /// Every rule finished; the corrections as `correct` returns them
+    Finished(CorrectionSet),
+}
+
+/// The corrections of a command, with how finding them went.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorrectionSet {
+    /// The corrections, best first
+    pub corrections: Vec<CorrectedCommand>,
+    /// Rules that could match the command and were tried
+    pub evaluated: usize,
+    /// Rules that matched
+    pub matched: usize,
+    /// Corrections found but then left out
+    pub suppressed: usize,
+    /// How long the rules took
+    pub elapsed: Duration,
+    /// Rules that panicked and were skipped
+    pub failures: Vec<RuleFailure>,
+}
+
+/// A rule that panicked while it was tried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleFailure {
+    /// The rule's name
+    pub rule: String,
+    /// The panic's message
+    pub payload: String,
+}
+
+impl RuleFailure {
+    fn new(rule: &str, payload: Box<dyn std::any::Any + Send>) -> Self {
+        let payload = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "a panic without a message".to_string(),
+            },
+        };
+        Self {
+            rule: rule.to_string(),
+            payload,
+        }
+    }
+}
+
+/// How trying one rule went.
+enum Tried {
+    Skipped,
+    Matched(Vec<CorrectedCommand>),
+    Failed(RuleFailure),
+}
+
+impl CorrectionSet {
+    /// Passes the corrections through `filter`, counting those it leaves
+    /// out as suppressed. Returns how many it left out.
+    pub fn filter(&mut self, filter: impl FnOnce(Vec<CorrectedCommand>) -> Vec<CorrectedCommand>) -> usize {
+        let before = self.corrections.len();
+        self.corrections = filter(std::mem::take(&mut self.corrections));
+        let removed = before.saturating_sub(self.corrections.len());
+        self.suppressed += removed;
+        removed
+    }
+}
+
+/// Where a streamed correction reports finished rules.
+struct Progress {
+    events: Sender<CorrectionEvent>,
+    done: AtomicUsize,
+    total: usize,
+}
+
+impl Progress {
+    fn rule_done(&self, rule: &str, corrections: &[CorrectedCommand]) {
+        // A receiver that went away doesn't stop the correction
+        for correction in corrections {
+            let _ = self.events.send(CorrectionEvent::Correction(correction.clone()));
+        }
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let _ = self.events.send(CorrectionEvent::RuleDone {
+            rule: rule.to_string(),
+            done,
+            total: self.total,
+        });
+    }
 }
 
 impl Corrector {
     /// Creates a new corrector with a rule registry.
     pub fn new(registry: RuleRegistry) -> Self {
         Self {
-            rules: registry.rules.into(),
+            prefilter: Some(Prefilter::new(&registry.rules)),
+            index: Some(registry.index()),
+            rules: registry.rules,
+            projects: HashSet::new(),
+            project_penalty: 0,
+            context: None,
+            verify_budget: Duration::ZERO,
+            parallel_threshold: 0,
+        }
+    }
+
+    /// Creates a corrector for just `rules`, letting them verify their
+    /// corrections against `context` with a budget no test should exhaust.
+    pub fn with_rules(rules: Vec<Box<dyn Rule>>, context: Arc<RuleContext>) -> Self {
+        let mut registry = RuleRegistry::new();
+        registry.add_rules(rules);
+        Self::new(registry).with_context(context, Duration::from_secs(60))
+    }
+
+    /// Builds the corrector for `script`: the enabled rule groups of
+    /// `factories` that could match it, at their configured or learned priorities,
+    /// ranking rules for other toolchains lower and letting rules check their
+    /// corrections within the configured budget.
+    pub fn for_command(
+        factories: Vec<RuleFactory<'_>>,
+        config: &Config,
+        learned: &AdaptivePriorities,
+        context: &Arc<RuleContext>,
+        script: &str,
+    ) -> Self {
+        Self::for_script(factories, config, learned, context, Some(script))
+    }
+
+    /// Builds the corrector for any command, like [`Corrector::for_command`]
+    /// with every enabled rule group, for processes that correct many.
+    pub fn for_any_command(
+        factories: Vec<RuleFactory<'_>>,
+        config: &Config,
+        learned: &AdaptivePriorities,
+        context: &Arc<RuleContext>,
+    ) -> Self {
+        Self::for_script(factories, config, learned, context, None)
+    }
+
+    fn for_script(
+        factories: Vec<RuleFactory<'_>>,
+        config: &Config,
+        learned: &AdaptivePriorities,
+        context: &Arc<RuleContext>,
+        script: Option<&str>,
+    ) -> Self {
+        let mut registry = RuleRegistry::new();
+        registry.add_factories(factories, script);
+        registry.retain(|rule| config.is_rule_enabled(rule.name()));
+
+        // Config overrides win over learned adjustments
+        registry.override_priorities(|rule| {
+            learned.effective_priority(rule.name(), rule.priority(), config.get_rule_priority(rule.name()))
+        });
+
+        Corrector::from(registry)
+            .with_projects(context.project_types().clone(), config.global.project_penalty)
+            .with_context(Arc::clone(context), Duration::from_millis(config.global.verify_budget_ms))
+    }
+
+    /// Gets how much of a command's output to keep: `configured`, unless a
+    /// rule needs all of it.
+    pub fn max_output_bytes(&self, configured: usize) -> usize {
+        if self.rules().iter().any(|rule| rule.requires_full_output()) {
+            usize::MAX
+        } else {
+            configured
+        }
+    }
+
+    /// Ranks corrections from rules that aren't relevant to `projects` lower
+    /// by adding `penalty` to their priority.
+    ///
+    /// With no detected project types every rule counts as relevant.
+    pub fn with_projects(mut self, projects: HashSet<ProjectType>, penalty: i32) -> Self {
+        self.projects = projects;
+        self.project_penalty = penalty;
+        self
+    }
+
+    /// Lets rules verify their corrections against `context` after
+    /// matching.
+    ///
+    /// Checks stop once `budget` has passed since matching began; later
+    /// corrections are kept unchecked. Without a context nothing is verified.
+    pub fn with_context(mut self, context: Arc<RuleContext>, budget: Duration) -> Self {
+        self.context = Some(context);
+        self.verify_budget = budget;
+        self
+    }
+
+    /// Calls every rule's `matches`, instead of skipping rules whose
+    /// required substrings the command doesn't contain.
+    ///
+    /// The results are the same; this is a reference for testing the
+    /// pre-filter.
+    pub fn without_prefilter(mut self) -> Self {
+        self.prefilter = None;
+        self
+    }
+
+    /// Matches rules one after another when there are fewer than `threshold`
+    /// of them, where starting the thread pool costs more than it saves.
+    ///
+    /// Every count is matched in parallel by default. Without the `parallel`
+    /// feature rules are always matched one after another.
+    pub fn set_parallel_threshold(&mut self, threshold: usize) {
+        self.parallel_threshold = threshold;
+    }
+
+    /// Checks every rule, instead of only those indexed under the command's
+    /// executable and those without a leading token.
+    ///
+    /// The results are the same; this is a reference for testing the index.
+    pub fn without_index(mut self) -> Self {
+        self.index = None;
+        self
+    }
+
+    /// Gets the positions of the rules that could match `command`.
+    fn positions(&self, command: &Command) -> Vec<usize> {
+        match &self.index {
+            Some(index) => index.lookup(command),
+            None => (0..self.rules.len()).collect(),
         }
     }
 
+    /// Returns true if `rule` fits the detected project types.
+    fn is_relevant(&self, rule: &dyn Rule) -> bool {
+        self.projects.is_empty()
+            || rule
+                .relevant_projects()
+                .iter()
+                .any(|project| self.projects.contains(project))
+    }
+
     /// Gets all enabled rules.
+    // Boxed as it always was, so library callers naming the type still build
+    #[allow(clippy::borrowed_box)]
     pub fn rules(&self) -> Vec<&Box<dyn Rule>> {
-        self.rules
-            .iter()
-            .filter(|rule| rule.enabled_by_default())
-            .collect()
+        self.rules.iter().filter(|rule| rule.enabled_by_default()).collect()
     }
 
     /// Finds and returns all corrections for a command, sorted by priority.
-    ///
-    /// This uses parallel evaluation via Rayon for performance.
     pub fn get_corrections(&self, command: &Command) -> Vec<CorrectedCommand> {
-        // Parallel rule matching and correction
-        let mut corrections: Vec<CorrectedCommand> = self
-            .rules
-            .par_iter()
-            .filter(|rule| {
-                // Skip rules that require output but command has no output
-                if rule.requires_output() && command.output.is_empty() {
-                    return false;
+        self.correct(command).corrections
+    }
+
+    /// Finds the corrections for a command like `get_corrections`, with how
+    /// many rules were tried and matched and how long they took.
+    pub fn correct(&self, command: &Command) -> CorrectionSet {
+        self.find_corrections(command, None)
+    }
+
+    /// Finds the corrections for a command like `get_corrections`, sending
+    /// `events` as each rule finishes and all the corrections at the end.
+    pub fn stream(&self, command: &Command, events: Sender<CorrectionEvent>) {
+        let progress = Progress {
+            events,
+            done: AtomicUsize::new(0),
+            total: self.positions(command).len(),
+        };
+        let set = self.find_corrections(command, Some(&progress));
+        let _ = progress.events.send(CorrectionEvent::Finished(set));
+    }
+
+    fn find_corrections(&self, command: &Command, progress: Option<&Progress>) -> CorrectionSet {
+        let span = tracing::info_span!(
+            "correct",
+            script = %command.script,
+            exit_code = command.exit_code,
+            corrections = field::Empty
+        );
+        let _entered = span.enter();
+        let started = Instant::now();
+        let deadline = started + self.verify_budget;
+        let candidates = self.prefilter.as_ref().map(|prefilter| prefilter.candidates(command));
+
+        let positions = self.positions(command);
+        let (primary, fallbacks): (Vec<usize>, Vec<usize>) =
+            positions.iter().partition(|&&position| !self.rules[position].is_fallback());
+
+        let mut tried = self.try_rules(command, &primary, candidates.as_ref(), deadline, &span, progress);
+        let mut evaluated = primary.len();
+        let corrected = tried.iter().any(|outcome| matches!(outcome, Tried::Matched(found) if !found.is_empty()));
+        if !corrected {
+            // Fallbacks are only tried when no other rule corrects the command
+            tried.extend(self.try_rules(command, &fallbacks, candidates.as_ref(), deadline, &span, progress));
+            evaluated += fallbacks.len();
+        } else if let Some(progress) = progress {
+            // Reported done untried, so progress still reaches the total
+            for &position in &fallbacks {
+                progress.rule_done(self.rules[position].name(), &[]);
+            }
+        }
+
+        let mut matched = 0;
+        let mut corrections = Vec::new();
+        let mut failures = Vec::new();
+        for outcome in tried {
+            match outcome {
+                Tried::Skipped => {}
+                Tried::Matched(found) => {
+                    matched += 1;
+                    corrections.extend(found);
                 }
-                rule.matches(command)
-            })
-            .flat_map(|rule| rule.get_corrected_commands(command))
-            .collect();
+                Tried::Failed(failure) => failures.push(failure),
+            }
+        }
 
         // Sort by priority and remove duplicates
         corrections.sort();
         corrections.dedup();
 
+        span.record("corrections", corrections.len());
+        CorrectionSet {
+            corrections,
+            evaluated,
+            matched,
+            suppressed: 0,
+            elapsed: started.elapsed(),
+            failures,
+        }
+    }
+
+    /// Tries the rules at `positions`, in parallel unless there are fewer
+    /// than the threshold.
+    fn try_rules(
+        &self,
+        command: &Command,
+        positions: &[usize],
+        candidates: Option<&Candidates>,
+        deadline: Instant,
+        span: &Span,
+        progress: Option<&Progress>,
+    ) -> Vec<Tried> {
+        if positions.len() >= self.parallel_threshold {
+            self.parallel_corrections(command, positions, candidates, deadline, span, progress)
+        } else {
+            self.serial_corrections(command, positions, candidates, deadline, span, progress)
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn parallel_corrections(
+        &self,
+        command: &Command,
+        positions: &[usize],
+        candidates: Option<&Candidates>,
+        deadline: Instant,
+        span: &Span,
+        progress: Option<&Progress>,
+    ) -> Vec<Tried> {
+        positions
+            .par_iter()
+            .map(|&position| self.try_rule(position, command, candidates, deadline, span, progress))
+            .collect()
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn parallel_corrections(
+        &self,
+        command: &Command,
+        positions: &[usize],
+        candidates: Option<&Candidates>,
+        deadline: Instant,
+        span: &Span,
+        progress: Option<&Progress>,
+    ) -> Vec<Tried> {
+        self.serial_corrections(command, positions, candidates, deadline, span, progress)
+    }
+
+    fn serial_corrections(
+        &self,
+        command: &Command,
+        positions: &[usize],
+        candidates: Option<&Candidates>,
+        deadline: Instant,
+        span: &Span,
+        progress: Option<&Progress>,
+    ) -> Vec<Tried> {
+        positions
+            .iter()
+            .map(|&position| self.try_rule(position, command, candidates, deadline, span, progress))
+            .collect()
+    }
+
+    /// Tries the rule at `position` on `command` in a `rule` span under
+    /// `parent`, and reports it done to `progress`. The parent is passed
+    /// along because rayon's threads don't share the current span. A panic
+    /// in the rule is caught, so one broken rule can't take down the rest.
+    fn try_rule(
+        &self,
+        position: usize,
+        command: &Command,
+        candidates: Option<&Candidates>,
+        deadline: Instant,
+        parent: &Span,
+        progress: Option<&Progress>,
+    ) -> Tried {
+        let rule = self.rules[position].as_ref();
+        let span = tracing::debug_span!(
+            parent: parent,
+            "rule",
+            rule = rule.name(),
+            matched = field::Empty,
+            skipped = field::Empty,
+            elapsed_us = field::Empty
+        );
+        let _entered = span.enter();
+        let start = Instant::now();
+
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            match self.skip_reason(position, rule, command, candidates) {
+                Some(reason) => Err(reason),
+                None => Ok(self.corrections_from(rule, command, deadline)),
+            }
+        }));
+        let (tried, skipped) = match outcome {
+            Ok(Ok(corrections)) => (Tried::Matched(corrections), None),
+            Ok(Err(reason)) => (Tried::Skipped, Some(reason)),
+            Err(payload) => {
+                let failure = RuleFailure::new(rule.name(), payload);
+                tracing::warn!(payload = %failure.payload, "rule panicked");
+                (Tried::Failed(failure), Some("panicked"))
+            }
+        };
+
+        span.record("matched", skipped.is_none());
+        if let Some(reason) = skipped {
+            span.record("skipped", reason);
+        }
+        span.record("elapsed_us", start.elapsed().as_micros() as u64);
+        if let Some(progress) = progress {
+            let corrections = match &tried {
+                Tried::Matched(corrections) => corrections.as_slice(),
+                _ => &[],
+            };
+            progress.rule_done(rule.name(), corrections);
+        }
+        tried
+    }
+
+    /// Times each rule the index consults for `command`: its `matches`, and
+    /// when it matches, its corrections. Rules run one after another, slowest
+    /// first in the result.
+    #[cfg(feature = "bench-internals")]
+    pub fn rule_timings(&self, command: &Command) -> Vec<(String, Duration)> {
+        let deadline = Instant::now() + self.verify_budget;
+        let candidates = self.prefilter.as_ref().map(|prefilter| prefilter.candidates(command));
+
+        let mut timings: Vec<(String, Duration)> = self
+            .positions(command)
+            .into_iter()
+            .map(|position| {
+                let rule = self.rules[position].as_ref();
+                let start = Instant::now();
+                if self.skip_reason(position, rule, command, candidates.as_ref()).is_none() {
+                    std::hint::black_box(self.corrections_from(rule, command, deadline));
+                }
+                (rule.name().to_string(), start.elapsed())
+            })
+            .collect();
+        timings.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        timings
+    }
+
+    /// Gets why the rule at `position` doesn't match `command`, or `None` if
+    /// it does.
+    fn skip_reason(
+        &self,
+        position: usize,
+        rule: &dyn Rule,
+        command: &Command,
+        candidates: Option<&Candidates>,
+    ) -> Option<&'static str> {
+        // Skip rules that require output but command has no output
+        if rule.requires_output() && command.output.is_empty() {
+            return Some("no output");
+        }
+        // Skip rules missing a substring they require
+        if candidates.is_some_and(|candidates| !candidates.contains(position)) {
+            return Some("prefilter");
+        }
+        (!rule.matches(command)).then_some("no match")
+    }
+
+    /// Gets the corrections of a matching rule, ranked for the detected
+    /// projects and verified until `deadline`.
+    fn corrections_from(&self, rule: &dyn Rule, command: &Command, deadline: Instant) -> Vec<CorrectedCommand> {
+        let mut corrections = rule.get_corrected_commands(command);
+        if corrections.is_empty() {
+            tracing::debug!("rule {} matched but produced no change", rule.name());
+        }
+        if !self.is_relevant(rule) {
+            for correction in &mut corrections {
+                correction.priority += self.project_penalty;
+            }
+        }
+        if let Some(context) = &self.context {
+            corrections.retain_mut(|correction| {
+                if Instant::now() >= deadline {
+                    return true;
+                }
+                match rule.verify(correction, context) {
+                    Verdict::Keep => true,
+                    Verdict::Demote => {
+                        correction.priority += DEMOTE_PENALTY;
+                        true
+                    }
+                    Verdict::Drop => false,
+                }
+            });
+        }
         corrections
     }
 
@@ -73,6 +593,9 @@ mod tests {
         name: String,
         matches_result: bool,
         suggestions: Vec<String>,
+        projects: &'static [ProjectType],
+        verdict: Verdict,
+        fallback: bool,
     }
 
     impl TestRule {
@@ -81,8 +604,26 @@ mod tests {
                 name: name.to_string(),
                 matches_result,
                 suggestions,
+                projects: ProjectType::ALL,
+                verdict: Verdict::Keep,
+                fallback: false,
             }
         }
+
+        fn fallback(mut self) -> Self {
+            self.fallback = true;
+            self
+        }
+
+        fn verified_as(mut self, verdict: Verdict) -> Self {
+            self.verdict = verdict;
+            self
+        }
+
+        fn relevant_to(mut self, projects: &'static [ProjectType]) -> Self {
+            self.projects = projects;
+            self
+        }
     }
 
     impl Rule for TestRule {
@@ -97,6 +638,31 @@ mod tests {
         fn get_new_commands(&self, _command: &Command) -> Vec<String> {
             self.suggestions.clone()
         }
+
+        fn relevant_projects(&self) -> &[ProjectType] {
+            self.projects
+        }
+
+        fn verify(&self, _correction: &CorrectedCommand, _context: &RuleContext) -> Verdict {
+            self.verdict
+        }
+
+        fn is_fallback(&self) -> bool {
+            self.fallback
+        }
+    }
+
+    fn project_registry() -> RuleRegistry {
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(
+            TestRule::new("npm_rule", true, vec!["npm install".to_string()])
+                .relevant_to(&[ProjectType::Node]),
+        ));
+        registry.add_rule(Box::new(
+            TestRule::new("cargo_rule", true, vec!["cargo build".to_string()])
+                .relevant_to(&[ProjectType::Rust]),
+        ));
+        registry
     }
 
     #[test]
@@ -125,6 +691,20 @@ mod tests {
         assert_eq!(corrections[0].script, "corrected");
     }
 
+    #[test]
+    fn test_corrections_that_change_nothing_are_left_out() {
+        let suggestions = ["", "  ", "git psuh", "git push"].map(str::to_string).to_vec();
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(TestRule::new("echo", true, suggestions)));
+
+        let corrector = Corrector::new(registry);
+        let corrections = corrector.get_corrections(&Command::new("git psuh", "error", 1));
+        let scripts: Vec<_> = corrections.iter().map(|correction| correction.script.as_str()).collect();
+        assert_eq!(scripts, ["git push"]);
+        // Its rank among what the rule suggested is kept
+        assert_eq!(corrections[0].priority, 4000);
+    }
+
     #[test]
     fn test_corrector_no_match() {
         let registry = RuleRegistry::new();
@@ -173,4 +753,324 @@ mod tests {
         // (Our TestRule defaults to requires_output() = true)
         assert_eq!(corrections.len(), 0);
     }
+
+    #[test]
+    fn test_irrelevant_project_corrections_are_demoted() {
+        let cmd = Command::new("build", "error", 1);
+
+        let rust = Corrector::new(project_registry())
+            .with_projects(HashSet::from([ProjectType::Rust, ProjectType::Git]), 500);
+        let corrections = rust.get_corrections(&cmd);
+        assert_eq!(corrections[0].script, "cargo build");
+        assert_eq!(corrections[1].script, "npm install");
+        assert_eq!(corrections[1].priority, 1500);
+
+        let node = Corrector::new(project_registry())
+            .with_projects(HashSet::from([ProjectType::Node]), 500);
+        assert_eq!(node.get_corrections(&cmd)[0].script, "npm install");
+    }
+
+    #[test]
+    fn test_no_detected_project_keeps_priorities() {
+        let corrector = Corrector::new(project_registry()).with_projects(HashSet::new(), 500);
+        let corrections = corrector.get_corrections(&Command::new("build", "error", 1));
+
+        assert!(corrections.iter().all(|correction| correction.priority == 1000));
+    }
+
+    fn verify_registry() -> RuleRegistry {
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(
+            TestRule::new("dropped", true, vec!["git checkout -b main".to_string()])
+                .verified_as(Verdict::Drop),
+        ));
+        registry.add_rule(Box::new(
+            TestRule::new("demoted", true, vec!["cd buidl".to_string()]).verified_as(Verdict::Demote),
+        ));
+        registry.add_rule(Box::new(TestRule::new("kept", true, vec!["cd build".to_string()])));
+        registry
+    }
+
+    #[test]
+    fn test_verify_drops_and_demotes() {
+        let corrector = Corrector::new(verify_registry())
+            .with_context(Arc::new(RuleContext::default()), Duration::from_secs(60));
+        let corrections = corrector.get_corrections(&Command::new("cd biuld", "error", 1));
+
+        let scripts: Vec<_> = corrections.iter().map(|c| c.script.as_str()).collect();
+        assert_eq!(scripts, vec!["cd build", "cd buidl"]);
+        assert_eq!(corrections[1].priority, 1000 + DEMOTE_PENALTY);
+    }
+
+    #[test]
+    fn test_verify_needs_context() {
+        let corrector = Corrector::new(verify_registry());
+        assert_eq!(corrector.get_corrections(&Command::new("cd biuld", "error", 1)).len(), 3);
+    }
+
+    #[test]
+    fn test_verify_stops_at_budget() {
+        let corrector = Corrector::new(verify_registry())
+            .with_context(Arc::new(RuleContext::default()), Duration::ZERO);
+        let corrections = corrector.get_corrections(&Command::new("cd biuld", "error", 1));
+
+        assert_eq!(corrections.len(), 3);
+        assert!(corrections.iter().all(|correction| correction.priority == 1000));
+    }
+
+    #[test]
+    fn test_serial_and_parallel_corrections_agree() {
+        let cmd = Command::new("cd biuld", "error", 1);
+        let parallel = Corrector::new(verify_registry());
+        let mut serial = Corrector::new(verify_registry());
+        serial.set_parallel_threshold(usize::MAX);
+
+        let corrections = serial.get_corrections(&cmd);
+        assert_eq!(corrections.len(), 3);
+        assert_eq!(corrections, parallel.get_corrections(&cmd));
+    }
+
+    fn stream_registry() -> RuleRegistry {
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(TestRule::new("second", true, vec!["git pull".to_string()])));
+        registry.add_rule(Box::new(TestRule::new("unmatched", false, vec!["ls".to_string()])));
+        registry.add_rule(Box::new(TestRule::new("first", true, vec!["git push".to_string()])));
+        registry
+    }
+
+    fn stream(corrector: &Corrector, cmd: &Command) -> Vec<CorrectionEvent> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        corrector.stream(cmd, sender);
+        receiver.into_iter().collect()
+    }
+
+    #[test]
+    fn test_stream_reports_each_rule_then_the_ranked_corrections() {
+        let mut corrector = Corrector::new(stream_registry());
+        corrector.set_parallel_threshold(usize::MAX);
+        let cmd = Command::new("git psuh", "error", 1);
+
+        let done = |rule: &str, done| CorrectionEvent::RuleDone {
+            rule: rule.to_string(),
+            done,
+            total: 3,
+        };
+        let mut events = stream(&corrector, &cmd);
+        let Some(CorrectionEvent::Finished(set)) = events.pop() else {
+            panic!("the stream didn't finish: {:?}", events);
+        };
+        assert_eq!((set.corrections, set.evaluated, set.matched), (corrector.get_corrections(&cmd), 3, 2));
+        assert_eq!(
+            events,
+            vec![
+                CorrectionEvent::Correction(CorrectedCommand::new("git pull", 100)),
+                done("second", 1),
+                done("unmatched", 2),
+                CorrectionEvent::Correction(CorrectedCommand::new("git push", 100)),
+                done("first", 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_correction_set_counts_rules_and_suppressed() {
+        let corrector = Corrector::new(stream_registry());
+        let mut set = corrector.correct(&Command::new("git psuh", "error", 1));
+        assert_eq!((set.evaluated, set.matched, set.suppressed), (3, 2, 0));
+
+        let removed = set.filter(|corrections| corrections.into_iter().filter(|c| c.script != "git pull").collect());
+        assert_eq!(removed, 1);
+        assert_eq!(set.corrections, [CorrectedCommand::new("git push", 100)]);
+        assert_eq!(set.suppressed, 1);
+    }
+
+    #[test]
+    fn test_panicking_rule_is_skipped_and_recorded() {
+        let cmd = Command::new("git psuh", "error", 1);
+        for threshold in [0, usize::MAX] {
+            let mut registry = stream_registry();
+            registry.add_rule(
+                crate::RegexRuleBuilder::new("bad_capture")
+                    .match_command_regex(r"^git (\w+)")
+                    .unwrap()
+                    .replace_with(|_, captures| vec![captures[2].to_string()])
+                    .build()
+                    .unwrap(),
+            );
+            let mut corrector = Corrector::new(registry);
+            corrector.set_parallel_threshold(threshold);
+
+            let set = corrector.correct(&cmd);
+            let scripts: Vec<_> = set.corrections.iter().map(|c| c.script.as_str()).collect();
+            assert_eq!(scripts, ["git pull", "git push"]);
+            assert_eq!((set.evaluated, set.matched), (4, 2));
+            assert_eq!(set.failures.len(), 1);
+            assert_eq!(set.failures[0].rule, "bad_capture");
+            assert!(set.failures[0].payload.contains("no group at index '2'"), "{:?}", set.failures);
+        }
+    }
+
+    #[test]
+    fn test_parallel_stream_counts_every_rule() {
+        let corrector = Corrector::new(stream_registry());
+        let cmd = Command::new("git psuh", "error", 1);
+
+        let events = stream(&corrector, &cmd);
+        let mut done: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                CorrectionEvent::RuleDone { done, total, .. } => Some((*done, *total)),
+                _ => None,
+            })
+            .collect();
+        done.sort();
+        assert_eq!(done, vec![(1, 3), (2, 3), (3, 3)]);
+        let streamed = events.iter().filter(|event| matches!(event, CorrectionEvent::Correction(_))).count();
+        assert_eq!(streamed, 2);
+        match events.last() {
+            Some(CorrectionEvent::Finished(set)) => assert_eq!(set.corrections, corrector.get_corrections(&cmd)),
+            event => panic!("the stream finished with {:?}", event),
+        }
+    }
+
+    fn fallback_registry(primary: bool) -> RuleRegistry {
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(TestRule::new("man", true, vec!["man git".to_string()]).fallback()));
+        registry.add_rule(Box::new(TestRule::new("primary", primary, vec!["git push".to_string()])));
+        registry
+    }
+
+    #[test]
+    fn test_fallbacks_run_when_nothing_else_corrects() {
+        let cmd = Command::new("git psuh", "usage: git [--version] [--help]", 2);
+        for threshold in [0, usize::MAX] {
+            let mut corrector = Corrector::new(fallback_registry(false));
+            corrector.set_parallel_threshold(threshold);
+
+            let set = corrector.correct(&cmd);
+            let scripts: Vec<_> = set.corrections.iter().map(|c| c.script.as_str()).collect();
+            assert_eq!(scripts, ["man git"]);
+            assert_eq!((set.evaluated, set.matched), (2, 1));
+        }
+
+        // A match that changes nothing doesn't count as a correction
+        let mut registry = fallback_registry(false);
+        registry.add_rule(Box::new(TestRule::new("unchanged", true, vec!["git psuh".to_string()])));
+        let scripts: Vec<_> = Corrector::new(registry).get_corrections(&cmd).into_iter().map(|c| c.script).collect();
+        assert_eq!(scripts, ["man git"]);
+    }
+
+    #[test]
+    fn test_fallbacks_are_skipped_when_a_rule_corrects() {
+        let cmd = Command::new("git psuh", "usage: git [--version] [--help]", 2);
+        for threshold in [0, usize::MAX] {
+            let mut corrector = Corrector::new(fallback_registry(true));
+            corrector.set_parallel_threshold(threshold);
+
+            let set = corrector.correct(&cmd);
+            let scripts: Vec<_> = set.corrections.iter().map(|c| c.script.as_str()).collect();
+            assert_eq!(scripts, ["git push"]);
+            assert_eq!((set.evaluated, set.matched), (1, 1));
+        }
+    }
+
+    #[test]
+    fn test_skipped_fallbacks_are_streamed_done() {
+        let corrector = Corrector::new(fallback_registry(true));
+        let events = stream(&corrector, &Command::new("git psuh", "usage: git", 2));
+        let mut done: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                CorrectionEvent::RuleDone { rule, done, total } => Some((rule.as_str(), *done, *total)),
+                _ => None,
+            })
+            .collect();
+        done.sort();
+        assert_eq!(done, vec![("man", 2, 2), ("primary", 1, 2)]);
+    }
+
+    #[cfg(feature = "bench-internals")]
+    #[test]
+    fn test_rule_timings_cover_every_rule() {
+        let corrector = Corrector::new(verify_registry());
+        let timings = corrector.rule_timings(&Command::new("cd biuld", "error", 1));
+
+        let mut names: Vec<_> = timings.iter().map(|(name, _)| name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["demoted", "dropped", "kept"]);
+        assert!(timings.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    /// Collects what a `tracing` subscriber writes.
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl tracing_subscriber::fmt::MakeWriter<'_> for LogBuffer {
+        type Writer = Self;
+
+        fn make_writer(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_rule_spans_are_logged() {
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(TestRule::new("hit", true, vec!["fixed".to_string()])));
+        registry.add_rule(Box::new(TestRule::new("miss", false, vec![])));
+        let mut corrector = Corrector::new(registry);
+        // The subscriber is only the default on this thread
+        corrector.set_parallel_threshold(usize::MAX);
+
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            corrector.get_corrections(&Command::new("typo", "error", 1));
+        });
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("rule{rule=\"hit\" matched=true elapsed_us="), "{}", log);
+        assert!(log.contains("rule{rule=\"miss\" matched=false skipped=\"no match\" elapsed_us="), "{}", log);
+        assert!(log.contains("correct{script=typo exit_code=1 corrections=1}"), "{}", log);
+    }
+
+    #[test]
+    fn test_match_without_change_is_traced() {
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(TestRule::new("echo", true, vec!["typo".to_string()])));
+        registry.add_rule(Box::new(TestRule::new("silent", true, vec![])));
+        let mut corrector = Corrector::new(registry);
+        corrector.set_parallel_threshold(usize::MAX);
+
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        let corrections = tracing::subscriber::with_default(subscriber, || {
+            corrector.get_corrections(&Command::new("typo", "error", 1))
+        });
+
+        assert!(corrections.is_empty());
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("rule echo matched but produced no change"), "{}", log);
+        assert!(log.contains("rule silent matched but produced no change"), "{}", log);
+    }
 }
@@ -0,0 +1,477 @@
+//! A long-running process that answers correction requests over a unix
+//! socket.
+//!
+//! Every invocation of ftf starts a process and compiles the built-in rules'
+//! patterns. `ftf daemon` pays for that once: it listens on
+//! `$XDG_RUNTIME_DIR/fasterthefuck/daemon.sock`, and ftf forwards each
+//! correction to it when the socket exists. Rules are built for each request
+//! against the request's directory, which is cheap once the patterns are
+//! compiled (see `benches/STARTUP.md`).
+//!
+//! Requests and responses are newline-delimited JSON, one response per
+//! request line:
+//!
+//! ```text
+//! {"version":2,"script":"git pus","output":"git: 'pus' is not a git command...","exit_code":1,"cwd":"/src/app","env":{"HOME":"/home/ada",...}}
+//! {"version":2,"corrections":[{"script":"git push","priority":1000,...}]}
+//! ```
+//!
+//! The configuration is read once when the daemon starts; learned priorities
+//! are read for every request, as a separate invocation would. Rules see the
+//! client's environment, not the daemon's. Clients send
+//! a hash of their configuration, and a daemon started with another one
+//! refuses the request, so a changed config.toml is never ignored.
+
+use crate::learning::AdaptivePriorities;
+use crate::{output, stats, BashShell, Command, Config, CorrectedCommand, Corrector, Error, Result, RuleContext};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// Version of the request and response format. A daemon only answers
+/// requests of its own version.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// How long a client waits for the daemon before correcting in-process.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a connection may sit idle before the daemon closes it.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Room in a request line for everything besides the output: the script,
+/// directory and environment.
+const REQUEST_OVERHEAD_BYTES: u64 = 1024 * 1024;
+
+/// How often the accept loop checks whether to shut down.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A failed command to correct.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    pub script: String,
+    pub output: String,
+    pub exit_code: i32,
+    /// Directory the command ran in
+    pub cwd: PathBuf,
+    /// Environment the command ran with
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Hash of the client's configuration, see [`crate::replay::config_hash`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_hash: Option<String>,
+}
+
+impl Request {
+    /// Creates a request in the current protocol version.
+    pub fn new(script: impl Into<String>, output: impl Into<String>, exit_code: i32, cwd: PathBuf) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            script: script.into(),
+            output: output.into(),
+            exit_code,
+            cwd,
+            env: HashMap::new(),
+            config_hash: None,
+        }
+    }
+
+    /// Sends `env` as the environment the command ran with.
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Asks the daemon to answer only if it runs with the configuration
+    /// hashed as `hash`.
+    pub fn with_config_hash(mut self, hash: impl Into<String>) -> Self {
+        self.config_hash = Some(hash.into());
+        self
+    }
+}
+
+/// The corrections for a request, or why there are none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    #[serde(default)]
+    pub corrections: Vec<CorrectedCommand>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn corrections(corrections: Vec<CorrectedCommand>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            corrections,
+            error: None,
+        }
+    }
+
+    fn error(error: impl Into<String>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            corrections: Vec::new(),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Gets the daemon's socket path, under `$XDG_RUNTIME_DIR`. There is none
+/// without a runtime directory, since other users could reach a socket
+/// elsewhere.
+pub fn socket_path() -> Option<PathBuf> {
+    socket_path_in(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from))
+}
+
+fn socket_path_in(runtime_dir: Option<PathBuf>) -> Option<PathBuf> {
+    runtime_dir
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join("fasterthefuck").join("daemon.sock"))
+}
+
+/// Sends `request` to the daemon listening on `socket` and returns its
+/// corrections.
+pub fn query(socket: &Path, request: &Request) -> Result<Vec<CorrectedCommand>> {
+    let stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut line = serde_json::to_string(request).map_err(|e| Error::daemon(e.to_string()))?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    let response: Response = serde_json::from_str(&reply).map_err(|e| Error::daemon(format!("bad response: {}", e)))?;
+    match response.error {
+        Some(error) => Err(Error::daemon(error)),
+        None => Ok(response.corrections),
+    }
+}
+
+/// Corrects `request` the way a separate invocation of ftf in its directory
+/// would, with the configuration `config`.
+pub fn correct(config: &Config, request: &Request) -> Vec<CorrectedCommand> {
+    let shell = BashShell::in_dir(request.cwd.clone()).with_env(request.env.clone());
+    let context = Arc::new(RuleContext::new(Box::new(shell)));
+    let learned = stats::default_log_path()
+        .and_then(|path| AdaptivePriorities::load(&path).ok())
+        .unwrap_or_default();
+    let corrector = Corrector::for_command(
+        config.factories(&context),
+        config,
+        &learned,
+        &context,
+        &request.script,
+    );
+    correct_with(&corrector, config, request)
+}
+
+/// Corrects `request` with `corrector`, already built for its directory
+/// with the configuration `config`.
+pub fn correct_with(corrector: &Corrector, config: &Config, request: &Request) -> Vec<CorrectedCommand> {
+    let output = output::cap(&request.output, corrector.max_output_bytes(config.global.max_output_bytes));
+    let command = Command {
+        script: request.script.clone(),
+        output: output.text,
+        exit_code: request.exit_code,
+        output_truncated: output.truncated,
+        output_had_invalid_utf8: output.invalid_utf8,
+    };
+    corrector.get_corrections(&command)
+}
+
+/// A listening daemon socket, removed again when dropped.
+pub struct Daemon {
+    listener: UnixListener,
+    path: PathBuf,
+    config_hash: Option<String>,
+    max_request_bytes: u64,
+}
+
+impl Daemon {
+    /// Listens on `path`, replacing a socket left behind by a daemon that
+    /// didn't shut down cleanly. Fails if a daemon is already listening.
+    pub fn bind(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(Error::daemon(format!("a daemon is already listening on {}", path.display())));
+            }
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+            config_hash: None,
+            max_request_bytes: max_request_bytes(output::DEFAULT_MAX_OUTPUT_BYTES),
+        })
+    }
+
+    /// Refuses requests from clients whose configuration doesn't hash to
+    /// `hash`.
+    pub fn with_config_hash(mut self, hash: impl Into<String>) -> Self {
+        self.config_hash = Some(hash.into());
+        self
+    }
+
+    /// Refuses request lines too long to hold an output of `bytes`, as
+    /// clients cap it before sending.
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_request_bytes = max_request_bytes(bytes);
+        self
+    }
+
+    /// Answers requests with `handler` on `threads` worker threads until
+    /// `shutdown` is set.
+    pub fn serve<F>(&self, threads: usize, shutdown: &AtomicBool, handler: F) -> Result<()>
+    where
+        F: Fn(&Request) -> Vec<CorrectedCommand> + Sync,
+    {
+        let (sender, receiver) = mpsc::channel::<UnixStream>();
+        let receiver = Mutex::new(receiver);
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| loop {
+                    // The lock is released before the connection is handled
+                    let next = receiver.lock().map(|receiver| receiver.recv());
+                    match next {
+                        Ok(Ok(stream)) => {
+                            handle_connection(stream, self.config_hash.as_deref(), self.max_request_bytes, &handler)
+                        }
+                        _ => break,
+                    }
+                });
+            }
+
+            let result = self.accept_until(shutdown, &sender);
+            // Workers finish their connections and stop
+            drop(sender);
+            result
+        })
+    }
+
+    fn accept_until(&self, shutdown: &AtomicBool, sender: &mpsc::Sender<UnixStream>) -> Result<()> {
+        while !shutdown.load(Ordering::SeqCst) {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+                    if sender.send(stream).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Gets the longest request line that holds an output of `max_output_bytes`.
+/// JSON escapes each byte of it to at most six.
+fn max_request_bytes(max_output_bytes: usize) -> u64 {
+    u64::try_from(max_output_bytes)
+        .unwrap_or(u64::MAX)
+        .saturating_mul(6)
+        .saturating_add(REQUEST_OVERHEAD_BYTES)
+}
+
+/// Answers every request line on `stream` until the client hangs up, or
+/// sends a line longer than `max_line_bytes`.
+fn handle_connection<F>(stream: UnixStream, config_hash: Option<&str>, max_line_bytes: u64, handler: &F)
+where
+    F: Fn(&Request) -> Vec<CorrectedCommand>,
+{
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match (&mut reader).take(max_line_bytes).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let too_long = !line.ends_with('\n') && line.len() as u64 >= max_line_bytes;
+        if line.trim().is_empty() && !too_long {
+            continue;
+        }
+        let response = if too_long {
+            Response::error(format!("request longer than {} bytes", max_line_bytes))
+        } else {
+            respond(&line, config_hash, handler)
+        };
+        let Ok(mut encoded) = serde_json::to_string(&response) else {
+            return;
+        };
+        encoded.push('\n');
+        if writer.write_all(encoded.as_bytes()).is_err() || too_long {
+            return;
+        }
+    }
+}
+
+/// Gets the response to one request line, from a daemon running with the
+/// configuration hashed as `config_hash`.
+fn respond<F>(line: &str, config_hash: Option<&str>, handler: &F) -> Response
+where
+    F: Fn(&Request) -> Vec<CorrectedCommand>,
+{
+    // Read the version first, so a newer client gets a useful error even if
+    // the rest of its request changed
+    let version = serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|value| value.get("version").and_then(serde_json::Value::as_u64));
+    if version != Some(u64::from(PROTOCOL_VERSION)) {
+        return Response::error(format!(
+            "unsupported protocol version {}, expected {}",
+            version.map_or_else(|| "none".to_string(), |version| version.to_string()),
+            PROTOCOL_VERSION
+        ));
+    }
+
+    match serde_json::from_str::<Request>(line) {
+        Ok(request) if request.config_hash.is_some() && request.config_hash.as_deref() != config_hash => {
+            Response::error("the daemon runs with another configuration; restart it to pick up changes")
+        }
+        Ok(request) => Response::corrections(handler(&request)),
+        Err(e) => Response::error(format!("invalid request: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(request: &Request) -> Vec<CorrectedCommand> {
+        vec![CorrectedCommand::new(format!("{} --fixed", request.script), 100)]
+    }
+
+    #[test]
+    fn test_socket_path_needs_absolute_runtime_dir() {
+        assert_eq!(
+            socket_path_in(Some(PathBuf::from("/run/user/1000"))),
+            Some(PathBuf::from("/run/user/1000/fasterthefuck/daemon.sock"))
+        );
+        assert_eq!(socket_path_in(Some(PathBuf::from("run"))), None);
+        assert_eq!(socket_path_in(None), None);
+    }
+
+    #[test]
+    fn test_respond_to_request() {
+        let request = Request::new("git pus", "", 1, PathBuf::from("/"));
+        let response = respond(&serde_json::to_string(&request).unwrap(), None, &echo);
+
+        assert_eq!(response.error, None);
+        assert_eq!(response.corrections[0].script, "git pus --fixed");
+    }
+
+    #[test]
+    fn test_respond_rejects_other_configs() {
+        let request = Request::new("git pus", "", 1, PathBuf::from("/")).with_config_hash("abc");
+        let line = serde_json::to_string(&request).unwrap();
+        assert_eq!(respond(&line, Some("abc"), &echo).error, None);
+
+        let response = respond(&line, Some("def"), &echo);
+        assert!(response.error.unwrap().starts_with("the daemon runs with another configuration"));
+        assert!(response.corrections.is_empty());
+
+        // Clients that don't send a hash are answered as before
+        let request = Request::new("git pus", "", 1, PathBuf::from("/"));
+        assert_eq!(respond(&serde_json::to_string(&request).unwrap(), Some("def"), &echo).error, None);
+    }
+
+    #[test]
+    fn test_respond_rejects_other_versions() {
+        let response = respond(r#"{"version":3,"query":"git pus"}"#, None, &echo);
+        assert_eq!(response.error.as_deref(), Some("unsupported protocol version 3, expected 2"));
+
+        let response = respond(r#"{"script":"git pus"}"#, None, &echo);
+        assert_eq!(response.error.as_deref(), Some("unsupported protocol version none, expected 2"));
+    }
+
+    #[test]
+    fn test_respond_to_invalid_request() {
+        let response = respond(r#"{"version":2,"script":"git pus"}"#, None, &echo);
+        assert!(response.error.unwrap().starts_with("invalid request: missing field"));
+    }
+
+    #[test]
+    fn test_connection_refuses_long_requests() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let request = serde_json::to_string(&Request::new("git pus", "", 1, PathBuf::from("/"))).unwrap();
+        let limit = request.len() as u64 + 1;
+        let handler = std::thread::spawn(move || handle_connection(server, None, limit, &echo));
+
+        let mut reader = BufReader::new(&client);
+        let mut reply = String::new();
+        (&client).write_all(format!("{}\n", request).as_bytes()).unwrap();
+        reader.read_line(&mut reply).unwrap();
+        assert!(reply.contains("git pus --fixed"), "{}", reply);
+
+        // The rest of a longer line isn't read, and the connection is closed
+        reply.clear();
+        (&client).write_all(format!("{} \n", request).as_bytes()).unwrap();
+        reader.read_line(&mut reply).unwrap();
+        let response: Response = serde_json::from_str(&reply).unwrap();
+        assert_eq!(response.error, Some(format!("request longer than {} bytes", limit)));
+        handler.join().unwrap();
+    }
+
+    #[test]
+    fn test_serve_and_query() {
+        let dir = std::env::temp_dir().join(format!("ftf_daemon_{}", std::process::id()));
+        let path = dir.join("daemon.sock");
+        let daemon = Daemon::bind(&path).unwrap();
+        assert!(Daemon::bind(&path).is_err());
+
+        let shutdown = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let server = scope.spawn(|| daemon.serve(2, &shutdown, echo));
+
+            let request = Request::new("make", "", 2, dir.clone());
+            let corrections = query(&path, &request).unwrap();
+            assert_eq!(corrections[0].script, "make --fixed");
+
+            shutdown.store(true, Ordering::SeqCst);
+            server.join().unwrap().unwrap();
+        });
+
+        drop(daemon);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket() {
+        let dir = std::env::temp_dir().join(format!("ftf_daemon_stale_{}", std::process::id()));
+        let path = dir.join("daemon.sock");
+        std::fs::create_dir_all(&dir).unwrap();
+        // A socket nothing listens on any more
+        drop(UnixListener::bind(&path).unwrap());
+
+        assert!(Daemon::bind(&path).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
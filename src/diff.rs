@@ -0,0 +1,209 @@
+//! Word diffs between a failed command and its correction.
+//!
+//! Commands are split on spaces and compared token by token, keeping the
+//! longest common subsequence. The selector highlights the tokens a
+//! correction added, and `--diff` shows only the [`Region`] that changed,
+//! with a few tokens around it, which is easier to read than the whole of a
+//! long command.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What happened to a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    /// In both commands
+    Equal,
+    /// Only in the correction
+    Insert,
+    /// Only in the failed command
+    Delete,
+}
+
+/// A token of either command, and what happened to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub op: Op,
+    pub token: String,
+}
+
+impl Edit {
+    fn new(op: Op, token: &str) -> Self {
+        Self {
+            op,
+            token: token.to_string(),
+        }
+    }
+}
+
+/// Diffs the tokens of `old` and `new`. Where a token was replaced, its
+/// deletion comes before the insertion.
+pub fn diff(old: &str, new: &str) -> Vec<Edit> {
+    diff_tokens(&old.split(' ').collect::<Vec<_>>(), &new.split(' ').collect::<Vec<_>>())
+}
+
+/// Diffs two lists of tokens.
+pub fn diff_tokens(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // lengths[i][j] is the LCS length of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            edits.push(Edit::new(Op::Equal, new[j]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            edits.push(Edit::new(Op::Delete, old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::new(Op::Insert, new[j]));
+            j += 1;
+        }
+    }
+    edits.extend(old[i..].iter().map(|token| Edit::new(Op::Delete, token)));
+    edits.extend(new[j..].iter().map(|token| Edit::new(Op::Insert, token)));
+    edits
+}
+
+/// The part of a diff from its first change to its last, with unchanged
+/// tokens around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Whether tokens were left out before the region
+    pub before: bool,
+    /// The failed command's tokens, each marked if it was deleted
+    pub old: Vec<(String, bool)>,
+    /// The correction's tokens, each marked if it was inserted
+    pub new: Vec<(String, bool)>,
+    /// Whether tokens were left out after the region
+    pub after: bool,
+}
+
+impl Region {
+    /// Narrows `edits` to the changes with `context` unchanged tokens on
+    /// either side, or `None` if nothing changed.
+    pub fn new(edits: &[Edit], context: usize) -> Option<Self> {
+        let first = edits.iter().position(|edit| edit.op != Op::Equal)?;
+        let last = edits.iter().rposition(|edit| edit.op != Op::Equal)?;
+        let start = first.saturating_sub(context);
+        let end = (last + 1 + context).min(edits.len());
+        let window = &edits[start..end];
+        let side = |skipped: Op, changed: Op| {
+            window
+                .iter()
+                .filter(|edit| edit.op != skipped)
+                .map(|edit| (edit.token.clone(), edit.op == changed))
+                .collect()
+        };
+        Some(Self {
+            before: start > 0,
+            old: side(Op::Insert, Op::Delete),
+            new: side(Op::Delete, Op::Insert),
+            after: end < edits.len(),
+        })
+    }
+}
+
+impl Region {
+    /// Shows the region as `… old → new …`, leaving out a side with no
+    /// tokens, with the deleted tokens passed through `deleted` and the
+    /// inserted ones through `inserted`.
+    pub fn show(&self, deleted: impl Fn(&str) -> String, inserted: impl Fn(&str) -> String) -> String {
+        let side = |tokens: &[(String, bool)], paint: &dyn Fn(&str) -> String| {
+            tokens
+                .iter()
+                .map(|(token, changed)| if *changed && !token.is_empty() { paint(token) } else { token.clone() })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let parts = [
+            self.before.then(|| "…".to_string()),
+            Some(side(&self.old, &deleted)).filter(|_| !self.old.is_empty()),
+            Some("→".to_string()),
+            Some(side(&self.new, &inserted)).filter(|_| !self.new.is_empty()),
+            self.after.then(|| "…".to_string()),
+        ];
+        parts.into_iter().flatten().collect::<Vec<_>>().join(" ")
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.show(str::to_string, str::to_string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCKER: &str = "docker run -d --name web --network hsot -p 8080:80 nginx";
+
+    fn region(old: &str, new: &str, context: usize) -> String {
+        Region::new(&diff(old, new), context).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_diff_ops() {
+        let ops = |old, new| diff(old, new).into_iter().map(|edit| (edit.op, edit.token)).collect::<Vec<_>>();
+        assert_eq!(
+            ops("git psuh", "git push"),
+            [(Op::Equal, "git".into()), (Op::Delete, "psuh".into()), (Op::Insert, "push".into())]
+        );
+        assert_eq!(ops("ls", "sudo ls"), [(Op::Insert, "sudo".into()), (Op::Equal, "ls".into())]);
+        assert_eq!(
+            ops("rm -rf /tmp/x", "rm /tmp/x"),
+            [(Op::Equal, "rm".into()), (Op::Delete, "-rf".into()), (Op::Equal, "/tmp/x".into())]
+        );
+        assert!(Region::new(&diff("ls", "ls"), 1).is_none());
+    }
+
+    #[test]
+    fn test_replacements() {
+        assert_eq!(
+            region(DOCKER, &DOCKER.replace("hsot", "host"), 1),
+            "… --network hsot -p → --network host -p …"
+        );
+        assert_eq!(region("gti status", "git status", 1), "gti status → git status");
+        assert_eq!(region("git comit -m fix", "git commit -m fix", 0), "… comit → commit …");
+        assert_eq!(region("cd /tmp/foo", "cd /tmp/bar", 1), "cd /tmp/foo → cd /tmp/bar");
+    }
+
+    #[test]
+    fn test_insertions() {
+        assert_eq!(region("apt install vim", "sudo apt install vim", 1), "apt → sudo apt …");
+        assert_eq!(region(DOCKER, &DOCKER.replace(" -p", " --rm -p"), 1), "… hsot -p → hsot --rm -p …");
+        assert_eq!(region("git push", "git push --force", 1), "… push → push --force");
+        // Without context an insertion has nothing on the left
+        assert_eq!(region("git push", "git push --force", 0), "… → --force");
+    }
+
+    #[test]
+    fn test_deletions() {
+        assert_eq!(region("vim vim notes.txt", "vim notes.txt", 0), "… vim → …");
+        assert_eq!(region("cd ls -la", "ls -la", 1), "cd ls → ls …");
+        assert_eq!(region(DOCKER, &DOCKER.replace(" -d", ""), 1), "… run -d --name → run --name …");
+        assert_eq!(region("git status -", "git status", 1), "… status - → status");
+    }
+
+    #[test]
+    fn test_region_spans_every_change() {
+        let new = DOCKER.replace("docker", "podman").replace("nginx", "nginx:latest");
+        assert_eq!(region(DOCKER, &new, 2), format!("{} → {}", DOCKER, new));
+        let region = Region::new(&diff("git psuh origin", "git push origin"), 1).unwrap();
+        assert_eq!(region.old, [("git".to_string(), false), ("psuh".to_string(), true), ("origin".to_string(), false)]);
+        assert_eq!(region.new, [("git".to_string(), false), ("push".to_string(), true), ("origin".to_string(), false)]);
+    }
+}
@@ -0,0 +1,388 @@
+//! Side effects that run before a correction.
+//!
+//! Some corrections only work once something outside the command changes:
+//! a stale known_hosts entry is removed, a missing directory is created.
+//! Rather than chaining shell commands in front of the correction, rules
+//! attach a [`SideEffect`], which ftf runs itself right before the corrected
+//! command, once the user confirmed it. A failed effect is reported on its
+//! own and the correction is not run.
+//!
+//! [`plan`] previews what a filesystem correction will change without running
+//! it, and [`notify`] tells the user when a long correction has finished.
+
+pub mod notify;
+mod preview;
+
+pub use preview::{plan, FsChange};
+
+use crate::{Error, Result, Shell};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A named action ftf performs before running a correction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SideEffect {
+    /// Removes `host` from a known_hosts file, together with the 1-based
+    /// `line` ssh reported as offending. Hashed entries can only be removed
+    /// by line.
+    RemoveKnownHost {
+        known_hosts: PathBuf,
+        host: String,
+        line: Option<usize>,
+    },
+    /// Creates the missing parent directories of a path.
+    CreateParentDirs(PathBuf),
+    /// Copies a file to `<path>.bak`.
+    BackupFile(PathBuf),
+    /// Runs a command through the shell.
+    ShellCommand(String),
+    /// Clears the user's shell command cache (`hash -r`), so a binary
+    /// installed by the correction is found. Only the shell hook can do this.
+    Rehash,
+}
+
+impl SideEffect {
+    /// Gets the registry name of the effect.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SideEffect::RemoveKnownHost { .. } => "remove_known_host",
+            SideEffect::CreateParentDirs(_) => "create_parent_dirs",
+            SideEffect::BackupFile(_) => "backup_file",
+            SideEffect::ShellCommand(_) => "shell_command",
+            SideEffect::Rehash => "rehash",
+        }
+    }
+
+    /// Returns true if the effect has to run in the user's shell, so it is
+    /// passed to the shell hook instead of being run by ftf.
+    pub fn runs_in_hook(&self) -> bool {
+        matches!(self, SideEffect::Rehash)
+    }
+
+    /// Runs the effect. Relative paths are resolved against the shell's
+    /// working directory. Effects that run in the hook do nothing here.
+    pub fn run(&self, shell: &dyn Shell) -> Result<()> {
+        match self {
+            SideEffect::RemoveKnownHost {
+                known_hosts,
+                host,
+                line,
+            } => {
+                let known_hosts = resolve(shell, known_hosts)?;
+                let home = shell.env("HOME").map(PathBuf::from);
+                if !is_known_hosts(&known_hosts, home.as_deref()) {
+                    return Err(Error::side_effect(format!("{} isn't a known_hosts file", known_hosts.display())));
+                }
+                remove_known_host(&known_hosts, host, *line)
+            }
+            SideEffect::CreateParentDirs(path) => create_parent_dirs(&resolve(shell, path)?),
+            SideEffect::BackupFile(path) => backup_file(&resolve(shell, path)?),
+            SideEffect::ShellCommand(command) => {
+                let output = shell.execute(command)?;
+                if output.success {
+                    Ok(())
+                } else {
+                    Err(Error::side_effect(format!(
+                        "'{}' exited with {}",
+                        command, output.exit_code
+                    )))
+                }
+            }
+            SideEffect::Rehash => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for SideEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SideEffect::RemoveKnownHost {
+                known_hosts, host, ..
+            } => write!(f, "remove {} from {}", host, known_hosts.display()),
+            SideEffect::CreateParentDirs(path) => match path.parent() {
+                Some(parent) => write!(f, "create {}", parent.display()),
+                None => write!(f, "create the parents of {}", path.display()),
+            },
+            SideEffect::BackupFile(path) => write!(f, "back up {}", path.display()),
+            SideEffect::ShellCommand(command) => write!(f, "run '{}'", command),
+            SideEffect::Rehash => f.write_str("refresh the shell's command cache"),
+        }
+    }
+}
+
+/// Joins a relative `path` onto the shell's working directory.
+fn resolve(shell: &dyn Shell, path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(shell.cwd()?.join(path))
+    }
+}
+
+/// Checks that `path` looks like a known_hosts file: named `known_hosts*`,
+/// in a `.ssh` directory or `/etc/ssh`. Paths come from a command's output,
+/// which a file the user printed could have filled in.
+pub(crate) fn is_known_hosts_path(path: &Path) -> bool {
+    let named = path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("known_hosts"));
+    let dir = path.parent().unwrap_or(Path::new(""));
+    named
+        && path.is_absolute()
+        && !path.components().any(|component| component == Component::ParentDir)
+        && (dir == Path::new("/etc/ssh") || dir.file_name().is_some_and(|name| name == ".ssh"))
+}
+
+/// Checks that `path` is a known_hosts file of the user with `home`, or of
+/// the system, so that removing a host can't rewrite any other file.
+fn is_known_hosts(path: &Path, home: Option<&Path>) -> bool {
+    let dir = path.parent();
+    is_known_hosts_path(path)
+        && (dir == Some(Path::new("/etc/ssh")) || home.is_some_and(|home| dir == Some(home.join(".ssh").as_path())))
+}
+
+/// Rewrites `known_hosts` without the entries for `host` and without the
+/// 1-based `offending` line.
+fn remove_known_host(known_hosts: &Path, host: &str, offending: Option<usize>) -> Result<()> {
+    let contents = fs::read_to_string(known_hosts)?;
+    let kept: Vec<&str> = contents
+        .lines()
+        .enumerate()
+        .filter(|(index, entry)| {
+            let hosts = entry.split_whitespace().next().unwrap_or("");
+            Some(index + 1) != offending && !hosts.split(',').any(|entry_host| entry_host == host)
+        })
+        .map(|(_, entry)| entry)
+        .collect();
+
+    if kept.len() == contents.lines().count() {
+        return Err(Error::side_effect(format!(
+            "no entry for {} in {}",
+            host,
+            known_hosts.display()
+        )));
+    }
+
+    let mut rewritten = kept.join("\n");
+    if !kept.is_empty() {
+        rewritten.push('\n');
+    }
+    replace_file(known_hosts, &rewritten)
+}
+
+/// Replaces the contents of `path` atomically, keeping its permissions: a
+/// crash or a full disk never leaves it half written.
+fn replace_file(path: &Path, contents: &str) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".ftf-tmp");
+    let temporary = PathBuf::from(temporary);
+
+    let written = fs::write(&temporary, contents)
+        .and_then(|()| fs::set_permissions(&temporary, fs::metadata(path)?.permissions()))
+        .and_then(|()| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    Ok(written?)
+}
+
+/// Creates every missing directory above `path`.
+fn create_parent_dirs(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(parent) => Ok(fs::create_dir_all(parent)?),
+        None => Ok(()),
+    }
+}
+
+/// Copies `path` to `<path>.bak`, replacing an older backup.
+fn backup_file(path: &Path) -> Result<()> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    fs::copy(path, backup)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const KNOWN_HOSTS: &str = "github.com ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl\nbuild.example.com,10.0.4.12 ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBHkP\n|1|JfKTdBh7rNbXkVAQCRp4OQoPfmI=|USECr3SWf1JUPsms5AqfD5QfxkM= ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC7\n";
+
+    /// Writes `KNOWN_HOSTS` to `home`'s `.ssh/known_hosts`.
+    fn known_hosts_in(home: &Path) -> PathBuf {
+        fs::create_dir_all(home.join(".ssh")).unwrap();
+        let known_hosts = home.join(".ssh/known_hosts");
+        fs::write(&known_hosts, KNOWN_HOSTS).unwrap();
+        known_hosts
+    }
+
+    fn home(dir: &Path) -> MockShell {
+        MockShell::new().with_env("HOME", dir.to_str().unwrap())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_effects_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_remove_known_host_by_name() {
+        let dir = temp_dir("known_host_name");
+        let known_hosts = known_hosts_in(&dir);
+
+        let effect = SideEffect::RemoveKnownHost {
+            known_hosts: known_hosts.clone(),
+            host: "10.0.4.12".to_string(),
+            line: None,
+        };
+        effect.run(&home(&dir)).unwrap();
+
+        let remaining = fs::read_to_string(&known_hosts).unwrap();
+        assert_eq!(remaining.lines().count(), 2);
+        assert!(!remaining.contains("build.example.com"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_hashed_known_host_by_line() {
+        let dir = temp_dir("known_host_line");
+        let known_hosts = known_hosts_in(&dir);
+
+        let effect = SideEffect::RemoveKnownHost {
+            known_hosts: known_hosts.clone(),
+            host: "legacy.example.com".to_string(),
+            line: Some(3),
+        };
+        effect.run(&home(&dir)).unwrap();
+
+        let remaining = fs::read_to_string(&known_hosts).unwrap();
+        assert!(remaining.starts_with("github.com "));
+        assert!(!remaining.contains("|1|"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_known_host_replaces_the_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("known_host_replace");
+        let known_hosts = known_hosts_in(&dir);
+        fs::set_permissions(&known_hosts, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let effect = SideEffect::RemoveKnownHost {
+            known_hosts: known_hosts.clone(),
+            host: "github.com".to_string(),
+            line: None,
+        };
+        effect.run(&home(&dir)).unwrap();
+
+        let mode = fs::metadata(&known_hosts).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_dir(dir.join(".ssh")).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_known_host_refuses_other_files() {
+        let dir = temp_dir("known_host_other");
+        let known_hosts = known_hosts_in(&dir);
+        let bashrc = dir.join(".bashrc");
+        fs::write(&bashrc, KNOWN_HOSTS).unwrap();
+
+        for path in [bashrc.clone(), dir.join(".ssh/../.bashrc"), known_hosts.clone()] {
+            let effect = SideEffect::RemoveKnownHost {
+                known_hosts: path,
+                host: "github.com".to_string(),
+                line: Some(1),
+            };
+            // The last is a known_hosts file, but not under this user's home
+            assert!(matches!(effect.run(&home(Path::new("/home/someone"))), Err(Error::SideEffect(_))));
+        }
+        assert_eq!(fs::read_to_string(&bashrc).unwrap(), KNOWN_HOSTS);
+        assert_eq!(fs::read_to_string(&known_hosts).unwrap(), KNOWN_HOSTS);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_known_host_without_entry_fails() {
+        let dir = temp_dir("known_host_missing");
+        let known_hosts = known_hosts_in(&dir);
+
+        let effect = SideEffect::RemoveKnownHost {
+            known_hosts: known_hosts.clone(),
+            host: "gitlab.com".to_string(),
+            line: None,
+        };
+        assert!(matches!(effect.run(&home(&dir)), Err(Error::SideEffect(_))));
+        assert_eq!(fs::read_to_string(&known_hosts).unwrap(), KNOWN_HOSTS);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_create_parent_dirs_relative_to_cwd() {
+        let dir = temp_dir("parent_dirs");
+        let shell = MockShell::new().with_cwd(&dir);
+
+        SideEffect::CreateParentDirs(PathBuf::from("backup/2024/notes.txt"))
+            .run(&shell)
+            .unwrap();
+
+        assert!(dir.join("backup/2024").is_dir());
+        assert!(!dir.join("backup/2024/notes.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_file() {
+        let dir = temp_dir("backup");
+        fs::write(dir.join("nginx.conf"), "worker_processes 4;\n").unwrap();
+
+        SideEffect::BackupFile(dir.join("nginx.conf"))
+            .run(&MockShell::new())
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("nginx.conf.bak")).unwrap(),
+            "worker_processes 4;\n"
+        );
+        assert!(SideEffect::BackupFile(dir.join("missing.conf"))
+            .run(&MockShell::new())
+            .is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shell_command_failure() {
+        let shell = MockShell::new()
+            .with_output("docker network create ci", "", 0)
+            .with_output("docker network create prod", "", 1);
+
+        assert!(SideEffect::ShellCommand("docker network create ci".to_string())
+            .run(&shell)
+            .is_ok());
+        let err = SideEffect::ShellCommand("docker network create prod".to_string())
+            .run(&shell)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Side effect error: 'docker network create prod' exited with 1"
+        );
+    }
+
+    #[test]
+    fn test_side_effect_names() {
+        assert_eq!(SideEffect::CreateParentDirs(PathBuf::from("a/b")).name(), "create_parent_dirs");
+        assert_eq!(SideEffect::CreateParentDirs(PathBuf::from("a/b")).to_string(), "create a");
+    }
+}
@@ -0,0 +1,261 @@
+//! Desktop notifications for long-running corrections.
+//!
+//! When an executed correction takes a while the user has often switched to
+//! another window, so ftf tells them when it is done. The notification is
+//! sent through whatever the platform provides: notify-send on Linux and the
+//! BSDs, osascript on macOS, a PowerShell toast on Windows.
+
+use crate::{Error, Result};
+use std::process::{Command as StdCommand, Stdio};
+use std::time::{Duration, Instant};
+
+/// Something that can show a notification to the user.
+pub trait Notifier: Send + Sync {
+    /// Shows a notification with `title` and `body`.
+    fn notify(&self, title: &str, body: &str) -> Result<()>;
+}
+
+/// A source of the current time, so tests can control elapsed time.
+pub trait Clock: Send + Sync {
+    /// Gets the current instant.
+    fn now(&self) -> Instant;
+}
+
+/// The real clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Builds the command that shows a notification on one platform.
+type Backend = fn(&str, &str) -> Vec<String>;
+
+/// Notification commands by `std::env::consts::OS`.
+const BACKENDS: &[(&str, Backend)] = &[
+    ("linux", notify_send),
+    ("freebsd", notify_send),
+    ("openbsd", notify_send),
+    ("netbsd", notify_send),
+    ("macos", osascript),
+    ("windows", powershell_toast),
+];
+
+fn notify_send(title: &str, body: &str) -> Vec<String> {
+    vec!["notify-send".into(), "--app-name=ftf".into(), title.into(), body.into()]
+}
+
+fn osascript(title: &str, body: &str) -> Vec<String> {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    );
+    vec!["osascript".into(), "-e".into(), script]
+}
+
+fn powershell_toast(title: &str, body: &str) -> Vec<String> {
+    let script = format!(
+        "$template = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+         $text = $template.GetElementsByTagName('text'); \
+         $text.Item(0).AppendChild($template.CreateTextNode({})) > $null; \
+         $text.Item(1).AppendChild($template.CreateTextNode({})) > $null; \
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('ftf').Show([Windows.UI.Notifications.ToastNotification]::new($template))",
+        powershell_string(title),
+        powershell_string(body)
+    );
+    vec!["powershell".into(), "-NoProfile".into(), "-Command".into(), script]
+}
+
+/// Quotes `text` as an AppleScript string literal.
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quotes `text` as a single-quoted PowerShell string literal.
+fn powershell_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Gets the command that shows a notification on `os`, if it is supported.
+pub fn notification_command(os: &str, title: &str, body: &str) -> Option<Vec<String>> {
+    BACKENDS
+        .iter()
+        .find(|(name, _)| *name == os)
+        .map(|(_, backend)| backend(title, body))
+}
+
+/// Shows notifications with the current platform's notification command.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, title: &str, body: &str) -> Result<()> {
+        let os = std::env::consts::OS;
+        let command = notification_command(os, title, body)
+            .ok_or_else(|| Error::side_effect(format!("no notification command for {}", os)))?;
+
+        let status = StdCommand::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::side_effect(format!("{} exited with {}", command[0], status)))
+        }
+    }
+}
+
+/// Runs corrections and notifies the user about those that took at least a
+/// threshold.
+pub struct LongRunNotifier {
+    notifier: Box<dyn Notifier>,
+    clock: Box<dyn Clock>,
+    threshold: Duration,
+}
+
+impl LongRunNotifier {
+    /// Creates a notifier that reports through `notifier` and measures time
+    /// with `clock`.
+    pub fn new(notifier: Box<dyn Notifier>, clock: Box<dyn Clock>, threshold: Duration) -> Self {
+        Self {
+            notifier,
+            clock,
+            threshold,
+        }
+    }
+
+    /// Creates a notifier that shows desktop notifications.
+    pub fn desktop(threshold: Duration) -> Self {
+        Self::new(Box::new(DesktopNotifier), Box::new(SystemClock), threshold)
+    }
+
+    /// Runs `run`, which executes `script`, and notifies with the exit code
+    /// `exit_code` gets from its result if it took at least the threshold.
+    ///
+    /// Notification failures are returned next to the result, since the
+    /// correction itself has already run.
+    pub fn run<T>(
+        &self,
+        script: &str,
+        run: impl FnOnce() -> T,
+        exit_code: impl FnOnce(&T) -> i32,
+    ) -> (T, Result<bool>) {
+        let start = self.clock.now();
+        let result = run();
+        let elapsed = self.clock.now().saturating_duration_since(start);
+
+        if elapsed < self.threshold {
+            return (result, Ok(false));
+        }
+        let body = format!("correction finished: exit {}", exit_code(&result));
+        let notified = self.notifier.notify(script, &body).map(|()| true);
+        (result, notified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records notifications instead of showing them.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, title: &str, body: &str) -> Result<()> {
+            self.0.lock().unwrap().push((title.to_string(), body.to_string()));
+            Ok(())
+        }
+    }
+
+    /// A clock that moves forward by a fixed step every time it is read.
+    struct FakeClock {
+        start: Instant,
+        step: Duration,
+        reads: Mutex<u32>,
+    }
+
+    impl FakeClock {
+        fn stepping(step: Duration) -> Self {
+            Self {
+                start: Instant::now(),
+                step,
+                reads: Mutex::new(0),
+            }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            let mut reads = self.reads.lock().unwrap();
+            *reads += 1;
+            self.start + self.step * *reads
+        }
+    }
+
+    fn notifier(recorder: &Recorder, elapsed: Duration) -> LongRunNotifier {
+        let clock = FakeClock::stepping(elapsed);
+        LongRunNotifier::new(Box::new(recorder.clone()), Box::new(clock), Duration::from_secs(30))
+    }
+
+    #[test]
+    fn test_notifies_after_threshold() {
+        let recorder = Recorder::default();
+
+        let notifier = notifier(&recorder, Duration::from_secs(45));
+        let (exit_code, notified) = notifier.run("cargo build --release", || 0, |code| *code);
+
+        assert_eq!(exit_code, 0);
+        assert!(notified.unwrap());
+        assert_eq!(
+            recorder.0.lock().unwrap().as_slice(),
+            &[("cargo build --release".to_string(), "correction finished: exit 0".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_quick_corrections_are_not_notified() {
+        let recorder = Recorder::default();
+
+        let (exit_code, notified) = notifier(&recorder, Duration::from_secs(29)).run("git push", || 1, |code| *code);
+
+        assert_eq!(exit_code, 1);
+        assert!(!notified.unwrap());
+        assert!(recorder.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_notifies_exactly_at_threshold() {
+        let recorder = Recorder::default();
+
+        let (_, notified) = notifier(&recorder, Duration::from_secs(30)).run("make test", || 2, |code| *code);
+
+        assert!(notified.unwrap());
+        assert_eq!(recorder.0.lock().unwrap()[0].1, "correction finished: exit 2");
+    }
+
+    #[test]
+    fn test_notification_commands() {
+        assert_eq!(
+            notification_command("linux", "make", "correction finished: exit 0").unwrap(),
+            vec!["notify-send", "--app-name=ftf", "make", "correction finished: exit 0"]
+        );
+
+        let macos = notification_command("macos", "echo \"hi\"", "done").unwrap();
+        assert_eq!(macos[2], r#"display notification "done" with title "echo \"hi\"""#);
+
+        let windows = notification_command("windows", "it's", "done").unwrap();
+        assert_eq!(windows[0], "powershell");
+        assert!(windows[3].contains("'it''s'"));
+
+        assert!(notification_command("haiku", "make", "done").is_none());
+    }
+}
@@ -0,0 +1,425 @@
+//! Read-only preview of the filesystem changes a correction would make.
+//!
+//! The common coreutils verbs (`mkdir`, `touch`, `mv`, `cp`, `rm`, `rmdir`)
+//! are understood well enough to say what they create, move, copy or delete;
+//! anything else is reported as opaque. Nothing is ever written: globs are
+//! expanded and deleted directories counted by reading the filesystem only.
+
+use crate::history::glob_match;
+use crate::privilege;
+use crate::Script;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A change a correction is expected to make to the filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsChange {
+    /// A file or directory that will be created
+    Create(PathBuf),
+    /// A path that will be moved or renamed
+    Move { from: PathBuf, to: PathBuf },
+    /// A path that will be copied
+    Copy { from: PathBuf, to: PathBuf },
+    /// Existing paths that will be deleted, with the number of files in them
+    Delete { paths: Vec<PathBuf>, files: usize },
+    /// A command whose effect on the filesystem is unknown
+    Opaque(String),
+}
+
+impl fmt::Display for FsChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsChange::Create(path) => write!(f, "create {}", path.display()),
+            FsChange::Move { from, to } => write!(f, "move {} to {}", from.display(), to.display()),
+            FsChange::Copy { from, to } => write!(f, "copy {} to {}", from.display(), to.display()),
+            FsChange::Delete { paths, files } => {
+                let plural = if *files == 1 { "" } else { "s" };
+                match paths.as_slice() {
+                    [path] => write!(f, "delete {} ({} file{})", path.display(), files, plural),
+                    _ => write!(f, "delete {} paths ({} file{})", paths.len(), files, plural),
+                }
+            }
+            FsChange::Opaque(command) => write!(f, "run '{}' (changes unknown)", command),
+        }
+    }
+}
+
+/// Plans the filesystem changes of `script` without running it.
+///
+/// Relative paths are resolved against `cwd`, which follows any `cd` in the
+/// chain. Directories created by earlier commands count as existing for
+/// later ones.
+pub fn plan(script: &str, cwd: &Path) -> Vec<FsChange> {
+    let mut planner = Planner {
+        cwd: cwd.to_path_buf(),
+        planned_dirs: HashSet::new(),
+        changes: Vec::new(),
+    };
+    for segment in Script::split_chain(script) {
+        planner.plan_segment(segment);
+    }
+    planner.changes
+}
+
+/// State carried from one command of a chain to the next.
+struct Planner {
+    cwd: PathBuf,
+    planned_dirs: HashSet<PathBuf>,
+    changes: Vec<FsChange>,
+}
+
+impl Planner {
+    fn plan_segment(&mut self, segment: &str) {
+        let script = Script::parse(segment);
+        let mut tokens = script.tokens();
+        if tokens.first().is_some_and(|token| privilege::is_escalation(token)) {
+            tokens = &tokens[1..];
+        }
+
+        // Pipes and redirections can write anywhere
+        let Some((verb, args)) = tokens.split_first() else {
+            return;
+        };
+        if tokens.iter().any(|token| Script::is_operator(token)) {
+            self.changes.push(FsChange::Opaque(segment.to_string()));
+            return;
+        }
+
+        let (flags, operands) = split_args(args);
+        let paths: Vec<PathBuf> = operands.iter().map(|operand| self.resolve(operand)).collect();
+
+        match (verb.as_str(), paths.as_slice()) {
+            ("cd", [dir]) => self.cwd = dir.clone(),
+            ("mkdir", [_, ..]) => self.plan_mkdir(&paths, has_flag(&flags, 'p', "--parents")),
+            ("touch", [_, ..]) => {
+                let missing = paths.into_iter().filter(|path| !path.exists());
+                self.changes.extend(missing.map(FsChange::Create));
+            }
+            ("mv" | "cp", [_, _, ..]) => self.plan_transfer(verb == "mv", &operands, &paths),
+            ("rm", [_, ..]) => {
+                let recursive = has_flag(&flags, 'r', "--recursive") || has_flag(&flags, 'R', "--recursive");
+                self.plan_delete(&paths, recursive);
+            }
+            ("rmdir", [_, ..]) => self.plan_delete(&paths, false),
+            _ => self.changes.push(FsChange::Opaque(segment.to_string())),
+        }
+    }
+
+    fn plan_mkdir(&mut self, paths: &[PathBuf], parents: bool) {
+        for path in paths {
+            let mut missing: Vec<&Path> = if parents {
+                path.ancestors().take_while(|dir| !self.is_dir(dir)).collect()
+            } else if self.is_dir(path) {
+                Vec::new()
+            } else {
+                vec![path.as_path()]
+            };
+            missing.reverse();
+
+            for dir in missing {
+                self.planned_dirs.insert(dir.to_path_buf());
+                self.changes.push(FsChange::Create(dir.to_path_buf()));
+            }
+        }
+    }
+
+    fn plan_transfer(&mut self, is_move: bool, operands: &[&str], paths: &[PathBuf]) {
+        let Some((destination, sources)) = paths.split_last() else {
+            return;
+        };
+        let sources: Vec<PathBuf> = sources.iter().flat_map(|source| expand(source)).collect();
+        let into_dir = sources.len() > 1
+            || self.is_dir(destination)
+            || operands.last().is_some_and(|operand| operand.ends_with('/'));
+
+        for from in sources {
+            let to = match from.file_name() {
+                Some(name) if into_dir => destination.join(name),
+                _ => destination.clone(),
+            };
+            self.changes.push(if is_move {
+                FsChange::Move { from, to }
+            } else {
+                FsChange::Copy { from, to }
+            });
+        }
+    }
+
+    fn plan_delete(&mut self, paths: &[PathBuf], recursive: bool) {
+        let paths: Vec<PathBuf> = paths
+            .iter()
+            .flat_map(|path| expand(path))
+            .filter(|path| path.symlink_metadata().is_ok())
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+
+        let files = paths.iter().map(|path| count_files(path, recursive)).sum();
+        self.changes.push(FsChange::Delete { paths, files });
+    }
+
+    /// Resolves an operand against the current directory and `~`.
+    fn resolve(&self, operand: &str) -> PathBuf {
+        match operand.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => match dirs::home_dir() {
+                Some(home) => home.join(rest.trim_start_matches('/')),
+                None => self.cwd.join(operand),
+            },
+            _ => self.cwd.join(operand),
+        }
+    }
+
+    /// Returns true if `path` is a directory, or will be one by this point
+    /// in the chain.
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir() || self.planned_dirs.contains(path)
+    }
+}
+
+/// Splits arguments into flags and operands. Everything after `--` is an
+/// operand.
+fn split_args(args: &[String]) -> (Vec<&str>, Vec<&str>) {
+    let mut flags = Vec::new();
+    let mut operands = Vec::new();
+    let mut options_done = false;
+
+    for arg in args {
+        if options_done || arg == "-" || !arg.starts_with('-') {
+            operands.push(arg.as_str());
+        } else if arg == "--" {
+            options_done = true;
+        } else {
+            flags.push(arg.as_str());
+        }
+    }
+    (flags, operands)
+}
+
+/// Returns true if `flags` contain the short flag (possibly combined, as in
+/// `-rf`) or the long one.
+fn has_flag(flags: &[&str], short: char, long: &str) -> bool {
+    flags.iter().any(|flag| {
+        *flag == long || (!flag.starts_with("--") && flag[1..].contains(short))
+    })
+}
+
+/// Expands `*` and `?` in the last component of `path`, read-only. Paths
+/// without wildcards are returned as they are; hidden files only match
+/// patterns that start with a dot.
+fn expand(path: &Path) -> Vec<PathBuf> {
+    let Some(pattern) = path.file_name().and_then(|name| name.to_str()) else {
+        return vec![path.to_path_buf()];
+    };
+    if !pattern.contains(['*', '?']) {
+        return vec![path.to_path_buf()];
+    }
+
+    let parent = path.parent().unwrap_or(Path::new("/"));
+    let Ok(entries) = fs::read_dir(parent) else {
+        return Vec::new();
+    };
+    let mut matches: Vec<PathBuf> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| pattern.starts_with('.') || !name.starts_with('.'))
+        .filter(|name| glob_match(pattern, name))
+        .map(|name| parent.join(name))
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Counts the files removed by deleting `path`. Directories only count
+/// their contents when deleted recursively, and symlinks are not followed.
+fn count_files(path: &Path, recursive: bool) -> usize {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return 1;
+    }
+    if !recursive {
+        return 0;
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| count_files(&entry.path(), true))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_fs_plan_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(dir: &Path, files: &[&str]) {
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_plan_mkdir_parents() {
+        let dir = temp_dir("mkdir");
+        fs::create_dir(dir.join("backup")).unwrap();
+
+        assert_eq!(
+            plan("mkdir -p backup/2024/q1", &dir),
+            vec![
+                FsChange::Create(dir.join("backup/2024")),
+                FsChange::Create(dir.join("backup/2024/q1")),
+            ]
+        );
+        assert!(plan("mkdir backup", &dir).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_touch_only_missing_files() {
+        let dir = temp_dir("touch");
+        touch(&dir, &["README.md"]);
+
+        assert_eq!(
+            plan("touch README.md CHANGELOG.md", &dir),
+            vec![FsChange::Create(dir.join("CHANGELOG.md"))]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_mv_into_planned_directory() {
+        let dir = temp_dir("mv");
+        touch(&dir, &["report.pdf"]);
+
+        assert_eq!(
+            plan("mkdir -p archive && mv report.pdf archive", &dir),
+            vec![
+                FsChange::Create(dir.join("archive")),
+                FsChange::Move {
+                    from: dir.join("report.pdf"),
+                    to: dir.join("archive/report.pdf"),
+                },
+            ]
+        );
+        assert_eq!(
+            plan("mv report.pdf summary.pdf", &dir),
+            vec![FsChange::Move {
+                from: dir.join("report.pdf"),
+                to: dir.join("summary.pdf"),
+            }]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_cp_glob_into_directory() {
+        let dir = temp_dir("cp");
+        touch(&dir, &["a.conf", "b.conf", "notes.txt", "backup/.keep"]);
+
+        assert_eq!(
+            plan("cp -r *.conf backup/", &dir),
+            vec![
+                FsChange::Copy {
+                    from: dir.join("a.conf"),
+                    to: dir.join("backup/a.conf"),
+                },
+                FsChange::Copy {
+                    from: dir.join("b.conf"),
+                    to: dir.join("backup/b.conf"),
+                },
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_rm_glob_counts_files() {
+        let dir = temp_dir("rm_glob");
+        touch(&dir, &["app.log", "worker.log", ".hidden.log", "app.conf"]);
+
+        let changes = plan("rm -f *.log", &dir);
+        assert_eq!(
+            changes,
+            vec![FsChange::Delete {
+                paths: vec![dir.join("app.log"), dir.join("worker.log")],
+                files: 2,
+            }]
+        );
+        assert_eq!(changes[0].to_string(), "delete 2 paths (2 files)");
+        // Nothing was deleted
+        assert!(dir.join("app.log").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_rm_recursive_counts_nested_files() {
+        let dir = temp_dir("rm_recursive");
+        touch(&dir, &["build/app", "build/obj/main.o", "build/obj/util.o"]);
+
+        assert_eq!(
+            plan("rm -rf build missing", &dir),
+            vec![FsChange::Delete {
+                paths: vec![dir.join("build")],
+                files: 3,
+            }]
+        );
+        assert_eq!(
+            plan("rmdir build", &dir),
+            vec![FsChange::Delete {
+                paths: vec![dir.join("build")],
+                files: 0,
+            }]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_follows_cd() {
+        let dir = temp_dir("cd");
+        fs::create_dir(dir.join("web")).unwrap();
+
+        assert_eq!(
+            plan("cd web && mkdir dist", &dir),
+            vec![FsChange::Create(dir.join("web/dist"))]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_unknown_verbs_are_opaque() {
+        let dir = temp_dir("opaque");
+
+        assert_eq!(
+            plan("npm install && mkdir out > log.txt", &dir),
+            vec![
+                FsChange::Opaque("npm install".to_string()),
+                FsChange::Opaque("mkdir out > log.txt".to_string()),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
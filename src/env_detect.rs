@@ -0,0 +1,123 @@
+//! Detection of environments where nobody can answer a prompt.
+//!
+//! CI jobs and scripts sometimes end up invoking the shell hook. Prompting
+//! there blocks forever, so ftf behaves as if `--no-interaction` was passed
+//! and never executes a correction without `--yes`.
+
+use std::io::IsTerminal;
+
+/// Environment variables set by common CI services.
+const CI_VARIABLES: &[&str] = &[
+    "CI",
+    "CONTINUOUS_INTEGRATION",
+    "BUILD_NUMBER",
+    "GITHUB_ACTIONS",
+    "GITLAB_CI",
+    "JENKINS_URL",
+    "TF_BUILD",
+    "BUILDKITE",
+    "CIRCLECI",
+    "TRAVIS",
+];
+
+/// Why an environment counts as non-interactive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonInteractive {
+    /// stdin or stderr isn't a terminal
+    NoTerminal,
+    /// A CI service's environment variable is set
+    Ci(&'static str),
+    /// `TERM=dumb`
+    DumbTerminal,
+}
+
+impl std::fmt::Display for NonInteractive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NonInteractive::NoTerminal => f.write_str("stdin or stderr is not a terminal"),
+            NonInteractive::Ci(variable) => write!(f, "{} is set", variable),
+            NonInteractive::DumbTerminal => f.write_str("TERM is dumb"),
+        }
+    }
+}
+
+/// Returns true if a user can answer prompts: stdin and stderr are
+/// terminals, no CI variable is set and `TERM` isn't `dumb`.
+pub fn is_interactive() -> bool {
+    non_interactive_reason().is_none()
+}
+
+/// Gets why the current process can't prompt, if it can't.
+pub fn non_interactive_reason() -> Option<NonInteractive> {
+    detect(
+        |key| std::env::var(key).ok(),
+        std::io::stdin().is_terminal(),
+        std::io::stderr().is_terminal(),
+    )
+}
+
+/// Gets why an environment looked up through `env`, with the given terminal
+/// status of stdin and stderr, can't prompt.
+///
+/// CI variables set to `false` or `0` don't count.
+pub fn detect(
+    env: impl Fn(&str) -> Option<String>,
+    stdin_is_terminal: bool,
+    stderr_is_terminal: bool,
+) -> Option<NonInteractive> {
+    if let Some(variable) = CI_VARIABLES.iter().find(|variable| {
+        env(variable).is_some_and(|value| !matches!(value.trim(), "" | "0" | "false" | "FALSE" | "False"))
+    }) {
+        return Some(NonInteractive::Ci(variable));
+    }
+    if env("TERM").as_deref() == Some("dumb") {
+        return Some(NonInteractive::DumbTerminal);
+    }
+    if !stdin_is_terminal || !stderr_is_terminal {
+        return Some(NonInteractive::NoTerminal);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn test_terminal_is_interactive() {
+        assert_eq!(detect(env(&[("TERM", "xterm-256color")]), true, true), None);
+    }
+
+    #[test]
+    fn test_ci_variables() {
+        assert_eq!(detect(env(&[("CI", "true")]), true, true), Some(NonInteractive::Ci("CI")));
+        assert_eq!(
+            detect(env(&[("GITHUB_ACTIONS", "true")]), true, true),
+            Some(NonInteractive::Ci("GITHUB_ACTIONS"))
+        );
+        assert_eq!(detect(env(&[("CI", "false")]), true, true), None);
+        assert_eq!(detect(env(&[("CI", "0")]), true, true), None);
+    }
+
+    #[test]
+    fn test_dumb_terminal() {
+        assert_eq!(detect(env(&[("TERM", "dumb")]), true, true), Some(NonInteractive::DumbTerminal));
+    }
+
+    #[test]
+    fn test_piped_stdio() {
+        assert_eq!(detect(env(&[]), false, true), Some(NonInteractive::NoTerminal));
+        assert_eq!(detect(env(&[]), true, false), Some(NonInteractive::NoTerminal));
+    }
+
+    #[test]
+    fn test_reason_display() {
+        assert_eq!(NonInteractive::Ci("GITLAB_CI").to_string(), "GITLAB_CI is set");
+    }
+}
@@ -1,6 +1,8 @@
-/// Error types for fasterthefuck.
+//! Error types for fasterthefuck.
 
+use std::fmt;
 use std::io;
+use std::path::PathBuf;
 use thiserror::Error;
 
 /// Result type alias using our custom Error type.
@@ -15,18 +17,41 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Configuration error in {} at line {line}, column {col}: {message}", .path.display())]
+    ConfigParse {
+        path: PathBuf,
+        line: usize,
+        col: usize,
+        message: String,
+    },
+
+    #[error("Could not access the configuration at {}: {source}", .path.display())]
+    ConfigIo { path: PathBuf, source: io::Error },
+
     #[error("Rule error: {0}")]
     Rule(String),
 
+    #[error("Rule error: {0}")]
+    RuleBuild(#[from] RuleBuildError),
+
     #[error("Shell error: {0}")]
     Shell(String),
 
+    #[error("Could not start {shell}: {}", shell_init_reason(.source))]
+    ShellInit { shell: String, source: io::Error },
+
     #[error("Fuzzy matching error: {0}")]
     FuzzyMatch(String),
 
     #[error("Command parsing error: {0}")]
     CommandParse(String),
 
+    #[error("Side effect error: {0}")]
+    SideEffect(String),
+
+    #[error("Daemon error: {0}")]
+    Daemon(String),
+
     #[error("No corrections found")]
     NoCorrections,
 
@@ -34,12 +59,118 @@ pub enum Error {
     Other(String),
 }
 
+/// Why a rule builder couldn't build a rule.
+#[derive(Debug)]
+pub struct RuleBuildError {
+    /// The rule's name
+    pub rule_name: String,
+    /// Index of the `[[rules]]` table the rule was read from, if it came
+    /// from a TOML file
+    pub table: Option<usize>,
+    pub kind: RuleBuildErrorKind,
+}
+
+/// What was wrong with a rule a builder was given.
+#[derive(Debug)]
+pub enum RuleBuildErrorKind {
+    /// Neither a command nor an output pattern was set
+    MissingPattern,
+    /// No replacement was set
+    MissingReplacement,
+    /// The `field` pattern, command or output, doesn't compile, or compiles
+    /// to more than the limit
+    InvalidRegex { field: &'static str, source: regex::Error },
+    /// The `field` pattern is longer than the limit
+    PatternTooLong { field: &'static str, len: usize, limit: usize },
+    /// The replacement refers to a group the pattern doesn't have
+    InvalidTemplate { template: String, reason: String },
+}
+
+impl RuleBuildError {
+    /// Creates an error for the rule `rule_name`.
+    pub fn new(rule_name: impl Into<String>, kind: RuleBuildErrorKind) -> Self {
+        Self {
+            rule_name: rule_name.into(),
+            table: None,
+            kind,
+        }
+    }
+
+    /// Notes that the rule was read from the `[[rules]]` table at `index`.
+    pub fn in_table(mut self, index: usize) -> Self {
+        self.table = Some(index);
+        self
+    }
+}
+
+impl fmt::Display for RuleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rule '{}'", self.rule_name)?;
+        if let Some(index) = self.table {
+            write!(f, " ([[rules]] table {})", index + 1)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl fmt::Display for RuleBuildErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPattern => f.write_str("at least one pattern (command or output) must be set"),
+            Self::MissingReplacement => f.write_str("a replacement must be set"),
+            Self::InvalidRegex {
+                field,
+                source: regex::Error::CompiledTooBig(limit),
+            } => write!(f, "{} pattern compiles to more than {} bytes", field, limit),
+            Self::InvalidRegex { field, source } => write!(f, "invalid {} pattern: {}", field, source),
+            Self::PatternTooLong { field, len, limit } => {
+                write!(f, "{} pattern is {} bytes long, over the limit of {}", field, len, limit)
+            }
+            Self::InvalidTemplate { template, reason } => write!(f, "invalid replacement '{}': {}", template, reason),
+        }
+    }
+}
+
+impl std::error::Error for RuleBuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            RuleBuildErrorKind::InvalidRegex { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
 impl Error {
     /// Creates a new configuration error.
     pub fn config(msg: impl Into<String>) -> Self {
         Error::Config(msg.into())
     }
 
+    /// Creates a parse error for the config at `path`, at the line and
+    /// column of `contents` where `error` starts.
+    pub fn config_parse(path: impl Into<PathBuf>, contents: &str, error: &toml::de::Error) -> Self {
+        let path = path.into();
+        let Some(span) = error.span() else {
+            return Error::Config(format!("{}: {}", path.display(), error.message()));
+        };
+        let before = &contents[..span.start.min(contents.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Error::ConfigParse {
+            path,
+            line: before.matches('\n').count() + 1,
+            col: before[line_start..].chars().count() + 1,
+            message: error.message().trim_end().to_string(),
+        }
+    }
+
+    /// Creates an error for reading or writing the config at `path`.
+    pub fn config_io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::ConfigIo {
+            path: path.into(),
+            source,
+        }
+    }
+
     /// Creates a new rule error.
     pub fn rule(msg: impl Into<String>) -> Self {
         Error::Rule(msg.into())
@@ -50,15 +181,84 @@ impl Error {
         Error::Shell(msg.into())
     }
 
+    /// Creates an error for a `shell` that couldn't be set up.
+    pub fn shell_init(shell: impl Into<String>, source: io::Error) -> Self {
+        Error::ShellInit {
+            shell: shell.into(),
+            source,
+        }
+    }
+
     /// Creates a new fuzzy matching error.
     pub fn fuzzy_match(msg: impl Into<String>) -> Self {
         Error::FuzzyMatch(msg.into())
     }
 
+    /// Creates a new side effect error.
+    pub fn side_effect(msg: impl Into<String>) -> Self {
+        Error::SideEffect(msg.into())
+    }
+
+    /// Creates a new daemon error.
+    pub fn daemon(msg: impl Into<String>) -> Self {
+        Error::Daemon(msg.into())
+    }
+
     /// Creates a new command parsing error.
     pub fn command_parse(msg: impl Into<String>) -> Self {
         Error::CommandParse(msg.into())
     }
+
+    /// Creates an error whose message says it all, such as one from the
+    /// message catalog.
+    pub fn other(msg: impl Into<String>) -> Self {
+        Error::Other(msg.into())
+    }
+
+    /// Gets the code ftf exits with for this error: 1 when there was
+    /// nothing to correct, like a run that finds no correction, and 2 for
+    /// everything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoCorrections => 1,
+            _ => 2,
+        }
+    }
+
+    /// Gets the error as shown to the user: one line saying what went
+    /// wrong, and for a config that doesn't parse, the line at fault with
+    /// a caret under the column. The [`Debug`](fmt::Debug) form, with every
+    /// field, is only for debug logs.
+    pub fn user_message(&self) -> String {
+        match self {
+            Error::Other(message) => message.clone(),
+            Error::ConfigParse { path, line, col, .. } => {
+                let contents = std::fs::read_to_string(path).unwrap_or_default();
+                match contents.lines().nth(line.saturating_sub(1)) {
+                    Some(text) => quote_line(&self.to_string(), *line, *col, text),
+                    None => self.to_string(),
+                }
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Shows `message` over the numbered `line` of a file, `text`, with a caret
+/// under column `col`.
+fn quote_line(message: &str, line: usize, col: usize, text: &str) -> String {
+    let width = line.to_string().len();
+    format!("{}\n {:>width$} | {}\n {:width$} | {:col$}^", message, line, text, "", "", col = col.saturating_sub(1))
+}
+
+/// Explains a shell initialization failure. The current directory is what
+/// a shell reads as it starts, and it's most often gone because it was
+/// deleted from under the terminal.
+fn shell_init_reason(source: &io::Error) -> String {
+    match source.kind() {
+        io::ErrorKind::NotFound => "the current directory no longer exists; cd to one that does and retry".to_string(),
+        _ => format!("the current directory can't be read: {}", source),
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +271,91 @@ mod tests {
         assert_eq!(err.to_string(), "Configuration error: test config error");
     }
 
+    #[test]
+    fn test_config_parse_points_at_the_line() {
+        let contents = "[global]\ndebug = true\ndebug = false\n";
+        let error = toml::from_str::<toml::Table>(contents).unwrap_err();
+        let err = Error::config_parse("/etc/ftf/config.toml", contents, &error);
+        assert!(matches!(err, Error::ConfigParse { line: 3, col: 1, .. }), "{:?}", err);
+        assert!(err.to_string().starts_with("Configuration error in /etc/ftf/config.toml at line 3, column 1: "));
+    }
+
+    #[test]
+    fn test_rule_build_error_names_rule_and_table() {
+        let err = RuleBuildError::new("deploy_typo", RuleBuildErrorKind::MissingReplacement);
+        assert_eq!(err.to_string(), "rule 'deploy_typo': a replacement must be set");
+        let unclosed = String::from("(");
+        let source = regex::Regex::new(&unclosed).unwrap_err();
+        let err = RuleBuildError::new("deploy_typo", RuleBuildErrorKind::InvalidRegex { field: "output", source });
+        let err = Error::from(err.in_table(2));
+        let text = err.to_string();
+        let expected = "Rule error: rule 'deploy_typo' ([[rules]] table 3): invalid output pattern:";
+        assert!(text.starts_with(expected), "{}", text);
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_shell_init_explains_a_deleted_directory() {
+        let err = Error::shell_init("bash", io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(
+            err.to_string(),
+            "Could not start bash: the current directory no longer exists; cd to one that does and retry"
+        );
+        let err = Error::shell_init("bash", io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(err.to_string().starts_with("Could not start bash: the current directory can't be read: "));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_config_errors_are_shown_with_the_line() {
+        let path = std::env::temp_dir().join(format!("ftf_broken_config_{}.toml", std::process::id()));
+        std::fs::write(&path, "[global]\ndebug = true\nmax_output_bytes = \"lots\"\n").unwrap();
+        let error = crate::Config::load_from_file(&path).unwrap_err();
+        let text = error.user_message();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = format!(
+            "Configuration error in {} at line 3, column 20: invalid type: string \"lots\", expected usize\n \
+             3 | max_output_bytes = \"lots\"\n   |                    ^",
+            path.display()
+        );
+        assert_eq!(text, expected);
+        // Once the file is gone there's no line to quote
+        assert_eq!(error.user_message(), error.to_string());
+
+        let error = Error::config("Could not determine config directory");
+        assert_eq!(error.user_message(), "Configuration error: Could not determine config directory");
+        let error = Error::config_io("/etc/ftf/config.toml", io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(
+            error.user_message(),
+            "Could not access the configuration at /etc/ftf/config.toml: permission denied"
+        );
+    }
+
+    #[test]
+    fn test_shell_and_rule_errors_are_shown() {
+        let shown = [
+            Error::shell("the working directory /tmp/gone no longer exists"),
+            Error::shell_init("zsh", io::Error::from(io::ErrorKind::PermissionDenied)),
+            Error::rule("exited with 3"),
+            RuleBuildError::new("deploy_typo", RuleBuildErrorKind::MissingReplacement).in_table(0).into(),
+            Error::other("no rule packs are enabled"),
+            Error::NoCorrections,
+        ]
+        .map(|error| (error.exit_code(), error.user_message()));
+        assert_eq!(
+            shown,
+            [
+                (2, "Shell error: the working directory /tmp/gone no longer exists".to_string()),
+                (2, "Could not start zsh: the current directory can't be read: permission denied".into()),
+                (2, "Rule error: exited with 3".into()),
+                (2, "Rule error: rule 'deploy_typo' ([[rules]] table 1): a replacement must be set".into()),
+                (2, "no rule packs are enabled".into()),
+                (1, "No corrections found".into()),
+            ]
+        );
+    }
+
     #[test]
     fn test_result_type() {
         let result: Result<i32> = Ok(42);
@@ -0,0 +1,352 @@
+//! Step-by-step execution of corrections.
+//!
+//! A correction that chains commands with `&&`, or that has a side effect, is
+//! run as a plan of numbered steps instead of one opaque script. The steps
+//! can be previewed before anything runs, and a failure names the step that
+//! failed.
+//!
+//! A step that can succeed without changing anything, such as a `git stash`
+//! with nothing to stash, is checked with a probe before and after it runs.
+//! If it changed nothing, its rollback and a later step reverting it are
+//! skipped, as they would revert something else.
+//!
+//! Each step runs in a fresh shell, so a chain whose commands change the
+//! shell itself (`cd api && cargo build`) runs as a single step. Output is
+//! captured and printed once the plan has run, and commands get no terminal
+//! input, so a correction that needs one is better printed than executed.
+
+use crate::undo::{change_probe, inverse_command};
+use crate::{CorrectedCommand, Error, Script, Shell, ShellOutput, SideEffect};
+use std::cmp::Reverse;
+use std::fmt;
+
+/// Builtins that change the shell running them, which a later step run in a
+/// fresh shell wouldn't see.
+const SHELL_STATE_BUILTINS: &[&str] = &[
+    "cd", "pushd", "popd", "export", "unset", "source", ".", "set", "shopt", "alias", "unalias",
+    "umask", "ulimit", "declare", "typeset", "readonly",
+];
+
+/// One step of an execution plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// A side effect ftf runs itself.
+    SideEffect(SideEffect),
+    /// A command run through the shell.
+    Command(String),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::SideEffect(effect) => write!(f, "{} (side effect)", effect),
+            Step::Command(command) => f.write_str(command),
+        }
+    }
+}
+
+/// The steps of a correction, in the order they run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPlan {
+    steps: Vec<Step>,
+    /// Commands that revert a completed step, by step index
+    rollbacks: Vec<(usize, String)>,
+}
+
+impl ExecutionPlan {
+    /// Plans a correction: its side effect first, then each command of its
+    /// `&&` chain, together with the chain's rollbacks.
+    ///
+    /// Effects that only the shell hook can run are left out.
+    pub fn new(correction: &CorrectedCommand) -> Self {
+        let mut plan = Self::from_script(&correction.script);
+        // Rollbacks name chain segments, which a chain kept whole doesn't have
+        let split = plan.steps.len() == Script::split_chain(&correction.script).len();
+        let effect = correction.side_effect.as_ref().filter(|effect| !effect.runs_in_hook());
+        let offset = usize::from(effect.is_some());
+        if let Some(effect) = effect {
+            plan.steps.insert(0, Step::SideEffect(effect.clone()));
+        }
+        plan.rollbacks = correction
+            .rollbacks
+            .iter()
+            .map(|(segment, rollback)| (segment + offset, rollback.clone()))
+            .filter(|(step, _)| split && *step < plan.steps.len())
+            .collect();
+        plan
+    }
+
+    /// Plans running `commands` one after another, without rollbacks.
+    pub fn from_commands<I, S>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            steps: commands.into_iter().map(|command| Step::Command(command.into())).collect(),
+            rollbacks: Vec::new(),
+        }
+    }
+
+    /// Plans a script without a side effect or rollbacks.
+    ///
+    /// A chain that changes the shell's state before its last command is
+    /// kept whole, as one step.
+    pub fn from_script(script: &str) -> Self {
+        let segments = Script::split_chain(script);
+        let (_, earlier) = segments.split_last().unwrap_or((&"", &[]));
+        if earlier.iter().any(|segment| changes_shell_state(segment)) {
+            return Self::from_commands([script.trim()]);
+        }
+        Self::from_commands(segments)
+    }
+
+    /// Gets the steps.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Returns true if the plan is more than a single command, and so
+    /// should be previewed before running.
+    pub fn is_chained(&self) -> bool {
+        self.steps.len() > 1
+    }
+
+    /// Gets the commands that revert the steps completed before `report`'s
+    /// failure, most recent first. Empty if every step succeeded.
+    ///
+    /// Steps that changed nothing have nothing to revert.
+    pub fn rollbacks_after(&self, report: &ExecutionReport) -> Vec<String> {
+        let Some(failure) = &report.failure else {
+            return Vec::new();
+        };
+
+        let mut rollbacks: Vec<&(usize, String)> = self
+            .rollbacks
+            .iter()
+            .filter(|(step, _)| *step < failure.index && !report.unchanged.contains(step))
+            .collect();
+        rollbacks.sort_by_key(|(step, _)| Reverse(*step));
+        rollbacks.into_iter().map(|(_, rollback)| rollback.clone()).collect()
+    }
+
+    /// Runs the steps in order, stopping at the first one that fails.
+    ///
+    /// Commands run through `shell`, which captures their output. A command
+    /// reverting an earlier step that changed nothing is skipped.
+    pub fn run(&self, shell: &dyn Shell) -> ExecutionReport {
+        let mut report = ExecutionReport::default();
+        let cwd = shell.cwd().unwrap_or_default();
+
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Step::Command(command) = step {
+                let reverts_unchanged = report.unchanged.iter().any(|&earlier| match &self.steps[earlier] {
+                    Step::Command(earlier) => inverse_command(earlier, &cwd).as_deref() == Some(command.trim()),
+                    Step::SideEffect(_) => false,
+                });
+                if reverts_unchanged {
+                    report.skipped.push(index);
+                    continue;
+                }
+            }
+
+            let probe = match step {
+                Step::Command(command) => change_probe(command),
+                Step::SideEffect(_) => None,
+            };
+            let before = probe.map(|probe| probe_output(shell, probe));
+            let result = match step {
+                Step::SideEffect(effect) => effect.run(shell),
+                Step::Command(command) => shell.execute(command).and_then(|output| {
+                    let success = output.success;
+                    let exit_code = output.exit_code;
+                    report.outputs.push(output);
+                    if success {
+                        Ok(())
+                    } else {
+                        Err(Error::shell(format!("exited with {}", exit_code)))
+                    }
+                }),
+            };
+
+            if let Err(error) = result {
+                report.failure = Some(StepFailure {
+                    index,
+                    step: step.clone(),
+                    error,
+                });
+                break;
+            }
+            // A probe that can't run tells nothing, so the step counts as a change
+            if let (Some(probe), Some(Some(before))) = (probe, before) {
+                if probe_output(shell, probe).is_some_and(|after| after == before) {
+                    report.unchanged.push(index);
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Runs `probe`, getting its output and exit code, or None if it couldn't
+/// run. Probes exit with 1 to say "none", and with more when they failed.
+fn probe_output(shell: &dyn Shell, probe: &str) -> Option<(String, i32)> {
+    let output = shell.execute(probe).ok()?;
+    (output.exit_code <= 1).then_some((output.stdout, output.exit_code))
+}
+
+/// Returns true if `segment` changes the state of the shell running it: a
+/// builtin such as `cd` or `export`, or a bare variable assignment.
+fn changes_shell_state(segment: &str) -> bool {
+    let script = Script::parse(segment);
+    match script.tokens().iter().find(|token| !Script::is_assignment(token)) {
+        Some(command) => SHELL_STATE_BUILTINS.contains(&command.as_str()),
+        None => !script.is_empty(),
+    }
+}
+
+/// What happened when a plan ran.
+#[derive(Debug, Default)]
+pub struct ExecutionReport {
+    /// Output of each command step that ran, in order
+    pub outputs: Vec<ShellOutput>,
+    /// The step that stopped the plan, if any
+    pub failure: Option<StepFailure>,
+    /// Steps that succeeded without changing anything, by index
+    pub unchanged: Vec<usize>,
+    /// Steps not run, as they revert an unchanged step, by index
+    pub skipped: Vec<usize>,
+}
+
+impl ExecutionReport {
+    /// Returns true if every step succeeded.
+    pub fn success(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Gets the exit code to report: the last command's, or 1 if a side
+    /// effect or the shell itself failed.
+    pub fn exit_code(&self) -> i32 {
+        match (&self.failure, self.outputs.last()) {
+            (Some(StepFailure { step: Step::Command(_), .. }), Some(output)) if !output.success => {
+                output.exit_code
+            }
+            (Some(_), _) => 1,
+            (None, output) => output.map_or(0, |output| output.exit_code),
+        }
+    }
+}
+
+/// The step a plan stopped at.
+#[derive(Debug)]
+pub struct StepFailure {
+    /// 0-based position of the step in the plan
+    pub index: usize,
+    /// The step that failed
+    pub step: Step,
+    /// Why it failed
+    pub error: Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+    use crate::undo::STASH_PROBE;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_plan_splits_chain() {
+        let plan = ExecutionPlan::from_script("git stash && git pull --rebase && git stash pop");
+
+        assert!(plan.is_chained());
+        assert_eq!(
+            plan.steps(),
+            &[
+                Step::Command("git stash".to_string()),
+                Step::Command("git pull --rebase".to_string()),
+                Step::Command("git stash pop".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_puts_side_effect_first() {
+        let effect = SideEffect::CreateParentDirs(PathBuf::from("archive/report.pdf"));
+        let correction = CorrectedCommand::with_side_effect("mv report.pdf archive/report.pdf", 400, effect.clone());
+        let plan = ExecutionPlan::new(&correction);
+
+        assert!(plan.is_chained());
+        assert_eq!(plan.steps()[0], Step::SideEffect(effect));
+        assert_eq!(plan.steps()[0].to_string(), "create archive (side effect)");
+    }
+
+    #[test]
+    fn test_plan_leaves_out_hook_effects() {
+        let correction = CorrectedCommand::with_side_effect("pyenv install 3.12.1 && python", 200, SideEffect::Rehash);
+        let plan = ExecutionPlan::new(&correction);
+
+        assert_eq!(plan.steps().len(), 2);
+        assert!(matches!(plan.steps()[0], Step::Command(_)));
+    }
+
+    #[test]
+    fn test_chain_changing_shell_state_is_kept_whole() {
+        for script in ["cd api && cargo build", "export RUST_LOG=debug && cargo run", "DEBUG=1 && make"] {
+            assert_eq!(ExecutionPlan::from_script(script).steps(), &[Step::Command(script.to_string())]);
+        }
+        // Only later steps would miss the change
+        assert!(ExecutionPlan::from_script("mkdir -p build && cd build").is_chained());
+        assert!(ExecutionPlan::from_script("RUST_LOG=debug cargo test && cargo doc").is_chained());
+    }
+
+    #[test]
+    fn test_single_command_is_not_chained() {
+        assert!(!ExecutionPlan::from_script("git push").is_chained());
+    }
+
+    #[test]
+    fn test_rollbacks_after_failure() {
+        let correction = CorrectedCommand::new("git stash && git pull && git stash pop", 650)
+            .with_rollbacks(vec![(0, "git stash pop".to_string())]);
+        let plan = ExecutionPlan::new(&correction);
+        let shell = MockShell::new().with_output("git stash", "", 0).with_output("git pull", "", 1);
+
+        let report = plan.run(&shell);
+        assert_eq!(plan.rollbacks_after(&report), vec!["git stash pop"]);
+
+        // The stash probe printed the same before and after, so nothing was stashed
+        let shell = shell.with_output(STASH_PROBE, "0a1b2c\n", 0);
+        let report = plan.run(&shell);
+        assert_eq!(report.unchanged, vec![0]);
+        assert!(plan.rollbacks_after(&report).is_empty());
+
+        // Nothing completed, so nothing to roll back
+        let shell = MockShell::new().with_output("git stash", "", 1);
+        assert!(plan.rollbacks_after(&plan.run(&shell)).is_empty());
+    }
+
+    #[test]
+    fn test_rollbacks_shift_past_side_effect() {
+        let effect = SideEffect::BackupFile(PathBuf::from("/etc/hosts"));
+        let correction = CorrectedCommand::with_side_effect("a && b", 100, effect)
+            .with_rollbacks(vec![(1, "undo b".to_string()), (5, "unused".to_string())]);
+
+        let plan = ExecutionPlan::new(&correction);
+        assert_eq!(plan.rollbacks, vec![(2, "undo b".to_string())]);
+    }
+
+    #[test]
+    fn test_report_exit_code() {
+        let shell = MockShell::new().with_output("make", "", 0).with_output("make test", "", 2);
+
+        let report = ExecutionPlan::from_script("make && make test").run(&shell);
+        assert_eq!(report.failure.as_ref().map(|failure| failure.index), Some(1));
+        assert_eq!(report.exit_code(), 2);
+
+        let report = ExecutionPlan::from_script("make").run(&shell);
+        assert!(report.success());
+        assert_eq!(report.exit_code(), 0);
+    }
+}
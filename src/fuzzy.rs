@@ -1,4 +1,4 @@
-/// Fuzzy matching and selection utilities for command corrections.
+//! Fuzzy matching and selection utilities for command corrections.
 
 use crate::CorrectedCommand;
 use fuzzy_matcher::skim::SkimMatcherV2;
@@ -48,7 +48,7 @@ impl FuzzyMatcher {
             })
             .collect();
 
-        matches.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by score descending
+        matches.sort_by_key(|m| std::cmp::Reverse(m.1)); // Sort by score descending
         matches
     }
 }
@@ -82,6 +82,43 @@ pub fn fuzzy_find_path(query: &str, candidates: &[&str]) -> Option<String> {
         .map(|(path, _)| path.to_string())
 }
 
+/// Computes a typo-tolerant similarity between two strings in the range 0.0..=1.0.
+///
+/// Unlike the skim matcher this tolerates transposed and substituted characters,
+/// which makes it suitable for comparing whole command lines.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_damerau_levenshtein(a, b)
+}
+
+/// Minimum similarity for a history entry to be offered as a fallback.
+const MIN_HISTORY_SIMILARITY: f64 = 0.3;
+
+/// Ranks history entries (most recent first) by similarity to `query`.
+///
+/// Returns up to `limit` distinct entries, most similar first; ties keep the
+/// more recent entry first. The query itself is never returned.
+pub fn rank_history_candidates(query: &str, entries: &[String], limit: usize) -> Vec<String> {
+    let query = query.trim();
+    let mut seen = std::collections::HashSet::new();
+
+    let mut scored: Vec<(&str, f64)> = entries
+        .iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty() && *entry != query && seen.insert(*entry))
+        .map(|entry| (entry, similarity(entry, query)))
+        .filter(|(_, score)| *score >= MIN_HISTORY_SIMILARITY)
+        .collect();
+
+    // Stable sort keeps recency order among equal scores
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(entry, _)| entry.to_string())
+        .collect()
+}
+
 /// Filters candidates that fuzzy match a query string.
 pub fn filter_by_fuzzy_match(query: &str, candidates: &[&str], min_score: i64) -> Vec<String> {
     FuzzyMatcher::new()
@@ -95,6 +132,36 @@ pub fn filter_by_fuzzy_match(query: &str, candidates: &[&str], min_score: i64) -
 mod tests {
     use super::*;
 
+    fn history(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rank_history_candidates_orders_by_similarity() {
+        let entries = history(&["ls -la", "git status", "git stash", "cargo build"]);
+
+        let ranked = rank_history_candidates("git stauts", &entries, 5);
+        assert_eq!(ranked[0], "git status");
+        assert_eq!(ranked[1], "git stash");
+        assert!(!ranked.contains(&"cargo build".to_string()));
+    }
+
+    #[test]
+    fn test_rank_history_candidates_limit_and_dedupe() {
+        let entries = history(&["make test", "make test", "make tests", "make lint", "make"]);
+
+        let ranked = rank_history_candidates("make tset", &entries, 2);
+        assert_eq!(ranked, vec!["make test", "make tests"]);
+    }
+
+    #[test]
+    fn test_rank_history_candidates_skips_query() {
+        let entries = history(&["git stauts", "git status"]);
+
+        assert_eq!(rank_history_candidates("git stauts", &entries, 5), vec!["git status"]);
+        assert!(rank_history_candidates("anything", &[], 5).is_empty());
+    }
+
     #[test]
     fn test_fuzzy_matcher_creation() {
         let _matcher = FuzzyMatcher::new();
@@ -137,6 +204,13 @@ mod tests {
         assert!(result.is_some());
     }
 
+    #[test]
+    fn test_similarity_tolerates_transpositions() {
+        assert!(similarity("git stauts", "git status") > 0.8);
+        assert!(similarity("git status", "ls -la /tmp") < 0.5);
+        assert_eq!(similarity("same", "same"), 1.0);
+    }
+
     #[test]
     fn test_select_corrections_sorting() {
         let corrections = vec![
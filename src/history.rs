@@ -0,0 +1,668 @@
+//! Shell history file parsing.
+//!
+//! Real history files are messier than one command per line:
+//! - bash writes `#<epoch>` timestamp lines when HISTTIMEFORMAT is set
+//! - commands can span several lines via backslash continuations
+//! - zsh's extended format prefixes entries with `: <start>:<elapsed>;`
+//! - zsh "metafies" some bytes, which must be decoded before use
+//! - files hold raw bytes that aren't UTF-8, from pasted binary or other
+//!   encodings, which only spoil the lines they're on
+//!
+//! The parsers return entries oldest first; `HistoryFilter` then applies the
+//! user's HISTCONTROL / HISTIGNORE / HISTORY_IGNORE settings. The writers
+//! produce lines the parsers read back, for appending executed corrections.
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Byte zsh uses to escape metafied characters.
+const ZSH_META: u8 = 0x83;
+
+/// Highest byte zsh metafies; 0x83 up to here are its internal tokens.
+const ZSH_LAST_TOKEN: u8 = 0xa2;
+
+/// Returns true if the line ends with an unescaped backslash.
+fn is_continued(line: &str) -> bool {
+    let trailing = line.bytes().rev().take_while(|&b| b == b'\\').count();
+    trailing % 2 == 1
+}
+
+/// A history entry with the time it was run, if the history file records it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// The command line
+    pub command: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Creates a new history entry.
+    pub fn new(command: impl Into<String>, timestamp: Option<u64>) -> Self {
+        Self {
+            command: command.into(),
+            timestamp,
+        }
+    }
+}
+
+/// Parses a bash history file into commands, oldest first.
+///
+/// Timestamp comment lines are dropped and backslash continuations are joined.
+pub fn parse_bash_history(contents: &str) -> Vec<String> {
+    commands(parse_bash_history_entries(contents))
+}
+
+/// Parses a bash history file into entries, oldest first.
+///
+/// A `#<epoch>` line written with HISTTIMEFORMAT set timestamps the command
+/// that follows it.
+pub fn parse_bash_history_entries(contents: &str) -> Vec<HistoryEntry> {
+    bash_entries(contents.lines().map(Cow::Borrowed))
+}
+
+fn bash_entries<'a>(lines: impl Iterator<Item = Cow<'a, str>>) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut pending: Option<String> = None;
+    let mut timestamp: Option<u64> = None;
+
+    for line in lines {
+        let line = line.as_ref();
+        if pending.is_none() {
+            if let Some(epoch) = parse_timestamp(line) {
+                timestamp = Some(epoch);
+                continue;
+            }
+        }
+
+        let joined = match pending.take() {
+            Some(mut previous) => {
+                previous.push(' ');
+                previous.push_str(line.trim_start());
+                previous
+            }
+            None => line.to_string(),
+        };
+
+        if is_continued(&joined) {
+            let mut joined = joined;
+            joined.pop();
+            pending = Some(joined.trim_end().to_string());
+        } else if !joined.trim().is_empty() {
+            entries.push(HistoryEntry::new(joined, timestamp.take()));
+        }
+    }
+
+    if let Some(rest) = pending {
+        if !rest.trim().is_empty() {
+            entries.push(HistoryEntry::new(rest, timestamp));
+        }
+    }
+
+    entries
+}
+
+/// Extracts the commands from parsed entries.
+fn commands(entries: Vec<HistoryEntry>) -> Vec<String> {
+    entries.into_iter().map(|entry| entry.command).collect()
+}
+
+/// Parses bash HISTTIMEFORMAT lines such as `#1700000000`.
+fn parse_timestamp(line: &str) -> Option<u64> {
+    let rest = line.strip_prefix('#')?;
+    if rest.is_empty() || !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    rest.parse().ok()
+}
+
+/// Parses a (already unmetafied) zsh history file into commands, oldest first.
+///
+/// Handles both the plain and the EXTENDED_HISTORY `: <start>:<elapsed>;cmd`
+/// formats. Multi-line commands keep their embedded newlines.
+pub fn parse_zsh_history(contents: &str) -> Vec<String> {
+    commands(parse_zsh_history_entries(contents))
+}
+
+/// Parses a (already unmetafied) zsh history file into entries, oldest first.
+///
+/// Entries in the extended format carry their start time.
+pub fn parse_zsh_history_entries(contents: &str) -> Vec<HistoryEntry> {
+    zsh_entries(contents.lines().map(Cow::Borrowed))
+}
+
+fn zsh_entries<'a>(lines: impl Iterator<Item = Cow<'a, str>>) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut pending: Option<HistoryEntry> = None;
+
+    for line in lines {
+        let line = line.as_ref();
+        let mut entry = match pending.take() {
+            Some(mut previous) => {
+                previous.command.push('\n');
+                previous.command.push_str(line);
+                previous
+            }
+            None => {
+                let (timestamp, command) = split_zsh_extended_prefix(line);
+                HistoryEntry::new(command, timestamp)
+            }
+        };
+
+        if is_continued(&entry.command) {
+            entry.command.pop();
+            pending = Some(entry);
+        } else if !entry.command.trim().is_empty() {
+            entries.push(entry);
+        }
+    }
+
+    if let Some(rest) = pending {
+        if !rest.command.trim().is_empty() {
+            entries.push(rest);
+        }
+    }
+
+    entries
+}
+
+/// Strips the `: <start>:<elapsed>;` prefix of zsh's extended history format.
+fn strip_zsh_extended_prefix(line: &str) -> &str {
+    split_zsh_extended_prefix(line).1
+}
+
+/// Splits a zsh extended history line into its start time and command.
+///
+/// Lines without the prefix are returned unchanged with no timestamp.
+fn split_zsh_extended_prefix(line: &str) -> (Option<u64>, &str) {
+    let Some(rest) = line.strip_prefix(": ") else {
+        return (None, line);
+    };
+    let Some((meta, command)) = rest.split_once(';') else {
+        return (None, line);
+    };
+
+    let start = meta.split_once(':').and_then(|(start, elapsed)| {
+        let is_metadata = !start.is_empty()
+            && start.bytes().all(|b| b.is_ascii_digit())
+            && elapsed.bytes().all(|b| b.is_ascii_digit());
+        if is_metadata {
+            start.parse().ok()
+        } else {
+            None
+        }
+    });
+
+    match start {
+        Some(start) => (Some(start), command),
+        None => (None, line),
+    }
+}
+
+/// Decodes zsh metafied bytes: each `0x83 b` pair stands for `b ^ 0x20`.
+pub fn unmetafy_zsh(bytes: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == ZSH_META {
+            if let Some(&next) = iter.next() {
+                decoded.push(next ^ 0x20);
+            }
+        } else {
+            decoded.push(byte);
+        }
+    }
+    decoded
+}
+
+/// Encodes bytes the way zsh stores them: NUL and zsh's token bytes become
+/// `0x83 (b ^ 0x20)` pairs.
+pub fn metafy_zsh(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len());
+    for &byte in bytes {
+        if byte == 0 || (ZSH_META..=ZSH_LAST_TOKEN).contains(&byte) {
+            encoded.push(ZSH_META);
+            encoded.push(byte ^ 0x20);
+        } else {
+            encoded.push(byte);
+        }
+    }
+    encoded
+}
+
+/// Formats an entry as bash writes it, with a `#<epoch>` line before the
+/// command if it has a timestamp.
+pub fn format_bash_history_entry(entry: &HistoryEntry) -> String {
+    match entry.timestamp {
+        Some(timestamp) => format!("#{}\n{}\n", timestamp, entry.command),
+        None => format!("{}\n", entry.command),
+    }
+}
+
+/// Formats an entry as zsh writes it, metafied, in the extended format if it
+/// has a timestamp.
+///
+/// Embedded newlines are escaped with a backslash, as zsh does.
+pub fn format_zsh_history_entry(entry: &HistoryEntry) -> Vec<u8> {
+    let command = entry.command.replace('\n', "\\\n");
+    let line = match entry.timestamp {
+        Some(timestamp) => format!(": {}:0;{}\n", timestamp, command),
+        None => format!("{}\n", command),
+    };
+    metafy_zsh(line.as_bytes())
+}
+
+/// Gets the history file named by `HISTFILE`, or `~/.bash_history`.
+pub fn history_file_path(env: impl Fn(&str) -> Option<String>) -> PathBuf {
+    env("HISTFILE").map(PathBuf::from).unwrap_or_else(|| {
+        PathBuf::from(env("HOME").unwrap_or_else(|| ".".to_string())).join(".bash_history")
+    })
+}
+
+/// Appends `entry` to the history file at `path`, matching the file's format.
+///
+/// zsh files, recognised by their contents or by name, get an extended
+/// entry. bash files only get a timestamp line if they already have some,
+/// i.e. HISTTIMEFORMAT was set when they were written.
+pub fn append_history_entry(path: &Path, entry: &HistoryEntry) -> crate::Result<()> {
+    let existing = std::fs::read(path).unwrap_or_default();
+    let named_zsh = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains("zsh"));
+    let formatted = if named_zsh || looks_like_zsh(&existing) {
+        format_zsh_history_entry(entry)
+    } else {
+        let timestamped = String::from_utf8_lossy(&existing)
+            .lines()
+            .any(|line| parse_timestamp(line).is_some());
+        let entry = HistoryEntry::new(entry.command.clone(), entry.timestamp.filter(|_| timestamped));
+        format_bash_history_entry(&entry).into_bytes()
+    };
+
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    if !existing.is_empty() && !existing.ends_with(b"\n") {
+        file.write_all(b"\n")?;
+    }
+    file.write_all(&formatted)?;
+    Ok(())
+}
+
+/// Returns true if the contents look like a zsh extended history file.
+fn looks_like_zsh(bytes: &[u8]) -> bool {
+    bytes.contains(&ZSH_META)
+        || String::from_utf8_lossy(&bytes[..bytes.len().min(256)])
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| strip_zsh_extended_prefix(line).len() != line.len())
+}
+
+/// Parses raw history file bytes, detecting the zsh format automatically.
+pub fn parse_history_file(bytes: &[u8]) -> Vec<String> {
+    commands(parse_history_file_entries(bytes))
+}
+
+/// Parses raw history file bytes into timestamped entries, oldest first.
+///
+/// Each line is decoded on its own, so bytes that aren't UTF-8 become
+/// U+FFFD in the entry they're in and leave the others as they are.
+pub fn parse_history_file_entries(bytes: &[u8]) -> Vec<HistoryEntry> {
+    if looks_like_zsh(bytes) {
+        zsh_entries(byte_lines(&unmetafy_zsh(bytes)))
+    } else {
+        bash_entries(byte_lines(bytes))
+    }
+}
+
+/// Splits `bytes` into lines as `str::lines` does, decoding each lossily.
+fn byte_lines(bytes: &[u8]) -> impl Iterator<Item = Cow<'_, str>> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes
+        .split(|&b| b == b'\n')
+        .filter(move |_| !bytes.is_empty())
+        .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)))
+}
+
+/// User history settings that affect which entries reflect real executions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryFilter {
+    /// Glob patterns of commands to skip (HISTIGNORE / HISTORY_IGNORE)
+    pub ignore_patterns: Vec<String>,
+    /// Collapse consecutive duplicate entries (HISTCONTROL=ignoredups)
+    pub dedupe_consecutive: bool,
+    /// Skip entries starting with a space (HISTCONTROL=ignorespace)
+    pub ignore_space: bool,
+}
+
+impl HistoryFilter {
+    /// Builds a filter from environment variables looked up through `env`.
+    pub fn from_env(env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut filter = Self::default();
+
+        if let Some(control) = env("HISTCONTROL") {
+            for setting in control.split(':') {
+                match setting.trim() {
+                    "ignoredups" | "erasedups" => filter.dedupe_consecutive = true,
+                    "ignorespace" => filter.ignore_space = true,
+                    "ignoreboth" => {
+                        filter.dedupe_consecutive = true;
+                        filter.ignore_space = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // bash: colon-separated patterns, where `&` means "same as previous"
+        if let Some(ignore) = env("HISTIGNORE") {
+            for pattern in ignore.split(':').filter(|p| !p.is_empty()) {
+                if pattern == "&" {
+                    filter.dedupe_consecutive = true;
+                } else {
+                    filter.ignore_patterns.push(pattern.to_string());
+                }
+            }
+        }
+
+        // zsh: a single pattern, usually an alternation such as `(ls|cd|pwd)`
+        if let Some(ignore) = env("HISTORY_IGNORE") {
+            let ignore = ignore.trim();
+            let inner = ignore
+                .strip_prefix('(')
+                .and_then(|s| s.strip_suffix(')'))
+                .unwrap_or(ignore);
+            filter.ignore_patterns.extend(
+                inner
+                    .split('|')
+                    .filter(|p| !p.is_empty())
+                    .map(str::to_string),
+            );
+        }
+
+        filter
+    }
+
+    /// Applies the filter to commands (oldest first).
+    pub fn apply(&self, entries: Vec<String>) -> Vec<String> {
+        self.retain(entries, |entry| entry)
+    }
+
+    /// Applies the filter to timestamped entries (oldest first).
+    pub fn apply_entries(&self, entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+        self.retain(entries, |entry| &entry.command)
+    }
+
+    fn retain<T>(&self, entries: Vec<T>, command: impl Fn(&T) -> &str) -> Vec<T> {
+        let mut filtered: Vec<T> = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let text = command(&entry);
+            if self.ignore_space && text.starts_with(' ') {
+                continue;
+            }
+            if self.ignore_patterns.iter().any(|p| glob_match(p, text)) {
+                continue;
+            }
+            if self.dedupe_consecutive && filtered.last().is_some_and(|last| command(last) == text) {
+                continue;
+            }
+            filtered.push(entry);
+        }
+
+        filtered
+    }
+}
+
+/// Matches `text` against a shell glob supporting `*` and `?`.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASH_FIXTURE: &str = include_str!("../tests/fixtures/history/bash_history");
+    const ZSH_FIXTURE: &[u8] = include_bytes!("../tests/fixtures/history/zsh_history");
+
+    #[test]
+    fn test_parse_bash_history_fixture() {
+        let entries = parse_bash_history(BASH_FIXTURE);
+        assert_eq!(
+            entries,
+            vec![
+                "git status",
+                "ls -la",
+                "ls -la",
+                "docker run --rm -it ubuntu bash",
+                "echo trailing\\\\",
+                " secret-command --token abc",
+                "cargo build",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_zsh_history_fixture() {
+        let entries = parse_history_file(ZSH_FIXTURE);
+        assert_eq!(
+            entries,
+            vec![
+                "git status",
+                "for f in *.txt; do\n  echo $f\ndone",
+                "echo 日本",
+                "echo 日本",
+                "ls",
+                "plain line without metadata",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_bash_history_timestamps() {
+        let entries = parse_bash_history_entries(BASH_FIXTURE);
+        assert_eq!(entries[0], HistoryEntry::new("git status", Some(1700000000)));
+        assert_eq!(entries[1], HistoryEntry::new("ls -la", Some(1700000001)));
+        // A timestamp line only applies to the command right after it
+        assert_eq!(entries[2], HistoryEntry::new("ls -la", None));
+    }
+
+    #[test]
+    fn test_parse_zsh_history_timestamps() {
+        let entries = parse_history_file_entries(ZSH_FIXTURE);
+        assert_eq!(entries[0], HistoryEntry::new("git status", Some(1700000000)));
+        assert_eq!(entries[1].timestamp, Some(1700000001));
+        assert_eq!(entries[5], HistoryEntry::new("plain line without metadata", None));
+    }
+
+    #[test]
+    fn test_invalid_bytes_only_spoil_their_entry() {
+        let bytes = include_bytes!("../tests/fixtures/invalid_utf8/bash_history");
+        let entries = parse_history_file_entries(bytes);
+        assert_eq!(
+            entries,
+            vec![
+                HistoryEntry::new("git status", Some(1700000000)),
+                HistoryEntry::new("cd /srv/caf\u{fffd}", None),
+                HistoryEntry::new("ls r\u{fffd}sum\u{fffd}s", Some(1700000002)),
+                HistoryEntry::new("\u{fffd}\u{fffd}\u{fffd}", None),
+                HistoryEntry::new("git push", None),
+            ]
+        );
+
+        // zsh entries are decoded after unmetafying
+        let zsh = [b": 1700000000:0;echo caf\xe9\n".as_slice(), &metafy_zsh("echo 日本\n".as_bytes())].concat();
+        assert_eq!(
+            parse_history_file_entries(&zsh),
+            vec![HistoryEntry::new("echo caf\u{fffd}", Some(1700000000)), HistoryEntry::new("echo 日本", None)]
+        );
+    }
+
+    #[test]
+    fn test_unmetafy_zsh() {
+        // "日" is E6 97 A5; zsh writes 0x97 as 0x83 0xB7
+        assert_eq!(unmetafy_zsh(&[0xE6, 0x83, 0xB7, 0xA5]), "日".as_bytes());
+        assert_eq!(unmetafy_zsh(b"plain"), b"plain");
+    }
+
+    #[test]
+    fn test_metafy_zsh_round_trips() {
+        assert_eq!(metafy_zsh("日".as_bytes()), vec![0xE6, 0x83, 0xB7, 0xA5]);
+        let command = "echo '日本語' && ls";
+        assert_eq!(unmetafy_zsh(&metafy_zsh(command.as_bytes())), command.as_bytes());
+    }
+
+    #[test]
+    fn test_format_bash_history_entry() {
+        let entry = HistoryEntry::new("git push -u origin HEAD", Some(1700000000));
+        let formatted = format_bash_history_entry(&entry);
+        assert_eq!(formatted, "#1700000000\ngit push -u origin HEAD\n");
+        assert_eq!(parse_bash_history_entries(&formatted), vec![entry]);
+
+        let entry = HistoryEntry::new("ls -la", None);
+        assert_eq!(format_bash_history_entry(&entry), "ls -la\n");
+    }
+
+    #[test]
+    fn test_format_zsh_history_entry() {
+        let entry = HistoryEntry::new("git push -u origin HEAD", Some(1700000000));
+        assert_eq!(format_zsh_history_entry(&entry), b": 1700000000:0;git push -u origin HEAD\n");
+
+        // Multi-line and metafied commands read back unchanged
+        let entry = HistoryEntry::new("for f in *.日; do\n  echo $f\ndone", Some(1700000000));
+        let formatted = format_zsh_history_entry(&entry);
+        assert_eq!(parse_history_file_entries(&formatted), vec![entry]);
+    }
+
+    #[test]
+    fn test_append_history_entry_matches_file_format() {
+        let dir = std::env::temp_dir().join(format!("ftf_history_append_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = HistoryEntry::new("git checkout -b feature", Some(1700000100));
+
+        let bash = dir.join("bash_history");
+        std::fs::write(&bash, "ls\ngit chekout -b feature").unwrap();
+        append_history_entry(&bash, &entry).unwrap();
+        assert_eq!(std::fs::read_to_string(&bash).unwrap(), "ls\ngit chekout -b feature\ngit checkout -b feature\n");
+
+        let timestamped = dir.join("timestamped_history");
+        std::fs::write(&timestamped, "#1700000000\nls\n").unwrap();
+        append_history_entry(&timestamped, &entry).unwrap();
+        assert!(std::fs::read_to_string(&timestamped).unwrap().ends_with("#1700000100\ngit checkout -b feature\n"));
+
+        let zsh = dir.join(".zsh_history");
+        append_history_entry(&zsh, &entry).unwrap();
+        assert_eq!(std::fs::read_to_string(&zsh).unwrap(), ": 1700000100:0;git checkout -b feature\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_history_file_path() {
+        let env = |key: &str| match key {
+            "HISTFILE" => Some("/home/dev/.zsh_history".to_string()),
+            "HOME" => Some("/home/dev".to_string()),
+            _ => None,
+        };
+        assert_eq!(history_file_path(env), PathBuf::from("/home/dev/.zsh_history"));
+
+        let env = |key: &str| (key == "HOME").then(|| "/home/dev".to_string());
+        assert_eq!(history_file_path(env), PathBuf::from("/home/dev/.bash_history"));
+    }
+
+    #[test]
+    fn test_parse_history_file_detects_bash() {
+        let entries = parse_history_file(BASH_FIXTURE.as_bytes());
+        assert_eq!(entries[0], "git status");
+        assert_eq!(entries.len(), 7);
+    }
+
+    #[test]
+    fn test_strip_zsh_extended_prefix() {
+        assert_eq!(strip_zsh_extended_prefix(": 1700000000:0;git push"), "git push");
+        assert_eq!(strip_zsh_extended_prefix(": not;metadata"), ": not;metadata");
+        assert_eq!(strip_zsh_extended_prefix("git push"), "git push");
+        assert_eq!(
+            split_zsh_extended_prefix(": 1700000000:0;git push"),
+            (Some(1700000000), "git push")
+        );
+    }
+
+    #[test]
+    fn test_history_filter_from_env() {
+        let filter = HistoryFilter::from_env(|key| match key {
+            "HISTCONTROL" => Some("ignoreboth".to_string()),
+            "HISTIGNORE" => Some("ls:&:exit*".to_string()),
+            "HISTORY_IGNORE" => Some("(pwd|cd *)".to_string()),
+            _ => None,
+        });
+
+        assert!(filter.dedupe_consecutive);
+        assert!(filter.ignore_space);
+        assert_eq!(filter.ignore_patterns, vec!["ls", "exit*", "pwd", "cd *"]);
+    }
+
+    #[test]
+    fn test_history_filter_apply() {
+        let filter = HistoryFilter {
+            ignore_patterns: vec!["ls".to_string(), "cd *".to_string()],
+            dedupe_consecutive: true,
+            ignore_space: true,
+        };
+
+        let entries = parse_bash_history(BASH_FIXTURE);
+        assert_eq!(
+            filter.apply(entries),
+            vec![
+                "git status",
+                "ls -la",
+                "docker run --rm -it ubuntu bash",
+                "echo trailing\\\\",
+                "cargo build",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_history_filter_default_keeps_everything() {
+        let entries = vec!["ls".to_string(), "ls".to_string()];
+        assert_eq!(HistoryFilter::default().apply(entries.clone()), entries);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("ls", "ls"));
+        assert!(!glob_match("ls", "ls -la"));
+        assert!(glob_match("ls*", "ls -la"));
+        assert!(glob_match("git ?ush", "git push"));
+        assert!(glob_match("*secret*", "echo secret stuff"));
+        assert!(!glob_match("cd *", "cd"));
+    }
+}
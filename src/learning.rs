@@ -0,0 +1,118 @@
+//! Rule priorities learned from the correction log.
+//!
+//! Rules whose corrections are accepted often drift toward a higher priority
+//! (a lower number); rules that are mostly cancelled drift the other way. The
+//! adjustment grows logarithmically with the counts and is capped at
+//! `MAX_ADJUSTMENT`, so the built-in ordering can shift but not be inverted.
+//! Priority overrides from the config always win over learned adjustments.
+
+use crate::stats::{self, RuleStats, Stats};
+use crate::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Largest learned change to a rule's priority, in either direction.
+pub const MAX_ADJUSTMENT: i32 = 200;
+
+/// Priority points per unit of `ln(1 + count)`.
+const SCALE: f64 = 50.0;
+
+/// Computes the learned priority adjustment for a rule's acceptance counts.
+///
+/// Negative values raise the rule's priority.
+pub fn adjustment(stats: RuleStats) -> i32 {
+    let accepted = (1.0 + stats.accepted as f64).ln();
+    let cancelled = (1.0 + stats.cancelled as f64).ln();
+    let raw = (SCALE * (cancelled - accepted)).round() as i32;
+    raw.clamp(-MAX_ADJUSTMENT, MAX_ADJUSTMENT)
+}
+
+/// Learned priority adjustments per rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdaptivePriorities {
+    adjustments: BTreeMap<String, i32>,
+}
+
+impl AdaptivePriorities {
+    /// Computes adjustments from aggregated log statistics.
+    pub fn from_stats(stats: &Stats) -> Self {
+        let adjustments = stats
+            .rules
+            .iter()
+            .map(|(rule, counts)| (rule.clone(), adjustment(*counts)))
+            .filter(|(_, adjustment)| *adjustment != 0)
+            .collect();
+
+        Self { adjustments }
+    }
+
+    /// Loads adjustments from the correction log at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = stats::read_log(path)?;
+        Ok(Self::from_stats(&Stats::from_entries(&entries, [])))
+    }
+
+    /// Gets the learned adjustment for `rule`.
+    pub fn adjustment_for(&self, rule: &str) -> i32 {
+        self.adjustments.get(rule).copied().unwrap_or(0)
+    }
+
+    /// Gets the priority to use for `rule`: a config override if present,
+    /// otherwise the base priority plus the learned adjustment.
+    pub fn effective_priority(&self, rule: &str, base: i32, config_override: Option<i32>) -> i32 {
+        config_override.unwrap_or_else(|| base.saturating_add(self.adjustment_for(rule)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(accepted: usize, cancelled: usize) -> RuleStats {
+        RuleStats { accepted, cancelled }
+    }
+
+    #[test]
+    fn test_adjustment_is_zero_without_data() {
+        assert_eq!(adjustment(counts(0, 0)), 0);
+        assert_eq!(adjustment(counts(5, 5)), 0);
+    }
+
+    #[test]
+    fn test_adjustment_is_logarithmic() {
+        let ten = adjustment(counts(10, 0));
+        let hundred = adjustment(counts(100, 0));
+
+        assert!(ten < 0);
+        assert!(hundred < ten);
+        // Ten times the acceptances does not mean ten times the boost
+        assert!(hundred > ten * 2);
+    }
+
+    #[test]
+    fn test_adjustment_bounds() {
+        assert_eq!(adjustment(counts(1_000_000, 0)), -MAX_ADJUSTMENT);
+        assert_eq!(adjustment(counts(0, 1_000_000)), MAX_ADJUSTMENT);
+        assert!(adjustment(counts(1, 0)).abs() < MAX_ADJUSTMENT);
+    }
+
+    #[test]
+    fn test_effective_priority_precedence() {
+        let mut stats = Stats::default();
+        stats.rules.insert("git_push_force".to_string(), counts(1_000, 0));
+        let learned = AdaptivePriorities::from_stats(&stats);
+
+        // Learned adjustment applies on top of the base priority
+        assert_eq!(learned.effective_priority("git_push_force", 700, None), 500);
+        // Config overrides always win
+        assert_eq!(learned.effective_priority("git_push_force", 700, Some(900)), 900);
+        // Rules without data keep their base priority
+        assert_eq!(learned.effective_priority("mkdir_p", 100, None), 100);
+    }
+
+    #[test]
+    fn test_load_missing_log() {
+        let learned = AdaptivePriorities::load(Path::new("/nonexistent/ftf/corrections.jsonl")).unwrap();
+        assert_eq!(learned, AdaptivePriorities::default());
+    }
+}
@@ -1,17 +1,53 @@
-/// Fasterthefuck: A blazingly fast command correction engine written in Rust.
+//! Fasterthefuck: A blazingly fast command correction engine written in Rust.
 
 pub mod error;
+pub mod clipboard;
+pub mod compat;
+pub mod context;
+pub mod effects;
+pub mod env_detect;
+pub mod execution;
 pub mod types;
 pub mod corrector;
+pub mod corpus;
+pub mod daemon;
+pub mod diff;
 pub mod fuzzy;
+pub mod history;
+pub mod learning;
+pub mod messages;
+pub mod output;
+pub mod plugins;
+pub mod prefilter;
+pub mod progress;
 pub mod rules;
 pub mod shell;
 pub mod config;
+pub mod privilege;
+pub mod protocol;
+pub mod rejections;
+pub mod replay;
+pub mod render;
+pub mod selection;
+pub mod selector;
+pub mod rpc;
+pub mod script;
+pub mod stats;
+pub mod telemetry;
+pub mod undo;
 
-pub use error::{Error, Result};
-pub use types::{Command, CorrectedCommand, Rule};
-pub use corrector::Corrector;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+
+pub use error::{Error, Result, RuleBuildError, RuleBuildErrorKind};
+pub use effects::SideEffect;
+pub use execution::ExecutionPlan;
+pub use types::{Command, CorrectedCommand, FailureKind, Rule, Verdict};
+pub use corrector::{CorrectionSet, Corrector, RuleFailure};
 pub use fuzzy::FuzzyMatcher;
 pub use rules::{RuleRegistry, SimpleRuleBuilder, RegexRuleBuilder, FuzzyRuleBuilder};
-pub use shell::{Shell, BashShell, ShellOutput};
+pub use shell::{Shell, BashShell, HistoryIndex, SessionContext, ShellOutput};
+pub use history::HistoryEntry;
 pub use config::Config;
+pub use context::{ProjectType, RuleContext};
+pub use script::{Script, UnterminatedQuote};
@@ -1,145 +1,2409 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use fasterthefuck::{
-    Command, Config, Corrector, RuleRegistry,
-    rules::{
-        git, filesystem, permissions, package_managers,
-    },
+    BashShell, Command, Config, CorrectedCommand, CorrectionSet, Corrector, Error, ExecutionPlan, HistoryEntry,
+    ProjectType, RuleContext, RuleFailure, Script, SessionContext, Shell, SideEffect, fuzzy,
+    clipboard,
+    compat::thefuck,
+    corpus::{self, CorpusCase},
+    config::{ConfigDiagnostic, GlobalConfig, RulePackConfig, Severity, VALUE_PLACEHOLDER},
+    corrector::CorrectionEvent,
+    env_detect,
+    effects::{self, notify::LongRunNotifier, FsChange},
+    execution::{ExecutionReport, Step, StepFailure},
+    history::{append_history_entry, history_file_path},
+    learning::AdaptivePriorities,
+    messages::{self, Messages},
+    daemon::{self, Daemon},
+    diff::{self, Region},
+    output::{self, CappedOutput},
+    plugins::packs,
+    progress::{self, Spinner},
+    protocol,
+    rules::{self, Pattern, PatternKind, Reason, RuleInfo, RuleMetadata},
+    rejections::{self, Rejections},
+    replay::{self, Recording},
+    render::{self, Candidate, ColorMode, Renderer, Section, Theme},
+    rpc,
+    selection::CandidateList,
+    selector::{self, Layout},
+    shell::SessionEntry,
+    stats::{self, LogEntry, Outcome, Stats},
+    telemetry::{self, Event, HttpTransport, Spool},
+    undo::UndoRecord,
 };
-use std::io::{self, Write};
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Messages in the user's language, set once the config is loaded.
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+/// Gets the message catalog, in English until the config is loaded.
+fn messages() -> &'static Messages {
+    MESSAGES.get_or_init(Messages::english)
+}
+
+/// Gets the message `key` from the catalog, filling in `name = value` pairs.
+macro_rules! msg {
+    ($key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        messages().text($key, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*])
+    };
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "ftf")]
 #[command(about = "Faster version of 'thefuck' - automatic command correction")]
 #[command(version)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
     /// The command that failed
-    #[arg(long)]
-    command: String,
+    #[arg(long, required = true)]
+    command: Option<String>,
 
-    /// The output/error message from the failed command
-    #[arg(long)]
-    output: String,
+    /// The output/error message from the failed command, or `-` to read it
+    /// from stdin
+    #[arg(long, required_unless_present = "output_file")]
+    output: Option<OsString>,
+
+    /// Read the output of the failed command from a file, as raw bytes
+    #[arg(long, value_name = "PATH", conflicts_with = "output")]
+    output_file: Option<PathBuf>,
 
     /// The exit code from the failed command
-    #[arg(long)]
-    exit_code: i32,
+    #[arg(long, required = true)]
+    exit_code: Option<i32>,
 
     /// Skip interactive selection and just print first correction
     #[arg(long)]
     no_interaction: bool,
 
+    /// Print the top correction and nothing else, never prompting; the exit
+    /// status tells why nothing was printed
+    #[arg(long, conflicts_with = "execute")]
+    quiet: bool,
+
+    /// Show why each correction was offered, even with `show_reasons` off.
+    /// With --no-interaction they're listed on stderr, uncolored, before
+    /// the correction is printed
+    #[arg(long)]
+    explain: bool,
+
+    /// Print only the words the correction changes, with `diff_context`
+    /// words around them, instead of the whole command. The selector starts
+    /// in this view. What's printed can't be run, so no side effect runs
+    #[arg(long, conflicts_with_all = ["execute", "quiet"])]
+    diff: bool,
+
+    /// Also copy the selected correction to the clipboard, to paste it
+    /// elsewhere. In the selector, cN picks candidate N and copies it
+    #[arg(long)]
+    copy: bool,
+
+    /// After the results, print to stderr how many rules were tried and
+    /// matched, how long they took and how many corrections were left out.
+    /// Also on with `debug` in the config
+    #[arg(long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Execute the selected correction instead of printing it. Its output
+    /// is captured and shown once it finishes, and it can't read input
+    #[arg(long)]
+    execute: bool,
+
+    /// Execute chained corrections and side effects without confirmation
+    #[arg(long)]
+    yes: bool,
+
+    /// Preview the files the correction creates, moves or deletes, and ask
+    /// before executing it
+    #[arg(long)]
+    preview_fs: bool,
+
+    /// Roll back the completed steps of a chained correction that fails,
+    /// without asking
+    #[arg(long)]
+    auto_rollback: bool,
+
+    /// Apply the Nth correction of the list last shown in this terminal's
+    /// selector, without correcting the command again
+    #[arg(long, value_name = "N")]
+    apply_nth: Option<usize>,
+
+    /// Don't show a desktop notification when an executed correction takes
+    /// longer than `notify_after_secs`
+    #[arg(long)]
+    no_notify: bool,
+
+    /// Forward the correction to `ftf daemon`, and say so if it can't be
+    /// reached. Without this the daemon is used whenever its socket exists
+    #[arg(long)]
+    use_daemon: bool,
+
     /// Path to config file (defaults to ~/.config/fasterthefuck/config.toml)
     #[arg(long)]
     config: Option<String>,
+
+    /// Append a log of the rules tried, shell commands run and plugins
+    /// loaded to this file, at the config's `log_level`
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Accept thefuck's command line: the failed command as trailing
+    /// arguments, and `TF_*` and `THEFUCK_*` variables. Implied when ftf is
+    /// invoked as `thefuck`
+    #[arg(long)]
+    thefuck_compat: bool,
+
+    /// Color the correction selector: auto colors it when stderr is a
+    /// terminal, `color` is set in the config and NO_COLOR is unset
+    #[arg(long, value_enum, default_value_t = ColorWhen::Auto)]
+    color: ColorWhen,
+
+    /// Make the output depend on the arguments and config alone: no
+    /// timings, prompts, colors, terminal size, locale, shell history,
+    /// learned priorities, daemon or session state. For snapshot tests
+    #[arg(long, hide = true)]
+    deterministic: bool,
+
+    #[command(subcommand)]
+    subcommand: Option<Subcommands>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+#[derive(Subcommand, Debug)]
+enum Subcommands {
+    /// Show which rules have been accepted or cancelled, from the correction log
+    Stats,
+    /// Inspect the registered rules
+    Rules {
+        #[command(subcommand)]
+        action: RulesAction,
+    },
+    /// Check the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Answer corrections over a unix socket, building the rules' patterns
+    /// once instead of on every invocation
+    Daemon {
+        /// Number of requests answered at once
+        #[arg(long, default_value_t = 4)]
+        threads: usize,
+    },
+    /// Answer JSON-RPC 2.0 requests from an editor, one per line
+    Serve {
+        /// Read requests from stdin and write responses to stdout
+        #[arg(long)]
+        stdio: bool,
+    },
+    /// Show, send or delete the usage metrics enabled under [telemetry]
+    Telemetry {
+        #[command(subcommand)]
+        action: TelemetryAction,
+    },
+    /// Print the inverse of the most recent correction executed with --execute
+    Undo {
+        /// Run the inverse instead of printing it
+        #[arg(long)]
+        execute: bool,
+    },
+    /// Write an ftf config equivalent to thefuck's settings.py and THEFUCK_*
+    /// environment variables
+    ImportThefuck {
+        /// thefuck's settings file [default: ~/.config/thefuck/settings.py]
+        path: Option<PathBuf>,
+        /// Where to write the config, or `-` for stdout [default: ftf's
+        /// config file]
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Correct --command, --output and --exit-code as usual, and write them
+    /// with the probes rules ran on this machine to a file `ftf replay` reads
+    Record {
+        /// File to write the recording to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Correct a failure written by `ftf record` again, with the probes
+    /// answered from the recording instead of this machine
+    Replay {
+        /// The recording
+        file: PathBuf,
+        /// Show why each rule matched
+        #[arg(long)]
+        explain: bool,
+    },
+    /// Record failures and their corrections as regression cases
+    #[command(hide = true)]
+    Corpus {
+        #[command(subcommand)]
+        action: CorpusAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesAction {
+    /// List rules with their base, learned and effective priorities
+    List,
+    /// Print a rule's description, patterns and a worked example
+    Show {
+        /// The rule's name, as `ftf rules list` shows it
+        name: String,
+    },
+    /// Print every rule's patterns, description and example
+    Export {
+        /// Format to print the rules in
+        #[arg(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+    },
+    /// Download the rule packs listed under [rule_packs] in the config
+    Sync {
+        /// Only download the pack with this URL
+        url: Option<String>,
+    },
+    /// List pairs of rules that put different corrections first for the
+    /// same failures, from a corpus and inputs made from the rules' triggers
+    Conflicts {
+        /// Corpus file, or directory of them, to add to the inputs
+        /// [default: the local corpus, if any]
+        #[arg(long)]
+        corpus: Vec<PathBuf>,
+        /// Only list pairs that disagreed at least this many times
+        #[arg(long, default_value_t = 1)]
+        min_disagreements: usize,
+    },
+}
 
-    // Load configuration
-    let config = if let Some(config_path) = &args.config {
-        Config::load_from_file(std::path::Path::new(config_path))?
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// List mistakes in the config, such as misspelled rule names, and in
+    /// the synced rule packs; exits with 2 if a rule can't be built
+    Validate,
+}
+
+#[derive(Subcommand, Debug)]
+enum CorpusAction {
+    /// Append --command, --output and --exit-code, with the correction
+    /// accepted for them, to a local corpus file for tests/corpus/
+    Record {
+        /// The correction that was accepted [default: the first one
+        /// suggested]
+        #[arg(long)]
+        accepted: Option<String>,
+        /// Corpus file to append to [default: corpus.toml in ftf's data
+        /// directory]
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TelemetryAction {
+    /// Show whether metrics are sent, and how many are waiting
+    Status,
+    /// Send the waiting metrics now
+    Flush,
+    /// Delete the waiting metrics without sending them
+    Purge,
+}
+
+/// When to color the correction selector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    fn mode(self) -> ColorMode {
+        match self {
+            ColorWhen::Auto => ColorMode::Auto,
+            ColorWhen::Always => ColorMode::Always,
+            ColorWhen::Never => ColorMode::Never,
+        }
+    }
+}
+
+/// How ftf exits. Scripts rely on these codes, so every exit goes through
+/// here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExitStatus {
+    /// A correction was printed, or a subcommand succeeded
+    Success,
+    /// No correction was found, or none was picked
+    NoCorrection,
+    /// Bad arguments or config, or ftf itself failed
+    Error,
+    /// A correction was found but needs a confirmation that can't be given
+    Suppressed,
+    /// An executed command exited with this status
+    Executed(i32),
+}
+
+impl ExitStatus {
+    fn code(self) -> i32 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::NoCorrection => 1,
+            ExitStatus::Error => 2,
+            ExitStatus::Suppressed => 3,
+            ExitStatus::Executed(code) => code,
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        exit_code(status.code())
+    }
+}
+
+fn exit_code(code: i32) -> ExitCode {
+    // Shells only see the low byte; anything else is a plain failure
+    ExitCode::from(u8::try_from(code).unwrap_or(1))
+}
+
+/// Format of `ftf rules export`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ExportFormat {
+    Json,
+    Toml,
+}
+
+/// Runs ftf, and is the one place its errors are shown and turned into an
+/// exit code.
+fn main() -> ExitCode {
+    match run() {
+        Ok(status) => status.into(),
+        Err(e) => {
+            // Everything the error holds goes to the log, when there is one
+            tracing::debug!(error = ?e, "ftf failed");
+            eprintln!("{}", error_text(&e));
+            exit_code(e.exit_code())
+        }
+    }
+}
+
+/// Shows an error that stopped ftf, on stderr after `ftf: `.
+fn error_text(error: &Error) -> String {
+    let message = error.user_message();
+    if message.starts_with("ftf: ") {
+        message
+    } else {
+        format!("ftf: {}", message)
+    }
+}
+
+fn run() -> Result<ExitStatus, Error> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let mut args = match thefuck_args(&argv, |key| std::env::var(key).ok()) {
+        Some(native) => Args::parse_from(native),
+        None => Args::parse_from(argv),
+    };
+
+    // Load configuration. A broken config passed on the command line is an
+    // error; a broken default one is reported once messages are loaded
+    let (mut config, config_error) = match &args.config {
+        Some(config_path) => (Config::load_from_file(Path::new(config_path))?, None),
+        None => match Config::load_default() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        },
+    };
+    if args.thefuck_compat {
+        apply_thefuck_env(&mut config)?;
+    }
+    init_messages(&config, args.deterministic);
+    if let Some(e) = config_error {
+        eprintln!("ftf: {}", msg!("config_ignored", error = e.user_message()));
+    }
+    if let Some(path) = &args.log_file {
+        init_logging(path, &config.global)?;
+    }
+    // Like thefuck's `require_confirmation = False`, take the first correction
+    if !config.global.interactive || args.quiet || args.deterministic {
+        args.no_interaction = true;
+    }
+
+    // Nobody can answer a prompt in CI or with redirected input, so never
+    // block on one there, and only execute when told to with --yes
+    let would_prompt = !args.no_interaction || (args.execute && !args.yes);
+    if let Some(reason) = would_prompt.then(env_detect::non_interactive_reason).flatten() {
+        let not_executed = args.execute && !args.yes;
+        let notice = if not_executed {
+            msg!("non_interactive_not_executed", reason = reason)
+        } else {
+            msg!("non_interactive", reason = reason)
+        };
+        eprintln!("ftf: {}", notice);
+        args.no_interaction = true;
+        args.execute &= args.yes;
+    }
+
+    let context = if args.deterministic {
+        RuleContext::new(Box::new(BashShell::new()?))
+            .with_history(Vec::new())
+            .with_session(SessionContext::default())
+            .with_project_types(HashSet::new())
     } else {
-        Config::load_default().unwrap_or_default()
+        RuleContext::new(Box::new(BashShell::new()?))
+    };
+    // What rules learn from the shell goes into the recording
+    let context = match args.subcommand {
+        Some(Subcommands::Record { .. }) => context.recording_probes(),
+        _ => context,
     };
+    let context = Arc::new(context);
+    let factories = config.factories(&context);
+    let learned = stats::default_log_path()
+        .filter(|_| !args.deterministic)
+        .and_then(|path| AdaptivePriorities::load(&path).ok())
+        .unwrap_or_default();
+    let clock = if args.deterministic { Clock::Frozen } else { Clock::System };
 
-    // Create rule registry and populate with all available rules
-    let mut registry = RuleRegistry::new();
+    // Subcommands only need rule names, which are listed without building
+    // any rule
+    let enabled_rules = || {
+        factories
+            .iter()
+            .flat_map(|factory| factory.rules())
+            .filter(|(name, _)| config.is_rule_enabled(name))
+            .copied()
+    };
 
-    // Add all git rules
-    let mut git_rules = git::git_branch_rules();
-    git_rules.extend(git::git_push_pull_rules());
-    git_rules.extend(git::git_staging_rules());
-    registry.add_rules(filter_rules_by_config(git_rules, &config));
+    // Mistakes in the config are pointed out once on every run, unless
+    // asked for with `ftf config validate`
+    let known_rules: Vec<&str> = factories.iter().flat_map(|factory| factory.rules()).map(|(name, _)| *name).collect();
+    let validating = matches!(args.subcommand, Some(Subcommands::Config { .. }));
+    if !config.global.quiet_config_warnings && !args.quiet && !validating {
+        for diagnostic in config.validate(&known_rules) {
+            eprintln!("ftf: {}", diagnostic_text(messages(), &diagnostic));
+        }
+    }
+    if let Some(subcommand) = args.subcommand.take() {
+        match subcommand {
+            Subcommands::Stats => print_stats(enabled_rules().map(|(name, _)| name))?,
+            Subcommands::Config {
+                action: ConfigAction::Validate,
+            } => return Ok(validate_config(&config, &known_rules)),
+            Subcommands::Rules {
+                action: RulesAction::List,
+            } => print_rules(enabled_rules(), &config, &learned)?,
+            Subcommands::Rules {
+                action: RulesAction::Show { name },
+            } => {
+                let catalog = rules::metadata::export(factories);
+                let Some(rule) = catalog.rules.iter().find(|rule| rule.name == name) else {
+                    let names: Vec<&str> = catalog.rules.iter().map(|rule| rule.name.as_str()).collect();
+                    return Err(Error::other(match fasterthefuck::config::suggest(&name, &names) {
+                        Some(suggestion) => msg!("rules_show_unknown_suggest", name = name, suggestion = suggestion),
+                        None => msg!("rules_show_unknown", name = name),
+                    }));
+                };
+                print!("{}", rule_doc(messages(), rule));
+            }
+            Subcommands::Rules {
+                action: RulesAction::Export { format },
+            } => {
+                let catalog = rules::metadata::export(factories);
+                match format {
+                    ExportFormat::Json => print!("{}", catalog.to_json()?),
+                    ExportFormat::Toml => print!("{}", catalog.to_toml()?),
+                }
+            }
+            Subcommands::Rules {
+                action: RulesAction::Sync { url },
+            } => sync_rule_packs(&config, url.as_deref())?,
+            Subcommands::Rules {
+                action: RulesAction::Conflicts { corpus, min_disagreements },
+            } => print_rule_conflicts(&config, corpus, min_disagreements)?,
+            Subcommands::Telemetry { action } => manage_telemetry(&config, action)?,
+            Subcommands::Undo { execute } => return undo_last(&context, execute),
+            Subcommands::Daemon { threads } => run_daemon(&config, threads)?,
+            Subcommands::Serve { stdio } => {
+                if !stdio {
+                    return Err(Error::other(msg!("serve_needs_stdio")));
+                }
+                let mut server = rpc::Server::new(&config, learned);
+                rpc::serve(io::stdin().lock(), io::stdout().lock(), |method, params| server.handle(method, params))?;
+            }
+            Subcommands::ImportThefuck { path, output, force } => {
+                let known: Vec<&str> = factories
+                    .iter()
+                    .flat_map(|factory| factory.rules())
+                    .map(|(name, _)| *name)
+                    .collect();
+                import_thefuck(path, output, force, &known)?;
+            }
+            Subcommands::Record { out } => {
+                let corrector = || {
+                    let script = args.command.as_deref().unwrap_or_default();
+                    Corrector::for_command(factories, &config, &learned, &context, script)
+                };
+                return record_invocation(&args, &config, &context, &out, corrector);
+            }
+            Subcommands::Replay { file, explain } => return replay_invocation(&config, &file, explain),
+            Subcommands::Corpus {
+                action: CorpusAction::Record { accepted, file },
+            } => {
+                let corrector = || {
+                    let script = args.command.as_deref().unwrap_or_default();
+                    Corrector::for_command(factories, &config, &learned, &context, script)
+                };
+                record_corpus_case(&args, accepted, file, corrector)?;
+            }
+        }
+        return Ok(ExitStatus::Success);
+    }
 
-    // Add all filesystem rules
-    registry.add_rules(filter_rules_by_config(
-        filesystem::filesystem_rules(),
-        &config,
-    ));
+    let script = args.command.take().unwrap_or_default();
+    let exit_code = args.exit_code.unwrap_or_default();
 
-    // Add all permission rules
-    registry.add_rules(filter_rules_by_config(
-        permissions::permission_rules(),
-        &config,
-    ));
+    if let Some(n) = args.apply_nth {
+        let dir = session_dir().ok_or_else(|| Error::other(msg!("no_session_dir")))?;
+        let list = CandidateList::load(&dir)?.ok_or_else(|| Error::other(msg!("no_candidate_list")))?;
+        let correction = list.nth(&script, n)?;
+        // Only executing needs the rules, to find the correction's undo
+        let corrector =
+            args.execute.then(|| Corrector::for_command(factories, &config, &learned, &context, &script));
+        return apply_correction(Some(correction), &script, &args, &config, &context, corrector.as_ref());
+    }
+    let started = Instant::now();
 
-    // Add all package manager rules
-    registry.add_rules(filter_rules_by_config(
-        package_managers::package_manager_rules(),
-        &config,
-    ));
+    // A running daemon has the rules' patterns compiled already. Executed
+    // corrections need the rules themselves, and a config file given on the
+    // command line isn't the daemon's, so those are always found here.
+    let socket = daemon::socket_path().filter(|path| {
+        !args.execute && !args.deterministic && args.config.is_none() && (args.use_daemon || path.exists())
+    });
+    let mut stdin_output = None;
+    let mut from_daemon = None;
+    if let Some(socket) = socket {
+        // Capped before sending, since the daemon refuses longer requests
+        let output = read_output(&args, config.global.max_output_bytes)?;
+        let request = daemon::Request::new(&script, &output.text, exit_code, std::env::current_dir()?)
+            .with_env(std::env::vars().collect())
+            .with_config_hash(replay::config_hash(&config));
+        match daemon::query(&socket, &request) {
+            Ok(corrections) => {
+                let command = Command {
+                    output_truncated: output.truncated,
+                    output_had_invalid_utf8: output.invalid_utf8,
+                    ..Command::new(&script, output.text, exit_code)
+                };
+                from_daemon = Some((command, corrections));
+            }
+            Err(e) => {
+                if args.use_daemon {
+                    eprintln!("ftf: {}", msg!("daemon_unavailable", error = e.user_message()));
+                }
+                stdin_output = Some(output);
+            }
+        }
+    }
 
-    // Create corrector and evaluate
-    let corrector: Corrector = registry.into();
-    let cmd = Command {
-        script: args.command,
-        output: args.output,
-        exit_code: args.exit_code,
+    let by_daemon = from_daemon.is_some();
+    let (cmd, mut set, corrector) = match from_daemon {
+        Some((cmd, corrections)) => {
+            let set = CorrectionSet {
+                corrections,
+                elapsed: clock.measure(started.elapsed()),
+                ..CorrectionSet::default()
+            };
+            (cmd, set, None)
+        }
+        None => {
+            // Only the rule groups that could match the command are built
+            let corrector = Corrector::for_command(factories, &config, &learned, &context, &script);
+            // Long outputs keep their head and tail, unless a rule needs all of it
+            let max_output_bytes = corrector.max_output_bytes(config.global.max_output_bytes);
+            let output = match stdin_output {
+                Some(read) => {
+                    let capped = output::cap(&read.text, max_output_bytes);
+                    CappedOutput {
+                        truncated: read.truncated || capped.truncated,
+                        invalid_utf8: read.invalid_utf8,
+                        ..capped
+                    }
+                }
+                None => read_output(&args, max_output_bytes)?,
+            };
+            let cmd = Command {
+                script,
+                output: output.text,
+                exit_code,
+                output_truncated: output.truncated,
+                output_had_invalid_utf8: output.invalid_utf8,
+            };
+            let show_progress = !args.quiet && !args.deterministic && io::stderr().is_terminal();
+            let set = find_corrections(&corrector, &cmd, show_progress);
+            (cmd, set, Some(corrector))
+        }
     };
+    let elapsed = clock.measure(started.elapsed());
+    set.elapsed = clock.measure(set.elapsed);
 
-    let corrections = corrector.get_corrections(&cmd);
+    // A rule that panicked was skipped; name it, so it can be turned off
+    if !args.quiet {
+        for failure in &set.failures {
+            eprintln!("ftf: {}", failure_text(messages(), failure));
+        }
+    }
+
+    // Drop corrections the user rejected for this command a moment ago
+    let rejection_window = config.global.rejection_window_minutes * 60;
+    let session_dir = session_dir().filter(|_| !args.deterministic);
+    if let Some(dir) = &session_dir {
+        let rejections = Rejections::load(dir);
+        set.filter(|corrections| rejections.filter(&cmd.script, corrections, rejections::now(), rejection_window));
+    }
+    let mut corrections = std::mem::take(&mut set.corrections);
+
+    // Fall back to similar history entries when no rule matched. These are
+    // guesses, so they are only ever offered through the selector.
+    let from_history = corrections.is_empty() && config.global.history_fallback && !args.no_interaction;
+    if from_history {
+        corrections = history_fallback(&context, &cmd.script);
+    }
+
+    // Side effects ftf runs itself, such as deleting a host key, are never
+    // run unasked
+    let unconfirmed_effect = !args.yes
+        && corrections.first().is_some_and(|correction| {
+            correction
+                .side_effect
+                .as_ref()
+                .is_some_and(|effect| !effect.runs_in_hook())
+        });
 
     // Handle different correction scenarios
-    match corrections.len() {
-        0 => {
-            // No corrections found
-            std::process::exit(1);
-        }
-        1 => {
-            // Single correction - print and exit
-            println!("{}", corrections[0].script);
-            std::process::exit(0);
-        }
-        _ => {
-            // Multiple corrections - interactive selection or print first
-            if args.no_interaction {
-                println!("{}", corrections[0].script);
-                std::process::exit(0);
+    let needs_choice = from_history
+        || unconfirmed_effect
+        || corrections
+            .first()
+            .is_some_and(|correction| config.requires_confirmation(&correction.script));
+    let interactive = (corrections.len() > 1 || needs_choice) && !args.no_interaction;
+
+    // Why each rule matched, shown under its candidate
+    let explain = args.explain && args.no_interaction && !args.quiet;
+    let reasons = if (interactive && (config.global.show_reasons || args.explain)) || explain {
+        correction_reasons(messages(), corrector.as_ref(), &cmd, &corrections)
+    } else {
+        Vec::new()
+    };
+    let views: Vec<_> = (0..corrections.len())
+        .map(|i| View {
+            reason: reasons.get(i).cloned().flatten(),
+            diff: args.diff,
+        })
+        .collect();
+    if explain && !corrections.is_empty() {
+        let width = if args.deterministic { render::DEFAULT_WIDTH } else { render::terminal_width() };
+        let renderer = Renderer::new(Theme::default(), false)
+            .with_width(width)
+            .with_diff_context(config.global.diff_context);
+        eprint!("{}", explanation_text(messages(), &renderer, &corrections, &views, &cmd.script, &config));
+    }
+
+    let mut suppressed = false;
+    let mut copy = false;
+    let selected = match corrections.len() {
+        // No corrections found
+        0 => None,
+        // Dangerous corrections are never picked automatically
+        _ if needs_choice && args.no_interaction => {
+            match corrections[0].side_effect.as_ref().filter(|_| unconfirmed_effect) {
+                _ if args.quiet => {}
+                Some(effect) => eprintln!(
+                    "ftf: {}",
+                    msg!("needs_side_effect", script = corrections[0].script, effect = effect)
+                ),
+                None => eprintln!("ftf: {}", msg!("needs_confirmation", script = corrections[0].script)),
+            }
+            suppressed = true;
+            None
+        }
+        // History fallbacks, side effects and dangerous corrections always
+        // need confirmation
+        _ if needs_choice => {
+            select_correction_interactive(&corrections, views, from_history, &cmd.script, &config, args.color)
+                .map(|(correction, copied)| {
+                    copy = copied;
+                    correction
+                })
+        }
+        // Single correction - use it directly
+        1 => Some(&corrections[0]),
+        // Multiple corrections - interactive selection or first
+        _ if args.no_interaction => Some(&corrections[0]),
+        _ => select_correction_interactive(&corrections, views, false, &cmd.script, &config, args.color).map(
+            |(correction, copied)| {
+                copy = copied;
+                correction
+            },
+        ),
+    };
+    args.copy |= copy;
+
+    // Keep the list as numbered, for --apply-nth
+    if let Some(dir) = session_dir.as_ref().filter(|_| interactive) {
+        if let Err(e) = CandidateList::new(&cmd.script, &corrections, rejections::now()).save(dir) {
+            eprintln!("ftf: {}", msg!("candidates_not_saved", error = e.user_message()));
+        }
+    }
+
+    // Remember what the user passed over in the selector
+    if let Some(dir) = session_dir.as_ref().filter(|_| interactive && rejection_window > 0) {
+        let rejected = corrections
+            .iter()
+            .take_while(|correction| selected.is_none_or(|chosen| !std::ptr::eq(*correction, chosen)))
+            .map(|correction| correction.script.as_str());
+        if let Err(e) = Rejections::record(dir, &cmd.script, rejected, rejections::now()) {
+            eprintln!("ftf: {}", msg!("rejections_not_recorded", error = e.user_message()));
+        }
+    }
+
+    if config.global.log_corrections {
+        log_correction(&cmd, &corrections, selected);
+    }
+
+    let status = if suppressed {
+        Ok(ExitStatus::Suppressed)
+    } else {
+        apply_correction(selected, &cmd.script, &args, &config, &context, corrector.as_ref())
+    };
+    // Only once the correction is out, so metrics never delay it
+    if config.telemetry.active_endpoint().is_some() {
+        record_telemetry(args.config.as_deref(), config.telemetry.batch_size, &corrections, selected, elapsed);
+    }
+    let status = status?;
+    if (args.verbose || config.global.debug) && !args.quiet {
+        // The top correction is all that was suppressed for confirmation
+        set.corrections = corrections;
+        set.suppressed += usize::from(suppressed);
+        eprintln!("ftf: {}", summary_text(messages(), &set, by_daemon));
+    }
+    Ok(status)
+}
+
+/// Lists the mistakes in `config` and its synced rule packs on stderr.
+/// Fails if any is an error.
+fn validate_config(config: &Config, known_rules: &[&str]) -> ExitStatus {
+    let mut diagnostics = config.validate(known_rules);
+    if let Some(dir) = packs::default_dir() {
+        diagnostics.extend(config.validate_rule_packs(known_rules, &dir));
+    }
+    if diagnostics.is_empty() {
+        println!("{}", msg!("config_valid"));
+    }
+    for diagnostic in &diagnostics {
+        eprintln!("ftf: {}", diagnostic_text(messages(), diagnostic));
+    }
+    if diagnostics.iter().any(|diagnostic| diagnostic.severity() == Severity::Error) {
+        ExitStatus::Error
+    } else {
+        ExitStatus::Success
+    }
+}
+
+/// Shows a config mistake as a warning or an error.
+fn diagnostic_text(messages: &Messages, diagnostic: &ConfigDiagnostic) -> String {
+    let key = match diagnostic.severity() {
+        Severity::Warning => "config_warning",
+        Severity::Error => "config_error",
+    };
+    messages.text(key, &[("diagnostic", diagnostic)])
+}
+
+/// Warns that a rule panicked and was skipped, and says how to turn it off.
+fn failure_text(messages: &Messages, failure: &RuleFailure) -> String {
+    messages.text("rule_failed", &[("rule", &failure.rule), ("error", &failure.payload)])
+}
+
+/// Sums up how correcting went, for `--verbose`. The daemon doesn't say
+/// which rules it tried.
+fn summary_text(messages: &Messages, set: &CorrectionSet, by_daemon: bool) -> String {
+    let elapsed = elapsed_text(set.elapsed);
+    let (suggestions, suppressed) = (set.corrections.len(), set.suppressed);
+    if by_daemon {
+        messages.text(
+            "summary_daemon",
+            &[("elapsed", &elapsed), ("suggestions", &suggestions), ("suppressed", &suppressed)],
+        )
+    } else {
+        messages.text(
+            "summary",
+            &[
+                ("evaluated", &set.evaluated),
+                ("matched", &set.matched),
+                ("elapsed", &elapsed),
+                ("suggestions", &suggestions),
+                ("suppressed", &suppressed),
+            ],
+        )
+    }
+}
+
+/// Where `run` takes elapsed times from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clock {
+    System,
+    /// Every duration is zero, so output doesn't depend on how fast the
+    /// machine is
+    Frozen,
+}
+
+impl Clock {
+    /// Gets `elapsed` as this clock tells it.
+    fn measure(self, elapsed: Duration) -> Duration {
+        match self {
+            Clock::System => elapsed,
+            Clock::Frozen => Duration::ZERO,
+        }
+    }
+}
+
+/// Shows a duration in milliseconds to a tenth, or in seconds from one.
+fn elapsed_text(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{:.1}ms", elapsed.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", elapsed.as_secs_f64())
+    }
+}
+
+/// Prints or executes `selected`, the correction of `original`, asking
+/// first for a placeholder's value. `corrector` finds the undo of an
+/// executed correction.
+fn apply_correction(
+    selected: Option<&CorrectedCommand>,
+    original: &str,
+    args: &Args,
+    config: &Config,
+    context: &RuleContext,
+    corrector: Option<&Corrector>,
+) -> Result<ExitStatus, Error> {
+    // Corrections with a placeholder are only picked in the selector; ask for the value
+    let filled = selected
+        .filter(|correction| correction.script.contains(VALUE_PLACEHOLDER))
+        .map(prompt_for_value);
+    let selected = match &filled {
+        Some(filled) => filled.as_ref(),
+        None => selected,
+    };
+
+    // Copied whatever's done with it next, to be pasted elsewhere
+    if let Some(correction) = selected.filter(|_| args.copy) {
+        copy_to_clipboard(context, &correction.script, args.quiet);
+    }
+
+    match selected {
+        Some(correction) if args.execute => {
+            let plan = ExecutionPlan::new(correction);
+            let fs_changes = args
+                .preview_fs
+                .then(|| context.shell().and_then(|shell| shell.cwd().ok()))
+                .flatten()
+                .map(|cwd| effects::plan(&correction.script, &cwd));
+            let needs_confirmation = (plan.is_chained() && !args.yes) || fs_changes.is_some();
+            if needs_confirmation && !confirm_plan(&plan, fs_changes.as_deref(), args.no_interaction) {
+                return Ok(if args.no_interaction { ExitStatus::Suppressed } else { ExitStatus::NoCorrection });
+            }
+
+            let rule = corrector
+                .into_iter()
+                .flat_map(Corrector::rules)
+                .find(|rule| Some(rule.name()) == correction.rule.as_deref());
+            // Without the directory it runs in, a `mv` can't be inverted safely
+            let cwd = context.shell().and_then(|shell| shell.cwd().ok());
+            let undo = rule.zip(cwd).and_then(|(rule, cwd)| rule.undo_for(&correction.script, &cwd));
+            let rollback = if args.auto_rollback {
+                Rollback::Run
+            } else if args.no_interaction {
+                Rollback::Save
             } else {
-                if let Some(selected) = select_correction_interactive(&corrections) {
-                    println!("{}", selected);
-                    std::process::exit(0);
-                } else {
-                    // User cancelled or no selection
-                    std::process::exit(1);
+                Rollback::Ask
+            };
+            let notifier = (!args.no_notify && config.global.notify_after_secs > 0)
+                .then(|| LongRunNotifier::desktop(Duration::from_secs(config.global.notify_after_secs)));
+            let exit_code =
+                execute_correction(context, &correction.script, &plan, undo, rollback, notifier.as_ref())?;
+
+            // ftf runs in a child process and can't reach the user's shell
+            if correction.side_effect.as_ref().is_some_and(SideEffect::runs_in_hook) && exit_code == 0 {
+                eprintln!("ftf: {}", msg!("rehash_hint"));
+            }
+            Ok(ExitStatus::Executed(exit_code))
+        }
+        // Only what changed, for reading rather than running
+        Some(correction) if args.diff => {
+            match Region::new(&diff::diff(original, &correction.script), config.global.diff_context) {
+                Some(region) => println!("{}", region),
+                None => println!("{}", correction.script),
+            }
+            Ok(ExitStatus::Success)
+        }
+        // Only the script, for scripts reading a single line
+        Some(correction) if args.quiet => {
+            println!("{}", correction.script);
+            Ok(ExitStatus::Success)
+        }
+        Some(correction) => {
+            // The shell alias evaluates the printed script, so the effect runs first
+            if let Some(effect) = &correction.side_effect {
+                if let Err(e) = run_side_effect(context, effect) {
+                    let error = e.user_message();
+                    eprintln!("ftf: {}", msg!("side_effect_failed", effect = effect.name(), error = error));
+                    return Ok(ExitStatus::Error);
+                }
+            }
+            println!("{}", protocol::encode(correction));
+            Ok(ExitStatus::Success)
+        }
+        // No corrections, or the user cancelled
+        None => Ok(ExitStatus::NoCorrection),
+    }
+}
+
+/// Puts `script` on the clipboard, saying so unless `quiet`. Failing to copy
+/// is only a warning: the correction is still printed or executed.
+fn copy_to_clipboard(context: &RuleContext, script: &str, quiet: bool) {
+    let backend = context.shell().map_or(clipboard::Backend::Osc52, clipboard::Backend::probe);
+    // OSC 52 goes to stderr, as stdout is read by the shell alias
+    match backend.copy(script, &mut io::stderr()) {
+        Ok(()) if quiet => {}
+        Ok(()) => eprintln!("ftf: {}", msg!("copied", backend = backend)),
+        Err(e) => eprintln!("ftf: {}", msg!("copy_failed", backend = backend, error = e.user_message())),
+    }
+}
+
+/// The placeholder thefuck's alias puts before the alias's own arguments.
+const THEFUCK_ARGUMENT_PLACEHOLDER: &str = "THEFUCK_ARGUMENT_PLACEHOLDER";
+
+/// ftf options that take a value, passed through in thefuck compat mode.
+const VALUE_OPTIONS: &[&str] = &["--command", "--output", "--exit-code", "--config"];
+
+/// The `THEFUCK_*` variables honored in thefuck compat mode.
+const THEFUCK_COMPAT_VARIABLES: &[&str] = &["THEFUCK_REQUIRE_CONFIRMATION", "THEFUCK_NO_COLORS"];
+
+/// Rewrites a thefuck-style invocation, such as `thefuck -y git pus`, into
+/// ftf's own arguments. Returns `None` unless ftf was invoked as `thefuck` or
+/// with `--thefuck-compat` before the failed command.
+///
+/// The failed command is the trailing arguments joined by spaces, or else
+/// the last line of `TF_HISTORY` that isn't a call to the `TF_ALIAS` alias.
+/// Its output and exit code come from `TF_OUTPUT` and `TF_EXIT_CODE` unless
+/// `--output` and `--exit-code` are given; a missing exit code is 1. ftf
+/// corrects as bash whatever `TF_SHELL` says.
+fn thefuck_args(argv: &[OsString], env: impl Fn(&str) -> Option<String>) -> Option<Vec<OsString>> {
+    let (arg0, rest) = argv.split_first()?;
+    let mut compat = Path::new(arg0).file_name().is_some_and(|name| name == "thefuck");
+    let mut native = vec![arg0.clone(), OsString::from("--thefuck-compat")];
+    let mut yes = false;
+    let mut command: Vec<String> = Vec::new();
+
+    let mut rest = rest.iter().filter(|arg| *arg != THEFUCK_ARGUMENT_PLACEHOLDER);
+    while let Some(arg) = rest.next() {
+        let text = arg.to_string_lossy();
+        match text.as_ref() {
+            "--thefuck-compat" => compat = true,
+            "-y" | "--yes" => yes = true,
+            // thefuck's own options with no ftf equivalent
+            "-r" | "--repeat" | "-d" | "--debug" => {}
+            "--" => command.extend(rest.by_ref().map(|arg| arg.to_string_lossy().into_owned())),
+            option if VALUE_OPTIONS.contains(&option) => {
+                native.push(arg.clone());
+                native.extend(rest.next().cloned());
+            }
+            option if option.starts_with('-') => native.push(arg.clone()),
+            _ => {
+                command.push(text.into_owned());
+                command.extend(rest.by_ref().map(|arg| arg.to_string_lossy().into_owned()));
+            }
+        }
+    }
+    if !compat {
+        return None;
+    }
+
+    let given = |name: &str| {
+        native.iter().any(|arg| {
+            let arg = arg.to_string_lossy();
+            arg == name || arg.strip_prefix(name).is_some_and(|value| value.starts_with('='))
+        })
+    };
+    let mut defaults: Vec<String> = Vec::new();
+    if yes {
+        defaults.extend(["--no-interaction".to_string(), "--yes".to_string()]);
+    }
+    if !given("--command") {
+        let script = if command.is_empty() { last_thefuck_command(&env) } else { Some(command.join(" ")) };
+        defaults.extend(script.map(|script| format!("--command={}", script)));
+    }
+    if !given("--output") {
+        defaults.push(format!("--output={}", env("TF_OUTPUT").unwrap_or_default()));
+    }
+    if !given("--exit-code") {
+        defaults.push(format!("--exit-code={}", env("TF_EXIT_CODE").as_deref().unwrap_or("1")));
+    }
+    native.extend(defaults.into_iter().map(OsString::from));
+    Some(native)
+}
+
+/// Gets the last command of `TF_HISTORY`, skipping calls to the `TF_ALIAS`
+/// alias (`fuck` by default).
+fn last_thefuck_command(env: &impl Fn(&str) -> Option<String>) -> Option<String> {
+    let alias = env("TF_ALIAS").unwrap_or_else(|| "fuck".to_string());
+    let history = env("TF_HISTORY")?;
+    history
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| {
+            let call = line.strip_prefix(alias.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with(' '));
+            !line.is_empty() && !call
+        })
+        .map(str::to_string)
+}
+
+/// Applies the `THEFUCK_*` variables honored in thefuck compat mode over
+/// `config`.
+fn apply_thefuck_env(config: &mut Config) -> Result<(), Error> {
+    let mut settings = thefuck::Settings::default();
+    settings.apply_env(std::env::vars().filter(|(name, _)| THEFUCK_COMPAT_VARIABLES.contains(&name.as_str())))?;
+    if let Some(confirm) = settings.require_confirmation {
+        config.global.interactive = confirm;
+    }
+    // The settings parser ignores no_colors, which only affects the selector
+    if std::env::var("THEFUCK_NO_COLORS").is_ok_and(|value| value.eq_ignore_ascii_case("true")) {
+        config.global.color = false;
+    }
+    Ok(())
+}
+
+/// Loads the messages for the user's locale, or the default ones if
+/// `default_locale` is set, with the config's overrides. A broken locale
+/// file is reported and left out.
+fn init_messages(config: &Config, default_locale: bool) {
+    let locale = messages::locale(|key| std::env::var(key).ok()).filter(|_| !default_locale);
+    let dir = messages::default_dir();
+    let (loaded, error) = match Messages::load(&config.messages, locale.as_deref(), dir.as_deref()) {
+        Ok(loaded) => (loaded, None),
+        Err(e) => (Messages::load(&config.messages, locale.as_deref(), None).unwrap_or_default(), Some(e)),
+    };
+    let _ = MESSAGES.set(loaded);
+
+    if let Some(e) = error {
+        eprintln!("ftf: {}", msg!("locale_not_loaded", error = e.user_message()));
+    }
+    for key in Messages::unknown_keys(&config.messages) {
+        eprintln!("ftf: {}", msg!("unknown_message", key = key));
+    }
+}
+
+/// Logs to the file at `path` at the configured level, so stderr stays
+/// clean.
+fn init_logging(path: &Path, global: &GlobalConfig) -> Result<(), Error> {
+    let level = match &global.log_level {
+        Some(level) => level
+            .parse::<LevelFilter>()
+            .map_err(|_| Error::other(msg!("unknown_log_level", level = level)))?,
+        None if global.debug => LevelFilter::DEBUG,
+        None => LevelFilter::INFO,
+    };
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+    Ok(())
+}
+
+/// Set by SIGINT and SIGTERM to stop the daemon.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_: std::os::raw::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Runs the daemon until it's interrupted or terminated, then removes its
+/// socket.
+fn run_daemon(config: &Config, threads: usize) -> Result<(), Error> {
+    let path = daemon::socket_path().ok_or_else(|| Error::other(msg!("no_runtime_dir")))?;
+    let daemon = Daemon::bind(&path)?
+        .with_config_hash(replay::config_hash(config))
+        .with_max_output_bytes(config.global.max_output_bytes);
+
+    let action = SigAction::new(SigHandler::Handler(request_shutdown), SaFlags::empty(), SigSet::empty());
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        // SAFETY: the handler only stores to an atomic
+        unsafe { sigaction(signal, &action) }.map_err(io::Error::from)?;
+    }
+
+    eprintln!("ftf: {}", msg!("daemon_listening", path = path.display()));
+    daemon.serve(threads, &SHUTDOWN, |request| daemon::correct(config, request))?;
+    Ok(())
+}
+
+/// Downloads the configured rule packs, or only the one with `url`.
+fn sync_rule_packs(config: &Config, url: Option<&str>) -> Result<(), Error> {
+    let dir = packs::default_dir().ok_or_else(|| Error::other(msg!("no_config_dir")))?;
+    let mut selected: Vec<(&String, &RulePackConfig)> = config
+        .rule_packs
+        .iter()
+        .filter(|(_, pack)| url.map_or(pack.enabled, |url| pack.url == url))
+        .collect();
+    selected.sort_by_key(|(name, _)| *name);
+    if selected.is_empty() {
+        return Err(Error::other(match url {
+            Some(url) => msg!("no_pack_with_url", url = url),
+            None => msg!("no_packs_enabled"),
+        }));
+    }
+
+    let mut failed = 0;
+    for (name, pack) in selected {
+        match packs::sync(&dir, name, pack, &config.pattern_limits()) {
+            Ok(packs::SyncOutcome::Updated { rules }) => {
+                eprintln!("ftf: {}", msg!("pack_synced", name = name, rules = rules))
+            }
+            Ok(packs::SyncOutcome::Unchanged) => eprintln!("ftf: {}", msg!("pack_unchanged", name = name)),
+            Err(e) => {
+                eprintln!("ftf: {}", msg!("pack_not_synced", name = name, error = e.user_message()));
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Error::other(msg!("packs_not_synced", count = failed)));
+    }
+    Ok(())
+}
+
+/// Imports thefuck's settings from `path` and the environment, and writes
+/// the config to `output`.
+fn import_thefuck(
+    path: Option<PathBuf>,
+    output: Option<PathBuf>,
+    force: bool,
+    known: &[&str],
+) -> Result<(), Error> {
+    // Without an explicit path, the environment alone is enough
+    let explicit = path.is_some();
+    let path = path.or_else(thefuck::default_settings_path);
+    let mut settings = match path.as_deref().filter(|path| explicit || path.exists()) {
+        Some(path) => thefuck::Settings::parse(&std::fs::read_to_string(path)?)?,
+        None => thefuck::Settings::default(),
+    };
+    settings.apply_env(std::env::vars())?;
+    if settings.is_empty() {
+        return Err(Error::other(msg!("no_thefuck_settings")));
+    }
+
+    let import = thefuck::import(&settings, known);
+    for warning in import.warnings() {
+        eprintln!("ftf: {}", warning);
+    }
+
+    let output = match output {
+        Some(output) => output,
+        None => Config::default_config_path()?,
+    };
+    if output.as_os_str() == "-" {
+        print!("{}", import.to_toml());
+        return Ok(());
+    }
+    if output.exists() && !force {
+        return Err(Error::other(msg!("config_exists", path = output.display())));
+    }
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&output, import.to_toml())?;
+    eprintln!("ftf: {}", msg!("config_written", path = output.display()));
+    Ok(())
+}
+
+/// Reads the failed command's output, keeping at most `max_bytes` of it,
+/// from `--output-file`, stdin for `--output -`, or `--output` itself. All
+/// three are taken as bytes, so output that isn't UTF-8 reaches the rules
+/// with the invalid bytes replaced.
+fn read_output(args: &Args, max_bytes: usize) -> io::Result<CappedOutput> {
+    if let Some(path) = &args.output_file {
+        let file = std::fs::File::open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        return output::read_capped(file, max_bytes);
+    }
+    match args.output.as_deref() {
+        Some(output) if output == "-" => output::read_capped(io::stdin().lock(), max_bytes),
+        output => Ok(output::cap_bytes(output.unwrap_or_default().as_encoded_bytes(), max_bytes)),
+    }
+}
+
+/// Appends the failed command of `args` to the corpus file at `file`, or the
+/// default one, expecting `accepted` or else the first correction
+/// `corrector` finds.
+fn record_corpus_case(
+    args: &Args,
+    accepted: Option<String>,
+    file: Option<PathBuf>,
+    corrector: impl FnOnce() -> Corrector,
+) -> Result<(), Error> {
+    let (Some(script), Some(exit_code)) = (args.command.as_deref(), args.exit_code) else {
+        return Err(Error::other(msg!("corpus_needs_command")));
+    };
+    let output = read_output(args, usize::MAX)?;
+    let accepted = match accepted {
+        Some(accepted) => Some(accepted),
+        None => {
+            let cmd = Command::new(script, &output.text, exit_code);
+            corrector().get_corrections(&cmd).into_iter().next().map(|correction| correction.script)
+        }
+    };
+
+    let path = file.or_else(corpus::default_path).ok_or_else(|| Error::other(msg!("no_data_dir")))?;
+    corpus::append(&path, &CorpusCase::new(script, output.text, exit_code, accepted))?;
+    eprintln!("ftf: {}", msg!("corpus_recorded", path = path.display()));
+    Ok(())
+}
+
+/// Corrects `args`' failure with `corrector`, whose context records its
+/// probes, prints the corrections and writes the failure with the probes to
+/// `out`.
+fn record_invocation(
+    args: &Args,
+    config: &Config,
+    context: &RuleContext,
+    out: &Path,
+    corrector: impl FnOnce() -> Corrector,
+) -> Result<ExitStatus, Error> {
+    let (Some(script), Some(exit_code)) = (args.command.as_deref(), args.exit_code) else {
+        return Err(Error::other(msg!("record_needs_command")));
+    };
+    let output = read_output(args, usize::MAX)?;
+    let cmd = Command::new(script, &output.text, exit_code);
+    let corrections = corrector().get_corrections(&cmd);
+    for correction in &corrections {
+        println!("{}", correction.script);
+    }
+
+    let shell = context.shell();
+    let cwd = match shell.and_then(|shell| shell.cwd().ok()) {
+        Some(cwd) => cwd,
+        None => std::env::current_dir()?,
+    };
+    let projects = context.project_types();
+    let recording = Recording {
+        version: replay::FORMAT_VERSION,
+        script: cmd.script,
+        output: cmd.output,
+        exit_code,
+        cwd,
+        shell: shell.map_or("bash", |shell| shell.name()).to_string(),
+        config_hash: replay::config_hash(config),
+        project_types: ProjectType::ALL.iter().filter(|project| projects.contains(project)).copied().collect(),
+        probes: context.recorded_probes().unwrap_or_default(),
+    };
+    recording.write(out)?;
+    eprintln!("ftf: {}", msg!("recorded", path = out.display()));
+    Ok(if corrections.is_empty() { ExitStatus::NoCorrection } else { ExitStatus::Success })
+}
+
+/// Corrects the failure recorded in `file` again, in a context that answers
+/// probes from the recording, and prints the corrections, or why each rule
+/// matched with `explain`.
+fn replay_invocation(config: &Config, file: &Path, explain: bool) -> Result<ExitStatus, Error> {
+    let recording = Recording::read(file)?;
+    if recording.config_hash != replay::config_hash(config) {
+        eprintln!("ftf: {}", msg!("replay_config_differs"));
+    }
+
+    let context = RuleContext::new(Box::new(recording.shell()))
+        .with_history(Vec::new())
+        .with_session(SessionContext::default())
+        .with_project_types(recording.project_types.iter().copied().collect());
+    let context = Arc::new(context);
+    let learned = AdaptivePriorities::default();
+    let corrector = Corrector::for_command(config.factories(&context), config, &learned, &context, &recording.script);
+    let cmd = recording.command();
+    let corrections = corrector.get_corrections(&cmd);
+
+    if explain && !corrections.is_empty() {
+        let reasons = correction_reasons(messages(), Some(&corrector), &cmd, &corrections);
+        let views: Vec<View> = reasons
+            .into_iter()
+            .map(|reason| View { reason, diff: false })
+            .collect();
+        let renderer = Renderer::new(Theme::default(), false)
+            .with_width(render::DEFAULT_WIDTH)
+            .with_diff_context(config.global.diff_context);
+        print!("{}", explanation_text(messages(), &renderer, &corrections, &views, &cmd.script, config));
+    } else {
+        for correction in &corrections {
+            println!("{}", correction.script);
+        }
+    }
+    Ok(if corrections.is_empty() { ExitStatus::NoCorrection } else { ExitStatus::Success })
+}
+
+/// Finds the corrections for `cmd`, with a spinner counting the finished
+/// rules if `show_progress` is set and they take a while.
+fn find_corrections(corrector: &Corrector, cmd: &Command, show_progress: bool) -> CorrectionSet {
+    if !show_progress {
+        return corrector.correct(cmd);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        let worker = scope.spawn(move || corrector.stream(cmd, sender));
+        let mut spinner = Spinner::new(io::stderr(), true, progress::SPINNER_DELAY);
+        let (mut done, mut total) = (0, 0);
+        let corrections = loop {
+            match receiver.recv_timeout(progress::FRAME_INTERVAL) {
+                Ok(CorrectionEvent::RuleDone { done: finished, total: rules, .. }) => (done, total) = (finished, rules),
+                Ok(CorrectionEvent::Finished(set)) => break set,
+                Ok(CorrectionEvent::Correction(_)) | Err(RecvTimeoutError::Timeout) => {}
+                // The worker panicked; joining it passes the panic on
+                Err(RecvTimeoutError::Disconnected) => break CorrectionSet::default(),
+            }
+            spinner.tick(&msg!("checking_rules", done = done, total = total));
+        };
+        spinner.clear();
+        if let Err(panic) = worker.join() {
+            std::panic::resume_unwind(panic);
+        }
+        corrections
+    })
+}
+
+/// Number of recent history entries ranked for the fallback.
+const HISTORY_FALLBACK_SCAN: usize = 200;
+
+/// Number of history entries offered by the fallback.
+const HISTORY_FALLBACK_LIMIT: usize = 5;
+
+/// Gets the most similar recent history entries as low-confidence corrections.
+fn history_fallback(context: &RuleContext, script: &str) -> Vec<CorrectedCommand> {
+    if context.shell().is_none() {
+        return Vec::new();
+    }
+
+    let recent = context.history().most_recent(HISTORY_FALLBACK_SCAN);
+    fuzzy::rank_history_candidates(script, recent, HISTORY_FALLBACK_LIMIT)
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            CorrectedCommand::new(entry, i as i32 + 1)
+                .with_rule("history_fallback")
+                .with_category("history_fallback")
+        })
+        .collect()
+}
+
+/// How the selector shows a correction.
+#[derive(Debug, Clone, Default)]
+struct View {
+    /// Why it was offered
+    reason: Option<String>,
+    /// Whether only the words it changes are shown
+    diff: bool,
+}
+
+/// What the selector shows: how each correction is viewed and, in a
+/// terminal, the groups they're laid out in.
+#[derive(Debug, Clone, Default)]
+struct Screen {
+    views: Vec<View>,
+    /// `None` for a flat list
+    layout: Option<Layout>,
+}
+
+/// Shows the selector until a candidate is picked, and tells whether it's
+/// to be copied too. Anything but a number or a view toggle cancels.
+fn select_correction_interactive<'a>(
+    corrections: &'a [CorrectedCommand],
+    mut views: Vec<View>,
+    from_history: bool,
+    original: &str,
+    config: &Config,
+    color: ColorWhen,
+) -> Option<(&'a CorrectedCommand, bool)> {
+    let renderer = selector_renderer(config, color);
+    views.resize(corrections.len(), View::default());
+    // In a terminal, candidates are grouped by category and long groups paged
+    let lines_per_candidate = if views.iter().any(|view| view.reason.is_some()) { 2 } else { 1 };
+    let layout = io::stderr().is_terminal().then(|| {
+        let groups = selector::group(corrections.iter().map(|correction| correction.category.as_deref()));
+        let page_size = selector::page_size(render::terminal_height(), groups.len(), lines_per_candidate);
+        Layout::new(groups, page_size)
+    });
+    let mut screen = Screen {
+        views,
+        layout: layout.filter(Layout::is_useful),
+    };
+    loop {
+        eprint!("{}", selector_text(messages(), &renderer, corrections, &screen, from_history, original, config));
+        let _ = io::stderr().flush();
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input).is_err() {
+            return None;
+        }
+        if screen.layout.as_mut().is_some_and(|layout| navigate(layout, &input)) {
+            continue;
+        }
+        // d switches every candidate between the full and diff views, dN
+        // only candidate N
+        let views = &mut screen.views;
+        match input.trim().strip_prefix('d').map(str::trim) {
+            Some("") => {
+                let diff = views.iter().any(|view| !view.diff);
+                views.iter_mut().for_each(|view| view.diff = diff);
+                continue;
+            }
+            Some(n) => {
+                if let Some(view) = n.parse::<usize>().ok().and_then(|n| views.get_mut(n.checked_sub(1)?)) {
+                    view.diff = !view.diff;
+                    continue;
                 }
+                return None;
             }
+            None => {}
+        }
+
+        // cN picks candidate N and copies it to the clipboard too
+        let (n, copy) = match input.trim().strip_prefix('c') {
+            Some(n) => (n.trim(), true),
+            None => (input.trim(), false),
+        };
+        return match n.parse::<usize>() {
+            Ok(idx) if idx > 0 && idx <= corrections.len() => Some((&corrections[idx - 1], copy)),
+            _ => None,
+        };
+    }
+}
+
+/// Moves around the grouped selector if `input` is a navigation key: tab,
+/// `>` or → expand the next group, shift-tab, `<` or ← the previous one, and
+/// n and p turn the expanded group's pages. Arrows arrive as escape
+/// sequences, since the terminal is line-buffered.
+fn navigate(layout: &mut Layout, input: &str) -> bool {
+    match input.trim_end_matches(['\n', '\r']) {
+        "\t" | ">" | "\x1b[C" => layout.next_group(),
+        "\x1b[Z" | "<" | "\x1b[D" => layout.previous_group(),
+        "n" => layout.next_page(),
+        "p" => layout.previous_page(),
+        _ => return false,
+    }
+    true
+}
+
+/// Renders the selector for `corrections` of `original`, up to and
+/// including the prompt.
+fn selector_text(
+    messages: &Messages,
+    renderer: &Renderer,
+    corrections: &[CorrectedCommand],
+    screen: &Screen,
+    from_history: bool,
+    original: &str,
+    config: &Config,
+) -> String {
+    let header = if from_history {
+        messages.text("history_candidates", &[])
+    } else {
+        messages.text("multiple_corrections", &[])
+    };
+    let candidates = selector_candidates(messages, corrections, &screen.views, from_history, config);
+    let list = match &screen.layout {
+        Some(layout) => format!(
+            "{}{}\n",
+            renderer.sections(original, &sections(messages, layout, &candidates), candidates.len()),
+            messages.text("group_hint", &[])
+        ),
+        None => renderer.candidates(original, &candidates),
+    };
+    format!(
+        "\n{}\n{}\n{}",
+        header,
+        list,
+        messages.text("select_prompt", &[("count", &corrections.len())])
+    )
+}
+
+/// Splits `candidates` into a section per group of `layout`, with only the
+/// shown page of the expanded one listed.
+fn sections<'a>(messages: &Messages, layout: &Layout, candidates: &[Candidate<'a>]) -> Vec<Section<'a>> {
+    layout
+        .groups()
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let expanded = i == layout.expanded();
+            let category = group.category.replace('_', " ");
+            let count = group.members.len();
+            let header = if expanded && layout.pages() > 1 {
+                let (page, pages) = (layout.page() + 1, layout.pages());
+                messages.text(
+                    "group_page",
+                    &[("category", &category), ("count", &count), ("page", &page), ("pages", &pages)],
+                )
+            } else {
+                messages.text("group_header", &[("category", &category), ("count", &count)])
+            };
+            let shown = if expanded { layout.visible() } else { &[] };
+            Section {
+                header,
+                expanded,
+                candidates: shown.iter().map(|&index| (index + 1, candidates[index].clone())).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Renders `corrections` of `original` as the selector lists them, for
+/// `--no-interaction --explain`.
+fn explanation_text(
+    messages: &Messages,
+    renderer: &Renderer,
+    corrections: &[CorrectedCommand],
+    views: &[View],
+    original: &str,
+    config: &Config,
+) -> String {
+    renderer.candidates(original, &selector_candidates(messages, corrections, views, false, config))
+}
+
+/// Gets the selector's view of `corrections`, each shown as `views` says.
+/// Those without a view are shown in full, with no reason.
+fn selector_candidates<'a>(
+    messages: &Messages,
+    corrections: &'a [CorrectedCommand],
+    views: &[View],
+    from_history: bool,
+    config: &Config,
+) -> Vec<Candidate<'a>> {
+    let label = if from_history {
+        format!(" {}", messages.text("from_history", &[]))
+    } else {
+        String::new()
+    };
+    corrections
+        .iter()
+        .enumerate()
+        .map(|(i, correction)| {
+            let effect = correction
+                .side_effect
+                .as_ref()
+                .map(|effect| format!(" {}", messages.text("first_effect", &[("effect", effect)])))
+                .unwrap_or_default();
+            Candidate {
+                script: &correction.script,
+                note: format!("{}{}", effect, label),
+                rule: correction.rule.as_deref(),
+                dangerous: config.requires_confirmation(&correction.script),
+                reason: views.get(i).and_then(|view| view.reason.clone()),
+                diff: views.get(i).is_some_and(|view| view.diff),
+            }
+        })
+        .collect()
+}
+
+/// Gets why each of `corrections` was offered for `command`, from the
+/// metadata of its rule in `corrector`. Corrections from the daemon or
+/// history have no rule there, and no reason.
+fn correction_reasons(
+    messages: &Messages,
+    corrector: Option<&Corrector>,
+    command: &Command,
+    corrections: &[CorrectedCommand],
+) -> Vec<Option<String>> {
+    let rules = corrector.map(Corrector::rules).unwrap_or_default();
+    corrections
+        .iter()
+        .map(|correction| {
+            let name = correction.rule.as_deref()?;
+            let rule = rules.iter().find(|rule| rule.name() == name)?;
+            Some(reason_text(messages, name, &rule.metadata().reason(command)))
+        })
+        .collect()
+}
+
+/// Words why `rule` matched.
+fn reason_text(messages: &Messages, rule: &str, reason: &Reason) -> String {
+    match reason {
+        Reason::Output(text) => messages.text("reason_output", &[("rule", &rule), ("text", text)]),
+        Reason::Script(text) => messages.text("reason_script", &[("rule", &rule), ("text", text)]),
+        Reason::Described(description) => {
+            messages.text("reason_described", &[("rule", &rule), ("description", description)])
         }
+        Reason::Matched => messages.text("reason_matched", &[("rule", &rule)]),
     }
 }
 
-fn select_correction_interactive(corrections: &[fasterthefuck::CorrectedCommand]) -> Option<String> {
-    eprintln!("\nMultiple corrections available:");
-    for (i, correction) in corrections.iter().enumerate() {
-        eprintln!("  {}. {}", i + 1, correction.script);
+/// Builds the selector's renderer from the config's theme, coloring only
+/// when `color` allows it. Stdout is read by the shell alias, so only
+/// stderr, where the selector is shown, has to be a terminal.
+fn selector_renderer(config: &Config, color: ColorWhen) -> Renderer {
+    let theme = Theme::from_config(&config.theme).unwrap_or_else(|e| {
+        eprintln!("ftf: {}", msg!("invalid_theme", error = e.user_message()));
+        Theme::default()
+    });
+    let no_color = std::env::var("NO_COLOR").ok();
+    let enabled = color.mode().enabled(io::stderr().is_terminal(), config.global.color, no_color.as_deref());
+    Renderer::new(theme, enabled)
+        .with_width(render::terminal_width())
+        .with_diff_context(config.global.diff_context)
+}
+
+/// Asks for the value of the placeholder in `correction` and fills it in.
+/// Returns `None` if the user enters nothing.
+fn prompt_for_value(correction: &CorrectedCommand) -> Option<CorrectedCommand> {
+    eprint!("\n{}\n{}", correction.script, msg!("value_prompt", placeholder = VALUE_PLACEHOLDER));
+    let _ = io::stderr().flush();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).ok()?;
+    let value = input.trim();
+    if value.is_empty() {
+        return None;
     }
 
-    eprint!("\nSelect correction (1-{}): ", corrections.len());
+    let value = Script::from_tokens([value]).to_string();
+    Some(CorrectedCommand {
+        script: correction.script.replacen(VALUE_PLACEHOLDER, &value, 1),
+        ..correction.clone()
+    })
+}
+
+/// Gets the session cache directory for this terminal.
+fn session_dir() -> Option<PathBuf> {
+    SessionContext::session_dir(|key| std::env::var(key).ok())
+}
+
+/// What to do with the completed steps of a chain that failed part-way.
+#[derive(Debug, Clone, Copy)]
+enum Rollback {
+    /// Ask whether to roll them back now
+    Ask,
+    /// Roll them back without asking
+    Run,
+    /// Leave them for `ftf undo`
+    Save,
+}
+
+/// Prints the steps of `plan`, and the filesystem changes if previewed, and
+/// asks once whether to run them. Without interaction the answer is no.
+fn confirm_plan(plan: &ExecutionPlan, fs_changes: Option<&[FsChange]>, no_interaction: bool) -> bool {
+    if no_interaction && fs_changes.is_some() {
+        eprintln!("ftf: {}", msg!("preview_needs_confirmation"));
+        return false;
+    }
+    if no_interaction {
+        eprintln!("ftf: {}", msg!("plan_needs_yes", steps = plan.steps().len()));
+        return false;
+    }
+
+    eprintln!("\n{}", msg!("plan_steps"));
+    for (i, step) in plan.steps().iter().enumerate() {
+        eprintln!("  {}. {}", i + 1, step);
+    }
+    match fs_changes {
+        Some([]) => eprintln!("\n{}", msg!("no_file_changes")),
+        Some(changes) => {
+            eprintln!("\n{}", msg!("file_changes"));
+            for change in changes {
+                eprintln!("  - {}", change);
+            }
+        }
+        None => {}
+    }
+    match plan.steps().len() {
+        1 => eprint!("\n{}", msg!("run_prompt")),
+        steps => eprint!("\n{}", msg!("run_all_prompt", steps = steps)),
+    }
     let _ = io::stderr().flush();
 
     let mut input = String::new();
     if io::stdin().read_line(&mut input).is_err() {
-        return None;
+        return false;
+    }
+    matches!(input.trim(), "y" | "Y" | "yes")
+}
+
+/// Runs `plan` for `script`, records it in the session cache together with
+/// its inverse (if any), and returns its exit code.
+///
+/// If the plan fails part-way, its rollbacks are handled as `rollback` says;
+/// rollbacks that aren't run are saved for `ftf undo`. With a `notifier`, a
+/// long run ends with a desktop notification.
+fn execute_correction(
+    context: &RuleContext,
+    script: &str,
+    plan: &ExecutionPlan,
+    undo: Option<String>,
+    rollback: Rollback,
+    notifier: Option<&LongRunNotifier>,
+) -> Result<i32, Error> {
+    let shell = context.shell().ok_or_else(|| Error::other(msg!("no_shell")))?;
+    let report = match notifier {
+        Some(notifier) => {
+            let (report, notified) = notifier.run(script, || plan.run(shell), ExecutionReport::exit_code);
+            if let Err(e) = notified {
+                eprintln!("ftf: {}", msg!("notification_failed", error = e.user_message()));
+            }
+            report
+        }
+        None => plan.run(shell),
+    };
+    for output in &report.outputs {
+        print!("{}", output.stdout);
+        eprint!("{}", output.stderr);
+    }
+
+    match &report.failure {
+        Some(StepFailure {
+            step: Step::SideEffect(effect),
+            error,
+            ..
+        }) => eprintln!("ftf: {}", msg!("side_effect_failed", effect = effect.name(), error = error)),
+        // A single failing command speaks for itself
+        Some(failure) if plan.is_chained() => eprintln!(
+            "ftf: {}",
+            msg!(
+                "step_failed",
+                step = failure.index + 1,
+                steps = plan.steps().len(),
+                command = failure.step,
+                error = failure.error
+            )
+        ),
+        Some(StepFailure { error, .. }) if report.outputs.is_empty() => return Err(Error::other(error.user_message())),
+        _ => {}
     }
 
-    if let Ok(idx) = input.trim().parse::<usize>() {
-        if idx > 0 && idx <= corrections.len() {
-            return Some(corrections[idx - 1].script.clone());
+    // Best effort, so up-arrow finds the correction; the shell hook records
+    // the corrections it runs itself
+    let history_file = history_file_path(|key| shell.env(key));
+    let entry = HistoryEntry::new(script, Some(rejections::now()));
+    if let Err(e) = append_history_entry(&history_file, &entry) {
+        let error = e.user_message();
+        eprintln!("ftf: {}", msg!("history_not_updated", path = history_file.display(), error = error));
+    }
+
+    if let Some(dir) = session_dir() {
+        let captured: String = report
+            .outputs
+            .iter()
+            .map(|output| format!("{}{}", output.stdout, output.stderr))
+            .collect();
+        SessionContext::record(&dir, &SessionEntry::new(script, captured, report.exit_code()))?;
+
+        // Only the most recent executed correction can be undone, and not
+        // one with a step that changed nothing, which its inverse would revert
+        match undo {
+            Some(inverse) if report.success() && report.unchanged.is_empty() => {
+                UndoRecord::new(script, inverse).save(&dir)?
+            }
+            _ => UndoRecord::clear(&dir)?,
+        }
+    }
+
+    let rollbacks = plan.rollbacks_after(&report);
+    if !rollbacks.is_empty() {
+        roll_back(shell, script, &rollbacks, rollback)?;
+    }
+
+    Ok(report.exit_code())
+}
+
+/// Runs the rollbacks of a partially executed correction, or saves them so
+/// `ftf undo` can run them later.
+fn roll_back(
+    shell: &dyn Shell,
+    script: &str,
+    rollbacks: &[String],
+    rollback: Rollback,
+) -> Result<(), Error> {
+    let run = match rollback {
+        Rollback::Run => true,
+        Rollback::Save => false,
+        Rollback::Ask => {
+            eprintln!("\n{}", msg!("rollback_steps"));
+            for (i, command) in rollbacks.iter().enumerate() {
+                eprintln!("  {}. {}", i + 1, command);
+            }
+            eprint!("\n{}", msg!("rollback_prompt"));
+            let _ = io::stderr().flush();
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).is_ok() && matches!(input.trim(), "y" | "Y" | "yes")
+        }
+    };
+
+    if run {
+        let report = ExecutionPlan::from_commands(rollbacks).run(shell);
+        for output in &report.outputs {
+            print!("{}", output.stdout);
+            eprint!("{}", output.stderr);
         }
+        if let Some(failure) = &report.failure {
+            eprintln!(
+                "ftf: {}",
+                msg!("rollback_failed", step = failure.index + 1, command = failure.step, error = failure.error)
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(dir) = session_dir() {
+        UndoRecord::new(script, rollbacks.join(" && ")).save(&dir)?;
+        eprintln!("ftf: {}", msg!("rollback_later"));
+    }
+    Ok(())
+}
+
+/// Runs the side effect of the selected correction.
+fn run_side_effect(context: &RuleContext, effect: &SideEffect) -> Result<(), Error> {
+    let shell = context.shell().ok_or_else(|| Error::other(msg!("no_shell")))?;
+    effect.run(shell)?;
+    Ok(())
+}
+
+/// Prints (or runs) the inverse of the most recent correction ftf executed.
+fn undo_last(context: &RuleContext, execute: bool) -> Result<ExitStatus, Error> {
+    let Some(record) = session_dir().and_then(|dir| UndoRecord::load(&dir)) else {
+        eprintln!("ftf: {}", msg!("nothing_to_undo"));
+        return Ok(ExitStatus::NoCorrection);
+    };
+
+    if !execute {
+        println!("{}", record.inverse);
+        return Ok(ExitStatus::Success);
     }
 
-    None
+    let plan = ExecutionPlan::from_script(&record.inverse);
+    let exit_code = execute_correction(context, &record.inverse, &plan, None, Rollback::Save, None)?;
+    Ok(ExitStatus::Executed(exit_code))
 }
 
-/// Filters rules based on configuration.
-/// Removes rules that are disabled in the config.
-fn filter_rules_by_config(
-    rules: Vec<Box<dyn fasterthefuck::Rule>>,
+/// Appends this invocation to the correction log. Failures are reported but not fatal.
+fn log_correction(cmd: &Command, corrections: &[CorrectedCommand], selected: Option<&CorrectedCommand>) {
+    let Some(path) = stats::default_log_path() else {
+        return;
+    };
+
+    let entry = match (selected, corrections.first()) {
+        (Some(chosen), _) => LogEntry::new(
+            &cmd.script,
+            chosen.rule.clone(),
+            Some(chosen.script.clone()),
+            Outcome::Accepted,
+        ),
+        (None, Some(first)) => LogEntry::new(&cmd.script, first.rule.clone(), None, Outcome::Cancelled),
+        (None, None) => LogEntry::new(&cmd.script, None, None, Outcome::NoCorrection),
+    };
+
+    if let Err(e) = stats::append_entry(&path, &entry) {
+        eprintln!("ftf: {}", msg!("log_not_written", error = e.user_message()));
+    }
+}
+
+/// Spools a metrics event for this invocation, and once the batch is full
+/// sends it from a detached `ftf telemetry flush` reading `config_path`, so
+/// ftf exits without waiting for it. Failures are only logged: metrics never
+/// get in the way of a correction.
+fn record_telemetry(
+    config_path: Option<&str>,
+    batch_size: usize,
+    corrections: &[CorrectedCommand],
+    selected: Option<&CorrectedCommand>,
+    elapsed: Duration,
+) {
+    let Some(spool) = telemetry::default_spool_path().map(Spool::new) else {
+        return;
+    };
+    let event = match (selected, corrections.first()) {
+        (Some(chosen), _) => Event::new(chosen.rule.clone(), Outcome::Accepted, elapsed),
+        (None, Some(first)) => Event::new(first.rule.clone(), Outcome::Cancelled, elapsed),
+        (None, None) => Event::new(None, Outcome::NoCorrection, elapsed),
+    };
+    match spool.record(&event) {
+        Ok(spooled) if spooled >= batch_size => match spawn_telemetry_flush(config_path) {
+            Ok(()) => tracing::debug!(spooled, "sending metrics"),
+            Err(e) => tracing::debug!(error = %e, "metrics not sent"),
+        },
+        Ok(_) => {}
+        Err(e) => tracing::debug!(error = %e, "metrics not spooled"),
+    }
+}
+
+/// Starts `ftf telemetry flush` in its own process group, with nothing
+/// connected to the terminal, and leaves it running.
+fn spawn_telemetry_flush(config_path: Option<&str>) -> io::Result<()> {
+    let mut flush = std::process::Command::new(std::env::current_exe()?);
+    if let Some(path) = config_path {
+        flush.args(["--config", path]);
+    }
+    flush
+        .args(["telemetry", "flush"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    // Out of the shell's job control, so ^C at the prompt doesn't stop it
+    std::os::unix::process::CommandExt::process_group(&mut flush, 0);
+    flush.spawn().map(drop)
+}
+
+/// Prints whether metrics are sent and how many are spooled, sends them or
+/// deletes them.
+fn manage_telemetry(config: &Config, action: TelemetryAction) -> Result<(), Error> {
+    let spool = Spool::new(telemetry::default_spool_path().ok_or_else(|| Error::other(msg!("no_data_dir")))?);
+    match action {
+        TelemetryAction::Status => {
+            match config.telemetry.active_endpoint() {
+                Some(endpoint) => println!("{}", msg!("telemetry_enabled", endpoint = endpoint)),
+                None => println!("{}", msg!("telemetry_disabled")),
+            }
+            let spooled = spool.events()?.len();
+            println!("{}", msg!("telemetry_spooled", count = spooled, path = spool.path().display()));
+        }
+        TelemetryAction::Flush => {
+            let endpoint = config
+                .telemetry
+                .active_endpoint()
+                .ok_or_else(|| Error::other(msg!("telemetry_not_enabled")))?;
+            let sent = spool.flush(&HttpTransport::new(endpoint))?;
+            println!("{}", msg!("telemetry_sent", count = sent, endpoint = endpoint));
+        }
+        TelemetryAction::Purge => {
+            let spooled = spool.events()?.len();
+            spool.purge()?;
+            println!("{}", msg!("telemetry_purged", count = spooled));
+        }
+    }
+    Ok(())
+}
+
+/// Prints aggregated statistics from the correction log.
+fn print_stats<'a>(rules: impl Iterator<Item = &'a str>) -> Result<(), Error> {
+    let path = stats::default_log_path().ok_or_else(|| Error::other(msg!("no_data_dir")))?;
+    let entries = stats::read_log(&path)?;
+    let stats = Stats::from_entries(&entries, rules);
+
+    println!("{}", msg!("stats_invocations", count = stats.invocations));
+
+    println!("\n{}", msg!("stats_acceptance"));
+    for (rule, counts) in &stats.rules {
+        let accepted = format!("{:>4}", counts.accepted);
+        let cancelled = format!("{:>4}", counts.cancelled);
+        println!(
+            "  {:<28} {}",
+            rule,
+            msg!("stats_rule_counts", accepted = accepted, cancelled = cancelled)
+        );
+    }
+
+    println!("\n{}", msg!("stats_top_corrections"));
+    for (correction, count) in stats.top_corrections.iter().take(10) {
+        println!("  {:>4}  {}", count, correction);
+    }
+
+    println!("\n{}", msg!("stats_never_fired"));
+    for rule in &stats.never_fired {
+        println!("  {}", rule);
+    }
+
+    Ok(())
+}
+
+/// Prints every registered rule with its priorities.
+fn print_rules(
+    rules: impl Iterator<Item = RuleInfo>,
     config: &Config,
-) -> Vec<Box<dyn fasterthefuck::Rule>> {
-    rules
+    learned: &AdaptivePriorities,
+) -> Result<(), Error> {
+    println!(
+        "{:<28} {:>6} {:>8} {:>10}",
+        msg!("rules_column_rule"),
+        msg!("rules_column_base"),
+        msg!("rules_column_learned"),
+        msg!("rules_column_effective")
+    );
+    for (name, base) in rules {
+        let effective = learned.effective_priority(name, base, config.get_rule_priority(name));
+        println!(
+            "{:<28} {:>6} {:>+8} {:>10}",
+            name,
+            base,
+            learned.adjustment_for(name),
+            effective
+        );
+    }
+
+    Ok(())
+}
+
+/// Most lines of an example's output `ftf rules show` prints.
+const RULE_DOC_OUTPUT_LINES: usize = 8;
+
+/// Documents `rule` for `ftf rules show`: its description, what it
+/// matches, and its example failure with the correction for it.
+fn rule_doc(messages: &Messages, rule: &RuleMetadata) -> String {
+    let pattern = |pattern: &Pattern| {
+        let kind = match pattern.kind {
+            PatternKind::Substring => "substring",
+            PatternKind::Regex => "regex",
+            PatternKind::Fuzzy => "fuzzy",
+        };
+        format!("{} ({})", pattern.pattern, kind)
+    };
+    let mut fields = vec![(messages.text("rules_show_priority", &[]), rule.priority.to_string())];
+    if let Some(category) = &rule.category {
+        fields.insert(0, (messages.text("rules_show_group", &[]), category.clone()));
+    }
+    if !rule.enabled_by_default {
+        fields.push((messages.text("rules_show_enabled", &[]), messages.text("rules_show_disabled", &[])));
+    }
+    if let Some(executable) = &rule.executable {
+        fields.push((messages.text("rules_show_executable", &[]), executable.clone()));
+    }
+    if let Some(script) = &rule.match_command {
+        fields.push((messages.text("rules_show_script", &[]), pattern(script)));
+    }
+    if let Some(output) = &rule.match_output {
+        fields.push((messages.text("rules_show_output", &[]), pattern(output)));
+    }
+    if let Some(kind) = rule.failure_kind {
+        let name = serde_json::to_value(kind).ok().and_then(|kind| kind.as_str().map(str::to_string));
+        fields.push((messages.text("rules_show_failure", &[]), name.unwrap_or_default()));
+    }
+
+    let mut doc = format!("{}\n", rule.name);
+    if let Some(description) = &rule.description {
+        doc += &format!("{}\n", description);
+    }
+    doc += "\n";
+    let width = fields.iter().map(|(label, _)| label.chars().count()).max().unwrap_or_default();
+    for (label, value) in &fields {
+        doc += &format!("{:<width$}  {}\n", format!("{}:", label), value, width = width + 1);
+    }
+
+    doc += "\n";
+    let Some(example) = &rule.example else {
+        doc += &format!("{}\n", messages.text("rules_show_no_example", &[]));
+        return doc;
+    };
+    doc += &format!("{}\n", messages.text("rules_show_example", &[("exit_code", &example.exit_code)]));
+    doc += &format!("    $ {}\n", example.script);
+    let lines: Vec<&str> = example.output.lines().collect();
+    for line in lines.iter().take(RULE_DOC_OUTPUT_LINES) {
+        doc += &format!("    {}\n", line);
+    }
+    if lines.len() > RULE_DOC_OUTPUT_LINES {
+        doc += "    …\n";
+    }
+    doc += &format!("    → {}\n", example.correction);
+    doc
+}
+
+/// Lists the pairs of enabled rules that disagreed on at least
+/// `min_disagreements` failures from the corpus files at `paths`, or the
+/// local corpus, and inputs made from the rules' triggers.
+fn print_rule_conflicts(config: &Config, paths: Vec<PathBuf>, min_disagreements: usize) -> Result<(), Error> {
+    let paths = if paths.is_empty() {
+        corpus::default_path().filter(|path| path.exists()).into_iter().collect()
+    } else {
+        paths
+    };
+    let mut cases = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            cases.extend(corpus::read_dir(&path)?.into_iter().map(|(_, case)| case));
+        } else {
+            cases.extend(corpus::parse(&std::fs::read_to_string(&path)?)?);
+        }
+    }
+    let commands: Vec<Command> = cases.iter().map(CorpusCase::command).collect();
+
+    // Without a shell no rule is left out for a missing binary, and finding
+    // corrections runs nothing, so every machine reports the same pairs
+    let context = Arc::new(RuleContext::default());
+    let mut registry = rules::RuleRegistry::new();
+    registry.add_factories(config.factories(&context), None);
+    registry.retain(|rule| config.is_rule_enabled(rule.name()));
+
+    let conflicts: Vec<rules::Overlap> = registry
+        .find_overlaps(&commands)
         .into_iter()
-        .filter(|rule| config.is_rule_enabled(rule.name()))
-        .collect()
+        .filter(|overlap| overlap.disagreed >= min_disagreements.max(1))
+        .collect();
+    if conflicts.is_empty() {
+        println!("{}", msg!("rules_no_conflicts"));
+    }
+    for overlap in conflicts {
+        println!(
+            "{}",
+            msg!(
+                "rules_conflict",
+                first = overlap.first,
+                second = overlap.second,
+                co_fired = overlap.co_fired,
+                disagreed = overlap.disagreed
+            )
+        );
+        if let Some(example) = overlap.example {
+            println!("    $ {}  (exit {})", example.command.script, example.command.exit_code);
+            println!("    {}: {}", overlap.first, example.first_suggests);
+            println!("    {}: {}", overlap.second, example.second_suggests);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_status_codes() {
+        assert_eq!(ExitStatus::Success.code(), 0);
+        assert_eq!(ExitStatus::NoCorrection.code(), 1);
+        assert_eq!(ExitStatus::Error.code(), 2);
+        assert_eq!(ExitStatus::Suppressed.code(), 3);
+        assert_eq!(ExitStatus::Executed(42).code(), 42);
+    }
+
+    #[test]
+    fn test_exit_code_keeps_the_low_byte_only() {
+        assert_eq!(ExitCode::from(ExitStatus::Executed(130)), ExitCode::from(130));
+        assert_eq!(ExitCode::from(ExitStatus::Executed(-1)), ExitCode::from(1));
+        assert_eq!(ExitCode::from(ExitStatus::Executed(256)), ExitCode::from(1));
+    }
+
+    #[test]
+    fn test_config_messages_override_the_selector() {
+        let config: Config = toml::from_str(
+            "[messages]\nmultiple_corrections = \"Varias opciones:\"\nselect_prompt = \"¿Cuál? (1-{count}) \"\n",
+        )
+        .unwrap();
+        let messages = Messages::load(&config.messages, Some("en_US.UTF-8"), None).unwrap();
+        let corrections = [
+            CorrectedCommand::new("git push", 100).with_rule("git_not_command"),
+            CorrectedCommand::new("git pull", 200).with_rule("git_not_command"),
+        ];
+
+        let renderer = Renderer::new(Theme::default(), false);
+        let text = selector_text(&messages, &renderer, &corrections, &Screen::default(), false, "git psuh", &config);
+        assert!(text.starts_with("\nVarias opciones:\n  $ git psuh\n"), "{}", text);
+        assert!(text.ends_with("\n\n¿Cuál? (1-2) "), "{}", text);
+    }
+
+    #[test]
+    fn test_locale_translates_the_selector() {
+        let config = Config::default();
+        let messages = Messages::load(&config.messages, Some("es_ES.UTF-8"), None).unwrap();
+        let corrections = [CorrectedCommand::new("git status", 100)];
+
+        let renderer = Renderer::new(Theme::default(), false);
+        let text = selector_text(&messages, &renderer, &corrections, &Screen::default(), true, "git stats", &config);
+        assert!(text.contains("Ninguna regla coincide."), "{}", text);
+        assert!(text.contains("git status (del historial)"), "{}", text);
+        assert!(text.ends_with("Elige una corrección (1-1, d alterna las diferencias, cN copia): "), "{}", text);
+    }
+
+    #[test]
+    fn test_grouped_selector() {
+        let corrections = [
+            CorrectedCommand::new("git push", 100).with_rule("git_not_command").with_category("git"),
+            CorrectedCommand::new("ls", 200).with_rule("history_fallback").with_category("history_fallback"),
+            CorrectedCommand::new("git pull", 300).with_rule("git_not_command").with_category("git"),
+            CorrectedCommand::new("git status", 400).with_rule("git_not_command").with_category("git"),
+        ];
+        let groups = selector::group(corrections.iter().map(|correction| correction.category.as_deref()));
+        let mut screen = Screen {
+            views: Vec::new(),
+            layout: Some(Layout::new(groups, 2)),
+        };
+
+        let renderer = Renderer::new(Theme::default(), false);
+        let (messages, config) = (Messages::english(), Config::default());
+        let text = selector_text(&messages, &renderer, &corrections, &screen, false, "git psuh", &config);
+        let expected = "  ▾ git (3, page 1/2)
+  1.   git push  git_not_command
+  3.   git pull  git_not_command
+  ▸ history fallback (1)
+";
+        assert!(text.contains(expected), "{}", text);
+        assert!(text.ends_with("n and p turn its pages\n\nSelect correction (1-4, d toggles diffs, cN copies): "));
+
+        let layout = screen.layout.as_mut().unwrap();
+        assert!(navigate(layout, "n\n"));
+        assert_eq!(layout.visible(), [3]);
+        assert!(navigate(layout, "\t\n"));
+        assert_eq!(layout.visible(), [1]);
+        assert!(navigate(layout, "\x1b[D\n"));
+        assert_eq!(layout.expanded(), 0);
+        assert!(!navigate(layout, "2\n"));
+        assert!(!navigate(layout, "d\n"));
+    }
+
+    #[test]
+    fn test_errors_are_shown_once_prefixed() {
+        let error = Error::other(msg!("no_packs_enabled"));
+        assert_eq!(error_text(&error), format!("ftf: {}", msg!("no_packs_enabled")));
+        assert_eq!(error_text(&Error::other("ftf: already prefixed")), "ftf: already prefixed");
+        assert_eq!(error_text(&Error::shell("bash exited")), "ftf: Shell error: bash exited");
+    }
+
+    #[test]
+    fn test_frozen_clock() {
+        let elapsed = Duration::from_millis(42);
+        assert_eq!(Clock::System.measure(elapsed), elapsed);
+        assert_eq!(Clock::Frozen.measure(elapsed), Duration::ZERO);
+        assert_eq!(elapsed_text(Clock::Frozen.measure(elapsed)), "0.0ms");
+    }
+
+    #[test]
+    fn test_summary_footer() {
+        let set = CorrectionSet {
+            corrections: vec![
+                CorrectedCommand::new("git push", 100),
+                CorrectedCommand::new("git pull", 200),
+                CorrectedCommand::new("git status", 300),
+            ],
+            evaluated: 84,
+            matched: 12,
+            suppressed: 1,
+            elapsed: Duration::from_micros(9_340),
+            failures: Vec::new(),
+        };
+        assert_eq!(
+            summary_text(&Messages::english(), &set, false),
+            "evaluated 84 rules (12 matched) in 9.3ms, 3 suggestions (1 suppressed)"
+        );
+        assert_eq!(
+            summary_text(&Messages::english(), &set, true),
+            "corrected by the daemon in 9.3ms, 3 suggestions (1 suppressed)"
+        );
+        assert_eq!(elapsed_text(Duration::from_millis(2_500)), "2.50s");
+        assert_eq!(elapsed_text(Duration::ZERO), "0.0ms");
+    }
+
+    #[test]
+    fn test_diagnostics_are_labeled_by_severity() {
+        let warning = ConfigDiagnostic::GroupNotRule { name: "git".to_string() };
+        assert_eq!(
+            diagnostic_text(&Messages::english(), &warning),
+            "warning: [rules.git] names a group of rules; settings under [rules] apply to single rules"
+        );
+        let error = ConfigDiagnostic::InvalidCustomRule {
+            pack: "team".to_string(),
+            error: "invalid rule pack: expected `]`".to_string(),
+        };
+        assert_eq!(
+            diagnostic_text(&Messages::english(), &error),
+            "error: rule pack 'team': invalid rule pack: expected `]`"
+        );
+    }
+
+    #[test]
+    fn test_panicking_rule_is_named() {
+        let mut registry = fasterthefuck::RuleRegistry::new();
+        registry.add_rule(
+            fasterthefuck::RegexRuleBuilder::new("custom_deploy")
+                .match_command_regex(r"^deplyo (\w+)")
+                .unwrap()
+                .replace_with(|_, captures| vec![format!("deploy {}", &captures[2])])
+                .build()
+                .unwrap(),
+        );
+        let typo = fasterthefuck::SimpleRuleBuilder::new("deploy_typo").match_command("deplyo");
+        registry.add_rule(typo.replace("deplyo", "deploy"));
+        let set = Corrector::new(registry).correct(&Command::new("deplyo web", "command not found", 127));
+
+        assert_eq!(set.corrections, [CorrectedCommand::new("deploy web", 1000)]);
+        let [failure] = set.failures.as_slice() else {
+            panic!("expected one failure, got {:?}", set.failures);
+        };
+        let text = failure_text(&Messages::english(), failure);
+        assert!(text.starts_with("rule custom_deploy crashed and was skipped: "), "{}", text);
+        assert!(text.ends_with("set enabled = false under [rules.custom_deploy] in the config"), "{}", text);
+    }
+
+    #[test]
+    fn test_reason_text() {
+        let messages = Messages::english();
+        let reason = Reason::Output("has no upstream branch".to_string());
+        assert_eq!(
+            reason_text(&messages, "git_push_set_upstream", &reason),
+            "rule git_push_set_upstream: matched 'has no upstream branch' in output"
+        );
+        let reason = Reason::Described("Pushes to a new upstream".to_string());
+        assert_eq!(reason_text(&messages, "git_push", &reason), "rule git_push: Pushes to a new upstream");
+        assert_eq!(reason_text(&messages, "ls", &Reason::Matched), "rule ls");
+    }
+
+    #[test]
+    fn test_explanation_is_plain() {
+        let config = Config::default();
+        let corrections = [
+            CorrectedCommand::new("git push --set-upstream origin main", 100).with_rule("git_push_set_upstream"),
+            CorrectedCommand::new("git pull", 200),
+        ];
+        let views = [
+            View {
+                reason: Some("rule git_push_set_upstream: matched 'has no upstream branch' in output".to_string()),
+                diff: false,
+            },
+            View::default(),
+        ];
+
+        let renderer = Renderer::new(Theme::default(), false).with_width(50);
+        let text = explanation_text(&Messages::english(), &renderer, &corrections, &views, "git push", &config);
+        assert_eq!(
+            text,
+            "  $ git push\n  1.   git push --set-upstream origin main  git_push_set_upstream\n       \
+             rule git_push_set_upstream: matched 'has n…\n  2.   git pull\n"
+        );
+        assert!(!text.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_rule_doc() {
+        use fasterthefuck::SimpleRuleBuilder;
+        let output: String = (1..=10).map(|n| format!("line {}\n", n)).collect();
+        let rule = RuleMetadata {
+            category: Some("git".to_string()),
+            failure_kind: Some(fasterthefuck::FailureKind::UsageError),
+            ..RuleMetadata::of(SimpleRuleBuilder::new("test").match_output("line 1").replace("a", "b").as_ref())
+        }
+        .describe("Replaces a with b")
+        .example(rules::Example::new("git a", output, 2, "git b"));
+        assert_eq!(
+            rule_doc(&Messages::english(), &rule),
+            "test\nReplaces a with b\n\nGroup:     git\nPriority:  1000\nOutput:    line 1 (substring)\n\
+             Failure:   usage_error\n\nExample, exiting with 2:\n    $ git a\n    line 1\n    line 2\n    line 3\n    \
+             line 4\n    line 5\n    line 6\n    line 7\n    line 8\n    …\n    → git b\n"
+        );
+
+        let rule = RuleMetadata::of(SimpleRuleBuilder::new("test").replace("a", "b").as_ref());
+        let no_example = "\n\nNo example: what the rule suggests depends on this machine or the config.\n";
+        assert!(rule_doc(&Messages::english(), &rule).ends_with(no_example));
+    }
 }
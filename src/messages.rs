@@ -0,0 +1,234 @@
+//! Catalog of user-facing messages.
+//!
+//! Every prompt, warning and line of subcommand output has a key. Its text
+//! comes from, in order: the config's `[messages]` overrides, a locale file
+//! in the config directory, a locale shipped with ftf, and English. The
+//! locale is picked from `LC_ALL`, `LC_MESSAGES` or `LANG`, as `es_ES.UTF-8`
+//! or `es`. Words in braces, such as `{count}`, are filled in when a message
+//! is shown.
+
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// The English messages, which define every key.
+const ENGLISH: &str = include_str!("../locales/en.toml");
+
+/// Locales shipped with ftf, by language.
+const SHIPPED: &[(&str, &str)] = &[("es", include_str!("../locales/es.toml"))];
+
+/// The text of every message, by key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Messages {
+    texts: HashMap<String, String>,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+impl Messages {
+    /// Gets the English messages.
+    pub fn english() -> Self {
+        Self {
+            texts: parse(ENGLISH).expect("the English messages are valid TOML"),
+        }
+    }
+
+    /// Gets the messages for `locale`, such as `es_ES.UTF-8`, from the
+    /// shipped locales and the files in `dir`, with `overrides` on top.
+    /// Keys missing from the locale stay in English.
+    pub fn load(overrides: &HashMap<String, String>, locale: Option<&str>, dir: Option<&Path>) -> Result<Self> {
+        let mut messages = Self::english();
+        let languages = locale.map(languages).unwrap_or_default();
+
+        // Most general first, so the more specific one wins
+        for language in languages.iter().rev() {
+            if let Some((_, shipped)) = SHIPPED.iter().find(|(name, _)| name == language) {
+                messages.extend(parse(shipped)?);
+            }
+            let Some(path) = dir.map(|dir| dir.join(format!("{}.toml", language))) else {
+                continue;
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => messages.extend(
+                    parse(&contents).map_err(|e| Error::config(format!("{}: {}", path.display(), e)))?,
+                ),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        messages.extend(overrides.clone());
+        Ok(messages)
+    }
+
+    /// Gets the keys in `overrides` that aren't messages, most likely typos.
+    pub fn unknown_keys(overrides: &HashMap<String, String>) -> Vec<&str> {
+        let english = Self::english();
+        let mut unknown: Vec<&str> = overrides
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !english.texts.contains_key(*key))
+            .collect();
+        unknown.sort();
+        unknown
+    }
+
+    /// Gets the message `key` with the words in braces replaced by `args`.
+    /// An unknown key is shown as is.
+    pub fn text(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let Some(template) = self.texts.get(key) else {
+            return key.to_string();
+        };
+        args.iter().fold(template.clone(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), &value.to_string())
+        })
+    }
+
+    /// Adds `texts`, replacing messages with the same key. Keys English
+    /// doesn't have are left out.
+    fn extend(&mut self, texts: HashMap<String, String>) {
+        for (key, text) in texts {
+            if let Some(existing) = self.texts.get_mut(&key) {
+                *existing = text;
+            }
+        }
+    }
+}
+
+/// Gets the directory holding locale files: `~/.config/fasterthefuck/locales`.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fasterthefuck").join("locales"))
+}
+
+/// Gets the locale the environment asks for, from the first of `LC_ALL`,
+/// `LC_MESSAGES` and `LANG` that's set.
+pub fn locale(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|key| env(key).filter(|value| !value.is_empty()))
+}
+
+/// Gets the languages a locale names, most specific first: `es_ES.UTF-8`
+/// gives `es_ES` and `es`. The C and POSIX locales give none.
+fn languages(locale: &str) -> Vec<String> {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return Vec::new();
+    }
+    let mut languages = vec![name.to_string()];
+    if let Some((language, _)) = name.split_once('_') {
+        languages.push(language.to_string());
+    }
+    languages
+}
+
+/// Parses a locale file: a TOML table of messages by key.
+fn parse(contents: &str) -> Result<HashMap<String, String>> {
+    toml::from_str(contents).map_err(|e| Error::config(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_messages_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_text_fills_in_arguments() {
+        let messages = Messages::english();
+        assert_eq!(messages.text("select_prompt", &[("count", &3)]), "Select correction (1-3, d toggles diffs, cN copies): ");
+        assert_eq!(
+            messages.text("pack_synced", &[("name", &"team"), ("rules", &2)]),
+            "synced rule pack 'team' (2 rules)"
+        );
+        assert_eq!(messages.text("no_such_key", &[]), "no_such_key");
+    }
+
+    #[test]
+    fn test_shipped_locales_translate_every_message() {
+        let english = parse(ENGLISH).unwrap();
+        for (language, contents) in SHIPPED {
+            let texts = parse(contents).unwrap();
+            let mut missing: Vec<_> = english.keys().filter(|key| !texts.contains_key(*key)).collect();
+            missing.sort();
+            assert!(missing.is_empty(), "{} is missing {:?}", language, missing);
+            let mut extra: Vec<_> = texts.keys().filter(|key| !english.contains_key(*key)).collect();
+            extra.sort();
+            assert!(extra.is_empty(), "{} has unknown keys {:?}", language, extra);
+        }
+    }
+
+    #[test]
+    fn test_locale_picks_shipped_messages() {
+        let messages = Messages::load(&HashMap::new(), Some("es_ES.UTF-8"), None).unwrap();
+        assert_eq!(messages.text("multiple_corrections", &[]), "Hay varias correcciones disponibles:");
+
+        for locale in ["C", "POSIX", "C.UTF-8", "fr_FR.UTF-8"] {
+            let messages = Messages::load(&HashMap::new(), Some(locale), None).unwrap();
+            assert_eq!(messages, Messages::english(), "{}", locale);
+        }
+    }
+
+    #[test]
+    fn test_locale_file_and_overrides_take_precedence() {
+        let dir = temp_dir("precedence");
+        std::fs::write(dir.join("ja.toml"), "multiple_corrections = \"複数の修正候補があります:\"\n").unwrap();
+        std::fs::write(dir.join("es_MX.toml"), "plan_steps = \"La corrección corre estos pasos:\"\n").unwrap();
+
+        let messages = Messages::load(&HashMap::new(), Some("ja_JP.UTF-8"), Some(&dir)).unwrap();
+        assert_eq!(messages.text("multiple_corrections", &[]), "複数の修正候補があります:");
+        // Keys the file leaves out stay in English
+        assert_eq!(messages.text("select_prompt", &[("count", &2)]), "Select correction (1-2, d toggles diffs, cN copies): ");
+
+        // A regional file wins over the shipped language
+        let messages = Messages::load(&HashMap::new(), Some("es_MX"), Some(&dir)).unwrap();
+        assert_eq!(messages.text("plan_steps", &[]), "La corrección corre estos pasos:");
+        assert_eq!(messages.text("no_file_changes", &[]), "No modifica ningún archivo.");
+
+        let overrides = HashMap::from([("multiple_corrections".to_string(), "Pick one:".to_string())]);
+        let messages = Messages::load(&overrides, Some("ja_JP.UTF-8"), Some(&dir)).unwrap();
+        assert_eq!(messages.text("multiple_corrections", &[]), "Pick one:");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_broken_locale_file_is_an_error() {
+        let dir = temp_dir("broken");
+        std::fs::write(dir.join("de.toml"), "multiple_corrections = \n").unwrap();
+        let error = Messages::load(&HashMap::new(), Some("de_DE.UTF-8"), Some(&dir)).unwrap_err();
+        assert!(error.to_string().contains("de.toml"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unknown_override_keys() {
+        let overrides = HashMap::from([
+            ("select_prompt".to_string(), "Which? ".to_string()),
+            ("selct_prompt".to_string(), "Which? ".to_string()),
+        ]);
+        assert_eq!(Messages::unknown_keys(&overrides), ["selct_prompt"]);
+    }
+
+    #[test]
+    fn test_locale_from_environment() {
+        let env = |key: &str| match key {
+            "LC_MESSAGES" => Some(String::new()),
+            "LANG" => Some("es_ES.UTF-8".to_string()),
+            _ => None,
+        };
+        assert_eq!(locale(env).as_deref(), Some("es_ES.UTF-8"));
+        let env = |key: &str| (key == "LC_ALL").then(|| "ja_JP.UTF-8".to_string());
+        assert_eq!(locale(env).as_deref(), Some("ja_JP.UTF-8"));
+        assert_eq!(languages("es_ES.UTF-8@euro"), ["es_ES", "es"]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Ingestion of command output.
+//!
+//! A failed build can print hundreds of megabytes, and every rule scans the
+//! output at least once. Output is read with a cap, keeping its head and its
+//! tail (where most errors are) and dropping the middle, so memory use is
+//! bounded no matter how much is piped in.
+//!
+//! Output is read as bytes, since tools such as tar, or a binary cat'd by
+//! mistake, print bytes that aren't UTF-8. Those are replaced by U+FFFD, and
+//! [`CappedOutput::invalid_utf8`] records that they were.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+/// Default cap on the output kept, in bytes.
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Size of each read from a stream.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Output read with a cap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CappedOutput {
+    /// The head and tail of the output, joined by a line noting the gap
+    pub text: String,
+    /// Whether bytes were dropped from the middle
+    pub truncated: bool,
+    /// Whether the output had bytes that aren't UTF-8, replaced in `text`.
+    /// A character split by the cut doesn't count.
+    pub invalid_utf8: bool,
+}
+
+impl CappedOutput {
+    /// Joins `head` and `tail`, noting the `omitted` bytes between them.
+    fn join(head: &[u8], tail: &[u8], omitted: usize) -> Self {
+        if omitted == 0 {
+            let mut whole = head.to_vec();
+            whole.extend_from_slice(tail);
+            return Self {
+                invalid_utf8: std::str::from_utf8(&whole).is_err(),
+                text: String::from_utf8_lossy(&whole).into_owned(),
+                truncated: false,
+            };
+        }
+
+        // A cut may split a character; lossy decoding replaces its pieces
+        let text = format!(
+            "{}\n[ftf: {} bytes omitted]\n{}",
+            String::from_utf8_lossy(head),
+            omitted,
+            String::from_utf8_lossy(tail)
+        );
+        let head = &head[..head.len() - split_end(head)];
+        let tail = &tail[split_start(tail)..];
+        Self {
+            text,
+            truncated: true,
+            invalid_utf8: std::str::from_utf8(head).is_err() || std::str::from_utf8(tail).is_err(),
+        }
+    }
+}
+
+/// Caps output that's already in memory, such as an `--output` argument.
+pub fn cap(output: &str, max_bytes: usize) -> CappedOutput {
+    let bytes = output.as_bytes();
+    if bytes.len() <= max_bytes {
+        return CappedOutput {
+            text: output.to_string(),
+            truncated: false,
+            invalid_utf8: false,
+        };
+    }
+    cap_bytes(bytes, max_bytes)
+}
+
+/// Caps output that's in memory but may not be UTF-8, such as an
+/// `--output` argument the OS passed as raw bytes.
+pub fn cap_bytes(output: &[u8], max_bytes: usize) -> CappedOutput {
+    if output.len() <= max_bytes {
+        return CappedOutput::join(output, &[], 0);
+    }
+    let head = max_bytes / 2;
+    let tail = max_bytes - head;
+    CappedOutput::join(&output[..head], &output[output.len() - tail..], output.len() - max_bytes)
+}
+
+/// Gets how many bytes at the end of `head` are the start of a character
+/// the cut split.
+fn split_end(head: &[u8]) -> usize {
+    for back in 1..=head.len().min(3) {
+        let byte = head[head.len() - back];
+        if byte & 0xc0 != 0x80 {
+            // The lead byte says how long its character is
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            return if len > back { back } else { 0 };
+        }
+    }
+    0
+}
+
+/// Gets how many bytes at the start of `tail` are the rest of a character
+/// the cut split.
+fn split_start(tail: &[u8]) -> usize {
+    tail.iter().take(3).take_while(|byte| *byte & 0xc0 == 0x80).count()
+}
+
+/// Reads `reader` to the end, keeping at most `max_bytes` of it: the first
+/// half and the last half.
+///
+/// At most `max_bytes` plus one read chunk are held at any time.
+pub fn read_capped(mut reader: impl Read, max_bytes: usize) -> io::Result<CappedOutput> {
+    let head_limit = max_bytes / 2;
+    let tail_limit = max_bytes - head_limit;
+    let mut head = Vec::new();
+    let mut tail = VecDeque::new();
+    let mut omitted = 0;
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let mut data = &chunk[..read];
+
+        let to_head = data.len().min(head_limit - head.len());
+        head.extend_from_slice(&data[..to_head]);
+        data = &data[to_head..];
+
+        tail.extend(data);
+        if tail.len() > tail_limit {
+            let excess = tail.len() - tail_limit;
+            tail.drain(..excess);
+            omitted += excess;
+        }
+    }
+
+    Ok(CappedOutput::join(&head, tail.make_contiguous(), omitted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_output_is_kept_whole() {
+        let output = read_capped("error: something failed\n".as_bytes(), 1024).unwrap();
+        assert_eq!(output.text, "error: something failed\n");
+        assert!(!output.truncated);
+        assert_eq!(cap("error", 1024).text, "error");
+    }
+
+    #[test]
+    fn test_long_output_keeps_head_and_tail() {
+        let input = format!("{}{}{}", "a".repeat(100), "b".repeat(1000), "c".repeat(100));
+
+        let streamed = read_capped(input.as_bytes(), 200).unwrap();
+        assert!(streamed.truncated);
+        assert_eq!(streamed.text, format!("{}\n[ftf: 1000 bytes omitted]\n{}", "a".repeat(100), "c".repeat(100)));
+        assert_eq!(cap(&input, 200), streamed);
+    }
+
+    /// Yields `len` bytes of build log, then `last_line`, without ever
+    /// holding them all.
+    struct SyntheticLog {
+        remaining: usize,
+        last_line: &'static [u8],
+    }
+
+    impl Read for SyntheticLog {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.remaining == 0 {
+                let read = self.last_line.len().min(buf.len());
+                buf[..read].copy_from_slice(&self.last_line[..read]);
+                self.last_line = &self.last_line[read..];
+                return Ok(read);
+            }
+            let line = b"   Compiling some-crate v1.0.0 (/src/some-crate)\n";
+            let read = buf.len().min(self.remaining);
+            for (i, byte) in buf[..read].iter_mut().enumerate() {
+                *byte = line[i % line.len()];
+            }
+            self.remaining -= read;
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn test_large_stream_keeps_tail_error() {
+        let log = SyntheticLog {
+            remaining: 10 * 1024 * 1024,
+            last_line: b"\nerror[E0432]: unresolved import `serde_json`\n",
+        };
+
+        let output = read_capped(log, DEFAULT_MAX_OUTPUT_BYTES).unwrap();
+        assert!(output.truncated);
+        assert!(output.text.len() < DEFAULT_MAX_OUTPUT_BYTES + 64);
+        assert!(output.text.ends_with("error[E0432]: unresolved import `serde_json`\n"));
+    }
+
+    #[test]
+    fn test_split_character_is_replaced() {
+        let input = "é".repeat(100);
+        let output = cap(&input, 11);
+        assert!(output.truncated);
+        assert!(output.text.starts_with("éé\u{fffd}\n"));
+        assert!(!output.invalid_utf8);
+    }
+
+    #[test]
+    fn test_invalid_bytes_are_replaced_and_noted() {
+        let archive = include_bytes!("../tests/fixtures/invalid_utf8/tar_output");
+        let output = read_capped(archive.as_slice(), 1024).unwrap();
+        assert!(output.invalid_utf8);
+        assert!(output.text.contains('\u{fffd}'));
+        assert!(output.text.ends_with("tar: Exiting with failure status due to previous errors\n"));
+        assert_eq!(cap_bytes(archive, 1024), output);
+
+        // Invalid bytes on either side of the cut still count
+        let output = cap_bytes(archive, 64);
+        assert!(output.truncated && output.invalid_utf8);
+        let output = cap_bytes("é".repeat(100).as_bytes(), 11);
+        assert!(output.truncated && !output.invalid_utf8);
+        assert!(!cap_bytes(b"error: something failed", 1024).invalid_utf8);
+    }
+}
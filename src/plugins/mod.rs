@@ -0,0 +1,374 @@
+//! Rules implemented by external executables.
+//!
+//! Every executable in `~/.config/fasterthefuck/rules/` is a rule named after
+//! its file, without the extension, so the config can disable it like a
+//! built-in rule. It's run with the failed command as JSON on stdin:
+//!
+//! ```text
+//! {"script":"dokcer ps","output":"dokcer: command not found","exit_code":127}
+//! ```
+//!
+//! and prints a JSON array of corrections, empty if it doesn't match:
+//!
+//! ```text
+//! [{"script":"docker ps","priority":900}]
+//! ```
+//!
+//! A plugin that fails, prints something else or runs past its timeout is
+//! logged and offers no corrections; the other rules are
+//! unaffected.
+//!
+//! Plugins run as the user, so the directory and every plugin must be owned
+//! by them and writable by no one else. Otherwise they're logged and not
+//! run.
+//!
+//! With the `wasm-plugins` feature, rules compiled to WASM run in-process
+//! instead (see [`wasm`]). Rules shared as TOML files are loaded from
+//! [`packs`].
+
+pub mod packs;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm;
+
+use crate::rules::RuleFactory;
+use crate::{Command, CorrectedCommand, Error, Result, Rule};
+use serde::Deserialize;
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command as StdCommand, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Priority of plugin corrections that don't give one.
+pub const DEFAULT_PRIORITY: i32 = 1000;
+
+/// Gets the directory plugins are loaded from.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fasterthefuck").join("rules"))
+}
+
+/// Gets a factory for the plugins in `dir`, each allowed to run for
+/// `timeout`. Plugins could match any command, and aren't listed by name.
+pub fn factory(dir: PathBuf, timeout: Duration) -> RuleFactory<'static> {
+    RuleFactory::new("plugins", &[], move || load(&dir, timeout))
+}
+
+/// Loads every executable in `dir` as a rule, in file name order. A missing
+/// directory has no plugins, and neither has one others could write to.
+pub fn load(dir: &Path, timeout: Duration) -> Vec<Box<dyn Rule>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    if !is_private(dir) {
+        tracing::warn!(dir = %dir.display(), "plugin directory not loaded: others could change it");
+        return Vec::new();
+    }
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_plugin(path))
+        .filter(|path| {
+            let private = is_private(path);
+            if !private {
+                tracing::warn!(path = %path.display(), "plugin not loaded: others could change it");
+            }
+            private
+        })
+        .collect();
+    paths.sort();
+    tracing::debug!(dir = %dir.display(), plugins = paths.len(), "loading plugins");
+
+    paths
+        .into_iter()
+        .filter_map(|path| ScriptRule::new(path, timeout))
+        .inspect(|rule| tracing::debug!(plugin = rule.name, path = %rule.path.display(), "loaded plugin"))
+        .map(|rule| Box::new(rule) as Box<dyn Rule>)
+        .collect()
+}
+
+/// Returns true if `path` is an executable file that isn't hidden.
+fn is_plugin(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_none_or(|name| name.starts_with('.'));
+    !hidden
+        && std::fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Checks that `path` is owned by the current user and writable by no one
+/// else, so nobody else can choose what runs as them.
+fn is_private(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.uid() == nix::unistd::getuid().as_raw() && metadata.mode() & 0o022 == 0)
+}
+
+/// Encodes `command` as plugins receive it.
+fn command_json(command: &Command) -> String {
+    serde_json::json!({
+        "script": command.script,
+        "output": command.output,
+        "exit_code": command.exit_code,
+    })
+    .to_string()
+}
+
+/// A correction as a plugin prints it.
+#[derive(Debug, Deserialize)]
+struct PluginCorrection {
+    script: String,
+    #[serde(default = "default_priority")]
+    priority: i32,
+}
+
+fn default_priority() -> i32 {
+    DEFAULT_PRIORITY
+}
+
+/// The fields of a command a plugin result was for.
+type CommandKey = (String, String, i32);
+
+/// A rule that runs an external executable.
+pub struct ScriptRule {
+    name: String,
+    path: PathBuf,
+    timeout: Duration,
+    /// The last command the plugin ran for, and its corrections, so
+    /// `matches` and the corrections run it once
+    last: Mutex<Option<(CommandKey, Vec<PluginCorrection>)>>,
+}
+
+impl ScriptRule {
+    /// Wraps the executable at `path`, named after its file without the
+    /// extension. Paths without a file name have no rule.
+    pub fn new(path: PathBuf, timeout: Duration) -> Option<Self> {
+        let name = path.file_stem()?.to_str()?.to_string();
+        Some(Self {
+            name,
+            path,
+            timeout,
+            last: Mutex::new(None),
+        })
+    }
+
+    /// Runs `f` on the plugin's corrections for `command`, running it if it
+    /// hasn't run for this command yet.
+    fn with_corrections<T>(&self, command: &Command, f: impl FnOnce(&[PluginCorrection]) -> T) -> T {
+        let key = (command.script.clone(), command.output.clone(), command.exit_code);
+        let mut last = self.last.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if last.as_ref().is_none_or(|(last_key, _)| *last_key != key) {
+            let corrections = self.run(command).unwrap_or_else(|e| {
+                tracing::warn!(plugin = self.name, error = %e, "plugin failed");
+                Vec::new()
+            });
+            *last = Some((key, corrections));
+        }
+        f(last.as_ref().map_or(&[][..], |(_, corrections)| corrections))
+    }
+
+    /// Runs the plugin on `command` and parses what it prints.
+    fn run(&self, command: &Command) -> Result<Vec<PluginCorrection>> {
+        let _entered = tracing::debug_span!("plugin", plugin = self.name).entered();
+        let input = command_json(command);
+
+        let mut process = StdCommand::new(&self.path);
+        process.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null());
+        // In its own process group, so a timeout also kills what it started
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        let mut child = process.spawn()?;
+
+        // Feed stdin on its own thread, so a plugin that doesn't read its
+        // input can't block the timeout
+        let mut stdin = child.stdin.take();
+        std::thread::spawn(move || {
+            if let Some(stdin) = stdin.as_mut() {
+                let _ = stdin.write_all(input.as_bytes());
+            }
+        });
+
+        let (status, printed, _, timed_out) = crate::shell::wait_for(child, Some(self.timeout))?;
+        if timed_out {
+            return Err(Error::rule(format!("timed out after {:?}", self.timeout)));
+        }
+        if !status.success() {
+            return Err(Error::rule(format!("exited with {}", status)));
+        }
+
+        serde_json::from_slice(&printed).map_err(|e| Error::rule(format!("printed invalid corrections: {}", e)))
+    }
+}
+
+impl Rule for ScriptRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        self.with_corrections(command, |corrections| !corrections.is_empty())
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.with_corrections(command, |corrections| {
+            corrections.iter().map(|correction| correction.script.clone()).collect()
+        })
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+
+    fn get_corrected_commands(&self, command: &Command) -> Vec<CorrectedCommand> {
+        // Plugins rank their own corrections
+        self.with_corrections(command, |corrections| {
+            corrections
+                .iter()
+                .map(|correction| CorrectedCommand::new(&correction.script, correction.priority).with_rule(&self.name))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_plugins_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    fn write_plugin(dir: &Path, name: &str, body: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_only_executables() {
+        let dir = plugin_dir("load");
+        write_plugin(&dir, "b_rule.sh", "echo '[]'", 0o755);
+        write_plugin(&dir, "a_rule", "echo '[]'", 0o755);
+        write_plugin(&dir, "notes.txt", "", 0o644);
+        write_plugin(&dir, ".hidden", "echo '[]'", 0o755);
+
+        let names: Vec<String> = load(&dir, Duration::from_secs(1))
+            .iter()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["a_rule", "b_rule"]);
+        assert!(load(&dir.join("missing"), Duration::from_secs(1)).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugins_others_can_change_are_refused() {
+        let dir = plugin_dir("writable");
+        write_plugin(&dir, "private", "echo '[]'", 0o755);
+        write_plugin(&dir, "group_writable", "echo '[]'", 0o775);
+        write_plugin(&dir, "world_writable", "echo '[]'", 0o757);
+
+        let names: Vec<String> = load(&dir, Duration::from_secs(1))
+            .iter()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["private"]);
+
+        // Nothing is loaded from a directory others could add plugins to
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(load(&dir, Duration::from_secs(1)).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_corrections_keep_their_priorities() {
+        let dir = plugin_dir("corrections");
+        let path = write_plugin(
+            &dir,
+            "docker_typo.sh",
+            r#"cat > /dev/null; echo '[{"script":"docker ps","priority":900},{"script":"docker ps -a"}]'"#,
+            0o755,
+        );
+        let rule = ScriptRule::new(path, Duration::from_secs(5)).unwrap();
+        let cmd = Command::new("dokcer ps", "dokcer: command not found", 127);
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, "docker ps");
+        assert_eq!(corrections[0].priority, 900);
+        assert_eq!(corrections[1].priority, DEFAULT_PRIORITY);
+        assert_eq!(corrections[1].rule.as_deref(), Some("docker_typo"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_runs_once_per_command() {
+        let dir = plugin_dir("once");
+        let count = dir.join("runs");
+        let path = write_plugin(
+            &dir,
+            "counting",
+            &format!("echo run >> '{}'; echo '[{{\"script\":\"ls\"}}]'", count.display()),
+            0o755,
+        );
+        let rule = ScriptRule::new(path, Duration::from_secs(5)).unwrap();
+        let cmd = Command::new("sl", "", 127);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ls"]);
+        assert_eq!(std::fs::read_to_string(&count).unwrap().lines().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failing_plugins_offer_nothing() {
+        let dir = plugin_dir("failing");
+        let cmd = Command::new("dokcer ps", "", 127);
+        let cases = [
+            ("crashes", "exit 3", "exited with exit status: 3"),
+            ("garbage", "echo 'not json'", "printed invalid corrections"),
+            ("slow", "sleep 5", "timed out after 200ms"),
+        ];
+
+        for (name, body, error) in cases {
+            let rule = ScriptRule::new(write_plugin(&dir, name, body, 0o755), Duration::from_millis(200)).unwrap();
+            let message = rule.run(&cmd).unwrap_err().to_string();
+            assert!(message.contains(error), "{}: {}", name, message);
+            assert!(!rule.matches(&cmd));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_timeout_kills_what_the_plugin_started() {
+        let dir = plugin_dir("orphans");
+        let pid_file = dir.join("pid");
+        let body = format!("sleep 30 &\necho $! > {}\nwait", pid_file.display());
+        let rule = ScriptRule::new(write_plugin(&dir, "spawns", &body, 0o755), Duration::from_millis(200)).unwrap();
+        let message = rule.run(&Command::new("dokcer ps", "", 127)).unwrap_err().to_string();
+        assert!(message.contains("timed out"), "{}", message);
+
+        // Gone, or a zombie waiting for init to reap it
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = Path::new("/proc").join(pid.trim()).join("stat");
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        let running = loop {
+            let running = std::fs::read_to_string(&stat).is_ok_and(|stat| !stat.contains(") Z "));
+            if !running || std::time::Instant::now() >= deadline {
+                break running;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(!running, "sleep {} outlived its plugin", pid.trim());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
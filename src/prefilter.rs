@@ -0,0 +1,170 @@
+//! Substring pre-filter for rule matching.
+//!
+//! Many rules only match when the script or output contains a fixed
+//! substring. Checking each one with `contains` scans a long output once per
+//! rule, so the substrings of all rules are combined into one Aho-Corasick
+//! automaton per field and each command is scanned once. Rules without a
+//! required substring always pass the filter.
+
+use crate::{Command, Rule};
+use aho_corasick::AhoCorasick;
+use std::collections::HashMap;
+
+/// Required substrings of one field (script or output) across all rules.
+struct FieldFilter {
+    automaton: Option<AhoCorasick>,
+    pattern_count: usize,
+    /// Pattern each rule requires, by rule index
+    required: Vec<Option<usize>>,
+}
+
+impl FieldFilter {
+    fn new<'a>(required: impl Iterator<Item = Option<&'a str>>) -> Self {
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        let mut patterns = Vec::new();
+        let required = required
+            .map(|substring| {
+                substring.map(|substring| {
+                    *ids.entry(substring).or_insert_with(|| {
+                        patterns.push(substring);
+                        patterns.len() - 1
+                    })
+                })
+            })
+            .collect();
+
+        // Building only fails for automata far larger than rule sets get
+        let automaton = (!patterns.is_empty())
+            .then(|| AhoCorasick::new(&patterns).ok())
+            .flatten();
+        Self {
+            automaton,
+            pattern_count: patterns.len(),
+            required,
+        }
+    }
+
+    /// Gets which patterns occur in `haystack`, by pattern id.
+    fn found(&self, haystack: &str) -> Vec<bool> {
+        let mut found = vec![false; self.pattern_count];
+        let Some(automaton) = &self.automaton else {
+            // Without an automaton every rule has to be checked
+            found.fill(true);
+            return found;
+        };
+
+        let mut remaining = self.pattern_count;
+        for found_match in automaton.find_overlapping_iter(haystack) {
+            let seen = &mut found[found_match.pattern().as_usize()];
+            if !*seen {
+                *seen = true;
+                remaining -= 1;
+                if remaining == 0 {
+                    break;
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Skips rules whose required substrings a command doesn't contain.
+pub struct Prefilter {
+    script: FieldFilter,
+    output: FieldFilter,
+}
+
+impl Prefilter {
+    /// Builds the filter for `rules`, indexed by their position.
+    pub fn new(rules: &[Box<dyn Rule>]) -> Self {
+        Self {
+            script: FieldFilter::new(rules.iter().map(|rule| rule.required_script())),
+            output: FieldFilter::new(rules.iter().map(|rule| rule.required_output())),
+        }
+    }
+
+    /// Scans `command` once and gets which rules could match it.
+    pub fn candidates(&self, command: &Command) -> Candidates {
+        let script = self.script.found(&command.script);
+        let output = self.output.found(&command.output);
+        let rules = self
+            .script
+            .required
+            .iter()
+            .zip(&self.output.required)
+            .map(|(in_script, in_output)| {
+                in_script.is_none_or(|id| script[id]) && in_output.is_none_or(|id| output[id])
+            })
+            .collect();
+        Candidates { rules }
+    }
+}
+
+/// The rules that passed the pre-filter for one command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidates {
+    rules: Vec<bool>,
+}
+
+impl Candidates {
+    /// Returns true if the rule at `index` could match.
+    ///
+    /// Indices the filter wasn't built with always could.
+    pub fn contains(&self, index: usize) -> bool {
+        self.rules.get(index).copied().unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimpleRuleBuilder;
+
+    fn rules() -> Vec<Box<dyn Rule>> {
+        vec![
+            SimpleRuleBuilder::new("apt_autoremove")
+                .match_command("apt remove")
+                .match_output("WARNING: The following")
+                .replace("apt remove", "apt autoremove"),
+            SimpleRuleBuilder::new("git_push_force")
+                .match_command("git push")
+                .match_output("Updates were rejected")
+                .replace("git push", "git push --force-with-lease"),
+            SimpleRuleBuilder::new("any_sudo")
+                .match_output("Permission denied")
+                .prepend("sudo"),
+            SimpleRuleBuilder::new("push_again")
+                .match_command("git push")
+                .replace("git push", "git push origin HEAD"),
+        ]
+    }
+
+    #[test]
+    fn test_candidates() {
+        let filter = Prefilter::new(&rules());
+        let cmd = Command::new("git push", "! [rejected]\nUpdates were rejected because the tip", 1);
+
+        let candidates = filter.candidates(&cmd);
+        assert!(!candidates.contains(0));
+        assert!(candidates.contains(1));
+        assert!(!candidates.contains(2));
+        assert!(candidates.contains(3));
+    }
+
+    #[test]
+    fn test_overlapping_substrings_are_all_found() {
+        let rules: Vec<Box<dyn Rule>> = vec![
+            SimpleRuleBuilder::new("long").match_output("not found here").prepend("a"),
+            SimpleRuleBuilder::new("short").match_output("found").prepend("b"),
+        ];
+        let candidates = Prefilter::new(&rules).candidates(&Command::new("x", "bash: x: not found here", 127));
+        assert!(candidates.contains(0));
+        assert!(candidates.contains(1));
+    }
+
+    #[test]
+    fn test_unknown_rules_pass() {
+        let filter = Prefilter::new(&[]);
+        assert!(filter.candidates(&Command::new("ls", "", 0)).contains(7));
+    }
+}
@@ -0,0 +1,118 @@
+//! Privilege escalation tools.
+//!
+//! Rules write corrections that need root with `sudo`. Some systems ship
+//! `doas` or systemd's `run0` instead, so the tool is resolved once per
+//! invocation and the corrections are rewritten to use it.
+
+use crate::{Script, Shell};
+
+/// Supported escalation tools, in order of preference.
+pub const TOOLS: &[&str] = &["sudo", "doas", "run0"];
+
+/// The tool rules write corrections with, and the fallback when none is found.
+pub const DEFAULT_TOOL: &str = "sudo";
+
+/// Returns true if `token` is a supported escalation tool.
+pub fn is_escalation(token: &str) -> bool {
+    TOOLS.contains(&token)
+}
+
+/// Gets the first supported escalation tool the shell can run, or `sudo` if
+/// none is found.
+///
+/// Lookup errors count as missing.
+pub fn escalation_command(shell: &dyn Shell) -> String {
+    TOOLS
+        .iter()
+        .find(|tool| shell.command_exists(tool).unwrap_or(false))
+        .unwrap_or(&DEFAULT_TOOL)
+        .to_string()
+}
+
+/// Runs `script` with privilege escalation.
+///
+/// The script is written with [`DEFAULT_TOOL`]; every built-in rule's
+/// corrections are rewritten to the system's tool by
+/// `rules::util::escalate_with`.
+pub fn escalate(script: Script) -> Script {
+    script.prepend_token(DEFAULT_TOOL)
+}
+
+/// Rewrites the `sudo` that starts any command of `correction` to `tool`.
+///
+/// Corrections without one are returned unchanged.
+pub fn use_tool(correction: &str, tool: &str) -> String {
+    if tool == DEFAULT_TOOL {
+        return correction.to_string();
+    }
+
+    let script = Script::parse(correction);
+    let starts: Vec<usize> = script
+        .tokens()
+        .iter()
+        .enumerate()
+        .filter(|(i, token)| {
+            *token == DEFAULT_TOOL && (*i == 0 || Script::is_operator(&script.tokens()[i - 1]))
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if starts.is_empty() {
+        return correction.to_string();
+    }
+
+    starts
+        .into_iter()
+        .fold(script, |script, i| script.replace_token_at(i, tool))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    #[test]
+    fn test_escalation_command_prefers_sudo() {
+        let shell = MockShell::new()
+            .with_output("doas --version", "", 0)
+            .with_output("sudo --version", "", 0);
+        assert_eq!(escalation_command(&shell), "sudo");
+    }
+
+    #[test]
+    fn test_escalation_command_falls_back() {
+        let shell = MockShell::new().with_output("doas --version", "", 0);
+        assert_eq!(escalation_command(&shell), "doas");
+
+        let shell = MockShell::new().with_output("run0 --version", "", 0);
+        assert_eq!(escalation_command(&shell), "run0");
+
+        assert_eq!(escalation_command(&MockShell::new()), "sudo");
+    }
+
+    #[test]
+    fn test_use_tool() {
+        assert_eq!(use_tool("sudo apt install vim", "doas"), "doas apt install vim");
+        assert_eq!(
+            use_tool("sudo systemctl start redis && redis-cli ping", "run0"),
+            "run0 systemctl start redis && redis-cli ping"
+        );
+        assert_eq!(use_tool("make && sudo make install", "doas"), "make && doas make install");
+    }
+
+    #[test]
+    fn test_use_tool_keeps_quoting() {
+        assert_eq!(use_tool("sudo rm -f *.o", "doas"), "doas rm -f *.o");
+        assert_eq!(
+            use_tool("sudo tee '$HOME/out' < \"my file\"", "doas"),
+            "doas tee '$HOME/out' < \"my file\""
+        );
+    }
+
+    #[test]
+    fn test_use_tool_leaves_other_sudo_alone() {
+        assert_eq!(use_tool("echo sudo", "doas"), "echo sudo");
+        assert_eq!(use_tool("sudo apt update", "sudo"), "sudo apt update");
+        assert_eq!(use_tool("git push", "doas"), "git push");
+    }
+}
@@ -0,0 +1,110 @@
+//! A spinner shown while slow rules run.
+//!
+//! Rules that shell out, to list branches or search packages, can take a
+//! while. The spinner only appears once correcting has taken longer than a
+//! short delay, so fast corrections show nothing, and it's erased before
+//! anything else is printed.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// How long correcting may take before the spinner appears.
+pub const SPINNER_DELAY: Duration = Duration::from_millis(100);
+
+/// How often the spinner moves while no rule finishes.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(80);
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Clears the line the cursor is on.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// A one-line spinner with a label, drawn over itself.
+pub struct Spinner<W: Write> {
+    out: W,
+    enabled: bool,
+    delay: Duration,
+    started: Instant,
+    frame: usize,
+    shown: bool,
+}
+
+impl<W: Write> Spinner<W> {
+    /// Creates a spinner drawing on `out` after `delay`. A disabled spinner,
+    /// for when `out` isn't a terminal, draws nothing.
+    pub fn new(out: W, enabled: bool, delay: Duration) -> Self {
+        Self {
+            out,
+            enabled,
+            delay,
+            started: Instant::now(),
+            frame: 0,
+            shown: false,
+        }
+    }
+
+    /// Draws the next frame with `label`, once the delay has passed.
+    pub fn tick(&mut self, label: &str) {
+        if !self.enabled || self.started.elapsed() < self.delay {
+            return;
+        }
+        // The spinner is decoration; failing to draw it is no error
+        let _ = write!(self.out, "{}{} {}", CLEAR_LINE, FRAMES[self.frame % FRAMES.len()], label);
+        let _ = self.out.flush();
+        self.frame += 1;
+        self.shown = true;
+    }
+
+    /// Erases the spinner if it was drawn.
+    pub fn clear(&mut self) {
+        if self.shown {
+            let _ = write!(self.out, "{}", CLEAR_LINE);
+            let _ = self.out.flush();
+            self.shown = false;
+        }
+    }
+}
+
+impl<W: Write> Drop for Spinner<W> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_spinner_draws_nothing() {
+        let mut out = Vec::new();
+        let mut spinner = Spinner::new(&mut out, false, Duration::ZERO);
+        spinner.tick("checking rules… (1/2)");
+        spinner.clear();
+        drop(spinner);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_spinner_waits_for_the_delay() {
+        let mut out = Vec::new();
+        let mut spinner = Spinner::new(&mut out, true, Duration::from_secs(60));
+        spinner.tick("checking rules… (1/2)");
+        drop(spinner);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_spinner_draws_over_itself_and_is_erased() {
+        let mut out = Vec::new();
+        let mut spinner = Spinner::new(&mut out, true, Duration::ZERO);
+        spinner.tick("checking rules… (1/2)");
+        spinner.tick("checking rules… (2/2)");
+        spinner.clear();
+        drop(spinner);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\r\x1b[2K⠋ checking rules… (1/2)\r\x1b[2K⠙ checking rules… (2/2)\r\x1b[2K"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! The output protocol between ftf and its shell hook.
+//!
+//! Without `--execute`, ftf prints the corrected script and the hook
+//! evaluates it. Side effects that only the user's shell can perform follow
+//! as comment lines, so output evaluated as-is still just runs the script:
+//!
+//! ```text
+//! pyenv install 3.12.1 && python manage.py test
+//! # ftf-effect: rehash
+//! ```
+
+use crate::CorrectedCommand;
+
+/// Prefix of the lines naming a side effect for the hook.
+pub const EFFECT_PREFIX: &str = "# ftf-effect: ";
+
+/// Encodes a correction for the shell hook.
+pub fn encode(correction: &CorrectedCommand) -> String {
+    let mut encoded = correction.script.clone();
+    for effect in correction.side_effect.iter().filter(|effect| effect.runs_in_hook()) {
+        encoded.push('\n');
+        encoded.push_str(EFFECT_PREFIX);
+        encoded.push_str(effect.name());
+    }
+    encoded
+}
+
+/// Splits printed output back into the script and the names of its hook
+/// effects.
+pub fn decode(output: &str) -> (String, Vec<String>) {
+    let mut script = Vec::new();
+    let mut effects = Vec::new();
+    for line in output.lines() {
+        match line.strip_prefix(EFFECT_PREFIX) {
+            Some(name) => effects.push(name.trim().to_string()),
+            None => script.push(line),
+        }
+    }
+    (script.join("\n"), effects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SideEffect;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_encode_plain_correction() {
+        let correction = CorrectedCommand::new("git push --set-upstream origin main", 100);
+        assert_eq!(encode(&correction), "git push --set-upstream origin main");
+    }
+
+    #[test]
+    fn test_encode_hook_effect() {
+        let correction =
+            CorrectedCommand::with_side_effect("rustup toolchain install nightly && cargo +nightly build", 200, SideEffect::Rehash);
+
+        assert_eq!(
+            encode(&correction),
+            "rustup toolchain install nightly && cargo +nightly build\n# ftf-effect: rehash"
+        );
+    }
+
+    #[test]
+    fn test_encode_skips_effects_ftf_runs_itself() {
+        let effect = SideEffect::CreateParentDirs(PathBuf::from("archive/report.pdf"));
+        let correction = CorrectedCommand::with_side_effect("mv report.pdf archive/report.pdf", 400, effect);
+
+        assert_eq!(encode(&correction), "mv report.pdf archive/report.pdf");
+    }
+
+    #[test]
+    fn test_decode_round_trip() {
+        let correction = CorrectedCommand::with_side_effect("nvm install 20 && npm ci", 200, SideEffect::Rehash);
+
+        let (script, effects) = decode(&encode(&correction));
+        assert_eq!(script, "nvm install 20 && npm ci");
+        assert_eq!(effects, vec!["rehash"]);
+
+        let (script, effects) = decode("ls -la\n");
+        assert_eq!(script, "ls -la");
+        assert!(effects.is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+//! Suppression of corrections the user just rejected.
+//!
+//! When the interactive selector is cancelled, or a lower-ranked candidate is
+//! chosen, the passed-over corrections are recorded in the session cache.
+//! Corrections rejected for the same original command within the configured
+//! window are then left out of the next suggestion list. Only hashes of the
+//! scripts are stored.
+
+use crate::stats::script_hash;
+use crate::{CorrectedCommand, Error, Result, SessionContext};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File in the session directory holding recent rejections.
+const REJECTIONS_FILE: &str = "rejections.jsonl";
+
+/// A correction the user declined for a given original command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Hash of the failed command the correction was offered for
+    pub original_hash: String,
+    /// Hash of the rejected correction
+    pub correction_hash: String,
+}
+
+/// Recent rejections loaded from the session cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rejections {
+    entries: Vec<Rejection>,
+}
+
+/// Gets the current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Rejections {
+    /// Creates a set of rejections from entries.
+    pub fn from_entries(entries: Vec<Rejection>) -> Self {
+        Self { entries }
+    }
+
+    /// Loads rejections from `session_dir`, skipping malformed lines.
+    pub fn load(session_dir: &Path) -> Self {
+        let entries = std::fs::read_to_string(session_dir.join(REJECTIONS_FILE))
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { entries }
+    }
+
+    /// Appends rejections of `corrections` for `original` to `session_dir`.
+    pub fn record<'a>(
+        session_dir: &Path,
+        original: &str,
+        corrections: impl IntoIterator<Item = &'a str>,
+        timestamp: u64,
+    ) -> Result<()> {
+        SessionContext::create_dir(session_dir)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(session_dir.join(REJECTIONS_FILE))?;
+
+        for correction in corrections {
+            let rejection = Rejection {
+                timestamp,
+                original_hash: script_hash(original),
+                correction_hash: script_hash(correction),
+            };
+            let line = serde_json::to_string(&rejection).map_err(|e| Error::Other(e.to_string()))?;
+            writeln!(file, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if `correction` was rejected for `original` within
+    /// `window_secs` before `now`.
+    pub fn was_rejected(&self, original: &str, correction: &str, now: u64, window_secs: u64) -> bool {
+        let original_hash = script_hash(original);
+        let correction_hash = script_hash(correction);
+
+        self.entries.iter().any(|rejection| {
+            rejection.original_hash == original_hash
+                && rejection.correction_hash == correction_hash
+                && now.saturating_sub(rejection.timestamp) <= window_secs
+        })
+    }
+
+    /// Drops corrections rejected for `original` within the window.
+    pub fn filter(
+        &self,
+        original: &str,
+        corrections: Vec<CorrectedCommand>,
+        now: u64,
+        window_secs: u64,
+    ) -> Vec<CorrectedCommand> {
+        if window_secs == 0 || self.entries.is_empty() {
+            return corrections;
+        }
+
+        corrections
+            .into_iter()
+            .filter(|correction| !self.was_rejected(original, &correction.script, now, window_secs))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn rejection(original: &str, correction: &str, timestamp: u64) -> Rejection {
+        Rejection {
+            timestamp,
+            original_hash: script_hash(original),
+            correction_hash: script_hash(correction),
+        }
+    }
+
+    fn corrections(scripts: &[&str]) -> Vec<CorrectedCommand> {
+        scripts
+            .iter()
+            .enumerate()
+            .map(|(i, script)| CorrectedCommand::new(*script, (i as i32 + 1) * 100))
+            .collect()
+    }
+
+    #[test]
+    fn test_recent_rejection_is_dropped() {
+        let rejections = Rejections::from_entries(vec![rejection("git psuh", "git push", NOW - 60)]);
+
+        let filtered = rejections.filter("git psuh", corrections(&["git push", "git pull"]), NOW, 300);
+        assert_eq!(filtered, corrections(&["git pull"]));
+    }
+
+    #[test]
+    fn test_rejection_expires_after_window() {
+        let rejections = Rejections::from_entries(vec![rejection("git psuh", "git push", NOW - 301)]);
+
+        assert!(!rejections.was_rejected("git psuh", "git push", NOW, 300));
+        assert!(rejections.was_rejected("git psuh", "git push", NOW - 100, 300));
+    }
+
+    #[test]
+    fn test_rejection_is_per_original_command() {
+        let rejections = Rejections::from_entries(vec![rejection("git psuh", "git push", NOW)]);
+
+        let filtered = rejections.filter("git puhs", corrections(&["git push"]), NOW, 300);
+        assert_eq!(filtered, corrections(&["git push"]));
+    }
+
+    #[test]
+    fn test_zero_window_disables_suppression() {
+        let rejections = Rejections::from_entries(vec![rejection("git psuh", "git push", NOW)]);
+
+        let filtered = rejections.filter("git psuh", corrections(&["git push"]), NOW, 0);
+        assert_eq!(filtered.len(), 1);
+    }
+
+    #[test]
+    fn test_record_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ftf_rejections_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        Rejections::record(&dir, "git psuh", ["git push", "git pull"], NOW).unwrap();
+        let rejections = Rejections::load(&dir);
+
+        assert!(rejections.was_rejected("git psuh", "git push", NOW + 10, 300));
+        assert!(rejections.was_rejected("git psuh", "git pull", NOW + 10, 300));
+        assert!(!rejections.was_rejected("git psuh", "git status", NOW + 10, 300));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
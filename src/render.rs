@@ -0,0 +1,594 @@
+//! Rendering of the correction selector.
+//!
+//! The selector shows the failed command, then each candidate numbered, with
+//! the tokens it changed highlighted and the rule that suggested it in a
+//! column on the right. A candidate can instead be shown as a diff of only
+//! the tokens it changed. Under each candidate a dimmed line can say why it
+//! was offered, cut to the terminal's width. Colors come from a theme, and
+//! are only used when asked for: the layout is the same without them.
+
+use crate::diff::{self, Op, Region};
+use crate::{Error, Result};
+use std::collections::HashMap;
+
+/// Glyph marking candidates that always need confirmation.
+pub const WARNING_GLYPH: &str = "⚠";
+
+/// Glyphs before the header of an expanded and a collapsed group.
+pub const EXPANDED_GLYPH: &str = "▾";
+pub const COLLAPSED_GLYPH: &str = "▸";
+
+/// Width assumed when the terminal's can't be found, as when stderr is
+/// redirected.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Height assumed when the terminal's can't be found.
+pub const DEFAULT_HEIGHT: usize = 24;
+
+/// When to color the selector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color when stderr is a terminal, the config allows it and `NO_COLOR`
+    /// is unset
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorMode {
+    /// Decides whether to color, given whether stderr is a terminal, the
+    /// config's `color` setting and `NO_COLOR`. An explicit mode wins over
+    /// both.
+    pub fn enabled(self, is_terminal: bool, config_color: bool, no_color: Option<&str>) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            // NO_COLOR only counts when set to something
+            ColorMode::Auto => is_terminal && config_color && no_color.is_none_or(str::is_empty),
+        }
+    }
+}
+
+/// Parts of the selector a theme colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Element {
+    /// The failed command
+    Original,
+    /// Candidate numbers
+    Number,
+    /// Tokens a candidate changed
+    Changed,
+    /// Names of the rules behind candidates
+    Rule,
+    /// The glyph marking dangerous candidates
+    Warning,
+    /// Why a candidate was offered
+    Reason,
+    /// Tokens of the failed command a candidate's diff removes
+    Removed,
+    /// Headers of the groups candidates are shown in
+    Group,
+}
+
+impl Element {
+    const ALL: [Element; 8] = [
+        Element::Original,
+        Element::Number,
+        Element::Changed,
+        Element::Rule,
+        Element::Warning,
+        Element::Reason,
+        Element::Removed,
+        Element::Group,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Element::Original => "original",
+            Element::Number => "number",
+            Element::Changed => "changed",
+            Element::Rule => "rule",
+            Element::Warning => "warning",
+            Element::Reason => "reason",
+            Element::Removed => "removed",
+            Element::Group => "group",
+        }
+    }
+}
+
+/// SGR codes of the style names a theme accepts.
+const STYLES: [(&str, &str); 20] = [
+    ("bold", "1"),
+    ("dim", "2"),
+    ("italic", "3"),
+    ("underline", "4"),
+    ("black", "30"),
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+    ("white", "37"),
+    ("bright_black", "90"),
+    ("bright_red", "91"),
+    ("bright_green", "92"),
+    ("bright_yellow", "93"),
+    ("bright_blue", "94"),
+    ("bright_magenta", "95"),
+    ("bright_cyan", "96"),
+    ("bright_white", "97"),
+];
+
+/// Colors of the selector's elements, as SGR parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    styles: HashMap<Element, String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let styles = [
+            (Element::Original, "2"),
+            (Element::Number, "1"),
+            (Element::Changed, "1;32"),
+            (Element::Rule, "90"),
+            (Element::Warning, "33"),
+            (Element::Reason, "2"),
+            (Element::Removed, "31"),
+            (Element::Group, "1"),
+        ];
+        Self {
+            styles: styles.into_iter().map(|(element, sgr)| (element, sgr.to_string())).collect(),
+        }
+    }
+}
+
+impl Theme {
+    /// Builds a theme from the config's `[theme]` section, mapping element
+    /// names to space-separated style names such as "bold green". Elements
+    /// left out keep their default; "none" leaves one uncolored.
+    pub fn from_config(theme: &HashMap<String, String>) -> Result<Self> {
+        let mut parsed = Self::default();
+        for (name, value) in theme {
+            let element = Element::ALL
+                .into_iter()
+                .find(|element| element.name() == name)
+                .ok_or_else(|| {
+                    let names: Vec<_> = Element::ALL.iter().map(|element| element.name()).collect();
+                    Error::config(format!("unknown theme element '{}' (expected one of {})", name, names.join(", ")))
+                })?;
+            parsed.styles.insert(element, parse_style(value)?);
+        }
+        Ok(parsed)
+    }
+
+    /// Wraps `text` in the escape codes of `element`'s style.
+    fn paint(&self, element: Element, text: &str) -> String {
+        match self.styles.get(&element).filter(|sgr| !sgr.is_empty()) {
+            Some(sgr) => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Parses space-separated style names into SGR parameters.
+fn parse_style(value: &str) -> Result<String> {
+    if value.trim() == "none" {
+        return Ok(String::new());
+    }
+    let codes = value
+        .split_whitespace()
+        .map(|name| {
+            STYLES
+                .iter()
+                .find(|(style, _)| *style == name)
+                .map(|(_, sgr)| *sgr)
+                .ok_or_else(|| Error::config(format!("unknown theme color '{}'", name)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if codes.is_empty() {
+        return Err(Error::config("empty theme color (use \"none\" to leave an element uncolored)"));
+    }
+    Ok(codes.join(";"))
+}
+
+/// A correction as the selector shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate<'a> {
+    /// The corrected command
+    pub script: &'a str,
+    /// Text shown after the command, such as the side effect it runs first
+    pub note: String,
+    /// The rule that suggested it
+    pub rule: Option<&'a str>,
+    /// Whether it always needs confirmation
+    pub dangerous: bool,
+    /// Why it was offered, shown on the line below
+    pub reason: Option<String>,
+    /// Whether to show only the tokens it changed, as a diff
+    pub diff: bool,
+}
+
+/// A group of candidates under a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// The header, such as "git (3)"
+    pub header: String,
+    /// Whether the group is expanded
+    pub expanded: bool,
+    /// The candidates shown under the header, with their numbers
+    pub candidates: Vec<(usize, Candidate<'a>)>,
+}
+
+/// Renders the selector, in color or not.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    theme: Theme,
+    color: bool,
+    width: usize,
+    diff_context: usize,
+}
+
+impl Renderer {
+    /// Creates a renderer using `theme` if `color` is set, for a terminal
+    /// [`DEFAULT_WIDTH`] columns wide.
+    pub fn new(theme: Theme, color: bool) -> Self {
+        Self {
+            theme,
+            color,
+            width: DEFAULT_WIDTH,
+            diff_context: 1,
+        }
+    }
+
+    /// Sets the terminal's width, which reasons are cut to.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets how many unchanged tokens a diff shows around the changes.
+    pub fn with_diff_context(mut self, context: usize) -> Self {
+        self.diff_context = context;
+        self
+    }
+
+    /// Renders `original` and the numbered `candidates`, one per line, with
+    /// the rule names right-aligned in a column after the longest candidate.
+    /// A candidate's reason goes on the next line, under its command.
+    pub fn candidates(&self, original: &str, candidates: &[Candidate]) -> String {
+        let numbered: Vec<_> = candidates.iter().enumerate().map(|(i, candidate)| (i + 1, candidate)).collect();
+        let mut out = self.original(original);
+        out.extend(self.rows(original, &numbered, candidates.len()));
+        out
+    }
+
+    /// Renders `original` and the `sections`, each a header followed by the
+    /// candidates shown under it. Candidates are numbered as in a list of
+    /// `total`, and their columns line up across sections.
+    pub fn sections(&self, original: &str, sections: &[Section], total: usize) -> String {
+        let numbered: Vec<_> = sections
+            .iter()
+            .flat_map(|section| section.candidates.iter().map(|(number, candidate)| (*number, candidate)))
+            .collect();
+        let mut rows = self.rows(original, &numbered, total).into_iter();
+        let mut out = self.original(original);
+        for section in sections {
+            let glyph = if section.expanded { EXPANDED_GLYPH } else { COLLAPSED_GLYPH };
+            out.push_str(&format!("  {} {}\n", glyph, self.style(Element::Group, &section.header)));
+            out.extend(rows.by_ref().take(section.candidates.len()));
+        }
+        out
+    }
+
+    fn original(&self, original: &str) -> String {
+        format!("  {}\n", self.style(Element::Original, &format!("$ {}", original)))
+    }
+
+    /// Renders each of the `numbered` candidates, with its reason if it has
+    /// one, the numbers padded for a list of `total`.
+    fn rows(&self, original: &str, numbered: &[(usize, &Candidate)], total: usize) -> Vec<String> {
+        let number_width = total.to_string().len();
+        let rows: Vec<_> = numbered
+            .iter()
+            .map(|(number, candidate)| {
+                let glyph = if candidate.dangerous { WARNING_GLYPH } else { " " };
+                let region = candidate
+                    .diff
+                    .then(|| Region::new(&diff::diff(original, candidate.script), self.diff_context))
+                    .flatten();
+                let shown = region.as_ref().map_or_else(|| candidate.script.to_string(), Region::to_string);
+                let plain = format!("{} {}{}", glyph, shown, candidate.note);
+                (plain.chars().count(), region, *number, *candidate)
+            })
+            .collect();
+        let text_width = rows.iter().map(|(width, _, _, _)| *width).max().unwrap_or(0);
+        let rule_width = numbered
+            .iter()
+            .map(|(_, candidate)| candidate.rule.map_or(0, |rule| rule.chars().count()))
+            .max()
+            .unwrap_or(0);
+
+        rows.into_iter()
+            .map(|(width, region, number, candidate)| {
+                let number = format!("{:>width$}.", number, width = number_width);
+                let glyph = if candidate.dangerous {
+                    self.style(Element::Warning, WARNING_GLYPH)
+                } else {
+                    " ".to_string()
+                };
+                let script = match &region {
+                    Some(region) => self.region(region),
+                    None => self.highlight(original, candidate.script),
+                };
+                let mut row = format!(
+                    "  {} {} {}{}",
+                    self.style(Element::Number, &number),
+                    glyph,
+                    script,
+                    candidate.note
+                );
+                if let Some(rule) = candidate.rule {
+                    let padding = text_width - width + 2 + rule_width - rule.chars().count();
+                    row.push_str(&" ".repeat(padding));
+                    row.push_str(&self.style(Element::Rule, rule));
+                }
+                row.push('\n');
+                if let Some(reason) = &candidate.reason {
+                    // Lined up with the command, after the number and glyph
+                    let indent = 2 + number.chars().count() + 3;
+                    let reason = truncate(reason, self.width.saturating_sub(indent));
+                    row.push_str(&format!("{:indent$}{}\n", "", self.style(Element::Reason, &reason), indent = indent));
+                }
+                row
+            })
+            .collect()
+    }
+
+    /// Highlights the tokens of `candidate` that aren't in `original`.
+    fn highlight(&self, original: &str, candidate: &str) -> String {
+        let tokens: Vec<_> = candidate.split(' ').collect();
+        let changed = changed_tokens(&original.split(' ').collect::<Vec<_>>(), &tokens);
+        tokens
+            .iter()
+            .zip(changed)
+            .map(|(token, changed)| {
+                if changed && !token.is_empty() {
+                    self.style(Element::Changed, token)
+                } else {
+                    token.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Renders a diff region, with the tokens it removes and adds
+    /// highlighted.
+    fn region(&self, region: &Region) -> String {
+        region.show(|token| self.style(Element::Removed, token), |token| self.style(Element::Changed, token))
+    }
+
+    fn style(&self, element: Element, text: &str) -> String {
+        if self.color {
+            self.theme.paint(element, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Cuts `text` to `width` characters, ending it with "…" if it was longer.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    match width {
+        0 => String::new(),
+        width => text.chars().take(width - 1).chain(['…']).collect(),
+    }
+}
+
+/// Gets the width of the terminal stderr is on, or [`DEFAULT_WIDTH`] if it
+/// isn't on one.
+pub fn terminal_width() -> usize {
+    size_or_default(terminal_size::terminal_size_of(std::io::stderr()).map(|(width, _)| width.0), DEFAULT_WIDTH)
+}
+
+/// Gets the height of the terminal stderr is on, or [`DEFAULT_HEIGHT`] if it
+/// isn't on one.
+pub fn terminal_height() -> usize {
+    size_or_default(terminal_size::terminal_size_of(std::io::stderr()).map(|(_, height)| height.0), DEFAULT_HEIGHT)
+}
+
+/// Gets a detected size, unless none was found or it's unusably small.
+fn size_or_default(detected: Option<u16>, default: usize) -> usize {
+    detected.filter(|size| *size > 0).map_or(default, usize::from)
+}
+
+/// Marks each token of `new` that isn't part of the longest common
+/// subsequence of `old` and `new`.
+pub fn changed_tokens(old: &[&str], new: &[&str]) -> Vec<bool> {
+    diff::diff_tokens(old, new)
+        .into_iter()
+        .filter(|edit| edit.op != Op::Delete)
+        .map(|edit| edit.op == Op::Insert)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<Candidate<'static>> {
+        vec![
+            Candidate {
+                script: "git push",
+                note: String::new(),
+                rule: Some("git_not_command"),
+                dangerous: false,
+                reason: None,
+                diff: false,
+            },
+            Candidate {
+                script: "git push --force",
+                note: " (first remove the lock file)".to_string(),
+                rule: Some("git_push_force"),
+                dangerous: true,
+                reason: None,
+                diff: false,
+            },
+            Candidate {
+                script: "git pull",
+                note: String::new(),
+                rule: None,
+                dangerous: false,
+                reason: None,
+                diff: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_plain_layout_matches_snapshot() {
+        let rendered = Renderer::new(Theme::default(), false).candidates("git psuh", &candidates());
+        assert_eq!(rendered, include_str!("../tests/fixtures/render/candidates.txt"));
+    }
+
+    #[test]
+    fn test_plain_layout_pads_numbers() {
+        let many: Vec<_> = (0..10)
+            .map(|_| Candidate {
+                script: "ls",
+                note: String::new(),
+                rule: Some("ls"),
+                dangerous: false,
+                reason: None,
+                diff: false,
+            })
+            .collect();
+        let rendered = Renderer::new(Theme::default(), false).candidates("sl", &many);
+        assert_eq!(rendered, include_str!("../tests/fixtures/render/ten_candidates.txt"));
+    }
+
+    #[test]
+    fn test_color_highlights_changed_tokens() {
+        let rendered = Renderer::new(Theme::default(), true).candidates("git psuh", &candidates()[..1]);
+        assert!(rendered.contains("\x1b[2m$ git psuh\x1b[0m"));
+        assert!(rendered.contains("git \x1b[1;32mpush\x1b[0m"));
+        assert!(rendered.contains("\x1b[90mgit_not_command\x1b[0m"));
+    }
+
+    #[test]
+    fn test_reasons_go_under_the_commands() {
+        let mut candidates = candidates();
+        candidates[0].reason = Some("rule git_not_command: matched 'is not a git command' in output".to_string());
+        candidates[1].reason = Some("rule git_push_force: matched 'rejected' in output".to_string());
+        let rendered = Renderer::new(Theme::default(), false).with_width(60).candidates("git psuh", &candidates);
+        assert_eq!(rendered, include_str!("../tests/fixtures/render/reasons.txt"));
+
+        let rendered = Renderer::new(Theme::default(), true).candidates("git psuh", &candidates[1..2]);
+        assert!(rendered.contains("\n       \x1b[2mrule git_push_force: matched 'rejected' in output\x1b[0m\n"));
+    }
+
+    #[test]
+    fn test_diff_view() {
+        let mut candidates = candidates();
+        candidates[1].diff = true;
+        let rendered = Renderer::new(Theme::default(), false).candidates("git psuh", &candidates);
+        assert!(rendered.contains("\n  2. ⚠ git psuh → git push --force (first remove"), "{}", rendered);
+
+        let rendered = Renderer::new(Theme::default(), true).with_diff_context(0).candidates("git psuh", &candidates);
+        assert!(rendered.contains("… \x1b[31mpsuh\x1b[0m → \x1b[1;32mpush\x1b[0m \x1b[1;32m--force\x1b[0m"));
+    }
+
+    #[test]
+    fn test_sections() {
+        let candidates = candidates();
+        let sections = [
+            Section {
+                header: "git (2)".to_string(),
+                expanded: true,
+                candidates: vec![(1, candidates[0].clone()), (3, candidates[1].clone())],
+            },
+            Section {
+                header: "other (1)".to_string(),
+                expanded: false,
+                candidates: Vec::new(),
+            },
+        ];
+        let rendered = Renderer::new(Theme::default(), false).sections("git psuh", &sections, 12);
+        assert_eq!(
+            rendered,
+            "  $ git psuh
+  ▾ git (2)
+   1.   git push                                       git_not_command
+   3. ⚠ git push --force (first remove the lock file)   git_push_force
+  ▸ other (1)
+"
+        );
+        let rendered = Renderer::new(Theme::default(), true).sections("git psuh", &sections[1..], 3);
+        assert!(rendered.ends_with("  ▸ \x1b[1mother (1)\x1b[0m\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("git push", 8), "git push");
+        assert_eq!(truncate("git push", 7), "git pu…");
+        assert_eq!(truncate("ñandú", 4), "ñan…");
+        assert_eq!(truncate("git push", 1), "…");
+        assert_eq!(truncate("git push", 0), "");
+    }
+
+    #[test]
+    fn test_width_falls_back_to_default() {
+        assert_eq!(size_or_default(Some(120), DEFAULT_WIDTH), 120);
+        assert_eq!(size_or_default(Some(0), DEFAULT_WIDTH), DEFAULT_WIDTH);
+        assert_eq!(size_or_default(None, DEFAULT_HEIGHT), DEFAULT_HEIGHT);
+    }
+
+    #[test]
+    fn test_changed_tokens() {
+        assert_eq!(changed_tokens(&["git", "psuh"], &["git", "push"]), [false, true]);
+        assert_eq!(changed_tokens(&["ls"], &["sudo", "ls"]), [true, false]);
+        assert_eq!(changed_tokens(&["a", "b", "c"], &["a", "c", "b"]), [false, false, true]);
+        assert_eq!(changed_tokens(&[], &["ls"]), [true]);
+    }
+
+    #[test]
+    fn test_theme_parsing() {
+        let config = HashMap::from([
+            ("changed".to_string(), "bold bright_cyan".to_string()),
+            ("rule".to_string(), "none".to_string()),
+        ]);
+        let theme = Theme::from_config(&config).unwrap();
+        assert_eq!(theme.paint(Element::Changed, "x"), "\x1b[1;96mx\x1b[0m");
+        assert_eq!(theme.paint(Element::Rule, "x"), "x");
+        // Elements left out keep their default
+        assert_eq!(theme.paint(Element::Warning, "x"), "\x1b[33mx\x1b[0m");
+    }
+
+    #[test]
+    fn test_theme_rejects_unknown_names() {
+        let element = HashMap::from([("prompt".to_string(), "red".to_string())]);
+        assert!(Theme::from_config(&element).unwrap_err().to_string().contains("unknown theme element 'prompt'"));
+        let color = HashMap::from([("rule".to_string(), "bold purple".to_string())]);
+        assert!(Theme::from_config(&color).unwrap_err().to_string().contains("unknown theme color 'purple'"));
+        let empty = HashMap::from([("rule".to_string(), " ".to_string())]);
+        assert!(Theme::from_config(&empty).is_err());
+    }
+
+    #[test]
+    fn test_color_mode() {
+        assert!(ColorMode::Auto.enabled(true, true, None));
+        assert!(ColorMode::Auto.enabled(true, true, Some("")));
+        assert!(!ColorMode::Auto.enabled(true, true, Some("1")));
+        assert!(!ColorMode::Auto.enabled(false, true, None));
+        assert!(!ColorMode::Auto.enabled(true, false, None));
+        assert!(ColorMode::Always.enabled(false, false, Some("1")));
+        assert!(!ColorMode::Never.enabled(true, true, None));
+    }
+}
@@ -0,0 +1,378 @@
+//! Recorded invocations, replayed to debug a correction on another machine.
+//!
+//! `ftf record --out failure.ftf` corrects a failure as usual and writes a
+//! [`Recording`]: the failure, the working directory, the shell, a hash of
+//! the config, and every probe rules ran through the shell on the way, such
+//! as `git branch` or a `PATH` lookup. `ftf replay failure.ftf` rebuilds the
+//! context around a [`ReplayShell`] that answers those probes from the file,
+//! so the same rules see the same machine.
+//!
+//! Only what rules asked the shell for is kept: the environment variables
+//! they read, not the whole environment. History isn't recorded, as it can
+//! hold secrets, so rules correcting from it suggest nothing on replay.
+
+use crate::context::ProjectType;
+use crate::plugins::packs::sha256_hex;
+use crate::shell::ShellOutput;
+use crate::{Command, Config, Error, Result, Shell};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Version of the recording format, bumped when a field changes meaning.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A command a rule ran through the shell, and what it printed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeRun {
+    /// The command line
+    pub command: String,
+    /// Its standard output
+    pub stdout: String,
+    /// Its standard error
+    pub stderr: String,
+    /// Its exit code
+    pub exit_code: i32,
+    /// Whether it was killed for running past its timeout
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+/// What rules asked the shell for during one invocation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Probes {
+    /// Environment variables read, with their values; unset ones are left out
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Executables looked up on `PATH`, and whether they were found
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub command_exists: BTreeMap<String, bool>,
+    /// Commands run, in the order they first ran
+    #[serde(default, rename = "run", skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<ProbeRun>,
+}
+
+/// The probes recorded so far, shared between a [`RecordingShell`] and the
+/// context it's in.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeLog(Arc<Mutex<Probes>>);
+
+impl ProbeLog {
+    /// Gets a copy of the probes recorded so far.
+    pub fn probes(&self) -> Probes {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Probes> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record_run(&self, command: &str, output: &ShellOutput) {
+        let mut probes = self.lock();
+        if probes.runs.iter().all(|run| run.command != command) {
+            probes.runs.push(ProbeRun {
+                command: command.to_string(),
+                stdout: output.stdout.clone(),
+                stderr: output.stderr.clone(),
+                exit_code: output.exit_code,
+                timed_out: output.timed_out,
+            });
+        }
+    }
+}
+
+/// A shell that passes everything to another one and records the probes
+/// rules run, for [`RuleContext::recording_probes`](crate::RuleContext::recording_probes).
+pub struct RecordingShell {
+    inner: Box<dyn Shell>,
+    log: ProbeLog,
+}
+
+impl RecordingShell {
+    /// Wraps `inner`, recording into `log`.
+    pub fn new(inner: Box<dyn Shell>, log: ProbeLog) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl Shell for RecordingShell {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn execute(&self, command: &str) -> Result<ShellOutput> {
+        let output = self.inner.execute(command)?;
+        self.log.record_run(command, &output);
+        Ok(output)
+    }
+
+    fn execute_with_timeout(&self, command: &str, timeout: Duration) -> Result<ShellOutput> {
+        let output = self.inner.execute_with_timeout(command, timeout)?;
+        self.log.record_run(command, &output);
+        Ok(output)
+    }
+
+    fn cwd(&self) -> Result<PathBuf> {
+        self.inner.cwd()
+    }
+
+    fn set_cwd(&mut self, path: PathBuf) -> Result<()> {
+        self.inner.set_cwd(path)
+    }
+
+    fn env(&self, key: &str) -> Option<String> {
+        let value = self.inner.env(key)?;
+        self.log.lock().env.insert(key.to_string(), value.clone());
+        Some(value)
+    }
+
+    fn set_env(&mut self, key: String, value: String) -> Result<()> {
+        self.inner.set_env(key, value)
+    }
+
+    fn history(&self) -> Result<Vec<String>> {
+        self.inner.history()
+    }
+
+    fn history_entries(&self) -> Result<Vec<crate::HistoryEntry>> {
+        self.inner.history_entries()
+    }
+
+    fn command_exists(&self, command: &str) -> Result<bool> {
+        let exists = self.inner.command_exists(command)?;
+        self.log.lock().command_exists.insert(command.to_string(), exists);
+        Ok(exists)
+    }
+}
+
+/// A shell that answers from recorded probes alone. Commands that weren't
+/// recorded fail, executables that weren't looked up are missing and
+/// variables that weren't read are unset, so nothing on the replaying
+/// machine leaks into the result.
+pub struct ReplayShell {
+    name: String,
+    cwd: PathBuf,
+    probes: Probes,
+}
+
+impl ReplayShell {
+    /// Creates a shell named `name` in `cwd`, answering from `probes`.
+    pub fn new(name: impl Into<String>, cwd: impl Into<PathBuf>, probes: Probes) -> Self {
+        Self {
+            name: name.into(),
+            cwd: cwd.into(),
+            probes,
+        }
+    }
+}
+
+impl Shell for ReplayShell {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&self, command: &str) -> Result<ShellOutput> {
+        let run = self.probes.runs.iter().find(|run| run.command == command);
+        let run = run.ok_or_else(|| Error::Shell(format!("`{}` wasn't run when the failure was recorded", command)))?;
+        Ok(ShellOutput {
+            command: run.command.clone(),
+            stdout: run.stdout.clone(),
+            stderr: run.stderr.clone(),
+            exit_code: run.exit_code,
+            success: run.exit_code == 0 && !run.timed_out,
+            timed_out: run.timed_out,
+        })
+    }
+
+    fn execute_with_timeout(&self, command: &str, _timeout: Duration) -> Result<ShellOutput> {
+        self.execute(command)
+    }
+
+    fn cwd(&self) -> Result<PathBuf> {
+        Ok(self.cwd.clone())
+    }
+
+    fn set_cwd(&mut self, path: PathBuf) -> Result<()> {
+        self.cwd = path;
+        Ok(())
+    }
+
+    fn env(&self, key: &str) -> Option<String> {
+        self.probes.env.get(key).cloned()
+    }
+
+    fn set_env(&mut self, key: String, value: String) -> Result<()> {
+        self.probes.env.insert(key, value);
+        Ok(())
+    }
+
+    fn history(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn command_exists(&self, command: &str) -> Result<bool> {
+        Ok(self.probes.command_exists.get(command).copied().unwrap_or(false))
+    }
+}
+
+/// A failure with everything rules learned about the machine while
+/// correcting it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    /// [`FORMAT_VERSION`] when it was written
+    pub version: u32,
+    /// The command that failed
+    pub script: String,
+    /// What it printed
+    pub output: String,
+    /// Its exit code
+    pub exit_code: i32,
+    /// The directory it ran in
+    pub cwd: PathBuf,
+    /// The shell it ran in, such as `bash`
+    pub shell: String,
+    /// [`config_hash`] of the config it was corrected with
+    pub config_hash: String,
+    /// The project types of `cwd`
+    #[serde(default)]
+    pub project_types: Vec<ProjectType>,
+    /// What rules asked the shell for
+    #[serde(flatten)]
+    pub probes: Probes,
+}
+
+impl Recording {
+    /// Gets the failed command.
+    pub fn command(&self) -> Command {
+        Command::new(&self.script, &self.output, self.exit_code)
+    }
+
+    /// Gets a shell answering from the recorded probes.
+    pub fn shell(&self) -> ReplayShell {
+        ReplayShell::new(&self.shell, &self.cwd, self.probes.clone())
+    }
+
+    /// Parses a recording, refusing ones written by a newer format.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let recording: Self = toml::from_str(contents).map_err(|e| Error::Other(e.to_string()))?;
+        if recording.version > FORMAT_VERSION {
+            return Err(Error::Other(format!(
+                "the recording has format version {}, newer than this ftf's {}",
+                recording.version, FORMAT_VERSION
+            )));
+        }
+        Ok(recording)
+    }
+
+    /// Reads the recording at `path`.
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+            .map_err(|e| Error::Other(format!("{}: {}", path.display(), e.user_message())))
+    }
+
+    /// Writes the recording to `path` as TOML.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string(self).map_err(|e| Error::Other(e.to_string()))?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Hashes `config`, so a replay can tell whether it runs with the config
+/// the failure was recorded with.
+pub fn config_hash(config: &Config) -> String {
+    let encoded = toml::to_string(config).unwrap_or_default();
+    sha256_hex(encoded.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    fn recording() -> Recording {
+        let mut probes = Probes::default();
+        probes.env.insert("KUBECONFIG".to_string(), "/home/me/.kube/config".to_string());
+        probes.command_exists.insert("sudo".to_string(), true);
+        probes.runs.push(ProbeRun {
+            command: "git branch --format='%(refname:short)'".to_string(),
+            stdout: "main\nfeature\n".to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+            timed_out: false,
+        });
+        Recording {
+            version: FORMAT_VERSION,
+            script: "git checkout featur".to_string(),
+            output: "error: pathspec 'featur' did not match any file(s) known to git\n".to_string(),
+            exit_code: 1,
+            cwd: PathBuf::from("/home/me/project"),
+            shell: "zsh".to_string(),
+            config_hash: config_hash(&Config::default()),
+            project_types: vec![ProjectType::Rust, ProjectType::Git],
+            probes,
+        }
+    }
+
+    #[test]
+    fn test_recording_round_trips_through_toml() {
+        let recording = recording();
+        let encoded = toml::to_string(&recording).unwrap();
+        assert!(encoded.contains("[[run]]"), "{}", encoded);
+        assert!(encoded.contains("project_types = [\"rust\", \"git\"]"), "{}", encoded);
+        assert_eq!(Recording::parse(&encoded).unwrap(), recording);
+    }
+
+    #[test]
+    fn test_newer_recordings_are_refused() {
+        let recording = Recording {
+            version: FORMAT_VERSION + 1,
+            ..recording()
+        };
+        let error = Recording::parse(&toml::to_string(&recording).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("newer than this ftf's"));
+    }
+
+    #[test]
+    fn test_recording_shell_records_probes() {
+        let log = ProbeLog::default();
+        let inner = MockShell::new()
+            .with_output("git branch", "main\n", 0)
+            .with_env("HOME", "/home/me")
+            .with_command("sudo", true)
+            .with_command("doas", false);
+        let shell = RecordingShell::new(Box::new(inner), log.clone());
+
+        shell.execute("git branch").unwrap();
+        shell.execute_with_timeout("git branch", Duration::from_secs(1)).unwrap();
+        assert_eq!(shell.env("HOME").as_deref(), Some("/home/me"));
+        assert_eq!(shell.env("UNSET"), None);
+        assert!(shell.command_exists("sudo").unwrap());
+        assert!(!shell.command_exists("doas").unwrap());
+
+        let probes = log.probes();
+        assert_eq!(probes.runs.len(), 1);
+        assert_eq!(probes.runs[0].stdout, "main\n");
+        assert_eq!(probes.env.keys().collect::<Vec<_>>(), vec!["HOME"]);
+        assert_eq!(probes.command_exists.get("sudo"), Some(&true));
+        assert_eq!(probes.command_exists.get("doas"), Some(&false));
+    }
+
+    #[test]
+    fn test_replay_shell_answers_from_the_recording_only() {
+        let shell = recording().shell();
+        assert_eq!(shell.name(), "zsh");
+        assert_eq!(shell.cwd().unwrap(), PathBuf::from("/home/me/project"));
+        let output = shell.execute("git branch --format='%(refname:short)'").unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout, "main\nfeature\n");
+        assert!(shell.execute("git status").is_err());
+        assert_eq!(shell.env("KUBECONFIG").as_deref(), Some("/home/me/.kube/config"));
+        assert_eq!(shell.env("HOME"), None);
+        assert!(shell.command_exists("sudo").unwrap());
+        assert!(!shell.command_exists("git").unwrap());
+        assert!(shell.history().unwrap().is_empty());
+    }
+}
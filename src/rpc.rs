@@ -0,0 +1,715 @@
+//! JSON-RPC 2.0 over stdio, for editor integrations.
+//!
+//! `ftf serve --stdio` reads one request per line on stdin and writes one
+//! response per line on stdout, keeping its rules between requests:
+//!
+//! ```text
+//! --> {"jsonrpc":"2.0","id":1,"method":"correct","params":{"script":"git pus","output":"...","exit_code":1,"cwd":"/src/app"}}
+//! <-- {"jsonrpc":"2.0","id":1,"result":[{"script":"git push","priority":300,...,"rule":"git_not_command"}]}
+//! ```
+//!
+//! Methods:
+//!
+//! - `correct`: [`CorrectParams`] → the corrections, best first. With
+//!   `"diff": true` each has a `diff` array of `{"op", "token"}` entries,
+//!   the word diff from the failed command. With `"verbose": true` they're
+//!   wrapped in a [`VerboseCorrections`], with how many rules were tried.
+//! - `explain`: [`CorrectParams`] → an [`Explanation`] for each rule that
+//!   matched, in the order of their best correction.
+//! - `listRules`: no params → a [`RuleSummary`] for each built-in rule.
+//!
+//! Batches and notifications work as the JSON-RPC spec describes. A line
+//! that isn't valid JSON-RPC gets an error response with the spec's codes,
+//! and the server keeps going.
+
+use crate::diff::{self, Edit};
+use crate::learning::AdaptivePriorities;
+use crate::{output, BashShell, Command, Config, CorrectedCommand, CorrectionSet, Corrector, RuleContext};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The protocol version every message carries.
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// The line isn't JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON isn't a request.
+pub const INVALID_REQUEST: i64 = -32600;
+/// There's no such method.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The method's params are missing or malformed.
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request broke the server.
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// How long rules built for a directory are reused. Rules cache what they
+/// find out about the system, such as which programs are installed, so
+/// they're rebuilt now and then to notice changes.
+pub const REGISTRY_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// A request, or a notification if it has no id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// A string, number or null; absent for notifications
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+/// Keeps an explicit `null` apart from a missing field.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+impl Request {
+    /// Creates a request with `id`.
+    pub fn new(id: impl Into<Value>, method: impl Into<String>, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(id.into()),
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// The answer to a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    /// The request's id, or null if it couldn't be read
+    pub id: Value,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+/// What a request produced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Result(Value),
+    Error(RpcError),
+}
+
+impl Response {
+    /// Creates a successful response.
+    pub fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            outcome: Outcome::Result(result),
+        }
+    }
+
+    /// Creates an error response.
+    pub fn error(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            outcome: Outcome::Error(error),
+        }
+    }
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// Creates an error with `code`, explained by `message`.
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Creates an invalid params error.
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+/// Params of `correct` and `explain`: a failed command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorrectParams {
+    pub script: String,
+    #[serde(default)]
+    pub output: String,
+    #[serde(default = "default_exit_code")]
+    pub exit_code: i32,
+    /// Directory the command ran in, the server's own if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Whether `correct` gives each correction's word diff
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub diff: bool,
+    /// Whether `correct` says how many rules it tried and how long they took
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub verbose: bool,
+}
+
+fn default_exit_code() -> i32 {
+    1
+}
+
+/// A correction with its word diff, as `correct` gives it when asked to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffedCorrection {
+    #[serde(flatten)]
+    pub correction: CorrectedCommand,
+    pub diff: Vec<Edit>,
+}
+
+/// The corrections with the numbers `--verbose` shows, as `correct` gives
+/// them when asked to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerboseCorrections {
+    /// The corrections, as `correct` gives them otherwise
+    pub corrections: Value,
+    pub evaluated: usize,
+    pub matched: usize,
+    pub suppressed: usize,
+    pub elapsed_ms: f64,
+}
+
+/// A rule, as `listRules` describes it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSummary {
+    pub name: String,
+    /// The rule group it belongs to, e.g. "git"
+    pub category: String,
+    /// Base priority, before config overrides and learned adjustments
+    pub priority: i32,
+    pub enabled: bool,
+}
+
+/// Why a rule's corrections were offered, as `explain` describes it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    pub rule: String,
+    /// Absent for rules without a listing, such as plugins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_priority: Option<i32>,
+    /// Priority the config sets, which wins over learning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub configured_priority: Option<i32>,
+    /// Change learned from accepted and rejected corrections
+    pub learned_adjustment: i32,
+    pub corrections: Vec<CorrectedCommand>,
+}
+
+/// Answers every line of `input` on `output` until `input` ends.
+/// `handler` runs methods; a panic in it is answered as an internal error.
+pub fn serve(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    mut handler: impl FnMut(&str, Option<Value>) -> Result<Value, RpcError>,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let response = match std::str::from_utf8(&line) {
+            Ok(text) if text.trim().is_empty() => continue,
+            Ok(text) => handle_line(text, &mut handler),
+            Err(_) => Some(encode(&Response::error(Value::Null, RpcError::new(PARSE_ERROR, "invalid UTF-8")))),
+        };
+        if let Some(response) = response {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+}
+
+/// Answers one line holding a request, a notification or a batch. Gets the
+/// response line, or `None` if nothing needs answering.
+pub fn handle_line(
+    line: &str,
+    handler: &mut impl FnMut(&str, Option<Value>) -> Result<Value, RpcError>,
+) -> Option<String> {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => return Some(encode(&Response::error(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())))),
+    };
+    match value {
+        Value::Array(batch) if batch.is_empty() => Some(encode(&Response::error(
+            Value::Null,
+            RpcError::new(INVALID_REQUEST, "empty batch"),
+        ))),
+        Value::Array(batch) => {
+            let responses: Vec<Response> = batch.into_iter().filter_map(|request| handle(request, handler)).collect();
+            (!responses.is_empty()).then(|| serde_json::to_string(&responses).unwrap_or_default())
+        }
+        request => handle(request, handler).map(|response| encode(&response)),
+    }
+}
+
+fn encode(response: &Response) -> String {
+    serde_json::to_string(response).unwrap_or_default()
+}
+
+/// Answers one request, or nothing for a notification.
+fn handle(
+    value: Value,
+    handler: &mut impl FnMut(&str, Option<Value>) -> Result<Value, RpcError>,
+) -> Option<Response> {
+    let id = value.get("id").cloned().filter(valid_id);
+    let request = match serde_json::from_value::<Request>(value) {
+        Ok(request) if request.jsonrpc == JSONRPC_VERSION && request.id.as_ref().is_none_or(valid_id) => request,
+        Ok(_) => return Some(Response::error(id.unwrap_or_default(), RpcError::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request"))),
+        Err(e) => return Some(Response::error(id.unwrap_or_default(), RpcError::new(INVALID_REQUEST, e.to_string()))),
+    };
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| handler(&request.method, request.params)))
+        .unwrap_or_else(|_| Err(RpcError::new(INTERNAL_ERROR, "the server failed to answer")));
+    let id = request.id?;
+    Some(match outcome {
+        Ok(result) => Response::result(id, result),
+        Err(error) => Response::error(id, error),
+    })
+}
+
+/// Returns true if `id` is a string, number or null, as the spec requires.
+fn valid_id(id: &Value) -> bool {
+    matches!(id, Value::String(_) | Value::Number(_) | Value::Null)
+}
+
+/// Reads a method's params.
+fn params<T: DeserializeOwned>(params: Option<Value>) -> Result<T, RpcError> {
+    serde_json::from_value(params.unwrap_or_default()).map_err(|e| RpcError::invalid_params(e.to_string()))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+/// Rules built for one directory.
+struct Registry {
+    cwd: PathBuf,
+    built: Instant,
+    corrector: Corrector,
+}
+
+/// Answers the methods with one set of rules, rebuilt when the directory
+/// changes or they're older than [`REGISTRY_MAX_AGE`].
+pub struct Server<'a> {
+    config: &'a Config,
+    learned: AdaptivePriorities,
+    registry: Option<Registry>,
+}
+
+impl<'a> Server<'a> {
+    /// Creates a server with the loaded config and learned priorities.
+    pub fn new(config: &'a Config, learned: AdaptivePriorities) -> Self {
+        Self {
+            config,
+            learned,
+            registry: None,
+        }
+    }
+
+    /// Runs `method` with `params`.
+    pub fn handle(&mut self, method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        match method {
+            "correct" => {
+                let params: CorrectParams = self::params(params)?;
+                let set = self.correction_set(&params)?;
+                let corrections = if params.diff {
+                    to_value(
+                        set.corrections
+                            .iter()
+                            .map(|correction| DiffedCorrection {
+                                diff: diff::diff(&params.script, &correction.script),
+                                correction: correction.clone(),
+                            })
+                            .collect::<Vec<_>>(),
+                    )?
+                } else {
+                    to_value(&set.corrections)?
+                };
+                if params.verbose {
+                    to_value(VerboseCorrections {
+                        corrections,
+                        evaluated: set.evaluated,
+                        matched: set.matched,
+                        suppressed: set.suppressed,
+                        elapsed_ms: set.elapsed.as_secs_f64() * 1000.0,
+                    })
+                } else {
+                    Ok(corrections)
+                }
+            }
+            "explain" => to_value(self.explain(&self::params(params)?)?),
+            "listRules" => to_value(self.list_rules()),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("no method '{}'", method))),
+        }
+    }
+
+    /// Gets the corrections for a failed command, best first.
+    pub fn correct(&mut self, params: &CorrectParams) -> Result<Vec<CorrectedCommand>, RpcError> {
+        Ok(self.correction_set(params)?.corrections)
+    }
+
+    /// Gets the corrections for a failed command with how many rules were
+    /// tried and how long they took.
+    pub fn correction_set(&mut self, params: &CorrectParams) -> Result<CorrectionSet, RpcError> {
+        let cwd = match &params.cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir().map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?,
+        };
+        if !cwd.is_dir() {
+            return Err(RpcError::invalid_params(format!("{} is not a directory", cwd.display())));
+        }
+
+        let config = self.config;
+        let corrector = self.corrector(cwd);
+        let output = output::cap(&params.output, corrector.max_output_bytes(config.global.max_output_bytes));
+        let command = Command {
+            script: params.script.clone(),
+            output: output.text,
+            exit_code: params.exit_code,
+            output_truncated: output.truncated,
+            output_had_invalid_utf8: output.invalid_utf8,
+        };
+        Ok(corrector.correct(&command))
+    }
+
+    /// Gets the corrections for a failed command grouped by the rule that
+    /// made them, with how each rule's priority came about.
+    pub fn explain(&mut self, params: &CorrectParams) -> Result<Vec<Explanation>, RpcError> {
+        let rules = self.list_rules();
+        let mut explanations: Vec<Explanation> = Vec::new();
+        for correction in self.correct(params)? {
+            let rule = correction.rule.clone().unwrap_or_default();
+            match explanations.iter_mut().find(|explanation| explanation.rule == rule) {
+                Some(explanation) => explanation.corrections.push(correction),
+                None => {
+                    let summary = rules.iter().find(|summary| summary.name == rule);
+                    explanations.push(Explanation {
+                        category: summary.map(|summary| summary.category.clone()),
+                        base_priority: summary.map(|summary| summary.priority),
+                        configured_priority: self.config.get_rule_priority(&rule),
+                        learned_adjustment: self.learned.adjustment_for(&rule),
+                        corrections: vec![correction],
+                        rule,
+                    });
+                }
+            }
+        }
+        Ok(explanations)
+    }
+
+    /// Lists the rules that can be listed without building them.
+    pub fn list_rules(&self) -> Vec<RuleSummary> {
+        let context = Arc::new(RuleContext::default());
+        let factories = self.config.factories(&context);
+        factories
+            .iter()
+            .flat_map(|factory| {
+                factory.rules().iter().map(|(name, priority)| RuleSummary {
+                    name: name.to_string(),
+                    category: factory.category().to_string(),
+                    priority: *priority,
+                    enabled: self.config.is_rule_enabled(name),
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the rules for `cwd`, building them if needed.
+    fn corrector(&mut self, cwd: PathBuf) -> &Corrector {
+        let stale = self
+            .registry
+            .as_ref()
+            .is_none_or(|registry| registry.cwd != cwd || registry.built.elapsed() > REGISTRY_MAX_AGE);
+        if stale {
+            let context = Arc::new(RuleContext::new(Box::new(BashShell::in_dir(cwd.clone()))));
+            let corrector =
+                Corrector::for_any_command(self.config.factories(&context), self.config, &self.learned, &context);
+            self.registry = Some(Registry {
+                cwd,
+                built: Instant::now(),
+                corrector,
+            });
+        }
+        &self.registry.as_ref().expect("registry was just built").corrector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const GIT_OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+    /// Answers `echo` with its params and panics on `crash`.
+    fn echo(method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        match method {
+            "echo" => Ok(params.unwrap_or_default()),
+            "crash" => panic!("handler bug"),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, "no such method")),
+        }
+    }
+
+    fn answer(line: &str) -> Option<Value> {
+        handle_line(line, &mut echo).map(|response| serde_json::from_str(&response).unwrap())
+    }
+
+    #[test]
+    fn test_request_serialization() {
+        let request = Request::new(7, "correct", Some(json!({"script": "git pus"})));
+        let encoded = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            encoded,
+            json!({"jsonrpc": "2.0", "id": 7, "method": "correct", "params": {"script": "git pus"}})
+        );
+        assert_eq!(serde_json::from_value::<Request>(encoded).unwrap(), request);
+
+        // A notification has no id, which isn't the same as a null one
+        let notification: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","method":"listRules"}"#).unwrap();
+        assert_eq!(notification.id, None);
+        assert_eq!(notification.params, None);
+        assert_eq!(serde_json::to_string(&notification).unwrap(), r#"{"jsonrpc":"2.0","method":"listRules"}"#);
+        let null_id: Request = serde_json::from_str(r#"{"jsonrpc":"2.0","id":null,"method":"listRules"}"#).unwrap();
+        assert_eq!(null_id.id, Some(Value::Null));
+    }
+
+    #[test]
+    fn test_response_serialization() {
+        let success = Response::result(json!("a"), json!([1]));
+        assert_eq!(serde_json::to_value(&success).unwrap(), json!({"jsonrpc": "2.0", "id": "a", "result": [1]}));
+
+        let mut error = RpcError::invalid_params("missing field `script`");
+        let failure = Response::error(json!(2), error.clone());
+        assert_eq!(
+            serde_json::to_value(&failure).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 2, "error": {"code": -32602, "message": "missing field `script`"}})
+        );
+
+        error.data = Some(json!({"field": "script"}));
+        let failure = Response::error(Value::Null, error);
+        let encoded = serde_json::to_string(&failure).unwrap();
+        assert_eq!(serde_json::from_str::<Response>(&encoded).unwrap(), failure);
+        assert_eq!(serde_json::from_str::<Response>(&serde_json::to_string(&success).unwrap()).unwrap(), success);
+    }
+
+    #[test]
+    fn test_params_serialization() {
+        let params: CorrectParams = serde_json::from_value(json!({"script": "git pus"})).unwrap();
+        assert_eq!(
+            params,
+            CorrectParams {
+                script: "git pus".to_string(),
+                output: String::new(),
+                exit_code: 1,
+                cwd: None,
+                diff: false,
+                verbose: false,
+            }
+        );
+        let full = CorrectParams {
+            script: "ls".to_string(),
+            output: "ls: x: No such file".to_string(),
+            exit_code: 2,
+            cwd: Some(PathBuf::from("/tmp")),
+            diff: false,
+            verbose: false,
+        };
+        let encoded = serde_json::to_value(&full).unwrap();
+        assert_eq!(encoded, json!({"script": "ls", "output": "ls: x: No such file", "exit_code": 2, "cwd": "/tmp"}));
+        assert_eq!(serde_json::from_value::<CorrectParams>(encoded).unwrap(), full);
+        assert!(serde_json::from_value::<CorrectParams>(json!({"output": "x"})).is_err());
+
+        let summary = RuleSummary {
+            name: "git_push_force".to_string(),
+            category: "git".to_string(),
+            priority: 700,
+            enabled: true,
+        };
+        let encoded = serde_json::to_value(&summary).unwrap();
+        assert_eq!(encoded, json!({"name": "git_push_force", "category": "git", "priority": 700, "enabled": true}));
+        assert_eq!(serde_json::from_value::<RuleSummary>(encoded).unwrap(), summary);
+
+        let explanation = Explanation {
+            rule: "plugin".to_string(),
+            category: None,
+            base_priority: None,
+            configured_priority: None,
+            learned_adjustment: -20,
+            corrections: vec![CorrectedCommand::new("docker ps", 900).with_rule("plugin")],
+        };
+        let encoded = serde_json::to_value(&explanation).unwrap();
+        assert!(encoded.get("category").is_none());
+        assert_eq!(encoded["learned_adjustment"], -20);
+        assert_eq!(serde_json::from_value::<Explanation>(encoded).unwrap(), explanation);
+    }
+
+    #[test]
+    fn test_handle_requests_and_notifications() {
+        assert_eq!(
+            answer(r#"{"jsonrpc":"2.0","id":1,"method":"echo","params":[1,2]}"#),
+            Some(json!({"jsonrpc": "2.0", "id": 1, "result": [1, 2]}))
+        );
+        assert_eq!(answer(r#"{"jsonrpc":"2.0","method":"echo"}"#), None);
+        assert_eq!(
+            answer(r#"{"jsonrpc":"2.0","id":null,"method":"echo"}"#),
+            Some(json!({"jsonrpc": "2.0", "id": null, "result": null}))
+        );
+        assert_eq!(answer(r#"{"jsonrpc":"2.0","id":"x","method":"nope"}"#).unwrap()["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_malformed_requests() {
+        let parse = answer("{not json").unwrap();
+        assert_eq!(parse["error"]["code"], PARSE_ERROR);
+        assert_eq!(parse["id"], Value::Null);
+
+        for line in [
+            r#"{"jsonrpc":"1.0","id":3,"method":"echo"}"#,
+            r#"{"jsonrpc":"2.0","id":3}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":5}"#,
+            r#"42"#,
+        ] {
+            let response = answer(line).unwrap();
+            assert_eq!(response["error"]["code"], INVALID_REQUEST, "{}", line);
+            assert_eq!(response["id"], if line == "42" { Value::Null } else { json!(3) }, "{}", line);
+        }
+        let object_id = answer(r#"{"jsonrpc":"2.0","id":{"a":1},"method":"echo"}"#).unwrap();
+        assert_eq!(object_id["error"]["code"], INVALID_REQUEST);
+        assert_eq!(object_id["id"], Value::Null);
+
+        assert_eq!(answer("[]").unwrap()["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_batch() {
+        let responses = answer(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"echo","params":"a"},{"jsonrpc":"2.0","method":"echo"},{"bad":true}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            responses,
+            json!([
+                {"jsonrpc": "2.0", "id": 1, "result": "a"},
+                {"jsonrpc": "2.0", "id": null, "error": {"code": INVALID_REQUEST, "message": "missing field `jsonrpc`"}},
+            ])
+        );
+        assert_eq!(answer(r#"[{"jsonrpc":"2.0","method":"echo"}]"#), None);
+    }
+
+    #[test]
+    fn test_handler_panic_is_internal_error() {
+        let response = answer(r#"{"jsonrpc":"2.0","id":9,"method":"crash"}"#).unwrap();
+        assert_eq!(response["error"]["code"], INTERNAL_ERROR);
+        assert_eq!(response["id"], 9);
+    }
+
+    #[test]
+    fn test_serve_keeps_going() {
+        let input = b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"echo\",\"params\":1}\n\n\xff\xfe\n{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"crash\"}\n{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"echo\",\"params\":3}";
+        let mut output = Vec::new();
+        serve(&input[..], &mut output, echo).unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["result"], 1);
+        assert_eq!(responses[1]["error"]["code"], PARSE_ERROR);
+        assert_eq!(responses[2]["error"]["code"], INTERNAL_ERROR);
+        assert_eq!(responses[3]["result"], 3);
+    }
+
+    #[test]
+    fn test_server_methods() {
+        let config = Config::default();
+        let mut server = Server::new(&config, AdaptivePriorities::default());
+        let cwd = std::env::temp_dir();
+        let params = json!({"script": "git pus", "output": GIT_OUTPUT, "exit_code": 1, "cwd": cwd});
+
+        let corrections: Vec<CorrectedCommand> =
+            serde_json::from_value(server.handle("correct", Some(params.clone())).unwrap()).unwrap();
+        assert_eq!(corrections[0].script, "git push");
+
+        let explanations: Vec<Explanation> =
+            serde_json::from_value(server.handle("explain", Some(params)).unwrap()).unwrap();
+        assert_eq!(explanations[0].rule, "git_not_command");
+        assert_eq!(explanations[0].category.as_deref(), Some("git"));
+        assert_eq!(explanations[0].corrections[0].script, "git push");
+
+        let params = json!({"script": "git pus", "output": GIT_OUTPUT, "exit_code": 1, "cwd": cwd, "diff": true});
+        let result = server.handle("correct", Some(params)).unwrap();
+        assert_eq!(
+            result[0]["diff"],
+            json!([{"op": "equal", "token": "git"}, {"op": "delete", "token": "pus"}, {"op": "insert", "token": "push"}])
+        );
+        let corrections: Vec<DiffedCorrection> = serde_json::from_value(result).unwrap();
+        assert_eq!(corrections[0].correction.script, "git push");
+
+        let params = json!({"script": "git pus", "output": GIT_OUTPUT, "exit_code": 1, "cwd": cwd, "verbose": true});
+        let result: VerboseCorrections =
+            serde_json::from_value(server.handle("correct", Some(params)).unwrap()).unwrap();
+        assert_eq!(result.corrections[0]["script"], "git push");
+        assert!(result.evaluated >= result.matched && result.matched >= 1, "{:?}", result);
+        assert_eq!(result.suppressed, 0);
+
+        let rules: Vec<RuleSummary> = serde_json::from_value(server.handle("listRules", None).unwrap()).unwrap();
+        assert!(rules.iter().any(|rule| rule.name == "git_push_force" && rule.category == "git"));
+
+        let error = server.handle("correct", Some(json!({"output": "x"}))).unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        let error = server
+            .handle("correct", Some(json!({"script": "ls", "cwd": "/nonexistent/ftf"})))
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_server_honors_config() {
+        let mut config = Config::default();
+        config.rules.insert(
+            "git_not_command".to_string(),
+            crate::config::RuleConfig {
+                enabled: false,
+                priority: None,
+                options: Default::default(),
+            },
+        );
+        let mut server = Server::new(&config, AdaptivePriorities::default());
+        let params = CorrectParams {
+            script: "git pus".to_string(),
+            output: GIT_OUTPUT.to_string(),
+            exit_code: 1,
+            cwd: Some(std::env::temp_dir()),
+            diff: false,
+            verbose: false,
+        };
+
+        let corrections = server.correct(&params).unwrap();
+        assert!(corrections.iter().all(|correction| correction.rule.as_deref() != Some("git_not_command")));
+        assert!(server.list_rules().iter().any(|rule| rule.name == "git_not_command" && !rule.enabled));
+    }
+}
@@ -0,0 +1,187 @@
+//! Generic wrapper for changing part of a rule's behaviour.
+//!
+//! Priority overrides, `PATH` filtering, project relevance and the like each
+//! change one or two [`Rule`] methods of the rule they wrap. They implement
+//! [`Adapt`] for just those methods, and [`Adapted`] delegates everything
+//! else, so a method added to [`Rule`] only has to be forwarded here.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use super::RuleMetadata;
+use crate::{Command, CorrectedCommand, ProjectType, Rule, RuleContext, SideEffect, Verdict};
+
+/// The part of a rule's behaviour an adapter changes. Every method defaults to
+/// the wrapped rule's behaviour.
+pub trait Adapt: Send + Sync {
+    /// Gets the corrections in place of `inner`'s.
+    fn get_new_commands<'a>(&'a self, inner: &'a dyn Rule, command: &'a Command) -> Vec<Cow<'a, str>> {
+        inner.get_new_commands_cow(command)
+    }
+
+    /// Gets the priority in place of `inner`'s.
+    fn priority(&self, inner: &dyn Rule) -> i32 {
+        inner.priority()
+    }
+
+    /// Gets the side effect in place of `inner`'s.
+    fn side_effect_for(&self, inner: &dyn Rule, command: &Command, correction: &str) -> Option<SideEffect> {
+        inner.side_effect_for(command, correction)
+    }
+
+    /// Gets the relevant projects in place of `inner`'s.
+    fn relevant_projects<'a>(&'a self, inner: &'a dyn Rule) -> &'a [ProjectType] {
+        inner.relevant_projects()
+    }
+
+    /// Gets the category in place of `inner`'s.
+    fn category<'a>(&'a self, inner: &'a dyn Rule) -> Option<&'a str> {
+        inner.category()
+    }
+}
+
+/// A rule wrapped by an adapter.
+pub struct Adapted<A> {
+    inner: Box<dyn Rule>,
+    adapter: A,
+}
+
+impl<A: Adapt> Adapted<A> {
+    /// Wraps `rule` with `adapter`.
+    pub fn wrap(rule: Box<dyn Rule>, adapter: A) -> Self {
+        Self { inner: rule, adapter }
+    }
+
+    /// Gets the wrapped rule.
+    pub fn inner(&self) -> &dyn Rule {
+        self.inner.as_ref()
+    }
+}
+
+/// Wraps every rule of a group with a copy of `adapter`.
+pub fn adapt_all<A: Adapt + Clone + 'static>(rules: Vec<Box<dyn Rule>>, adapter: A) -> Vec<Box<dyn Rule>> {
+    rules
+        .into_iter()
+        .map(|rule| Box::new(Adapted::wrap(rule, adapter.clone())) as Box<dyn Rule>)
+        .collect()
+}
+
+impl<A: Adapt> Rule for Adapted<A> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        self.inner.matches(command)
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.get_new_commands_cow(command).into_iter().map(Cow::into_owned).collect()
+    }
+
+    fn get_new_commands_cow<'a>(&'a self, command: &'a Command) -> Vec<Cow<'a, str>> {
+        self.adapter.get_new_commands(self.inner.as_ref(), command)
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        self.inner.enabled_by_default()
+    }
+
+    fn priority(&self) -> i32 {
+        self.adapter.priority(self.inner.as_ref())
+    }
+
+    fn requires_output(&self) -> bool {
+        self.inner.requires_output()
+    }
+
+    fn requires_full_output(&self) -> bool {
+        self.inner.requires_full_output()
+    }
+
+    fn is_fallback(&self) -> bool {
+        self.inner.is_fallback()
+    }
+
+    fn required_script(&self) -> Option<&str> {
+        self.inner.required_script()
+    }
+
+    fn required_output(&self) -> Option<&str> {
+        self.inner.required_output()
+    }
+
+    fn leading_token(&self) -> Option<&str> {
+        self.inner.leading_token()
+    }
+
+    fn undo_for(&self, correction: &str, cwd: &Path) -> Option<String> {
+        self.inner.undo_for(correction, cwd)
+    }
+
+    fn side_effect_for(&self, command: &Command, correction: &str) -> Option<SideEffect> {
+        self.adapter.side_effect_for(self.inner.as_ref(), command, correction)
+    }
+
+    fn rollbacks_for(&self, correction: &str) -> Vec<(usize, String)> {
+        self.inner.rollbacks_for(correction)
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        self.inner.verify(correction, context)
+    }
+
+    fn sample_matching_command(&self) -> Option<Command> {
+        self.inner.sample_matching_command()
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            priority: self.priority(),
+            ..self.inner.metadata()
+        }
+    }
+
+    fn relevant_projects(&self) -> &[ProjectType] {
+        self.adapter.relevant_projects(self.inner.as_ref())
+    }
+
+    fn category(&self) -> Option<&str> {
+        self.adapter.category(self.inner.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimpleRuleBuilder;
+
+    struct Unchanged;
+
+    impl Adapt for Unchanged {}
+
+    #[test]
+    fn test_default_adapter_delegates_everything() {
+        let rule = SimpleRuleBuilder::new("mkdir_p")
+            .match_command("mkdir")
+            .match_output("No such file or directory")
+            .priority(120)
+            .undoable()
+            .replace("mkdir", "mkdir -p");
+        let adapted = Adapted::wrap(rule, Unchanged);
+        let cmd = Command::new("mkdir a/b", "mkdir: a: No such file or directory", 1);
+        let cwd = Path::new("/nonexistent");
+
+        assert_eq!(adapted.name(), "mkdir_p");
+        assert!(adapted.matches(&cmd));
+        assert_eq!(adapted.priority(), 120);
+        assert_eq!(adapted.is_fallback(), adapted.inner().is_fallback());
+        assert_eq!(adapted.required_script(), adapted.inner().required_script());
+        assert_eq!(adapted.required_output(), adapted.inner().required_output());
+        assert_eq!(adapted.leading_token(), adapted.inner().leading_token());
+        assert_eq!(adapted.get_corrected_commands(&cmd), adapted.inner().get_corrected_commands(&cmd));
+        assert_eq!(adapted.undo_for("mkdir -p a/b", cwd), adapted.inner().undo_for("mkdir -p a/b", cwd));
+        assert_eq!(adapted.metadata(), adapted.inner().metadata());
+        assert_eq!(adapted.sample_matching_command(), adapted.inner().sample_matching_command());
+    }
+}
@@ -0,0 +1,178 @@
+//! Rules that swap a missing command for an installed equivalent.
+//!
+//! This module contains rules for commands that have a drop-in replacement:
+//! - docker and podman, which share most of their CLI
+//! - `python` on systems that only ship `python3`
+//! - The standalone `docker-compose`, replaced by the `docker compose` plugin
+//!
+//! Every rule is registered through [`requires_binary`], so a replacement is
+//! only suggested when it is on `PATH`.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::requires_binary;
+use crate::{Command, FailureKind, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("docker_to_podman", 200),
+    ("podman_to_docker", 200),
+    ("python_to_python3", 200),
+    ("docker_compose_plugin", 200),
+];
+
+/// Creates all alternative-command rules.
+///
+/// Installed binaries are looked up through the context's shell.
+pub fn alternative_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // docker_to_podman: Use podman when docker is missing
+        requires_binary(
+            Box::new(ReplaceExecutableRule::new("docker_to_podman", "docker", &["podman"])),
+            Arc::clone(&context),
+        ),
+        // podman_to_docker: Use docker when podman is missing
+        requires_binary(
+            Box::new(ReplaceExecutableRule::new("podman_to_docker", "podman", &["docker"])),
+            Arc::clone(&context),
+        ),
+        // python_to_python3: Use python3 when there is no python
+        requires_binary(
+            Box::new(ReplaceExecutableRule::new("python_to_python3", "python", &["python3"])),
+            Arc::clone(&context),
+        ),
+        // docker_compose_plugin: Use `docker compose` when docker-compose is missing
+        requires_binary(
+            Box::new(ReplaceExecutableRule::new(
+                "docker_compose_plugin",
+                "docker-compose",
+                &["docker", "compose"],
+            )),
+            context,
+        ),
+    ]
+}
+
+/// Replaces a missing executable with the tokens of its equivalent, e.g.
+/// `docker ps` → `podman ps` or `docker-compose up -d` → `docker compose up -d`
+struct ReplaceExecutableRule {
+    name: &'static str,
+    missing: &'static str,
+    replacement: &'static [&'static str],
+}
+
+impl ReplaceExecutableRule {
+    fn new(name: &'static str, missing: &'static str, replacement: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            missing,
+            replacement,
+        }
+    }
+}
+
+impl Rule for ReplaceExecutableRule {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        let replacement = self.replacement.join(" ");
+        RuleMetadata {
+            executable: Some(self.missing.to_string()),
+            failure_kind: Some(FailureKind::NotFound),
+            ..RuleMetadata::of(self)
+        }
+        .describe(format!("Use `{}` when `{}` isn't installed", replacement, self.missing))
+        .example(Example::new(
+            format!("{} --version", self.missing),
+            format!("bash: {}: command not found", self.missing),
+            127,
+            format!("{} --version", replacement),
+        ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() == FailureKind::NotFound
+            && Script::parse(&command.script).executable() == Some(self.missing)
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let tokens = self
+            .replacement
+            .iter()
+            .copied()
+            .chain(script.tokens().iter().skip(1).map(String::as_str));
+        vec![Script::from_tokens(tokens).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    fn rules_with(binary: &str) -> Vec<Box<dyn Rule>> {
+        let shell = MockShell::new().with_output(&format!("{} --version", binary), "", 0);
+        alternative_rules(Arc::new(RuleContext::new(Box::new(shell))))
+    }
+
+    fn corrections(rules: &[Box<dyn Rule>], cmd: &Command) -> Vec<String> {
+        rules
+            .iter()
+            .filter(|rule| rule.matches(cmd))
+            .flat_map(|rule| rule.get_new_commands(cmd))
+            .collect()
+    }
+
+    #[test]
+    fn test_docker_to_podman_when_installed() {
+        let cmd = Command::new("docker run --rm -it alpine sh", "bash: docker: command not found", 127);
+
+        assert_eq!(corrections(&rules_with("podman"), &cmd), vec!["podman run --rm -it alpine sh"]);
+        assert!(corrections(&rules_with("nerdctl"), &cmd).is_empty());
+    }
+
+    #[test]
+    fn test_podman_to_docker() {
+        let cmd = Command::new("podman ps -a", "zsh: command not found: podman", 127);
+        assert_eq!(corrections(&rules_with("docker"), &cmd), vec!["docker ps -a"]);
+    }
+
+    #[test]
+    fn test_python_to_python3_keeps_quoting() {
+        let cmd = Command::new("python -c 'print(1 + 1)'", "bash: python: command not found", 127);
+
+        assert_eq!(corrections(&rules_with("python3"), &cmd), vec!["python3 -c 'print(1 + 1)'"]);
+        assert!(corrections(&rules_with("python2"), &cmd).is_empty());
+    }
+
+    #[test]
+    fn test_docker_compose_plugin() {
+        let cmd = Command::new("docker-compose up -d", "bash: docker-compose: command not found", 127);
+
+        assert_eq!(corrections(&rules_with("docker"), &cmd), vec!["docker compose up -d"]);
+        assert!(corrections(&rules_with("podman"), &cmd).is_empty());
+    }
+
+    #[test]
+    fn test_alternatives_only_match_missing_commands() {
+        let cmd = Command::new("docker ps", "Cannot connect to the Docker daemon at unix:///var/run/docker.sock.", 1);
+        assert!(corrections(&rules_with("podman"), &cmd).is_empty());
+    }
+
+    #[test]
+    fn test_alternative_rules_exist() {
+        let rules = alternative_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["docker_to_podman", "podman_to_docker", "python_to_python3", "docker_compose_plugin"]
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! AWS CLI command correction rules.
+//!
+//! This module contains rules for common aws cli mistakes:
+//! - Mistyped services and operations, using the CLI's own list of choices
+//! - Expired SSO tokens
+//! - Missing region
+//! - `aws s3 cp` of a directory without `--recursive`
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::listed_suggestions;
+use crate::fuzzy::similarity;
+use crate::{Command, Rule, RuleContext, Script, SimpleRuleBuilder};
+use std::sync::Arc;
+
+/// Global options that don't take a value.
+const BOOLEAN_OPTIONS: &[&str] = &[
+    "--debug",
+    "--no-verify-ssl",
+    "--no-paginate",
+    "--no-sign-request",
+    "--no-cli-pager",
+    "--no-cli-auto-prompt",
+];
+
+/// Region suggested when none is configured.
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Minimum similarity for a valid choice to be suggested.
+const MIN_CHOICE_SIMILARITY: f64 = 0.5;
+
+/// Maximum number of choices suggested.
+const MAX_CHOICE_SUGGESTIONS: usize = 3;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("aws_invalid_choice", 300),
+    ("aws_sso_expired", 200),
+    ("aws_region_missing", 250),
+    ("aws_s3_recursive", 300),
+];
+
+/// Creates all aws cli rules.
+///
+/// `region` is the region suggested when one is missing; it defaults to
+/// us-east-1. The SSO rule reads `AWS_PROFILE` through the context's shell.
+pub fn aws_rules(context: Arc<RuleContext>, region: Option<String>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // aws_invalid_choice: Fix a mistyped service or operation
+        Box::new(AwsInvalidChoiceRule),
+        // aws_sso_expired: Log in again before retrying
+        Box::new(AwsSsoExpiredRule::new(context)),
+        // aws_region_missing: Add --region
+        Box::new(AwsRegionMissingRule::new(
+            region.unwrap_or_else(|| DEFAULT_REGION.to_string()),
+        )),
+        // aws_s3_recursive: Add --recursive when copying a directory
+        create_aws_s3_recursive(),
+    ]
+}
+
+/// Gets the value of `--<flag> value` or `--<flag>=value` from a script.
+fn flag_value<'a>(script: &'a Script, flag: &str) -> Option<&'a str> {
+    let tokens = script.tokens();
+    let prefix = format!("{}=", flag);
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        if token == flag {
+            tokens.get(i + 1).map(String::as_str)
+        } else {
+            token.strip_prefix(&prefix)
+        }
+    })
+}
+
+/// aws_invalid_choice: `aws ec3 describe-instances` → `aws ec2 describe-instances`
+struct AwsInvalidChoiceRule;
+
+impl AwsInvalidChoiceRule {
+    /// Gets the rejected choice, from the error or from the script position
+    /// named by the error (`argument command` or `argument operation`).
+    fn invalid_choice(script: &Script, output: &str) -> Option<String> {
+        for marker in ["Invalid choice: '", "invalid choice '"] {
+            if let Some(rest) = output.split(marker).nth(1) {
+                return rest.split('\'').next().map(str::to_string);
+            }
+        }
+
+        let position = if output.contains("argument command: Invalid choice") {
+            0
+        } else if output.contains("argument operation: Invalid choice") {
+            1
+        } else {
+            return None;
+        };
+
+        // Skip global options such as `--profile dev` before the command
+        let mut positional = Vec::new();
+        let mut tokens = script.tokens().iter().skip(1);
+        while let Some(token) = tokens.next() {
+            if token.starts_with("--") {
+                if !token.contains('=') && !BOOLEAN_OPTIONS.contains(&token.as_str()) {
+                    tokens.next();
+                }
+            } else {
+                positional.push(token.clone());
+            }
+        }
+        positional.into_iter().nth(position)
+    }
+
+    /// Gets the CLI's "maybe you meant" suggestions, or the closest entries of
+    /// its "valid choices" table.
+    fn suggestions(typo: &str, output: &str) -> Vec<String> {
+        let meant = listed_suggestions(output, "maybe you meant:");
+        if !meant.is_empty() {
+            return meant.into_iter().map(str::to_string).collect();
+        }
+
+        let mut choices: Vec<(&str, f64)> = output
+            .lines()
+            .skip_while(|line| !line.contains("valid choices are:"))
+            .skip(1)
+            .flat_map(|line| line.split('|'))
+            .map(str::trim)
+            .filter(|choice| !choice.is_empty() && !choice.contains(' '))
+            .map(|choice| (choice, similarity(choice, typo)))
+            .filter(|(_, score)| *score >= MIN_CHOICE_SIMILARITY)
+            .collect();
+        choices.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        choices
+            .into_iter()
+            .take(MAX_CHOICE_SUGGESTIONS)
+            .map(|(choice, _)| choice.to_string())
+            .collect()
+    }
+}
+
+impl Rule for AwsInvalidChoiceRule {
+    fn name(&self) -> &str {
+        "aws_invalid_choice"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Fix a mistyped service or operation from the CLI's list of choices")
+            .example(Example::new(
+                "aws ec3 describe-instances",
+                "Invalid choice: 'ec3', maybe you meant:\n\n  * ec2",
+                252,
+                "aws ec2 describe-instances",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        let script = Script::parse(&command.script);
+        script.executable() == Some("aws")
+            && Self::invalid_choice(&script, &command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let Some(typo) = Self::invalid_choice(&script, &command.output) else {
+            return vec![];
+        };
+
+        Self::suggestions(&typo, &command.output)
+            .into_iter()
+            .map(|choice| script.clone().replace_token(&typo, choice).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// aws_sso_expired: Run `aws sso login` for the same profile, then retry
+pub struct AwsSsoExpiredRule {
+    context: Arc<RuleContext>,
+}
+
+impl AwsSsoExpiredRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the profile from `--profile`, falling back to `$AWS_PROFILE`.
+    fn profile(&self, script: &Script) -> Option<String> {
+        flag_value(script, "--profile")
+            .map(str::to_string)
+            .or_else(|| self.context.shell()?.env("AWS_PROFILE"))
+    }
+}
+
+impl Rule for AwsSsoExpiredRule {
+    fn name(&self) -> &str {
+        "aws_sso_expired"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Log in to SSO again with the same profile, then retry")
+            .example(Example::new(
+                "aws s3 ls --profile prod",
+                "Error when retrieving token from sso: Token has expired and refresh failed",
+                255,
+                "aws sso login --profile prod && aws s3 ls --profile prod",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.output.contains("Error when retrieving token from sso: Token has expired")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let mut login = Script::from_tokens(["aws", "sso", "login"]);
+        if let Some(profile) = self.profile(&script) {
+            login = login.push_token("--profile").push_token(profile);
+        }
+
+        vec![login.append(" && ", script).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+/// aws_region_missing: `aws ec2 describe-instances` → `... --region us-east-1`
+pub struct AwsRegionMissingRule {
+    region: String,
+}
+
+impl AwsRegionMissingRule {
+    /// Creates the rule, suggesting `region`.
+    pub fn new(region: String) -> Self {
+        Self { region }
+    }
+}
+
+impl Rule for AwsRegionMissingRule {
+    fn name(&self) -> &str {
+        "aws_region_missing"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Add the configured `region` when none is set")
+            .example(Example::new(
+                "aws ec2 describe-instances",
+                "You must specify a region.",
+                253,
+                format!("aws ec2 describe-instances --region {}", self.region),
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        let script = Script::parse(&command.script);
+        script.executable() == Some("aws")
+            && command.output.contains("You must specify a region")
+            && flag_value(&script, "--region").is_none()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        vec![Script::parse(&command.script)
+            .push_token("--region")
+            .push_token(self.region.as_str())
+            .to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        250
+    }
+}
+
+/// aws_s3_recursive: `aws s3 cp dir s3://bucket/` → `aws s3 cp --recursive dir s3://bucket/`
+fn create_aws_s3_recursive() -> Box<dyn Rule> {
+    SimpleRuleBuilder::new("aws_s3_recursive")
+        .match_command("aws s3 cp")
+        .match_output("Is a directory")
+        .priority(300)
+        .describe("Copy a directory to or from S3 recursively")
+        .example(Example::new(
+            "aws s3 cp dist s3://site/",
+            "upload failed: dist to s3://site/dist [Errno 21] Is a directory: 'dist'",
+            1,
+            "aws s3 cp --recursive dist s3://site/",
+        ))
+        .insert_flag("cp", "--recursive")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const MAYBE_YOU_MEANT: &str = "\naws: [ERROR]: argument command: Found invalid choice 'ec3'\n\nusage: aws [options] <command> <subcommand> [<subcommand> ...] [parameters]\nTo see help text, you can run:\n\n  aws help\n  aws <command> help\n  aws <command> <subcommand> help\n\nInvalid choice: 'ec3', maybe you meant:\n\n  * ec2\n  * ecs\n";
+
+    const VALID_CHOICES: &str = "usage: aws [options] <command> <subcommand> [<subcommand> ...] [parameters]\nTo see help text, you can run:\n\n  aws help\n  aws <command> help\n  aws <command> <subcommand> help\n\naws: error: argument operation: Invalid choice, valid choices are:\n\ncp                                       | ls\nmb                                       | mv\npresign                                  | rb\nrm                                       | sync\nwebsite\n";
+
+    const SSO_EXPIRED: &str = "Error when retrieving token from sso: Token has expired and refresh failed";
+
+    const NO_REGION: &str = "You must specify a region. You can also configure your region by running \"aws configure\".";
+
+    fn context() -> Arc<RuleContext> {
+        Arc::new(RuleContext::default())
+    }
+
+    #[test]
+    fn test_aws_invalid_choice_maybe_you_meant() {
+        let rule = AwsInvalidChoiceRule;
+        let cmd = Command::new("aws ec3 describe-instances", MAYBE_YOU_MEANT, 252);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws ec2 describe-instances", "aws ecs describe-instances"]
+        );
+    }
+
+    #[test]
+    fn test_aws_invalid_choice_valid_choices_table() {
+        let rule = AwsInvalidChoiceRule;
+        let cmd = Command::new("aws --profile dev s3 snyc . s3://bucket", VALID_CHOICES, 252);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd)[0],
+            "aws --profile dev s3 sync . s3://bucket"
+        );
+    }
+
+    #[test]
+    fn test_aws_sso_expired_profile_from_flag() {
+        let rule = AwsSsoExpiredRule::new(context());
+        let cmd = Command::new("aws s3 ls --profile=prod", SSO_EXPIRED, 255);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws sso login --profile prod && aws s3 ls --profile=prod"]
+        );
+    }
+
+    #[test]
+    fn test_aws_sso_expired_profile_from_env() {
+        let shell = MockShell::new().with_env("AWS_PROFILE", "staging");
+        let rule = AwsSsoExpiredRule::new(Arc::new(RuleContext::new(Box::new(shell))));
+        let cmd = Command::new("aws s3 ls", SSO_EXPIRED, 255);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws sso login --profile staging && aws s3 ls"]
+        );
+    }
+
+    #[test]
+    fn test_aws_sso_expired_without_profile() {
+        let rule = AwsSsoExpiredRule::new(context());
+        let cmd = Command::new("aws s3 ls", SSO_EXPIRED, 255);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["aws sso login && aws s3 ls"]);
+    }
+
+    #[test]
+    fn test_aws_region_missing() {
+        let rule = AwsRegionMissingRule::new(DEFAULT_REGION.to_string());
+        let cmd = Command::new("aws ec2 describe-instances", NO_REGION, 253);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws ec2 describe-instances --region us-east-1"]
+        );
+    }
+
+    #[test]
+    fn test_aws_region_from_config() {
+        let rules = aws_rules(context(), Some("eu-west-1".to_string()));
+        let rule = rules.iter().find(|r| r.name() == "aws_region_missing").unwrap();
+        let cmd = Command::new("aws ec2 describe-instances", NO_REGION, 253);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws ec2 describe-instances --region eu-west-1"]
+        );
+    }
+
+    #[test]
+    fn test_aws_region_already_set() {
+        let rule = AwsRegionMissingRule::new(DEFAULT_REGION.to_string());
+        let cmd = Command::new("aws ec2 describe-instances --region ''", NO_REGION, 253);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_aws_s3_recursive() {
+        let rule = create_aws_s3_recursive();
+        let cmd = Command::new(
+            "aws s3 cp build s3://my-bucket/build",
+            "upload failed: build to s3://my-bucket/build [Errno 21] Is a directory: 'build'",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws s3 cp --recursive build s3://my-bucket/build"]
+        );
+    }
+
+    #[test]
+    fn test_aws_rules_exist() {
+        let rules = aws_rules(context(), None);
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["aws_invalid_choice", "aws_sso_expired", "aws_region_missing", "aws_s3_recursive"]
+        );
+    }
+}
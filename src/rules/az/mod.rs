@@ -0,0 +1,154 @@
+//! Azure CLI (`az`) command correction rules.
+//!
+//! This module contains rules for common az mistakes:
+//! - Mistyped command groups, using az's own "most similar choice" list
+//! - Expired login tokens
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::{listed_suggestions, prefix_then_retry, replace_with_suggestions};
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, Script};
+
+/// Header az prints before its suggestions ("choice ... is" or "choices ... are").
+const MOST_SIMILAR: &str = "The most similar choice";
+
+/// Executables every az rule requires.
+pub const EXECUTABLES: &[&str] = &["az"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("az_not_in_group", 300),
+    ("az_login_expired", 200),
+];
+
+/// Creates all az rules.
+pub fn az_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // az_not_in_group: Use az's suggestion for a mistyped command
+        Box::new(AzNotInGroupRule),
+        // az_login_expired: Log in again before retrying
+        create_az_login_expired(),
+    ]
+}
+
+/// az_not_in_group: `az stroage account list` → `az storage account list`
+struct AzNotInGroupRule;
+
+impl AzNotInGroupRule {
+    /// Gets the mistyped command from az's error.
+    fn unknown_command(output: &str) -> Option<&str> {
+        let rest = output.split("az: '").nth(1)?;
+        let (typo, rest) = rest.split_once('\'')?;
+        rest.starts_with(" is not in the '").then_some(typo)
+    }
+}
+
+impl Rule for AzNotInGroupRule {
+    fn name(&self) -> &str {
+        "az_not_in_group"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use az's suggestion for a mistyped command")
+            .example(Example::new(
+                "az stroage account list",
+                "az: 'stroage' is not in the 'az' command group.\n\nThe most similar choice to 'stroage' is:\n\tstorage",
+                2,
+                "az storage account list",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("az")
+            && Self::unknown_command(&command.output).is_some()
+            && !listed_suggestions(&command.output, MOST_SIMILAR).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::unknown_command(&command.output) else {
+            return vec![];
+        };
+
+        replace_with_suggestions(
+            &command.script,
+            typo,
+            listed_suggestions(&command.output, MOST_SIMILAR),
+        )
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// az_login_expired: Run `az login`, then retry
+fn create_az_login_expired() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("az_login_expired")
+        .match_command_static(static_regex!(r"^az\s"))
+        .match_output_static(static_regex!(r"(?i)(token has expired|AADSTS700082|Please run 'az login')"))
+        .priority(200)
+        .describe("Run `az login` when the token has expired, then retry")
+        .example(Example::new(
+            "az group list",
+            "ERROR: AADSTS700082: The refresh token has expired due to inactivity.",
+            1,
+            "az login && az group list",
+        ))
+        .replace_with(|original, _captures| vec![prefix_then_retry("az login", original)])
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOT_IN_GROUP: &str = "az: 'stroage' is not in the 'az' command group. See 'az --help'. If the command is from an extension, please make sure the corresponding extension is installed. To learn more about extensions, please visit https://docs.microsoft.com/cli/azure/azure-cli-extensions-overview\n\nThe most similar choice to 'stroage' is:\n\tstorage\n";
+
+    const NOT_IN_SUBGROUP: &str = "az: 'lst' is not in the 'az vm' command group. See 'az vm --help'.\n\nThe most similar choices to 'lst' are:\n    list\n    list-sizes\n    list-skus\n";
+
+    const TOKEN_EXPIRED: &str = "ERROR: AADSTS700082: The refresh token has expired due to inactivity. The token was issued on 2024-01-02T10:00:00Z and was inactive for 90.00:00:00.\nInteractive authentication is needed. Please run:\naz login\n";
+
+    #[test]
+    fn test_az_not_in_group() {
+        let rule = AzNotInGroupRule;
+        let cmd = Command::new("az stroage account list", NOT_IN_GROUP, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["az storage account list"]);
+    }
+
+    #[test]
+    fn test_az_not_in_subgroup() {
+        let rule = AzNotInGroupRule;
+        let cmd = Command::new("az vm lst -g web", NOT_IN_SUBGROUP, 2);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["az vm list -g web", "az vm list-sizes -g web", "az vm list-skus -g web"]
+        );
+    }
+
+    #[test]
+    fn test_az_login_expired() {
+        let rule = create_az_login_expired();
+        let cmd = Command::new("az group list", TOKEN_EXPIRED, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["az login && az group list"]);
+    }
+
+    #[test]
+    fn test_az_rules_do_not_match_other_errors() {
+        let cmd = Command::new("az group show -n web", "ERROR: Resource group 'web' could not be found.", 3);
+        for rule in az_rules() {
+            assert!(!rule.matches(&cmd), "{} should not match", rule.name());
+        }
+    }
+
+    #[test]
+    fn test_az_rules_exist() {
+        let rules = az_rules();
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["az_not_in_group", "az_login_expired"]);
+    }
+}
@@ -1,11 +1,15 @@
-/// Fuzzy rule builder for approximate pattern matching.
-///
-/// Uses fuzzy matching to identify commands that are "close enough" to a known pattern.
-/// Useful for typos and similar variations.
-
-use crate::{Command, Rule};
+//! Fuzzy rule builder for approximate pattern matching.
+//!
+//! Uses fuzzy matching to identify commands that are "close enough" to a known pattern.
+//! Useful for typos and similar variations.
+
+use super::{derive_example, SAMPLE_SCRIPT};
+use crate::error::{RuleBuildError, RuleBuildErrorKind};
+use crate::rules::{Example, Pattern, RuleMetadata};
+use crate::{Command, FailureKind, Rule};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
+use std::borrow::Cow;
 
 /// A rule builder for fuzzy pattern matching with typo correction.
 pub struct FuzzyRuleBuilder {
@@ -13,8 +17,12 @@ pub struct FuzzyRuleBuilder {
     command_pattern: Option<String>,
     output_pattern: Option<String>,
     replacement: Option<String>,
+    failure_kind: Option<FailureKind>,
     threshold: i64,
     priority: i32,
+    description: Option<String>,
+    example: Option<Example>,
+    example_failure: Option<(String, String)>,
 }
 
 impl FuzzyRuleBuilder {
@@ -25,8 +33,12 @@ impl FuzzyRuleBuilder {
             command_pattern: None,
             output_pattern: None,
             replacement: None,
+            failure_kind: None,
             threshold: 20, // Default threshold (SkimMatcher uses log scoring, 20 is reasonable default)
             priority: 1000,
+            description: None,
+            example: None,
+            example_failure: None,
         }
     }
 
@@ -45,7 +57,13 @@ impl FuzzyRuleBuilder {
     /// Sets the fuzzy match threshold (0-100, higher = stricter).
     /// Default is 50.
     pub fn threshold(mut self, t: i64) -> Self {
-        self.threshold = t.max(0).min(100);
+        self.threshold = t.clamp(0, 100);
+        self
+    }
+
+    /// Only matches commands that failed with the given kind.
+    pub fn match_failure_kind(mut self, kind: FailureKind) -> Self {
+        self.failure_kind = Some(kind);
         self
     }
 
@@ -55,6 +73,26 @@ impl FuzzyRuleBuilder {
         self
     }
 
+    /// Describes what the rule corrects, for `ftf rules export`.
+    pub fn describe(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Gives a failure the rule corrects, for `ftf rules export`.
+    pub fn example(mut self, example: Example) -> Self {
+        self.example = Some(example);
+        self
+    }
+
+    /// Gives a failure the rule corrects, for `ftf rules show`: `script`
+    /// printing `output`. Its exit code comes from the failure kind matched
+    /// and its correction from the rule, once built.
+    pub fn example_failure(mut self, script: impl Into<String>, output: impl Into<String>) -> Self {
+        self.example_failure = Some((script.into(), output.into()));
+        self
+    }
+
     /// Sets the fixed replacement command.
     pub fn replace(mut self, replacement: impl Into<String>) -> Self {
         self.replacement = Some(replacement.into());
@@ -62,24 +100,29 @@ impl FuzzyRuleBuilder {
     }
 
     /// Builds the fuzzy rule.
-    pub fn build(self) -> Result<Box<dyn Rule>, String> {
+    pub fn build(self) -> Result<Box<dyn Rule>, RuleBuildError> {
         if self.command_pattern.is_none() && self.output_pattern.is_none() {
-            return Err("FuzzyRuleBuilder: At least one pattern (command or output) must be set"
-                .to_string());
-        }
-
-        if self.replacement.is_none() {
-            return Err("FuzzyRuleBuilder: replacement must be set".to_string());
+            return Err(RuleBuildError::new(self.name, RuleBuildErrorKind::MissingPattern));
         }
+        let Some(replacement) = self.replacement else {
+            return Err(RuleBuildError::new(self.name, RuleBuildErrorKind::MissingReplacement));
+        };
 
-        Ok(Box::new(FuzzyRule {
+        let mut rule = FuzzyRule {
             name: self.name,
             command_pattern: self.command_pattern,
             output_pattern: self.output_pattern,
-            replacement: self.replacement.unwrap(),
+            replacement,
+            failure_kind: self.failure_kind,
             threshold: self.threshold,
             priority: self.priority,
-        }))
+            description: self.description,
+            example: self.example,
+        };
+        if let Some(failure) = self.example_failure {
+            rule.example = Some(derive_example(&rule, failure, rule.failure_kind));
+        }
+        Ok(Box::new(rule))
     }
 }
 
@@ -89,8 +132,11 @@ struct FuzzyRule {
     command_pattern: Option<String>,
     output_pattern: Option<String>,
     replacement: String,
+    failure_kind: Option<FailureKind>,
     threshold: i64,
     priority: i32,
+    description: Option<String>,
+    example: Option<Example>,
 }
 
 impl FuzzyRule {
@@ -113,6 +159,12 @@ impl Rule for FuzzyRule {
     }
 
     fn matches(&self, command: &Command) -> bool {
+        if let Some(kind) = self.failure_kind {
+            if command.failure_kind() != kind {
+                return false;
+            }
+        }
+
         let cmd_matches = if let Some(ref pattern) = self.command_pattern {
             self.fuzzy_matches(&command.script, pattern)
         } else {
@@ -128,13 +180,37 @@ impl Rule for FuzzyRule {
         cmd_matches && out_matches
     }
 
-    fn get_new_commands(&self, _command: &Command) -> Vec<String> {
-        vec![self.replacement.clone()]
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.get_new_commands_cow(command).into_iter().map(Cow::into_owned).collect()
+    }
+
+    fn get_new_commands_cow<'a>(&'a self, _command: &'a Command) -> Vec<Cow<'a, str>> {
+        vec![Cow::Borrowed(&self.replacement)]
     }
 
     fn priority(&self) -> i32 {
         self.priority
     }
+
+    fn sample_matching_command(&self) -> Option<Command> {
+        let command = Command::new(
+            self.command_pattern.as_deref().unwrap_or(SAMPLE_SCRIPT),
+            self.output_pattern.as_deref().unwrap_or_default(),
+            self.failure_kind.map_or(1, FailureKind::exit_code),
+        );
+        self.matches(&command).then_some(command)
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            match_command: self.command_pattern.clone().map(Pattern::fuzzy),
+            match_output: self.output_pattern.clone().map(Pattern::fuzzy),
+            failure_kind: self.failure_kind,
+            description: self.description.clone(),
+            example: self.example.clone(),
+            ..RuleMetadata::of(self)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -148,6 +224,20 @@ mod tests {
         assert_eq!(builder.threshold, 20);
     }
 
+    #[test]
+    fn test_sample_matching_command() {
+        let rule = FuzzyRuleBuilder::new("git_status")
+            .match_command("git status")
+            .match_output("not a git repository")
+            .replace("git init")
+            .build()
+            .unwrap();
+        let sample = rule.sample_matching_command().unwrap();
+        assert_eq!(sample.script, "git status");
+        assert_eq!(sample.output, "not a git repository");
+        assert!(rule.matches(&sample));
+    }
+
     #[test]
     fn test_fuzzy_rule_command_pattern() {
         let rule = FuzzyRuleBuilder::new("git_status")
@@ -162,6 +252,8 @@ mod tests {
             script: "git status".to_string(),
             output: "".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
         assert!(rule.matches(&cmd_exact));
 
@@ -170,6 +262,8 @@ mod tests {
             script: "apt update".to_string(),
             output: "".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
         assert!(!rule.matches(&cmd_far));
     }
@@ -187,6 +281,8 @@ mod tests {
             script: "apt update".to_string(),
             output: "Permission denied".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd_match));
@@ -212,6 +308,8 @@ mod tests {
             script: "git status".to_string(),
             output: "".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         // Lenient should match exact string
@@ -231,18 +329,50 @@ mod tests {
             script: "git push".to_string(),
             output: "error: rejected".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         let cmd_cmd_only = Command {
             script: "git push".to_string(),
             output: "Success".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd_both_match));
         assert!(!rule.matches(&cmd_cmd_only));
     }
 
+    #[test]
+    fn test_fuzzy_rule_failure_kind() {
+        let rule = FuzzyRuleBuilder::new("interrupted")
+            .match_command("git status")
+            .match_failure_kind(FailureKind::Interrupted)
+            .replace("git status")
+            .build()
+            .unwrap();
+
+        assert!(rule.matches(&Command::new("git status", "", 130)));
+        assert!(!rule.matches(&Command::new("git status", "", 1)));
+    }
+
+    #[test]
+    fn test_fuzzy_rule_metadata_keeps_patterns() {
+        let rule = FuzzyRuleBuilder::new("test")
+            .match_output("not found")
+            .example(Example::new("gti", "not found", 1, "git"))
+            .replace("git")
+            .build()
+            .unwrap();
+
+        let metadata = rule.metadata();
+        assert_eq!(metadata.match_command, None);
+        assert_eq!(metadata.match_output, Some(Pattern::fuzzy("not found")));
+        assert_eq!(metadata.example.unwrap().correction, "git");
+    }
+
     #[test]
     fn test_fuzzy_rule_missing_pattern_error() {
         let result = FuzzyRuleBuilder::new("invalid")
@@ -283,6 +413,8 @@ mod tests {
             script: "old".to_string(),
             output: "".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         let corrections = rule.get_new_commands(&cmd);
@@ -1,9 +1,26 @@
-/// Rule builders for creating rules with fluent API.
+//! Rule builders for creating rules with fluent API.
 
 pub mod simple;
 pub mod regex;
 pub mod fuzzy;
 
 pub use simple::SimpleRuleBuilder;
-pub use regex::RegexRuleBuilder;
+pub use regex::{PatternLimits, RegexRuleBuilder};
 pub use fuzzy::FuzzyRuleBuilder;
+
+use crate::rules::Example;
+use crate::{Command, FailureKind, Rule};
+
+/// Stands in for the script of a sampled command when no pattern gives one,
+/// for [`Rule::sample_matching_command`](crate::Rule::sample_matching_command).
+pub(crate) const SAMPLE_SCRIPT: &str = "placeholder";
+
+/// Completes an example given as a failure alone, with `example_failure`:
+/// it fails with the exit code of `failure_kind` and is corrected to what
+/// `rule` offers first, so the example can't drift from the rule.
+pub(crate) fn derive_example(rule: &dyn Rule, failure: (String, String), failure_kind: Option<FailureKind>) -> Example {
+    let (script, output) = failure;
+    let exit_code = failure_kind.map_or(1, FailureKind::exit_code);
+    let correction = rule.get_new_commands(&Command::new(&script, &output, exit_code)).into_iter().next();
+    Example::new(script, output, exit_code, correction.unwrap_or_default())
+}
@@ -1,15 +1,85 @@
-/// Regex-based rule builder for pattern matching and replacement with capture groups.
+//! Regex-based rule builder for pattern matching and replacement with capture groups.
 
-use crate::{Command, Rule};
-use regex::Regex;
+use super::{derive_example, SAMPLE_SCRIPT};
+use crate::rules::{Example, Pattern, RuleMetadata};
+use crate::error::{RuleBuildError, RuleBuildErrorKind};
+use crate::undo::inverse_command;
+use crate::{Command, CorrectedCommand, FailureKind, Rule, RuleContext, Script, SideEffect, Verdict};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Replacement callback receiving the original script and the regex captures.
+type ReplacementFn = Box<dyn for<'a> Fn(&'a str, &regex::Captures<'a>) -> Vec<Cow<'a, str>> + Send + Sync>;
+
+/// Side effect callback receiving the original script and the regex captures.
+type SideEffectFn = Box<dyn Fn(&str, &regex::Captures) -> Option<SideEffect> + Send + Sync>;
+
+/// Verify callback receiving a correction and the rule context.
+type VerifyFn = Box<dyn Fn(&CorrectedCommand, &RuleContext) -> Verdict + Send + Sync>;
+
+/// Bounds on patterns from untrusted sources, such as the config or plugins.
+///
+/// The regex crate matches in time linear in the output, but the factor
+/// grows with the compiled pattern, and a pattern can compile to an enormous
+/// program. Output is capped (`max_output_bytes`), so bounding the compiled
+/// size bounds the time any one rule spends matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternLimits {
+    /// Longest pattern accepted, in bytes
+    pub max_pattern_len: usize,
+    /// Largest compiled program accepted, in bytes. Also bounds the lazy
+    /// DFA's cache.
+    pub max_compiled_bytes: usize,
+}
+
+/// Default for [`PatternLimits::max_compiled_bytes`]: far more than any
+/// built-in rule needs, and a thirtieth of the regex crate's own limit.
+pub const DEFAULT_MAX_COMPILED_PATTERN_BYTES: usize = 256 * 1024;
+
+impl Default for PatternLimits {
+    fn default() -> Self {
+        Self {
+            max_pattern_len: 4096,
+            max_compiled_bytes: DEFAULT_MAX_COMPILED_PATTERN_BYTES,
+        }
+    }
+}
+
+impl PatternLimits {
+    /// Compiles the `field` pattern of rule `rule` within these limits.
+    pub fn compile(&self, rule: &str, field: &'static str, pattern: &str) -> Result<Regex, RuleBuildError> {
+        if pattern.len() > self.max_pattern_len {
+            let kind = RuleBuildErrorKind::PatternTooLong {
+                field,
+                len: pattern.len(),
+                limit: self.max_pattern_len,
+            };
+            return Err(RuleBuildError::new(rule, kind));
+        }
+        RegexBuilder::new(pattern)
+            .size_limit(self.max_compiled_bytes)
+            .dfa_size_limit(self.max_compiled_bytes)
+            .build()
+            .map_err(|source| RuleBuildError::new(rule, RuleBuildErrorKind::InvalidRegex { field, source }))
+    }
+}
 
 /// A rule builder for regex-based pattern matching with capture group support.
 pub struct RegexRuleBuilder {
     name: String,
-    command_pattern: Option<Regex>,
-    output_pattern: Option<Regex>,
-    replacement_fn: Option<Box<dyn Fn(&str, &regex::Captures) -> Vec<String> + Send + Sync>>,
+    command_pattern: Option<Cow<'static, Regex>>,
+    output_pattern: Option<Cow<'static, Regex>>,
+    replacement_fn: Option<ReplacementFn>,
+    side_effect_fn: Option<SideEffectFn>,
+    verify_fn: Option<VerifyFn>,
+    rollbacks: Vec<(usize, String)>,
+    failure_kind: Option<FailureKind>,
+    undoable: bool,
     priority: i32,
+    description: Option<String>,
+    example: Option<Example>,
+    example_failure: Option<(String, String)>,
 }
 
 impl RegexRuleBuilder {
@@ -20,92 +90,466 @@ impl RegexRuleBuilder {
             command_pattern: None,
             output_pattern: None,
             replacement_fn: None,
+            side_effect_fn: None,
+            verify_fn: None,
+            rollbacks: Vec::new(),
+            failure_kind: None,
+            undoable: false,
             priority: 1000,
+            description: None,
+            example: None,
+            example_failure: None,
         }
     }
 
     /// Sets a regex pattern to match against the command.
     /// Returns an error if the regex is invalid.
-    pub fn match_command_regex(mut self, pattern: &str) -> Result<Self, regex::Error> {
-        self.command_pattern = Some(Regex::new(pattern)?);
+    ///
+    /// A pattern anchored to a literal word and whitespace, such as
+    /// `^git\s+push`, gives the rule that word as its leading token.
+    pub fn match_command_regex(mut self, pattern: &str) -> Result<Self, RuleBuildError> {
+        self.command_pattern = Some(Cow::Owned(self.compile("command", pattern)?));
         Ok(self)
     }
 
+    /// Sets a pattern from an untrusted source to match against the command,
+    /// rejecting it if it exceeds `limits`.
+    pub fn match_command_bounded(mut self, pattern: &str, limits: &PatternLimits) -> Result<Self, RuleBuildError> {
+        self.command_pattern = Some(Cow::Owned(limits.compile(&self.name, "command", pattern)?));
+        Ok(self)
+    }
+
+    /// Sets an already compiled regex to match against the command.
+    ///
+    /// Built-in rules pass a [`static_regex!`](crate::static_regex), so
+    /// building them again doesn't recompile their patterns.
+    pub fn match_command_static(mut self, regex: &'static Regex) -> Self {
+        self.command_pattern = Some(Cow::Borrowed(regex));
+        self
+    }
+
     /// Sets a regex pattern to match against the command output.
     /// Returns an error if the regex is invalid.
-    pub fn match_output_regex(mut self, pattern: &str) -> Result<Self, regex::Error> {
-        self.output_pattern = Some(Regex::new(pattern)?);
+    pub fn match_output_regex(mut self, pattern: &str) -> Result<Self, RuleBuildError> {
+        self.output_pattern = Some(Cow::Owned(self.compile("output", pattern)?));
+        Ok(self)
+    }
+
+    /// Sets a pattern from an untrusted source to match against the command
+    /// output, rejecting it if it exceeds `limits`.
+    pub fn match_output_bounded(mut self, pattern: &str, limits: &PatternLimits) -> Result<Self, RuleBuildError> {
+        self.output_pattern = Some(Cow::Owned(limits.compile(&self.name, "output", pattern)?));
         Ok(self)
     }
 
+    /// Sets an already compiled regex to match against the command output.
+    pub fn match_output_static(mut self, regex: &'static Regex) -> Self {
+        self.output_pattern = Some(Cow::Borrowed(regex));
+        self
+    }
+
+    /// Only matches commands that failed with the given kind.
+    pub fn match_failure_kind(mut self, kind: FailureKind) -> Self {
+        self.failure_kind = Some(kind);
+        self
+    }
+
+    /// Declares that executed corrections can be reverted with `undo::inverse_command`.
+    pub fn undoable(mut self) -> Self {
+        self.undoable = true;
+        self
+    }
+
     /// Sets the priority (lower = higher priority).
     pub fn priority(mut self, p: i32) -> Self {
         self.priority = p;
         self
     }
 
+    /// Describes what the rule corrects, for `ftf rules export`.
+    pub fn describe(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Gives a failure the rule corrects, for `ftf rules export`.
+    pub fn example(mut self, example: Example) -> Self {
+        self.example = Some(example);
+        self
+    }
+
+    /// Gives a failure the rule corrects, for `ftf rules show`: `script`
+    /// printing `output`. Its exit code comes from the failure kind matched
+    /// and its correction from the rule, once built.
+    pub fn example_failure(mut self, script: impl Into<String>, output: impl Into<String>) -> Self {
+        self.example_failure = Some((script.into(), output.into()));
+        self
+    }
+
     /// Sets a replacement function that generates corrections based on the matched command and capture groups.
     /// The function receives the original command and the regex captures.
     pub fn replace_with<F>(mut self, f: F) -> Self
     where
         F: Fn(&str, &regex::Captures) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.replacement_fn = Some(Box::new(move |original, captures| {
+            f(original, captures).into_iter().map(Cow::Owned).collect()
+        }));
+        self
+    }
+
+    /// Sets a replacement function whose corrections may borrow from the
+    /// original command or the captures, such as retrying the command
+    /// unchanged. Only the corrections kept are allocated.
+    pub fn replace_with_cow<F>(mut self, f: F) -> Self
+    where
+        F: for<'a> Fn(&'a str, &regex::Captures<'a>) -> Vec<Cow<'a, str>> + Send + Sync + 'static,
     {
         self.replacement_fn = Some(Box::new(f));
         self
     }
 
+    /// Sets a function that attaches a side effect to the corrections, from
+    /// the same original command and captures as the replacement.
+    pub fn side_effect<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &regex::Captures) -> Option<SideEffect> + Send + Sync + 'static,
+    {
+        self.side_effect_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a function that checks each correction is likely to succeed
+    /// before it is offered.
+    pub fn verify<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&CorrectedCommand, &RuleContext) -> Verdict + Send + Sync + 'static,
+    {
+        self.verify_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Declares `command` as the rollback for the 0-based `segment` of the
+    /// corrections' `&&` chains, run if a later segment fails.
+    pub fn rollback_for_segment(mut self, segment: usize, command: impl Into<String>) -> Self {
+        self.rollbacks.push((segment, command.into()));
+        self
+    }
+
+    /// Compiles the `field` pattern of this rule, without limits.
+    fn compile(&self, field: &'static str, pattern: &str) -> Result<Regex, RuleBuildError> {
+        Regex::new(pattern)
+            .map_err(|source| RuleBuildError::new(&self.name, RuleBuildErrorKind::InvalidRegex { field, source }))
+    }
+
     /// Builds the regex rule.
-    pub fn build(self) -> Result<Box<dyn Rule>, String> {
+    pub fn build(self) -> Result<Box<dyn Rule>, RuleBuildError> {
         if self.command_pattern.is_none() && self.output_pattern.is_none() {
-            return Err("RegexRuleBuilder: At least one pattern (command or output) must be set"
-                .to_string());
-        }
-
-        if self.replacement_fn.is_none() {
-            return Err("RegexRuleBuilder: replacement_fn must be set".to_string());
+            return Err(RuleBuildError::new(self.name, RuleBuildErrorKind::MissingPattern));
         }
+        let Some(replacement_fn) = self.replacement_fn else {
+            return Err(RuleBuildError::new(self.name, RuleBuildErrorKind::MissingReplacement));
+        };
 
-        Ok(Box::new(RegexRule {
+        let leading_token = self
+            .command_pattern
+            .as_ref()
+            .and_then(|pattern| leading_word(pattern.as_str()))
+            .map(str::to_string);
+        let mut rule = RegexRule {
             name: self.name,
+            leading_token,
             command_pattern: self.command_pattern,
             output_pattern: self.output_pattern,
-            replacement_fn: self.replacement_fn.unwrap(),
+            replacement_fn,
+            side_effect_fn: self.side_effect_fn,
+            verify_fn: self.verify_fn,
+            rollbacks: self.rollbacks,
+            failure_kind: self.failure_kind,
+            undoable: self.undoable,
             priority: self.priority,
-        }))
+            description: self.description,
+            example: self.example,
+        };
+        if let Some(failure) = self.example_failure {
+            rule.example = Some(derive_example(&rule, failure, rule.failure_kind));
+        }
+        Ok(Box::new(rule))
+    }
+
+    /// Builds a built-in rule. Its patterns and replacement are fixed, so a
+    /// mistake in one is a bug: it panics here, which the test building
+    /// every built-in rule catches before a release does.
+    #[track_caller]
+    pub fn build_builtin(self) -> Box<dyn Rule> {
+        self.build().unwrap_or_else(|e| panic!("invalid built-in {}", e))
+    }
+
+    /// Builds a rule whose correction is `template` expanded with the groups
+    /// of the command pattern, or of the output pattern if there's none:
+    /// `$1` or `${name}` stands for a group, and `$$` for a `$`. A reference
+    /// to a group the pattern doesn't have is an error.
+    pub fn replace_template(self, template: impl Into<String>) -> Result<Box<dyn Rule>, RuleBuildError> {
+        let template = template.into();
+        self.check_groups(&template, check_template)?;
+        self.replace_with(move |_, captures| {
+            let mut correction = String::new();
+            captures.expand(&template, &mut correction);
+            vec![correction]
+        })
+        .build()
     }
 
     /// Convenience method: builds a rule with a simple string replacement.
     /// The replacement string can reference capture groups using $1, $2, etc.
-    pub fn replace_simple(self, replacement_template: impl Into<String>) -> Result<Box<dyn Rule>, String> {
+    /// A group that didn't take part in the match stands for nothing, and
+    /// one the pattern doesn't have is an error.
+    pub fn replace_simple(self, replacement_template: impl Into<String>) -> Result<Box<dyn Rule>, RuleBuildError> {
         let template = replacement_template.into();
-        self.replace_with(move |original, captures| {
-            let mut result = template.clone();
-
-            // Replace capture group references: $1, $2, etc.
-            for i in 0..captures.len() {
-                if let Some(m) = captures.get(i) {
-                    let placeholder = format!("${}", i);
-                    result = result.replace(&placeholder, m.as_str());
-                }
-            }
+        self.check_groups(&template, check_positional)?;
+        self.replace_with(move |_, captures| vec![substitute_groups(&template, captures)]).build()
+    }
 
-            // If no replacements occurred, use original
-            if result == template {
-                vec![original.to_string()]
-            } else {
-                vec![result]
-            }
-        }).build()
+    /// Checks the groups `template` refers to with `check`, against the
+    /// command pattern or, if there's none, the output pattern: the one
+    /// captures are taken from.
+    fn check_groups(
+        &self,
+        template: &str,
+        check: fn(&str, &Regex) -> Result<(), String>,
+    ) -> Result<(), RuleBuildError> {
+        let Some(pattern) = self.command_pattern.as_ref().or(self.output_pattern.as_ref()) else {
+            return Ok(());
+        };
+        check(template, pattern).map_err(|reason| {
+            let template = template.to_string();
+            RuleBuildError::new(&self.name, RuleBuildErrorKind::InvalidTemplate { template, reason })
+        })
+    }
+}
+
+/// Checks that every `$N` in `template`, read the way [`substitute_groups`]
+/// reads it, is one of `pattern`'s groups.
+fn check_positional(template: &str, pattern: &Regex) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        rest = &rest[dollar + 1..];
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let (number, after) = rest.split_at(digits);
+        // Too many digits to parse names no group either
+        if !number.is_empty() && number.parse::<usize>().map_or(true, |index| index >= pattern.captures_len()) {
+            return Err(format!("the pattern has no group '{}'", number));
+        }
+        rest = after;
+    }
+    Ok(())
+}
+
+/// Replaces each `$N` in `template` with group `N` of `captures`, taking
+/// all the digits after the `$`, so `$10` is never read as `$1` and a `0`.
+/// A `$` without digits is kept as is.
+fn substitute_groups(template: &str, captures: &regex::Captures) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        result.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let digits = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+        match after[..digits].parse::<usize>() {
+            Ok(index) => result.push_str(captures.get(index).map_or("", |group| group.as_str())),
+            Err(_) => result.push_str(&rest[dollar..dollar + 1 + digits]),
+        }
+        rest = &after[digits..];
     }
+    result.push_str(rest);
+    result
 }
 
 /// A regex-based rule that uses pattern matching and capture groups for corrections.
 struct RegexRule {
     name: String,
-    command_pattern: Option<Regex>,
-    output_pattern: Option<Regex>,
-    replacement_fn: Box<dyn Fn(&str, &regex::Captures) -> Vec<String> + Send + Sync>,
+    leading_token: Option<String>,
+    command_pattern: Option<Cow<'static, Regex>>,
+    output_pattern: Option<Cow<'static, Regex>>,
+    replacement_fn: ReplacementFn,
+    side_effect_fn: Option<SideEffectFn>,
+    verify_fn: Option<VerifyFn>,
+    rollbacks: Vec<(usize, String)>,
+    failure_kind: Option<FailureKind>,
+    undoable: bool,
     priority: i32,
+    description: Option<String>,
+    example: Option<Example>,
+}
+
+/// Checks that every group `template` refers to, the way
+/// [`regex::Captures::expand`] reads it, is one of `pattern`'s.
+fn check_template(template: &str, pattern: &Regex) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        rest = &rest[dollar + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+            continue;
+        }
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                // Expanded as written
+                None => continue,
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        rest = after;
+        if name.is_empty() {
+            continue;
+        }
+        let known = match name.parse::<usize>() {
+            Ok(index) => index < pattern.captures_len(),
+            Err(_) => pattern.capture_names().flatten().any(|group| group == name),
+        };
+        if !known {
+            return Err(format!("the pattern has no group '{}'", name));
+        }
+    }
+    Ok(())
+}
+
+/// Gets the word every match of `pattern` starts with: a literal word right
+/// after a `^` anchor, followed by required whitespace. Patterns with a
+/// top-level alternation have none, since another branch could match.
+fn leading_word(pattern: &str) -> Option<&str> {
+    let rest = pattern.strip_prefix('^')?;
+    let end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(rest.len());
+    let (word, after) = rest.split_at(end);
+    let after = after.strip_prefix(r"\s").or_else(|| after.strip_prefix(' '))?;
+    // `\s*` or `\s?` could match no whitespace at all
+    if word.is_empty() || after.starts_with(['*', '?', '{']) || has_top_level_alternation(pattern) {
+        return None;
+    }
+    Some(word)
+}
+
+/// Returns true if `pattern` has a `|` outside any group or class.
+fn has_top_level_alternation(pattern: &str) -> bool {
+    let mut depth = 0usize;
+    let mut in_class = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => depth = depth.saturating_sub(1),
+            '|' if !in_class && depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Gets the character standing in for the escape `\<c>` in a sample.
+fn sample_escape(c: char) -> Option<char> {
+    match c {
+        's' => Some(' '),
+        'd' => Some('1'),
+        'w' | 'S' | 'D' => Some('x'),
+        'W' => Some('-'),
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        // Assertions stand for no character
+        'b' | 'B' | 'A' | 'z' => None,
+        _ => Some(c),
+    }
+}
+
+/// Skips the rest of an alternation: everything up to the `)` closing the
+/// group it's in, which is left to be read, or to the end.
+fn skip_alternatives(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    let (mut depth, mut in_class) = (0usize, false);
+    while let Some(&c) = chars.peek() {
+        match c {
+            ')' if !in_class && depth == 0 => return,
+            '\\' => {
+                chars.next();
+            }
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => depth -= 1,
+            _ => {}
+        }
+        chars.next();
+    }
+}
+
+/// Makes text that `pattern` may match, for
+/// [`Rule::sample_matching_command`]: literals are kept, a class or `.`
+/// becomes one character it allows, a quantified atom appears once and an
+/// alternation takes its first branch.
+///
+/// Counted repetitions and lookaround-like constructs aren't followed, so
+/// callers check the text against the pattern.
+fn sample_text(pattern: &str) -> String {
+    let mut text = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' | '$' | ')' | '*' | '+' | '?' => {}
+            '\\' => text.extend(chars.next().and_then(sample_escape)),
+            '.' => text.push('x'),
+            '|' => skip_alternatives(&mut chars),
+            '{' => while chars.next().is_some_and(|c| c != '}') {},
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let first = match chars.next() {
+                    Some('\\') => chars.next().and_then(sample_escape),
+                    first => first,
+                };
+                // The rest of the class, where a leading `]` is a literal
+                while chars.next().is_some_and(|c| c != ']') {}
+                text.extend(if negated { Some('x') } else { first });
+            }
+            // Named, non-capturing and flag groups are read as plain groups
+            '(' if chars.next_if_eq(&'?').is_some() => {
+                while chars.next_if(|c| c.is_ascii_alphabetic() || *c == '-').is_some() {}
+                if chars.next_if_eq(&'<').is_some() {
+                    while chars.next().is_some_and(|c| c != '>') {}
+                } else {
+                    chars.next_if(|c| *c == ':' || *c == ')');
+                }
+            }
+            '(' => {}
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+impl RegexRule {
+    /// Captures from the command pattern, or else from the output pattern.
+    fn captures<'a>(&self, command: &'a Command) -> Option<regex::Captures<'a>> {
+        if let Some(ref pattern) = self.command_pattern {
+            if let Some(captures) = pattern.captures(&command.script) {
+                return Some(captures);
+            }
+        }
+
+        // If no command pattern, try output pattern
+        self.output_pattern
+            .as_ref()
+            .and_then(|pattern| pattern.captures(&command.output))
+    }
 }
 
 impl Rule for RegexRule {
@@ -114,6 +558,12 @@ impl Rule for RegexRule {
     }
 
     fn matches(&self, command: &Command) -> bool {
+        if let Some(kind) = self.failure_kind {
+            if command.failure_kind() != kind {
+                return false;
+            }
+        }
+
         let cmd_matches = if let Some(ref pattern) = self.command_pattern {
             pattern.is_match(&command.script)
         } else {
@@ -129,25 +579,72 @@ impl Rule for RegexRule {
         cmd_matches && out_matches
     }
 
+    fn leading_token(&self) -> Option<&str> {
+        self.leading_token.as_deref()
+    }
+
     fn get_new_commands(&self, command: &Command) -> Vec<String> {
-        if let Some(ref pattern) = self.command_pattern {
-            if let Some(captures) = pattern.captures(&command.script) {
-                return (self.replacement_fn)(&command.script, &captures);
-            }
+        self.get_new_commands_cow(command).into_iter().map(Cow::into_owned).collect()
+    }
+
+    fn get_new_commands_cow<'a>(&'a self, command: &'a Command) -> Vec<Cow<'a, str>> {
+        self.captures(command)
+            .map(|captures| (self.replacement_fn)(&command.script, &captures))
+            .unwrap_or_default()
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn sample_matching_command(&self) -> Option<Command> {
+        let script = match &self.command_pattern {
+            Some(pattern) => sample_text(pattern.as_str()),
+            None => self.leading_token.as_deref().unwrap_or(SAMPLE_SCRIPT).to_string(),
+        };
+        let output = self.output_pattern.as_ref().map(|pattern| sample_text(pattern.as_str()));
+        let exit_code = self.failure_kind.map_or(1, FailureKind::exit_code);
+        let command = Command::new(script, output.unwrap_or_default(), exit_code);
+        self.matches(&command).then_some(command)
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            match_command: self.command_pattern.as_ref().map(|pattern| Pattern::regex(pattern.as_str())),
+            match_output: self.output_pattern.as_ref().map(|pattern| Pattern::regex(pattern.as_str())),
+            failure_kind: self.failure_kind,
+            description: self.description.clone(),
+            example: self.example.clone(),
+            ..RuleMetadata::of(self)
         }
+    }
 
-        // If no command pattern, try output pattern
-        if let Some(ref pattern) = self.output_pattern {
-            if let Some(captures) = pattern.captures(&command.output) {
-                return (self.replacement_fn)(&command.script, &captures);
-            }
+    fn undo_for(&self, correction: &str, cwd: &Path) -> Option<String> {
+        if self.undoable {
+            inverse_command(correction, cwd)
+        } else {
+            None
         }
+    }
 
-        Vec::new()
+    fn side_effect_for(&self, command: &Command, _correction: &str) -> Option<SideEffect> {
+        let side_effect_fn = self.side_effect_fn.as_ref()?;
+        side_effect_fn(&command.script, &self.captures(command)?)
     }
 
-    fn priority(&self) -> i32 {
-        self.priority
+    fn rollbacks_for(&self, correction: &str) -> Vec<(usize, String)> {
+        let segments = Script::split_chain(correction).len();
+        self.rollbacks
+            .iter()
+            .filter(|(segment, _)| *segment < segments)
+            .cloned()
+            .collect()
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        self.verify_fn
+            .as_ref()
+            .map_or(Verdict::Keep, |verify_fn| verify_fn(correction, context))
     }
 }
 
@@ -173,6 +670,8 @@ mod tests {
             script: "git push".to_string(),
             output: "Everything up-to-date".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
@@ -199,6 +698,8 @@ mod tests {
             script: "git push main".to_string(),
             output: "".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
@@ -206,6 +707,56 @@ mod tests {
         assert_eq!(corrections[0], "git push -u origin main");
     }
 
+    #[test]
+    fn test_replace_simple_groups() {
+        let correct = |pattern: &str, template: &str, script: &str| {
+            let rule = RegexRuleBuilder::new("test")
+                .match_command_regex(pattern)
+                .unwrap()
+                .replace_simple(template)
+                .unwrap();
+            rule.get_new_commands(&Command::new(script, "", 1))
+        };
+        let ten = r"^(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)$";
+        assert_eq!(correct(ten, "$10 $1 $1$2", "abcdefghij"), ["j a ab"]);
+        // Substituted text isn't read again
+        assert_eq!(correct(r"^echo (\S+) (\S+)$", "echo $2 $1", "echo $2 x"), ["echo x $2"]);
+        assert_eq!(correct(r"^git push( -f)?$", "git push$1 --force-with-lease", "git push"), [
+            "git push --force-with-lease"
+        ]);
+        assert_eq!(correct(r"^git push( -f)?$", "echo $ $x$", "git push"), ["echo $ $x$"]);
+        assert_eq!(correct(r"^git psuh$", "git push", "git psuh"), ["git push"]);
+    }
+
+    #[test]
+    fn test_replace_simple_rejects_missing_groups() {
+        let build = |template: &str| {
+            RegexRuleBuilder::new("deploy")
+                .match_command_regex(r"^deplyo (\S+)")
+                .unwrap()
+                .replace_simple(template)
+        };
+        assert!(build("deploy $1 $0").is_ok());
+        // The last has more digits than a group number can
+        for group in ["2", "10", "99999999999999999999"] {
+            let template = format!("deploy ${}", group);
+            let Err(error) = build(&template) else {
+                panic!("{} built", template);
+            };
+            assert_eq!(
+                error.to_string(),
+                format!("rule 'deploy': invalid replacement '{}': the pattern has no group '{}'", template, group)
+            );
+        }
+
+        // Without a command pattern, groups come from the output pattern
+        let rule = RegexRuleBuilder::new("sudo")
+            .match_output_regex(r"(Permission) denied")
+            .unwrap()
+            .replace_simple("sudo $1 $2");
+        assert!(rule.is_err());
+    }
+
     #[test]
     fn test_regex_rule_output_pattern() {
         let rule = RegexRuleBuilder::new("permission_denied")
@@ -218,6 +769,8 @@ mod tests {
             script: "apt update".to_string(),
             output: "Permission denied".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
@@ -237,18 +790,52 @@ mod tests {
             script: "git status".to_string(),
             output: "error: not a repository".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         let cmd_no_match_output = Command {
             script: "git status".to_string(),
             output: "On branch main".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd_match));
         assert!(!rule.matches(&cmd_no_match_output));
     }
 
+    #[test]
+    fn test_regex_rule_failure_kind() {
+        let rule = RegexRuleBuilder::new("not_found")
+            .match_command_regex(r"^(\S+)")
+            .unwrap()
+            .match_failure_kind(FailureKind::NotFound)
+            .replace_simple("echo $1")
+            .unwrap();
+
+        assert!(rule.matches(&Command::new("gti", "bash: gti: command not found", 127)));
+        assert!(!rule.matches(&Command::new("gti", "fatal: error", 128)));
+    }
+
+    #[test]
+    fn test_regex_rule_metadata_keeps_patterns() {
+        let rule = RegexRuleBuilder::new("not_found")
+            .match_command_regex(r"^(\S+)")
+            .unwrap()
+            .match_failure_kind(FailureKind::NotFound)
+            .describe("Echo the command")
+            .replace_simple("echo $1")
+            .unwrap();
+
+        let metadata = rule.metadata();
+        assert_eq!(metadata.match_command, Some(Pattern::regex(r"^(\S+)")));
+        assert_eq!(metadata.match_output, None);
+        assert_eq!(metadata.failure_kind, Some(FailureKind::NotFound));
+        assert_eq!(metadata.description.as_deref(), Some("Echo the command"));
+    }
+
     #[test]
     fn test_regex_rule_missing_pattern_error() {
         let result = RegexRuleBuilder::new("invalid").build();
@@ -266,4 +853,203 @@ mod tests {
 
         assert_eq!(rule.priority(), 500);
     }
+
+    #[test]
+    fn test_static_regex_matches_like_owned() {
+        let owned = RegexRuleBuilder::new("pathspec")
+            .match_command_regex(r"git checkout (\S+)")
+            .unwrap()
+            .match_output_regex(r"did not match any file")
+            .unwrap()
+            .replace_simple("git checkout -b $1")
+            .unwrap();
+        let shared = RegexRuleBuilder::new("pathspec")
+            .match_command_static(crate::static_regex!(r"git checkout (\S+)"))
+            .match_output_static(crate::static_regex!(r"did not match any file"))
+            .replace_simple("git checkout -b $1")
+            .unwrap();
+
+        let commands = [
+            Command::new("git checkout feature", "error: pathspec 'feature' did not match any file(s)", 1),
+            Command::new("git checkout feature", "Switched to branch 'feature'", 0),
+            Command::new("git status", "error: pathspec 'x' did not match any file(s)", 1),
+        ];
+        for cmd in &commands {
+            assert_eq!(owned.matches(cmd), shared.matches(cmd));
+            assert_eq!(owned.get_new_commands(cmd), shared.get_new_commands(cmd));
+        }
+        assert_eq!(shared.get_new_commands(&commands[0]), vec!["git checkout -b feature"]);
+    }
+
+    #[test]
+    fn test_static_regex_is_compiled_once() {
+        fn pattern() -> &'static Regex {
+            crate::static_regex!(r"^make\b")
+        }
+        assert!(std::ptr::eq(pattern(), pattern()));
+    }
+
+    #[test]
+    fn test_regex_rule_side_effect() {
+        let rule = RegexRuleBuilder::new("tee_parent_dirs")
+            .match_output_regex(r"tee: (\S+): No such file or directory")
+            .unwrap()
+            .replace_with(|original, _captures| vec![original.to_string()])
+            .side_effect(|_original, captures| {
+                Some(SideEffect::CreateParentDirs(captures[1].into()))
+            })
+            .build()
+            .unwrap();
+
+        let cmd = Command::new("echo hi | tee logs/out.txt", "tee: logs/out.txt: No such file or directory", 1);
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(
+            corrections[0].side_effect,
+            Some(SideEffect::CreateParentDirs("logs/out.txt".into()))
+        );
+        assert_eq!(rule.side_effect_for(&Command::new("tee x", "", 0), "tee x"), None);
+    }
+
+    #[test]
+    fn test_leading_word() {
+        assert_eq!(leading_word(r"^git\s+push"), Some("git"));
+        assert_eq!(leading_word(r"^redis-cli (\S+)"), Some("redis-cli"));
+        assert_eq!(leading_word(r"^terraform\s+(apply|plan)"), Some("terraform"));
+
+        // Not anchored, or the word could continue
+        assert_eq!(leading_word(r"git push$"), None);
+        assert_eq!(leading_word(r"^psql\b"), None);
+        assert_eq!(leading_word(r"^git\s*push"), None);
+        assert_eq!(leading_word(r"^gits?\s"), None);
+        assert_eq!(leading_word(r"(?i)^git\s"), None);
+        // Another branch could match anything
+        assert_eq!(leading_word(r"^git\s|hub\s"), None);
+        assert_eq!(leading_word(r"^git\s[|]"), Some("git"));
+    }
+
+    #[test]
+    fn test_leading_token_from_command_pattern() {
+        let rule = RegexRuleBuilder::new("git_push")
+            .match_command_regex(r"^git\s+push")
+            .unwrap()
+            .replace_simple("git push -u origin main")
+            .unwrap();
+        assert_eq!(rule.leading_token(), Some("git"));
+
+        let rule = RegexRuleBuilder::new("any")
+            .match_output_regex("error")
+            .unwrap()
+            .replace_simple("retry")
+            .unwrap();
+        assert_eq!(rule.leading_token(), None);
+    }
+
+    #[test]
+    fn test_bounded_pattern_within_limits() {
+        let rule = RegexRuleBuilder::new("custom_push")
+            .match_command_bounded(r"^git\s+push\b", &PatternLimits::default())
+            .unwrap()
+            .match_output_bounded(r"has no upstream branch", &PatternLimits::default())
+            .unwrap()
+            .replace_simple("git push --set-upstream origin HEAD")
+            .unwrap();
+
+        assert!(rule.matches(&Command::new("git push", "fatal: The current branch has no upstream branch.", 128)));
+    }
+
+    #[test]
+    fn test_enormous_pattern_is_rejected() {
+        // Small to write down, but a huge program once the repetition is unrolled
+        let words: Vec<String> = (0..200).map(|i| format!("word{}", i)).collect();
+        let pattern = format!("(?:{}){{100}}", words.join("|"));
+        assert!(pattern.len() < PatternLimits::default().max_pattern_len);
+
+        let error = RegexRuleBuilder::new("custom_huge")
+            .match_output_bounded(&pattern, &PatternLimits::default())
+            .err()
+            .unwrap();
+        assert!(matches!(error.kind, RuleBuildErrorKind::InvalidRegex { field: "output", .. }));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "rule 'custom_huge': output pattern compiles to more than {} bytes",
+                DEFAULT_MAX_COMPILED_PATTERN_BYTES
+            )
+        );
+    }
+
+    #[test]
+    fn test_long_and_invalid_patterns_are_rejected() {
+        let limits = PatternLimits {
+            max_pattern_len: 16,
+            ..PatternLimits::default()
+        };
+        let error = limits.compile("custom_long", "command", &"a".repeat(17)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "rule 'custom_long': command pattern is 17 bytes long, over the limit of 16"
+        );
+        assert!(matches!(error.kind, RuleBuildErrorKind::PatternTooLong { len: 17, limit: 16, .. }));
+
+        let error = limits.compile("custom_invalid", "command", "(").unwrap_err();
+        assert!(error.to_string().starts_with("rule 'custom_invalid': invalid command pattern:"));
+    }
+
+    #[test]
+    fn test_example_failure_is_corrected_by_the_rule() {
+        let rule = RegexRuleBuilder::new("git_push_set_upstream")
+            .match_output_regex(r"git push --set-upstream origin (\S+)")
+            .unwrap()
+            .example_failure("git push", "fatal: no upstream\n\n    git push --set-upstream origin main\n")
+            .replace_with(|_, captures| vec![format!("git push -u origin {}", &captures[1])])
+            .build()
+            .unwrap();
+        let example = rule.metadata().example.unwrap();
+        assert_eq!((example.exit_code, example.correction.as_str()), (1, "git push -u origin main"));
+
+        // A failure the rule doesn't correct is kept, for the test of every
+        // built-in example to point out
+        let rule = RegexRuleBuilder::new("git_push_set_upstream")
+            .match_output_regex(r"no upstream (\S+)")
+            .unwrap()
+            .example_failure("git push", "everything up to date")
+            .replace_simple("git push -u origin $1")
+            .unwrap();
+        assert_eq!(rule.metadata().example.unwrap().correction, "");
+    }
+
+    #[test]
+    fn test_sample_text() {
+        assert_eq!(sample_text(r"^git push$"), "git push");
+        assert_eq!(sample_text(r"\bnpm\s+i(nstall)?\b"), "npm install");
+        assert_eq!(sample_text(r"port (\d+) is (?:already )?in use"), "port 1 is already in use");
+        assert_eq!(sample_text(r"(?i)no (such|matching) file"), "no such file");
+        assert_eq!(sample_text(r"(?P<name>[a-z]+)=[^ ]*"), "a=x");
+        assert_eq!(sample_text(r"pip|pip3 install"), "pip");
+        assert_eq!(sample_text(r"x{2,3}\.y"), "x.y");
+    }
+
+    #[test]
+    fn test_sample_matching_command() {
+        let rule = RegexRuleBuilder::new("port_in_use")
+            .match_command_regex(r"^(npm|yarn) start")
+            .unwrap()
+            .match_output_regex(r"port (\d+) is already in use")
+            .unwrap()
+            .replace_with(|script, captures| vec![format!("PORT={} {}", &captures[1], script)])
+            .build()
+            .unwrap();
+        let sample = rule.sample_matching_command().unwrap();
+        assert_eq!(sample.script, "npm start");
+        assert_eq!(sample.output, "port 1 is already in use");
+        assert!(rule.matches(&sample));
+
+        // A counted repetition isn't followed, so no sample matches
+        let rule = RegexRuleBuilder::new("counted")
+            .match_command_regex(r"^a{3}$")
+            .unwrap()
+            .replace_simple("b")
+            .unwrap();
+        assert!(rule.sample_matching_command().is_none());
+    }
 }
@@ -1,14 +1,33 @@
-/// Simple rule builder for rules with basic string matching and replacement.
+//! Simple rule builder for rules with basic string matching and replacement.
 
-use crate::{Command, Rule};
+use super::{derive_example, SAMPLE_SCRIPT};
+use crate::rules::{Example, RuleMetadata};
+use crate::undo::inverse_command;
+use crate::{Command, FailureKind, Rule, Script};
+use std::borrow::Cow;
+use std::path::Path;
+
+/// How a simple rule builds its correction from the failed command.
+enum Correction {
+    /// Plain substring replacement on the raw script.
+    Replace(String, String),
+    /// Insert a token at the start of the script (e.g. `sudo`).
+    PrependToken(String),
+    /// Insert a flag directly after an executable or subcommand token.
+    InsertFlag { executable: String, flag: String },
+}
 
 /// A simple rule builder for string-based matching and replacement.
 pub struct SimpleRuleBuilder {
     name: String,
     match_cmd: Option<String>,
     match_out: Option<String>,
-    replacement: Option<(String, String)>,
+    failure_kind: Option<FailureKind>,
+    undoable: bool,
     priority: i32,
+    description: Option<String>,
+    example: Option<Example>,
+    example_failure: Option<(String, String)>,
 }
 
 impl SimpleRuleBuilder {
@@ -18,8 +37,12 @@ impl SimpleRuleBuilder {
             name: name.into(),
             match_cmd: None,
             match_out: None,
-            replacement: None,
+            failure_kind: None,
+            undoable: false,
             priority: 1000,
+            description: None,
+            example: None,
+            example_failure: None,
         }
     }
 
@@ -35,22 +58,78 @@ impl SimpleRuleBuilder {
         self
     }
 
+    /// Only matches commands that failed with the given kind.
+    pub fn match_failure_kind(mut self, kind: FailureKind) -> Self {
+        self.failure_kind = Some(kind);
+        self
+    }
+
+    /// Declares that executed corrections can be reverted with `undo::inverse_command`.
+    pub fn undoable(mut self) -> Self {
+        self.undoable = true;
+        self
+    }
+
     /// Sets the priority (lower = higher priority).
     pub fn priority(mut self, p: i32) -> Self {
         self.priority = p;
         self
     }
 
+    /// Describes what the rule corrects, for `ftf rules export`.
+    pub fn describe(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Gives a failure the rule corrects, for `ftf rules export`.
+    pub fn example(mut self, example: Example) -> Self {
+        self.example = Some(example);
+        self
+    }
+
+    /// Gives a failure the rule corrects, for `ftf rules show`: `script`
+    /// printing `output`. Its exit code comes from the failure kind matched
+    /// and its correction from the rule, once built.
+    pub fn example_failure(mut self, script: impl Into<String>, output: impl Into<String>) -> Self {
+        self.example_failure = Some((script.into(), output.into()));
+        self
+    }
+
     /// Builds a simple rule with a fixed string replacement.
-    pub fn replace(mut self, old: impl Into<String>, new: impl Into<String>) -> Box<dyn Rule> {
-        self.replacement = Some((old.into(), new.into()));
-        Box::new(SimpleRule {
+    pub fn replace(self, old: impl Into<String>, new: impl Into<String>) -> Box<dyn Rule> {
+        self.build(Correction::Replace(old.into(), new.into()))
+    }
+
+    /// Builds a simple rule that prepends a token to the script (e.g. `sudo`).
+    pub fn prepend(self, token: impl Into<String>) -> Box<dyn Rule> {
+        self.build(Correction::PrependToken(token.into()))
+    }
+
+    /// Builds a simple rule that inserts `flag` after the `executable` token.
+    pub fn insert_flag(self, executable: impl Into<String>, flag: impl Into<String>) -> Box<dyn Rule> {
+        self.build(Correction::InsertFlag {
+            executable: executable.into(),
+            flag: flag.into(),
+        })
+    }
+
+    fn build(self, correction: Correction) -> Box<dyn Rule> {
+        let mut rule = SimpleRule {
             name: self.name,
             match_cmd: self.match_cmd,
             match_out: self.match_out,
-            replacement: self.replacement,
+            failure_kind: self.failure_kind,
+            undoable: self.undoable,
+            correction,
             priority: self.priority,
-        })
+            description: self.description,
+            example: self.example,
+        };
+        if let Some(failure) = self.example_failure {
+            rule.example = Some(derive_example(&rule, failure, rule.failure_kind));
+        }
+        Box::new(rule)
     }
 }
 
@@ -59,8 +138,12 @@ struct SimpleRule {
     name: String,
     match_cmd: Option<String>,
     match_out: Option<String>,
-    replacement: Option<(String, String)>,
+    failure_kind: Option<FailureKind>,
+    correction: Correction,
+    undoable: bool,
     priority: i32,
+    description: Option<String>,
+    example: Option<Example>,
 }
 
 impl Rule for SimpleRule {
@@ -81,20 +164,76 @@ impl Rule for SimpleRule {
             .map(|p| command.output.contains(p))
             .unwrap_or(true);
 
-        cmd_match && out_match
+        let kind_match = self
+            .failure_kind
+            .map(|kind| command.failure_kind() == kind)
+            .unwrap_or(true);
+
+        cmd_match && out_match && kind_match
+    }
+
+    fn required_script(&self) -> Option<&str> {
+        self.match_cmd.as_deref()
+    }
+
+    fn required_output(&self) -> Option<&str> {
+        self.match_out.as_deref()
     }
 
     fn get_new_commands(&self, command: &Command) -> Vec<String> {
-        if let Some((old, new)) = &self.replacement {
-            vec![command.script.replace(old, new)]
-        } else {
+        self.get_new_commands_cow(command).into_iter().map(Cow::into_owned).collect()
+    }
+
+    fn get_new_commands_cow<'a>(&'a self, command: &'a Command) -> Vec<Cow<'a, str>> {
+        let corrected = match &self.correction {
+            // Replacing nothing would put `new` between every character, and
+            // a script without `old` would come back unchanged
+            Correction::Replace(old, _) if old.is_empty() || !command.script.contains(old.as_str()) => return vec![],
+            Correction::Replace(old, new) => command.script.replace(old, new),
+            Correction::PrependToken(token) => {
+                Script::parse(&command.script).prepend_token(token).to_string()
+            }
+            Correction::InsertFlag { executable, flag } => Script::parse(&command.script)
+                .insert_flag_after(executable, flag)
+                .to_string(),
+        };
+
+        if corrected == command.script {
             vec![]
+        } else {
+            vec![Cow::Owned(corrected)]
         }
     }
 
     fn priority(&self) -> i32 {
         self.priority
     }
+
+    fn sample_matching_command(&self) -> Option<Command> {
+        let command = Command::new(
+            self.match_cmd.as_deref().unwrap_or(SAMPLE_SCRIPT),
+            self.match_out.as_deref().unwrap_or_default(),
+            self.failure_kind.map_or(1, FailureKind::exit_code),
+        );
+        self.matches(&command).then_some(command)
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            failure_kind: self.failure_kind,
+            description: self.description.clone(),
+            example: self.example.clone(),
+            ..RuleMetadata::of(self)
+        }
+    }
+
+    fn undo_for(&self, correction: &str, cwd: &Path) -> Option<String> {
+        if self.undoable {
+            inverse_command(correction, cwd)
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +255,40 @@ mod tests {
         assert_eq!(corrections[0], "git branch -D feature");
     }
 
+    #[test]
+    fn test_sample_matching_command() {
+        let rule = SimpleRuleBuilder::new("test_rule")
+            .match_command("git branch -d")
+            .match_output("If you are sure")
+            .replace("-d", "-D");
+        let sample = rule.sample_matching_command().unwrap();
+        assert_eq!(sample.script, "git branch -d");
+        assert_eq!(sample.output, "If you are sure");
+        assert_eq!(rule.get_new_commands(&sample), vec!["git branch -D"]);
+
+        let rule = SimpleRuleBuilder::new("test_rule").match_output("denied").prepend("sudo");
+        assert_eq!(rule.sample_matching_command().unwrap().script, SAMPLE_SCRIPT);
+    }
+
+    #[test]
+    fn test_example_failure_is_corrected_by_the_rule() {
+        let rule = SimpleRuleBuilder::new("test_rule")
+            .example_failure("./run.sh", "bash: ./run.sh: Permission denied")
+            .match_output("Permission denied")
+            .match_failure_kind(FailureKind::NotExecutable)
+            .prepend("sudo");
+        let example = rule.metadata().example.unwrap();
+        assert_eq!(example, Example::new("./run.sh", "bash: ./run.sh: Permission denied", 126, "sudo ./run.sh"));
+    }
+
+    #[test]
+    fn test_empty_replaced_string_corrects_nothing() {
+        let rule = SimpleRuleBuilder::new("test").match_command("git").replace("", "x");
+        let cmd = Command::new("git status", "", 1);
+        assert!(rule.matches(&cmd));
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
     #[test]
     fn test_simple_rule_builder_multiple_replacements() {
         let rule = SimpleRuleBuilder::new("test_rule")
@@ -138,4 +311,51 @@ mod tests {
 
         assert_eq!(rule.priority(), 500);
     }
+
+    #[test]
+    fn test_simple_rule_failure_kind() {
+        let rule = SimpleRuleBuilder::new("test")
+            .match_failure_kind(FailureKind::Killed)
+            .replace("make -j16", "make -j2");
+
+        assert!(rule.matches(&Command::new("make -j16", "Killed", 137)));
+        assert!(!rule.matches(&Command::new("make -j16", "error", 1)));
+    }
+
+    #[test]
+    fn test_simple_rule_prepend() {
+        let rule = SimpleRuleBuilder::new("test")
+            .match_output("Permission denied")
+            .prepend("sudo");
+
+        let cmd = Command::new("cat '/root/my file'", "Permission denied", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo cat '/root/my file'"]);
+    }
+
+    #[test]
+    fn test_simple_rule_insert_flag() {
+        let rule = SimpleRuleBuilder::new("test")
+            .match_output("Is a directory")
+            .insert_flag("rm", "-r");
+
+        let cmd = Command::new("rm \"old stuff\"", "Is a directory", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["rm -r \"old stuff\""]);
+    }
+
+    #[test]
+    fn test_simple_rule_undoable() {
+        let rule = SimpleRuleBuilder::new("test").undoable().replace("mv", "mv");
+        assert_eq!(rule.undo_for("mv a b", Path::new("/nonexistent")).as_deref(), Some("mv b a"));
+
+        let rule = SimpleRuleBuilder::new("test").replace("mv", "mv");
+        assert_eq!(rule.undo_for("mv a b", Path::new("/nonexistent")), None);
+    }
+
+    #[test]
+    fn test_simple_rule_no_op_correction_is_dropped() {
+        let rule = SimpleRuleBuilder::new("test").insert_flag("rm", "-r");
+
+        let cmd = Command::new("rm -r dir", "error", 1);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
 }
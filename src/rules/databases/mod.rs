@@ -0,0 +1,361 @@
+//! psql, mysql and redis-cli command correction rules.
+//!
+//! This module contains rules for common database client mistakes:
+//! - Connecting as a role or user that doesn't exist or needs a password
+//! - Connecting over a socket when the server only listens on TCP
+//! - Connecting to a database that hasn't been created yet
+//! - Connecting to a redis server that isn't running
+
+use super::{Example, RuleInfo};
+use super::util::prefix_then_retry;
+use crate::privilege;
+use crate::{static_regex, RegexRuleBuilder, Rule, Script};
+
+/// psql options whose value is reused when creating a database.
+const PSQL_CONNECTION_OPTIONS: &[&str] = &["-U", "--username", "-h", "--host", "-p", "--port"];
+
+/// mysql options whose value is reused when creating a database.
+const MYSQL_CONNECTION_OPTIONS: &[&str] = &["-u", "--user", "-h", "--host", "-P", "--port"];
+
+/// Executables every database rule requires.
+pub const EXECUTABLES: &[&str] = &["psql", "mysql", "redis-cli"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("psql_role_missing", 300),
+    ("psql_socket_missing", 300),
+    ("mysql_access_denied", 300),
+    ("database_missing", 300),
+    ("redis_connection_refused", 400),
+];
+
+/// Creates all database client rules.
+pub fn database_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // psql_role_missing: Connect as postgres instead
+        create_psql_role_missing(),
+        // psql_socket_missing: Connect over TCP to localhost
+        create_psql_socket_missing(),
+        // mysql_access_denied: Prompt for a password, or use sudo
+        create_mysql_access_denied(),
+        // database_missing: Create the database before connecting
+        create_database_missing(),
+        // redis_connection_refused: Start redis before connecting
+        create_redis_connection_refused(),
+    ]
+}
+
+/// Collects the connection options (and their values) from a client script,
+/// so a helper command connects to the same server as the same user.
+fn connection_args(script: &Script, options: &[&str]) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut tokens = script.tokens().iter().skip(1);
+    while let Some(token) = tokens.next() {
+        if options.contains(&token.as_str()) {
+            if let Some(value) = tokens.next() {
+                args.extend([token.clone(), value.clone()]);
+            }
+        } else if options
+            .iter()
+            .any(|option| option.starts_with("--") && token.starts_with(&format!("{}=", option)))
+        {
+            args.push(token.clone());
+        }
+    }
+    args
+}
+
+/// psql_role_missing: `psql` → `psql -U postgres` or `sudo -u postgres psql`
+fn create_psql_role_missing() -> Box<dyn Rule> {
+    // The role comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("psql_role_missing")
+        .match_output_static(static_regex!(r#"FATAL:\s+role "([^"]+)" does not exist"#))
+        .priority(300)
+        .describe("Connect as postgres when your role doesn't exist")
+        .example(Example::new(
+            "psql shop",
+            "psql: error: FATAL:  role \"root\" does not exist",
+            2,
+            "psql -U postgres shop",
+        ))
+        .replace_with(|original, captures| {
+            let script = Script::parse(original);
+            if script.executable() != Some("psql") {
+                return vec![];
+            }
+            // Already connecting as postgres; nothing to switch to
+            if captures.get(1).map(|m| m.as_str()) == Some("postgres") {
+                return vec![];
+            }
+
+            let mut corrections = Vec::new();
+            let has_user = script
+                .tokens()
+                .iter()
+                .any(|token| token == "-U" || token.starts_with("--username"));
+            if !has_user {
+                let mut tokens = script.tokens().to_vec();
+                tokens.splice(1..1, ["-U".to_string(), "postgres".to_string()]);
+                corrections.push(Script::from_tokens(tokens).to_string());
+            }
+            corrections.push(privilege::escalate(script.prepend_token("postgres").prepend_token("-u")).to_string());
+            corrections
+        })
+        .build_builtin()
+}
+
+/// psql_socket_missing: `psql shop` → `psql -h localhost shop`
+fn create_psql_socket_missing() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("psql_socket_missing")
+        .match_command_static(static_regex!(r"^psql\b"))
+        .match_output_static(static_regex!(r#"(could not connect to server|connection to server on socket "[^"]+" failed): No such file or directory"#))
+        .priority(300)
+        .describe("Connect over TCP when the server has no unix socket")
+        .example(Example::new(
+            "psql shop",
+            "psql: could not connect to server: No such file or directory",
+            2,
+            "psql -h localhost shop",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            if script.tokens().iter().any(|token| token == "-h" || token.starts_with("--host")) {
+                return vec![];
+            }
+            let mut tokens = script.tokens().to_vec();
+            tokens.splice(1..1, ["-h".to_string(), "localhost".to_string()]);
+            vec![Script::from_tokens(tokens).to_string()]
+        })
+        .build_builtin()
+}
+
+/// mysql_access_denied: `mysql -u app shop` → `mysql -u app -p shop`, or
+/// `sudo mysql` for root over the local socket
+fn create_mysql_access_denied() -> Box<dyn Rule> {
+    // The user comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("mysql_access_denied")
+        .match_output_static(static_regex!(r"ERROR 1045 \(28000\): Access denied for user '([^']*)'@'[^']*' \(using password: (YES|NO)\)"))
+        .priority(300)
+        .describe("Prompt for a password, or use sudo for root")
+        .example(Example::new(
+            "mysql -u app shop",
+            "ERROR 1045 (28000): Access denied for user 'app'@'localhost' (using password: NO)",
+            1,
+            "mysql -p -u app shop",
+        ))
+        .replace_with(|original, captures| {
+            let script = Script::parse(original);
+            if script.executable() != Some("mysql") {
+                return vec![];
+            }
+            let user = captures.get(1).map(|m| m.as_str()).unwrap_or("");
+            let used_password = captures.get(2).map(|m| m.as_str()) == Some("YES");
+
+            let mut corrections = Vec::new();
+            if !used_password {
+                corrections.push(script.clone().insert_flag_after("mysql", "-p").to_string());
+            }
+            // Root authenticates through the unix socket on most distributions
+            if user == "root" {
+                corrections.push(privilege::escalate(script).to_string());
+            }
+            corrections
+        })
+        .build_builtin()
+}
+
+/// database_missing: `psql shop` → `createdb shop && psql shop`;
+/// `mysql shop` → `mysqladmin create shop && mysql shop` (mysql has no
+/// `createdb`)
+fn create_database_missing() -> Box<dyn Rule> {
+    // The database comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("database_missing")
+        .match_output_static(static_regex!(r#"FATAL:\s+database "([^"]+)" does not exist|ERROR 1049 \(42000\): Unknown database '([^']+)'"#))
+        .priority(300)
+        .describe("Create a missing database before connecting to it")
+        .example(Example::new(
+            "psql shop",
+            "psql: error: FATAL:  database \"shop\" does not exist",
+            2,
+            "createdb shop && psql shop",
+        ))
+        .replace_with(|original, captures| {
+            let database = captures.get(1).or_else(|| captures.get(2));
+            let Some(database) = database.map(|m| m.as_str()) else {
+                return vec![];
+            };
+
+            let script = Script::parse(original);
+            let create = match script.executable() {
+                Some("psql") => {
+                    let mut tokens = vec!["createdb".to_string()];
+                    tokens.extend(connection_args(&script, PSQL_CONNECTION_OPTIONS));
+                    tokens.push(database.to_string());
+                    tokens
+                }
+                Some("mysql") => {
+                    let mut tokens = vec!["mysqladmin".to_string()];
+                    tokens.extend(connection_args(&script, MYSQL_CONNECTION_OPTIONS));
+                    let prompts_password = script
+                        .tokens()
+                        .iter()
+                        .any(|token| token.starts_with("-p") || token.starts_with("--password"));
+                    if prompts_password {
+                        tokens.push("-p".to_string());
+                    }
+                    tokens.extend(["create".to_string(), database.to_string()]);
+                    tokens
+                }
+                _ => return vec![],
+            };
+            vec![prefix_then_retry(&Script::from_tokens(create).to_string(), original)]
+        })
+        .build_builtin()
+}
+
+/// redis_connection_refused: `redis-cli ping` → `sudo systemctl start redis && redis-cli ping`
+fn create_redis_connection_refused() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("redis_connection_refused")
+        .match_command_static(static_regex!(r"^redis-cli\b"))
+        .match_output_static(static_regex!(r"Could not connect to Redis at (127\.0\.0\.1|localhost|::1):\d+: Connection refused"))
+        .priority(400)
+        .describe("Start redis before connecting to it")
+        .example(Example::new(
+            "redis-cli ping",
+            "Could not connect to Redis at 127.0.0.1:6379: Connection refused",
+            1,
+            "sudo systemctl start redis && redis-cli ping",
+        ))
+        .replace_with(|original, _captures| {
+            vec![
+                prefix_then_retry(&privilege::escalate(Script::parse("systemctl start redis")).to_string(), original),
+                prefix_then_retry("redis-server --daemonize yes", original),
+            ]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Command;
+
+    const PSQL_ROLE_MISSING: &str = "psql: error: connection to server on socket \"/var/run/postgresql/.s.PGSQL.5432\" failed: FATAL:  role \"root\" does not exist\n";
+
+    const PSQL_NO_SOCKET: &str = "psql: error: connection to server on socket \"/var/run/postgresql/.s.PGSQL.5432\" failed: No such file or directory\n\tIs the server running locally and accepting connections on that socket?\n";
+
+    const PSQL_NO_SOCKET_LEGACY: &str = "psql: could not connect to server: No such file or directory\n\tIs the server running locally and accepting\n\tconnections on Unix domain socket \"/tmp/.s.PGSQL.5432\"?\n";
+
+    const PSQL_DATABASE_MISSING: &str = "psql: error: connection to server at \"localhost\" (127.0.0.1), port 5432 failed: FATAL:  database \"shop\" does not exist\n";
+
+    const MYSQL_ACCESS_DENIED_NO_PASSWORD: &str = "ERROR 1045 (28000): Access denied for user 'app'@'localhost' (using password: NO)\n";
+
+    const MYSQL_ACCESS_DENIED_ROOT: &str = "ERROR 1045 (28000): Access denied for user 'root'@'localhost' (using password: YES)\n";
+
+    const MYSQL_UNKNOWN_DATABASE: &str = "ERROR 1049 (42000): Unknown database 'shop'\n";
+
+    const REDIS_REFUSED: &str = "Could not connect to Redis at 127.0.0.1:6379: Connection refused\nnot connected> ";
+
+    #[test]
+    fn test_psql_role_missing() {
+        let rule = create_psql_role_missing();
+        let cmd = Command::new("psql shop", PSQL_ROLE_MISSING, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["psql -U postgres shop", "sudo -u postgres psql shop"]
+        );
+    }
+
+    #[test]
+    fn test_psql_socket_missing() {
+        let rule = create_psql_socket_missing();
+
+        let cmd = Command::new("psql -U app shop", PSQL_NO_SOCKET, 2);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["psql -h localhost -U app shop"]);
+
+        let cmd = Command::new("psql shop", PSQL_NO_SOCKET_LEGACY, 2);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["psql -h localhost shop"]);
+    }
+
+    #[test]
+    fn test_mysql_access_denied_without_password() {
+        let rule = create_mysql_access_denied();
+        let cmd = Command::new("mysql -u app shop", MYSQL_ACCESS_DENIED_NO_PASSWORD, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["mysql -p -u app shop"]);
+    }
+
+    #[test]
+    fn test_mysql_access_denied_root() {
+        let rule = create_mysql_access_denied();
+        let cmd = Command::new("mysql -u root -p", MYSQL_ACCESS_DENIED_ROOT, 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo mysql -u root -p"]);
+    }
+
+    #[test]
+    fn test_psql_database_missing() {
+        let rule = create_database_missing();
+        let cmd = Command::new("psql -h localhost -U app shop", PSQL_DATABASE_MISSING, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["createdb -h localhost -U app shop && psql -h localhost -U app shop"]
+        );
+    }
+
+    #[test]
+    fn test_mysql_database_missing() {
+        let rule = create_database_missing();
+        let cmd = Command::new("mysql -u app -p shop", MYSQL_UNKNOWN_DATABASE, 1);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["mysqladmin -u app -p create shop && mysql -u app -p shop"]
+        );
+    }
+
+    #[test]
+    fn test_redis_connection_refused() {
+        let rule = create_redis_connection_refused();
+        let cmd = Command::new("redis-cli ping", REDIS_REFUSED, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "sudo systemctl start redis && redis-cli ping",
+                "redis-server --daemonize yes && redis-cli ping",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_database_rules_ignore_other_clients() {
+        let cmd = Command::new("pg_dump shop", PSQL_DATABASE_MISSING, 1);
+        for rule in database_rules() {
+            assert!(rule.get_new_commands(&cmd).is_empty(), "{} should not correct", rule.name());
+        }
+    }
+
+    #[test]
+    fn test_database_rules_exist() {
+        let rules = database_rules();
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "psql_role_missing",
+                "psql_socket_missing",
+                "mysql_access_denied",
+                "database_missing",
+                "redis_connection_refused",
+            ]
+        );
+    }
+}
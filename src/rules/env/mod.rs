@@ -0,0 +1,157 @@
+//! Environment variable rules.
+//!
+//! This module contains rules for commands that fail because an environment
+//! variable is unset. The variable name is lifted from the common phrasings:
+//! - "environment variable 'EDITOR' is not set"
+//! - "The EDITOR environment variable is not set"
+//! - Python's `KeyError: 'DATABASE_URL'` from `os.environ[...]`
+//! - "missing required env var AWS_PROFILE"
+//!
+//! The correction assigns the variable for a single run. Values come from
+//! `[env_defaults]` in the config; anything else gets a placeholder, which
+//! always needs confirmation.
+
+use super::{Example, RuleInfo};
+use crate::config::VALUE_PLACEHOLDER;
+use crate::{static_regex, RegexRuleBuilder, Rule, Script};
+use std::collections::HashMap;
+
+/// Phrasings that name an unset variable, one capture group per phrasing.
+const UNSET_VARIABLE: &str = concat!(
+    r#"(?i:environment variable|env var)\s+['"`]?([A-Z][A-Z0-9_]*)['"`]?\s+(?i:is\s+)?(?i:not set|unset|undefined)"#,
+    r#"|\b([A-Z][A-Z0-9_]*) (?i:environment variable is not set)"#,
+    r#"|KeyError: '([A-Z][A-Z0-9_]*)'"#,
+    r#"|(?i:missing required env(?:ironment)? var(?:iable)?):?\s+['"`]?([A-Z][A-Z0-9_]*)"#,
+);
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("env_var_missing", 400),
+];
+
+/// Creates all environment variable rules.
+///
+/// `defaults` maps variable names to the value suggested for them.
+pub fn env_rules(defaults: HashMap<String, String>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // env_var_missing: Set the missing variable for this run
+        create_env_var_missing(defaults),
+    ]
+}
+
+/// env_var_missing: `git commit` → `EDITOR=vim git commit`, or
+/// `DATABASE_URL=<value> ./manage.py migrate` without a default
+fn create_env_var_missing(defaults: HashMap<String, String>) -> Box<dyn Rule> {
+    // The variable comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("env_var_missing")
+        .match_output_static(static_regex!(UNSET_VARIABLE))
+        .priority(400)
+        .describe("Set an unset environment variable for one run, from `[env_defaults]` or a placeholder")
+        .example(Example::new(
+            "./release.sh",
+            "fatal: The GITHUB_TOKEN environment variable is not set.",
+            1,
+            "GITHUB_TOKEN=<value> ./release.sh",
+        ))
+        .replace_with(move |original, captures| {
+            let Some(name) = captures.iter().skip(1).flatten().next().map(|m| m.as_str()) else {
+                return vec![];
+            };
+            // Already set on the command line, so the value itself is wrong
+            if original.starts_with(&format!("{}=", name)) {
+                return vec![];
+            }
+
+            let value = match defaults.get(name) {
+                Some(value) => Script::from_tokens([value.as_str()]).to_string(),
+                None => VALUE_PLACEHOLDER.to_string(),
+            };
+            vec![format!("{}={} {}", name, value, original)]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, Config};
+
+    const EDITOR_UNSET: &str = "error: environment variable 'EDITOR' is not set\n";
+
+    const DJANGO_KEY_ERROR: &str = "Traceback (most recent call last):\n  File \"/app/manage.py\", line 22, in <module>\n    main()\n  File \"/app/config/settings.py\", line 14, in <module>\n    DATABASES = {\"default\": dj_database_url.parse(os.environ[\"DATABASE_URL\"])}\n  File \"/usr/lib/python3.12/os.py\", line 714, in __getitem__\n    raise KeyError(key) from None\nKeyError: 'DATABASE_URL'\n";
+
+    const MISSING_ENV_VAR: &str = "Error: missing required env var AWS_PROFILE\n";
+
+    const NAMED_FIRST: &str = "fatal: The GITHUB_TOKEN environment variable is not set.\n";
+
+    fn rule_with(defaults: &[(&str, &str)]) -> Box<dyn Rule> {
+        create_env_var_missing(
+            defaults
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_env_var_from_config_default() {
+        let config: Config = toml::from_str("[env_defaults]\nEDITOR = \"vim\"\n").unwrap();
+        let rule = env_rules(config.env_defaults).remove(0);
+        let cmd = Command::new("git commit", EDITOR_UNSET, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["EDITOR=vim git commit"]);
+    }
+
+    #[test]
+    fn test_env_var_default_is_quoted() {
+        let rule = rule_with(&[("EDITOR", "code --wait")]);
+        let cmd = Command::new("git commit", EDITOR_UNSET, 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["EDITOR='code --wait' git commit"]);
+    }
+
+    #[test]
+    fn test_env_var_placeholder_needs_confirmation() {
+        let rule = rule_with(&[]);
+        let cmd = Command::new("./manage.py migrate", DJANGO_KEY_ERROR, 1);
+
+        let corrections = rule.get_new_commands(&cmd);
+        assert_eq!(corrections, vec!["DATABASE_URL=<value> ./manage.py migrate"]);
+        assert!(Config::default().requires_confirmation(&corrections[0]));
+    }
+
+    #[test]
+    fn test_env_var_phrasings() {
+        let rule = rule_with(&[]);
+
+        let cmd = Command::new("terraform plan", MISSING_ENV_VAR, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["AWS_PROFILE=<value> terraform plan"]);
+
+        let cmd = Command::new("./release.sh", NAMED_FIRST, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["GITHUB_TOKEN=<value> ./release.sh"]);
+    }
+
+    #[test]
+    fn test_env_var_ignores_other_key_errors() {
+        let rule = rule_with(&[]);
+        let cmd = Command::new("python report.py", "KeyError: 'total'\n", 1);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_env_var_already_assigned() {
+        let rule = rule_with(&[("EDITOR", "vim")]);
+        let cmd = Command::new("EDITOR=vim git commit", EDITOR_UNSET, 1);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_env_rules_exist() {
+        let rules = env_rules(HashMap::new());
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["env_var_missing"]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Lazily constructed rule groups.
+//!
+//! Building every rule compiles a few hundred regexes, most of which can't
+//! match the command being corrected: a `git push` never needs the apt or
+//! psql rules. Groups whose rules all require a specific executable are
+//! registered as a [`RuleFactory`] keyed by those executables, and only built
+//! when the command runs one of them.
+
+use super::adapter::{adapt_all, Adapt};
+use crate::{privilege, Rule, Script};
+
+/// Wrappers that run the command given as their arguments.
+const WRAPPERS: &[&str] = &["env", "time", "nice", "nohup", "command", "exec"];
+
+/// A rule's name and base priority, known without building it.
+pub type RuleInfo = (&'static str, i32);
+
+/// Builds a group of rules on demand.
+pub struct RuleFactory<'a> {
+    category: &'static str,
+    rules: &'static [RuleInfo],
+    executables: &'static [&'static str],
+    build: Box<dyn FnOnce() -> Vec<Box<dyn Rule>> + 'a>,
+}
+
+impl<'a> RuleFactory<'a> {
+    /// Creates a factory for a group that could match any command, such as
+    /// one with rules that only look at the output.
+    ///
+    /// `rules` must list what `build` returns, in order.
+    pub fn new(
+        category: &'static str,
+        rules: &'static [RuleInfo],
+        build: impl FnOnce() -> Vec<Box<dyn Rule>> + 'a,
+    ) -> Self {
+        Self {
+            category,
+            rules,
+            executables: &[],
+            build: Box::new(build),
+        }
+    }
+
+    /// Restricts the group to commands that run one of `executables`.
+    ///
+    /// Every rule of the group must require one of them, or it would silently
+    /// stop matching.
+    pub fn for_executables(mut self, executables: &'static [&'static str]) -> Self {
+        self.executables = executables;
+        self
+    }
+
+    /// Passes the group's rules through `adapt` once they're built, e.g. to
+    /// wrap each one.
+    pub fn map_rules(mut self, adapt: impl FnOnce(Vec<Box<dyn Rule>>) -> Vec<Box<dyn Rule>> + 'a) -> Self {
+        let build = self.build;
+        self.build = Box::new(move || adapt(build()));
+        self
+    }
+
+    /// Gets the group's name.
+    pub fn category(&self) -> &'static str {
+        self.category
+    }
+
+    /// Gets the names and base priorities of the group's rules.
+    pub fn rules(&self) -> &'static [RuleInfo] {
+        self.rules
+    }
+
+    /// Gets the executables the group is restricted to, empty if it isn't.
+    pub fn executables(&self) -> &'static [&'static str] {
+        self.executables
+    }
+
+    /// Returns true if the group could match a command running `executables`,
+    /// as found by [`command_executables`].
+    pub fn could_match(&self, executables: &[String]) -> bool {
+        self.executables.is_empty()
+            || executables
+                .iter()
+                .any(|executable| self.executables.contains(&executable.as_str()))
+    }
+
+    /// Builds the group's rules, which report the group as their category.
+    pub fn build(self) -> Vec<Box<dyn Rule>> {
+        adapt_all((self.build)(), InCategory(self.category))
+    }
+}
+
+/// Reports the group a rule was built in.
+#[derive(Clone)]
+struct InCategory(&'static str);
+
+impl Adapt for InCategory {
+    fn category<'a>(&'a self, _inner: &'a dyn Rule) -> Option<&'a str> {
+        Some(self.0)
+    }
+}
+
+/// Gets the executables `script` could run: the first word of every command
+/// in its chains and pipelines, looking through variable assignments. After
+/// privilege escalation or a wrapper such as `env` or `time`, every word
+/// counts. Paths are reduced to their file name.
+///
+/// This may find words that aren't executables, but never misses one.
+pub fn command_executables(script: &str) -> Vec<String> {
+    let script = Script::parse(script);
+    let mut executables = Vec::new();
+    let mut at_start = true;
+    // Wrapper options can take values, so every word after a wrapper counts
+    let mut wrapped = false;
+
+    for token in script.tokens() {
+        if Script::is_operator(token) {
+            at_start = true;
+            wrapped = false;
+            continue;
+        }
+        if !at_start {
+            continue;
+        }
+        if privilege::is_escalation(token) || WRAPPERS.contains(&token.as_str()) {
+            wrapped = true;
+            continue;
+        }
+        if token.starts_with('-') || Script::is_assignment(token) {
+            continue;
+        }
+
+        let name = token.rsplit('/').next().unwrap_or(token);
+        if !executables.iter().any(|executable| executable == name) {
+            executables.push(name.to_string());
+        }
+        at_start = wrapped;
+    }
+
+    executables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{git, package_managers, RuleRegistry};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_command_executables() {
+        assert_eq!(command_executables("git push"), vec!["git"]);
+        assert_eq!(
+            command_executables("cd api && apt install libpq-dev | tee log"),
+            vec!["cd", "apt", "tee"]
+        );
+        assert_eq!(command_executables("RUST_LOG=debug cargo test"), vec!["cargo"]);
+        assert!(command_executables("sudo -u postgres psql").contains(&"psql".to_string()));
+        assert_eq!(command_executables("/usr/bin/psql -U app"), vec!["psql"]);
+        assert!(command_executables("").is_empty());
+    }
+
+    #[test]
+    fn test_could_match() {
+        let apt = RuleFactory::new("package_managers", package_managers::RULES, Vec::new)
+            .for_executables(package_managers::EXECUTABLES);
+        assert!(apt.could_match(&command_executables("sudo apt remove vim")));
+        assert!(!apt.could_match(&command_executables("git push")));
+
+        let anything = RuleFactory::new("filesystem", &[], Vec::new);
+        assert!(anything.could_match(&command_executables("git push")));
+    }
+
+    #[test]
+    fn test_git_command_never_constructs_apt_rules() {
+        let git_built = AtomicUsize::new(0);
+        let apt_built = AtomicUsize::new(0);
+        let factories = vec![
+            RuleFactory::new("git", git::RULES, || {
+                git_built.fetch_add(1, Ordering::SeqCst);
+                git::git_typo_rules()
+            })
+            .for_executables(git::EXECUTABLES),
+            RuleFactory::new("package_managers", package_managers::RULES, || {
+                apt_built.fetch_add(1, Ordering::SeqCst);
+                package_managers::package_manager_rules()
+            })
+            .for_executables(package_managers::EXECUTABLES),
+        ];
+
+        let mut registry = RuleRegistry::new();
+        registry.add_factories(factories, Some("git pus"));
+
+        assert_eq!(git_built.load(Ordering::SeqCst), 1);
+        assert_eq!(apt_built.load(Ordering::SeqCst), 0);
+        assert!(registry.rules().iter().all(|rule| !rule.name().starts_with("apt_")));
+    }
+
+    #[test]
+    fn test_listing_rules_builds_nothing() {
+        let built = AtomicUsize::new(0);
+        let factory = RuleFactory::new("git", git::RULES, || {
+            built.fetch_add(1, Ordering::SeqCst);
+            Vec::new()
+        });
+
+        assert!(factory.rules().contains(&("git_push_force", 700)));
+        assert_eq!(built.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_without_script_every_factory_is_built() {
+        let built = AtomicUsize::new(0);
+        let factories = vec![
+            RuleFactory::new("git", git::RULES, || {
+                built.fetch_add(1, Ordering::SeqCst);
+                Vec::new()
+            })
+            .for_executables(git::EXECUTABLES),
+            RuleFactory::new("package_managers", package_managers::RULES, || {
+                built.fetch_add(1, Ordering::SeqCst);
+                Vec::new()
+            })
+            .for_executables(package_managers::EXECUTABLES),
+        ];
+
+        RuleRegistry::new().add_factories(factories, None);
+        assert_eq!(built.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_corrections_carry_the_category() {
+        let factory = RuleFactory::new("filesystem", &[("mkdir_p", 1000)], || {
+            vec![crate::SimpleRuleBuilder::new("mkdir_p").replace("mkdir", "mkdir -p")]
+        });
+        let rules = factory.build();
+        assert_eq!(rules[0].category(), Some("filesystem"));
+
+        let command = crate::Command::new("mkdir a/b", "", 1);
+        let corrections = rules[0].get_corrected_commands(&command);
+        assert_eq!(corrections[0].category.as_deref(), Some("filesystem"));
+        // Priority overrides keep it
+        let rule = crate::rules::PriorityOverride::new(rules.into_iter().next().unwrap(), 10);
+        assert_eq!(rule.get_corrected_commands(&command)[0].category.as_deref(), Some("filesystem"));
+    }
+}
@@ -1,17 +1,50 @@
-/// File system operation rules.
-///
-/// This module contains rules for common filesystem mistakes:
-/// - File/directory not found
-/// - Path issues
-/// - Recursive operations
-/// - Directory creation
+//! File system operation rules.
+//!
+//! This module contains rules for common filesystem mistakes:
+//! - File/directory not found
+//! - Path issues
+//! - Recursive operations
+//! - Directory creation
+//! - Mistyped `cd` targets
+//! - rsync into a destination whose parent is missing, or into a directory
+//!   named like the source, which copies the source inside it
 
-use crate::{Rule, SimpleRuleBuilder};
-#[cfg(test)]
-use crate::Command;
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::{static_regex, Command, CorrectedCommand, RegexRuleBuilder, Rule, RuleContext, Script, SideEffect, SimpleRuleBuilder, Verdict};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Minimum similarity for a directory entry to replace a mistyped path
+/// component.
+const MIN_CD_SIMILARITY: f64 = 0.6;
+
+/// Maximum number of `cd` corrections offered.
+const MAX_CD_CORRECTIONS: usize = 3;
+
+/// rsync options that take a separate value (`-e ssh`, `--exclude .git`).
+const RSYNC_VALUE_OPTIONS: &[&str] = &[
+    "-e", "--rsh", "-f", "--filter", "--exclude", "--include", "--exclude-from", "--include-from", "--files-from",
+    "-T", "--temp-dir", "--partial-dir", "--backup-dir", "--link-dest", "--compare-dest", "--copy-dest", "--chmod",
+    "--chown", "-B", "--block-size", "--bwlimit", "--timeout", "--log-file", "--password-file", "--port",
+];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("mkdir_p", 100),
+    ("rm_recursive", 200),
+    ("cp_recursive", 300),
+    ("mv_to_directory", 400),
+    ("cd_correction", 500),
+    ("rsync_missing_parent", 400),
+    ("rsync_trailing_slash", 600),
+];
 
 /// Creates all filesystem operation rules.
-pub fn filesystem_rules() -> Vec<Box<dyn Rule>> {
+///
+/// Paths are resolved against the working directory of the context's shell.
+pub fn filesystem_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
     vec![
         // mkdir_p: Create parent directories
         create_mkdir_p(),
@@ -21,6 +54,12 @@ pub fn filesystem_rules() -> Vec<Box<dyn Rule>> {
         create_cp_recursive(),
         // mv_to_directory: Create target directory if it doesn't exist
         create_mv_to_directory(),
+        // cd_correction: Fix a mistyped directory name
+        Box::new(CdCorrectionRule::new(context)),
+        // rsync_missing_parent: Create the destination's parent directories
+        create_rsync_missing_parent(),
+        // rsync_trailing_slash: Copy a directory's contents, not the directory
+        Box::new(RsyncTrailingSlashRule),
     ]
 }
 
@@ -30,6 +69,13 @@ fn create_mkdir_p() -> Box<dyn Rule> {
         .match_command("mkdir ")
         .match_output("No such file or directory")
         .priority(100)
+        .describe("Create missing parent directories with `mkdir -p`")
+        .example(Example::new(
+            "mkdir build/release",
+            "mkdir: cannot create directory 'build/release': No such file or directory",
+            1,
+            "mkdir -p build/release",
+        ))
         .replace("mkdir ", "mkdir -p ")
 }
 
@@ -39,7 +85,9 @@ fn create_rm_recursive() -> Box<dyn Rule> {
         .match_command("rm ")
         .match_output("Is a directory")
         .priority(200)
-        .replace("rm ", "rm -r ")
+        .describe("Remove a directory recursively")
+        .example(Example::new("rm build", "rm: cannot remove 'build': Is a directory", 1, "rm -r build"))
+        .insert_flag("rm", "-r")
 }
 
 /// cp_recursive: Add recursive flag when trying to copy directory
@@ -48,21 +96,312 @@ fn create_cp_recursive() -> Box<dyn Rule> {
         .match_command("cp ")
         .match_output("Is a directory")
         .priority(300)
-        .replace("cp ", "cp -r ")
+        .describe("Copy a directory recursively")
+        .example(Example::new(
+            "cp src backup",
+            "cp: -r not specified; omitting directory 'src'\ncp: 'src': Is a directory",
+            1,
+            "cp -r src backup",
+        ))
+        .insert_flag("cp", "-r")
 }
 
-/// mv_to_directory: Suggest moving into directory when target doesn't exist
+/// mv_to_directory: Retry the move after creating the target's missing
+/// parent directories
 fn create_mv_to_directory() -> Box<dyn Rule> {
-    SimpleRuleBuilder::new("mv_to_directory")
-        .match_command("mv ")
-        .match_output("No such file or directory")
+    // The target comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("mv_to_directory")
+        .match_output_static(static_regex!(r"mv: cannot move '.+' to '(.+)': No such file or directory"))
+        .priority(400)
+        .describe("Create the target's missing parent directories and move again")
+        .example(Example::new(
+            "mv notes.txt archive/2024/",
+            "mv: cannot move 'notes.txt' to 'archive/2024/': No such file or directory",
+            1,
+            "mv notes.txt archive/2024/",
+        ))
+        .replace_with_cow(|original, _captures| vec![Cow::Borrowed(original)])
+        .side_effect(|_original, captures| Some(SideEffect::CreateParentDirs(captures.get(1)?.as_str().into())))
+        .build_builtin()
+}
+
+/// cd_correction: `cd buidl/relase` → `cd build/release`
+///
+/// Candidates are picked by name only; [`Rule::verify`] drops those that
+/// aren't directories.
+pub struct CdCorrectionRule {
+    context: Arc<RuleContext>,
+}
+
+impl CdCorrectionRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the target of a `cd` with a single argument.
+    fn target(script: &str) -> Option<String> {
+        let script = Script::parse(script);
+        match script.tokens() {
+            [cd, target] if cd == "cd" => Some(target.clone()),
+            _ => None,
+        }
+    }
+
+    /// Gets the entries of `dir` most similar to `name`, most similar first.
+    fn similar_entries(&self, dir: &Path, name: &str) -> Vec<String> {
+        let entries = self.context.dir_entries(dir);
+        if entries.iter().any(|entry| entry == name) {
+            return vec![name.to_string()];
+        }
+
+        let mut similar: Vec<(f64, &String)> = entries
+            .iter()
+            .map(|entry| (similarity(name, entry), entry))
+            .filter(|(score, _)| *score >= MIN_CD_SIMILARITY)
+            .collect();
+        similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+        similar.into_iter().map(|(_, entry)| entry.clone()).collect()
+    }
+
+    /// Corrects each component of `target` to the closest existing entry,
+    /// offering alternatives for the last one.
+    fn corrected_targets(&self, target: &str) -> Vec<PathBuf> {
+        if target.starts_with(['~', '$']) {
+            return vec![];
+        }
+
+        let components: Vec<Component> = Path::new(target).components().collect();
+        let mut corrected = PathBuf::new();
+        for (i, component) in components.iter().enumerate() {
+            let Component::Normal(name) = component else {
+                corrected.push(component);
+                continue;
+            };
+            let candidates = self.similar_entries(&corrected, &name.to_string_lossy());
+            if i + 1 == components.len() {
+                return candidates
+                    .into_iter()
+                    .take(MAX_CD_CORRECTIONS)
+                    .map(|candidate| corrected.join(candidate))
+                    .collect();
+            }
+            let Some(best) = candidates.into_iter().next() else {
+                return vec![];
+            };
+            corrected.push(best);
+        }
+        vec![]
+    }
+}
+
+impl Rule for CdCorrectionRule {
+    fn name(&self) -> &str {
+        "cd_correction"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Fix mistyped directory names in a `cd` target")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.output.to_lowercase().contains("no such file or directory")
+            && Self::target(&command.script).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(target) = Self::target(&command.script) else {
+            return vec![];
+        };
+
+        self.corrected_targets(&target)
+            .into_iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .filter(|path| *path != target)
+            .map(|path| Script::from_tokens(["cd".to_string(), path]).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        500
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        let Some(target) = Self::target(&correction.script) else {
+            return Verdict::Keep;
+        };
+        let Some(cwd) = context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return Verdict::Keep;
+        };
+
+        if cwd.join(target).is_dir() {
+            Verdict::Keep
+        } else {
+            Verdict::Drop
+        }
+    }
+}
+
+/// Gets the positions of the sources and destination of an rsync script.
+/// Options and their values are skipped.
+fn rsync_operands(script: &Script) -> Vec<usize> {
+    let mut operands = Vec::new();
+    let mut tokens = script.tokens().iter().enumerate().skip_while(|(_, token)| *token != "rsync").skip(1);
+    while let Some((index, token)) = tokens.next() {
+        if RSYNC_VALUE_OPTIONS.contains(&token.as_str()) {
+            tokens.next();
+        } else if !token.starts_with('-') {
+            operands.push(index);
+        }
+    }
+    operands
+}
+
+/// Gets the local part of an rsync operand: the path after `host:`, or
+/// `None` for an rsync daemon URL or module.
+fn rsync_path(operand: &str) -> Option<&str> {
+    if operand.starts_with("rsync://") || operand.contains("::") {
+        return None;
+    }
+    match operand.split_once(':') {
+        Some((host, path)) if !host.contains('/') => Some(path),
+        _ => Some(operand),
+    }
+}
+
+/// Returns true if an rsync operand is on another host.
+fn is_remote(operand: &str) -> bool {
+    rsync_path(operand) != Some(operand)
+}
+
+/// rsync_missing_parent: Retry the sync after creating the destination's
+/// missing parent directories
+fn create_rsync_missing_parent() -> Box<dyn Rule> {
+    // The directory comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("rsync_missing_parent")
+        .match_output_static(static_regex!(
+            r#"rsync: (?:\[Receiver\] )?mkdir "(.+)" failed: No such file or directory"#
+        ))
         .priority(400)
-        .replace("mv ", "mkdir -p /path && mv ")
+        .describe("Create the destination's missing parent directories and sync again")
+        .example(Example::new(
+            "rsync -a photos/ backup/2024/photos",
+            "rsync: [Receiver] mkdir \"backup/2024/photos\" failed: No such file or directory (2)\n\
+             rsync error: error in file IO (code 11) at main.c(791) [Receiver=3.2.7]",
+            11,
+            "rsync -a photos/ backup/2024/photos",
+        ))
+        .replace_with_cow(|original, _captures| {
+            // The parents of a remote destination can't be created here
+            let script = Script::parse(original);
+            match rsync_operands(&script).last() {
+                Some(&index) if !is_remote(&script.tokens()[index]) => vec![Cow::Borrowed(original)],
+                _ => vec![],
+            }
+        })
+        .side_effect(|_original, captures| Some(SideEffect::CreateParentDirs(captures.get(1)?.as_str().into())))
+        .build_builtin()
+}
+
+/// rsync_trailing_slash: `rsync -a photos backup/photos` →
+/// `rsync -a photos/ backup/photos`
+///
+/// Without a trailing slash rsync copies the source directory itself into
+/// the destination, leaving `backup/photos/photos`. [`Rule::verify`] ranks
+/// the correction lower unless that nested copy exists.
+pub struct RsyncTrailingSlashRule;
+
+impl RsyncTrailingSlashRule {
+    /// Gets the position of the source, and the name shared by the source
+    /// and the destination, if the script copies one directory into another
+    /// of the same name.
+    fn nested_copy(script: &Script) -> Option<(usize, String)> {
+        let [source, destination] = rsync_operands(script)[..] else {
+            return None;
+        };
+        let tokens = script.tokens();
+        if tokens[source].ends_with('/') {
+            return None;
+        }
+
+        let name = |operand: &str| -> Option<String> {
+            let path = Path::new(rsync_path(operand)?.trim_end_matches('/'));
+            match path.components().next_back()? {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            }
+        };
+        let shared = name(&tokens[source])?;
+        (name(&tokens[destination])? == shared).then_some((source, shared))
+    }
+}
+
+impl Rule for RsyncTrailingSlashRule {
+    fn name(&self) -> &str {
+        "rsync_trailing_slash"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Sync a directory's contents into a directory of the same name, not the directory itself")
+            .example(Example::new(
+                "rsync -av photos backup/photos",
+                "sending incremental file list\nphotos/\nphotos/beach.jpg\n\n\
+                 sent 1,024 bytes  received 39 bytes  2,126.00 bytes/sec",
+                0,
+                "rsync -av photos/ backup/photos",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Self::nested_copy(&Script::parse(&command.script)).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let Some((source, _)) = Self::nested_copy(&script) else {
+            return vec![];
+        };
+        let slashed = format!("{}/", script.tokens()[source]);
+        vec![script.replace_token_at(source, slashed).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        600
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        let Some(cwd) = context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return Verdict::Keep;
+        };
+        let script = Script::parse(&correction.script);
+        let Some(&destination) = rsync_operands(&script).last() else {
+            return Verdict::Keep;
+        };
+        let destination = &script.tokens()[destination];
+        if is_remote(destination) {
+            return Verdict::Keep;
+        }
+
+        // The source was copied into the destination under its own name
+        let nested = Path::new(destination.trim_end_matches('/'));
+        match nested.file_name() {
+            Some(name) if cwd.join(nested).join(name).is_dir() => Verdict::Keep,
+            _ => Verdict::Demote,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{fake_context, MockShell};
+    use crate::Corrector;
+
+    fn context_in(dir: &Path) -> Arc<RuleContext> {
+        Arc::new(RuleContext::new(Box::new(MockShell::new().with_cwd(dir))))
+    }
 
     #[test]
     fn test_mkdir_p_rule() {
@@ -73,6 +412,8 @@ mod tests {
             script: "mkdir a/b/c".to_string(),
             output: "mkdir: cannot create directory 'a/b/c': No such file or directory".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
@@ -90,12 +431,29 @@ mod tests {
             script: "rm my_dir".to_string(),
             output: "rm: cannot remove 'my_dir': Is a directory".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
         let corrections = rule.get_new_commands(&cmd);
         assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("-r"));
+        assert_eq!(corrections[0], "rm -r my_dir");
+    }
+
+    #[test]
+    fn test_rm_recursive_does_not_rewrite_arguments() {
+        let rule = create_rm_recursive();
+
+        let cmd = Command {
+            script: "rm farm ".to_string(),
+            output: "rm: cannot remove 'farm': Is a directory".to_string(),
+            exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
+        };
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["rm -r farm"]);
     }
 
     #[test]
@@ -107,12 +465,30 @@ mod tests {
             script: "cp my_dir /backup/".to_string(),
             output: "cp: my_dir is a directory (not copied).Is a directory".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
         let corrections = rule.get_new_commands(&cmd);
         assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("-r"));
+        assert_eq!(corrections[0], "cp -r my_dir /backup/");
+    }
+
+    #[test]
+    fn test_cp_recursive_keeps_spaces_in_arguments() {
+        let rule = create_cp_recursive();
+
+        let cmd = Command {
+            script: "cp \"My Photos\" /backup/".to_string(),
+            output: "cp: -r not specified; omitting directory 'My Photos': Is a directory".to_string(),
+            exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
+        };
+
+        let corrections = rule.get_new_commands(&cmd);
+        assert_eq!(corrections, vec!["cp -r \"My Photos\" /backup/"]);
     }
 
     #[test]
@@ -124,20 +500,151 @@ mod tests {
             script: "mv file.txt backup/file.txt".to_string(),
             output: "mv: cannot move 'file.txt' to 'backup/file.txt': No such file or directory".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, "mv file.txt backup/file.txt");
+        assert_eq!(
+            corrections[0].side_effect,
+            Some(SideEffect::CreateParentDirs("backup/file.txt".into()))
+        );
+    }
+
+    #[test]
+    fn test_mv_to_directory_missing_source() {
+        let rule = create_mv_to_directory();
+        let cmd = Command::new(
+            "mv notes.txt archive/",
+            "mv: cannot stat 'notes.txt': No such file or directory",
+            1,
+        );
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_mv_to_directory_effect_creates_target_dir() {
+        let dir = std::env::temp_dir().join(format!("ftf_mv_to_directory_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let shell = crate::testing::MockShell::new().with_cwd(&dir);
+
+        let cmd = Command::new(
+            "mv report.pdf archive/2024/report.pdf",
+            "mv: cannot move 'report.pdf' to 'archive/2024/report.pdf': No such file or directory",
+            1,
+        );
+        let corrections = create_mv_to_directory().get_corrected_commands(&cmd);
+        corrections[0].side_effect.as_ref().unwrap().run(&shell).unwrap();
+
+        assert!(dir.join("archive/2024").is_dir());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cd_correction() {
+        let fake = fake_context().dir_entries(["build/release/"]).build();
+        let rule = CdCorrectionRule::new(fake.context());
+
+        let cmd = Command::new("cd buidl/relase", "bash: cd: buidl/relase: No such file or directory", 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cd build/release"]);
+
+        let cmd = Command::new("cd nothing-like-it", "cd: no such file or directory: nothing-like-it", 1);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+        // Directory names are read, not listed by running commands
+        assert!(fake.calls().commands().is_empty());
+    }
+
+    #[test]
+    fn test_cd_correction_verify_drops_files() {
+        let fake = fake_context().dir_entries(["build/", "buildx"]).build();
+        let cmd = Command::new("cd buidl", "bash: cd: buidl: No such file or directory", 1);
+
+        // Both entries look like the target, but only one is a directory
+        let rule = CdCorrectionRule::new(fake.context());
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cd build", "cd buildx"]);
+
+        let corrector = Corrector::with_rules(vec![Box::new(rule)], fake.context());
+        let scripts: Vec<_> = corrector.get_corrections(&cmd).into_iter().map(|c| c.script).collect();
+        assert_eq!(scripts, vec!["cd build"]);
+        assert!(fake.calls().commands().is_empty());
+    }
+
+    #[test]
+    fn test_rsync_missing_parent_rule() {
+        let rule = create_rsync_missing_parent();
+        let output = "rsync: mkdir \"/mnt/backup/2024/photos\" failed: No such file or directory (2)\n\
+                      rsync error: error in file IO (code 11) at main.c(682) [Receiver=3.1.3]";
+        let cmd = Command::new("rsync -a --exclude .cache photos/ /mnt/backup/2024/photos", output, 11);
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, cmd.script);
+        assert_eq!(corrections[0].side_effect, Some(SideEffect::CreateParentDirs("/mnt/backup/2024/photos".into())));
+    }
+
+    #[test]
+    fn test_rsync_missing_parent_ignores_remote_destinations() {
+        let rule = create_rsync_missing_parent();
+        let output = "rsync: [Receiver] mkdir \"/srv/backup/photos\" failed: No such file or directory (2)";
+        let cmd = Command::new("rsync -a photos/ nas:/srv/backup/photos", output, 11);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_rsync_trailing_slash_rule() {
+        let rule = RsyncTrailingSlashRule;
+        let cmd = Command::new("rsync -a -e 'ssh -p 2222' photos nas:backup/photos/", "", 0);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["rsync -a -e 'ssh -p 2222' photos/ nas:backup/photos/"]);
+    }
+
+    #[test]
+    fn test_rsync_trailing_slash_needs_matching_names() {
+        let rule = RsyncTrailingSlashRule;
+
+        for script in [
+            "rsync -a photos/ backup/photos",
+            "rsync -a photos backup/",
+            "rsync -a photos videos backup/photos",
+            "rsync -a . backup/.",
+            "rsync -a nas::photos photos",
+        ] {
+            assert!(!rule.matches(&Command::new(script, "", 0)), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_rsync_trailing_slash_verify_looks_for_the_nested_copy() {
+        let dir = std::env::temp_dir().join(format!("ftf_rsync_trailing_slash_verify_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("backup/photos/photos")).unwrap();
+        std::fs::create_dir_all(dir.join("backup/videos")).unwrap();
+        let context = context_in(&dir);
+        let rule = RsyncTrailingSlashRule;
+
+        let nested = CorrectedCommand::new("rsync -a photos/ backup/photos", 600);
+        assert_eq!(rule.verify(&nested, &context), Verdict::Keep);
+        let not_nested = CorrectedCommand::new("rsync -a videos/ backup/videos", 600);
+        assert_eq!(rule.verify(&not_nested, &context), Verdict::Demote);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
     fn test_filesystem_rules_exist() {
-        let rules = filesystem_rules();
-        assert_eq!(rules.len(), 4);
+        let rules = filesystem_rules(Arc::new(RuleContext::default()));
+        assert_eq!(rules.len(), 7);
     }
 
     #[test]
     fn test_filesystem_rules_priorities() {
-        let rules = filesystem_rules();
+        let rules = filesystem_rules(Arc::new(RuleContext::default()));
         let priorities: Vec<_> = rules.iter().map(|r| r.priority()).collect();
 
         // Check that priorities are different
@@ -0,0 +1,236 @@
+//! GitHub (`gh`) and GitLab (`glab`) CLI correction rules.
+//!
+//! This module contains rules for common git-hosting CLI mistakes:
+//! - Running a command before logging in, using the login command the CLI
+//!   prints
+//! - Mistyped commands, using the CLI's own "Did you mean this?" list
+//! - `gh pr create` on a branch that hasn't been pushed yet
+//!
+//! `glab mr create` can push the branch itself, so only gh needs the push rule.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::{command_after, listed_suggestions, prefix_then_retry, replace_with_suggestions};
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, Script};
+
+/// Header gh and glab print before their suggestions for a mistyped command.
+const DID_YOU_MEAN: &str = "Did you mean this?";
+
+/// Text gh and glab print before the login command.
+const PLEASE_RUN: &str = "please run:";
+
+/// Executables every forge rule requires.
+pub const EXECUTABLES: &[&str] = &["gh", "glab"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("forge_login", 200),
+    ("forge_unknown_command", 300),
+    ("gh_pr_create_push", 200),
+];
+
+/// Creates all gh and glab rules.
+pub fn forge_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // forge_login: Run the login command the CLI asks for, then retry
+        Box::new(ForgeLoginRule),
+        // forge_unknown_command: Use the CLI's suggestion for a mistyped command
+        Box::new(ForgeUnknownCommandRule),
+        // gh_pr_create_push: Push the branch before creating the pull request
+        create_gh_pr_create_push(),
+    ]
+}
+
+/// Returns true if `script` runs gh or glab.
+fn is_forge_cli(script: &str) -> bool {
+    matches!(Script::parse(script).executable(), Some("gh" | "glab"))
+}
+
+/// forge_login: `gh pr list` → `gh auth login && gh pr list`
+struct ForgeLoginRule;
+
+impl ForgeLoginRule {
+    /// Gets the login command from the output, if it belongs to the same CLI.
+    fn login_command(command: &Command) -> Option<&str> {
+        let login = command_after(&command.output, PLEASE_RUN)?;
+        let script = Script::parse(&command.script);
+        (Script::parse(login).executable() == script.executable()).then_some(login)
+    }
+}
+
+impl Rule for ForgeLoginRule {
+    fn name(&self) -> &str {
+        "forge_login"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Run the login command gh or glab asks for, then retry")
+            .example(Example::new(
+                "glab mr list",
+                "To get started with GitLab CLI, please run: glab auth login",
+                1,
+                "glab auth login && glab mr list",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        is_forge_cli(&command.script) && Self::login_command(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        Self::login_command(command)
+            .map(|login| prefix_then_retry(login, &command.script))
+            .into_iter()
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+/// forge_unknown_command: `gh pr craete` → `gh pr create`
+struct ForgeUnknownCommandRule;
+
+impl ForgeUnknownCommandRule {
+    /// Gets the mistyped command from the CLI's error.
+    fn unknown_command(output: &str) -> Option<&str> {
+        let rest = output.split("unknown command \"").nth(1)?;
+        rest.split('"').next()
+    }
+}
+
+impl Rule for ForgeUnknownCommandRule {
+    fn name(&self) -> &str {
+        "forge_unknown_command"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use gh's or glab's suggestion for a mistyped command")
+            .example(Example::new(
+                "glab mrr list",
+                "Error: unknown command \"mrr\" for \"glab\"\n\nDid you mean this?\n\tmr",
+                1,
+                "glab mr list",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        is_forge_cli(&command.script)
+            && Self::unknown_command(&command.output).is_some()
+            && !listed_suggestions(&command.output, DID_YOU_MEAN).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::unknown_command(&command.output) else {
+            return vec![];
+        };
+
+        replace_with_suggestions(
+            &command.script,
+            typo,
+            listed_suggestions(&command.output, DID_YOU_MEAN),
+        )
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// gh_pr_create_push: `gh pr create --fill` →
+/// `git push -u origin HEAD && gh pr create --fill`
+fn create_gh_pr_create_push() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("gh_pr_create_push")
+        .match_command_static(static_regex!(r"^gh\s+pr\s+create\b"))
+        .match_output_static(static_regex!(r"you must first push the current branch to a remote"))
+        .priority(200)
+        .describe("Push the branch before creating a pull request from it")
+        .example(Example::new(
+            "gh pr create --fill",
+            "aborted: you must first push the current branch to a remote, or use the --head flag",
+            1,
+            "git push -u origin HEAD && gh pr create --fill",
+        ))
+        .replace_with(|original, _captures| {
+            vec![prefix_then_retry("git push -u origin HEAD", original)]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GH_NOT_LOGGED_IN: &str = "To get started with GitHub CLI, please run:  gh auth login\nAlternatively, populate the GH_TOKEN environment variable with a GitHub API authentication token.\n";
+
+    const GLAB_NOT_LOGGED_IN: &str = "To get started with GitLab CLI, please run: glab auth login\n";
+
+    const GH_UNKNOWN_COMMAND: &str = "unknown command \"craete\" for \"gh pr\"\n\nDid you mean this?\n\tcreate\n\nUsage:  gh pr <command> [flags]\n\nAvailable commands:\n  checkout\n  checks\n  close\n  comment\n  create\n  diff\n  edit\n  list\n  merge\n  ready\n  reopen\n  review\n  status\n  view\n";
+
+    const GLAB_UNKNOWN_COMMAND: &str = "Error: unknown command \"mrr\" for \"glab\"\n\nDid you mean this?\n\tmr\n\nRun 'glab --help' for usage.\n";
+
+    const GH_NOT_PUSHED: &str = "aborted: you must first push the current branch to a remote, or use the --head flag\n";
+
+    #[test]
+    fn test_gh_login() {
+        let rule = ForgeLoginRule;
+        let cmd = Command::new("gh pr list --author @me", GH_NOT_LOGGED_IN, 4);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["gh auth login && gh pr list --author @me"]);
+    }
+
+    #[test]
+    fn test_glab_login() {
+        let rule = ForgeLoginRule;
+        let cmd = Command::new("glab mr list", GLAB_NOT_LOGGED_IN, 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["glab auth login && glab mr list"]);
+    }
+
+    #[test]
+    fn test_login_from_another_cli_is_ignored() {
+        let rule = ForgeLoginRule;
+        let cmd = Command::new("glab mr list", GH_NOT_LOGGED_IN, 1);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_gh_unknown_subcommand() {
+        let rule = ForgeUnknownCommandRule;
+        let cmd = Command::new("gh pr craete --fill", GH_UNKNOWN_COMMAND, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["gh pr create --fill"]);
+    }
+
+    #[test]
+    fn test_glab_unknown_command() {
+        let rule = ForgeUnknownCommandRule;
+        let cmd = Command::new("glab mrr list", GLAB_UNKNOWN_COMMAND, 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["glab mr list"]);
+    }
+
+    #[test]
+    fn test_gh_pr_create_push() {
+        let rule = create_gh_pr_create_push();
+        let cmd = Command::new("gh pr create --title 'Fix login' --body ''", GH_NOT_PUSHED, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["git push -u origin HEAD && gh pr create --title 'Fix login' --body ''"]
+        );
+    }
+
+    #[test]
+    fn test_forge_rules_exist() {
+        let rules = forge_rules();
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["forge_login", "forge_unknown_command", "gh_pr_create_push"]);
+    }
+}
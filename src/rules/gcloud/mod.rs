@@ -0,0 +1,265 @@
+//! gcloud command correction rules.
+//!
+//! This module contains rules for common gcloud mistakes:
+//! - Mistyped groups and commands, using gcloud's own "Maybe you meant" list
+//! - Missing permissions, usually from logging in with the wrong account
+//! - Running a command before a project is configured
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::{listed_suggestions, prefix_then_retry, replace_with_suggestions};
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// Header gcloud prints before its suggestions for a mistyped command.
+const MAYBE_YOU_MEANT: &str = "Maybe you meant:";
+
+/// Executables every gcloud rule requires.
+pub const EXECUTABLES: &[&str] = &["gcloud"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("gcloud_invalid_choice", 300),
+    ("gcloud_permission_denied", 400),
+    ("gcloud_project_unset", 250),
+];
+
+/// Creates all gcloud rules.
+///
+/// `project` is the project set when none is configured; without it the
+/// project rule falls back to `$GOOGLE_CLOUD_PROJECT` read through the
+/// context's shell, and suggests nothing if that is unset too.
+pub fn gcloud_rules(context: Arc<RuleContext>, project: Option<String>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // gcloud_invalid_choice: Use gcloud's suggestion for a mistyped command
+        Box::new(GcloudInvalidChoiceRule),
+        // gcloud_permission_denied: Log in again before retrying
+        create_gcloud_permission_denied(),
+        // gcloud_project_unset: Set the project before retrying
+        Box::new(GcloudProjectUnsetRule::new(context, project)),
+    ]
+}
+
+/// gcloud_invalid_choice: `gcloud comptue instances list` → `gcloud compute instances list`
+struct GcloudInvalidChoiceRule;
+
+impl GcloudInvalidChoiceRule {
+    /// Gets the mistyped choice from gcloud's error.
+    fn invalid_choice(output: &str) -> Option<&str> {
+        let rest = output.split("Invalid choice: '").nth(1)?;
+        rest.split('\'').next()
+    }
+}
+
+impl Rule for GcloudInvalidChoiceRule {
+    fn name(&self) -> &str {
+        "gcloud_invalid_choice"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use gcloud's suggestion for a mistyped group or command")
+            .example(Example::new(
+                "gcloud comptue instances list",
+                "ERROR: (gcloud) Invalid choice: 'comptue'.\nMaybe you meant:\n  gcloud compute",
+                2,
+                "gcloud compute instances list",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("gcloud")
+            && Self::invalid_choice(&command.output).is_some()
+            && !listed_suggestions(&command.output, MAYBE_YOU_MEANT).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::invalid_choice(&command.output) else {
+            return vec![];
+        };
+
+        replace_with_suggestions(
+            &command.script,
+            typo,
+            listed_suggestions(&command.output, MAYBE_YOU_MEANT),
+        )
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// gcloud_permission_denied: Log in (as a user or for application default
+/// credentials), then retry
+fn create_gcloud_permission_denied() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("gcloud_permission_denied")
+        .match_command_static(static_regex!(r"^gcloud\s"))
+        .match_output_static(static_regex!(r"does not have permission"))
+        .priority(400)
+        .describe("Log in again, as a user or for application default credentials, then retry")
+        .example(Example::new(
+            "gcloud compute instances list",
+            "ERROR: (gcloud.compute.instances.list) User [me@example.com] does not have permission",
+            1,
+            "gcloud auth login && gcloud compute instances list",
+        ))
+        .replace_with(|original, _captures| {
+            vec![
+                prefix_then_retry("gcloud auth login", original),
+                prefix_then_retry("gcloud auth application-default login", original),
+            ]
+        })
+        .build_builtin()
+}
+
+/// gcloud_project_unset: `gcloud compute instances list` →
+/// `gcloud config set project <project> && gcloud compute instances list`
+pub struct GcloudProjectUnsetRule {
+    context: Arc<RuleContext>,
+    project: Option<String>,
+}
+
+impl GcloudProjectUnsetRule {
+    /// Creates the rule, preferring `project` over the environment.
+    pub fn new(context: Arc<RuleContext>, project: Option<String>) -> Self {
+        Self { context, project }
+    }
+
+    /// Gets the configured project, falling back to `$GOOGLE_CLOUD_PROJECT`.
+    fn project(&self) -> Option<String> {
+        self.project
+            .clone()
+            .or_else(|| self.context.shell()?.env("GOOGLE_CLOUD_PROJECT"))
+            .filter(|project| !project.is_empty())
+    }
+}
+
+impl Rule for GcloudProjectUnsetRule {
+    fn name(&self) -> &str {
+        "gcloud_project_unset"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Set the configured `project` or `$GOOGLE_CLOUD_PROJECT` before retrying")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("gcloud")
+            && command
+                .output
+                .contains("The required property [project] is not currently set")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(project) = self.project() else {
+            return vec![];
+        };
+
+        let setup = Script::from_tokens(["gcloud", "config", "set", "project", project.as_str()]);
+        vec![prefix_then_retry(&setup.to_string(), &command.script)]
+    }
+
+    fn priority(&self) -> i32 {
+        250
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const INVALID_GROUP: &str = "ERROR: (gcloud) Invalid choice: 'comptue'.\nMaybe you meant:\n  gcloud compute\n\nTo search the help text of gcloud commands, run:\n  gcloud help -- SEARCH_TERMS\n";
+
+    const INVALID_COMMAND: &str = "ERROR: (gcloud.compute) Invalid choice: 'instnces'.\nMaybe you meant:\n  gcloud compute instances\n  gcloud compute instance-groups\n  gcloud compute instance-templates\n\nTo search the help text of gcloud commands, run:\n  gcloud help -- SEARCH_TERMS\n";
+
+    const PERMISSION_DENIED: &str = "ERROR: (gcloud.projects.describe) [dev@example.com] does not have permission to access projects instance [billing-prod] (or it may not exist): The caller does not have permission\n";
+
+    const PROJECT_UNSET: &str = "ERROR: (gcloud.compute.instances.list) The required property [project] is not currently set.\nIt can be set on a per-command basis by re-running your command with the [--project] flag.\n\nYou may set it for your current workspace by running:\n\n  $ gcloud config set project VALUE\n\nor it can be set temporarily by the environment variable [CLOUDSDK_CORE_PROJECT]\n";
+
+    fn context() -> Arc<RuleContext> {
+        Arc::new(RuleContext::default())
+    }
+
+    #[test]
+    fn test_gcloud_invalid_group() {
+        let rule = GcloudInvalidChoiceRule;
+        let cmd = Command::new("gcloud comptue instances list", INVALID_GROUP, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["gcloud compute instances list"]);
+    }
+
+    #[test]
+    fn test_gcloud_invalid_command() {
+        let rule = GcloudInvalidChoiceRule;
+        let cmd = Command::new("gcloud compute instnces list", INVALID_COMMAND, 2);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "gcloud compute instances list",
+                "gcloud compute instance-groups list",
+                "gcloud compute instance-templates list",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gcloud_permission_denied() {
+        let rule = create_gcloud_permission_denied();
+        let cmd = Command::new("gcloud projects describe billing-prod", PERMISSION_DENIED, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "gcloud auth login && gcloud projects describe billing-prod",
+                "gcloud auth application-default login && gcloud projects describe billing-prod",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gcloud_project_unset_from_config() {
+        let rule = GcloudProjectUnsetRule::new(context(), Some("my-project".to_string()));
+        let cmd = Command::new("gcloud compute instances list", PROJECT_UNSET, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["gcloud config set project my-project && gcloud compute instances list"]
+        );
+    }
+
+    #[test]
+    fn test_gcloud_project_unset_from_env() {
+        let shell = MockShell::new().with_env("GOOGLE_CLOUD_PROJECT", "env-project");
+        let rule = GcloudProjectUnsetRule::new(Arc::new(RuleContext::new(Box::new(shell))), None);
+        let cmd = Command::new("gcloud compute instances list", PROJECT_UNSET, 1);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["gcloud config set project env-project && gcloud compute instances list"]
+        );
+    }
+
+    #[test]
+    fn test_gcloud_project_unset_without_project() {
+        let rule = GcloudProjectUnsetRule::new(context(), None);
+        let cmd = Command::new("gcloud compute instances list", PROJECT_UNSET, 1);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_gcloud_rules_exist() {
+        let rules = gcloud_rules(context(), None);
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["gcloud_invalid_choice", "gcloud_permission_denied", "gcloud_project_unset"]
+        );
+    }
+}
@@ -1,15 +1,32 @@
-/// Git-related command correction rules.
-///
-/// This module contains rules for common git mistakes including:
-/// - Branch operations (delete, rename, create)
-/// - Push/pull operations
-/// - Staging and committing
-/// - Rebasing and merging
-/// - Typos and similar errors
-
-use crate::{Rule, SimpleRuleBuilder, RegexRuleBuilder};
-#[cfg(test)]
-use crate::Command;
+//! Git-related command correction rules.
+//!
+//! This module contains rules for common git mistakes including:
+//! - Branch operations (delete, rename, create)
+//! - Push/pull operations
+//! - Staging and committing
+//! - Rebasing and merging
+//! - Typos and similar errors
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::{listed_suggestions, replace_with_suggestions};
+use crate::{static_regex, Command, Rule, Script, SimpleRuleBuilder, RegexRuleBuilder, Verdict};
+
+/// Executables every git rule requires.
+pub const EXECUTABLES: &[&str] = &["git"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("git_branch_delete", 500),
+    ("git_branch_exists", 1000),
+    ("git_branch_0flag", 400),
+    ("git_push_set_upstream", 600),
+    ("git_pull_rebase", 500),
+    ("git_push_force", 700),
+    ("git_add_all", 800),
+    ("git_commit_amend", 550),
+    ("git_stash", 650),
+    ("git_not_command", 300),
+];
 
 /// Creates all git branch operation rules.
 /// These are simple git branch-related corrections.
@@ -43,6 +60,16 @@ pub fn git_staging_rules() -> Vec<Box<dyn Rule>> {
         create_git_add_all(),
         // git_commit_amend: Amend last commit
         create_git_commit_amend(),
+        // git_stash: Stash local changes that block a checkout or rebase
+        create_git_stash(),
+    ]
+}
+
+/// Creates all git typo rules.
+pub fn git_typo_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // git_not_command: Use git's own suggestion for a mistyped subcommand
+        Box::new(GitNotCommandRule),
     ]
 }
 
@@ -52,16 +79,28 @@ fn create_git_branch_delete() -> Box<dyn Rule> {
         .match_command("git branch -d")
         .match_output("error: The branch")
         .priority(500)
+        .describe("Force-delete a branch that isn't fully merged")
+        .example(Example::new(
+            "git branch -d feature",
+            "error: The branch 'feature' is not fully merged.",
+            1,
+            "git branch -D feature",
+        ))
         .replace("git branch -d", "git branch -D")
 }
 
 /// git_branch_exists: Create branch if it doesn't exist
 fn create_git_branch_exists() -> Box<dyn Rule> {
     RegexRuleBuilder::new("git_branch_exists")
-        .match_command_regex(r"git checkout ([a-z_-]+)")
-        .unwrap()
-        .match_output_regex(r"error: pathspec '([a-z_-]+)' did not match")
-        .unwrap()
+        .match_command_static(static_regex!(r"git checkout ([a-z_-]+)"))
+        .match_output_static(static_regex!(r"error: pathspec '([a-z_-]+)' did not match"))
+        .describe("Create the branch a checkout couldn't find")
+        .example(Example::new(
+            "git checkout feature",
+            "error: pathspec 'feature' did not match any file(s) known to git",
+            1,
+            "git checkout -b feature",
+        ))
         .replace_with(|_original, captures| {
             if let Some(branch) = captures.get(1) {
                 vec![format!("git checkout -b {}", branch.as_str())]
@@ -69,8 +108,15 @@ fn create_git_branch_exists() -> Box<dyn Rule> {
                 vec![]
             }
         })
-        .build()
-        .unwrap()
+        .verify(|correction, context| {
+            // Creating a branch that already exists fails
+            let script = Script::parse(&correction.script);
+            match script.tokens().last() {
+                Some(branch) if context.git_branches().contains(branch) => Verdict::Drop,
+                _ => Verdict::Keep,
+            }
+        })
+        .build_builtin()
 }
 
 /// git_branch_0flag: Fix missing argument flag
@@ -79,6 +125,13 @@ fn create_git_branch_0flag() -> Box<dyn Rule> {
         .match_command("git branch")
         .match_output("fatal: bad revision")
         .priority(400)
+        .describe("List remote branches too when git can't resolve a revision")
+        .example(Example::new(
+            "git branch --contains v2",
+            "fatal: bad revision 'v2'",
+            128,
+            "git branch -a --contains v2",
+        ))
         .replace("git branch", "git branch -a")
 }
 
@@ -88,6 +141,13 @@ fn create_git_push_set_upstream() -> Box<dyn Rule> {
         .match_command("git push")
         .match_output("fatal: The current branch")
         .priority(600)
+        .describe("Set the upstream on a branch's first push")
+        .example(Example::new(
+            "git push",
+            "fatal: The current branch feature has no upstream branch.",
+            128,
+            "git push -u origin",
+        ))
         .replace("git push", "git push -u origin")
 }
 
@@ -97,6 +157,13 @@ fn create_git_pull_rebase() -> Box<dyn Rule> {
         .match_command("git pull")
         .match_output("Please specify which branch you want to merge with")
         .priority(500)
+        .describe("Pull from origin with a rebase when the branch tracks nothing")
+        .example(Example::new(
+            "git pull",
+            "There is no tracking information.\nPlease specify which branch you want to merge with.",
+            1,
+            "git pull --rebase origin",
+        ))
         .replace("git pull", "git pull --rebase origin")
 }
 
@@ -106,7 +173,14 @@ fn create_git_push_force() -> Box<dyn Rule> {
         .match_command("git push")
         .match_output("rejected")
         .priority(700)
-        .replace("git push", "git push --force-with-lease")
+        .describe("Force a rejected push, unless the remote changed since the last fetch")
+        .example(Example::new(
+            "git push origin main",
+            " ! [rejected]        main -> main (non-fast-forward)",
+            1,
+            "git push --force-with-lease origin main",
+        ))
+        .insert_flag("push", "--force-with-lease")
 }
 
 /// git_add_all: Add all files before commit
@@ -115,6 +189,13 @@ fn create_git_add_all() -> Box<dyn Rule> {
         .match_command("git commit")
         .match_output("fatal: your current branch is behind")
         .priority(800)
+        .describe("Stage every change before committing")
+        .example(Example::new(
+            "git commit -m fix",
+            "fatal: your current branch is behind 'origin/main'",
+            128,
+            "git add -A && git commit -m fix",
+        ))
         .replace("git commit", "git add -A && git commit")
 }
 
@@ -124,58 +205,158 @@ fn create_git_commit_amend() -> Box<dyn Rule> {
         .match_command("git commit")
         .match_output("nothing to commit")
         .priority(550)
+        .describe("Amend the last commit when there's nothing new to commit")
+        .example(Example::new("git commit", "nothing to commit, working tree clean", 1, "git commit --amend --no-edit"))
         .replace("git commit", "git commit --amend --no-edit")
 }
 
+/// git_stash: Stash local changes that would be overwritten, then retry.
+/// Pulls stay on the same branch, so the changes are restored afterwards.
+fn create_git_stash() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("git_stash")
+        .match_command_static(static_regex!(r"^git (checkout|switch|rebase|merge|pull)\b"))
+        .match_output_static(static_regex!(r"Please commit your changes or stash them"))
+        .priority(650)
+        .describe("Stash local changes that block a checkout, rebase, merge or pull")
+        .example(Example::new(
+            "git checkout main",
+            "error: Your local changes would be overwritten by checkout.\nPlease commit your changes or stash them.",
+            1,
+            "git stash && git checkout main",
+        ))
+        .undoable()
+        // Skipped when there was nothing to stash, see `undo::change_probe`
+        .rollback_for_segment(0, "git stash pop")
+        .replace_with(|original, captures| {
+            let stashed = format!("git stash && {}", original);
+            if captures.get(1).is_some_and(|verb| verb.as_str() == "pull") {
+                vec![format!("{} && git stash pop", stashed)]
+            } else {
+                vec![stashed]
+            }
+        })
+        .build_builtin()
+}
+
+/// git_not_command: `git comit` → `git commit`
+struct GitNotCommandRule;
+
+impl GitNotCommandRule {
+    /// Header git prints before its suggestions ("is" or "s are").
+    const SIMILAR: &'static str = "The most similar command";
+
+    /// Gets the mistyped subcommand from git's error, on whichever line of
+    /// the output it's on.
+    fn unknown_command(output: &str) -> Option<&str> {
+        output.lines().find_map(|line| {
+            let rest = line.strip_prefix("git: '")?;
+            let (typo, rest) = rest.split_once('\'')?;
+            rest.starts_with(" is not a git command").then_some(typo)
+        })
+    }
+}
+
+impl Rule for GitNotCommandRule {
+    fn name(&self) -> &str {
+        "git_not_command"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use git's own suggestion for a mistyped subcommand")
+            .example(Example::new(
+                "git comit",
+                "git: 'comit' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tcommit",
+                1,
+                "git commit",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.script.starts_with("git ")
+            && Self::unknown_command(&command.output).is_some()
+            && !listed_suggestions(&command.output, Self::SIMILAR).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::unknown_command(&command.output) else {
+            return vec![];
+        };
+
+        replace_with_suggestions(
+            &command.script,
+            typo,
+            listed_suggestions(&command.output, Self::SIMILAR),
+        )
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixture_output;
+    use crate::testing::RuleTester;
+    use std::path::Path;
 
     #[test]
     fn test_git_branch_delete_rule() {
         let rule = create_git_branch_delete();
         assert_eq!(rule.name(), "git_branch_delete");
 
-        let cmd = Command {
-            script: "git branch -d feature".to_string(),
-            output: "error: The branch 'feature' is not fully merged.".to_string(),
-            exit_code: 1,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("-D"));
+        RuleTester::boxed(rule)
+            .given("git branch -d feature", "error: The branch 'feature' is not fully merged.", 1)
+            .expect_correction_containing("-D")
+            .given("git branch -d main", "Deleted branch main", 0)
+            .expect_no_match()
+            .run();
     }
 
     #[test]
-    fn test_git_branch_delete_no_match() {
-        let rule = create_git_branch_delete();
-
-        let cmd = Command {
-            script: "git branch -d main".to_string(),
-            output: "Deleted branch main".to_string(),
-            exit_code: 0,
-        };
+    fn test_git_branch_exists_rule() {
+        let rule = create_git_branch_exists();
+        assert_eq!(rule.name(), "git_branch_exists");
 
-        assert!(!rule.matches(&cmd));
+        RuleTester::boxed(rule)
+            .given("git checkout feature", "error: pathspec 'feature' did not match any file(s) known to git", 1)
+            .expect_correction_containing("checkout -b")
+            .run();
     }
 
     #[test]
-    fn test_git_branch_exists_rule() {
-        let rule = create_git_branch_exists();
-        assert_eq!(rule.name(), "git_branch_exists");
+    fn test_git_branch_exists_verify() {
+        use crate::testing::fake_context;
+        use crate::{CorrectedCommand, Corrector};
 
-        let cmd = Command {
-            script: "git checkout feature".to_string(),
-            output: "error: pathspec 'feature' did not match any file(s) known to git".to_string(),
-            exit_code: 1,
-        };
+        let rule = create_git_branch_exists();
+        let fake = fake_context().output("git branch --format='%(refname:short)'", "main\nfeature\n", 0).build();
+        let context = fake.context();
+
+        let existing = CorrectedCommand::new("git checkout -b feature", 1000);
+        assert_eq!(rule.verify(&existing, &context), Verdict::Drop);
+        let new = CorrectedCommand::new("git checkout -b fix-login", 1000);
+        assert_eq!(rule.verify(&new, &context), Verdict::Keep);
+
+        // The branches are listed once, and only when verifying
+        let corrector = Corrector::with_rules(vec![create_git_branch_exists()], fake.context());
+        let cmd = Command::new("git checkout feature", "error: pathspec 'feature' did not match any file(s)", 1);
+        assert!(corrector.get_corrections(&cmd).is_empty());
+        fake.calls().assert_only(&["git branch --format"]);
+        assert_eq!(fake.calls().commands().len(), 1);
+    }
 
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("checkout -b"));
+    #[test]
+    fn test_git_branch_exists_does_not_probe_without_a_match() {
+        use crate::testing::fake_context;
+        use crate::Corrector;
+
+        let fake = fake_context().build();
+        let corrector = Corrector::with_rules(vec![create_git_branch_exists()], fake.context());
+        assert!(corrector.get_corrections(&Command::new("git checkout main", "Already on 'main'", 0)).is_empty());
+        assert!(fake.calls().commands().is_empty());
     }
 
     #[test]
@@ -183,16 +364,10 @@ mod tests {
         let rule = create_git_branch_0flag();
         assert_eq!(rule.name(), "git_branch_0flag");
 
-        let cmd = Command {
-            script: "git branch".to_string(),
-            output: "fatal: bad revision ''".to_string(),
-            exit_code: 128,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("-a"));
+        RuleTester::boxed(rule)
+            .given("git branch", "fatal: bad revision ''", 128)
+            .expect_correction_containing("-a")
+            .run();
     }
 
     #[test]
@@ -200,16 +375,13 @@ mod tests {
         let rule = create_git_push_set_upstream();
         assert_eq!(rule.name(), "git_push_set_upstream");
 
-        let cmd = Command {
-            script: "git push".to_string(),
-            output: "fatal: The current branch main has no upstream branch.".to_string(),
-            exit_code: 1,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("-u origin"));
+        RuleTester::boxed(rule)
+            .given("git push", "fatal: The current branch main has no upstream branch.", 1)
+            .expect_correction_containing("-u origin")
+            .given("git push", fixture_output!("git/push_no_upstream"), 128)
+            .expect_corrections(["git push -u origin"])
+            .expect_priority_lt(800)
+            .run();
     }
 
     #[test]
@@ -217,16 +389,10 @@ mod tests {
         let rule = create_git_pull_rebase();
         assert_eq!(rule.name(), "git_pull_rebase");
 
-        let cmd = Command {
-            script: "git pull".to_string(),
-            output: "Please specify which branch you want to merge with".to_string(),
-            exit_code: 1,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("--rebase"));
+        RuleTester::boxed(rule)
+            .given("git pull", "Please specify which branch you want to merge with", 1)
+            .expect_correction_containing("--rebase")
+            .run();
     }
 
     #[test]
@@ -234,16 +400,17 @@ mod tests {
         let rule = create_git_push_force();
         assert_eq!(rule.name(), "git_push_force");
 
-        let cmd = Command {
-            script: "git push".to_string(),
-            output: "error: failed to push some refs to origin\n[rejected]        main -> main (non-fast-forward)".to_string(),
-            exit_code: 1,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("--force-with-lease"));
+        RuleTester::boxed(rule)
+            .given(
+                "git push",
+                "error: failed to push some refs to origin\n[rejected]        main -> main (non-fast-forward)",
+                1,
+            )
+            .expect_corrections(["git push --force-with-lease"])
+            // The remote and branch stay after the flag
+            .given("git push origin feature/x", "! [rejected]        feature/x -> feature/x (fetch first)", 1)
+            .expect_corrections(["git push --force-with-lease origin feature/x"])
+            .run();
     }
 
     #[test]
@@ -251,16 +418,10 @@ mod tests {
         let rule = create_git_add_all();
         assert_eq!(rule.name(), "git_add_all");
 
-        let cmd = Command {
-            script: "git commit -m 'test'".to_string(),
-            output: "fatal: your current branch is behind 'origin/main' by 1 commit".to_string(),
-            exit_code: 1,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("git add -A"));
+        RuleTester::boxed(rule)
+            .given("git commit -m 'test'", "fatal: your current branch is behind 'origin/main' by 1 commit", 1)
+            .expect_correction_containing("git add -A")
+            .run();
     }
 
     #[test]
@@ -268,15 +429,91 @@ mod tests {
         let rule = create_git_commit_amend();
         assert_eq!(rule.name(), "git_commit_amend");
 
-        let cmd = Command {
-            script: "git commit --allow-empty".to_string(),
-            output: "On branch main\nnothing to commit, working tree clean".to_string(),
-            exit_code: 1,
-        };
+        RuleTester::boxed(rule)
+            .given("git commit --allow-empty", "On branch main\nnothing to commit, working tree clean", 1)
+            .expect_correction_containing("--amend")
+            .run();
+    }
+
+    #[test]
+    fn test_git_stash_rule() {
+        let rule = create_git_stash();
+        assert_eq!(rule.name(), "git_stash");
+
+        let cmd = Command::new(
+            "git checkout main",
+            "error: Your local changes would be overwritten by checkout.\nPlease commit your changes or stash them.",
+            1,
+        );
 
         assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("--amend"));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["git stash && git checkout main"]);
+    }
+
+    #[test]
+    fn test_git_stash_pull_restores_changes() {
+        let rule = create_git_stash();
+        let cmd = Command::new(
+            "git pull",
+            "error: Your local changes to the following files would be overwritten by merge:\n\tCargo.lock\nPlease commit your changes or stash them before you merge.\nAborting",
+            1,
+        );
+
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, "git stash && git pull && git stash pop");
+        assert_eq!(corrections[0].rollbacks, vec![(0, "git stash pop".to_string())]);
+    }
+
+    #[test]
+    fn test_git_stash_undo() {
+        let rule = create_git_stash();
+        assert_eq!(
+            rule.undo_for("git stash && git checkout main", Path::new("/nonexistent")).as_deref(),
+            Some("git checkout - && git stash pop")
+        );
+        // Rebases cannot be reverted with a single safe command
+        assert_eq!(rule.undo_for("git stash && git rebase main", Path::new("/nonexistent")), None);
+    }
+
+    #[test]
+    fn test_git_commit_amend_has_no_undo() {
+        let rule = create_git_commit_amend();
+        assert_eq!(rule.undo_for("git commit --amend --no-edit", Path::new("/nonexistent")), None);
+    }
+
+    #[test]
+    fn test_git_not_command_rule() {
+        let rules = git_typo_rules();
+        let rule = &rules[0];
+        assert_eq!(rule.name(), "git_not_command");
+
+        let cmd = Command::new(
+            "git comit -m 'fix'",
+            "git: 'comit' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tcommit\n",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["git commit -m 'fix'"]);
+    }
+
+    #[test]
+    fn test_git_not_command_several_suggestions() {
+        let rules = git_typo_rules();
+        let cmd = Command::new(
+            "git stat",
+            "git: 'stat' is not a git command. See 'git --help'.\n\nThe most similar commands are\n\tstash\n\tstatus\n",
+            1,
+        );
+
+        assert_eq!(rules[0].get_new_commands(&cmd), vec!["git stash", "git status"]);
+    }
+
+    #[test]
+    fn test_git_not_command_without_suggestions() {
+        let rules = git_typo_rules();
+        let cmd = Command::new("git xyzzy", "git: 'xyzzy' is not a git command. See 'git --help'.\n", 1);
+
+        assert!(!rules[0].matches(&cmd));
     }
 }
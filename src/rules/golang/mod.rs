@@ -0,0 +1,338 @@
+//! Go toolchain command correction rules.
+//!
+//! This module contains rules for common go mistakes:
+//! - Running go outside a module
+//! - Importing a package no required module provides
+//! - `go run cmd/server`, which names a standard library package, for a
+//!   directory of the module
+//! - goimports, or gofmt, not being installed
+//!
+//! A module root below the working directory is found with the same bounded
+//! search the monorepo rules use.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::monorepo::find_manifest_dirs;
+use super::util::{prefix_then_retry, rehash_after_install};
+use crate::{static_regex, Command, FailureKind, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Maximum number of module roots suggested.
+const MAX_MODULE_SUGGESTIONS: usize = 3;
+
+/// The package goimports is installed from.
+const GOIMPORTS_PACKAGE: &str = "golang.org/x/tools/cmd/goimports@latest";
+
+/// Executables every go rule requires.
+pub const EXECUTABLES: &[&str] = &["go", "gofmt", "goimports"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("go_no_module", 300),
+    ("go_get_missing", 200),
+    ("go_run_local_package", 300),
+    ("go_tools_install", 200),
+];
+
+/// Creates all go rules.
+///
+/// Module roots are searched for below the working directory of the
+/// context's shell.
+pub fn go_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    let mut rules: Vec<Box<dyn Rule>> = vec![
+        // go_no_module: Create a module, or change into one
+        Box::new(GoNoModuleRule::new(context)),
+        // go_get_missing: Add the missing module first
+        Box::new(GoGetMissingRule),
+        // go_run_local_package: Prefix the package directory with ./
+        create_go_run_local_package(),
+    ];
+    rules.extend(rehash_after_install(vec![
+        // go_tools_install: Install goimports first
+        create_go_tools_install(),
+    ]));
+    rules
+}
+
+/// go_no_module: `go build` → `go mod init app && go build`, or `cd
+/// services/gateway && go build`
+pub struct GoNoModuleRule {
+    context: Arc<RuleContext>,
+}
+
+impl GoNoModuleRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+}
+
+/// Gets a module path for a module in `dir`: the directory's name, with
+/// characters module paths can't have replaced.
+fn module_name(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_string_lossy();
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-._~".contains(c) { c } else { '-' })
+        .collect();
+    Some(name)
+}
+
+impl Rule for GoNoModuleRule {
+    fn name(&self) -> &str {
+        "go_no_module"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Create a go module, or change into the directory of one")
+            .example(Example::new(
+                "go build ./...",
+                "go: cannot find main module, but found .git/config in /home/dev/src/app\n\
+                 \tto create a module there, run:\n\tcd .. && go mod init",
+                1,
+                "cd .. && go mod init app",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("go")
+            && (command.output.contains("go.mod file not found in current directory")
+                || command.output.contains("cannot find main module"))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let cwd = self.context.shell().and_then(|shell| shell.cwd().ok());
+        let found_root = static_regex!(concat!(
+            r"cannot find main module, but found \S+ in (.+)\n",
+            r"\s*to create a module there, run:\n\s*(?:cd (\S+) && )?go mod init",
+        ))
+        .captures(&command.output);
+
+        let mut corrections: Vec<String> = Vec::new();
+        if let (Some(cwd), Some(_)) = (&cwd, &found_root) {
+            // go looks for go.mod upwards, so a module it missed can only be below
+            let roots = find_manifest_dirs(cwd, "go.mod").into_iter().take(MAX_MODULE_SUGGESTIONS);
+            corrections.extend(roots.map(|dir| {
+                let cd = Script::from_tokens(["cd".to_string(), dir.to_string_lossy().into_owned()]);
+                prefix_then_retry(&cd.to_string(), &command.script)
+            }));
+        }
+
+        let init = |dir: &Path| module_name(dir).map(|name| Script::from_tokens(["go", "mod", "init", &name]));
+        match found_root {
+            Some(captures) => {
+                let Some(init) = init(Path::new(captures[1].trim())) else {
+                    return corrections;
+                };
+                corrections.push(match captures.get(2) {
+                    Some(cd) => Script::from_tokens(["cd", cd.as_str()]).append(" && ", init).to_string(),
+                    None => prefix_then_retry(&init.to_string(), &command.script),
+                });
+            }
+            None => {
+                if let Some(init) = cwd.as_deref().and_then(init) {
+                    corrections.push(prefix_then_retry(&init.to_string(), &command.script));
+                }
+            }
+        }
+        corrections
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// go_get_missing: `go run .` → `go get github.com/google/uuid && go run .`
+struct GoGetMissingRule;
+
+impl GoGetMissingRule {
+    /// Gets the packages go says no required module provides, in order.
+    fn packages(output: &str) -> Vec<&str> {
+        let mut packages: Vec<&str> = Vec::new();
+        for captures in static_regex!(r"no required module provides package ([^\s;]+)").captures_iter(output) {
+            let package = captures.get(1).map_or("", |package| package.as_str());
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+        packages
+    }
+}
+
+impl Rule for GoGetMissingRule {
+    fn name(&self) -> &str {
+        "go_get_missing"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Add the modules providing missing packages with go get, then retry")
+            .example(Example::new(
+                "go run .",
+                "main.go:6:2: no required module provides package github.com/google/uuid; to add it:\n\
+                 \tgo get github.com/google/uuid",
+                1,
+                "go get github.com/google/uuid && go run .",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("go") && !Self::packages(&command.output).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let packages = Self::packages(&command.output);
+        if packages.is_empty() {
+            return vec![];
+        }
+        let get = Script::from_tokens(["go", "get"].into_iter().chain(packages));
+        vec![prefix_then_retry(&get.to_string(), &command.script)]
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+/// go_run_local_package: `go run cmd/server` → `go run ./cmd/server`
+fn create_go_run_local_package() -> Box<dyn Rule> {
+    // The package comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("go_run_local_package")
+        .match_output_static(static_regex!(r"package (\S+) is not in (?:std|GOROOT) \("))
+        .priority(300)
+        .describe("Prefix a package directory of the module with ./")
+        .example(Example::new(
+            "go run cmd/server",
+            "package cmd/server is not in std (/usr/local/go/src/cmd/server)",
+            1,
+            "go run ./cmd/server",
+        ))
+        .replace_with(|original, captures| {
+            let Some(package) = captures.get(1).map(|package| package.as_str()) else {
+                return vec![];
+            };
+            let script = Script::parse(original);
+            if script.executable() != Some("go") {
+                return vec![];
+            }
+            let Some(index) = script.tokens().iter().position(|token| token == package) else {
+                return vec![];
+            };
+            vec![script.replace_token_at(index, format!("./{}", package)).to_string()]
+        })
+        .build_builtin()
+}
+
+/// go_tools_install: `goimports -w .` → `go install
+/// golang.org/x/tools/cmd/goimports@latest && goimports -w .`
+fn create_go_tools_install() -> Box<dyn Rule> {
+    // goimports formats like gofmt does, so it stands in for a missing gofmt
+    RegexRuleBuilder::new("go_tools_install")
+        .match_command_static(static_regex!(r"^go(?:imports|fmt)\b"))
+        .match_failure_kind(FailureKind::NotFound)
+        .priority(200)
+        .describe("Install goimports with go install, then run it")
+        .example(Example::new(
+            "goimports -w .",
+            "bash: goimports: command not found",
+            127,
+            "go install golang.org/x/tools/cmd/goimports@latest && goimports -w .",
+        ))
+        .replace_with(|original, _captures| {
+            let retry = Script::parse(original).replace_token_at(0, "goimports");
+            let install = Script::from_tokens(["go", "install", GOIMPORTS_PACKAGE]);
+            vec![prefix_then_retry(&install.to_string(), &retry.to_string())]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const GO_NO_MODULE: &str = "go: go.mod file not found in current directory or any parent directory; see 'go help modules'\n";
+
+    const GO_MAIN_MODULE_IN_PARENT: &str = "go: cannot find main module, but found .git/config in /home/dev/src/app\n\tto create a module there, run:\n\tcd ../.. && go mod init\n";
+
+    const GO_MAIN_MODULE_HERE: &str = "go: cannot find main module, but found .git/config in /home/dev/src/my app\n\tto create a module there, run:\n\tgo mod init\n";
+
+    const GO_MISSING_PACKAGES: &str = "main.go:6:2: no required module provides package github.com/google/uuid; to add it:\n\tgo get github.com/google/uuid\nmain.go:7:2: no required module provides package golang.org/x/sync/errgroup; to add it:\n\tgo get golang.org/x/sync/errgroup\n";
+
+    const GO_NOT_IN_STD: &str = "package cmd/server is not in std (/usr/local/go/src/cmd/server)\n";
+
+    fn rule_in(dir: &str) -> GoNoModuleRule {
+        GoNoModuleRule::new(Arc::new(RuleContext::new(Box::new(MockShell::new().with_cwd(dir)))))
+    }
+
+    #[test]
+    fn test_go_no_module() {
+        let rule = rule_in("/home/dev/src/scratch");
+        let cmd = Command::new("go run .", GO_NO_MODULE, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["go mod init scratch && go run ."]);
+    }
+
+    #[test]
+    fn test_go_no_module_found_root() {
+        let rule = rule_in("/home/dev/src/app/cmd/server");
+        let cmd = Command::new("go build", GO_MAIN_MODULE_IN_PARENT, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cd ../.. && go mod init app"]);
+
+        let cmd = Command::new("go build", GO_MAIN_MODULE_HERE, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["go mod init my-app && go build"]);
+    }
+
+    #[test]
+    fn test_go_no_module_in_a_checkout_of_modules() {
+        let rule = rule_in(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/monorepo"));
+        let output = GO_MAIN_MODULE_HERE.replace("/home/dev/src/my app", "/home/dev/src/monorepo");
+        let cmd = Command::new("go test ./...", output, 1);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["cd services/gateway && go test ./...", "go mod init monorepo && go test ./..."]
+        );
+    }
+
+    #[test]
+    fn test_go_get_missing() {
+        let rule = GoGetMissingRule;
+        let cmd = Command::new("go run main.go", GO_MISSING_PACKAGES, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["go get github.com/google/uuid golang.org/x/sync/errgroup && go run main.go"]
+        );
+        assert!(!rule.matches(&Command::new("go vet", "main.go:3:1: expected 'package', found 'EOF'", 1)));
+    }
+
+    #[test]
+    fn test_go_run_local_package() {
+        let rule = create_go_run_local_package();
+        let cmd = Command::new("go build -o bin/server cmd/server", GO_NOT_IN_STD, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["go build -o bin/server ./cmd/server"]);
+        // Go before 1.18 named GOROOT instead
+        let cmd = Command::new("go run server", "package server is not in GOROOT (/usr/lib/go/src/server)\n", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["go run ./server"]);
+    }
+
+    #[test]
+    fn test_go_tools_install() {
+        let rules = go_rules(Arc::new(RuleContext::default()));
+        let rule = rules.iter().find(|rule| rule.name() == "go_tools_install").unwrap();
+        let cmd = Command::new("gofmt -l .", "bash: gofmt: command not found", 127);
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, "go install golang.org/x/tools/cmd/goimports@latest && goimports -l .");
+        assert_eq!(corrections[0].side_effect, Some(crate::SideEffect::Rehash));
+        assert!(!rule.matches(&Command::new("goimports -w .", "main.go:1:1: expected 'package'", 2)));
+    }
+}
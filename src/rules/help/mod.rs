@@ -0,0 +1,354 @@
+//! Fallback rules for usage errors.
+//!
+//! When a command fails with a usage error (exit code 2, or output that
+//! starts with `usage:`) and no other rule has a correction, these offer a
+//! way to find the right invocation:
+//! - The first subcommand listed in the usage, when the command has none
+//! - `<cmd> --help`
+//! - `man <cmd>`, if the command has a manual page
+//!
+//! They are [fallbacks](crate::Rule::is_fallback), so they never appear
+//! next to a real correction.
+
+use super::util::listed_suggestions;
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::privilege;
+use crate::shell::PROBE_TIMEOUT;
+use crate::{static_regex, Command, CorrectedCommand, FailureKind, Rule, RuleContext, Script, Verdict};
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[("usage_subcommand", 500), ("help_flag", 800), ("man_page", 900)];
+
+/// Creates all usage error fallback rules.
+pub fn help_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // usage_subcommand: Add the first subcommand the usage lists
+        Box::new(UsageSubcommandRule),
+        // help_flag: Show the command's help
+        Box::new(HelpFlagRule),
+        // man_page: Open the command's manual page
+        Box::new(ManPageRule),
+    ]
+}
+
+/// Returns true if `command` failed because it was used wrongly.
+fn is_usage_error(command: &Command) -> bool {
+    let output = command.output.trim_start();
+    if output.get(..6).is_some_and(|start| start.eq_ignore_ascii_case("usage:")) {
+        return true;
+    }
+    // ls and grep also exit with 2 for files that don't exist
+    command.failure_kind() == FailureKind::UsageError && !output.contains("No such file or directory")
+}
+
+/// Gets the position of the command a script runs, past variable
+/// assignments and `sudo`.
+fn command_index(script: &Script) -> Option<usize> {
+    script
+        .tokens()
+        .iter()
+        .position(|token| !Script::is_assignment(token) && !privilege::is_escalation(token))
+}
+
+/// Gets the command a usage error is about, unless it's already asking for
+/// help.
+fn failed_command(command: &Command) -> Option<String> {
+    if !is_usage_error(command) {
+        return None;
+    }
+    let script = Script::parse(&command.script);
+    let index = command_index(&script)?;
+    let tokens = &script.tokens()[index..];
+    if tokens.iter().any(|token| token == "--help" || token == "-h") {
+        return None;
+    }
+    Some(tokens[0].clone())
+}
+
+/// usage_subcommand: `pipx` → `pipx install`
+pub struct UsageSubcommandRule;
+
+impl UsageSubcommandRule {
+    /// Gets the text after `usage:` on the usage line.
+    fn usage_line(output: &str) -> Option<&str> {
+        output.lines().map(str::trim).find_map(|line| {
+            let (prefix, usage) = line.split_at_checked(6)?;
+            prefix.eq_ignore_ascii_case("usage:").then(|| usage.trim())
+        })
+    }
+
+    /// Gets the first subcommand listed for `program`: the first of the
+    /// choices in its usage line (`{init,build}`, `<start|stop>`), or the
+    /// first entry under a `Commands:` header when the usage takes a
+    /// `COMMAND`.
+    fn first_subcommand(output: &str, program: &str) -> Option<String> {
+        let usage = Self::usage_line(output)?;
+        let name = program.rsplit('/').next()?;
+        if usage.split_whitespace().next()?.rsplit('/').next() != Some(name) {
+            return None;
+        }
+
+        let choices =
+            static_regex!(r"\{([\w.-]+(?:,[\w.-]+)+)\}|<([\w.-]+(?:\|[\w.-]+)+)>|\(([\w.-]+(?:\|[\w.-]+)+)\)");
+        if let Some(captures) = choices.captures(usage) {
+            let choices = captures.get(1).or_else(|| captures.get(2)).or_else(|| captures.get(3))?.as_str();
+            return choices.split([',', '|']).next().map(str::to_string);
+        }
+        if !static_regex!(r"(?i)\bcommand\b").is_match(usage) {
+            return None;
+        }
+        listed_suggestions(output, "Commands:")
+            .into_iter()
+            .filter_map(|entry| entry.split_whitespace().next())
+            .map(|entry| entry.trim_end_matches(','))
+            .find(|entry| *entry != "help")
+            .map(str::to_string)
+    }
+
+    /// Gets the subcommand to add to `command`, if it has no arguments but
+    /// options.
+    fn missing_subcommand(command: &Command) -> Option<String> {
+        let program = failed_command(command)?;
+        let script = Script::parse(&command.script);
+        let index = command_index(&script)?;
+        if script.tokens()[index + 1..].iter().any(|token| !token.starts_with('-')) {
+            return None;
+        }
+        Self::first_subcommand(&command.output, &program)
+    }
+}
+
+impl Rule for UsageSubcommandRule {
+    fn name(&self) -> &str {
+        "usage_subcommand"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Add the first subcommand listed in the usage of a command run without one")
+            .example(Example::new(
+                "pipx",
+                "usage: pipx [-h] [--version] {install,uninstall,list,run} ...\n\
+                 pipx: error: the following arguments are required: command",
+                2,
+                "pipx install",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Self::missing_subcommand(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(subcommand) = Self::missing_subcommand(command) else {
+            return vec![];
+        };
+        vec![Script::parse(&command.script).push_token(subcommand).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        500
+    }
+
+    fn is_fallback(&self) -> bool {
+        true
+    }
+}
+
+/// help_flag: `tar` → `tar --help`
+pub struct HelpFlagRule;
+
+impl Rule for HelpFlagRule {
+    fn name(&self) -> &str {
+        "help_flag"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Show the help of a command that was used wrongly")
+            .example(Example::new(
+                "tar archive.tar",
+                "tar: You must specify one of the '-Acdtrux', '--delete' or '--test-label' options\n\
+                 Try 'tar --help' or 'tar --usage' for more information.",
+                2,
+                "tar --help",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        failed_command(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(program) = failed_command(command) else {
+            return vec![];
+        };
+        vec![Script::from_tokens([program, "--help".to_string()]).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        800
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+
+    fn is_fallback(&self) -> bool {
+        true
+    }
+}
+
+/// man_page: `tar` → `man tar`
+///
+/// [`Rule::verify`] drops the correction when `man -w` finds no page.
+pub struct ManPageRule;
+
+impl ManPageRule {
+    /// Gets the name to look up: the command, if it's a name rather than a
+    /// path and not `man` itself.
+    fn page(command: &Command) -> Option<String> {
+        failed_command(command).filter(|program| !program.contains('/') && program != "man")
+    }
+}
+
+impl Rule for ManPageRule {
+    fn name(&self) -> &str {
+        "man_page"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Open the manual page of a command that was used wrongly")
+            .example(Example::new(
+                "tar archive.tar",
+                "tar: You must specify one of the '-Acdtrux', '--delete' or '--test-label' options\n\
+                 Try 'tar --help' or 'tar --usage' for more information.",
+                2,
+                "man tar",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Self::page(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(page) = Self::page(command) else {
+            return vec![];
+        };
+        vec![Script::from_tokens(["man".to_string(), page]).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        900
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+
+    fn is_fallback(&self) -> bool {
+        true
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        let Some(shell) = context.shell() else {
+            return Verdict::Keep;
+        };
+        let Some(page) = Script::parse(&correction.script).tokens().last().cloned() else {
+            return Verdict::Keep;
+        };
+        let lookup = Script::from_tokens(["man".to_string(), "-w".to_string(), page]).to_string();
+        match shell.execute_with_timeout(&lookup, PROBE_TIMEOUT) {
+            Ok(output) if !output.success => Verdict::Drop,
+            _ => Verdict::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const ARGPARSE_USAGE: &str = "usage: pipx [-h] [--version] {install,uninstall,list,run} ...\n\
+                                  pipx: error: the following arguments are required: command";
+
+    const CLAP_USAGE: &str = "Manage deployments\n\nUsage: deployctl [OPTIONS] <COMMAND>\n\n\
+                              Commands:\n  help     Print this message\n  rollout  Start a rollout\n  \
+                              status   Show the status\n\nOptions:\n  -v, --verbose  Verbose output\n";
+
+    #[test]
+    fn test_usage_error_detection() {
+        assert!(is_usage_error(&Command::new("tar", "tar: You must specify one of the options", 2)));
+        assert!(is_usage_error(&Command::new("mytool", "Usage: mytool FILE", 1)));
+        assert!(is_usage_error(&Command::new("mytool", "  USAGE: mytool FILE", 64)));
+        assert!(!is_usage_error(&Command::new("ls nope", "ls: cannot access 'nope': No such file or directory", 2)));
+        assert!(!is_usage_error(&Command::new("false", "", 1)));
+    }
+
+    #[test]
+    fn test_usage_subcommand_from_choices() {
+        let cmd = Command::new("pipx --verbose", ARGPARSE_USAGE, 2);
+        assert!(UsageSubcommandRule.matches(&cmd));
+        assert_eq!(UsageSubcommandRule.get_new_commands(&cmd), vec!["pipx --verbose install"]);
+
+        let cmd = Command::new("./svc", "Usage: ./svc <start|stop|status>", 1);
+        assert_eq!(UsageSubcommandRule.get_new_commands(&cmd), vec!["./svc start"]);
+        let cmd = Command::new("sudo service-ctl", "usage: service-ctl (enable|disable) NAME", 2);
+        assert_eq!(UsageSubcommandRule.get_new_commands(&cmd), vec!["sudo service-ctl enable"]);
+    }
+
+    #[test]
+    fn test_usage_subcommand_from_commands_list() {
+        let cmd = Command::new("deployctl", CLAP_USAGE, 2);
+        assert_eq!(UsageSubcommandRule.get_new_commands(&cmd), vec!["deployctl rollout"]);
+    }
+
+    #[test]
+    fn test_usage_subcommand_needs_a_missing_subcommand() {
+        // An argument was given, so it isn't obviously missing
+        assert!(!UsageSubcommandRule.matches(&Command::new("pipx instal", ARGPARSE_USAGE, 2)));
+        // The usage is of another program
+        assert!(!UsageSubcommandRule.matches(&Command::new("pip", ARGPARSE_USAGE, 2)));
+        // Nothing is listed
+        assert!(!UsageSubcommandRule.matches(&Command::new("cp", "usage: cp [-R] source target", 64)));
+    }
+
+    #[test]
+    fn test_help_flag() {
+        let cmd = Command::new("LANG=C tar archive.tar", "tar: You must specify one of the options", 2);
+        assert!(HelpFlagRule.matches(&cmd));
+        assert_eq!(HelpFlagRule.get_new_commands(&cmd), vec!["tar --help"]);
+
+        assert!(!HelpFlagRule.matches(&Command::new("tar --help", "usage: tar", 2)));
+        assert!(!HelpFlagRule.matches(&Command::new("deployctl rollout", "error: rollout failed", 1)));
+    }
+
+    #[test]
+    fn test_man_page() {
+        let cmd = Command::new("sudo tar archive.tar", "tar: You must specify one of the options", 2);
+        assert_eq!(ManPageRule.get_new_commands(&cmd), vec!["man tar"]);
+
+        assert!(!ManPageRule.matches(&Command::new("./build.sh -x", "usage: build.sh", 2)));
+        assert!(!ManPageRule.matches(&Command::new("man", "What manual page do you want?", 1)));
+    }
+
+    #[test]
+    fn test_man_page_verify_needs_a_page() {
+        let shell = MockShell::new().with_output("man -w tar", "/usr/share/man/man1/tar.1.gz", 0);
+        let context = RuleContext::new(Box::new(shell));
+
+        assert_eq!(ManPageRule.verify(&CorrectedCommand::new("man tar", 900), &context), Verdict::Keep);
+        assert_eq!(ManPageRule.verify(&CorrectedCommand::new("man deployctl", 900), &context), Verdict::Drop);
+    }
+
+    #[test]
+    fn test_help_rules_are_fallbacks() {
+        let rules = help_rules();
+        assert!(rules.iter().all(|rule| rule.is_fallback()));
+        let names: Vec<_> = rules.iter().map(|rule| rule.name()).collect();
+        assert_eq!(names, RULES.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,396 @@
+//! Shell history based rules.
+//!
+//! This module contains rules that draw on the user's own shell history:
+//! - Re-running a similar command that previously succeeded
+//! - Re-running the previous full command with sudo
+
+use super::{RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::privilege;
+use crate::{Command, FailureKind, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// Minimum similarity for a history entry to be suggested.
+///
+/// [`similarity`] is one minus the Damerau-Levenshtein distance over the
+/// longer length, so a typo or two in a command scores around 0.9 (`git
+/// stauts` and `git status`, `make tset` and `make test`), while another
+/// subcommand of the same executable scores below 0.6 (`git log` and `git
+/// add .` 0.44, `docker ps -a` and `docker images` 0.54). Near neighbours
+/// such as `git push` and `git pull` (0.75) still pass, and are left to the
+/// rule's low priority.
+const MIN_SIMILARITY: f64 = 0.6;
+
+/// Output fragments that indicate the command lacked privileges.
+const PERMISSION_PATTERNS: &[&str] = &[
+    "Permission denied",
+    "Operation not permitted",
+    "are you root?",
+    "must be run as root",
+    "must be root",
+    "EACCES",
+];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("sudo_previous", 50),
+    ("history_search", 5000),
+];
+
+/// Creates all history-based rules.
+///
+/// Only the most recent `scan_limit` history entries are considered.
+pub fn history_rules(context: Arc<RuleContext>, scan_limit: usize) -> Vec<Box<dyn Rule>> {
+    vec![
+        // sudo_previous: Re-run the previous full command with sudo
+        Box::new(SudoPreviousRule::new(Arc::clone(&context))),
+        // history_search: Re-run a similar past command
+        Box::new(HistorySearchRule::new(context, scan_limit)),
+    ]
+}
+
+/// Returns the first token of a script.
+fn executable(script: &str) -> Option<&str> {
+    script.split_whitespace().next()
+}
+
+/// history_search: Suggest a similar command from history with the same executable.
+///
+/// Entries are compared with [`similarity`] rather than the skim
+/// [`FuzzyMatcher`](crate::fuzzy::FuzzyMatcher) the selector uses: skim only
+/// matches the query as a subsequence, so a transposed or mistyped letter,
+/// which is what a failed command usually has, matches nothing at all.
+pub struct HistorySearchRule {
+    context: Arc<RuleContext>,
+    scan_limit: usize,
+}
+
+impl HistorySearchRule {
+    /// Creates the rule, scanning at most `scan_limit` recent history entries.
+    pub fn new(context: Arc<RuleContext>, scan_limit: usize) -> Self {
+        Self {
+            context,
+            scan_limit,
+        }
+    }
+
+    /// Gets the history entries this rule scans, most recent first.
+    fn recent_history(&self) -> &[String] {
+        self.context.history().most_recent(self.scan_limit)
+    }
+
+    /// Finds the most similar history entry, preferring the most recent on ties.
+    fn best_match(&self, script: &str) -> Option<&str> {
+        let script = script.trim();
+        let executable = executable(script)?;
+
+        let mut best: Option<(&str, f64)> = None;
+        for entry in self.recent_history() {
+            let entry = entry.trim();
+            if entry == script || entry.split_whitespace().next() != Some(executable) {
+                continue;
+            }
+
+            let score = similarity(entry, script);
+            if score >= MIN_SIMILARITY && best.is_none_or(|(_, s)| score > s) {
+                best = Some((entry, score));
+            }
+        }
+
+        best.map(|(entry, _)| entry)
+    }
+}
+
+impl Rule for HistorySearchRule {
+    fn name(&self) -> &str {
+        "history_search"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Re-run a similar command with the same executable from your shell history")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() != FailureKind::Success && self.best_match(&command.script).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.best_match(&command.script)
+            .map(|entry| vec![entry.to_string()])
+            .unwrap_or_default()
+    }
+
+    fn priority(&self) -> i32 {
+        // Low priority so that specific rules win
+        5000
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+}
+
+/// sudo_previous: `sudo !!` for a failed command that was a retyped fragment
+/// of the previous history entry.
+pub struct SudoPreviousRule {
+    context: Arc<RuleContext>,
+}
+
+impl SudoPreviousRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Finds the previous history entry, skipping the failed script itself
+    /// in case the shell hook already recorded it.
+    fn previous_command(&self, script: &str) -> Option<&str> {
+        self.context
+            .history()
+            .entries()
+            .iter()
+            .map(|entry| entry.trim())
+            .find(|entry| !entry.is_empty() && *entry != script)
+    }
+
+    /// Returns the previous full command if `script` is a fragment of it.
+    fn fragment_of_previous(&self, script: &str) -> Option<&str> {
+        let script = script.trim();
+        let previous = self.previous_command(script)?;
+
+        if executable(previous).is_some_and(privilege::is_escalation) || !is_fragment(script, previous) {
+            return None;
+        }
+
+        Some(previous)
+    }
+}
+
+/// Checks whether `fragment` is a retyped, shorter or slightly mistyped start of `full`.
+fn is_fragment(fragment: &str, full: &str) -> bool {
+    if fragment.is_empty() || fragment.len() >= full.len() {
+        return false;
+    }
+
+    let fragment_tokens = Script::parse(fragment);
+    let full_tokens = Script::parse(full);
+    if full_tokens.tokens().starts_with(fragment_tokens.tokens()) {
+        return true;
+    }
+
+    // Compare a mistyped fragment against the same number of leading tokens
+    let prefix = Script::from_tokens(full_tokens.tokens().iter().take(fragment_tokens.tokens().len()));
+    fragment_tokens.executable() == full_tokens.executable()
+        && similarity(&fragment_tokens.to_string(), &prefix.to_string()) >= MIN_SIMILARITY
+}
+
+impl Rule for SudoPreviousRule {
+    fn name(&self) -> &str {
+        "sudo_previous"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Re-run the whole previous command with sudo when only part of it was retyped")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        PERMISSION_PATTERNS
+            .iter()
+            .any(|pattern| command.output.contains(pattern))
+            && self.fragment_of_previous(&command.script).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.fragment_of_previous(&command.script)
+            .map(|previous| vec![privilege::escalate(Script::parse(previous)).to_string()])
+            .unwrap_or_default()
+    }
+
+    fn priority(&self) -> i32 {
+        // Beats sudo_permission_denied, which would only sudo the fragment
+        50
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::{BashShell, Shell};
+    use std::path::{Path, PathBuf};
+
+    /// Writes a synthetic 5000-line history file (oldest first) and returns its path.
+    ///
+    /// The only `git status` entry sits 1500 entries before the most recent one.
+    fn write_synthetic_history(name: &str) -> PathBuf {
+        let mut lines: Vec<String> = (0..5000).map(|i| format!("echo line{}", i)).collect();
+        lines[5000 - 1500] = "git status".to_string();
+        lines[5000 - 10] = "cargo build --release".to_string();
+
+        let path = std::env::temp_dir().join(format!("ftf_history_{}_{}", name, std::process::id()));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    fn context_with_history_file(path: &Path) -> Arc<RuleContext> {
+        let mut shell = BashShell::new().unwrap();
+        shell
+            .set_env("HISTFILE".to_string(), path.display().to_string())
+            .unwrap();
+        Arc::new(RuleContext::new(Box::new(shell)))
+    }
+
+    fn context(history: &[&str]) -> Arc<RuleContext> {
+        Arc::new(RuleContext::from_history(
+            history.iter().map(|s| s.to_string()).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_history_search_suggests_similar_command() {
+        let path = write_synthetic_history("similar");
+        let context = context_with_history_file(&path);
+        let rule = HistorySearchRule::new(context, 2000);
+
+        let cmd = Command::new("git stauts", "git: 'stauts' is not a git command.", 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["git status"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_history_search_respects_scan_limit() {
+        let path = write_synthetic_history("limit");
+        let context = context_with_history_file(&path);
+        let rule = HistorySearchRule::new(context, 1000);
+
+        assert_eq!(rule.recent_history().len(), 1000);
+
+        // The matching entry is older than the 1000 most recent entries
+        let cmd = Command::new("git stauts", "", 1);
+        assert!(!rule.matches(&cmd));
+
+        // Recent entries are still found
+        let cmd = Command::new("cargo biuld --release", "", 101);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cargo build --release"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_history_search_skips_failed_script() {
+        let rule = HistorySearchRule::new(context(&["git stauts"]), 1000);
+
+        let cmd = Command::new("git stauts", "", 1);
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_history_search_requires_same_executable() {
+        let rule = HistorySearchRule::new(context(&["gitk status"]), 1000);
+
+        let cmd = Command::new("git stauts", "", 1);
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_history_search_prefers_closest_match() {
+        let rule = HistorySearchRule::new(context(&["git stash", "git status"]), 1000);
+
+        let cmd = Command::new("git stauts", "", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["git status"]);
+    }
+
+    #[test]
+    fn test_history_search_threshold() {
+        // Skim's subsequence matching finds nothing for a transposition
+        let matcher = crate::fuzzy::FuzzyMatcher::new();
+        assert!(matcher.find_best_match("git stauts", &["git status"]).is_none());
+
+        let rule = HistorySearchRule::new(context(&["git log", "docker images", "git status"]), 1000);
+        assert_eq!(rule.get_new_commands(&Command::new("git stauts", "", 1)), vec!["git status"]);
+        assert!(!rule.matches(&Command::new("git add .", "", 1)));
+        assert!(!rule.matches(&Command::new("docker ps -a", "", 1)));
+    }
+
+    #[test]
+    fn test_history_search_low_priority() {
+        let rule = HistorySearchRule::new(context(&[]), 1000);
+        assert_eq!(rule.name(), "history_search");
+        assert!(rule.priority() > 1000);
+        assert!(!rule.requires_output());
+    }
+
+    #[test]
+    fn test_sudo_previous_fragment_of_full_command() {
+        let rule = SudoPreviousRule::new(context(&[
+            "make install",
+            "make install PREFIX=/usr/local",
+        ]));
+        // The hook recorded the fragment; the entry before it is the full command
+        let cmd = Command::new(
+            "make install",
+            "install: cannot create regular file '/usr/local/bin/tool': Permission denied",
+            2,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["sudo make install PREFIX=/usr/local"]
+        );
+    }
+
+    #[test]
+    fn test_sudo_previous_mistyped_fragment() {
+        let rule = SudoPreviousRule::new(context(&["apt install vim git curl"]));
+        let cmd = Command::new(
+            "apt instal vim",
+            "E: Could not open lock file /var/lib/dpkg/lock - open (13: Permission denied)",
+            100,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo apt install vim git curl"]);
+    }
+
+    #[test]
+    fn test_sudo_previous_unrelated_previous_command() {
+        let rule = SudoPreviousRule::new(context(&["ls -la /tmp"]));
+        let cmd = Command::new("apt update", "E: Could not open lock file (13: Permission denied)", 100);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_sudo_previous_ignores_fragment_inside_chain() {
+        // Only `make` was retyped, so the `rm` before it mustn't be run again
+        let rule = SudoPreviousRule::new(context(&["rm -rf build/ && make"]));
+        let cmd = Command::new("make", "make: /usr/local/bin: Permission denied", 2);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_sudo_previous_requires_permission_error() {
+        let rule = SudoPreviousRule::new(context(&["make install PREFIX=/usr/local"]));
+        let cmd = Command::new("make install", "make: *** No rule to make target 'install'.", 2);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_sudo_previous_skips_previous_sudo() {
+        let rule = SudoPreviousRule::new(context(&["sudo make install PREFIX=/usr/local"]));
+        let cmd = Command::new("make install", "Permission denied", 2);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_history_rules_exist() {
+        let rules = history_rules(context(&[]), 1000);
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["sudo_previous", "history_search"]);
+    }
+}
@@ -0,0 +1,86 @@
+//! Index of rules by the executable their commands start with.
+//!
+//! Most regex rules only match commands that run one executable, such as
+//! `^git\s+push`. Their [`Rule::leading_token`] files them under that word,
+//! so a command is only checked against the rules for its executable and the
+//! rules without a leading token.
+
+use crate::{Command, Rule};
+use std::collections::HashMap;
+
+/// Positions of a registry's rules, by leading token.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleIndex {
+    by_token: HashMap<String, Vec<usize>>,
+    any: Vec<usize>,
+}
+
+impl RuleIndex {
+    /// Indexes `rules` by their position.
+    pub fn new(rules: &[Box<dyn Rule>]) -> Self {
+        let mut index = Self::default();
+        for (position, rule) in rules.iter().enumerate() {
+            match rule.leading_token() {
+                Some(token) => index.by_token.entry(token.to_string()).or_default().push(position),
+                None => index.any.push(position),
+            }
+        }
+        index
+    }
+
+    /// Gets the positions of the rules that could match `command`, in
+    /// registration order.
+    pub fn lookup(&self, command: &Command) -> Vec<usize> {
+        let bucket = command
+            .executable()
+            .and_then(|executable| self.by_token.get(executable))
+            .map_or(&[][..], Vec::as_slice);
+
+        // Both lists are sorted, so merging them keeps the rules' order
+        let mut positions = Vec::with_capacity(bucket.len() + self.any.len());
+        let (mut i, mut j) = (0, 0);
+        while i < bucket.len() && j < self.any.len() {
+            if bucket[i] < self.any[j] {
+                positions.push(bucket[i]);
+                i += 1;
+            } else {
+                positions.push(self.any[j]);
+                j += 1;
+            }
+        }
+        positions.extend_from_slice(&bucket[i..]);
+        positions.extend_from_slice(&self.any[j..]);
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RegexRuleBuilder, SimpleRuleBuilder};
+
+    fn regex_rule(name: &str, pattern: &str) -> Box<dyn Rule> {
+        RegexRuleBuilder::new(name)
+            .match_command_regex(pattern)
+            .unwrap()
+            .replace_simple("fixed")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lookup_keeps_registration_order() {
+        let rules = vec![
+            regex_rule("git_push", r"^git\s+push"),
+            SimpleRuleBuilder::new("sudo").match_output("Permission denied").prepend("sudo"),
+            regex_rule("apt_install", r"^apt\s+install"),
+            regex_rule("git_pull", r"^git\s+pull"),
+            regex_rule("unanchored", r"docker (\S+)"),
+        ];
+        let index = RuleIndex::new(&rules);
+
+        assert_eq!(index.lookup(&Command::new("git push", "", 1)), vec![0, 1, 3, 4]);
+        assert_eq!(index.lookup(&Command::new("apt install vim", "", 1)), vec![1, 2, 4]);
+        assert_eq!(index.lookup(&Command::new("sudo git push", "", 1)), vec![1, 4]);
+        assert_eq!(index.lookup(&Command::new("", "", 1)), vec![1, 4]);
+    }
+}
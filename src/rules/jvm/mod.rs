@@ -0,0 +1,635 @@
+//! Java, Gradle and Maven command correction rules.
+//!
+//! This module contains rules for common JVM build mistakes:
+//! - `mvn` run without a goal
+//! - Running gradle or mvn when the project ships its own wrapper script
+//! - Running on a JDK too old or too new for the class files involved
+//! - Mistyped Gradle tasks, matched against `gradle tasks --all`
+//!
+//! JDKs are found among the installs of SDKMAN! and asdf, and the wrapper
+//! and tasks through the context's shell.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::prefix_then_retry;
+use crate::fuzzy::similarity;
+use crate::{static_regex, Command, FailureKind, RegexRuleBuilder, Rule, RuleContext, Script, Shell, SideEffect};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Build tools, and the wrapper script a project ships in their place.
+const WRAPPERS: &[(&str, &str)] = &[("gradle", "gradlew"), ("mvn", "mvnw")];
+
+/// Difference between a class file's major version and the Java release
+/// that writes it: Java 8 writes 52, Java 21 writes 65.
+const CLASS_FILE_VERSION_OFFSET: u32 = 44;
+
+/// Maximum number of JDKs suggested.
+const MAX_JDK_SUGGESTIONS: usize = 3;
+
+/// Minimum similarity for a Gradle task to be suggested.
+const MIN_TASK_SIMILARITY: f64 = 0.5;
+
+/// Maximum number of Gradle tasks suggested.
+const MAX_TASK_SUGGESTIONS: usize = 3;
+
+/// How long listing Gradle's tasks may take. Gradle configures the whole
+/// build to list them, which is slower than other probes.
+const TASKS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("mvn_no_goals", 300),
+    ("build_wrapper", 150),
+    ("java_version_mismatch", 200),
+    ("gradle_unknown_task", 300),
+];
+
+/// Creates all Java, Gradle and Maven rules.
+///
+/// Wrappers are looked up from the working directory of the context's shell,
+/// JDKs under its `$HOME`, and Gradle tasks are listed through it.
+pub fn jvm_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // mvn_no_goals: Run the usual clean install
+        create_mvn_no_goals(),
+        // build_wrapper: Run the project's gradlew or mvnw
+        Box::new(BuildWrapperRule::new(Arc::clone(&context))),
+        // java_version_mismatch: Switch to an installed JDK of the right version
+        Box::new(JavaVersionMismatchRule::new(Arc::clone(&context))),
+        // gradle_unknown_task: Use the closest task from `gradle tasks --all`
+        Box::new(GradleUnknownTaskRule::new(context)),
+    ]
+}
+
+/// mvn_no_goals: `mvn` → `mvn clean install`
+fn create_mvn_no_goals() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("mvn_no_goals")
+        .match_command_static(static_regex!(r"^(?:\S*/)?mvnw?\b"))
+        .match_output_static(static_regex!(r"No goals have been specified for this build"))
+        .priority(300)
+        .describe("Run Maven's clean install when no goal was given")
+        .example(Example::new(
+            "mvn",
+            "[ERROR] No goals have been specified for this build. You must specify a valid lifecycle phase or a goal",
+            1,
+            "mvn clean install",
+        ))
+        .replace_with(|original, _captures| {
+            vec![Script::parse(original).push_token("clean").push_token("install").to_string()]
+        })
+        .build_builtin()
+}
+
+/// build_wrapper: `gradle build` → `./gradlew build`; `mvn test` →
+/// `../mvnw test` from a module of the project
+pub struct BuildWrapperRule {
+    context: Arc<RuleContext>,
+}
+
+impl BuildWrapperRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Finds the wrapper replacing the command's build tool in the working
+    /// directory or above it: how to run it from there, and its path.
+    fn wrapper(&self, command: &Command) -> Option<(String, PathBuf)> {
+        let script = Script::parse(&command.script);
+        let (_, name) = WRAPPERS.iter().find(|(tool, _)| script.executable() == Some(*tool))?;
+        let cwd = self.context.shell()?.cwd().ok()?;
+        cwd.ancestors().enumerate().find_map(|(depth, dir)| {
+            let path = dir.join(name);
+            let relative = match depth {
+                0 => format!("./{}", name),
+                _ => format!("{}{}", "../".repeat(depth), name),
+            };
+            path.is_file().then_some((relative, path))
+        })
+    }
+}
+
+impl Rule for BuildWrapperRule {
+    fn name(&self) -> &str {
+        "build_wrapper"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            failure_kind: Some(FailureKind::NotFound),
+            ..RuleMetadata::of(self)
+        }
+        .describe("Run the project's gradlew or mvnw when gradle or mvn isn't installed")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() == FailureKind::NotFound
+            && Script::parse(&command.script)
+                .executable()
+                .is_some_and(|executable| WRAPPERS.iter().any(|(tool, _)| *tool == executable))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some((wrapper, _)) = self.wrapper(command) else {
+            return vec![];
+        };
+        vec![Script::parse(&command.script).replace_token_at(0, wrapper).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        150
+    }
+
+    fn side_effect_for(&self, command: &Command, _correction: &str) -> Option<SideEffect> {
+        // Wrappers checked out without the executable bit are common
+        let (wrapper, path) = self.wrapper(command)?;
+        let executable = std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0);
+        let chmod = Script::from_tokens(["chmod", "+x", wrapper.as_str()]);
+        (!executable).then(|| SideEffect::ShellCommand(chmod.to_string()))
+    }
+}
+
+/// The Java release a failure asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JavaRequirement {
+    /// Class files were written by a newer release than the one running
+    AtLeast(u32),
+    /// The running release writes class files a tool can't read
+    Below(u32),
+}
+
+impl JavaRequirement {
+    /// Parses the requirement from a failure's output.
+    fn parse(output: &str) -> Option<Self> {
+        let class_file_release = |version: &str| version.parse::<u32>().ok()?.checked_sub(CLASS_FILE_VERSION_OFFSET);
+        let too_old = static_regex!(
+            r"compiled by a more recent version of the Java Runtime \(class file version (\d+)",
+        );
+        if let Some(captures) = too_old.captures(output) {
+            return class_file_release(&captures[1]).map(Self::AtLeast);
+        }
+        let captures = static_regex!(r"Unsupported class file major version (\d+)").captures(output)?;
+        class_file_release(&captures[1]).map(Self::Below)
+    }
+
+    /// Returns true if `release` meets the requirement.
+    fn allows(self, release: u32) -> bool {
+        match self {
+            Self::AtLeast(minimum) => release >= minimum,
+            Self::Below(maximum) => release < maximum,
+        }
+    }
+}
+
+/// A version manager that installs JDKs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JdkManager {
+    Sdkman,
+    Asdf,
+}
+
+/// An installed JDK.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Jdk {
+    manager: JdkManager,
+    /// The install's name, such as `21.0.1-tem` or `temurin-21.0.1+12`
+    name: String,
+    release: u32,
+    path: PathBuf,
+}
+
+impl Jdk {
+    /// Lists the JDKs installed by SDKMAN! and asdf for `shell`'s user.
+    fn installed(shell: &dyn Shell) -> Vec<Self> {
+        let home = shell.env("HOME").map(PathBuf::from);
+        let dir = |var: &str, default: &str| {
+            shell.env(var).map(PathBuf::from).or_else(|| Some(home.as_ref()?.join(default)))
+        };
+        let managers = [
+            (JdkManager::Sdkman, dir("SDKMAN_DIR", ".sdkman").map(|dir| dir.join("candidates/java"))),
+            (JdkManager::Asdf, dir("ASDF_DATA_DIR", ".asdf").map(|dir| dir.join("installs/java"))),
+        ];
+
+        let mut jdks = Vec::new();
+        for (manager, dir) in managers {
+            let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir).ok()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // SDKMAN!'s current is a link to one of the others
+                if name == "current" || !entry.path().is_dir() {
+                    continue;
+                }
+                if let Some(release) = java_release(&name) {
+                    jdks.push(Self { manager, name, release, path: entry.path() });
+                }
+            }
+        }
+        jdks.sort_by(|a, b| (a.manager as u8, &a.name).cmp(&(b.manager as u8, &b.name)));
+        jdks
+    }
+
+    /// Gets the command that runs `original` on this JDK.
+    fn switch(&self, original: &str) -> String {
+        match self.manager {
+            JdkManager::Sdkman => {
+                let switch = Script::from_tokens(["sdk", "use", "java", self.name.as_str()]);
+                prefix_then_retry(&switch.to_string(), original)
+            }
+            JdkManager::Asdf => {
+                let home = Script::from_tokens([self.path.to_string_lossy()]);
+                format!("JAVA_HOME={} {}", home, original)
+            }
+        }
+    }
+}
+
+/// Gets the Java release of a JDK install's name: 21 for `21.0.1-tem` or
+/// `temurin-21.0.1+12`, and 8 for `openjdk-1.8.0`.
+fn java_release(name: &str) -> Option<u32> {
+    let start = name.find(|c: char| c.is_ascii_digit())?;
+    let mut numbers = name[start..].split(|c: char| !c.is_ascii_digit());
+    match numbers.next()?.parse().ok()? {
+        1 => numbers.next()?.parse().ok(),
+        release => Some(release),
+    }
+}
+
+/// java_version_mismatch: `./gradlew build` → `sdk use java 17.0.9-tem &&
+/// ./gradlew build`, or `JAVA_HOME=/home/dev/.asdf/installs/java/temurin-17.0.9+9
+/// ./gradlew build`
+pub struct JavaVersionMismatchRule {
+    context: Arc<RuleContext>,
+}
+
+impl JavaVersionMismatchRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+}
+
+impl Rule for JavaVersionMismatchRule {
+    fn name(&self) -> &str {
+        "java_version_mismatch"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self).describe("Switch to an installed JDK of the Java release a failure asks for")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        JavaRequirement::parse(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(requirement) = JavaRequirement::parse(&command.output) else {
+            return vec![];
+        };
+        let Some(shell) = self.context.shell() else {
+            return vec![];
+        };
+
+        let mut jdks: Vec<Jdk> = Jdk::installed(shell)
+            .into_iter()
+            .filter(|jdk| requirement.allows(jdk.release))
+            .collect();
+        // The closest release first: the oldest that's new enough, or the newest that's old enough
+        jdks.sort_by_key(|jdk| match requirement {
+            JavaRequirement::AtLeast(_) => jdk.release as i64,
+            JavaRequirement::Below(_) => -(jdk.release as i64),
+        });
+        jdks.iter().take(MAX_JDK_SUGGESTIONS).map(|jdk| jdk.switch(&command.script)).collect()
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+/// Gets the task names in the output of `gradle tasks --all`, in order:
+/// `build` from `build - Assembles and tests this project.`, and
+/// `app:compileJava` from a subproject's line.
+pub fn gradle_tasks(output: &str) -> Vec<String> {
+    let mut tasks: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let name = line.split(" - ").next().unwrap_or_default().trim_end();
+        let is_task = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '-' | '.'));
+        if is_task && !tasks.iter().any(|task| task == name) {
+            tasks.push(name.to_string());
+        }
+    }
+    tasks
+}
+
+/// gradle_unknown_task: `./gradlew biuld` → `./gradlew build`; `gradle
+/// app:tset` → `gradle app:test`
+pub struct GradleUnknownTaskRule {
+    context: Arc<RuleContext>,
+}
+
+impl GradleUnknownTaskRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the task Gradle couldn't find.
+    fn typo(output: &str) -> Option<&str> {
+        static_regex!(r"(?i:task) '([^']+)' not found in (?:root )?project")
+            .captures(output)
+            .and_then(|captures| captures.get(1))
+            .map(|typo| typo.as_str())
+    }
+
+    /// Gets the names of the tasks there are: the candidates Gradle listed,
+    /// or else those of `<executable> tasks --all`, without their project.
+    fn task_names(&self, executable: &str, output: &str) -> Vec<String> {
+        let listed = output.split("Some candidates are: ").nth(1).unwrap_or_default();
+        let mut names: Vec<String> = static_regex!(r"'([^']+)'")
+            .captures_iter(listed.lines().next().unwrap_or_default())
+            .map(|captures| captures[1].to_string())
+            .collect();
+        if names.is_empty() {
+            let list = Script::from_tokens([executable, "tasks", "--all"]).to_string();
+            let output = self.context.shell().and_then(|shell| shell.execute_with_timeout(&list, TASKS_TIMEOUT).ok());
+            if let Some(output) = output.filter(|output| output.success) {
+                names = gradle_tasks(&output.stdout);
+            }
+        }
+
+        let mut unique: Vec<String> = Vec::new();
+        for name in names {
+            let name = name.rsplit(':').next().unwrap_or_default().to_string();
+            if !unique.contains(&name) {
+                unique.push(name);
+            }
+        }
+        unique
+    }
+}
+
+impl Rule for GradleUnknownTaskRule {
+    fn name(&self) -> &str {
+        "gradle_unknown_task"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use the closest task from `gradle tasks --all`")
+            .example(Example::new(
+                "./gradlew tset",
+                "Task 'tset' not found in root project 'demo'. Some candidates are: 'test', 'testClasses'.",
+                1,
+                "./gradlew test",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        let script = Script::parse(&command.script);
+        let gradle = |executable: &str| matches!(executable.rsplit('/').next(), Some("gradle" | "gradlew"));
+        script.executable().is_some_and(gradle)
+            && Self::typo(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::typo(&command.output) else {
+            return vec![];
+        };
+        let script = Script::parse(&command.script);
+        let Some(executable) = script.executable() else {
+            return vec![];
+        };
+        // The task as typed, with the project path Gradle left out of the error
+        let qualified = format!(":{}", typo);
+        let Some(index) = script.tokens().iter().position(|token| token == typo || token.ends_with(&qualified)) else {
+            return vec![];
+        };
+        let project = &script.tokens()[index][..script.tokens()[index].len() - typo.len()];
+
+        let names = self.task_names(executable, &command.output);
+        let mut scored: Vec<(&str, f64)> = names
+            .iter()
+            .map(|name| (name.as_str(), similarity(name, typo)))
+            .filter(|(name, score)| *score >= MIN_TASK_SIMILARITY && *name != typo)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored
+            .into_iter()
+            .take(MAX_TASK_SUGGESTIONS)
+            .map(|(name, _)| script.clone().replace_token_at(index, format!("{}{}", project, name)).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+    use std::path::Path;
+
+    const GRADLE_NOT_FOUND: &str = "bash: gradle: command not found";
+
+    const GRADLE_TASKS: &str = "\
+> Task :tasks
+
+------------------------------------------------------------
+Tasks runnable from root project 'demo'
+------------------------------------------------------------
+
+Build tasks
+-----------
+assemble - Assembles the outputs of this project.
+build - Assembles and tests this project.
+app:compileJava - Compiles main Java source.
+compileTestJava
+
+Verification tasks
+------------------
+check - Runs all checks.
+test - Runs the test suite.
+
+BUILD SUCCESSFUL in 1s
+1 actionable task: 1 executed
+";
+
+    /// Creates a project directory with a `gradlew` and a `module`
+    /// subdirectory.
+    fn project(name: &str, executable: bool) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_jvm_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("module")).unwrap();
+        std::fs::write(dir.join("gradlew"), "#!/bin/sh\n").unwrap();
+        set_executable(&dir.join("gradlew"), executable);
+        dir
+    }
+
+    fn set_executable(path: &Path, executable: bool) {
+        let mode = if executable { 0o755 } else { 0o644 };
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn context(shell: MockShell) -> Arc<RuleContext> {
+        Arc::new(RuleContext::new(Box::new(shell)))
+    }
+
+    /// Creates a home with the JDKs named `sdkman` and `asdf` installed.
+    fn home_with(name: &str, sdkman: &[&str], asdf: &[&str]) -> PathBuf {
+        let home = std::env::temp_dir().join(format!("ftf_jvm_home_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        for jdk in sdkman {
+            std::fs::create_dir_all(home.join(".sdkman/candidates/java").join(jdk)).unwrap();
+        }
+        for jdk in asdf {
+            std::fs::create_dir_all(home.join(".asdf/installs/java").join(jdk)).unwrap();
+        }
+        home
+    }
+
+    #[test]
+    fn test_mvn_no_goals() {
+        let rule = create_mvn_no_goals();
+        let cmd = Command::new("./mvnw -q", "[ERROR] No goals have been specified for this build.", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["./mvnw -q clean install"]);
+    }
+
+    #[test]
+    fn test_build_wrapper() {
+        let dir = project("wrapper", true);
+        let rule = BuildWrapperRule::new(context(MockShell::new().with_cwd(&dir)));
+        let cmd = Command::new("gradle build --info", GRADLE_NOT_FOUND, 127);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["./gradlew build --info"]);
+        assert_eq!(rule.side_effect_for(&cmd, "./gradlew build --info"), None);
+
+        // From a module, the project's wrapper is run
+        let rule = BuildWrapperRule::new(context(MockShell::new().with_cwd(dir.join("module"))));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["../gradlew build --info"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_wrapper_made_executable() {
+        let dir = project("wrapper_mode", false);
+        let rule = BuildWrapperRule::new(context(MockShell::new().with_cwd(&dir)));
+        let cmd = Command::new("gradle test", GRADLE_NOT_FOUND, 127);
+
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, "./gradlew test");
+        assert_eq!(corrections[0].side_effect, Some(SideEffect::ShellCommand("chmod +x ./gradlew".to_string())));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_wrapper_needs_the_wrapper() {
+        let dir = project("no_mvnw", true);
+        let rule = BuildWrapperRule::new(context(MockShell::new().with_cwd(&dir)));
+        // The project has gradlew but no mvnw
+        let cmd = Command::new("mvn package", "bash: mvn: command not found", 127);
+
+        assert!(rule.matches(&cmd));
+        assert!(rule.get_new_commands(&cmd).is_empty());
+        assert!(!rule.matches(&Command::new("gradle build", "FAILURE: Build failed with an exception.", 1)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_java_requirement() {
+        let too_old = "java.lang.UnsupportedClassVersionError: app/Main has been compiled by a more recent version of \
+            the Java Runtime (class file version 65.0), this version of the Java Runtime only recognizes class file \
+            versions up to 55.0";
+        assert_eq!(JavaRequirement::parse(too_old), Some(JavaRequirement::AtLeast(21)));
+        let too_new = "General error during conversion: Unsupported class file major version 65";
+        assert_eq!(JavaRequirement::parse(too_new), Some(JavaRequirement::Below(21)));
+        assert_eq!(JavaRequirement::parse("Unsupported class file major version 12"), None);
+        assert_eq!(JavaRequirement::parse("BUILD FAILED"), None);
+    }
+
+    #[test]
+    fn test_java_release() {
+        assert_eq!(java_release("21.0.1-tem"), Some(21));
+        assert_eq!(java_release("temurin-17.0.9+9"), Some(17));
+        assert_eq!(java_release("8.0.392-zulu"), Some(8));
+        assert_eq!(java_release("openjdk-1.8.0"), Some(8));
+        assert_eq!(java_release("latest"), None);
+    }
+
+    #[test]
+    fn test_java_version_mismatch() {
+        let home = home_with("jdks", &["11.0.21-tem", "17.0.9-tem", "21.0.1-tem"], &["temurin-17.0.9+9"]);
+        let shell = MockShell::new().with_env("HOME", home.to_str().unwrap());
+        let rule = JavaVersionMismatchRule::new(context(shell));
+
+        // Gradle on a JDK newer than it reads: the newest that's older
+        let cmd = Command::new("./gradlew build", "Unsupported class file major version 65", 1);
+        assert!(rule.matches(&cmd));
+        let asdf = home.join(".asdf/installs/java/temurin-17.0.9+9");
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "sdk use java 17.0.9-tem && ./gradlew build".to_string(),
+                format!("JAVA_HOME={} ./gradlew build", asdf.display()),
+                "sdk use java 11.0.21-tem && ./gradlew build".to_string(),
+            ]
+        );
+
+        let cmd = Command::new(
+            "java -jar app.jar",
+            "has been compiled by a more recent version of the Java Runtime (class file version 61.0)",
+            1,
+        );
+        assert_eq!(rule.get_new_commands(&cmd)[0], "sdk use java 17.0.9-tem && java -jar app.jar");
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_java_version_mismatch_without_jdks() {
+        let rule = JavaVersionMismatchRule::new(context(MockShell::new().with_env("HOME", "/nonexistent")));
+        let cmd = Command::new("./gradlew build", "Unsupported class file major version 65", 1);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_gradle_tasks() {
+        assert_eq!(
+            gradle_tasks(GRADLE_TASKS),
+            vec!["assemble", "build", "app:compileJava", "compileTestJava", "check", "test"]
+        );
+    }
+
+    #[test]
+    fn test_gradle_unknown_task() {
+        let shell = MockShell::new().with_output("./gradlew tasks --all", GRADLE_TASKS, 0);
+        let rule = GradleUnknownTaskRule::new(context(shell));
+        let cmd = Command::new(
+            "./gradlew app:biuld --info",
+            "Cannot locate tasks that match 'app:biuld' as task 'biuld' not found in project ':app'.",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["./gradlew app:build --info"]);
+    }
+
+    #[test]
+    fn test_gradle_unknown_task_uses_listed_candidates() {
+        let rule = GradleUnknownTaskRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new(
+            "gradle tset",
+            "Task 'tset' not found in root project 'demo'. Some candidates are: 'test', 'testClasses'.",
+            1,
+        );
+        assert_eq!(rule.get_new_commands(&cmd), vec!["gradle test"]);
+    }
+}
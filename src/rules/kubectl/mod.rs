@@ -0,0 +1,400 @@
+//! kubectl command correction rules.
+//!
+//! This module contains rules for common kubectl mistakes:
+//! - Mistyped subcommands, using kubectl's own "Did you mean this?" list
+//! - Mistyped resource types, matched against the built-in resource names
+//! - Expired credentials, re-authenticating with a configured command
+//! - Resources that live in another namespace
+//! - `kubectl apply` without `-f`
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::{listed_suggestions, replace_with_suggestions};
+use crate::fuzzy::similarity;
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, Script, SimpleRuleBuilder};
+
+/// Resource type names kubectl accepts: plural, singular and short forms.
+const RESOURCE_TYPES: &[&str] = &[
+    "pods", "pod", "po",
+    "services", "service", "svc",
+    "deployments", "deployment", "deploy",
+    "replicasets", "replicaset", "rs",
+    "statefulsets", "statefulset", "sts",
+    "daemonsets", "daemonset", "ds",
+    "jobs", "job",
+    "cronjobs", "cronjob", "cj",
+    "configmaps", "configmap", "cm",
+    "secrets", "secret",
+    "namespaces", "namespace", "ns",
+    "nodes", "node", "no",
+    "persistentvolumes", "persistentvolume", "pv",
+    "persistentvolumeclaims", "persistentvolumeclaim", "pvc",
+    "ingresses", "ingress", "ing",
+    "serviceaccounts", "serviceaccount", "sa",
+    "endpoints", "ep",
+    "events", "event", "ev",
+    "horizontalpodautoscalers", "horizontalpodautoscaler", "hpa",
+    "networkpolicies", "networkpolicy", "netpol",
+    "roles", "role",
+    "rolebindings", "rolebinding",
+    "clusterroles", "clusterrole",
+    "clusterrolebindings", "clusterrolebinding",
+    "storageclasses", "storageclass", "sc",
+    "customresourcedefinitions", "customresourcedefinition", "crd", "crds",
+];
+
+/// Minimum similarity for a resource type to be suggested.
+const MIN_RESOURCE_SIMILARITY: f64 = 0.6;
+
+/// Maximum number of resource type suggestions.
+const MAX_RESOURCE_SUGGESTIONS: usize = 3;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("kubectl_did_you_mean", 300),
+    ("kubectl_resource_typo", 350),
+    ("kubectl_unauthorized", 200),
+    ("kubectl_all_namespaces", 600),
+    ("kubectl_apply_missing_f", 250),
+];
+
+/// Creates all kubectl rules.
+///
+/// `reauth_command` is the command that refreshes cluster credentials (for
+/// example `aws eks update-kubeconfig --name prod`); without it the
+/// unauthorized rule never suggests anything.
+pub fn kubectl_rules(reauth_command: Option<String>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // kubectl_did_you_mean: Use kubectl's suggestion for a mistyped subcommand
+        Box::new(KubectlDidYouMeanRule),
+        // kubectl_resource_typo: Fix a mistyped resource type
+        create_kubectl_resource_typo(),
+        // kubectl_unauthorized: Re-authenticate and retry
+        create_kubectl_unauthorized(reauth_command),
+        // kubectl_all_namespaces: Look for a missing resource in all namespaces
+        create_kubectl_all_namespaces(),
+        // kubectl_apply_missing_f: Add -f to kubectl apply
+        create_kubectl_apply_missing_f(),
+    ]
+}
+
+/// Header kubectl prints before its suggestions for a mistyped subcommand.
+const DID_YOU_MEAN: &str = "Did you mean this?";
+
+/// kubectl_did_you_mean: `kubectl gte pods` → `kubectl get pods`
+struct KubectlDidYouMeanRule;
+
+impl KubectlDidYouMeanRule {
+    /// Gets the mistyped subcommand from kubectl's error.
+    fn unknown_command(output: &str) -> Option<&str> {
+        let rest = output.split("unknown command \"").nth(1)?;
+        rest.split('"').next()
+    }
+}
+
+impl Rule for KubectlDidYouMeanRule {
+    fn name(&self) -> &str {
+        "kubectl_did_you_mean"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use kubectl's suggestion for a mistyped subcommand")
+            .example(Example::new(
+                "kubectl gte pods",
+                "error: unknown command \"gte\" for \"kubectl\"\n\nDid you mean this?\n\tget",
+                1,
+                "kubectl get pods",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("kubectl")
+            && Self::unknown_command(&command.output).is_some()
+            && !listed_suggestions(&command.output, DID_YOU_MEAN).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::unknown_command(&command.output) else {
+            return vec![];
+        };
+
+        replace_with_suggestions(
+            &command.script,
+            typo,
+            listed_suggestions(&command.output, DID_YOU_MEAN),
+        )
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// kubectl_resource_typo: `kubectl get deploymnets` → `kubectl get deployments`
+fn create_kubectl_resource_typo() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("kubectl_resource_typo")
+        .match_output_static(static_regex!(r#"the server doesn't have a resource type "([^"]+)""#))
+        .priority(350)
+        .describe("Fix a mistyped resource type")
+        .example(Example::new(
+            "kubectl get deploymnets",
+            "error: the server doesn't have a resource type \"deploymnets\"",
+            1,
+            "kubectl get deployments",
+        ))
+        .replace_with(|original, captures| {
+            let Some(kind) = captures.get(1).map(|m| m.as_str()) else {
+                return vec![];
+            };
+
+            // The type may be written as `type/name`; keep the name
+            let script = Script::parse(original);
+            let Some(token) = script
+                .tokens()
+                .iter()
+                .find(|token| token.as_str() == kind || token.starts_with(&format!("{}/", kind)))
+            else {
+                return vec![];
+            };
+            let suffix = &token[kind.len()..];
+
+            let mut scored: Vec<(&str, f64)> = RESOURCE_TYPES
+                .iter()
+                .map(|candidate| (*candidate, similarity(kind, candidate)))
+                .filter(|(candidate, score)| *score >= MIN_RESOURCE_SIMILARITY && *candidate != kind)
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            scored
+                .into_iter()
+                .take(MAX_RESOURCE_SUGGESTIONS)
+                .map(|(candidate, _)| {
+                    script
+                        .clone()
+                        .replace_token(token, format!("{}{}", candidate, suffix))
+                        .to_string()
+                })
+                .collect()
+        })
+        .build_builtin()
+}
+
+/// kubectl_unauthorized: Run the configured re-auth command, then retry
+fn create_kubectl_unauthorized(reauth_command: Option<String>) -> Box<dyn Rule> {
+    RegexRuleBuilder::new("kubectl_unauthorized")
+        .match_command_static(static_regex!(r"^kubectl\s"))
+        .match_output_static(static_regex!(r"You must be logged in to the server \(Unauthorized\)"))
+        .priority(200)
+        .describe("Re-authenticate with the configured `reauth_command` and retry")
+        .replace_with(move |original, _captures| match &reauth_command {
+            Some(reauth) => vec![Script::parse(reauth)
+                .append(" && ", Script::parse(original))
+                .to_string()],
+            None => vec![],
+        })
+        .build_builtin()
+}
+
+/// kubectl_all_namespaces: A resource that is not found may live in another namespace
+fn create_kubectl_all_namespaces() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("kubectl_all_namespaces")
+        .match_output_static(static_regex!(r#"Error from server \(NotFound\): ([a-z]+)(?:\.[a-z0-9.]+)? "([^"]+)" not found"#))
+        .priority(600)
+        .describe("Look for a resource that wasn't found in every namespace")
+        .example(Example::new(
+            "kubectl get pod web-0",
+            "Error from server (NotFound): pods \"web-0\" not found",
+            1,
+            "kubectl get pods --all-namespaces --field-selector metadata.name=web-0",
+        ))
+        .replace_with(|original, captures| {
+            let script = Script::parse(original);
+            let tokens = script.tokens();
+            if script.executable() != Some("kubectl")
+                || tokens.iter().any(|t| t == "-A" || t == "--all-namespaces")
+            {
+                return vec![];
+            }
+
+            let (Some(resource), Some(name)) = (captures.get(1), captures.get(2)) else {
+                return vec![];
+            };
+            let selector = format!("metadata.name={}", name.as_str());
+            vec![Script::from_tokens([
+                "kubectl",
+                "get",
+                resource.as_str(),
+                "--all-namespaces",
+                "--field-selector",
+                selector.as_str(),
+            ])
+            .to_string()]
+        })
+        .build_builtin()
+}
+
+/// kubectl_apply_missing_f: `kubectl apply manifests/` → `kubectl apply -f manifests/`
+fn create_kubectl_apply_missing_f() -> Box<dyn Rule> {
+    SimpleRuleBuilder::new("kubectl_apply_missing_f")
+        .match_command("kubectl apply")
+        .match_output("error: must specify one of -f and -k")
+        .priority(250)
+        .describe("Add the `-f` that `kubectl apply` needs")
+        .example(Example::new(
+            "kubectl apply manifests/",
+            "error: must specify one of -f and -k",
+            1,
+            "kubectl apply -f manifests/",
+        ))
+        .insert_flag("apply", "-f")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kubectl_did_you_mean() {
+        let rule = KubectlDidYouMeanRule;
+        let cmd = Command::new(
+            "kubectl gte pods",
+            "error: unknown command \"gte\" for \"kubectl\"\n\nDid you mean this?\n\tget\n\tset\n",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["kubectl get pods", "kubectl set pods"]
+        );
+    }
+
+    #[test]
+    fn test_kubectl_did_you_mean_without_suggestions() {
+        let rule = KubectlDidYouMeanRule;
+        let cmd = Command::new(
+            "kubectl frobnicate",
+            "error: unknown command \"frobnicate\" for \"kubectl\"\nRun 'kubectl --help' for usage.",
+            1,
+        );
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_kubectl_resource_typo() {
+        let rule = create_kubectl_resource_typo();
+        let cmd = Command::new(
+            "kubectl get deploymnets -n web",
+            "error: the server doesn't have a resource type \"deploymnets\"",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_new_commands(&cmd);
+        assert_eq!(corrections[0], "kubectl get deployments -n web");
+    }
+
+    #[test]
+    fn test_kubectl_resource_typo_keeps_name() {
+        let rule = create_kubectl_resource_typo();
+        let cmd = Command::new(
+            "kubectl describe serivce/api",
+            "error: the server doesn't have a resource type \"serivce\"",
+            1,
+        );
+
+        assert_eq!(rule.get_new_commands(&cmd)[0], "kubectl describe service/api");
+    }
+
+    #[test]
+    fn test_kubectl_unauthorized_with_reauth() {
+        let rule = create_kubectl_unauthorized(Some(
+            "aws eks update-kubeconfig --name prod".to_string(),
+        ));
+        let cmd = Command::new(
+            "kubectl get pods",
+            "error: You must be logged in to the server (Unauthorized)",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["aws eks update-kubeconfig --name prod && kubectl get pods"]
+        );
+    }
+
+    #[test]
+    fn test_kubectl_unauthorized_without_reauth() {
+        let rule = create_kubectl_unauthorized(None);
+        let cmd = Command::new(
+            "kubectl get pods",
+            "error: You must be logged in to the server (Unauthorized)",
+            1,
+        );
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_kubectl_all_namespaces() {
+        let rule = create_kubectl_all_namespaces();
+        let cmd = Command::new(
+            "kubectl get deployment api",
+            "Error from server (NotFound): deployments.apps \"api\" not found",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["kubectl get deployments --all-namespaces --field-selector metadata.name=api"]
+        );
+    }
+
+    #[test]
+    fn test_kubectl_all_namespaces_already_all() {
+        let rule = create_kubectl_all_namespaces();
+        let cmd = Command::new(
+            "kubectl get pods web-1 -A",
+            "Error from server (NotFound): pods \"web-1\" not found",
+            1,
+        );
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_kubectl_apply_missing_f() {
+        let rule = create_kubectl_apply_missing_f();
+        let cmd = Command::new("kubectl apply manifests/", "error: must specify one of -f and -k", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["kubectl apply -f manifests/"]);
+    }
+
+    #[test]
+    fn test_kubectl_apply_with_f_no_change() {
+        let rule = create_kubectl_apply_missing_f();
+        let cmd = Command::new("kubectl apply -f x.yaml", "error: must specify one of -f and -k", 1);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_kubectl_rules_exist() {
+        let rules = kubectl_rules(None);
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "kubectl_did_you_mean",
+                "kubectl_resource_typo",
+                "kubectl_unauthorized",
+                "kubectl_all_namespaces",
+                "kubectl_apply_missing_f",
+            ]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Project-local binary rules.
+//!
+//! This module contains rules for tools installed into the project rather
+//! than on `PATH`:
+//! - Python virtualenvs in `.venv`
+//! - Node packages in `node_modules/.bin`
+//! - Project scripts in `./bin`
+//!
+//! Binaries are found by name; corrections that would run a file without
+//! the executable bit are dropped when verified.
+
+use super::{RuleInfo, RuleMetadata};
+use super::util::prefix_then_retry;
+use crate::{Command, CorrectedCommand, FailureKind, Rule, RuleContext, Script, Verdict};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("local_bin_resolver", 150),
+];
+
+/// Creates all project-local binary rules.
+///
+/// Binaries are looked up relative to the working directory of the context's
+/// shell.
+pub fn local_bin_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // local_bin_resolver: Run a missing command from the project's own bin directories
+        Box::new(LocalBinResolverRule::new(context)),
+    ]
+}
+
+/// local_bin_resolver: `pytest -x` → `source .venv/bin/activate && pytest -x`
+/// or `.venv/bin/pytest -x`; `eslint .` → `npx eslint .` or
+/// `node_modules/.bin/eslint .`; `rails s` → `./bin/rails s`
+pub struct LocalBinResolverRule {
+    context: Arc<RuleContext>,
+}
+
+impl LocalBinResolverRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the corrections for `binary` found under `cwd`, with `script`'s
+    /// arguments preserved.
+    fn corrections(cwd: &Path, binary: &str, script: &Script) -> Vec<String> {
+        let with_executable = |path: String| script.clone().replace_token_at(0, path).to_string();
+        let mut corrections = Vec::new();
+
+        if cwd.join(".venv/bin").join(binary).is_file() {
+            corrections.push(prefix_then_retry("source .venv/bin/activate", &script.to_string()));
+            corrections.push(with_executable(format!(".venv/bin/{}", binary)));
+        }
+        if cwd.join("node_modules/.bin").join(binary).is_file() {
+            corrections.push(script.clone().prepend_token("npx").to_string());
+            corrections.push(with_executable(format!("node_modules/.bin/{}", binary)));
+        }
+        if cwd.join("bin").join(binary).is_file() {
+            corrections.push(with_executable(format!("./bin/{}", binary)));
+        }
+        corrections
+    }
+}
+
+impl Rule for LocalBinResolverRule {
+    fn name(&self) -> &str {
+        "local_bin_resolver"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            failure_kind: Some(FailureKind::NotFound),
+            ..RuleMetadata::of(self)
+        }
+        .describe("Run a missing command from the project's virtualenv, node_modules or ./bin")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() == FailureKind::NotFound
+            && Script::parse(&command.script)
+                .executable()
+                .is_some_and(|binary| !binary.contains('/'))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let Some(binary) = script.executable() else {
+            return vec![];
+        };
+        let Some(cwd) = self.context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return vec![];
+        };
+
+        Self::corrections(&cwd, binary, &script)
+    }
+
+    fn priority(&self) -> i32 {
+        150
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        use std::os::unix::fs::PermissionsExt;
+
+        let Some(cwd) = context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return Verdict::Keep;
+        };
+        let script = Script::parse(&correction.script);
+        match script.tokens() {
+            // A virtualenv without its activate script may still work when
+            // run directly, so only rank it lower
+            [source, activate, ..] if source == "source" => {
+                if cwd.join(activate).is_file() {
+                    Verdict::Keep
+                } else {
+                    Verdict::Demote
+                }
+            }
+            [executable, ..] if executable.contains('/') => {
+                let runnable = std::fs::metadata(cwd.join(executable))
+                    .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0);
+                if runnable {
+                    Verdict::Keep
+                } else {
+                    Verdict::Drop
+                }
+            }
+            _ => Verdict::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+    use std::path::PathBuf;
+
+    /// Creates a project directory containing empty files at `binaries`.
+    fn project_with(name: &str, binaries: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_local_bin_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for binary in binaries {
+            let path = dir.join(binary);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rule_in(dir: &Path) -> LocalBinResolverRule {
+        let shell = MockShell::new().with_cwd(dir);
+        LocalBinResolverRule::new(Arc::new(RuleContext::new(Box::new(shell))))
+    }
+
+    #[test]
+    fn test_local_bin_virtualenv() {
+        let dir = project_with("venv", &[".venv/bin/pytest"]);
+        let rule = rule_in(&dir);
+        let cmd = Command::new("pytest -x -k 'slow and not db'", "bash: pytest: command not found", 127);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "source .venv/bin/activate && pytest -x -k 'slow and not db'",
+                ".venv/bin/pytest -x -k 'slow and not db'",
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_bin_node_modules() {
+        let dir = project_with("node", &["node_modules/.bin/eslint"]);
+        let rule = rule_in(&dir);
+        let cmd = Command::new("eslint --fix src", "zsh: command not found: eslint", 127);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["npx eslint --fix src", "node_modules/.bin/eslint --fix src"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_bin_project_scripts() {
+        let dir = project_with("scripts", &["bin/rails"]);
+        let rule = rule_in(&dir);
+        let cmd = Command::new("rails server -p 4000", "bash: rails: command not found", 127);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["./bin/rails server -p 4000"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_bin_missing_everywhere() {
+        let dir = project_with("empty", &[".venv/bin/python"]);
+        let rule = rule_in(&dir);
+        let cmd = Command::new("pytest", "bash: pytest: command not found", 127);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+        assert!(LocalBinResolverRule::new(Arc::new(RuleContext::default()))
+            .get_new_commands(&cmd)
+            .is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_bin_verify_drops_non_executables() {
+        use crate::{Corrector, RuleRegistry};
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::Duration;
+
+        let dir = project_with("verify", &["node_modules/.bin/eslint", "bin/rails"]);
+        let rails = dir.join("bin/rails");
+        std::fs::set_permissions(&rails, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let context = Arc::new(RuleContext::new(Box::new(MockShell::new().with_cwd(&dir))));
+        let mut registry = RuleRegistry::new();
+        registry.add_rule(Box::new(LocalBinResolverRule::new(Arc::clone(&context))));
+        let corrector = Corrector::new(registry).with_context(context, Duration::from_secs(60));
+
+        // The eslint file isn't executable, so only npx can run it
+        let cmd = Command::new("eslint .", "zsh: command not found: eslint", 127);
+        let scripts: Vec<_> = corrector.get_corrections(&cmd).into_iter().map(|c| c.script).collect();
+        assert_eq!(scripts, vec!["npx eslint ."]);
+
+        let cmd = Command::new("rails s", "bash: rails: command not found", 127);
+        assert_eq!(corrector.get_corrections(&cmd)[0].script, "./bin/rails s");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_bin_only_on_not_found() {
+        let rule = LocalBinResolverRule::new(Arc::new(RuleContext::default()));
+
+        assert!(!rule.matches(&Command::new("pytest", "1 failed, 3 passed", 1)));
+        assert!(!rule.matches(&Command::new("./pytest", "bash: ./pytest: No such file or directory", 127)));
+    }
+
+    #[test]
+    fn test_local_bin_rules_exist() {
+        let rules = local_bin_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["local_bin_resolver"]);
+    }
+}
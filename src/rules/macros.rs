@@ -1,4 +1,24 @@
-/// Macros for generating rule implementations with reduced boilerplate.
+//! Macros for generating rule implementations with reduced boilerplate.
+
+/// Compiles a regex literal once and returns it as a `&'static Regex`.
+///
+/// Built-in rules use this with
+/// [`RegexRuleBuilder::match_command_static`](crate::RegexRuleBuilder::match_command_static),
+/// so building the rules again reuses the compiled patterns. Panics on first
+/// use if the pattern is invalid.
+///
+/// # Example
+/// ```
+/// let regex = fasterthefuck::static_regex!(r"^git\s");
+/// assert!(regex.is_match("git push"));
+/// ```
+#[macro_export]
+macro_rules! static_regex {
+    ($pattern:expr $(,)?) => {{
+        static REGEX: ::std::sync::OnceLock<::regex::Regex> = ::std::sync::OnceLock::new();
+        REGEX.get_or_init(|| ::regex::Regex::new($pattern).unwrap())
+    }};
+}
 
 /// Simple rule macro for basic string matching and replacement.
 ///
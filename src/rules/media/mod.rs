@@ -0,0 +1,507 @@
+//! ffmpeg and ImageMagick command correction rules.
+//!
+//! This module contains rules for common media tool mistakes:
+//! - Inputs given after the output file they feed (`ffmpeg out.mp4 -i in.mov`)
+//! - Encoders this ffmpeg build doesn't include, such as `libx264` where only
+//!   `h264_videotoolbox` is available
+//! - `convert` and the other ImageMagick 6 commands on ImageMagick 7, which
+//!   only installs `magick`
+//! - Images larger than ImageMagick's width or height limit
+//!
+//! Installed encoders are listed with `ffmpeg -encoders` through the context's
+//! shell, once per invocation.
+
+use super::util::requires_binary;
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::shell::PROBE_TIMEOUT;
+use crate::{static_regex, Command, FailureKind, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// Maximum number of replacement encoders suggested.
+const MAX_ENCODER_SUGGESTIONS: usize = 3;
+
+/// Width and height limit suggested for oversized images. `-limit` can't
+/// raise a limit above the one in ImageMagick's `policy.xml`.
+const PIXEL_LIMIT: &str = "64KP";
+
+/// Executables every media rule requires.
+pub const EXECUTABLES: &[&str] = &["ffmpeg", "magick", "convert", "identify", "mogrify", "composite", "montage"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("ffmpeg_input_order", 300),
+    ("ffmpeg_unknown_encoder", 300),
+    ("imagemagick_v7", 200),
+    ("imagemagick_limit", 400),
+];
+
+/// ImageMagick 6 commands that are `magick` subcommands in ImageMagick 7.
+const IMAGEMAGICK_TOOLS: &[&str] = &["convert", "identify", "mogrify", "composite", "montage", "compare"];
+
+/// ffmpeg options that take no value. Every other option takes one.
+const FFMPEG_FLAGS: &[&str] = &[
+    "-y", "-n", "-hide_banner", "-nostdin", "-nostats", "-stats", "-an", "-vn", "-sn", "-dn", "-shortest", "-re",
+    "-copyts", "-benchmark", "-xerror", "-report", "-ignore_unknown", "-h", "-version",
+];
+
+/// Codecs of common encoders whose names don't start with the codec.
+const ENCODER_CODECS: &[(&str, &str)] = &[
+    ("libx264", "h264"),
+    ("libx264rgb", "h264"),
+    ("libopenh264", "h264"),
+    ("libx265", "hevc"),
+    ("libvpx", "vp8"),
+    ("libvpx-vp9", "vp9"),
+    ("libaom-av1", "av1"),
+    ("libsvtav1", "av1"),
+    ("librav1e", "av1"),
+    ("libmp3lame", "mp3"),
+    ("libfdk_aac", "aac"),
+    ("libopus", "opus"),
+    ("libvorbis", "vorbis"),
+];
+
+/// Creates all media rules.
+///
+/// Encoders are listed, and `magick` looked up, through the context's shell.
+pub fn media_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // ffmpeg_input_order: Move inputs before the outputs
+        Box::new(FfmpegInputOrderRule),
+        // ffmpeg_unknown_encoder: Use an installed encoder for the same codec
+        Box::new(FfmpegUnknownEncoderRule::new(Arc::clone(&context))),
+        // imagemagick_v7: Run ImageMagick 6 commands through magick
+        requires_binary(Box::new(ImageMagickV7Rule), context),
+        // imagemagick_limit: Raise the width and height limits
+        create_imagemagick_limit(),
+    ]
+}
+
+/// A run of an ffmpeg command line: an input with its options, or an output
+/// with its options. Holds token indices.
+#[derive(Debug, PartialEq)]
+enum FfmpegFile {
+    Input(Vec<usize>),
+    Output(Vec<usize>),
+}
+
+/// Splits the arguments of an ffmpeg script into inputs and outputs, each
+/// with the options before it, followed by any trailing options.
+fn ffmpeg_files(tokens: &[String]) -> (Vec<FfmpegFile>, Vec<usize>) {
+    let mut files = Vec::new();
+    let mut pending = Vec::new();
+    let mut index = 1;
+    while index < tokens.len() {
+        let token = tokens[index].as_str();
+        if token == "-i" && index + 1 < tokens.len() {
+            pending.extend([index, index + 1]);
+            files.push(FfmpegFile::Input(std::mem::take(&mut pending)));
+            index += 2;
+        } else if token.len() > 1 && token.starts_with('-') {
+            pending.push(index);
+            if !FFMPEG_FLAGS.contains(&token) && index + 1 < tokens.len() {
+                pending.push(index + 1);
+                index += 1;
+            }
+            index += 1;
+        } else {
+            pending.push(index);
+            files.push(FfmpegFile::Output(std::mem::take(&mut pending)));
+            index += 1;
+        }
+    }
+    (files, pending)
+}
+
+/// ffmpeg_input_order: `ffmpeg out.mp4 -i in.mov` → `ffmpeg -i in.mov out.mp4`
+pub struct FfmpegInputOrderRule;
+
+impl FfmpegInputOrderRule {
+    /// Gets the script with every input, and the options before it, moved
+    /// ahead of the outputs. Options after the last output go before its
+    /// file name, since ffmpeg ignores them there. Returns `None` if no input
+    /// comes after an output.
+    fn reorder(script: &str) -> Option<String> {
+        let script = Script::parse(script);
+        if script.executable() != Some("ffmpeg") {
+            return None;
+        }
+        let (files, trailing) = ffmpeg_files(script.tokens());
+        let first_output = files.iter().position(|file| matches!(file, FfmpegFile::Output(_)))?;
+        if !files[first_output..].iter().any(|file| matches!(file, FfmpegFile::Input(_))) {
+            return None;
+        }
+
+        let mut order = vec![0];
+        for file in &files {
+            if let FfmpegFile::Input(indices) = file {
+                order.extend(indices);
+            }
+        }
+        for file in &files {
+            if let FfmpegFile::Output(indices) = file {
+                order.extend(indices);
+            }
+        }
+        // The last output's file name is the last index pushed
+        let last = order.pop()?;
+        order.extend(trailing);
+        order.push(last);
+        Some(script.reordered(&order).to_string())
+    }
+}
+
+impl Rule for FfmpegInputOrderRule {
+    fn name(&self) -> &str {
+        "ffmpeg_input_order"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Move ffmpeg inputs given after an output file before the outputs")
+            .example(Example::new(
+                "ffmpeg out.mp4 -i in.mov",
+                "Output file #0 does not contain any stream",
+                1,
+                "ffmpeg -i in.mov out.mp4",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        static_regex!(r"Output file (?:#\d+ )?does not contain any stream").is_match(&command.output)
+            && Self::reorder(&command.script).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        Self::reorder(&command.script).into_iter().collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// An encoder listed by `ffmpeg -encoders`.
+#[derive(Debug, Clone, PartialEq)]
+struct Encoder {
+    name: String,
+    codec: String,
+}
+
+/// Parses the output of `ffmpeg -encoders`. Encoders without a `(codec x)`
+/// note are named after their codec.
+fn parse_encoders(output: &str) -> Vec<Encoder> {
+    let line = static_regex!(r"^\s*[VAS][A-Z.]{5}\s+(\S+)\s+(.*)$");
+    output
+        .lines()
+        .filter_map(|text| line.captures(text))
+        .filter(|captures| &captures[1] != "=")
+        .map(|captures| {
+            let codec = static_regex!(r"\(codec (\S+)\)").captures(&captures[2]);
+            Encoder {
+                name: captures[1].to_string(),
+                codec: codec.map_or(&captures[1], |codec| codec.get(1).map_or("", |m| m.as_str())).to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Gets the codec an encoder produces: from [`ENCODER_CODECS`], the prefix
+/// of hardware encoders (`hevc_nvenc`), or the name itself.
+fn encoder_codec(name: &str) -> &str {
+    if let Some((_, codec)) = ENCODER_CODECS.iter().find(|(encoder, _)| *encoder == name) {
+        return codec;
+    }
+    match name.split_once('_') {
+        Some((codec, _)) if !codec.starts_with("lib") => codec,
+        _ => name,
+    }
+}
+
+/// ffmpeg_unknown_encoder: `ffmpeg -i in.mov -c:v libx264 out.mp4` →
+/// `ffmpeg -i in.mov -c:v h264_videotoolbox out.mp4`
+pub struct FfmpegUnknownEncoderRule {
+    context: Arc<RuleContext>,
+}
+
+impl FfmpegUnknownEncoderRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the encoders this ffmpeg includes, or nothing without a shell.
+    fn encoders(&self) -> Arc<Vec<Encoder>> {
+        self.context.cached("ffmpeg_encoders", || {
+            let output = self.context.shell().and_then(|shell| {
+                shell.execute_with_timeout("ffmpeg -hide_banner -encoders", PROBE_TIMEOUT).ok()
+            });
+            output.filter(|output| output.success).map(|output| parse_encoders(&output.stdout)).unwrap_or_default()
+        })
+    }
+
+    /// Gets the encoder ffmpeg doesn't know.
+    fn unknown_encoder(command: &Command) -> Option<&str> {
+        let captures = static_regex!(r"Unknown encoder '([^']+)'").captures(&command.output)?;
+        captures.get(1).map(|encoder| encoder.as_str())
+    }
+}
+
+impl Rule for FfmpegUnknownEncoderRule {
+    fn name(&self) -> &str {
+        "ffmpeg_unknown_encoder"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        // The replacements depend on the local ffmpeg build, so there's no example
+        RuleMetadata::of(self).describe("Use an installed ffmpeg encoder for the codec of one that isn't built in")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("ffmpeg") && Self::unknown_encoder(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(unknown) = Self::unknown_encoder(command) else {
+            return vec![];
+        };
+        let script = Script::parse(&command.script);
+        let Some(index) = script.tokens().iter().position(|token| token == unknown) else {
+            return vec![];
+        };
+        let codec = encoder_codec(unknown);
+        self.encoders()
+            .iter()
+            .filter(|encoder| encoder.codec == codec && encoder.name != unknown)
+            .take(MAX_ENCODER_SUGGESTIONS)
+            .map(|encoder| script.clone().replace_token_at(index, encoder.name.clone()).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// imagemagick_v7: `convert in.png out.jpg` → `magick in.png out.jpg`, and
+/// `identify in.png` → `magick identify in.png`
+pub struct ImageMagickV7Rule;
+
+impl Rule for ImageMagickV7Rule {
+    fn name(&self) -> &str {
+        "imagemagick_v7"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata {
+            failure_kind: Some(FailureKind::NotFound),
+            ..RuleMetadata::of(self)
+        }
+        .describe("Run ImageMagick 6 commands through magick, which ImageMagick 7 installs instead")
+        .example(Example::new(
+            "convert in.png -resize 50% out.jpg",
+            "bash: convert: command not found",
+            127,
+            "magick in.png -resize 50% out.jpg",
+        ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() == FailureKind::NotFound
+            && Script::parse(&command.script).executable().is_some_and(|tool| IMAGEMAGICK_TOOLS.contains(&tool))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        // magick itself converts; `magick convert` is the deprecated v6 spelling
+        let correction = match script.executable() {
+            Some("convert") => script.replace_token_at(0, "magick"),
+            Some(_) => script.prepend_token("magick"),
+            None => return vec![],
+        };
+        vec![correction.to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+/// imagemagick_limit: `convert big.png out.jpg` → `convert -limit width 64KP
+/// -limit height 64KP big.png out.jpg`
+fn create_imagemagick_limit() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("imagemagick_limit")
+        .match_command_static(static_regex!(r"^(?:magick|convert|identify|mogrify|composite|montage|compare)\b"))
+        .match_output_static(static_regex!(r"width or height exceeds limit"))
+        .priority(400)
+        .describe("Raise ImageMagick's width and height limits for an oversized image")
+        .example(Example::new(
+            "convert big.png out.jpg",
+            "convert: width or height exceeds limit `big.png' @ error/cache.c/OpenPixelCache/3911.",
+            1,
+            "convert -limit width 64KP -limit height 64KP big.png out.jpg",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            if script.tokens().iter().any(|token| token == "-limit") {
+                return vec![];
+            }
+            // Settings go after the tool of `magick identify`, which parses its own
+            let index = match script.tokens().get(1) {
+                Some(tool) if script.executable() == Some("magick") && IMAGEMAGICK_TOOLS.contains(&tool.as_str()) => 2,
+                _ => 1,
+            };
+            let limits = Script::from_tokens(["-limit", "width", PIXEL_LIMIT, "-limit", "height", PIXEL_LIMIT]);
+            vec![script.insert_script_at(index, limits).to_string()]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const NO_STREAM: &str = "Output file #0 does not contain any stream\n";
+
+    const ENCODERS: &str = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n \
+                            V....D h264_videotoolbox    VideoToolbox H.264 Encoder (codec h264)\n \
+                            V....D hevc_videotoolbox    VideoToolbox H.265 Encoder (codec hevc)\n \
+                            V....D mpeg4                MPEG-4 part 2\n \
+                            A....D aac                  AAC (Advanced Audio Coding)\n \
+                            A....D aac_at               aac (AudioToolbox) (codec aac)\n";
+
+    fn reorder(script: &str) -> Option<String> {
+        FfmpegInputOrderRule::reorder(script)
+    }
+
+    #[test]
+    fn test_ffmpeg_files() {
+        let script = Script::parse("ffmpeg -y -ss 5 -i a.mov -c:v libx264 -an out.mp4");
+        let (files, trailing) = ffmpeg_files(script.tokens());
+        assert_eq!(files, vec![FfmpegFile::Input(vec![1, 2, 3, 4, 5]), FfmpegFile::Output(vec![6, 7, 8, 9])]);
+        assert!(trailing.is_empty());
+    }
+
+    #[test]
+    fn test_ffmpeg_input_order() {
+        let cmd = Command::new("ffmpeg out.mp4 -i in.mov", NO_STREAM, 1);
+        assert!(FfmpegInputOrderRule.matches(&cmd));
+        assert_eq!(FfmpegInputOrderRule.get_new_commands(&cmd), vec!["ffmpeg -i in.mov out.mp4"]);
+
+        // Input options move with their input, output options stay with the output
+        assert_eq!(
+            reorder("ffmpeg -c:v libx264 'my clip.mp4' -ss 00:01:00 -i in.mov").as_deref(),
+            Some("ffmpeg -ss 00:01:00 -i in.mov -c:v libx264 'my clip.mp4'")
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_input_order_multiple_inputs() {
+        assert_eq!(
+            reorder("ffmpeg out.mp4 -i video.mp4 -i audio.wav").as_deref(),
+            Some("ffmpeg -i video.mp4 -i audio.wav out.mp4")
+        );
+        // Options left after the last file belong to the output
+        assert_eq!(
+            reorder("ffmpeg -i video.mp4 out.mp4 -i audio.wav -map 0:v -map 1:a -shortest").as_deref(),
+            Some("ffmpeg -i video.mp4 -i audio.wav -map 0:v -map 1:a -shortest out.mp4")
+        );
+        assert_eq!(
+            reorder("ffmpeg -i a.mov a.mp4 -i b.mov -vn b.mp3").as_deref(),
+            Some("ffmpeg -i a.mov -i b.mov a.mp4 -vn b.mp3")
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_input_order_needs_a_misplaced_input() {
+        assert_eq!(reorder("ffmpeg -i in.mov -map 5 out.mp4"), None);
+        let cmd = Command::new("ffmpeg -i silent.mov -map 0:a out.mp3", NO_STREAM, 1);
+        assert!(!FfmpegInputOrderRule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_parse_encoders() {
+        let encoders = parse_encoders(ENCODERS);
+        let names: Vec<_> = encoders.iter().map(|encoder| encoder.name.as_str()).collect();
+        assert_eq!(names, vec!["h264_videotoolbox", "hevc_videotoolbox", "mpeg4", "aac", "aac_at"]);
+        assert_eq!(encoders[0].codec, "h264");
+        assert_eq!(encoders[3].codec, "aac");
+    }
+
+    #[test]
+    fn test_encoder_codec() {
+        assert_eq!(encoder_codec("libx264"), "h264");
+        assert_eq!(encoder_codec("hevc_nvenc"), "hevc");
+        assert_eq!(encoder_codec("libfdk_aac"), "aac");
+        assert_eq!(encoder_codec("libunknown_x"), "libunknown_x");
+    }
+
+    #[test]
+    fn test_ffmpeg_unknown_encoder() {
+        let shell = MockShell::new().with_output("ffmpeg -hide_banner -encoders", ENCODERS, 0);
+        let rule = FfmpegUnknownEncoderRule::new(Arc::new(RuleContext::new(Box::new(shell))));
+
+        let cmd = Command::new("ffmpeg -i in.mov -c:v libx264 out.mp4", "Unknown encoder 'libx264'", 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ffmpeg -i in.mov -c:v h264_videotoolbox out.mp4"]);
+
+        let cmd = Command::new("ffmpeg -i in.wav -acodec libfdk_aac out.m4a", "Unknown encoder 'libfdk_aac'", 1);
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["ffmpeg -i in.wav -acodec aac out.m4a", "ffmpeg -i in.wav -acodec aac_at out.m4a"]
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_unknown_encoder_without_a_shell() {
+        let rule = FfmpegUnknownEncoderRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new("ffmpeg -i in.mov -c:v libx264 out.mp4", "Unknown encoder 'libx264'", 1);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_imagemagick_v7() {
+        let cmd = Command::new("convert in.png -resize 50% out.jpg", "bash: convert: command not found", 127);
+        assert!(ImageMagickV7Rule.matches(&cmd));
+        assert_eq!(ImageMagickV7Rule.get_new_commands(&cmd), vec!["magick in.png -resize 50% out.jpg"]);
+
+        let cmd = Command::new("identify -format %wx%h in.png", "zsh: command not found: identify", 127);
+        assert_eq!(ImageMagickV7Rule.get_new_commands(&cmd), vec!["magick identify -format %wx%h in.png"]);
+        assert!(!ImageMagickV7Rule.matches(&Command::new("convert a.png b.jpg", "convert: no images defined", 1)));
+    }
+
+    #[test]
+    fn test_imagemagick_v7_needs_magick() {
+        let shell = MockShell::new().with_output("magick --version", "Version: ImageMagick 7.1.1-21", 0);
+        let with_magick = media_rules(Arc::new(RuleContext::new(Box::new(shell))));
+        let without = media_rules(Arc::new(RuleContext::new(Box::new(MockShell::new()))));
+        let cmd = Command::new("convert in.png out.jpg", "bash: convert: command not found", 127);
+
+        let corrections = |rules: &[Box<dyn Rule>]| {
+            let rule = rules.iter().find(|rule| rule.name() == "imagemagick_v7").unwrap();
+            rule.get_new_commands(&cmd)
+        };
+        assert_eq!(corrections(&with_magick), vec!["magick in.png out.jpg"]);
+        assert!(corrections(&without).is_empty());
+    }
+
+    #[test]
+    fn test_imagemagick_limit() {
+        let rule = create_imagemagick_limit();
+        let output = "magick: width or height exceeds limit `big.tif' @ error/cache.c/OpenPixelCache/3911.";
+
+        let cmd = Command::new("magick identify big.tif", output, 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["magick identify -limit width 64KP -limit height 64KP big.tif"]
+        );
+        let cmd = Command::new("magick big.tif -resize 10% small.png", output, 1);
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["magick -limit width 64KP -limit height 64KP big.tif -resize 10% small.png"]
+        );
+        assert!(rule.get_new_commands(&Command::new("convert -limit width 1MP big.tif out.png", output, 1)).is_empty());
+    }
+}
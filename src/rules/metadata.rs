@@ -0,0 +1,399 @@
+//! Machine-readable descriptions of rules, for `ftf rules export`.
+//!
+//! Every rule describes itself through [`Rule::metadata`]: what it matches,
+//! what it's for and an example of a failure it corrects. [`export`] collects
+//! them into a [`Catalog`], whose JSON form is a stable schema: fields are
+//! only added, and a change to an existing one bumps [`SCHEMA_VERSION`].
+
+use super::RuleFactory;
+use crate::{Command, FailureKind, Rule};
+use serde::{Deserialize, Serialize};
+
+/// Version of the exported schema.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How a pattern is matched against the script or output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    /// The text must contain the pattern.
+    Substring,
+    /// The text must match the pattern as a regex.
+    Regex,
+    /// The text must match the pattern approximately.
+    Fuzzy,
+}
+
+/// A pattern a rule matches the script or output with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pattern {
+    /// How the pattern is matched.
+    pub kind: PatternKind,
+    /// The pattern's source text.
+    pub pattern: String,
+}
+
+impl Pattern {
+    /// Creates a pattern matched as a substring.
+    pub fn substring(pattern: impl Into<String>) -> Self {
+        Self {
+            kind: PatternKind::Substring,
+            pattern: pattern.into(),
+        }
+    }
+
+    /// Creates a pattern matched as a regex.
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        Self {
+            kind: PatternKind::Regex,
+            pattern: pattern.into(),
+        }
+    }
+
+    /// Creates a pattern matched approximately.
+    pub fn fuzzy(pattern: impl Into<String>) -> Self {
+        Self {
+            kind: PatternKind::Fuzzy,
+            pattern: pattern.into(),
+        }
+    }
+}
+
+/// A failed command a rule corrects, and the correction it offers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Example {
+    /// The failed command.
+    pub script: String,
+    /// What it printed.
+    pub output: String,
+    /// Its exit code.
+    pub exit_code: i32,
+    /// The correction the rule offers for it.
+    pub correction: String,
+}
+
+impl Example {
+    /// Creates an example of `script` failing with `output` and `exit_code`,
+    /// corrected to `correction`.
+    pub fn new(
+        script: impl Into<String>,
+        output: impl Into<String>,
+        exit_code: i32,
+        correction: impl Into<String>,
+    ) -> Self {
+        Self {
+            script: script.into(),
+            output: output.into(),
+            exit_code,
+            correction: correction.into(),
+        }
+    }
+
+    /// Gets the failed command.
+    pub fn command(&self) -> Command {
+        Command::new(&self.script, &self.output, self.exit_code)
+    }
+}
+
+/// What a rule matches and corrects, without its code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleMetadata {
+    /// The rule's name.
+    pub name: String,
+    /// The group the rule is registered in, such as `git`.
+    pub category: Option<String>,
+    /// The rule's base priority (lower = higher priority).
+    pub priority: i32,
+    /// Whether the rule is enabled without configuration.
+    pub enabled_by_default: bool,
+    /// The executable every script the rule matches runs.
+    pub executable: Option<String>,
+    /// The pattern the script must match.
+    pub match_command: Option<Pattern>,
+    /// The pattern the output must match.
+    pub match_output: Option<Pattern>,
+    /// The kind of failure the rule only matches.
+    pub failure_kind: Option<FailureKind>,
+    /// What the rule corrects.
+    pub description: Option<String>,
+    /// A failure the rule corrects.
+    pub example: Option<Example>,
+}
+
+impl RuleMetadata {
+    /// Gets what `rule` declares through the [`Rule`] methods, with its
+    /// required substrings as patterns.
+    pub fn of<R: Rule + ?Sized>(rule: &R) -> Self {
+        Self {
+            name: rule.name().to_string(),
+            category: None,
+            priority: rule.priority(),
+            enabled_by_default: rule.enabled_by_default(),
+            executable: rule.leading_token().map(str::to_string),
+            match_command: rule.required_script().map(Pattern::substring),
+            match_output: rule.required_output().map(Pattern::substring),
+            failure_kind: None,
+            description: None,
+            example: None,
+        }
+    }
+
+    /// Sets the description.
+    pub fn describe(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the example.
+    pub fn example(mut self, example: Example) -> Self {
+        self.example = Some(example);
+        self
+    }
+
+    /// Gets why the rule matched `command`: the text its output or script
+    /// pattern found, or else what the rule corrects.
+    pub fn reason(&self, command: &Command) -> Reason {
+        if let Some(text) = self.match_output.as_ref().and_then(|pattern| pattern.find(&command.output)) {
+            return Reason::Output(text);
+        }
+        if let Some(text) = self.match_command.as_ref().and_then(|pattern| pattern.find(&command.script)) {
+            return Reason::Script(text);
+        }
+        match &self.description {
+            Some(description) => Reason::Described(description.clone()),
+            None => Reason::Matched,
+        }
+    }
+}
+
+impl Pattern {
+    /// Gets the first line of the text the pattern finds in `text`. Fuzzy
+    /// patterns give the pattern itself.
+    fn find(&self, text: &str) -> Option<String> {
+        let found = match self.kind {
+            PatternKind::Substring => text.contains(&self.pattern).then(|| self.pattern.clone())?,
+            PatternKind::Regex => regex::Regex::new(&self.pattern).ok()?.find(text)?.as_str().to_string(),
+            PatternKind::Fuzzy => self.pattern.clone(),
+        };
+        let line = found.lines().map(str::trim).find(|line| !line.is_empty())?;
+        Some(line.to_string())
+    }
+}
+
+/// Why a rule offered a correction, shown under it in the selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The output pattern found this text
+    Output(String),
+    /// The script pattern found this text
+    Script(String),
+    /// Neither pattern found anything to show; this is what the rule corrects
+    Described(String),
+    /// The rule matched without saying why
+    Matched,
+}
+
+/// Every rule's metadata, as exported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Catalog {
+    /// Version of the schema, [`SCHEMA_VERSION`].
+    pub schema_version: u32,
+    /// The rules, in registration order.
+    pub rules: Vec<RuleMetadata>,
+}
+
+impl Catalog {
+    /// Encodes the catalog as pretty-printed JSON, ending with a newline.
+    pub fn to_json(&self) -> crate::Result<String> {
+        serde_json::to_string_pretty(self)
+            .map(|json| json + "\n")
+            .map_err(|e| crate::Error::Other(format!("cannot encode rules as JSON: {}", e)))
+    }
+
+    /// Encodes the catalog as TOML, with a `[[rules]]` table per rule. Absent
+    /// fields are left out.
+    pub fn to_toml(&self) -> crate::Result<String> {
+        toml::to_string(self).map_err(|e| crate::Error::Other(format!("cannot encode rules as TOML: {}", e)))
+    }
+}
+
+/// Builds every rule of `factories` and collects their metadata, with each
+/// factory's category.
+pub fn export<'a>(factories: impl IntoIterator<Item = RuleFactory<'a>>) -> Catalog {
+    let mut rules = Vec::new();
+    for factory in factories {
+        let category = factory.category();
+        rules.extend(factory.build().iter().map(|rule| RuleMetadata {
+            category: Some(category.to_string()),
+            ..rule.metadata()
+        }));
+    }
+    Catalog {
+        schema_version: SCHEMA_VERSION,
+        rules,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::builtin_factories;
+    use crate::{Config, RuleContext};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_default_metadata_uses_required_substrings() {
+        let rule = crate::SimpleRuleBuilder::new("test")
+            .match_command("git push")
+            .match_output("rejected")
+            .priority(700)
+            .replace("git push", "git push --force");
+        let metadata = RuleMetadata::of(rule.as_ref());
+        assert_eq!(metadata.name, "test");
+        assert_eq!(metadata.priority, 700);
+        assert_eq!(metadata.match_command, Some(Pattern::substring("git push")));
+        assert_eq!(metadata.match_output, Some(Pattern::substring("rejected")));
+        assert_eq!(metadata.description, None);
+    }
+
+    #[test]
+    fn test_builtin_rules_are_described() {
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        let catalog = export(builtin_factories(&config, &context));
+        for rule in &catalog.rules {
+            assert!(rule.description.is_some(), "{} has no description", rule.name);
+        }
+    }
+
+    /// Built-in rules whose corrections come from the machine, such as its
+    /// files, history or what a tool lists, or from the config, so no
+    /// example of theirs could be replayed by the test below.
+    const WITHOUT_EXAMPLES: &[&str] = &[
+        "ansible_playbook_not_found",
+        "build_wrapper",
+        "cd_after_clone",
+        "cd_correction",
+        "chown_invalid_user",
+        "ffmpeg_unknown_encoder",
+        "gcloud_project_unset",
+        "heroku_missing_app",
+        "history_search",
+        "java_version_mismatch",
+        "just_unknown_recipe",
+        "kubectl_unauthorized",
+        "local_bin_resolver",
+        "make_no_rule",
+        "make_other_runner",
+        "manifest_in_subdirectory",
+        "ssh_unknown_host",
+        "sudo_previous",
+        "systemctl_unit_typo",
+    ];
+
+    #[test]
+    fn test_builtin_rules_have_examples() {
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        let catalog = export(builtin_factories(&config, &context));
+        let missing: Vec<&str> = catalog
+            .rules
+            .iter()
+            .filter(|rule| rule.example.is_none() && !WITHOUT_EXAMPLES.contains(&rule.name.as_str()))
+            .map(|rule| rule.name.as_str())
+            .collect();
+        assert!(missing.is_empty(), "rules without an example: {}", missing.join(", "));
+
+        let listed: Vec<&&str> = WITHOUT_EXAMPLES
+            .iter()
+            .filter(|name| !catalog.rules.iter().any(|rule| rule.name == **name && rule.example.is_none()))
+            .collect();
+        assert!(listed.is_empty(), "rules listed without an example that have one, or don't exist: {:?}", listed);
+    }
+
+    #[test]
+    fn test_builtin_examples_are_matched() {
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        for factory in builtin_factories(&config, &context) {
+            for rule in factory.build() {
+                let Some(example) = rule.metadata().example else {
+                    continue;
+                };
+                let command = example.command();
+                assert!(rule.matches(&command), "{} doesn't match its example", rule.name());
+                let corrections = rule.get_new_commands(&command);
+                assert!(
+                    corrections.contains(&example.correction),
+                    "{} corrects its example to {:?}",
+                    rule.name(),
+                    corrections
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtin_examples_are_matched_among_invalid_bytes() {
+        // Binary noise after the error, as when a tool goes on to print an
+        // archive, decoded as the CLI does
+        let noise = crate::output::cap_bytes(b"\x7fELF\x02\xff\xfe\xc3\x28\x80 noise \xf0\x28\x8c\x28", 1024);
+        assert!(noise.invalid_utf8);
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        for factory in builtin_factories(&config, &context) {
+            for rule in factory.build() {
+                let Some(example) = rule.metadata().example else {
+                    continue;
+                };
+                let mut command = example.command();
+                command.output = format!("{}\n{}\n", command.output, noise.text);
+                command.output_had_invalid_utf8 = true;
+                assert!(rule.matches(&command), "{} doesn't match its example among noise", rule.name());
+                let corrections = rule.get_new_commands(&command);
+                assert!(corrections.contains(&example.correction), "{} corrects to {:?}", rule.name(), corrections);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reason_shows_matched_text() {
+        let command = Command::new("git push", "fatal: The current branch has no upstream branch.\n", 128);
+        let rule = crate::RegexRuleBuilder::new("git_push_set_upstream")
+            .match_output_regex(r"has no upstream \w+")
+            .unwrap()
+            .replace_with(|_, _| vec!["git push -u origin main".to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(rule.metadata().reason(&command), Reason::Output("has no upstream branch".to_string()));
+
+        let rule = crate::SimpleRuleBuilder::new("test").match_command("git push").replace("a", "b");
+        assert_eq!(RuleMetadata::of(rule.as_ref()).reason(&command), Reason::Script("git push".to_string()));
+
+        let metadata = RuleMetadata::of(crate::SimpleRuleBuilder::new("test").replace("a", "b").as_ref());
+        assert_eq!(metadata.reason(&command), Reason::Matched);
+        assert_eq!(
+            metadata.describe("Sets the upstream").reason(&command),
+            Reason::Described("Sets the upstream".to_string())
+        );
+    }
+
+    #[test]
+    fn test_toml_leaves_out_absent_fields() {
+        let catalog = Catalog {
+            schema_version: SCHEMA_VERSION,
+            rules: vec![RuleMetadata {
+                category: Some("git".to_string()),
+                ..RuleMetadata::of(
+                    crate::SimpleRuleBuilder::new("test").match_command("git").replace("a", "b").as_ref(),
+                )
+            }],
+        };
+        let toml = catalog.to_toml().unwrap();
+        assert!(toml.contains("[[rules]]"));
+        assert!(toml.contains("[rules.match_command]\nkind = \"substring\"\npattern = \"git\""));
+        assert!(!toml.contains("match_output"));
+        let json: serde_json::Value = serde_json::from_str(&catalog.to_json().unwrap()).unwrap();
+        assert_eq!(json["rules"][0]["match_output"], serde_json::Value::Null);
+    }
+}
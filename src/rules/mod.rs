@@ -1,20 +1,188 @@
-/// Rule system for command correction.
-///
-/// This module provides the infrastructure for defining and managing correction rules.
+//! Rule system for command correction.
+//!
+//! This module provides the infrastructure for defining and managing correction rules.
 
+pub mod adapter;
 pub mod builders;
+pub mod factory;
+pub mod index;
 pub mod macros;
+pub mod metadata;
+pub mod overlap;
+pub mod priority;
+pub mod util;
 
+pub mod alternatives;
+pub mod aws;
+pub mod az;
+pub mod databases;
 pub mod git;
+pub mod history;
+pub mod jvm;
+pub mod kubectl;
+pub mod local_bin;
+pub mod media;
+pub mod monorepo;
+pub mod multiplexer;
+pub mod network;
+pub mod paas;
 pub mod permissions;
+pub mod process;
+pub mod provisioning;
+pub mod quoting;
+pub mod env;
 pub mod filesystem;
+pub mod forge;
+pub mod gcloud;
+pub mod golang;
+pub mod help;
 pub mod package_managers;
+pub mod runners;
+pub mod session;
+pub mod ssh;
+pub mod system;
+pub mod systemd;
+pub mod terraform;
+pub mod textutils;
+pub mod version_managers;
 
-pub use builders::{SimpleRuleBuilder, RegexRuleBuilder, FuzzyRuleBuilder};
+pub use builders::{PatternLimits, SimpleRuleBuilder, RegexRuleBuilder, FuzzyRuleBuilder};
+pub use factory::{RuleFactory, RuleInfo};
+pub use index::RuleIndex;
+pub use metadata::{Catalog, Example, Pattern, PatternKind, Reason, RuleMetadata};
+pub use overlap::Overlap;
+pub use priority::PriorityOverride;
 
-use crate::{Corrector, Rule};
-#[cfg(test)]
-use crate::Command;
+use crate::{Command, Config, Corrector, Rule, RuleContext};
+use util::escalate_with;
+use std::sync::Arc;
+
+/// Gets a factory for every built-in rule group, with options from `config`.
+///
+/// Groups tied to executables are only built for commands running one of
+/// them; the rest may match any command's output. Rules the config disables
+/// are still built.
+pub fn builtin_factories<'a>(config: &'a Config, context: &'a Arc<RuleContext>) -> Vec<RuleFactory<'a>> {
+    // Corrections of every group use the system's escalation tool instead of sudo
+    let escalate = move |rules| escalate_with(rules, Arc::clone(context), config.global.privilege_tool.clone());
+
+    let groups = vec![
+        // All git rules
+        RuleFactory::new("git", git::RULES, || {
+            let mut git_rules = git::git_branch_rules();
+            git_rules.extend(git::git_push_pull_rules());
+            git_rules.extend(git::git_staging_rules());
+            git_rules.extend(git::git_typo_rules());
+            git_rules
+        })
+        .for_executables(git::EXECUTABLES),
+        // Filesystem rules
+        RuleFactory::new("filesystem", filesystem::RULES, || {
+            filesystem::filesystem_rules(Arc::clone(context))
+        }),
+        // Permission rules
+        RuleFactory::new("permissions", permissions::RULES, || {
+            permissions::permission_rules(Arc::clone(context))
+        }),
+        // kill, pkill and port conflict rules
+        RuleFactory::new("process", process::RULES, process::process_rules),
+        // Package manager rules
+        RuleFactory::new("package_managers", package_managers::RULES, package_managers::package_manager_rules)
+            .for_executables(package_managers::EXECUTABLES),
+        // kubectl rules
+        RuleFactory::new("kubectl", kubectl::RULES, || {
+            kubectl::kubectl_rules(
+                config
+                    .rule_option("kubectl_unauthorized", "reauth_command")
+                    .map(str::to_string),
+            )
+        }),
+        // terraform rules
+        RuleFactory::new("terraform", terraform::RULES, || terraform::terraform_rules(Arc::clone(context)))
+            .for_executables(terraform::EXECUTABLES),
+        // aws cli rules
+        RuleFactory::new("aws", aws::RULES, || {
+            aws::aws_rules(
+                Arc::clone(context),
+                config.rule_option("aws_region_missing", "region").map(str::to_string),
+            )
+        }),
+        // gcloud rules
+        RuleFactory::new("gcloud", gcloud::RULES, || {
+            gcloud::gcloud_rules(
+                Arc::clone(context),
+                config.rule_option("gcloud_project_unset", "project").map(str::to_string),
+            )
+        })
+        .for_executables(gcloud::EXECUTABLES),
+        // az rules
+        RuleFactory::new("az", az::RULES, az::az_rules).for_executables(az::EXECUTABLES),
+        // ssh and scp rules
+        RuleFactory::new("ssh", ssh::RULES, || ssh::ssh_rules(Arc::clone(context))),
+        // systemctl and journalctl rules
+        RuleFactory::new("systemd", systemd::RULES, || systemd::systemd_rules(Arc::clone(context)))
+        .for_executables(systemd::EXECUTABLES),
+        // tmux and screen rules
+        RuleFactory::new("multiplexer", multiplexer::RULES, multiplexer::multiplexer_rules)
+            .for_executables(multiplexer::EXECUTABLES),
+        // curl, wget and HTTP rules
+        RuleFactory::new("network", network::RULES, || network::network_rules(Arc::clone(context)))
+            .for_executables(network::EXECUTABLES),
+        // heroku, flyctl and vercel rules
+        RuleFactory::new("paas", paas::RULES, || paas::paas_rules(Arc::clone(context)))
+            .for_executables(paas::EXECUTABLES),
+        // psql, mysql and redis-cli rules
+        RuleFactory::new("databases", databases::RULES, databases::database_rules)
+            .for_executables(databases::EXECUTABLES),
+        // grep, sed and awk rules
+        RuleFactory::new("textutils", textutils::RULES, textutils::textutils_rules)
+            .for_executables(textutils::EXECUTABLES),
+        // docker/podman, python3 and docker compose alternatives
+        RuleFactory::new("alternatives", alternatives::RULES, || {
+            alternatives::alternative_rules(Arc::clone(context))
+        }),
+        // virtualenv, node_modules and ./bin rules
+        RuleFactory::new("local_bin", local_bin::RULES, || local_bin::local_bin_rules(Arc::clone(context))),
+        // Environment variable rules
+        RuleFactory::new("env", env::RULES, || env::env_rules(config.env_defaults.clone())),
+        // Monorepo working-directory rules
+        RuleFactory::new("monorepo", monorepo::RULES, || monorepo::monorepo_rules(Arc::clone(context))),
+        // go toolchain rules
+        RuleFactory::new("golang", golang::RULES, || golang::go_rules(Arc::clone(context)))
+            .for_executables(golang::EXECUTABLES),
+        // java, gradle and maven rules
+        RuleFactory::new("jvm", jvm::RULES, || jvm::jvm_rules(Arc::clone(context))),
+        // ansible and vagrant rules
+        RuleFactory::new("provisioning", provisioning::RULES, || {
+            provisioning::provisioning_rules(Arc::clone(context))
+        })
+        .for_executables(provisioning::EXECUTABLES),
+        // make, just and task rules
+        RuleFactory::new("runners", runners::RULES, || runners::runner_rules(Arc::clone(context))),
+        // gh and glab rules
+        RuleFactory::new("forge", forge::RULES, forge::forge_rules).for_executables(forge::EXECUTABLES),
+        // ffmpeg and ImageMagick rules
+        RuleFactory::new("media", media::RULES, || media::media_rules(Arc::clone(context)))
+            .for_executables(media::EXECUTABLES),
+        // nvm, pyenv, rustup and SDKMAN! rules
+        RuleFactory::new("version_managers", version_managers::RULES, || {
+            version_managers::version_manager_rules(Arc::clone(context))
+        }),
+        // Unclosed quotes, history expansion and backtick rules
+        RuleFactory::new("quoting", quoting::RULES, quoting::quoting_rules),
+        // "Did you mean" hints of tools without their own rules
+        RuleFactory::new("system", system::RULES, system::system_rules),
+        // man and --help fallbacks for usage errors
+        RuleFactory::new("help", help::RULES, help::help_rules),
+        // History-based rules (history is only read if one of them is evaluated)
+        RuleFactory::new("history", history::RULES, || {
+            history::history_rules(Arc::clone(context), config.global.history_scan_limit)
+        }),
+        // Session-based rules (the session cache is only read if one of them is evaluated)
+        RuleFactory::new("session", session::RULES, || session::session_rules(Arc::clone(context))),
+    ];
+    groups.into_iter().map(|group| group.map_rules(escalate)).collect()
+}
 
 /// Registry that manages all available rules.
 pub struct RuleRegistry {
@@ -39,6 +207,22 @@ impl RuleRegistry {
         self.rules.extend(rules);
     }
 
+    /// Builds and adds the rules of every factory that could match `script`,
+    /// or of every factory if there is no script to correct.
+    pub fn add_factories<'a>(&mut self, factories: impl IntoIterator<Item = RuleFactory<'a>>, script: Option<&str>) {
+        let executables = script.map(factory::command_executables);
+        for factory in factories {
+            if executables.as_ref().is_none_or(|executables| factory.could_match(executables)) {
+                self.rules.extend(factory.build());
+            }
+        }
+    }
+
+    /// Keeps only the rules `keep` returns true for.
+    pub fn retain(&mut self, keep: impl Fn(&dyn Rule) -> bool) {
+        self.rules.retain(|rule| keep(rule.as_ref()));
+    }
+
     /// Gets the number of registered rules.
     pub fn len(&self) -> usize {
         self.rules.len()
@@ -54,17 +238,43 @@ impl RuleRegistry {
         &self.rules
     }
 
+    /// Indexes the rules by their leading token.
+    pub fn index(&self) -> RuleIndex {
+        RuleIndex::new(&self.rules)
+    }
+
+    /// Runs every rule over `corpus` and inputs synthesized from the rules'
+    /// own triggers, and gets the pairs that corrected a failure together.
+    pub fn find_overlaps(&self, corpus: &[Command]) -> Vec<Overlap> {
+        overlap::find(&self.rules, corpus)
+    }
+
     /// Gets mutable access to rules (for disabling/enabling).
     pub fn rules_mut(&mut self) -> &mut [Box<dyn Rule>] {
         &mut self.rules
     }
 
+    /// Re-prioritizes rules: any rule whose `priority_for` differs from its
+    /// own priority is wrapped in a `PriorityOverride`.
+    pub fn override_priorities(&mut self, priority_for: impl Fn(&dyn Rule) -> i32) {
+        self.rules = std::mem::take(&mut self.rules)
+            .into_iter()
+            .map(|rule| {
+                let priority = priority_for(rule.as_ref());
+                if priority == rule.priority() {
+                    rule
+                } else {
+                    Box::new(PriorityOverride::new(rule, priority)) as Box<dyn Rule>
+                }
+            })
+            .collect();
+    }
+
     /// Gets all enabled rules.
+    // Boxed as it always was, so library callers naming the type still build
+    #[allow(clippy::borrowed_box)]
     pub fn enabled_rules(&self) -> Vec<&Box<dyn Rule>> {
-        self.rules
-            .iter()
-            .filter(|rule| rule.enabled_by_default())
-            .collect()
+        self.rules.iter().filter(|rule| rule.enabled_by_default()).collect()
     }
 
     /// Creates a corrector from this registry.
@@ -139,4 +349,63 @@ mod tests {
 
         assert_eq!(registry.len(), 2);
     }
+
+    #[test]
+    fn test_builtin_factories_list_their_rules() {
+        let config = Config::default();
+        let context = Arc::new(RuleContext::default());
+        for factory in builtin_factories(&config, &context) {
+            let category = factory.category();
+            let listed = factory.rules().to_vec();
+            let built: Vec<_> = factory.build().iter().map(|rule| (rule.name().to_string(), rule.priority())).collect();
+            let listed: Vec<_> = listed.into_iter().map(|(name, priority)| (name.to_string(), priority)).collect();
+            assert_eq!(built, listed, "RULES of {} is out of date", category);
+        }
+    }
+
+    #[test]
+    fn test_builtin_rules_use_the_system_escalation_tool() {
+        let shell = crate::testing::MockShell::new().with_output("doas --version", "", 0);
+        let context = Arc::new(RuleContext::new(Box::new(shell)));
+        let mut registry = RuleRegistry::new();
+        registry.add_factories(builtin_factories(&Config::default(), &context), None);
+        let corrector = registry.into_corrector();
+
+        let redis = Command::new(
+            "redis-cli ping",
+            "Could not connect to Redis at 127.0.0.1:6379: Connection refused\n",
+            1,
+        );
+        let journal = Command::new(
+            "journalctl -u nginx",
+            "No journal files were opened due to insufficient permissions.\n",
+            1,
+        );
+        for (cmd, expected) in [
+            (&redis, "doas systemctl start redis && redis-cli ping"),
+            (&journal, "doas journalctl -u nginx"),
+        ] {
+            let corrections = corrector.get_corrections(cmd);
+            assert!(corrections.iter().any(|correction| correction.script == expected), "{:?}", corrections);
+            assert!(corrections.iter().all(|correction| !correction.script.starts_with("sudo")));
+        }
+    }
+
+    #[test]
+    fn test_rule_registry_override_priorities() {
+        let mut registry = RuleRegistry::new();
+        registry.add_rules(vec![
+            Box::new(TestRule {
+                name: "test1".to_string(),
+            }),
+            Box::new(TestRule {
+                name: "test2".to_string(),
+            }),
+        ]);
+
+        registry.override_priorities(|rule| if rule.name() == "test2" { 10 } else { rule.priority() });
+
+        let priorities: Vec<_> = registry.rules().iter().map(|r| r.priority()).collect();
+        assert_eq!(priorities, vec![1000, 10]);
+    }
 }
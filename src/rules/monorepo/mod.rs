@@ -0,0 +1,229 @@
+//! Monorepo working-directory rules.
+//!
+//! This module contains rules for build tools run from the wrong directory,
+//! usually the root of a monorepo whose projects live in subdirectories:
+//! - cargo without a `Cargo.toml`
+//! - npm without a `package.json`
+//! - go without a `go.mod`
+//! - maven without a `pom.xml`
+//!
+//! The directory walk is bounded in depth and size, and skips dependency and
+//! build directories.
+
+use super::{RuleInfo, RuleMetadata};
+use super::util::prefix_then_retry;
+use crate::{Command, Rule, RuleContext, Script};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How many directory levels below the cwd are searched.
+const MAX_DEPTH: usize = 2;
+
+/// Maximum number of directories read during one search.
+const MAX_VISITED_DIRS: usize = 256;
+
+/// Maximum number of directories suggested.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Each tool, the error fragment it prints for a missing manifest, and the
+/// manifest's file name.
+const MISSING_MANIFESTS: &[(&str, &str, &str)] = &[
+    ("cargo", "could not find `Cargo.toml`", "Cargo.toml"),
+    ("npm", "/package.json'", "package.json"),
+    ("go", "go.mod file not found", "go.mod"),
+    ("mvn", "there is no POM in this directory", "pom.xml"),
+];
+
+/// Directories that never contain the project the user meant.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", ".git"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("manifest_in_subdirectory", 300),
+];
+
+/// Creates all monorepo rules.
+///
+/// Manifests are searched for below the working directory of the context's
+/// shell.
+pub fn monorepo_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // manifest_in_subdirectory: cd into a subdirectory that has the manifest
+        Box::new(ManifestInSubdirectoryRule::new(context)),
+    ]
+}
+
+/// manifest_in_subdirectory: `cargo build` → `cd services/api && cargo build`
+pub struct ManifestInSubdirectoryRule {
+    context: Arc<RuleContext>,
+}
+
+impl ManifestInSubdirectoryRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the manifest the command's tool complained about.
+    fn missing_manifest(command: &Command) -> Option<&'static str> {
+        let script = Script::parse(&command.script);
+        let executable = script.executable()?;
+        MISSING_MANIFESTS
+            .iter()
+            .find(|(tool, message, _)| *tool == executable && command.output.contains(message))
+            .map(|(_, _, manifest)| *manifest)
+    }
+}
+
+/// Finds directories below `root` containing `manifest`, relative to
+/// `root` and shortest first.
+pub(super) fn find_manifest_dirs(root: &Path, manifest: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut visited = 0;
+    let mut level = vec![PathBuf::new()];
+
+    for _ in 0..MAX_DEPTH {
+        let mut next = Vec::new();
+        for relative in level {
+            if visited == MAX_VISITED_DIRS {
+                break;
+            }
+            visited += 1;
+
+            let Ok(entries) = std::fs::read_dir(root.join(&relative)) else {
+                continue;
+            };
+            let mut children: Vec<PathBuf> = entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                .filter(|entry| !SKIPPED_DIRS.iter().any(|skipped| entry.file_name() == *skipped))
+                .map(|entry| relative.join(entry.file_name()))
+                .collect();
+            children.sort();
+
+            found.extend(
+                children
+                    .iter()
+                    .filter(|child| root.join(child).join(manifest).is_file())
+                    .cloned(),
+            );
+            next.extend(children);
+        }
+        level = next;
+    }
+
+    found.sort_by(|a, b| {
+        let (a, b) = (a.as_os_str(), b.as_os_str());
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    });
+    found
+}
+
+impl Rule for ManifestInSubdirectoryRule {
+    fn name(&self) -> &str {
+        "manifest_in_subdirectory"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Change into the subdirectory with the Cargo.toml, package.json, go.mod or pom.xml a build tool needs")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.exit_code != 0 && Self::missing_manifest(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(manifest) = Self::missing_manifest(command) else {
+            return vec![];
+        };
+        let Some(cwd) = self.context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return vec![];
+        };
+
+        find_manifest_dirs(&cwd, manifest)
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|dir| {
+                let cd = Script::from_tokens(["cd".to_string(), dir.to_string_lossy().into_owned()]);
+                prefix_then_retry(&cd.to_string(), &command.script)
+            })
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const CARGO_NO_MANIFEST: &str = "error: could not find `Cargo.toml` in `/home/dev/src/monorepo` or any parent directory\n";
+
+    const NPM_NO_PACKAGE_JSON: &str = "npm error code ENOENT\nnpm error syscall open\nnpm error path /home/dev/src/monorepo/package.json\nnpm error errno -2\nnpm error enoent Could not read package.json: Error: ENOENT: no such file or directory, open '/home/dev/src/monorepo/package.json'\n";
+
+    const GO_NO_MODULE: &str = "go: go.mod file not found in current directory or any parent directory; see 'go help modules'\n";
+
+    const MAVEN_NO_POM: &str = "[ERROR] The goal you specified requires a project to execute but there is no POM in this directory (/home/dev/src/monorepo). Please verify you invoked Maven from the correct directory. -> [Help 1]\n";
+
+    fn rule() -> ManifestInSubdirectoryRule {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/monorepo");
+        let shell = MockShell::new().with_cwd(fixture);
+        ManifestInSubdirectoryRule::new(Arc::new(RuleContext::new(Box::new(shell))))
+    }
+
+    #[test]
+    fn test_cargo_shortest_paths_first() {
+        let cmd = Command::new("cargo build --release", CARGO_NO_MANIFEST, 101);
+
+        assert!(rule().matches(&cmd));
+        // services/billing-worker is a fourth hit, and tools/deploy/scripts is too deep
+        assert_eq!(
+            rule().get_new_commands(&cmd),
+            vec![
+                "cd cli && cargo build --release",
+                "cd services/api && cargo build --release",
+                "cd services/ledger && cargo build --release",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_npm_skips_node_modules() {
+        let cmd = Command::new("npm run dev", NPM_NO_PACKAGE_JSON, 254);
+        assert_eq!(rule().get_new_commands(&cmd), vec!["cd services/web && npm run dev"]);
+    }
+
+    #[test]
+    fn test_go_and_maven_manifests() {
+        let cmd = Command::new("go test ./...", GO_NO_MODULE, 1);
+        assert_eq!(rule().get_new_commands(&cmd), vec!["cd services/gateway && go test ./..."]);
+
+        let cmd = Command::new("mvn -q package", MAVEN_NO_POM, 1);
+        assert_eq!(rule().get_new_commands(&cmd), vec!["cd legacy && mvn -q package"]);
+    }
+
+    #[test]
+    fn test_manifest_without_shell() {
+        let rule = ManifestInSubdirectoryRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new("cargo build", CARGO_NO_MANIFEST, 101);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_other_errors() {
+        let cmd = Command::new("cargo build", "error[E0425]: cannot find value `x` in this scope", 101);
+        assert!(!rule().matches(&cmd));
+    }
+
+    #[test]
+    fn test_monorepo_rules_exist() {
+        let rules = monorepo_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["manifest_in_subdirectory"]);
+    }
+}
@@ -0,0 +1,440 @@
+//! tmux and screen command correction rules.
+//!
+//! This module contains rules for common terminal multiplexer mistakes:
+//! - Attaching when no tmux session exists
+//! - Creating a tmux session whose name is already taken
+//! - Mistyped or ambiguous tmux commands
+//! - Starting tmux from inside tmux
+//! - `screen -r` when several sessions could be resumed
+//!
+//! Where the error lists candidates (ambiguous commands, screen sessions),
+//! each one becomes its own correction.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::listed_suggestions;
+use crate::fuzzy::similarity;
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, Script};
+
+/// tmux commands and their aliases.
+const TMUX_COMMANDS: &[&str] = &[
+    "attach-session", "attach", "detach-client", "detach", "has-session", "has",
+    "kill-server", "kill-session", "kill-window", "killw", "kill-pane", "killp",
+    "list-clients", "lsc", "list-commands", "lscm", "list-sessions", "ls",
+    "list-windows", "lsw", "list-panes", "lsp", "list-keys", "lsk",
+    "list-buffers", "lsb", "new-session", "new", "new-window", "neww",
+    "rename-session", "rename", "rename-window", "renamew", "refresh-client",
+    "refresh", "source-file", "source", "start-server", "start",
+    "switch-client", "switchc", "select-window", "selectw", "select-pane",
+    "selectp", "select-layout", "selectl", "split-window", "splitw",
+    "resize-pane", "resizep", "swap-pane", "swapp", "swap-window", "swapw",
+    "move-window", "movew", "join-pane", "joinp", "break-pane", "breakp",
+    "send-keys", "send", "bind-key", "bind", "unbind-key", "unbind",
+    "set-option", "set", "show-options", "show", "set-environment", "setenv",
+    "show-environment", "showenv", "capture-pane", "capturep",
+    "display-message", "display", "copy-mode", "choose-tree", "next-window",
+    "next", "previous-window", "prev", "last-window", "last", "run-shell",
+    "run", "if-shell", "if", "save-buffer", "saveb", "paste-buffer", "pasteb",
+    "show-messages", "showmsgs", "lock-session", "locks", "wait-for", "wait",
+];
+
+/// Session created when attaching finds nothing to attach to.
+const DEFAULT_SESSION: &str = "main";
+
+/// Minimum similarity for a tmux command to be suggested.
+const MIN_COMMAND_SIMILARITY: f64 = 0.6;
+
+/// Maximum number of tmux command suggestions.
+const MAX_COMMAND_SUGGESTIONS: usize = 3;
+
+/// Executables every multiplexer rule requires.
+pub const EXECUTABLES: &[&str] = &["tmux", "screen"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("tmux_no_sessions", 300),
+    ("tmux_duplicate_session", 300),
+    ("tmux_unknown_command", 300),
+    ("tmux_nested", 400),
+    ("screen_several_sessions", 300),
+];
+
+/// Creates all tmux and screen rules.
+pub fn multiplexer_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // tmux_no_sessions: Create a session instead of attaching
+        create_tmux_no_sessions(),
+        // tmux_duplicate_session: Attach to the existing session
+        create_tmux_duplicate_session(),
+        // tmux_unknown_command: Fix a mistyped or ambiguous tmux command
+        Box::new(TmuxUnknownCommandRule),
+        // tmux_nested: Switch or create detached instead of nesting
+        create_tmux_nested(),
+        // screen_several_sessions: Resume a specific screen session
+        Box::new(ScreenSeveralSessionsRule),
+    ]
+}
+
+/// Gets the value of `flag` (`-t name` or `-tname`) from a script.
+fn flag_value<'a>(script: &'a Script, flag: &str) -> Option<&'a str> {
+    let tokens = script.tokens();
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        if token == flag {
+            tokens.get(i + 1).map(String::as_str)
+        } else {
+            token.strip_prefix(flag).filter(|value| !value.is_empty())
+        }
+    })
+}
+
+/// tmux_no_sessions: `tmux attach` → `tmux new -s main`
+fn create_tmux_no_sessions() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("tmux_no_sessions")
+        .match_command_static(static_regex!(r"^tmux\s+(a|at|attach|attach-session)\b"))
+        .match_output_static(static_regex!(r"^(no sessions|can't find session|error connecting to)"))
+        .priority(300)
+        .describe("Create a tmux session when there's none to attach to")
+        .example(Example::new("tmux attach", "no sessions", 1, "tmux new -s main"))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let name = flag_value(&script, "-t").unwrap_or(DEFAULT_SESSION);
+            vec![Script::from_tokens(["tmux", "new", "-s", name]).to_string()]
+        })
+        .build_builtin()
+}
+
+/// tmux_duplicate_session: `tmux new -s work` → `tmux attach -t work`
+fn create_tmux_duplicate_session() -> Box<dyn Rule> {
+    // The session name comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("tmux_duplicate_session")
+        .match_output_static(static_regex!(r"duplicate session: (\S+)"))
+        .priority(300)
+        .describe("Attach to a tmux session whose name is already taken")
+        .example(Example::new("tmux new -s work", "duplicate session: work", 1, "tmux attach -t work"))
+        .replace_with(|original, captures| {
+            let Some(name) = captures.get(1).map(|m| m.as_str()) else {
+                return vec![];
+            };
+            if Script::parse(original).executable() != Some("tmux") {
+                return vec![];
+            }
+            vec![Script::from_tokens(["tmux", "attach", "-t", name]).to_string()]
+        })
+        .build_builtin()
+}
+
+/// tmux_nested: `tmux new -s work` inside tmux → `tmux new -d -s work &&
+/// tmux switch-client -t work`; `tmux attach -t work` → `tmux switch-client -t work`
+fn create_tmux_nested() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("tmux_nested")
+        .match_command_static(static_regex!(r"^tmux\b"))
+        .match_output_static(static_regex!(r"sessions should be nested with care"))
+        .priority(400)
+        .describe("Switch to the session, or create it detached, instead of nesting tmux")
+        .example(Example::new(
+            "tmux attach -t work",
+            "sessions should be nested with care, unset $TMUX to force",
+            1,
+            "tmux switch-client -t work",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let subcommand = script
+                .tokens()
+                .iter()
+                .skip(1)
+                .find(|token| !token.starts_with('-'))
+                .map(String::as_str);
+
+            let mut corrections = Vec::new();
+            match subcommand {
+                Some("a" | "at" | "attach" | "attach-session") => {
+                    if let Some(name) = flag_value(&script, "-t") {
+                        let switch = Script::from_tokens(["tmux", "switch-client", "-t", name]);
+                        corrections.push(switch.to_string());
+                    }
+                }
+                Some("new" | "new-session") | None => {
+                    let detached = match subcommand {
+                        Some(subcommand) => script.clone().insert_flag_after(subcommand, "-d"),
+                        None => script.clone().push_token("new").push_token("-d"),
+                    };
+                    let correction = match flag_value(&script, "-s") {
+                        Some(name) => detached.append(
+                            " && ",
+                            Script::from_tokens(["tmux", "switch-client", "-t", name]),
+                        ),
+                        None => detached,
+                    };
+                    corrections.push(correction.to_string());
+                }
+                _ => {}
+            }
+
+            // Nesting on purpose is still an option
+            corrections.push(script.prepend_token("TMUX=").to_string());
+            corrections
+        })
+        .build_builtin()
+}
+
+/// tmux_unknown_command: `tmux attch` → `tmux attach`; `tmux kill-s` →
+/// `tmux kill-server` or `tmux kill-session`
+struct TmuxUnknownCommandRule;
+
+impl TmuxUnknownCommandRule {
+    /// Gets the rejected command from tmux's error.
+    fn rejected(output: &str) -> Option<&str> {
+        let rest = output
+            .strip_prefix("unknown command: ")
+            .or_else(|| output.strip_prefix("ambiguous command: "))?;
+        rest.split([',', '\n']).next().map(str::trim)
+    }
+
+    /// Gets the candidates tmux lists for an ambiguous command.
+    fn ambiguous_candidates(output: &str) -> Vec<&str> {
+        output
+            .split("could be: ")
+            .nth(1)
+            .map(|list| list.lines().next().unwrap_or("").split(", ").map(str::trim).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Rule for TmuxUnknownCommandRule {
+    fn name(&self) -> &str {
+        "tmux_unknown_command"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Fix a mistyped or ambiguous tmux command")
+            .example(Example::new("tmux attch", "unknown command: attch", 1, "tmux attach"))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("tmux")
+            && Self::rejected(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::rejected(&command.output) else {
+            return vec![];
+        };
+
+        // tmux's own candidates for an ambiguous prefix need no threshold
+        let ambiguous = Self::ambiguous_candidates(&command.output);
+        let (candidates, threshold) = if ambiguous.is_empty() {
+            (TMUX_COMMANDS.to_vec(), MIN_COMMAND_SIMILARITY)
+        } else {
+            (ambiguous, 0.0)
+        };
+
+        let mut scored: Vec<(&str, f64)> = candidates
+            .into_iter()
+            .map(|candidate| (candidate, similarity(candidate, typo)))
+            .filter(|(_, score)| *score >= threshold)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let script = Script::parse(&command.script);
+        scored
+            .into_iter()
+            .take(MAX_COMMAND_SUGGESTIONS)
+            .map(|(candidate, _)| script.clone().replace_token(typo, candidate).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// screen_several_sessions: `screen -r` → `screen -r 47730.build`, one per
+/// session; attached sessions are detached elsewhere first with `-d`
+struct ScreenSeveralSessionsRule;
+
+impl ScreenSeveralSessionsRule {
+    /// Gets `(id, attached)` for every session screen listed.
+    fn sessions(output: &str) -> Vec<(&str, bool)> {
+        listed_suggestions(output, " on:")
+            .into_iter()
+            .filter_map(|line| {
+                let id = line.split_whitespace().next()?;
+                Some((id, line.contains("(Attached)")))
+            })
+            .collect()
+    }
+}
+
+impl Rule for ScreenSeveralSessionsRule {
+    fn name(&self) -> &str {
+        "screen_several_sessions"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Resume each screen session `screen -r` listed")
+            .example(Example::new(
+                "screen -r",
+                "There are several suitable screens on:\n\t47730.build\t(Detached)\n\t47712.logs\t(Detached)",
+                1,
+                "screen -r 47730.build",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("screen")
+            && (command.output.contains("There are several suitable screens on:")
+                || command.output.contains("There is no screen to be resumed"))
+            && !Self::sessions(&command.output).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        Self::sessions(&command.output)
+            .into_iter()
+            .map(|(id, attached)| {
+                let flags: &[&str] = if attached { &["-d", "-r"] } else { &["-r"] };
+                Script::from_tokens(std::iter::once("screen").chain(flags.iter().copied()).chain([id]))
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN_SEVERAL: &str = include_str!("../../../tests/fixtures/multiplexer/screen_several");
+    const SCREEN_ATTACHED: &str = include_str!("../../../tests/fixtures/multiplexer/screen_attached");
+    const TMUX_AMBIGUOUS: &str = include_str!("../../../tests/fixtures/multiplexer/tmux_ambiguous");
+
+    #[test]
+    fn test_tmux_no_sessions() {
+        let rule = create_tmux_no_sessions();
+        let cmd = Command::new("tmux attach", "no sessions\n", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["tmux new -s main"]);
+
+        let cmd = Command::new(
+            "tmux a -t work",
+            "error connecting to /tmp/tmux-1000/default (No such file or directory)\n",
+            1,
+        );
+        assert_eq!(rule.get_new_commands(&cmd), vec!["tmux new -s work"]);
+    }
+
+    #[test]
+    fn test_tmux_duplicate_session() {
+        let rule = create_tmux_duplicate_session();
+        let cmd = Command::new("tmux new -s work", "duplicate session: work\n", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["tmux attach -t work"]);
+    }
+
+    #[test]
+    fn test_tmux_unknown_command_typos() {
+        let rule = TmuxUnknownCommandRule;
+
+        let cmd = Command::new("tmux attch -t work", "unknown command: attch\n", 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd)[0], "tmux attach -t work");
+
+        let cmd = Command::new("tmux kil-session -t work", "unknown command: kil-session\n", 1);
+        assert_eq!(rule.get_new_commands(&cmd)[0], "tmux kill-session -t work");
+    }
+
+    #[test]
+    fn test_tmux_ambiguous_command_one_correction_per_candidate() {
+        let rule = TmuxUnknownCommandRule;
+        let cmd = Command::new("tmux kill-s -t work", TMUX_AMBIGUOUS, 1);
+
+        let mut corrections = rule.get_new_commands(&cmd);
+        corrections.sort();
+        assert_eq!(
+            corrections,
+            vec!["tmux kill-server -t work", "tmux kill-session -t work"]
+        );
+    }
+
+    #[test]
+    fn test_tmux_nested_new_session() {
+        let rule = create_tmux_nested();
+        let cmd = Command::new(
+            "tmux new -s work",
+            "sessions should be nested with care, unset $TMUX to force\n",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "tmux new -d -s work && tmux switch-client -t work",
+                "TMUX= tmux new -s work",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tmux_nested_attach() {
+        let rule = create_tmux_nested();
+        let cmd = Command::new(
+            "tmux attach -t logs",
+            "sessions should be nested with care, unset $TMUX to force\n",
+            1,
+        );
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["tmux switch-client -t logs", "TMUX= tmux attach -t logs"]
+        );
+    }
+
+    #[test]
+    fn test_screen_several_sessions() {
+        let rule = ScreenSeveralSessionsRule;
+        let cmd = Command::new("screen -r", SCREEN_SEVERAL, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "screen -r 48213.pts-3.devbox",
+                "screen -r 47730.build",
+                "screen -d -r 41207.logs",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_screen_only_attached_session() {
+        let rule = ScreenSeveralSessionsRule;
+        let cmd = Command::new("screen -r", SCREEN_ATTACHED, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["screen -d -r 41207.logs"]);
+    }
+
+    #[test]
+    fn test_multiplexer_rules_exist() {
+        let rules = multiplexer_rules();
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "tmux_no_sessions",
+                "tmux_duplicate_session",
+                "tmux_unknown_command",
+                "tmux_nested",
+                "screen_several_sessions",
+            ]
+        );
+    }
+}
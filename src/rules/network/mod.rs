@@ -0,0 +1,590 @@
+//! curl, wget and HTTP command correction rules.
+//!
+//! This module contains rules for common download and HTTP mistakes:
+//! - URLs with a missing or mistyped scheme
+//! - Redirect responses that curl doesn't follow by default
+//! - Certificate errors (skipping verification is offered last, and is
+//!   listed in the default `dangerous_patterns`)
+//! - Translating between curl and wget when only one is installed
+//! - Request methods that don't match the data being sent
+//!
+//! URLs are handled as whole tokens, so query strings stay intact.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::requires_binary;
+use crate::{static_regex, Command, FailureKind, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// curl options that take a separate value.
+const CURL_VALUE_OPTIONS: &[&str] = &[
+    "-o", "--output", "-X", "--request", "-H", "--header", "-d", "--data",
+    "--data-raw", "--data-binary", "--data-urlencode", "-F", "--form", "-u",
+    "--user", "-A", "--user-agent", "-e", "--referer", "-b", "--cookie", "-c",
+    "--cookie-jar", "-x", "--proxy", "-T", "--upload-file", "-w",
+    "--write-out", "-m", "--max-time", "--connect-timeout", "-r", "--range",
+    "--cacert", "--cert", "--key", "-E", "--retry", "-C", "--continue-at",
+];
+
+/// curl options that send a request body.
+const CURL_DATA_OPTIONS: &[&str] = &[
+    "-d", "--data", "--data-raw", "--data-binary", "--data-urlencode", "-F", "--form",
+];
+
+/// Methods offered when a server answers 405 with an `Allow` header.
+const SUGGESTED_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+/// Executables every network rule requires.
+pub const EXECUTABLES: &[&str] = &["curl", "wget"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("curl_url_scheme", 300),
+    ("curl_follow_redirects", 300),
+    ("curl_insecure", 9000),
+    ("curl_wget_translate", 200),
+    ("curl_method", 400),
+];
+
+/// Creates all curl, wget and HTTP rules.
+///
+/// The translation rule checks which of curl and wget is installed through
+/// the context's shell.
+pub fn network_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // curl_url_scheme: Add or fix the URL scheme
+        create_curl_url_scheme(),
+        // curl_follow_redirects: Add -L when the response is a redirect
+        create_curl_follow_redirects(),
+        // curl_insecure: Skip certificate verification (last resort)
+        create_curl_insecure(),
+        // curl_wget_translate: Use wget when curl is missing, and vice versa
+        requires_binary(Box::new(CurlWgetTranslateRule), context),
+        // curl_method: Fix the request method
+        Box::new(CurlMethodRule),
+    ]
+}
+
+/// Gets the index of the URL in a curl script: the value of `--url`, or the
+/// first argument that isn't an option or an option's value.
+fn curl_url_index(script: &Script) -> Option<usize> {
+    let tokens = script.tokens();
+    let mut index = 1;
+    while index < tokens.len() {
+        let token = tokens[index].as_str();
+        if token == "--url" {
+            return (index + 1 < tokens.len()).then_some(index + 1);
+        }
+        if CURL_VALUE_OPTIONS.contains(&token) {
+            index += 2;
+        } else if token.starts_with('-') {
+            index += 1;
+        } else {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// curl_url_scheme: `curl example.com/api` → `curl https://example.com/api`;
+/// `curl htps://example.com` → `curl https://example.com`
+fn create_curl_url_scheme() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("curl_url_scheme")
+        .match_command_static(static_regex!(r"^curl\s"))
+        .match_output_static(static_regex!(r#"curl: \((1|3)\) (URL using bad/illegal format|URL rejected|Protocol "[^"]*" not supported)"#))
+        .priority(300)
+        .describe("Add a missing URL scheme, or fix a mistyped one")
+        .example(Example::new(
+            "curl htps://example.com",
+            "curl: (1) Protocol \"htps\" not supported or disabled in libcurl",
+            1,
+            "curl https://example.com",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let Some(index) = curl_url_index(&script) else {
+                return vec![];
+            };
+
+            let url = &script.tokens()[index];
+            let rest = match url.split_once("://") {
+                // Only fix schemes that look like a typo of http(s)
+                Some((scheme, rest)) if scheme.starts_with('h') && scheme != "https" => rest,
+                Some(_) => return vec![],
+                None => url.trim_start_matches(['/', ':']),
+            };
+            if rest.is_empty() {
+                return vec![];
+            }
+
+            let fixed = format!("https://{}", rest);
+            vec![script.clone().replace_token_at(index, fixed).to_string()]
+        })
+        .build_builtin()
+}
+
+/// curl_follow_redirects: `curl http://example.com` → `curl -L http://example.com`
+fn create_curl_follow_redirects() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("curl_follow_redirects")
+        .match_command_static(static_regex!(r"^curl\s"))
+        .match_output_static(static_regex!(r"(?i)<title>30[1278] |\b(Moved Permanently|302 Found|Temporary Redirect|Permanent Redirect)\b|^HTTP/\S+ 30[1278]"))
+        .priority(300)
+        .describe("Follow a redirect curl printed instead of following")
+        .example(Example::new(
+            "curl http://example.com",
+            "<html><head><title>301 Moved Permanently</title></head></html>",
+            0,
+            "curl -L http://example.com",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let follows = script.tokens().iter().any(|token| {
+                token == "--location"
+                    || (token.starts_with('-') && !token.starts_with("--") && token.contains('L'))
+            });
+            if follows {
+                return vec![];
+            }
+            vec![script.insert_flag_after("curl", "-L").to_string()]
+        })
+        .build_builtin()
+}
+
+/// curl_insecure: `curl https://self-signed.local` → `curl -k https://self-signed.local`
+///
+/// This disables certificate checks, so it is only ever the last suggestion.
+fn create_curl_insecure() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("curl_insecure")
+        .match_command_static(static_regex!(r"^curl\s"))
+        .match_output_static(static_regex!(r"curl: \(60\) SSL certificate problem"))
+        .priority(9000)
+        .describe("Skip certificate verification, as a last resort")
+        .example(Example::new(
+            "curl https://self-signed.local",
+            "curl: (60) SSL certificate problem: self-signed certificate",
+            60,
+            "curl -k https://self-signed.local",
+        ))
+        .replace_with(|original, _captures| {
+            vec![Script::parse(original).insert_flag_after("curl", "-k").to_string()]
+        })
+        .build_builtin()
+}
+
+/// curl_wget_translate: `wget https://x/f.tgz` → `curl -fLO https://x/f.tgz`
+/// when wget is missing, and `curl https://x` → `wget -qO- https://x` when
+/// curl is.
+///
+/// Registered through [`requires_binary`], so nothing is suggested when the
+/// other tool is missing too.
+pub struct CurlWgetTranslateRule;
+
+impl CurlWgetTranslateRule {
+    /// Translates a wget script to curl. Unknown options give `None`.
+    fn wget_to_curl(script: &Script) -> Option<Script> {
+        let mut output: Option<String> = None;
+        let mut extra: Vec<String> = Vec::new();
+        let mut urls: Vec<String> = Vec::new();
+
+        let mut tokens = script.tokens().iter().skip(1);
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "-q" | "--quiet" => extra.push("-s".to_string()),
+                "-c" | "--continue" => extra.extend(["-C".to_string(), "-".to_string()]),
+                "-qO-" => {
+                    extra.push("-s".to_string());
+                    output = Some("-".to_string());
+                }
+                "-qO" => {
+                    extra.push("-s".to_string());
+                    output = Some(tokens.next()?.clone());
+                }
+                "-O" | "--output-document" => output = Some(tokens.next()?.clone()),
+                "--header" | "-U" | "--user-agent" => {
+                    let flag = if token == "--header" { "-H" } else { "-A" };
+                    extra.extend([flag.to_string(), tokens.next()?.clone()]);
+                }
+                other if other.starts_with("-O") => output = Some(other[2..].to_string()),
+                other if other.starts_with("--output-document=") => {
+                    output = Some(other["--output-document=".len()..].to_string())
+                }
+                other if other.starts_with("--header=") => {
+                    extra.extend(["-H".to_string(), other["--header=".len()..].to_string()])
+                }
+                other if other.starts_with('-') => return None,
+                url => urls.push(url.to_string()),
+            }
+        }
+        if urls.is_empty() {
+            return None;
+        }
+
+        let mut curl = vec!["curl".to_string()];
+        match output.as_deref() {
+            Some("-") => curl.push("-fL".to_string()),
+            Some(file) => curl.extend(["-fL".to_string(), "-o".to_string(), file.to_string()]),
+            None => curl.push("-fLO".to_string()),
+        }
+        curl.extend(extra);
+        curl.extend(urls);
+        Some(Script::from_tokens(curl))
+    }
+
+    /// Translates a curl script to wget. Unknown options give `None`.
+    fn curl_to_wget(script: &Script) -> Option<Script> {
+        let mut output: Option<String> = Some("-".to_string());
+        let mut extra: Vec<String> = Vec::new();
+        let mut quiet = false;
+        let mut urls: Vec<String> = Vec::new();
+
+        let mut tokens = script.tokens().iter().skip(1);
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "-o" | "--output" => output = Some(tokens.next()?.clone()),
+                "-O" | "--remote-name" => output = None,
+                "-H" | "--header" => extra.push(format!("--header={}", tokens.next()?)),
+                "-A" | "--user-agent" => extra.push(format!("--user-agent={}", tokens.next()?)),
+                "-L" | "--location" | "-f" | "--fail" | "-S" | "--show-error" => {}
+                "-s" | "--silent" => quiet = true,
+                "-C" => {
+                    tokens.next()?;
+                    extra.push("-c".to_string());
+                }
+                other if other.starts_with("--") => return None,
+                other if other.starts_with('-') => {
+                    // Clusters such as -fsSL or -sLO
+                    for flag in other[1..].chars() {
+                        match flag {
+                            's' => quiet = true,
+                            'O' => output = None,
+                            'f' | 'S' | 'L' => {}
+                            _ => return None,
+                        }
+                    }
+                }
+                url => urls.push(url.to_string()),
+            }
+        }
+        if urls.is_empty() {
+            return None;
+        }
+
+        let mut wget = vec!["wget".to_string()];
+        match output.as_deref() {
+            Some("-") => wget.push(if quiet { "-qO-" } else { "-O-" }.to_string()),
+            Some(file) => {
+                if quiet {
+                    wget.push("-q".to_string());
+                }
+                wget.extend(["-O".to_string(), file.to_string()]);
+            }
+            None if quiet => wget.push("-q".to_string()),
+            None => {}
+        }
+        wget.extend(extra);
+        wget.extend(urls);
+        Some(Script::from_tokens(wget))
+    }
+}
+
+impl Rule for CurlWgetTranslateRule {
+    fn name(&self) -> &str {
+        "curl_wget_translate"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use curl when wget isn't installed, and wget when curl isn't")
+            .example(Example::new(
+                "wget https://example.com/f.tgz",
+                "bash: wget: command not found",
+                127,
+                "curl -fLO https://example.com/f.tgz",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() == FailureKind::NotFound
+            && matches!(Script::parse(&command.script).executable(), Some("curl" | "wget"))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let translated = match script.executable() {
+            Some("wget") => Self::wget_to_curl(&script),
+            Some("curl") => Self::curl_to_wget(&script),
+            _ => None,
+        };
+        translated.map(|script| script.to_string()).into_iter().collect()
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+/// curl_method: drop a redundant `-X POST` next to `-d`, or retry with the
+/// methods a 405 response says are allowed
+struct CurlMethodRule;
+
+impl CurlMethodRule {
+    /// Gets the index of the `-X`/`--request` value, if any.
+    fn method_index(script: &Script) -> Option<usize> {
+        script
+            .tokens()
+            .iter()
+            .position(|token| token == "-X" || token == "--request")
+            .map(|index| index + 1)
+            .filter(|index| *index < script.tokens().len())
+    }
+
+    /// Gets the methods from an `Allow:` response header.
+    fn allowed_methods(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim().eq_ignore_ascii_case("allow").then_some(value)
+            })
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|method| SUGGESTED_METHODS.contains(method))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Rule for CurlMethodRule {
+    fn name(&self) -> &str {
+        "curl_method"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Drop a redundant `-X POST`, or retry with the methods a 405 response allows")
+            .example(Example::new(
+                "curl -X POST -d a=1 https://example.com",
+                "Note: Unnecessary use of -X or --request, POST is already inferred.",
+                0,
+                "curl -d a=1 https://example.com",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("curl")
+            && (command.output.contains("Unnecessary use of -X or --request, POST is already inferred")
+                || (command.output.contains("405") && !Self::allowed_methods(&command.output).is_empty()))
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let method_index = Self::method_index(&script);
+
+        if command.output.contains("POST is already inferred") {
+            let Some(index) = method_index else {
+                return vec![];
+            };
+            let mut tokens = script.tokens().to_vec();
+            tokens.drain(index - 1..=index);
+            return vec![Script::from_tokens(tokens).to_string()];
+        }
+
+        let sends_data = script
+            .tokens()
+            .iter()
+            .any(|token| CURL_DATA_OPTIONS.contains(&token.as_str()));
+        let current = match method_index {
+            Some(index) => script.tokens()[index].to_uppercase(),
+            None if sends_data => "POST".to_string(),
+            None => "GET".to_string(),
+        };
+
+        Self::allowed_methods(&command.output)
+            .into_iter()
+            .filter(|method| *method != current)
+            .map(|method| {
+                let mut tokens = script.tokens().to_vec();
+                match method_index {
+                    Some(index) => tokens[index] = method.to_string(),
+                    None => {
+                        tokens.splice(1..1, ["-X".to_string(), method.to_string()]);
+                    }
+                }
+                Script::from_tokens(tokens).to_string()
+            })
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        400
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const REDIRECT_BODY: &str = "<html>\r\n<head><title>301 Moved Permanently</title></head>\r\n<body>\r\n<center><h1>301 Moved Permanently</h1></center>\r\n<hr><center>nginx</center>\r\n</body>\r\n</html>\r\n";
+
+    const METHOD_NOT_ALLOWED: &str = "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, PUT, HEAD\r\nContent-Length: 0\r\n\r\n";
+
+    fn context_with(binary: &str) -> Arc<RuleContext> {
+        let shell = MockShell::new().with_output(&format!("{} --version", binary), "", 0);
+        Arc::new(RuleContext::new(Box::new(shell)))
+    }
+
+    #[test]
+    fn test_curl_url_missing_scheme_keeps_query_string() {
+        let rule = create_curl_url_scheme();
+        let cmd = Command::new(
+            "curl -H 'Accept: application/json' '//api.example.com/search?q=rust&page=2'",
+            "curl: (3) URL using bad/illegal format or missing URL\n",
+            3,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["curl -H 'Accept: application/json' 'https://api.example.com/search?q=rust&page=2'"]
+        );
+    }
+
+    #[test]
+    fn test_curl_url_scheme_typo() {
+        let rule = create_curl_url_scheme();
+        let cmd = Command::new(
+            "curl htps://example.com",
+            "curl: (1) Protocol \"htps\" not supported or disabled in libcurl\n",
+            1,
+        );
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["curl https://example.com"]);
+    }
+
+    #[test]
+    fn test_curl_follow_redirects() {
+        let rule = create_curl_follow_redirects();
+        let cmd = Command::new("curl http://example.com/docs", REDIRECT_BODY, 0);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["curl -L http://example.com/docs"]);
+
+        let cmd = Command::new("curl -sL http://example.com/docs", REDIRECT_BODY, 0);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_curl_insecure_is_last_and_dangerous() {
+        let rule = create_curl_insecure();
+        let cmd = Command::new(
+            "curl https://self-signed.local",
+            "curl: (60) SSL certificate problem: self-signed certificate\nMore details here: https://curl.se/docs/sslcerts.html\n",
+            60,
+        );
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_new_commands(&cmd);
+        assert_eq!(corrections, vec!["curl -k https://self-signed.local"]);
+        assert!(crate::Config::default().requires_confirmation(&corrections[0]));
+
+        for other in network_rules(Arc::new(RuleContext::default())) {
+            assert!(other.priority() <= rule.priority());
+        }
+    }
+
+    #[test]
+    fn test_wget_to_curl_when_wget_missing() {
+        let rule = requires_binary(Box::new(CurlWgetTranslateRule), context_with("curl"));
+        let cmd = Command::new(
+            "wget -O release.tgz 'https://example.com/dl?v=1.2&os=linux'",
+            "bash: wget: command not found",
+            127,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["curl -fL -o release.tgz 'https://example.com/dl?v=1.2&os=linux'"]
+        );
+
+        let cmd = Command::new("wget https://example.com/f.tgz", "bash: wget: command not found", 127);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["curl -fLO https://example.com/f.tgz"]);
+    }
+
+    #[test]
+    fn test_curl_to_wget_when_curl_missing() {
+        let rule = requires_binary(Box::new(CurlWgetTranslateRule), context_with("wget"));
+
+        let cmd = Command::new("curl -fsSL https://example.com/install.sh", "zsh: command not found: curl", 127);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["wget -qO- https://example.com/install.sh"]);
+
+        let cmd = Command::new("curl -o out.json -H 'X-Token: 1' https://example.com", "zsh: command not found: curl", 127);
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["wget -O out.json '--header=X-Token: 1' https://example.com"]
+        );
+    }
+
+    #[test]
+    fn test_translate_requires_other_binary() {
+        let context = Arc::new(RuleContext::new(Box::new(MockShell::new())));
+        let rule = requires_binary(Box::new(CurlWgetTranslateRule), context);
+        let cmd = Command::new("wget https://example.com/f.tgz", "bash: wget: command not found", 127);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_curl_method_redundant_post() {
+        let rule = CurlMethodRule;
+        let cmd = Command::new(
+            "curl -X POST -d 'a=1&b=2' https://example.com/form",
+            "Note: Unnecessary use of -X or --request, POST is already inferred.\n",
+            0,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["curl -d 'a=1&b=2' https://example.com/form"]
+        );
+    }
+
+    #[test]
+    fn test_curl_method_not_allowed() {
+        let rule = CurlMethodRule;
+        let cmd = Command::new(
+            "curl -i -d '{\"name\":\"x\"}' https://example.com/items/1",
+            METHOD_NOT_ALLOWED,
+            0,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "curl -X GET -i -d '{\"name\":\"x\"}' https://example.com/items/1",
+                "curl -X PUT -i -d '{\"name\":\"x\"}' https://example.com/items/1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_network_rules_exist() {
+        let rules = network_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "curl_url_scheme",
+                "curl_follow_redirects",
+                "curl_insecure",
+                "curl_wget_translate",
+                "curl_method",
+            ]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Pairs of rules that fire on the same failures.
+//!
+//! Two rules matching one command is fine while they agree, but when they
+//! suggest different fixes, such as `sudo` against `chmod +x` for "Permission
+//! denied", only priorities decide which one the user sees first. Every rule
+//! is run over a corpus of failures plus inputs synthesized from each rule's
+//! own triggers, and the pairs that fired together are counted.
+
+use crate::{Command, Rule};
+use std::collections::{BTreeMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Arguments appended to a trigger, so rules anchored on the end of a
+/// command are told apart from ones that only look at its start.
+const EXTRA_ARGS: &[&str] = &[" foo", " --help"];
+
+/// Exit codes a trigger's output is replayed with: a plain failure and a
+/// usage error, which tools mix up. Not 126 or 127, which alone are enough
+/// for some rules and would pair those with every other rule.
+const EXIT_CODES: &[i32] = &[1, 2];
+
+/// A failure two rules both corrected, differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disagreement {
+    /// The failure
+    pub command: Command,
+    /// The first rule's top correction
+    pub first_suggests: String,
+    /// The second rule's top correction
+    pub second_suggests: String,
+}
+
+/// Two rules that both corrected some of the same failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// The rule whose name sorts first
+    pub first: String,
+    /// The other rule
+    pub second: String,
+    /// How many failures both rules corrected
+    pub co_fired: usize,
+    /// How many of those they put different corrections first for
+    pub disagreed: usize,
+    /// The first failure they disagreed on
+    pub example: Option<Disagreement>,
+}
+
+impl Overlap {
+    /// Checks whether the rules ever disagreed, so that the order they
+    /// are tried in changes what is suggested.
+    pub fn is_conflict(&self) -> bool {
+        self.disagreed > 0
+    }
+}
+
+/// Gets inputs shaped to trigger `rules`: each rule's sample and example,
+/// with arguments appended and their output under other exit codes.
+///
+/// Inputs are in the order of the rules, without duplicates.
+pub fn synthesize_inputs(rules: &[Box<dyn Rule>]) -> Vec<Command> {
+    let mut seen = HashSet::new();
+    let mut inputs = Vec::new();
+    for rule in rules {
+        let example = rule.metadata().example;
+        let example = example.map(|example| Command::new(example.script, example.output, example.exit_code));
+        for trigger in rule.sample_matching_command().into_iter().chain(example) {
+            let scripts = EXTRA_ARGS.iter().map(|extra| format!("{}{}", trigger.script, extra));
+            let variants = std::iter::once(trigger.script.clone())
+                .chain(scripts)
+                .map(|script| Command::new(script, &trigger.output, trigger.exit_code))
+                .chain(EXIT_CODES.iter().map(|&code| Command::new(&trigger.script, &trigger.output, code)));
+            for variant in variants {
+                if seen.insert((variant.script.clone(), variant.output.clone(), variant.exit_code)) {
+                    inputs.push(variant);
+                }
+            }
+        }
+    }
+    inputs
+}
+
+/// Gets the top correction each of `rules` gives for `command`, by position,
+/// for the rules that match it and correct it. As in the corrector, a rule
+/// that panics is left out, and fallbacks only count when no other rule
+/// corrected the command.
+fn top_corrections(rules: &[Box<dyn Rule>], command: &Command) -> Vec<(usize, String)> {
+    let tops: Vec<(usize, String)> = rules
+        .iter()
+        .enumerate()
+        .filter_map(|(position, rule)| {
+            let top = panic::catch_unwind(AssertUnwindSafe(|| {
+                if rule.matches(command) {
+                    rule.get_corrected_commands(command).into_iter().next()
+                } else {
+                    None
+                }
+            }));
+            top.ok().flatten().map(|correction| (position, correction.script))
+        })
+        .collect();
+    let (primary, fallbacks): (Vec<_>, Vec<_>) =
+        tops.into_iter().partition(|(position, _)| !rules[*position].is_fallback());
+    if primary.is_empty() {
+        fallbacks
+    } else {
+        primary
+    }
+}
+
+/// Runs `rules` over `corpus` and the inputs synthesized from them, and
+/// gets every pair that corrected a failure together.
+///
+/// Overlaps come most disagreements first, then by name.
+pub fn find(rules: &[Box<dyn Rule>], corpus: &[Command]) -> Vec<Overlap> {
+    let mut overlaps: BTreeMap<(&str, &str), Overlap> = BTreeMap::new();
+    for command in corpus.iter().cloned().chain(synthesize_inputs(rules)) {
+        let tops = top_corrections(rules, &command);
+        for (i, (first, first_top)) in tops.iter().enumerate() {
+            for (second, second_top) in &tops[i + 1..] {
+                let (mut first, mut second, mut first_top, mut second_top) = (first, second, first_top, second_top);
+                if rules[*second].name() < rules[*first].name() {
+                    (first, second, first_top, second_top) = (second, first, second_top, first_top);
+                }
+                let (first_name, second_name) = (rules[*first].name(), rules[*second].name());
+                let overlap = overlaps.entry((first_name, second_name)).or_insert_with(|| Overlap {
+                    first: first_name.to_string(),
+                    second: second_name.to_string(),
+                    co_fired: 0,
+                    disagreed: 0,
+                    example: None,
+                });
+                overlap.co_fired += 1;
+                if first_top != second_top {
+                    overlap.disagreed += 1;
+                    overlap.example.get_or_insert_with(|| Disagreement {
+                        command: command.clone(),
+                        first_suggests: first_top.clone(),
+                        second_suggests: second_top.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut overlaps: Vec<Overlap> = overlaps.into_values().collect();
+    overlaps.sort_by(|a, b| {
+        let by_name = (&a.first, &a.second).cmp(&(&b.first, &b.second));
+        b.disagreed.cmp(&a.disagreed).then(by_name)
+    });
+    overlaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::SimpleRuleBuilder;
+
+    fn rules() -> Vec<Box<dyn Rule>> {
+        vec![
+            SimpleRuleBuilder::new("sudo").match_output("Permission denied").prepend("sudo"),
+            SimpleRuleBuilder::new("chmod").match_output("Permission denied").replace("./", "chmod +x ./"),
+            SimpleRuleBuilder::new("also_sudo").match_output("Permission denied").prepend("sudo"),
+            SimpleRuleBuilder::new("git").match_command("git pus").replace("pus", "push"),
+        ]
+    }
+
+    #[test]
+    fn test_synthesize_inputs() {
+        let inputs = synthesize_inputs(&rules()[3..]);
+        let scripts: Vec<&str> = inputs.iter().map(|input| input.script.as_str()).collect();
+        assert_eq!(scripts, vec!["git pus", "git pus foo", "git pus --help", "git pus"]);
+        let codes: Vec<i32> = inputs.iter().map(|input| input.exit_code).collect();
+        assert_eq!(codes, vec![1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_find_overlaps() {
+        let corpus = [Command::new("./run.sh", "bash: ./run.sh: Permission denied", 126)];
+        let overlaps = find(&rules(), &corpus);
+        let pairs: Vec<(&str, &str, bool)> = overlaps
+            .iter()
+            .map(|overlap| (overlap.first.as_str(), overlap.second.as_str(), overlap.is_conflict()))
+            .collect();
+        assert_eq!(pairs, vec![("also_sudo", "chmod", true), ("chmod", "sudo", true), ("also_sudo", "sudo", false)]);
+
+        let example = overlaps[1].example.as_ref().unwrap();
+        assert_eq!(example.command, corpus[0]);
+        assert_eq!(example.first_suggests, "chmod +x ./run.sh");
+        assert_eq!(example.second_suggests, "sudo ./run.sh");
+        assert!(overlaps[2].co_fired > 0);
+        assert_eq!(overlaps[2].example, None);
+    }
+
+    #[test]
+    fn test_panicking_rules_are_left_out() {
+        struct Panics;
+        impl Rule for Panics {
+            fn name(&self) -> &str {
+                "panics"
+            }
+            fn matches(&self, _command: &Command) -> bool {
+                panic!("broken rule")
+            }
+            fn get_new_commands(&self, _command: &Command) -> Vec<String> {
+                vec![]
+            }
+        }
+
+        let mut rules = rules();
+        rules.push(Box::new(Panics));
+        let corpus = [Command::new("ls", "Permission denied", 1)];
+        assert!(find(&rules, &corpus).iter().all(|overlap| overlap.first != "panics" && overlap.second != "panics"));
+    }
+}
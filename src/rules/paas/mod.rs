@@ -0,0 +1,264 @@
+//! heroku, flyctl and vercel command correction rules.
+//!
+//! This module contains rules for common deployment CLI mistakes:
+//! - Expired or missing logins, fixed by logging in and retrying
+//! - heroku commands run outside an app directory without `-a`
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::prefix_then_retry;
+use crate::fuzzy::similarity;
+use crate::shell::PROBE_TIMEOUT;
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// Maximum number of heroku apps suggested.
+const MAX_APP_SUGGESTIONS: usize = 3;
+
+/// Executables every PaaS rule requires.
+pub const EXECUTABLES: &[&str] = &["heroku", "fly", "flyctl", "vercel"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("heroku_login", 200),
+    ("heroku_missing_app", 300),
+    ("fly_login", 200),
+    ("vercel_login", 200),
+];
+
+/// Creates all deployment CLI rules.
+///
+/// The heroku app rule lists apps through the context's shell.
+pub fn paas_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // heroku_login: Log in again before retrying
+        create_heroku_login(),
+        // heroku_missing_app: Add -a with one of your apps
+        Box::new(HerokuMissingAppRule::new(context)),
+        // fly_login: Log in to fly.io before retrying
+        create_fly_login(),
+        // vercel_login: Run the login command vercel asks for
+        create_vercel_login(),
+    ]
+}
+
+/// heroku_login: `heroku ps` → `heroku login && heroku ps`
+fn create_heroku_login() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("heroku_login")
+        .match_command_static(static_regex!(r"^heroku\s"))
+        .match_output_static(static_regex!(r"Invalid credentials provided"))
+        .priority(200)
+        .describe("Log in to heroku again, then retry")
+        .example(Example::new("heroku ps", "Error: Invalid credentials provided.", 1, "heroku login && heroku ps"))
+        .replace_with(|original, _captures| vec![prefix_then_retry("heroku login", original)])
+        .build_builtin()
+}
+
+/// fly_login: `fly deploy` → `fly auth login && fly deploy`
+fn create_fly_login() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("fly_login")
+        .match_command_static(static_regex!(r"^(fly|flyctl)\s"))
+        .match_output_static(static_regex!(r"No access token available"))
+        .priority(200)
+        .describe("Log in to fly.io, then retry")
+        .example(Example::new(
+            "fly deploy",
+            "Error: No access token available. Please login with 'flyctl auth login'",
+            1,
+            "fly auth login && fly deploy",
+        ))
+        .replace_with(|original, _captures| {
+            // Keep whichever name the user invoked
+            let executable = Script::parse(original).executable().unwrap_or("fly").to_string();
+            let login = Script::from_tokens([executable.as_str(), "auth", "login"]);
+            vec![prefix_then_retry(&login.to_string(), original)]
+        })
+        .build_builtin()
+}
+
+/// vercel_login: `vercel deploy` → `vercel login && vercel deploy`, using the
+/// command from vercel's own message
+fn create_vercel_login() -> Box<dyn Rule> {
+    // The login command comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("vercel_login")
+        .match_output_static(static_regex!(r"Error: .*Please run `(vercel [^`]+)`"))
+        .priority(200)
+        .describe("Run the login command vercel asks for, then retry")
+        .example(Example::new(
+            "vercel deploy",
+            "Error: The specified token is not valid. Please run `vercel login`",
+            1,
+            "vercel login && vercel deploy",
+        ))
+        .replace_with(|original, captures| {
+            let Some(login) = captures.get(1).map(|m| m.as_str()) else {
+                return vec![];
+            };
+            if Script::parse(original).executable() != Some("vercel") {
+                return vec![];
+            }
+            vec![prefix_then_retry(login, original)]
+        })
+        .build_builtin()
+}
+
+/// heroku_missing_app: `heroku logs --tail` → `heroku logs --tail -a billing-api`
+pub struct HerokuMissingAppRule {
+    context: Arc<RuleContext>,
+}
+
+impl HerokuMissingAppRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Lists apps via `heroku apps`, most similar to the current directory's
+    /// name first.
+    fn ranked_apps(&self) -> Vec<String> {
+        let Some(shell) = self.context.shell() else {
+            return Vec::new();
+        };
+        let apps: Vec<String> = match shell.execute_with_timeout("heroku apps", PROBE_TIMEOUT) {
+            Ok(output) if output.success => output
+                .stdout
+                .lines()
+                .filter(|line| !line.starts_with("===") && !line.trim().is_empty())
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string)
+                .collect(),
+            _ => return Vec::new(),
+        };
+
+        let directory = shell
+            .cwd()
+            .ok()
+            .and_then(|cwd| cwd.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        let mut scored: Vec<(String, f64)> = apps
+            .into_iter()
+            .map(|app| {
+                let score = similarity(&app, &directory);
+                (app, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().map(|(app, _)| app).collect()
+    }
+}
+
+impl Rule for HerokuMissingAppRule {
+    fn name(&self) -> &str {
+        "heroku_missing_app"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Name one of your heroku apps with `-a` outside an app directory")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("heroku")
+            && command.output.contains("Missing required flag")
+            && command.output.contains("--app")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        self.ranked_apps()
+            .into_iter()
+            .take(MAX_APP_SUGGESTIONS)
+            .map(|app| script.clone().push_token("-a").push_token(app).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const HEROKU_INVALID_CREDENTIALS: &str = " \u{203a}   Error: Invalid credentials provided.\n";
+
+    const HEROKU_MISSING_APP: &str = " \u{203a}   Error: Missing required flag:\n \u{203a}     -a, --app APP  app to run command against\n \u{203a}   See more help with --help\n";
+
+    const HEROKU_APPS: &str = "=== dev@example.com Apps\nbilling-api\nbilling-web (eu)\nmarketing-site\n";
+
+    const FLY_NO_TOKEN: &str = "Error: No access token available. Please login with 'flyctl auth login'\n";
+
+    const VERCEL_NO_CREDENTIALS: &str = "Vercel CLI 33.0.1\nError: No existing credentials found. Please run `vercel login` or pass \"--token\"\nLearn More: https://err.sh/vercel/no-credentials-found\n";
+
+    #[test]
+    fn test_heroku_login() {
+        let rule = create_heroku_login();
+        let cmd = Command::new("heroku run 'rake db:migrate'", HEROKU_INVALID_CREDENTIALS, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["heroku login && heroku run 'rake db:migrate'"]
+        );
+    }
+
+    #[test]
+    fn test_heroku_missing_app_ranked_by_directory() {
+        let shell = MockShell::new()
+            .with_output("heroku apps", HEROKU_APPS, 0)
+            .with_cwd("/home/dev/src/billing-web");
+        let rule = HerokuMissingAppRule::new(Arc::new(RuleContext::new(Box::new(shell))));
+        let cmd = Command::new("heroku logs --tail", HEROKU_MISSING_APP, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "heroku logs --tail -a billing-web",
+                "heroku logs --tail -a billing-api",
+                "heroku logs --tail -a marketing-site",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_heroku_missing_app_without_shell() {
+        let rule = HerokuMissingAppRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new("heroku logs --tail", HEROKU_MISSING_APP, 2);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_fly_login_keeps_executable() {
+        let rule = create_fly_login();
+
+        let cmd = Command::new("fly deploy", FLY_NO_TOKEN, 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["fly auth login && fly deploy"]);
+
+        let cmd = Command::new("flyctl status", FLY_NO_TOKEN, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["flyctl auth login && flyctl status"]);
+    }
+
+    #[test]
+    fn test_vercel_login_lifted_from_output() {
+        let rule = create_vercel_login();
+        let cmd = Command::new("vercel deploy --prod", VERCEL_NO_CREDENTIALS, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["vercel login && vercel deploy --prod"]);
+    }
+
+    #[test]
+    fn test_paas_rules_exist() {
+        let rules = paas_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["heroku_login", "heroku_missing_app", "fly_login", "vercel_login"]
+        );
+    }
+}
@@ -1,15 +1,26 @@
-/// Package manager rules for apt, brew, npm, pip, etc.
-///
-/// This module contains rules for common package manager mistakes:
-/// - Missing flags (update, upgrade, search)
-/// - Wrong command order
-/// - Permission issues
-/// - Configuration problems
-
+//! Package manager rules for apt, brew, npm, pip, etc.
+//!
+//! This module contains rules for common package manager mistakes:
+//! - Missing flags (update, upgrade, search)
+//! - Wrong command order
+//! - Permission issues
+//! - Configuration problems
+
+use super::{Example, RuleInfo};
 use crate::{Rule, SimpleRuleBuilder};
 #[cfg(test)]
 use crate::Command;
 
+/// Executables every package manager rule requires.
+pub const EXECUTABLES: &[&str] = &["apt"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("apt_autoremove", 600),
+    ("apt_get_search", 500),
+    ("apt_install_builddeps", 700),
+];
+
 /// Creates all package manager rules.
 pub fn package_manager_rules() -> Vec<Box<dyn Rule>> {
     vec![
@@ -28,6 +39,13 @@ fn create_apt_autoremove() -> Box<dyn Rule> {
         .match_command("apt remove")
         .match_output("WARNING: The following")
         .priority(600)
+        .describe("Remove the packages apt says are no longer needed")
+        .example(Example::new(
+            "apt remove libfoo",
+            "WARNING: The following packages were automatically installed and are no longer required",
+            0,
+            "apt autoremove libfoo",
+        ))
         .replace("apt remove", "apt autoremove")
 }
 
@@ -37,6 +55,8 @@ fn create_apt_get_search() -> Box<dyn Rule> {
         .match_command("apt search")
         .match_output("E: Invalid operation")
         .priority(500)
+        .describe("Search with apt-cache where apt has no search")
+        .example(Example::new("apt search ripgrep", "E: Invalid operation search", 100, "apt-cache search ripgrep"))
         .replace("apt search", "apt-cache search")
 }
 
@@ -46,6 +66,13 @@ fn create_apt_install_builddeps() -> Box<dyn Rule> {
         .match_command("apt install")
         .match_output("error: you need to be root")
         .priority(700)
+        .describe("Install build-essential along with the package")
+        .example(Example::new(
+            "apt install libssl-dev",
+            "error: you need to be root",
+            1,
+            "apt install build-essential libssl-dev",
+        ))
         .replace("apt install", "apt install build-essential")
 }
 
@@ -62,6 +89,8 @@ mod tests {
             script: "apt remove package_name".to_string(),
             output: "WARNING: The following packages were automatically installed".to_string(),
             exit_code: 0,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
@@ -76,6 +105,8 @@ mod tests {
             script: "apt search keyword".to_string(),
             output: "E: Invalid operation search".to_string(),
             exit_code: 100,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
@@ -93,6 +124,8 @@ mod tests {
             script: "apt install some-package".to_string(),
             output: "error: you need to be root".to_string(),
             exit_code: 1,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         };
 
         assert!(rule.matches(&cmd));
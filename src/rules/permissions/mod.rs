@@ -1,17 +1,45 @@
-/// Permission-related command correction rules.
-///
-/// This module contains rules for common permission-related mistakes:
-/// - Missing sudo for privileged operations
-/// - File permissions (chmod)
-/// - Directory permissions
-/// - Ownership changes
+//! Permission-related command correction rules.
+//!
+//! This module contains rules for common permission-related mistakes:
+//! - Missing sudo for privileged operations
+//! - File permissions (chmod)
+//! - Directory permissions
+//! - Ownership changes: mistyped user names, checked against the local
+//!   accounts, and directories changed without `-R`
 
-use crate::{Rule, SimpleRuleBuilder};
-#[cfg(test)]
-use crate::Command;
+use super::util::{passwd_users, PASSWD_PATH};
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::privilege;
+use crate::{
+    static_regex, Command, CorrectedCommand, FailureKind, RegexRuleBuilder, Rule, RuleContext, Script,
+    SimpleRuleBuilder, Verdict,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Minimum similarity for a local account to replace a mistyped user.
+const MIN_USER_SIMILARITY: f64 = 0.5;
+
+/// Maximum number of user suggestions.
+const MAX_USER_SUGGESTIONS: usize = 3;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("sudo_permission_denied", 100),
+    ("sudo_apt", 200),
+    ("chmod_execute", 300),
+    ("chmod_recursive", 400),
+    ("chown_invalid_user", 300),
+    ("chown_recursive", 400),
+];
 
 /// Creates all permission-related rules.
-pub fn permission_rules() -> Vec<Box<dyn Rule>> {
+///
+/// The user name rule reads the local accounts from `/etc/passwd`, and the
+/// recursive rule checks paths against the context shell's working
+/// directory.
+pub fn permission_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
     vec![
         // sudo_commands: Add sudo when permission denied
         create_sudo_permission_denied(),
@@ -21,6 +49,10 @@ pub fn permission_rules() -> Vec<Box<dyn Rule>> {
         create_chmod_execute(),
         // chmod_recursive: Add recursive flag for directories
         create_chmod_recursive(),
+        // chown_invalid_user: Fix a mistyped user from the local accounts
+        Box::new(ChownInvalidUserRule::new(context)),
+        // chown_recursive: Add -R when chown lists files of a directory
+        Box::new(ChownRecursiveRule),
     ]
 }
 
@@ -29,7 +61,14 @@ fn create_sudo_permission_denied() -> Box<dyn Rule> {
     SimpleRuleBuilder::new("sudo_permission_denied")
         .match_output("Permission denied")
         .priority(100)
-        .replace("", "sudo ")
+        .describe("Run a command that was denied permission with sudo")
+        .example(Example::new(
+            "touch /etc/motd",
+            "touch: cannot touch '/etc/motd': Permission denied",
+            1,
+            "sudo touch /etc/motd",
+        ))
+        .prepend(privilege::DEFAULT_TOOL)
 }
 
 /// sudo_apt: Add sudo for apt/apt-get operations
@@ -38,16 +77,37 @@ fn create_sudo_apt() -> Box<dyn Rule> {
         .match_command("apt ")
         .match_output("E: Could not open lock file")
         .priority(200)
-        .replace("apt ", "sudo apt ")
+        .describe("Run apt with sudo when it can't take its lock")
+        .example(Example::new(
+            "apt update",
+            "E: Could not open lock file /var/lib/apt/lists/lock - open (13: Permission denied)",
+            100,
+            "sudo apt update",
+        ))
+        .prepend(privilege::DEFAULT_TOOL)
 }
 
 /// chmod_execute: Add execute permission when file is not executable
 fn create_chmod_execute() -> Box<dyn Rule> {
-    SimpleRuleBuilder::new("chmod_execute")
-        .match_output("Permission denied")
-        .match_command(".")
+    RegexRuleBuilder::new("chmod_execute")
+        .match_command_static(static_regex!(r"^\s*(\S+)"))
+        .match_failure_kind(FailureKind::NotExecutable)
         .priority(300)
-        .replace(".", "chmod +x ")
+        .describe("Make a script executable before running it")
+        .example(Example::new(
+            "./deploy.sh",
+            "bash: ./deploy.sh: Permission denied",
+            126,
+            "chmod +x ./deploy.sh && ./deploy.sh",
+        ))
+        .replace_with(|original, captures| {
+            let Some(executable) = captures.get(1) else {
+                return vec![];
+            };
+            let chmod = Script::from_tokens(["chmod", "+x", executable.as_str()]);
+            vec![chmod.append(" && ", Script::parse(original)).to_string()]
+        })
+        .build_builtin()
 }
 
 /// chmod_recursive: Add recursive flag for directory chmod
@@ -56,25 +116,210 @@ fn create_chmod_recursive() -> Box<dyn Rule> {
         .match_command("chmod ")
         .match_output("No such file or directory")
         .priority(400)
-        .replace("chmod ", "chmod -R ")
+        .describe("Change a directory's mode recursively")
+        .example(Example::new(
+            "chmod 755 public",
+            "chmod: cannot access 'public': No such file or directory",
+            1,
+            "chmod -R 755 public",
+        ))
+        .insert_flag("chmod", "-R")
+}
+
+/// Gets the positions of the operands of a chown script: the owner, then
+/// the paths. Options are skipped.
+fn chown_operands(script: &Script) -> Vec<usize> {
+    script
+        .tokens()
+        .iter()
+        .enumerate()
+        .skip_while(|(_, token)| *token != "chown")
+        .skip(1)
+        .filter(|(_, token)| !token.starts_with('-'))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// chown_invalid_user: `chown dpeloy: app.log` → `chown deploy: app.log`
+pub struct ChownInvalidUserRule {
+    context: Arc<RuleContext>,
+    passwd: PathBuf,
+}
+
+impl ChownInvalidUserRule {
+    /// Creates the rule, reading the accounts from `/etc/passwd`.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self {
+            context,
+            passwd: PathBuf::from(PASSWD_PATH),
+        }
+    }
+
+    /// Reads the accounts from `passwd` instead.
+    pub fn with_passwd(self, passwd: impl Into<PathBuf>) -> Self {
+        Self {
+            passwd: passwd.into(),
+            ..self
+        }
+    }
+
+    /// Gets the user chown couldn't find, from GNU's "invalid user:
+    /// 'dpeloy:'" or BSD's "dpeloy: illegal user name".
+    fn invalid_user(output: &str) -> Option<&str> {
+        let captures =
+            static_regex!(r"(?m)invalid user: ['‘`]?([^'’\s]+)|^chown: ([^:\s]+): illegal user name").captures(output)?;
+        let spec = captures.get(1).or_else(|| captures.get(2))?.as_str();
+        spec.split(':').next().filter(|user| !user.is_empty())
+    }
+}
+
+impl Rule for ChownInvalidUserRule {
+    fn name(&self) -> &str {
+        "chown_invalid_user"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self).describe("Fix a mistyped user from the local accounts in `/etc/passwd`")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).tokens().iter().any(|token| token == "chown")
+            && Self::invalid_user(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::invalid_user(&command.output) else {
+            return vec![];
+        };
+        let script = Script::parse(&command.script);
+        let Some(&index) = chown_operands(&script).first() else {
+            return vec![];
+        };
+        let Some(group) = script.tokens()[index].strip_prefix(typo) else {
+            return vec![];
+        };
+
+        let users = passwd_users(&self.context, &self.passwd);
+        let mut similar: Vec<(f64, &String)> = users
+            .iter()
+            .map(|user| (similarity(typo, user), user))
+            .filter(|(score, user)| *user != typo && *score >= MIN_USER_SIMILARITY)
+            .collect();
+        similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        similar
+            .into_iter()
+            .take(MAX_USER_SUGGESTIONS)
+            .map(|(_, user)| script.clone().replace_token_at(index, format!("{}{}", user, group)).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// chown_recursive: `chown deploy: public` → `chown -R deploy: public`
+pub struct ChownRecursiveRule;
+
+impl ChownRecursiveRule {
+    /// Gets the distinct paths chown reported errors for.
+    fn failed_paths(output: &str) -> Vec<&str> {
+        let mut paths: Vec<&str> = Vec::new();
+        for captures in static_regex!(r"(?m)^chown: [^'‘\n]*['‘]([^'’\n]+)['’]").captures_iter(output) {
+            let path = captures.get(1).map_or("", |path| path.as_str());
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// Returns true if the script already changes ownership recursively.
+    fn is_recursive(script: &Script) -> bool {
+        script.tokens().iter().any(|token| {
+            token == "--recursive" || (token.starts_with('-') && !token.starts_with("--") && token.contains('R'))
+        })
+    }
+}
+
+impl Rule for ChownRecursiveRule {
+    fn name(&self) -> &str {
+        "chown_recursive"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Change a directory's owner recursively when chown lists several of its files")
+            .example(Example::new(
+                "chown deploy: public",
+                "chown: changing ownership of 'public/index.html': Operation not permitted\n\
+                 chown: changing ownership of 'public/app.js': Operation not permitted",
+                1,
+                "chown -R deploy: public",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        let script = Script::parse(&command.script);
+        script.tokens().iter().any(|token| token == "chown")
+            && !Self::is_recursive(&script)
+            && Self::failed_paths(&command.output).len() > 1
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        vec![Script::parse(&command.script).insert_flag_after("chown", "-R").to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        400
+    }
+
+    fn verify(&self, correction: &CorrectedCommand, context: &RuleContext) -> Verdict {
+        let Some(cwd) = context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return Verdict::Keep;
+        };
+
+        // Recursion only helps if one of the paths is a directory
+        let script = Script::parse(&correction.script);
+        let operands = chown_operands(&script);
+        if operands.iter().skip(1).any(|&index| cwd.join(&script.tokens()[index]).is_dir()) {
+            Verdict::Keep
+        } else {
+            Verdict::Demote
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{MockShell, RuleTester};
+
+    fn passwd_fixture() -> &'static str {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/passwd/passwd")
+    }
+
+    fn chown_invalid_user() -> ChownInvalidUserRule {
+        ChownInvalidUserRule::new(Arc::new(RuleContext::default())).with_passwd(passwd_fixture())
+    }
 
     #[test]
     fn test_sudo_permission_denied_rule() {
         let rule = create_sudo_permission_denied();
         assert_eq!(rule.name(), "sudo_permission_denied");
 
-        let cmd = Command {
-            script: "apt update".to_string(),
-            output: "E: Could not open lock file /var/lib/apt/lists/lock - open (13: Permission denied)".to_string(),
-            exit_code: 100,
-        };
-
-        assert!(rule.matches(&cmd));
+        RuleTester::boxed(rule)
+            .given(
+                "apt update",
+                "E: Could not open lock file /var/lib/apt/lists/lock - open (13: Permission denied)",
+                100,
+            )
+            .expect_corrections(["sudo apt update"])
+            // Quoted arguments stay quoted
+            .given("touch \"/etc/my file\"", "touch: cannot touch '/etc/my file': Permission denied", 1)
+            .expect_corrections(["sudo touch \"/etc/my file\""])
+            .run();
     }
 
     #[test]
@@ -82,16 +327,10 @@ mod tests {
         let rule = create_sudo_apt();
         assert_eq!(rule.name(), "sudo_apt");
 
-        let cmd = Command {
-            script: "apt update".to_string(),
-            output: "E: Could not open lock file /var/lib/apt/lists/lock".to_string(),
-            exit_code: 100,
-        };
-
-        assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("sudo"));
+        RuleTester::boxed(rule)
+            .given("apt update", "E: Could not open lock file /var/lib/apt/lists/lock", 100)
+            .expect_correction_containing("sudo")
+            .run();
     }
 
     #[test]
@@ -99,13 +338,13 @@ mod tests {
         let rule = create_chmod_execute();
         assert_eq!(rule.name(), "chmod_execute");
 
-        let cmd = Command {
-            script: "./script.sh".to_string(),
-            output: "bash: ./script.sh: Permission denied".to_string(),
-            exit_code: 126,
-        };
-
-        assert!(rule.matches(&cmd));
+        RuleTester::boxed(rule)
+            .given("./script.sh", "bash: ./script.sh: Permission denied", 126)
+            .expect_corrections(["chmod +x ./script.sh && ./script.sh"])
+            // Only the script itself is made executable, not its arguments
+            .given("cat ./secret.txt", "cat: ./secret.txt: Permission denied", 1)
+            .expect_no_match()
+            .run();
     }
 
     #[test]
@@ -113,27 +352,81 @@ mod tests {
         let rule = create_chmod_recursive();
         assert_eq!(rule.name(), "chmod_recursive");
 
-        let cmd = Command {
-            script: "chmod 755 /path/to/dir".to_string(),
-            output: "chmod: cannot access '/path/to/dir': No such file or directory".to_string(),
-            exit_code: 1,
-        };
+        RuleTester::boxed(rule)
+            .given("chmod 755 /path/to/dir", "chmod: cannot access '/path/to/dir': No such file or directory", 1)
+            .expect_correction_containing("chmod -R 755 /path/to/dir")
+            .run();
+    }
+
+    #[test]
+    fn test_chown_invalid_user_gnu() {
+        let rule = chown_invalid_user();
+        let cmd = Command::new("chown -R dpeloy:www-data /srv/app", "chown: invalid user: ‘dpeloy:www-data’", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd)[0], "chown -R deploy:www-data /srv/app");
+    }
+
+    #[test]
+    fn test_chown_invalid_user_bsd() {
+        let rule = chown_invalid_user();
+        let cmd = Command::new("sudo chown postgers /var/db", "chown: postgers: illegal user name", 1);
 
         assert!(rule.matches(&cmd));
-        let corrections = rule.get_new_commands(&cmd);
-        assert!(!corrections.is_empty());
-        assert!(corrections[0].contains("-R"));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo chown postgres /var/db"]);
+    }
+
+    #[test]
+    fn test_chown_invalid_user_without_close_accounts() {
+        let rule = chown_invalid_user();
+        let cmd = Command::new("chown zzzzzz app.log", "chown: invalid user: 'zzzzzz'", 1);
+
+        assert!(rule.matches(&cmd));
+        assert!(rule.get_new_commands(&cmd).is_empty());
+        let unreadable = ChownInvalidUserRule::new(Arc::new(RuleContext::default())).with_passwd("/nonexistent");
+        let cmd = Command::new("chown dpeloy a", "chown: invalid user: 'dpeloy'", 1);
+        assert!(unreadable.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_chown_recursive_rule() {
+        let rule = ChownRecursiveRule;
+        let output = "chown: changing ownership of 'public/index.html': Operation not permitted\n\
+                      chown: changing ownership of 'public/app.js': Operation not permitted\n";
+        let cmd = Command::new("chown deploy: public", output, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["chown -R deploy: public"]);
+
+        // One file, or an already recursive chown, isn't corrected
+        let single = Command::new("chown deploy: a", "chown: cannot access 'a': No such file or directory", 1);
+        assert!(!rule.matches(&single));
+        assert!(!rule.matches(&Command::new("chown -hR deploy: public", output, 1)));
+    }
+
+    #[test]
+    fn test_chown_recursive_verify_needs_a_directory() {
+        let rule = ChownRecursiveRule;
+        let dir = std::env::temp_dir().join(format!("ftf_chown_recursive_verify_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("public")).unwrap();
+        let context = RuleContext::new(Box::new(MockShell::new().with_cwd(&dir)));
+
+        let directory = CorrectedCommand::new("chown -R deploy: public", 400);
+        assert_eq!(rule.verify(&directory, &context), Verdict::Keep);
+        let missing = CorrectedCommand::new("chown -R deploy: index.html", 400);
+        assert_eq!(rule.verify(&missing, &context), Verdict::Demote);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
     fn test_permission_rules_exist() {
-        let rules = permission_rules();
-        assert_eq!(rules.len(), 4);
+        let rules = permission_rules(Arc::new(RuleContext::default()));
+        assert_eq!(rules.len(), 6);
     }
 
     #[test]
     fn test_permission_rules_have_different_names() {
-        let rules = permission_rules();
+        let rules = permission_rules(Arc::new(RuleContext::default()));
         let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
 
         // Check that we have at least some unique names
@@ -141,5 +434,7 @@ mod tests {
         assert!(names.contains(&"sudo_apt"));
         assert!(names.contains(&"chmod_execute"));
         assert!(names.contains(&"chmod_recursive"));
+        assert!(names.contains(&"chown_invalid_user"));
+        assert!(names.contains(&"chown_recursive"));
     }
 }
@@ -0,0 +1,63 @@
+//! Adapter for changing a rule's priority without rebuilding it.
+
+use super::adapter::{Adapt, Adapted};
+use crate::Rule;
+
+/// Reports a fixed priority in place of the wrapped rule's.
+pub struct Priority(i32);
+
+impl Adapt for Priority {
+    fn priority(&self, _inner: &dyn Rule) -> i32 {
+        self.0
+    }
+}
+
+/// Wraps a rule and reports a different priority.
+///
+/// Used for `[rules.<name>] priority = ...` config overrides and for learned
+/// priority adjustments. Everything else is delegated to the wrapped rule.
+pub type PriorityOverride = Adapted<Priority>;
+
+impl PriorityOverride {
+    /// Wraps `rule` so it reports `priority`.
+    pub fn new(rule: Box<dyn Rule>, priority: i32) -> Self {
+        Self::wrap(rule, Priority(priority))
+    }
+
+    /// Gets the priority of the wrapped rule.
+    pub fn base_priority(&self) -> i32 {
+        self.inner().priority()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, SimpleRuleBuilder};
+
+    #[test]
+    fn test_priority_override_changes_priority_only() {
+        let rule = SimpleRuleBuilder::new("mkdir_p")
+            .match_output("No such file or directory")
+            .priority(100)
+            .replace("mkdir", "mkdir -p");
+        let rule = PriorityOverride::new(rule, 150);
+
+        assert_eq!(rule.name(), "mkdir_p");
+        assert_eq!(rule.priority(), 150);
+        assert_eq!(rule.base_priority(), 100);
+
+        let cmd = Command::new("mkdir a/b", "mkdir: a: No such file or directory", 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["mkdir -p a/b"]);
+    }
+
+    #[test]
+    fn test_priority_override_corrected_commands_use_new_priority() {
+        let rule = PriorityOverride::new(SimpleRuleBuilder::new("test").replace("a", "b"), 42);
+
+        let corrections = rule.get_corrected_commands(&Command::new("a", "", 1));
+        assert_eq!(corrections[0].priority, 42);
+        assert_eq!(corrections[0].rule.as_deref(), Some("test"));
+    }
+}
@@ -0,0 +1,503 @@
+//! Process management command correction rules.
+//!
+//! This module contains rules for common process management mistakes:
+//! - Servers that fail to start because their port is taken, fixed by
+//!   killing whatever listens on it before retrying
+//! - `kill` given a process name instead of a PID
+//! - `kill` denied permission to signal another user's process
+//! - Mistyped signals such as `kill -KILL9`
+//!
+//! The port comes from the error when the server prints it (node, Go,
+//! nginx, Puma, Flask, uvicorn), and otherwise from the command line.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::prefix_then_retry;
+use crate::fuzzy::similarity;
+use crate::privilege;
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, Script};
+use regex::Regex;
+
+/// Signal names and their numbers on Linux.
+const SIGNALS: &[(&str, u32)] = &[
+    ("HUP", 1), ("INT", 2), ("QUIT", 3), ("ILL", 4), ("TRAP", 5), ("ABRT", 6), ("BUS", 7), ("FPE", 8),
+    ("KILL", 9), ("USR1", 10), ("SEGV", 11), ("USR2", 12), ("PIPE", 13), ("ALRM", 14), ("TERM", 15),
+    ("CHLD", 17), ("CONT", 18), ("STOP", 19), ("TSTP", 20), ("TTIN", 21), ("TTOU", 22), ("WINCH", 28),
+];
+
+/// Minimum similarity for a signal name to replace a mistyped one.
+const MIN_SIGNAL_SIMILARITY: f64 = 0.6;
+
+/// kill options that take the signal as a separate value (`-s KILL`).
+const KILL_SIGNAL_OPTIONS: &[&str] = &["-s", "-n", "--signal"];
+
+/// Options that set a server's port (`--port 3000`, `-p 8080:80`).
+const PORT_OPTIONS: &[&str] = &["--port", "-p", "--http-port", "--bind", "-b", "--listen"];
+
+/// Subcommands that serve on a port given as an argument, and their
+/// default port.
+const SERVE_COMMANDS: &[(&str, u16)] = &[("http.server", 8000), ("runserver", 8000)];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("port_in_use", 300),
+    ("kill_process_name", 300),
+    ("kill_not_permitted", 200),
+    ("kill_signal", 300),
+];
+
+/// Creates all process management rules.
+pub fn process_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // port_in_use: Kill whatever listens on the port and retry
+        Box::new(PortInUseRule),
+        // kill_process_name: Use pkill for process names
+        Box::new(KillProcessNameRule),
+        // kill_not_permitted: Signal another user's process with sudo
+        create_kill_not_permitted(),
+        // kill_signal: Fix a mistyped signal
+        Box::new(KillSignalRule),
+    ]
+}
+
+/// Gets the operands of a kill or pkill script after its executable, as
+/// positions: the signal options and their values are skipped.
+fn kill_operands(script: &Script) -> Vec<usize> {
+    let mut operands = Vec::new();
+    let mut tokens = script.tokens().iter().enumerate().skip(1);
+    while let Some((index, token)) = tokens.next() {
+        if KILL_SIGNAL_OPTIONS.contains(&token.as_str()) {
+            tokens.next();
+        } else if !token.starts_with('-') {
+            operands.push(index);
+        }
+    }
+    operands
+}
+
+/// port_in_use: `npm start` → `lsof -ti:3000 | xargs kill && npm start`
+pub struct PortInUseRule;
+
+impl PortInUseRule {
+    /// Gets the port from the server's error.
+    fn port_in_output(output: &str) -> Option<u16> {
+        let patterns: [&Regex; 6] = [
+            // node: "listen EADDRINUSE: address already in use :::3000"
+            static_regex!(r"EADDRINUSE[^\n]*?:(\d{1,5})\b"),
+            // Go: "listen tcp :8080: bind: address already in use"
+            static_regex!(r"listen tcp[46]? \S*:(\d{1,5}): bind"),
+            // nginx: "bind() to 0.0.0.0:80 failed (98: Address already in use)"
+            static_regex!(r"bind\(\) to \S*:(\d{1,5}) failed"),
+            // Puma: "Address already in use - bind(2) for "0.0.0.0" port 3000"
+            static_regex!(r#"bind\(2\) for "[^"]*" port (\d{1,5})"#),
+            // Flask: "Port 5000 is in use by another program."
+            static_regex!(r"Port (\d{1,5}) is in use"),
+            // uvicorn and asyncio: "error while attempting to bind on address ('127.0.0.1', 8000)"
+            static_regex!(r"bind on address \([^)]*?(\d{1,5})\)"),
+        ];
+        patterns
+            .iter()
+            .find_map(|pattern| pattern.captures(output))
+            .and_then(|captures| captures[1].parse().ok())
+    }
+
+    /// Gets the port from the command line: a port option, a `PORT`
+    /// variable, or the argument of a serve command such as
+    /// `python -m http.server`.
+    fn port_in_script(script: &str) -> Option<u16> {
+        let script = Script::parse(script);
+        let tokens = script.tokens();
+        for (index, token) in tokens.iter().enumerate() {
+            if let Some(port) = token.strip_prefix("PORT=") {
+                return parse_port(port);
+            }
+            if let Some((option, value)) = token.split_once('=') {
+                if PORT_OPTIONS.contains(&option) {
+                    return parse_port(value);
+                }
+            }
+            if PORT_OPTIONS.contains(&token.as_str()) {
+                return tokens.get(index + 1).and_then(|value| parse_port(value));
+            }
+            if let Some(port) = token.strip_prefix("-p").filter(|port| port.starts_with(|c: char| c.is_ascii_digit())) {
+                return parse_port(port);
+            }
+            if let Some((_, default)) = SERVE_COMMANDS.iter().find(|(command, _)| command == token) {
+                return match tokens.get(index + 1) {
+                    Some(argument) if !argument.starts_with('-') => parse_port(argument),
+                    _ => Some(*default),
+                };
+            }
+        }
+        None
+    }
+
+    /// Gets the port a server failed to listen on.
+    fn port(command: &Command) -> Option<u16> {
+        let in_use = static_regex!(r"(?i)address already in use|EADDRINUSE|port \d+ is in use|port is already in use");
+        if !in_use.is_match(&command.output) {
+            return None;
+        }
+        Self::port_in_output(&command.output).or_else(|| Self::port_in_script(&command.script))
+    }
+}
+
+/// Parses a port from `3000`, `0.0.0.0:3000` or Docker's `3000:80`, where
+/// the host's port comes first.
+fn parse_port(value: &str) -> Option<u16> {
+    let parts: Vec<&str> = value.split(':').collect();
+    let port = if parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit())) {
+        parts.first()?
+    } else {
+        parts.last()?
+    };
+    port.parse().ok().filter(|port| *port > 0)
+}
+
+impl Rule for PortInUseRule {
+    fn name(&self) -> &str {
+        "port_in_use"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Kill the process listening on a server's port and start it again")
+            .example(Example::new(
+                "npm start",
+                "Error: listen EADDRINUSE: address already in use :::3000",
+                1,
+                "lsof -ti:3000 | xargs kill && npm start",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Self::port(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(port) = Self::port(command) else {
+            return vec![];
+        };
+        vec![prefix_then_retry(&format!("lsof -ti:{} | xargs kill", port), &command.script)]
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// kill_process_name: `kill -9 node` → `pkill -9 node`
+pub struct KillProcessNameRule;
+
+impl KillProcessNameRule {
+    /// Gets the positions of the process names given to kill, if all of its
+    /// operands are names rather than PIDs or jobs.
+    fn names(script: &Script) -> Option<Vec<usize>> {
+        if script.executable() != Some("kill") {
+            return None;
+        }
+        let operands = kill_operands(script);
+        let is_name = |index: &usize| {
+            let token = &script.tokens()[*index];
+            !token.starts_with('%') && !token.chars().all(|c| c.is_ascii_digit())
+        };
+        (!operands.is_empty() && operands.iter().all(is_name)).then_some(operands)
+    }
+}
+
+impl Rule for KillProcessNameRule {
+    fn name(&self) -> &str {
+        "kill_process_name"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Kill processes by name with `pkill`")
+            .example(Example::new(
+                "kill node",
+                "bash: kill: node: arguments must be process or job IDs",
+                1,
+                "pkill node",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        let rejected = static_regex!(
+            r"(?i)arguments must be process or job ids|illegal pid|illegal number|failed to parse argument|bad pid"
+        );
+        rejected.is_match(&command.output) && Self::names(&Script::parse(&command.script)).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let Some(names) = Self::names(&script) else {
+            return vec![];
+        };
+
+        // pkill takes a single pattern, and -s sets a session rather than a signal
+        let tokens = script.tokens();
+        let mut pkill = vec!["pkill".to_string()];
+        let mut options = tokens.iter().enumerate().skip(1).filter(|(index, _)| !names.contains(index));
+        while let Some((_, token)) = options.next() {
+            if KILL_SIGNAL_OPTIONS.contains(&token.as_str()) {
+                pkill.push("--signal".to_string());
+                pkill.extend(options.next().map(|(_, value)| value.clone()));
+            } else {
+                pkill.push(token.clone());
+            }
+        }
+        let pattern: Vec<&str> = names.iter().map(|&index| tokens[index].as_str()).collect();
+        pkill.push(pattern.join("|"));
+        vec![Script::from_tokens(pkill).to_string()]
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// kill_not_permitted: `kill 1234` → `sudo kill 1234`
+fn create_kill_not_permitted() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("kill_not_permitted")
+        .match_command_static(static_regex!(r"^(?:kill|pkill|killall)\s"))
+        .match_output_static(static_regex!(r"Operation not permitted"))
+        .priority(200)
+        .describe("Signal another user's process with sudo")
+        .example(Example::new(
+            "kill 1234",
+            "bash: kill: (1234) - Operation not permitted",
+            1,
+            "sudo kill 1234",
+        ))
+        .replace_with(|original, _captures| vec![privilege::escalate(Script::parse(original)).to_string()])
+        .build_builtin()
+}
+
+/// kill_signal: `kill -KILL9 1234` → `kill -9 1234`
+pub struct KillSignalRule;
+
+impl KillSignalRule {
+    /// Gets the position of the signal in a kill script, and whether it's
+    /// written as an option (`-KILL`) rather than the value of `-s`.
+    fn signal(script: &Script) -> Option<(usize, bool)> {
+        if script.executable() != Some("kill") {
+            return None;
+        }
+        let tokens = script.tokens();
+        let index = tokens.iter().skip(1).position(|token| token.starts_with('-') && token != "--")? + 1;
+        match tokens[index].as_str() {
+            "-l" | "-L" | "--list" => None,
+            option if KILL_SIGNAL_OPTIONS.contains(&option) => (index + 1 < tokens.len()).then_some((index + 1, false)),
+            _ => Some((index, true)),
+        }
+    }
+
+    /// Gets the signals `spec` was likely meant to be: the number it
+    /// contains, then the closest name.
+    fn normalize(spec: &str) -> Vec<String> {
+        let upper = spec.to_uppercase();
+        let spec = upper.strip_prefix("SIG").unwrap_or(&upper);
+        if let Some((name, _)) = SIGNALS.iter().find(|(name, _)| *name == spec) {
+            return vec![name.to_string()];
+        }
+
+        let digits: String = spec.chars().filter(char::is_ascii_digit).collect();
+        let letters: String = spec.chars().filter(char::is_ascii_alphabetic).collect();
+        let number = digits.parse::<u32>().ok().filter(|n| SIGNALS.iter().any(|(_, number)| number == n));
+        let name = SIGNALS
+            .iter()
+            .map(|(name, number)| (similarity(&letters, name), name, number))
+            .filter(|(score, _, _)| *score >= MIN_SIGNAL_SIMILARITY)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut signals: Vec<String> = number.iter().map(u32::to_string).collect();
+        if let Some((_, name, number)) = name {
+            if !signals.contains(&number.to_string()) {
+                signals.push(name.to_string());
+            }
+        }
+        signals
+    }
+}
+
+impl Rule for KillSignalRule {
+    fn name(&self) -> &str {
+        "kill_signal"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Fix a mistyped signal name or number")
+            .example(Example::new(
+                "kill -KILL9 1234",
+                "bash: kill: KILL9: invalid signal specification",
+                1,
+                "kill -9 1234",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        static_regex!(r"(?i)invalid signal|unknown signal|failed to parse argument").is_match(&command.output)
+            && Self::signal(&Script::parse(&command.script)).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let Some((index, is_option)) = Self::signal(&script) else {
+            return vec![];
+        };
+        let spec = &script.tokens()[index];
+        let prefix = if is_option { "-" } else { "" };
+
+        Self::normalize(spec.trim_start_matches('-'))
+            .into_iter()
+            .map(|signal| format!("{}{}", prefix, signal))
+            .filter(|signal| signal != spec)
+            .map(|signal| script.clone().replace_token_at(index, signal).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port_in_use(script: &str, output: &str) -> Vec<String> {
+        let cmd = Command::new(script, output, 1);
+        assert!(PortInUseRule.matches(&cmd), "{:?} should match", output);
+        PortInUseRule.get_new_commands(&cmd)
+    }
+
+    #[test]
+    fn test_port_in_use_node() {
+        let output = "node:events:497\n      throw er; // Unhandled 'error' event\n      ^\n\n\
+                      Error: listen EADDRINUSE: address already in use 127.0.0.1:4000\n    at Server.setupListenHandle";
+        assert_eq!(port_in_use("node server.js", output), vec!["lsof -ti:4000 | xargs kill && node server.js"]);
+        let output = "Error: listen EADDRINUSE :::3000\n    at Object.exports._errnoException (util.js:1018:11)";
+        assert_eq!(port_in_use("npm run dev", output), vec!["lsof -ti:3000 | xargs kill && npm run dev"]);
+    }
+
+    #[test]
+    fn test_port_in_use_python() {
+        // The plain OSError has no port, so it comes from the command line
+        let output = "Traceback (most recent call last):\n  ...\nOSError: [Errno 98] Address already in use";
+        assert_eq!(
+            port_in_use("python3 -m http.server 8080", output),
+            vec!["lsof -ti:8080 | xargs kill && python3 -m http.server 8080"]
+        );
+        assert_eq!(
+            port_in_use("python -m http.server", output),
+            vec!["lsof -ti:8000 | xargs kill && python -m http.server"]
+        );
+        let output = "ERROR:    [Errno 98] error while attempting to bind on address ('127.0.0.1', 8001): \
+                      address already in use";
+        assert_eq!(port_in_use("uvicorn app:app", output), vec!["lsof -ti:8001 | xargs kill && uvicorn app:app"]);
+        let output = "Address already in use\nPort 5000 is in use by another program.";
+        assert_eq!(port_in_use("flask run", output), vec!["lsof -ti:5000 | xargs kill && flask run"]);
+    }
+
+    #[test]
+    fn test_port_in_use_nginx() {
+        let output = "nginx: [emerg] bind() to 0.0.0.0:80 failed (98: Address already in use)\n\
+                      nginx: [emerg] bind() to [::]:80 failed (98: Address already in use)";
+        assert_eq!(
+            port_in_use("nginx -g 'daemon off;'", output),
+            vec!["lsof -ti:80 | xargs kill && nginx -g 'daemon off;'"]
+        );
+    }
+
+    #[test]
+    fn test_port_in_use_go_and_puma() {
+        let output = "2024/05/01 12:00:00 listen tcp :8080: bind: address already in use\nexit status 1";
+        assert_eq!(port_in_use("go run .", output), vec!["lsof -ti:8080 | xargs kill && go run ."]);
+        let output = "Address already in use - bind(2) for \"0.0.0.0\" port 3000 (Errno::EADDRINUSE)";
+        assert_eq!(port_in_use("bin/rails server", output), vec!["lsof -ti:3000 | xargs kill && bin/rails server"]);
+    }
+
+    #[test]
+    fn test_port_in_use_port_from_script() {
+        let output = "Error: That port is already in use.";
+        for (script, port) in [
+            ("python manage.py runserver 0.0.0.0:9000", 9000),
+            ("vite --port 5174", 5174),
+            ("hugo server --port=1314", 1314),
+            ("PORT=4001 npm start", 4001),
+            ("docker run -p 8081:80 nginx", 8081),
+        ] {
+            let cmd = Command::new(script, output, 1);
+            let expected = format!("lsof -ti:{} | xargs kill && {}", port, script);
+            assert_eq!(PortInUseRule.get_new_commands(&cmd), vec![expected], "{}", script);
+        }
+
+        // No port option or serve command to read it from
+        assert!(!PortInUseRule.matches(&Command::new("php -S localhost:8002", output, 1)));
+    }
+
+    #[test]
+    fn test_port_in_use_needs_the_error() {
+        assert!(!PortInUseRule.matches(&Command::new("vite --port 5174", "error: unknown option", 1)));
+    }
+
+    #[test]
+    fn test_kill_process_name() {
+        let cmd = Command::new("kill node", "bash: kill: node: arguments must be process or job IDs", 1);
+        assert!(KillProcessNameRule.matches(&cmd));
+        assert_eq!(KillProcessNameRule.get_new_commands(&cmd), vec!["pkill node"]);
+
+        let cmd = Command::new("kill -s TERM node python", "kill: illegal pid: node", 1);
+        assert_eq!(KillProcessNameRule.get_new_commands(&cmd), vec!["pkill --signal TERM 'node|python'"]);
+        let cmd = Command::new("kill -9 firefox", "kill: failed to parse argument: 'firefox'", 1);
+        assert_eq!(KillProcessNameRule.get_new_commands(&cmd), vec!["pkill -9 firefox"]);
+    }
+
+    #[test]
+    fn test_kill_process_name_ignores_pids_and_jobs() {
+        let output = "bash: kill: node: arguments must be process or job IDs";
+        assert!(!KillProcessNameRule.matches(&Command::new("kill 1234 node", output, 1)));
+        assert!(!KillProcessNameRule.matches(&Command::new("kill %1", output, 1)));
+    }
+
+    #[test]
+    fn test_kill_not_permitted() {
+        let rule = create_kill_not_permitted();
+        let cmd = Command::new("kill -9 1", "kill: (1): Operation not permitted", 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo kill -9 1"]);
+
+        let cmd = Command::new("pkill nginx", "pkill: killing pid 812 failed: Operation not permitted", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo pkill nginx"]);
+    }
+
+    #[test]
+    fn test_kill_signal() {
+        let output = "bash: kill: KILL9: invalid signal specification";
+        let cmd = Command::new("kill -KILL9 1234", output, 1);
+        assert!(KillSignalRule.matches(&cmd));
+        assert_eq!(KillSignalRule.get_new_commands(&cmd), vec!["kill -9 1234"]);
+
+        let cmd = Command::new("kill -SIGTREM 1234", "kill: unknown signal: SIGTREM", 1);
+        assert_eq!(KillSignalRule.get_new_commands(&cmd), vec!["kill -TERM 1234"]);
+        let cmd = Command::new("kill -s kill9 1234", "kill: unknown signal: kill9", 1);
+        assert_eq!(KillSignalRule.get_new_commands(&cmd), vec!["kill -s 9 1234"]);
+        let cmd = Command::new("kill -sigterm 1234", "kill: unknown signal: sigterm", 1);
+        assert_eq!(KillSignalRule.get_new_commands(&cmd), vec!["kill -TERM 1234"]);
+    }
+
+    #[test]
+    fn test_kill_signal_offers_number_and_name_when_they_differ() {
+        let cmd = Command::new("kill -TERM9 1234", "bash: kill: TERM9: invalid signal specification", 1);
+        assert_eq!(KillSignalRule.get_new_commands(&cmd), vec!["kill -9 1234", "kill -TERM 1234"]);
+        let cmd = Command::new("kill -l", "bash: kill: invalid signal specification", 1);
+        assert!(!KillSignalRule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_process_rules_exist() {
+        let rules = process_rules();
+        let names: Vec<_> = rules.iter().map(|rule| rule.name()).collect();
+        assert_eq!(names, RULES.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+    }
+}
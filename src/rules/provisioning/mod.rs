@@ -0,0 +1,330 @@
+//! Ansible and Vagrant command correction rules.
+//!
+//! This module contains rules for common provisioning mistakes:
+//! - Misspelled playbook names
+//! - Playbooks that become root without a sudo password to do it with
+//! - Running vagrant outside the directory of a Vagrantfile
+//! - Boxes that haven't been added yet
+//!
+//! Playbooks are looked for next to the one named, and Vagrantfiles below the
+//! working directory with the same bounded search the monorepo rules use.
+
+use super::monorepo::find_manifest_dirs;
+use super::util::prefix_then_retry;
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Minimum similarity for a playbook to be suggested for a missing one.
+const MIN_PLAYBOOK_SIMILARITY: f64 = 0.5;
+
+/// Maximum number of playbooks, or Vagrant environments, suggested.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Executables every provisioning rule requires.
+pub const EXECUTABLES: &[&str] = &["ansible", "ansible-playbook", "vagrant"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("ansible_playbook_not_found", 300),
+    ("ansible_become_pass", 200),
+    ("vagrant_no_environment", 300),
+    ("vagrant_box_missing", 200),
+];
+
+/// Creates all provisioning rules.
+///
+/// Playbooks and Vagrantfiles are looked up relative to the working
+/// directory of the context's shell.
+pub fn provisioning_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // ansible_playbook_not_found: Use the most similar playbook
+        Box::new(AnsiblePlaybookNotFoundRule::new(Arc::clone(&context))),
+        // ansible_become_pass: Ask for the sudo password
+        create_ansible_become_pass(),
+        // vagrant_no_environment: Change into the directory of a Vagrantfile
+        Box::new(VagrantNoEnvironmentRule::new(context)),
+        // vagrant_box_missing: Add the box first
+        create_vagrant_box_missing(),
+    ]
+}
+
+/// ansible_playbook_not_found: `ansible-playbook sit.yml` →
+/// `ansible-playbook site.yml`
+pub struct AnsiblePlaybookNotFoundRule {
+    context: Arc<RuleContext>,
+}
+
+impl AnsiblePlaybookNotFoundRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the playbook ansible couldn't find.
+    fn missing_playbook(command: &Command) -> Option<&str> {
+        let captures = static_regex!(r"the playbook: (.+?) could not be found").captures(&command.output)?;
+        captures.get(1).map(|playbook| playbook.as_str())
+    }
+
+    /// Gets the YAML files next to `playbook` most similar to it, most
+    /// similar first, with the same directory prefix.
+    fn similar_playbooks(&self, playbook: &str) -> Vec<String> {
+        let path = Path::new(playbook);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+            return vec![];
+        };
+
+        let entries = self.context.dir_entries(if dir.as_os_str().is_empty() { Path::new(".") } else { dir });
+        let mut similar: Vec<(f64, &String)> = entries
+            .iter()
+            .filter(|entry| entry.ends_with(".yml") || entry.ends_with(".yaml"))
+            .map(|entry| (similarity(&name, entry), entry))
+            .filter(|(score, entry)| *score >= MIN_PLAYBOOK_SIMILARITY && **entry != name)
+            .collect();
+        similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+        similar
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, entry)| dir.join(entry).to_string_lossy().into_owned())
+            .collect()
+    }
+}
+
+impl Rule for AnsiblePlaybookNotFoundRule {
+    fn name(&self) -> &str {
+        "ansible_playbook_not_found"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        // The suggestions depend on the playbooks in the working directory, so there's no example
+        RuleMetadata::of(self).describe("Run the playbook with the name most similar to one that doesn't exist")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("ansible-playbook")
+            && Self::missing_playbook(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(playbook) = Self::missing_playbook(command) else {
+            return vec![];
+        };
+        let script = Script::parse(&command.script);
+        let Some(index) = script.tokens().iter().position(|token| token == playbook) else {
+            return vec![];
+        };
+        self.similar_playbooks(playbook)
+            .into_iter()
+            .map(|similar| script.clone().replace_token_at(index, similar).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// ansible_become_pass: `ansible-playbook site.yml` → `ansible-playbook
+/// --ask-become-pass site.yml`
+fn create_ansible_become_pass() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("ansible_become_pass")
+        .match_command_static(static_regex!(r"^ansible(?:-playbook)?\b"))
+        .match_output_static(static_regex!(r"Missing sudo password"))
+        .priority(200)
+        .describe("Ask for the sudo password of tasks that become root")
+        .example(Example::new(
+            "ansible-playbook -i hosts site.yml",
+            "fatal: [web1]: FAILED! => {\"msg\": \"Missing sudo password\"}",
+            2,
+            "ansible-playbook --ask-become-pass -i hosts site.yml",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            if script.tokens().iter().any(|token| token == "-K" || token == "--ask-become-pass") {
+                return vec![];
+            }
+            let Some(executable) = script.executable().map(str::to_string) else {
+                return vec![];
+            };
+            vec![script.insert_flag_after(&executable, "--ask-become-pass").to_string()]
+        })
+        .build_builtin()
+}
+
+/// vagrant_no_environment: `vagrant up` → `cd infra/dev && vagrant up`, or
+/// `vagrant global-status`
+pub struct VagrantNoEnvironmentRule {
+    context: Arc<RuleContext>,
+}
+
+impl VagrantNoEnvironmentRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+}
+
+impl Rule for VagrantNoEnvironmentRule {
+    fn name(&self) -> &str {
+        "vagrant_no_environment"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Change into a directory with a Vagrantfile, or list the machines vagrant knows")
+            .example(Example::new(
+                "vagrant ssh",
+                "A Vagrant environment or target machine is required to run this\n\
+                 command. Run `vagrant init` to create a new Vagrant environment. Or,\n\
+                 get an ID of a target machine from `vagrant global-status` to run\n\
+                 this command on.",
+                1,
+                "vagrant global-status",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("vagrant")
+            && command.output.contains("A Vagrant environment or target machine is required")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let mut corrections: Vec<String> = Vec::new();
+        // vagrant looks for a Vagrantfile upwards, so one it missed can only be below
+        if let Some(cwd) = self.context.shell().and_then(|shell| shell.cwd().ok()) {
+            let dirs = find_manifest_dirs(&cwd, "Vagrantfile").into_iter().take(MAX_SUGGESTIONS);
+            corrections.extend(dirs.map(|dir| {
+                let cd = Script::from_tokens(["cd".to_string(), dir.to_string_lossy().into_owned()]);
+                prefix_then_retry(&cd.to_string(), &command.script)
+            }));
+        }
+        corrections.push("vagrant global-status".to_string());
+        corrections
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// vagrant_box_missing: `vagrant up` → `vagrant box add ubuntu/jammy64 &&
+/// vagrant up`
+fn create_vagrant_box_missing() -> Box<dyn Rule> {
+    // The box comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("vagrant_box_missing")
+        .match_output_static(static_regex!(r"[Bb]ox '([^']+)' could not be found"))
+        .priority(200)
+        .describe("Add a missing box with vagrant box add, then retry")
+        .example(Example::new(
+            "vagrant up",
+            "The box 'ubuntu/jammy64' could not be found or\n\
+             could not be accessed in the remote catalog.",
+            1,
+            "vagrant box add ubuntu/jammy64 && vagrant up",
+        ))
+        .replace_with(|original, captures| {
+            let Some(name) = captures.get(1).map(|name| name.as_str()) else {
+                return vec![];
+            };
+            let script = Script::parse(original);
+            if script.executable() != Some("vagrant") || script.tokens().get(1).is_some_and(|token| token == "box") {
+                return vec![];
+            }
+            let add = Script::from_tokens(["vagrant", "box", "add", name]);
+            vec![prefix_then_retry(&add.to_string(), original)]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fake_context;
+
+    const NO_ENVIRONMENT: &str = "A Vagrant environment or target machine is required to run this\n\
+                                  command. Run `vagrant init` to create a new Vagrant environment.";
+
+    #[test]
+    fn test_ansible_playbook_not_found() {
+        let fake = fake_context().dir_entries(["site.yml", "site.yaml.bak", "deploy.yaml", "README.md"]).build();
+        let rule = AnsiblePlaybookNotFoundRule::new(fake.context());
+
+        let output = "ERROR! the playbook: sit.yml could not be found";
+        let cmd = Command::new("ansible-playbook -i hosts sit.yml", output, 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ansible-playbook -i hosts site.yml"]);
+
+        let cmd = Command::new("ansible-playbook delpoy.yml", "ERROR! the playbook: delpoy.yml could not be found", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ansible-playbook deploy.yaml"]);
+    }
+
+    #[test]
+    fn test_ansible_playbook_not_found_in_a_directory() {
+        let fake = fake_context().dir_entries(["playbooks/webservers.yml", "playbooks/databases.yml"]).build();
+        let rule = AnsiblePlaybookNotFoundRule::new(fake.context());
+        let output = "ERROR! the playbook: playbooks/webserver.yml could not be found";
+
+        let cmd = Command::new("ansible-playbook playbooks/webserver.yml", output, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ansible-playbook playbooks/webservers.yml"]);
+        let cmd = Command::new("ansible-playbook playbooks/zzz.yml", output.replace("webserver", "zzz"), 1);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_ansible_become_pass() {
+        let rule = create_ansible_become_pass();
+        let output = "fatal: [db1]: FAILED! => {\"changed\": false, \"msg\": \"Missing sudo password\"}";
+
+        let cmd = Command::new("ansible all -b -m ping", output, 2);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ansible --ask-become-pass all -b -m ping"]);
+        assert!(rule.get_new_commands(&Command::new("ansible-playbook -K site.yml", output, 2)).is_empty());
+    }
+
+    #[test]
+    fn test_vagrant_no_environment() {
+        let fake = fake_context()
+            .dir_entries(["infra/dev/Vagrantfile", "infra/test/Vagrantfile", "docs/README.md"])
+            .build();
+        let rule = VagrantNoEnvironmentRule::new(fake.context());
+        let cmd = Command::new("vagrant up --provision", NO_ENVIRONMENT, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec![
+                "cd infra/dev && vagrant up --provision",
+                "cd infra/test && vagrant up --provision",
+                "vagrant global-status"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vagrant_no_environment_without_vagrantfiles() {
+        let fake = fake_context().dir_entries(["notes.txt"]).build();
+        let rule = VagrantNoEnvironmentRule::new(fake.context());
+
+        let cmd = Command::new("vagrant ssh", NO_ENVIRONMENT, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["vagrant global-status"]);
+        assert!(!rule.matches(&Command::new("vagrant ssh", "The provider 'virtualbox' is not usable", 1)));
+    }
+
+    #[test]
+    fn test_vagrant_box_missing() {
+        let rule = create_vagrant_box_missing();
+        let output = "Bringing machine 'default' up with 'virtualbox' provider...\n\
+                      ==> default: Box 'generic/alpine318' could not be found. Attempting to find and install...\n\
+                      The box 'generic/alpine318' could not be found or\n\
+                      could not be accessed in the remote catalog.";
+
+        let cmd = Command::new("vagrant up", output, 1);
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["vagrant box add generic/alpine318 && vagrant up"]);
+        assert!(rule.get_new_commands(&Command::new("vagrant box add generic/alpine318", output, 1)).is_empty());
+    }
+}
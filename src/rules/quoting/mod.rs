@@ -0,0 +1,419 @@
+//! Shell quoting mistake rules.
+//!
+//! This module contains rules for mistakes the shell makes of a command
+//! before running it:
+//! - A quote left open, which bash reports as an unexpected end of file
+//! - `!` in a double-quoted or bare word, taken for a history expansion
+//!   ("event not found")
+//! - Backtick command substitutions inside double quotes, whose own quotes
+//!   end the outer string
+//!
+//! These look at the script rather than the output: the shell often fails
+//! before printing anything a hook records. A `!` alone doesn't say why a
+//! command failed, though, so history expansions need bash's error.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::{Command, Rule, Script};
+use std::ops::Range;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("unterminated_quote", 300),
+    ("history_expansion", 300),
+    ("backtick_substitution", 400),
+];
+
+/// Creates all shell quoting rules.
+pub fn quoting_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // unterminated_quote: Close the quote left open, or escape an apostrophe
+        Box::new(UnterminatedQuoteRule),
+        // history_expansion: Escape or single-quote a ! taken for history
+        Box::new(HistoryExpansionRule),
+        // backtick_substitution: Use $(...) for backticks in double quotes
+        Box::new(BacktickSubstitutionRule),
+    ]
+}
+
+/// Returns true if the character at `position` is between two letters or
+/// digits, like the apostrophe of `it's`.
+fn is_in_word(script: &str, position: usize) -> bool {
+    script[..position].chars().next_back().is_some_and(char::is_alphanumeric)
+        && script[position + 1..].chars().next().is_some_and(char::is_alphanumeric)
+}
+
+/// unterminated_quote: `git commit -m "fix login` → `git commit -m "fix login"`
+pub struct UnterminatedQuoteRule;
+
+impl Rule for UnterminatedQuoteRule {
+    fn name(&self) -> &str {
+        "unterminated_quote"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Close a quote the command leaves open")
+            .example(Example::new(
+                "git commit -m \"fix login",
+                "bash: -c: line 1: unexpected EOF while looking for matching `\"'",
+                2,
+                "git commit -m \"fix login\"",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::unterminated_quote(&command.script).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = command.script.trim_end();
+        let Some(open) = Script::unterminated_quote(script) else {
+            return vec![];
+        };
+
+        let closed = format!("{}{}", script, open.quote);
+        // Apostrophes inside words (it's) were most likely meant literally
+        if open.quote == '\'' && is_in_word(script, open.position) {
+            let mut escaped = String::with_capacity(script.len() + 4);
+            for (i, c) in script.char_indices() {
+                if c == '\'' && is_in_word(script, i) && !script[..i].ends_with('\\') {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            if Script::unterminated_quote(&escaped).is_none() {
+                return vec![escaped, closed];
+            }
+        }
+        vec![closed]
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+}
+
+/// Gets the byte offsets of the `!`s in `script` that start a history
+/// expansion, with whether each is inside double quotes.
+///
+/// A `!` followed by a blank, `=` or `(`, or ending the script, doesn't,
+/// nor does one in single quotes, escaped, or in `$!` or a `[!...]` glob.
+fn history_bangs(script: &str) -> Vec<(usize, bool)> {
+    let mut bangs = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut previous: Option<char> = None;
+    let mut chars = script.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if escaped {
+            escaped = false;
+        } else if quote == Some('\'') {
+            if c == '\'' {
+                quote = None;
+            }
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' || c == '\'' {
+            quote = if quote == Some(c) { None } else { quote.or(Some(c)) };
+        } else if c == '!' && !matches!(previous, Some('$' | '[')) {
+            let expands = chars.peek().is_some_and(|(_, next)| !next.is_whitespace() && !"=(".contains(*next));
+            if expands {
+                bangs.push((i, quote == Some('"')));
+            }
+        }
+        previous = Some(c);
+    }
+    bangs
+}
+
+/// history_expansion: `echo "Hi!"` → `echo "Hi"\!` or `echo 'Hi!'`
+pub struct HistoryExpansionRule;
+
+impl HistoryExpansionRule {
+    /// Escapes every `!` that starts a history expansion. One in double
+    /// quotes is moved out of them, as a backslash before it would be kept.
+    fn escape(script: &str, bangs: &[(usize, bool)]) -> String {
+        let mut escaped = String::with_capacity(script.len() + bangs.len() * 3);
+        let mut rest = 0;
+        for &(position, in_double_quotes) in bangs {
+            escaped.push_str(&script[rest..position]);
+            rest = position + 1;
+            if !in_double_quotes {
+                escaped.push_str("\\!");
+            } else if script[rest..].starts_with('"') {
+                // "Hi!" → "Hi"\!
+                escaped.push_str("\"\\!");
+                rest += 1;
+            } else if script[..position].ends_with('"') && !script[..position].ends_with("\\\"") {
+                // The quote before it opens the string: "!x" → \!"x"
+                escaped.pop();
+                escaped.push_str("\\!\"");
+            } else {
+                escaped.push_str("\"\\!\"");
+            }
+        }
+        escaped.push_str(&script[rest..]);
+        escaped
+    }
+
+    /// Single-quotes the words holding a `!` that starts a history
+    /// expansion, if none of them expands anything else or holds a single
+    /// quote.
+    fn single_quote(script: &str) -> Option<String> {
+        let parsed = Script::parse(script);
+        let mut words = Vec::with_capacity(parsed.tokens().len());
+        let mut changed = false;
+        for (index, token) in parsed.tokens().iter().enumerate() {
+            let raw = parsed.raw_token(index)?;
+            if history_bangs(raw).is_empty() {
+                words.push(raw.to_string());
+                continue;
+            }
+            if raw.contains(['\'', '$', '`']) {
+                return None;
+            }
+            words.push(format!("'{}'", token));
+            changed = true;
+        }
+        changed.then(|| words.join(" "))
+    }
+}
+
+impl Rule for HistoryExpansionRule {
+    fn name(&self) -> &str {
+        "history_expansion"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Keep the shell from taking a `!` for a history expansion")
+            .example(Example::new("echo \"Hi!\"", "bash: !\": event not found", 1, "echo \"Hi\"\\!"))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        // A command that merely contains a `!` may have failed for any reason
+        command.output.contains("event not found") && !history_bangs(&command.script).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let bangs = history_bangs(&command.script);
+        if bangs.is_empty() {
+            return vec![];
+        }
+        let mut corrections = vec![Self::escape(&command.script, &bangs)];
+        corrections.extend(Self::single_quote(&command.script));
+        corrections
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// Gets the backtick command substitutions in `script`, quotes included,
+/// with whether each is inside double quotes.
+fn backtick_substitutions(script: &str) -> Vec<(Range<usize>, bool)> {
+    let mut substitutions = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut opened: Option<(usize, bool)> = None;
+
+    for (i, c) in script.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote != Some('\'') {
+            escaped = true;
+        } else if let Some((start, in_double_quotes)) = opened {
+            // Quotes inside the substitution belong to its command
+            if c == '`' {
+                substitutions.push((start..i + 1, in_double_quotes));
+                opened = None;
+            }
+        } else if quote == Some('\'') {
+            if c == '\'' {
+                quote = None;
+            }
+        } else if c == '`' {
+            opened = Some((i, quote == Some('"')));
+        } else if c == '"' || c == '\'' {
+            quote = if quote == Some(c) { None } else { Some(c) };
+        }
+    }
+    substitutions
+}
+
+/// backtick_substitution: ``echo "Size: `du -sh "my dir"`"`` →
+/// `echo "Size: $(du -sh "my dir")"`
+pub struct BacktickSubstitutionRule;
+
+impl BacktickSubstitutionRule {
+    /// Rewrites every backtick substitution as `$(...)`, dropping the
+    /// backslashes only backticks need before `$`, `` ` `` and `\`.
+    fn rewrite(script: &str, substitutions: &[(Range<usize>, bool)]) -> String {
+        let mut rewritten = String::with_capacity(script.len() + substitutions.len() * 2);
+        let mut rest = 0;
+        for (range, _) in substitutions {
+            rewritten.push_str(&script[rest..range.start]);
+            let inner = &script[range.start + 1..range.end - 1];
+            rewritten.push_str("$(");
+            let mut chars = inner.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c == '\\' && chars.peek().is_some_and(|next| "$`\\".contains(*next)) {
+                    continue;
+                }
+                rewritten.push(c);
+            }
+            rewritten.push(')');
+            rest = range.end;
+        }
+        rewritten.push_str(&script[rest..]);
+        rewritten
+    }
+}
+
+impl Rule for BacktickSubstitutionRule {
+    fn name(&self) -> &str {
+        "backtick_substitution"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Write backtick command substitutions inside double quotes as `$(...)`")
+            .example(Example::new(
+                "echo \"Size: `du -sh \"my dir\"`\"",
+                "du: cannot access 'my': No such file or directory\ndu: cannot access 'dir': No such file or directory",
+                1,
+                "echo \"Size: $(du -sh \"my dir\")\"",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        backtick_substitutions(&command.script).iter().any(|(_, in_double_quotes)| *in_double_quotes)
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let substitutions = backtick_substitutions(&command.script);
+        if !substitutions.iter().any(|(_, in_double_quotes)| *in_double_quotes) {
+            return vec![];
+        }
+        vec![Self::rewrite(&command.script, &substitutions)]
+    }
+
+    fn priority(&self) -> i32 {
+        400
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corrections(rule: &dyn Rule, script: &str, output: &str) -> Vec<String> {
+        let cmd = Command::new(script, output, 1);
+        assert!(rule.matches(&cmd), "{} should match", script);
+        rule.get_new_commands(&cmd)
+    }
+
+    #[test]
+    fn test_unterminated_quote_is_closed() {
+        let output = "bash: -c: line 1: unexpected EOF while looking for matching `\"'";
+        assert_eq!(corrections(&UnterminatedQuoteRule, "git commit -m \"fix login ", output), vec![
+            "git commit -m \"fix login\""
+        ]);
+        assert_eq!(corrections(&UnterminatedQuoteRule, "echo `date", ""), vec!["echo `date`"]);
+        // No output is needed
+        assert!(!UnterminatedQuoteRule.requires_output());
+    }
+
+    #[test]
+    fn test_unterminated_apostrophe_in_a_word_is_escaped() {
+        assert_eq!(corrections(&UnterminatedQuoteRule, "echo it's done", "zsh: unmatched '"), vec![
+            "echo it\\'s done",
+            "echo it's done'"
+        ]);
+        assert_eq!(corrections(&UnterminatedQuoteRule, "echo don't won't can't", ""), vec![
+            "echo don\\'t won\\'t can\\'t",
+            "echo don't won't can't'"
+        ]);
+    }
+
+    #[test]
+    fn test_balanced_quotes_are_not_corrected() {
+        for script in ["echo \"a 'b' c\"", "echo it\\'s", "ls # it's", "echo $'it\\'s'"] {
+            assert!(!UnterminatedQuoteRule.matches(&Command::new(script, "", 1)), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_history_bangs() {
+        assert_eq!(history_bangs("echo \"Hi!\""), vec![(8, true)]);
+        assert_eq!(history_bangs("echo hi!there"), vec![(7, false)]);
+        for script in ["echo 'Hi!'", "echo hi!", "[ a != b ]", "! grep x f", "echo \\!x", "kill $!", "ls [!a]*"] {
+            assert!(history_bangs(script).is_empty(), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_history_expansion_in_double_quotes() {
+        let output = "bash: !\": event not found";
+        assert_eq!(corrections(&HistoryExpansionRule, "echo \"Hi!\"", output), vec!["echo \"Hi\"\\!", "echo 'Hi!'"]);
+        assert_eq!(
+            corrections(&HistoryExpansionRule, "git commit -m \"fix!important\"", "bash: !important: event not found"),
+            vec!["git commit -m \"fix\"\\!\"important\"", "git commit -m 'fix!important'"]
+        );
+        assert_eq!(
+            corrections(&HistoryExpansionRule, "echo \"!x\"", "bash: !x: event not found"),
+            vec!["echo \\!\"x\"", "echo '!x'"]
+        );
+    }
+
+    #[test]
+    fn test_history_expansion_unquoted_and_with_variables() {
+        assert_eq!(
+            corrections(&HistoryExpansionRule, "echo wow!such", "bash: !such: event not found"),
+            vec!["echo wow\\!such", "echo 'wow!such'"]
+        );
+        // Single quotes would stop $USER from expanding
+        assert_eq!(
+            corrections(&HistoryExpansionRule, "echo \"Hi $USER!\"", "bash: !\": event not found"),
+            vec!["echo \"Hi $USER\"\\!"]
+        );
+        assert!(!HistoryExpansionRule.matches(&Command::new("echo \"Hi!\"", "Hi!", 1)));
+        // Without the shell's error, the `!` needn't be why it failed
+        assert!(!HistoryExpansionRule.matches(&Command::new("git commit -m \"fix!\"", "", 1)));
+    }
+
+    #[test]
+    fn test_backtick_substitution() {
+        assert_eq!(
+            corrections(&BacktickSubstitutionRule, "echo \"Size: `du -sh \"my dir\"`\"", ""),
+            vec!["echo \"Size: $(du -sh \"my dir\")\""]
+        );
+        assert_eq!(
+            corrections(&BacktickSubstitutionRule, "echo \"`echo \\$HOME` and `pwd`\" `date`", ""),
+            vec!["echo \"$(echo $HOME) and $(pwd)\" $(date)"]
+        );
+    }
+
+    #[test]
+    fn test_backtick_substitution_needs_double_quotes() {
+        for script in ["echo `date`", "echo '`date`'", "echo \"a \\`b\\` c\""] {
+            assert!(!BacktickSubstitutionRule.matches(&Command::new(script, "", 1)), "{}", script);
+        }
+    }
+
+    #[test]
+    fn test_quoting_rules_exist() {
+        let names: Vec<_> = quoting_rules().iter().map(|rule| rule.name().to_string()).collect();
+        assert_eq!(names, RULES.iter().map(|(name, _)| name.to_string()).collect::<Vec<_>>());
+    }
+}
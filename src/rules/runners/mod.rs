@@ -0,0 +1,396 @@
+//! make, just and task runner rules.
+//!
+//! This module contains rules for common build runner mistakes:
+//! - Mistyped make targets, matched against the targets in the Makefile
+//! - Mistyped just recipes, matched against `just --summary`
+//! - Running make in a project that uses just or task instead
+
+use super::{RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::shell::PROBE_TIMEOUT;
+use crate::{Command, FailureKind, Rule, RuleContext, Script};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Minimum similarity for a target or recipe to be suggested.
+const MIN_TARGET_SIMILARITY: f64 = 0.5;
+
+/// Maximum number of targets or recipes suggested.
+const MAX_TARGET_SUGGESTIONS: usize = 3;
+
+/// File names GNU make reads, in its lookup order.
+const MAKEFILES: &[&str] = &["GNUmakefile", "makefile", "Makefile"];
+
+/// Other runners, and the file names that mark a project using them.
+const RUNNER_FILES: &[(&str, &[&str])] = &[
+    ("just", &["justfile", "Justfile", ".justfile"]),
+    ("task", &["Taskfile.yml", "Taskfile.yaml", "taskfile.yml", "taskfile.yaml"]),
+];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("make_no_rule", 300),
+    ("just_unknown_recipe", 300),
+    ("make_other_runner", 200),
+];
+
+/// Creates all build runner rules.
+///
+/// Makefiles are read from the working directory of the context's shell, and
+/// just recipes are listed through it.
+pub fn runner_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // make_no_rule: Use the closest target from the Makefile
+        Box::new(MakeNoRuleRule::new(Arc::clone(&context))),
+        // just_unknown_recipe: Use the closest recipe from `just --summary`
+        Box::new(JustUnknownRecipeRule::new(Arc::clone(&context))),
+        // make_other_runner: Use just or task when the project has no Makefile
+        Box::new(MakeOtherRunnerRule::new(context)),
+    ]
+}
+
+/// Parses the explicit targets defined in a Makefile, in order.
+///
+/// Special targets (`.PHONY`, `.DEFAULT_GOAL`, ...), pattern rules,
+/// targets built from variables and variable assignments (`A:=b`) are
+/// skipped, as are recipe lines.
+pub fn makefile_targets(contents: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let Some((name, rest)) = line.split_once(':') else {
+            continue;
+        };
+        let is_target = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+            && !rest.starts_with('=')
+            && !rest.starts_with(":=");
+        if is_target && !targets.iter().any(|target| target == name) {
+            targets.push(name.to_string());
+        }
+    }
+    targets
+}
+
+/// Gets up to [`MAX_TARGET_SUGGESTIONS`] candidates similar to `typo`, most
+/// similar first.
+fn closest<'a>(typo: &str, candidates: &'a [String]) -> Vec<&'a str> {
+    let mut scored: Vec<(&str, f64)> = candidates
+        .iter()
+        .map(|candidate| (candidate.as_str(), similarity(candidate, typo)))
+        .filter(|(_, score)| *score >= MIN_TARGET_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored
+        .into_iter()
+        .take(MAX_TARGET_SUGGESTIONS)
+        .map(|(candidate, _)| candidate)
+        .collect()
+}
+
+/// Gets the name between the quotes following `prefix` in `output`.
+///
+/// Older tools open quotes with a backtick, so both quote styles are accepted.
+fn quoted_after<'a>(output: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = output.split(prefix).nth(1)?;
+    let rest = rest.strip_prefix(['\'', '`'])?;
+    rest.split(['\'', '`']).next().filter(|name| !name.is_empty())
+}
+
+/// make_no_rule: `make biuld` → `make build`
+pub struct MakeNoRuleRule {
+    context: Arc<RuleContext>,
+}
+
+impl MakeNoRuleRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Reads the targets of the Makefile make would use in `cwd`.
+    fn targets(cwd: &Path) -> Vec<String> {
+        MAKEFILES
+            .iter()
+            .find_map(|name| std::fs::read_to_string(cwd.join(name)).ok())
+            .map(|contents| makefile_targets(&contents))
+            .unwrap_or_default()
+    }
+}
+
+impl Rule for MakeNoRuleRule {
+    fn name(&self) -> &str {
+        "make_no_rule"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use the closest target from the Makefile")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("make")
+            && quoted_after(&command.output, "No rule to make target ").is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = quoted_after(&command.output, "No rule to make target ") else {
+            return vec![];
+        };
+        let Some(cwd) = self.context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return vec![];
+        };
+
+        let targets = Self::targets(&cwd);
+        let script = Script::parse(&command.script);
+        closest(typo, &targets)
+            .into_iter()
+            .map(|target| script.clone().replace_token(typo, target).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// just_unknown_recipe: `just tset` → `just test`
+pub struct JustUnknownRecipeRule {
+    context: Arc<RuleContext>,
+}
+
+impl JustUnknownRecipeRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Lists the recipes via `just --summary`.
+    fn recipes(&self) -> Vec<String> {
+        let Some(shell) = self.context.shell() else {
+            return Vec::new();
+        };
+        match shell.execute_with_timeout("just --summary", PROBE_TIMEOUT) {
+            Ok(output) if output.success => {
+                output.stdout.split_whitespace().map(str::to_string).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Rule for JustUnknownRecipeRule {
+    fn name(&self) -> &str {
+        "just_unknown_recipe"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use the closest recipe from `just --summary`")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("just")
+            && quoted_after(&command.output, "does not contain recipe ").is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = quoted_after(&command.output, "does not contain recipe ") else {
+            return vec![];
+        };
+
+        let recipes = self.recipes();
+        let script = Script::parse(&command.script);
+        closest(typo, &recipes)
+            .into_iter()
+            .map(|recipe| script.clone().replace_token(typo, recipe).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+/// make_other_runner: `make test` → `just test` in a project with a
+/// justfile and no Makefile
+pub struct MakeOtherRunnerRule {
+    context: Arc<RuleContext>,
+}
+
+impl MakeOtherRunnerRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+}
+
+impl Rule for MakeOtherRunnerRule {
+    fn name(&self) -> &str {
+        "make_other_runner"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use just or task when the project has no Makefile")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.failure_kind() == FailureKind::NotFound
+            && Script::parse(&command.script).executable() == Some("make")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(cwd) = self.context.shell().and_then(|shell| shell.cwd().ok()) else {
+            return vec![];
+        };
+        // With a Makefile present, make itself needs installing
+        if MAKEFILES.iter().any(|name| cwd.join(name).is_file()) {
+            return vec![];
+        }
+
+        let script = Script::parse(&command.script);
+        RUNNER_FILES
+            .iter()
+            .filter(|(_, files)| files.iter().any(|name| cwd.join(name).is_file()))
+            .map(|(runner, _)| script.clone().replace_token_at(0, *runner).to_string())
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        200
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+    use std::path::PathBuf;
+
+    const MESSY_MAKEFILE: &str = "\
+# Build helpers
+PREFIX ?= /usr/local
+CFLAGS:=-O2
+VERSION := $(shell git describe)
+
+.PHONY: build test install clean
+.DEFAULT_GOAL := build
+
+build: src/main.o
+\t$(CC) $(CFLAGS) -o app src/main.o
+\techo \"done: ok\"
+
+%.o: %.c
+\t$(CC) -c $< -o $@
+
+$(BINDIR)/app: build
+\tinstall app $@
+
+test: build
+\t./run-tests
+
+install:: build
+clean-all clean:
+clean:
+\trm -f app
+";
+
+    const MAKE_NO_RULE: &str = "make: *** No rule to make target 'biuld'.  Stop.\n";
+
+    const JUST_UNKNOWN_RECIPE: &str = "error: Justfile does not contain recipe `tset`.\n";
+
+    /// Creates a project directory containing empty files named `files`,
+    /// except a Makefile with `MESSY_MAKEFILE`.
+    fn project_with(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ftf_runners_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            let contents = if *file == "Makefile" { MESSY_MAKEFILE } else { "" };
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    fn context_in(dir: &Path) -> Arc<RuleContext> {
+        Arc::new(RuleContext::new(Box::new(MockShell::new().with_cwd(dir))))
+    }
+
+    #[test]
+    fn test_makefile_targets() {
+        assert_eq!(makefile_targets(MESSY_MAKEFILE), vec!["build", "test", "install", "clean"]);
+    }
+
+    #[test]
+    fn test_makefile_targets_empty() {
+        assert!(makefile_targets("").is_empty());
+        assert!(makefile_targets("# nothing to see\nCC = gcc\n").is_empty());
+    }
+
+    #[test]
+    fn test_make_no_rule() {
+        let dir = project_with("make", &["Makefile"]);
+        let rule = MakeNoRuleRule::new(context_in(&dir));
+        let cmd = Command::new("make biuld -j8", MAKE_NO_RULE, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["make build -j8"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_make_no_rule_backtick_quote() {
+        let dir = project_with("make_backtick", &["Makefile"]);
+        let rule = MakeNoRuleRule::new(context_in(&dir));
+        let cmd = Command::new("make tets", "make: *** No rule to make target `tets'.  Stop.\n", 2);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["make test"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_just_unknown_recipe() {
+        let shell = MockShell::new().with_output("just --summary", "build deploy lint test\n", 0);
+        let rule = JustUnknownRecipeRule::new(Arc::new(RuleContext::new(Box::new(shell))));
+        let cmd = Command::new("just tset --verbose", JUST_UNKNOWN_RECIPE, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["just test --verbose"]);
+    }
+
+    #[test]
+    fn test_make_other_runner() {
+        let cmd = Command::new("make test", "bash: make: command not found", 127);
+
+        let dir = project_with("just", &["justfile"]);
+        let rule = MakeOtherRunnerRule::new(context_in(&dir));
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["just test"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dir = project_with("task", &["Taskfile.yml"]);
+        let rule = MakeOtherRunnerRule::new(context_in(&dir));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["task test"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_make_other_runner_with_makefile() {
+        let dir = project_with("both", &["Makefile", "justfile"]);
+        let rule = MakeOtherRunnerRule::new(context_in(&dir));
+        let cmd = Command::new("make test", "bash: make: command not found", 127);
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_runner_rules_exist() {
+        let rules = runner_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["make_no_rule", "just_unknown_recipe", "make_other_runner"]);
+    }
+}
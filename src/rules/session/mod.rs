@@ -0,0 +1,246 @@
+//! Session cache based rules.
+//!
+//! This module contains rules that look at the commands run just before the
+//! failed one, as recorded by the shell hook:
+//! - cd-ing into a directory created by a preceding `git clone`
+//!
+//! The hook records each command's script and exit code, but not its output:
+//! an entry's output is empty unless something else captured it, so these
+//! rules only use it when it's there.
+
+use super::{RuleInfo, RuleMetadata};
+use crate::{Command, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// `git clone` options that take a separate value argument.
+const CLONE_VALUE_OPTIONS: &[&str] = &[
+    "-b",
+    "--branch",
+    "-o",
+    "--origin",
+    "-c",
+    "--config",
+    "-j",
+    "--jobs",
+    "-u",
+    "--upload-pack",
+    "--depth",
+    "--filter",
+    "--reference",
+    "--separate-git-dir",
+    "--template",
+];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("cd_after_clone", 500),
+];
+
+/// Creates all session-based rules.
+pub fn session_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // cd_after_clone: cd into the directory a preceding git clone created
+        Box::new(CdAfterCloneRule::new(context)),
+    ]
+}
+
+/// cd_after_clone: A failed `cd` right after a `git clone` most likely meant
+/// the directory the clone created.
+pub struct CdAfterCloneRule {
+    context: Arc<RuleContext>,
+}
+
+impl CdAfterCloneRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Finds the directory created by the most recent successful clone,
+    /// if it differs from the failed `cd` target.
+    fn cloned_directory(&self, command: &Command) -> Option<String> {
+        let script = Script::parse(&command.script);
+        let [cd, target] = script.tokens() else {
+            return None;
+        };
+        if cd != "cd" {
+            return None;
+        }
+
+        let directory = self
+            .context
+            .session()
+            .before(&command.script)
+            .iter()
+            .filter(|entry| entry.exit_code == 0)
+            .find_map(|entry| clone_directory(&entry.command, &entry.output))?;
+
+        (directory != target.trim_end_matches('/')).then_some(directory)
+    }
+}
+
+/// Gets the directory a `git clone` command created, from its output if it
+/// was captured, otherwise from its arguments.
+fn clone_directory(script: &str, output: &str) -> Option<String> {
+    let script = Script::parse(script);
+    let tokens = script.tokens();
+    if tokens.len() < 3 || tokens[0] != "git" || tokens[1] != "clone" {
+        return None;
+    }
+
+    if let Some(directory) = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Cloning into '")?.split('\'').next())
+    {
+        return Some(directory.to_string());
+    }
+
+    let mut positional = Vec::new();
+    let mut args = tokens[2..].iter();
+    while let Some(arg) = args.next() {
+        if CLONE_VALUE_OPTIONS.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            positional.push(arg.as_str());
+        }
+    }
+
+    match positional.as_slice() {
+        [_, directory] => Some(directory.trim_end_matches('/').to_string()),
+        [repository] => {
+            let name = repository.trim_end_matches('/').rsplit(['/', ':']).next()?;
+            let name = name.strip_suffix(".git").unwrap_or(name);
+            (!name.is_empty()).then(|| name.to_string())
+        }
+        _ => None,
+    }
+}
+
+impl Rule for CdAfterCloneRule {
+    fn name(&self) -> &str {
+        "cd_after_clone"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Change into the directory a preceding `git clone` created")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.exit_code != 0 && self.cloned_directory(command).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.cloned_directory(command)
+            .map(|directory| vec![Script::from_tokens(["cd", directory.as_str()]).to_string()])
+            .unwrap_or_default()
+    }
+
+    fn priority(&self) -> i32 {
+        500
+    }
+
+    fn requires_output(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::{SessionContext, SessionEntry};
+    use std::path::Path;
+
+    fn fixture_context() -> Arc<RuleContext> {
+        let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/session"));
+        Arc::new(RuleContext::default().with_session(SessionContext::load(dir)))
+    }
+
+    fn context(entries: Vec<SessionEntry>) -> Arc<RuleContext> {
+        Arc::new(RuleContext::default().with_session(SessionContext::from_entries(entries)))
+    }
+
+    #[test]
+    fn test_cd_after_clone_fixture_session() {
+        let rule = CdAfterCloneRule::new(fixture_context());
+        let cmd = Command::new(
+            "cd fasterthefuk",
+            "bash: cd: fasterthefuk: No such file or directory",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cd fasterthefuck"]);
+    }
+
+    #[test]
+    fn test_cd_after_clone_without_captured_output() {
+        let rule = CdAfterCloneRule::new(context(vec![SessionEntry::new(
+            "git clone --depth 1 -b main git@github.com:rust-lang/rust.git",
+            "",
+            0,
+        )]));
+        let cmd = Command::new("cd rust-lang", "", 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cd rust"]);
+    }
+
+    #[test]
+    fn test_cd_after_clone_explicit_directory() {
+        let rule = CdAfterCloneRule::new(context(vec![SessionEntry::new(
+            "git clone https://example.com/repo.git my checkout",
+            "",
+            0,
+        )]));
+        let cmd = Command::new("cd repo", "", 1);
+
+        // Three positional arguments is not a valid clone; nothing to suggest
+        assert!(!rule.matches(&cmd));
+
+        let rule = CdAfterCloneRule::new(context(vec![SessionEntry::new(
+            "git clone https://example.com/repo.git 'my checkout'",
+            "",
+            0,
+        )]));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["cd 'my checkout'"]);
+    }
+
+    #[test]
+    fn test_cd_after_clone_ignores_failed_clone() {
+        let rule = CdAfterCloneRule::new(context(vec![SessionEntry::new(
+            "git clone https://example.com/repo.git",
+            "fatal: repository not found",
+            128,
+        )]));
+        let cmd = Command::new("cd rpeo", "", 1);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_cd_after_clone_same_directory() {
+        let rule = CdAfterCloneRule::new(context(vec![SessionEntry::new(
+            "git clone https://example.com/repo.git",
+            "",
+            0,
+        )]));
+        let cmd = Command::new("cd repo/", "", 1);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_cd_after_clone_requires_cd() {
+        let rule = CdAfterCloneRule::new(fixture_context());
+        let cmd = Command::new("ls fasterthefuk", "", 2);
+
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_session_rules_exist() {
+        let rules = session_rules(context(Vec::new()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(names, vec!["cd_after_clone"]);
+    }
+}
@@ -0,0 +1,85 @@
+//! Minimal `~/.ssh/config` parser.
+//!
+//! Only `Host` lines are read: their literal aliases are what users type, and
+//! what a mistyped hostname is most likely meant to be. Patterns containing
+//! `*`, `?` or `!` are skipped since they are not hosts you can connect to.
+
+use std::path::Path;
+
+/// Host aliases declared in an ssh client config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SshConfig {
+    hosts: Vec<String>,
+}
+
+impl SshConfig {
+    /// Parses the `Host` aliases out of config file contents.
+    ///
+    /// Keywords are case-insensitive and may be separated from their value by
+    /// `=` as well as whitespace.
+    pub fn parse(contents: &str) -> Self {
+        let mut hosts: Vec<String> = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, value) = match line.split_once(|c: char| c == '=' || c.is_whitespace()) {
+                Some((keyword, value)) => (keyword, value.trim_start_matches(['=', ' ', '\t'])),
+                None => continue,
+            };
+            if !keyword.eq_ignore_ascii_case("host") {
+                continue;
+            }
+
+            for alias in value.split_whitespace() {
+                let alias = alias.trim_matches('"');
+                let is_pattern = alias.contains(['*', '?', '!']);
+                if !is_pattern && !hosts.iter().any(|host| host == alias) {
+                    hosts.push(alias.to_string());
+                }
+            }
+        }
+
+        Self { hosts }
+    }
+
+    /// Loads the config at `path`. A missing or unreadable file has no hosts.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    /// Gets the literal host aliases, in file order.
+    pub fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../../tests/fixtures/ssh/.ssh/config");
+
+    #[test]
+    fn test_parse_fixture_excludes_wildcards() {
+        let config = SshConfig::parse(FIXTURE);
+        assert_eq!(config.hosts(), &["prod", "prod-db", "staging", "bastion"]);
+    }
+
+    #[test]
+    fn test_parse_ignores_other_keywords() {
+        let config = SshConfig::parse("HostName example.com\nUser me\n  # Host commented\n");
+        assert!(config.hosts().is_empty());
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let config = SshConfig::load(Path::new("/nonexistent/ftf/.ssh/config"));
+        assert_eq!(config, SshConfig::default());
+    }
+}
@@ -0,0 +1,598 @@
+//! ssh and scp command correction rules.
+//!
+//! This module contains rules for common ssh mistakes:
+//! - Host keys that changed since they were recorded in known_hosts, fixed
+//!   by removing the stale entry before retrying
+//! - Public key authentication failures
+//! - Copying a directory with scp without `-r`
+//! - Mistyped hostnames, matched against the aliases in `~/.ssh/config`
+
+pub mod config;
+
+pub use config::SshConfig;
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::prefix_then_retry;
+use crate::effects::is_known_hosts_path;
+use crate::fuzzy::similarity;
+use crate::{Command, Rule, RuleContext, Script, SideEffect, SimpleRuleBuilder};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// ssh and scp options that take a separate value (`-p 2222`, `-i key`).
+const VALUE_OPTIONS: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Key suggested when public key authentication fails.
+const DEFAULT_IDENTITY: &str = "~/.ssh/id_ed25519";
+
+/// Minimum similarity for a configured host to be suggested.
+const MIN_HOST_SIMILARITY: f64 = 0.5;
+
+/// Maximum number of host suggestions.
+const MAX_HOST_SUGGESTIONS: usize = 3;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("ssh_host_key_changed", 300),
+    ("ssh_publickey_denied", 500),
+    ("scp_recursive", 300),
+    ("ssh_unknown_host", 400),
+];
+
+/// Creates all ssh and scp rules.
+///
+/// The hostname rule reads `~/.ssh/config` using `$HOME` from the context's
+/// shell.
+pub fn ssh_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // ssh_host_key_changed: Remove the stale known_hosts entry and retry
+        Box::new(SshHostKeyChangedRule),
+        // ssh_publickey_denied: Install a key, or offer a specific one
+        Box::new(SshPublickeyDeniedRule),
+        // scp_recursive: Add -r when copying a directory
+        create_scp_recursive(),
+        // ssh_unknown_host: Fix a mistyped host from ~/.ssh/config
+        Box::new(SshUnknownHostRule::new(context)),
+    ]
+}
+
+/// Gets the remote `[user@]host` from an ssh, ssh-copy-id or scp script.
+///
+/// For ssh this is the first argument after the options; for scp it is the
+/// part before `:` of the first remote path.
+fn destination(script: &Script) -> Option<String> {
+    let executable = script.executable()?;
+    let mut positional = Vec::new();
+    let mut tokens = script.tokens().iter().skip(1);
+    while let Some(token) = tokens.next() {
+        if let Some(option) = token.strip_prefix('-') {
+            if option.len() == 1 && VALUE_OPTIONS.contains(option) {
+                tokens.next();
+            }
+        } else {
+            positional.push(token.as_str());
+        }
+    }
+
+    let remote = if executable == "scp" {
+        positional
+            .into_iter()
+            .find(|arg| split_remote(arg).is_some_and(|(_, _, rest)| rest.starts_with(':')))?
+    } else {
+        positional.into_iter().next()?
+    };
+    let (user, host, _) = split_remote(remote)?;
+    Some(format!("{}{}", user, host))
+}
+
+/// Splits a remote argument into its `user@` prefix, host and the remainder
+/// (`:path`, `:port` or `/path`). Any `ssh://` or `scp://` scheme is dropped.
+fn split_remote(arg: &str) -> Option<(&str, &str, &str)> {
+    let arg = arg
+        .strip_prefix("ssh://")
+        .or_else(|| arg.strip_prefix("scp://"))
+        .unwrap_or(arg);
+    let end = arg.find([':', '/']).unwrap_or(arg.len());
+    let (authority, rest) = arg.split_at(end);
+    let (user, host) = match authority.rfind('@') {
+        Some(at) => authority.split_at(at + 1),
+        None => ("", authority),
+    };
+    (!host.is_empty()).then_some((user, host, rest))
+}
+
+/// Gets the host part of a `[user@]host` destination.
+fn host_of(destination: &str) -> &str {
+    destination.rsplit('@').next().unwrap_or(destination)
+}
+
+/// ssh_host_key_changed: `ssh build.example.com` → `ssh build.example.com`
+/// after removing the stale known_hosts entry, or
+/// `ssh-keygen -R build.example.com && ssh build.example.com` when ssh didn't
+/// say which known_hosts file it read
+struct SshHostKeyChangedRule;
+
+impl SshHostKeyChangedRule {
+    /// Gets the known_hosts entry to remove: from ssh's own `-R` hint, from
+    /// the offending line in known_hosts, or from the original command.
+    fn stale_host(command: &Command) -> Option<String> {
+        if let Some(hint) = command.output.split(" -R \"").nth(1) {
+            if let Some(host) = hint.split('"').next().filter(|host| !host.is_empty()) {
+                return Some(host.to_string());
+            }
+        }
+
+        Self::offending_line(&command.output)
+            .and_then(|(path, line)| Self::known_hosts_entry(&path, line))
+            .or_else(|| {
+                destination(&Script::parse(&command.script)).map(|dest| host_of(&dest).to_string())
+            })
+    }
+
+    /// Parses `Offending ECDSA key in /path/known_hosts:12`, for a path
+    /// that is a known_hosts file.
+    fn offending_line(output: &str) -> Option<(PathBuf, usize)> {
+        let rest = output.split("Offending ").nth(1)?;
+        let location = rest.split(" key in ").nth(1)?.lines().next()?.trim();
+        let (path, line) = location.rsplit_once(':')?;
+        let path = PathBuf::from(path);
+        is_known_hosts_path(&path).then_some((path, line.parse().ok()?))
+    }
+
+    /// Gets the known_hosts file ssh read, with the offending line if ssh
+    /// reported one, falling back to the `-f` of its `ssh-keygen` hint.
+    fn known_hosts_file(output: &str) -> Option<(PathBuf, Option<usize>)> {
+        if let Some((path, line)) = Self::offending_line(output) {
+            return Some((path, Some(line)));
+        }
+
+        let hint = output.split("ssh-keygen -f \"").nth(1)?;
+        let path = PathBuf::from(hint.split('"').next()?);
+        is_known_hosts_path(&path).then_some((path, None))
+    }
+
+    /// Reads the first host of a 1-based known_hosts line. Hashed entries
+    /// cannot be read back and yield `None`.
+    fn known_hosts_entry(path: &Path, line: usize) -> Option<String> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let entry = contents.lines().nth(line.checked_sub(1)?)?;
+        let hosts = entry.split_whitespace().next()?;
+        if hosts.starts_with('|') {
+            return None;
+        }
+        hosts.split(',').next().map(str::to_string)
+    }
+}
+
+impl Rule for SshHostKeyChangedRule {
+    fn name(&self) -> &str {
+        "ssh_host_key_changed"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Remove a host's stale known_hosts entry, then connect again")
+            .example(Example::new(
+                "ssh build.example.com",
+                "@@@ WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED! @@@",
+                255,
+                "ssh-keygen -R build.example.com && ssh build.example.com",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        // Only ssh's own warning: the output of `cat notes.txt` could quote one
+        let script = Script::parse(&command.script);
+        matches!(script.executable(), Some("ssh" | "scp" | "sftp" | "rsync" | "git"))
+            && command.output.contains("REMOTE HOST IDENTIFICATION HAS CHANGED")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.get_new_commands_cow(command).into_iter().map(Cow::into_owned).collect()
+    }
+
+    fn get_new_commands_cow<'a>(&'a self, command: &'a Command) -> Vec<Cow<'a, str>> {
+        let Some(host) = Self::stale_host(command) else {
+            return vec![];
+        };
+
+        // The entry is removed by the side effect when the file is known
+        if Self::known_hosts_file(&command.output).is_some() {
+            return vec![Cow::Borrowed(&command.script)];
+        }
+
+        let setup = Script::from_tokens(["ssh-keygen", "-R", host.as_str()]);
+        vec![Cow::Owned(prefix_then_retry(&setup.to_string(), &command.script))]
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+
+    fn side_effect_for(&self, command: &Command, _correction: &str) -> Option<SideEffect> {
+        let (known_hosts, line) = Self::known_hosts_file(&command.output)?;
+        Some(SideEffect::RemoveKnownHost {
+            known_hosts,
+            host: Self::stale_host(command)?,
+            line,
+        })
+    }
+}
+
+/// ssh_publickey_denied: `ssh deploy@prod` → `ssh-copy-id deploy@prod`, or
+/// `ssh -i ~/.ssh/id_ed25519 deploy@prod`
+struct SshPublickeyDeniedRule;
+
+impl Rule for SshPublickeyDeniedRule {
+    fn name(&self) -> &str {
+        "ssh_publickey_denied"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Install a key with ssh-copy-id, or offer a specific one")
+            .example(Example::new(
+                "ssh deploy@prod",
+                "deploy@prod: Permission denied (publickey).",
+                255,
+                "ssh-copy-id deploy@prod",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        let script = Script::parse(&command.script);
+        matches!(script.executable(), Some("ssh" | "scp"))
+            && command.output.contains("Permission denied (publickey")
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        let Some(destination) = destination(&script) else {
+            return vec![];
+        };
+
+        let mut corrections =
+            vec![Script::from_tokens(["ssh-copy-id", destination.as_str()]).to_string()];
+
+        if !script.tokens().iter().any(|token| token.starts_with("-i")) {
+            let identity = Script::parse(&format!("-i {}", DEFAULT_IDENTITY));
+            corrections.push(script.insert_script_at(1, identity).to_string());
+        }
+        corrections
+    }
+
+    fn priority(&self) -> i32 {
+        500
+    }
+}
+
+/// scp_recursive: `scp dist host:/srv` → `scp -r dist host:/srv`
+fn create_scp_recursive() -> Box<dyn Rule> {
+    SimpleRuleBuilder::new("scp_recursive")
+        .match_command("scp ")
+        .match_output("not a regular file")
+        .priority(300)
+        .describe("Copy a directory with `scp -r`")
+        .example(Example::new("scp dist prod:/srv", "scp: dist: not a regular file", 1, "scp -r dist prod:/srv"))
+        .insert_flag("scp", "-r")
+}
+
+/// ssh_unknown_host: `ssh deploy@prdo` → `ssh deploy@prod`
+pub struct SshUnknownHostRule {
+    context: Arc<RuleContext>,
+}
+
+impl SshUnknownHostRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the unresolvable hostname from ssh's error.
+    fn unresolved_host(output: &str) -> Option<&str> {
+        let rest = output.split("Could not resolve hostname ").nth(1)?;
+        rest.split(':').next().filter(|host| !host.is_empty())
+    }
+
+    /// Loads `~/.ssh/config` through the context's shell.
+    fn config(&self) -> SshConfig {
+        self.context
+            .shell()
+            .and_then(|shell| shell.env("HOME"))
+            .map(|home| SshConfig::load(&Path::new(&home).join(".ssh").join("config")))
+            .unwrap_or_default()
+    }
+}
+
+impl Rule for SshUnknownHostRule {
+    fn name(&self) -> &str {
+        "ssh_unknown_host"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Fix a mistyped host from the aliases in `~/.ssh/config`")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        matches!(Script::parse(&command.script).executable(), Some("ssh" | "scp"))
+            && Self::unresolved_host(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(typo) = Self::unresolved_host(&command.output) else {
+            return vec![];
+        };
+
+        let mut hosts: Vec<(String, f64)> = self
+            .config()
+            .hosts()
+            .iter()
+            .map(|host| (host.clone(), similarity(host, typo)))
+            .filter(|(host, score)| host != typo && *score >= MIN_HOST_SIMILARITY)
+            .collect();
+        hosts.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let script = Script::parse(&command.script);
+        let Some(index) = script
+            .tokens()
+            .iter()
+            .position(|token| split_remote(token).is_some_and(|(_, host, _)| host == typo))
+        else {
+            return vec![];
+        };
+
+        hosts
+            .into_iter()
+            .take(MAX_HOST_SUGGESTIONS)
+            .map(|(host, _)| {
+                let token = &script.tokens()[index];
+                let (user, _, rest) = split_remote(token).unwrap_or(("", typo, ""));
+                let scheme = &token[..token.len() - user.len() - typo.len() - rest.len()];
+                let replacement = format!("{}{}{}{}", scheme, user, host, rest);
+                script.clone().replace_token_at(index, replacement).to_string()
+            })
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        400
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const FIXTURE_HOME: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/ssh");
+
+    const PUBLICKEY_DENIED: &str = "deploy@10.0.4.12: Permission denied (publickey).\n";
+
+    fn host_key_changed(offending: &str, hint: &str) -> String {
+        format!(
+            "@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+             @    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @\n\
+             @@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+             IT IS POSSIBLE THAT SOMEONE IS DOING SOMETHING NASTY!\n\
+             Someone could be eavesdropping on you right now (man-in-the-middle attack)!\n\
+             It is also possible that a host key has just been changed.\n\
+             Add correct host key in {home}/.ssh/known_hosts to get rid of this message.\n\
+             Offending ECDSA key in {home}/.ssh/known_hosts:{offending}\n\
+             {hint}\
+             Host key verification failed.\n",
+            home = FIXTURE_HOME,
+            offending = offending,
+            hint = hint,
+        )
+    }
+
+    fn context_with_home(home: &str) -> Arc<RuleContext> {
+        let shell = MockShell::new().with_env("HOME", home);
+        Arc::new(RuleContext::new(Box::new(shell)))
+    }
+
+    #[test]
+    fn test_destination() {
+        let dest = |script: &str| destination(&Script::parse(script));
+
+        assert_eq!(dest("ssh -p 2222 -i key deploy@prod uptime").as_deref(), Some("deploy@prod"));
+        assert_eq!(dest("ssh ssh://prod:2222").as_deref(), Some("prod"));
+        assert_eq!(dest("scp -P 2222 ./dist deploy@prod:/srv").as_deref(), Some("deploy@prod"));
+        assert_eq!(dest("scp ./a ./b"), None);
+    }
+
+    fn known_hosts_effect(host: &str, line: Option<usize>) -> SideEffect {
+        SideEffect::RemoveKnownHost {
+            known_hosts: Path::new(FIXTURE_HOME).join(".ssh/known_hosts"),
+            host: host.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_ssh_host_key_changed_uses_hint() {
+        let rule = SshHostKeyChangedRule;
+        let output = host_key_changed(
+            "2",
+            "  remove with:\n  ssh-keygen -f \"/home/me/.ssh/known_hosts\" -R \"[build.example.com]:2222\"\n",
+        );
+        let cmd = Command::new("ssh -p 2222 build.example.com", &output, 255);
+
+        assert!(rule.matches(&cmd));
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].script, "ssh -p 2222 build.example.com");
+        assert_eq!(
+            corrections[0].side_effect,
+            Some(known_hosts_effect("[build.example.com]:2222", Some(2)))
+        );
+    }
+
+    #[test]
+    fn test_ssh_host_key_changed_reads_known_hosts_line() {
+        let rule = SshHostKeyChangedRule;
+        let output = host_key_changed("2", "");
+        let cmd = Command::new("ssh ci", &output, 255);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ssh ci"]);
+        assert_eq!(
+            rule.side_effect_for(&cmd, "ssh ci"),
+            Some(known_hosts_effect("build.example.com", Some(2)))
+        );
+    }
+
+    #[test]
+    fn test_ssh_host_key_changed_hashed_line_falls_back_to_command() {
+        let rule = SshHostKeyChangedRule;
+        let output = host_key_changed("3", "");
+        let cmd = Command::new("ssh deploy@build.example.com", &output, 255);
+
+        assert_eq!(
+            rule.side_effect_for(&cmd, &cmd.script),
+            Some(known_hosts_effect("build.example.com", Some(3)))
+        );
+    }
+
+    #[test]
+    fn test_ssh_host_key_changed_without_known_hosts_path() {
+        let rule = SshHostKeyChangedRule;
+        let output = "WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!\nHost key verification failed.\n";
+        let cmd = Command::new("ssh deploy@build.example.com", output, 255);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["ssh-keygen -R build.example.com && ssh deploy@build.example.com"]
+        );
+        assert_eq!(rule.side_effect_for(&cmd, &cmd.script), None);
+    }
+
+    #[test]
+    fn test_ssh_host_key_changed_effect_removes_entry() {
+        let dir = std::env::temp_dir().join(format!("ftf_ssh_known_hosts_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(".ssh")).unwrap();
+        let known_hosts = dir.join(".ssh/known_hosts");
+        std::fs::copy(Path::new(FIXTURE_HOME).join(".ssh/known_hosts"), &known_hosts).unwrap();
+
+        let output = host_key_changed("2", "").replace(
+            &format!("{}/.ssh/known_hosts", FIXTURE_HOME),
+            &known_hosts.display().to_string(),
+        );
+        let cmd = Command::new("ssh ci", &output, 255);
+        let corrections = SshHostKeyChangedRule.get_corrected_commands(&cmd);
+        corrections[0]
+            .side_effect
+            .as_ref()
+            .unwrap()
+            .run(&MockShell::new().with_env("HOME", dir.to_str().unwrap()))
+            .unwrap();
+
+        let remaining = std::fs::read_to_string(&known_hosts).unwrap();
+        assert_eq!(remaining.lines().count(), 2);
+        assert!(!remaining.contains("build.example.com"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ssh_host_key_changed_ignores_quoted_warnings() {
+        // A pasted warning naming another file, printed by something else
+        let known_hosts = format!("{}/.ssh/known_hosts", FIXTURE_HOME);
+        let output = host_key_changed("3", "").replace(&known_hosts, "/home/me/.bashrc");
+        assert!(!SshHostKeyChangedRule.matches(&Command::new("cat notes.txt", &output, 0)));
+
+        // ssh itself, but a path that isn't a known_hosts file gets no effect
+        let cmd = Command::new("ssh ci", &output, 255);
+        assert!(SshHostKeyChangedRule.matches(&cmd));
+        assert_eq!(SshHostKeyChangedRule.side_effect_for(&cmd, "ssh ci"), None);
+        assert_eq!(SshHostKeyChangedRule.get_new_commands(&cmd), vec!["ssh-keygen -R ci && ssh ci"]);
+    }
+
+    #[test]
+    fn test_ssh_publickey_denied() {
+        let rule = SshPublickeyDeniedRule;
+        let cmd = Command::new("ssh deploy@10.0.4.12", PUBLICKEY_DENIED, 255);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["ssh-copy-id deploy@10.0.4.12", "ssh -i ~/.ssh/id_ed25519 deploy@10.0.4.12"]
+        );
+    }
+
+    #[test]
+    fn test_ssh_publickey_denied_with_identity() {
+        let rule = SshPublickeyDeniedRule;
+        let cmd = Command::new("ssh -i ~/.ssh/work deploy@10.0.4.12", PUBLICKEY_DENIED, 255);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ssh-copy-id deploy@10.0.4.12"]);
+    }
+
+    #[test]
+    fn test_scp_recursive() {
+        let rule = create_scp_recursive();
+        let cmd = Command::new("scp dist prod:/srv/app", "scp: local \"dist\" is not a regular file\n", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["scp -r dist prod:/srv/app"]);
+    }
+
+    #[test]
+    fn test_ssh_unknown_host_from_config() {
+        let rule = SshUnknownHostRule::new(context_with_home(FIXTURE_HOME));
+        let cmd = Command::new(
+            "ssh deploy@stagng",
+            "ssh: Could not resolve hostname stagng: Name or service not known\n",
+            255,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["ssh deploy@staging"]);
+    }
+
+    #[test]
+    fn test_scp_unknown_host_keeps_path() {
+        let rule = SshUnknownHostRule::new(context_with_home(FIXTURE_HOME));
+        let cmd = Command::new(
+            "scp backup.sql prdo:/tmp/",
+            "ssh: Could not resolve hostname prdo: nodename nor servname provided, or not known\n",
+            1,
+        );
+
+        assert_eq!(rule.get_new_commands(&cmd)[0], "scp backup.sql prod:/tmp/");
+    }
+
+    #[test]
+    fn test_ssh_unknown_host_never_suggests_patterns() {
+        let rule = SshUnknownHostRule::new(context_with_home(FIXTURE_HOME));
+        let cmd = Command::new(
+            "ssh build-x",
+            "ssh: Could not resolve hostname build-x: Name or service not known\n",
+            255,
+        );
+
+        assert!(rule.get_new_commands(&cmd).iter().all(|c| !c.contains(['*', '?'])));
+    }
+
+    #[test]
+    fn test_ssh_unknown_host_without_config() {
+        let rule = SshUnknownHostRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new(
+            "ssh prdo",
+            "ssh: Could not resolve hostname prdo: Name or service not known\n",
+            255,
+        );
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_ssh_rules_exist() {
+        let rules = ssh_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["ssh_host_key_changed", "ssh_publickey_denied", "scp_recursive", "ssh_unknown_host"]
+        );
+    }
+}
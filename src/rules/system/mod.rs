@@ -0,0 +1,358 @@
+//! Rules that apply to any command.
+//!
+//! This module contains:
+//! - A harvester for the "Did you mean X?" hints many CLIs print after a
+//!   typo, for tools without a rule of their own
+//!
+//! Tool-specific rules know exactly which token a hint replaces, so the
+//! harvester leaves the tools they cover alone, ranks below every other rule,
+//! and only suggests a substitution it can place confidently.
+
+use super::util::listed_suggestions;
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::{Command, Rule, Script};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Minimum similarity between a suggestion and the token it replaces.
+const MIN_TOKEN_SIMILARITY: f64 = 0.5;
+
+/// How much more similar to a suggestion the replaced token must be than
+/// the next best token, when the output doesn't quote either.
+const MIN_SIMILARITY_MARGIN: f64 = 0.15;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[("did_you_mean", 2000)];
+
+/// Executables whose hints their own rules already lift.
+const COVERED_EXECUTABLES: &[&str] = &["git", "kubectl", "gh", "glab", "aws", "az", "gcloud"];
+
+/// Suggestions given on the line of the hint, by the tools that phrase them
+/// that way. The first group of each pattern is the suggestion.
+const INLINE_PHRASINGS: &[(&str, &str)] = &[
+    ("cargo", r"[Dd]id you mean `([^`\s]+)`\?"),
+    ("cargo", r"a command with a similar name exists: `([^`\s]+)`"),
+    ("clap", r"a similar (?:argument|subcommand|value) exists: '([^'\s]+)'"),
+    ("pip", r#"maybe you meant "([^"\s]+)""#),
+    ("argparse", r"maybe you meant '([^'\s]+)'\?"),
+    ("yarn, bundler", r#"[Dd]id you mean ['"]([^'"\s]+)['"]\??"#),
+    ("rustc, deno", r"[Pp]erhaps you meant `([^`\s]+)`"),
+];
+
+/// Headers of the indented suggestion lists tools print below them.
+const LISTED_PHRASINGS: &[(&str, &str)] = &[
+    ("git", "The most similar command"),
+    ("cobra: kubectl, gh, hugo, docker", "Did you mean this?"),
+    ("npm", "Did you mean one of these?"),
+    ("aws", "maybe you meant:"),
+    ("gcloud", "Maybe you meant:"),
+];
+
+/// Creates the rules that apply to any command.
+pub fn system_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // did_you_mean: Use the suggestion the command printed
+        Box::new(DidYouMeanRule),
+    ]
+}
+
+/// Gets the compiled [`INLINE_PHRASINGS`] patterns.
+fn inline_patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        INLINE_PHRASINGS
+            .iter()
+            .map(|(_, pattern)| Regex::new(pattern).unwrap())
+            .collect()
+    })
+}
+
+/// Gets the suggestions in `output`, in the order they appear, without
+/// duplicates.
+///
+/// A listed suggestion may be a whole command (`npm install # Install a
+/// package`); only its last word is kept, as with
+/// [`replace_with_suggestions`](super::util::replace_with_suggestions).
+fn harvest_suggestions(output: &str) -> Vec<String> {
+    let mut found: Vec<(usize, String)> = Vec::new();
+    for pattern in inline_patterns() {
+        for captures in pattern.captures_iter(output) {
+            if let Some(suggestion) = captures.get(1) {
+                found.push((suggestion.start(), suggestion.as_str().to_string()));
+            }
+        }
+    }
+    for (_, header) in LISTED_PHRASINGS {
+        let Some(start) = output.find(header) else {
+            continue;
+        };
+        let listed = listed_suggestions(output, header).into_iter().filter_map(|entry| {
+            let entry = entry.split(" #").next()?;
+            entry.split_whitespace().last().map(|word| word.trim_end_matches(['?', ',', '.']))
+        });
+        found.extend(listed.filter(|word| !word.is_empty()).map(|word| (start, word.to_string())));
+    }
+
+    found.sort_by_key(|(position, _)| *position);
+    let mut suggestions: Vec<String> = Vec::new();
+    for (_, suggestion) in found {
+        if !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+    suggestions
+}
+
+/// Returns true if `output` mentions `token` in quotes or backticks, as
+/// tools do when naming what they didn't recognize.
+fn is_quoted_in(output: &str, token: &str) -> bool {
+    ['\'', '"', '`'].iter().any(|quote| output.contains(&format!("{0}{1}{0}", quote, token)))
+        || output.contains(&format!("‘{}’", token))
+}
+
+/// Finds the token of `script` that `suggestion` replaces: the arguments
+/// at least [`MIN_TOKEN_SIMILARITY`] similar to it, narrowed to the one the
+/// output quotes, or else the most similar by [`MIN_SIMILARITY_MARGIN`].
+///
+/// Returns `None` if the suggestion is already a token, or no single token
+/// stands out.
+fn locate_typo(script: &Script, output: &str, suggestion: &str) -> Option<usize> {
+    let tokens = script.tokens();
+    if tokens.iter().any(|token| token == suggestion) {
+        return None;
+    }
+    let mut candidates: Vec<(usize, f64)> = tokens
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(index, token)| (index, similarity(token, suggestion)))
+        .filter(|(_, score)| *score >= MIN_TOKEN_SIMILARITY)
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let quoted: Vec<usize> = candidates
+        .iter()
+        .map(|(index, _)| *index)
+        .filter(|&index| is_quoted_in(output, &tokens[index]))
+        .collect();
+    match (quoted.as_slice(), candidates.as_slice()) {
+        ([index], _) => Some(*index),
+        ([], [(index, _)]) => Some(*index),
+        ([], [(index, best), (_, next), ..]) if best - next >= MIN_SIMILARITY_MARGIN => Some(*index),
+        _ => None,
+    }
+}
+
+/// did_you_mean: `tool instal` → `tool install`, from the tool's own hint
+pub struct DidYouMeanRule;
+
+impl DidYouMeanRule {
+    /// Gets one correction per suggestion that replaces a located token.
+    fn corrections(command: &Command) -> Vec<String> {
+        let script = Script::parse(&command.script);
+        if script.executable().is_none_or(|executable| COVERED_EXECUTABLES.contains(&executable)) {
+            return vec![];
+        }
+        let mut corrections: Vec<String> = Vec::new();
+        for suggestion in harvest_suggestions(&command.output) {
+            let Some(index) = locate_typo(&script, &command.output, &suggestion) else {
+                continue;
+            };
+            let correction = script.clone().replace_token_at(index, suggestion).to_string();
+            if !corrections.contains(&correction) {
+                corrections.push(correction);
+            }
+        }
+        corrections
+    }
+}
+
+impl Rule for DidYouMeanRule {
+    fn name(&self) -> &str {
+        "did_you_mean"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Use the suggestion a command printed for a mistyped word")
+            .example(Example::new(
+                "pip instal requests",
+                "ERROR: unknown command \"instal\" - maybe you meant \"install\"",
+                1,
+                "pip install requests",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        !Self::corrections(command).is_empty()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        Self::corrections(command)
+    }
+
+    fn priority(&self) -> i32 {
+        2000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (tool, output, suggestions harvested from it)
+    const HARVESTS: &[(&str, &str, &[&str])] = &[
+        (
+            "git",
+            "git: 'stat' is not a git command. See 'git --help'.\n\nThe most similar commands are\n\tstash\n\tstatus\n",
+            &["stash", "status"],
+        ),
+        ("cargo", "error: no such command: `biuld`\n\n\tDid you mean `build`?\n", &["build"]),
+        (
+            "cargo",
+            "error: no such command: `tets`\n\nhelp: a command with a similar name exists: `test`\n",
+            &["test"],
+        ),
+        (
+            "clap",
+            "error: unexpected argument '--relase' found\n\n  tip: a similar argument exists: '--release'\n",
+            &["--release"],
+        ),
+        ("pip", "ERROR: unknown command \"instal\" - maybe you meant \"install\"\n", &["install"]),
+        (
+            "argparse",
+            "usage: tool [-h] {serve,build} ...\ntool: error: argument command: invalid choice: 'serv', \
+             maybe you meant 'serve'? (choose from serve, build)\n",
+            &["serve"],
+        ),
+        (
+            "npm",
+            "Unknown command: \"isntall\"\n\nDid you mean this?\n    npm install # Install a package\n\n\
+             To see a list of supported npm commands, run:\n  npm help\n",
+            &["install"],
+        ),
+        (
+            "npm",
+            "Unknown command: \"tes\"\n\nDid you mean one of these?\n    npm test # Test a package\n    \
+             npm exec # Run a command from a local or remote npm package\n",
+            &["test", "exec"],
+        ),
+        (
+            "gh",
+            "unknown command \"craete\" for \"gh pr\"\n\nDid you mean this?\n\tcreate\n\n\
+             Usage:  gh pr <command> [flags]\n",
+            &["create"],
+        ),
+        (
+            "hugo",
+            "Error: unknown command \"sever\" for \"hugo\"\n\nDid you mean this?\n\tserver\n\n\
+             Run 'hugo --help' for usage.\n",
+            &["server"],
+        ),
+        (
+            "docker",
+            "unknown command \"biuld\" for \"docker buildx\"\n\nDid you mean this?\n\tbuild\n",
+            &["build"],
+        ),
+        (
+            "kubectl",
+            "error: unknown command \"gte\" for \"kubectl\"\n\nDid you mean this?\n\tget\n\tset\n",
+            &["get", "set"],
+        ),
+        ("aws", "Invalid choice: 'ec3', maybe you meant:\n\n  * ec2\n  * ecs\n", &["ec2", "ecs"]),
+        ("yarn", "error Command \"biuld\" not found. Did you mean \"build\"?\n", &["build"]),
+        ("none", "error: something went wrong\n", &[]),
+        ("cobra without a list", "Did you mean this?\nRun 'tool --help' for usage.\n", &[]),
+    ];
+
+    /// (script, output, expected corrections)
+    const CORRECTIONS: &[(&str, &str, &[&str])] = &[
+        (
+            "cargo biuld --release",
+            "error: no such command: `biuld`\n\n\tDid you mean `build`?\n",
+            &["cargo build --release"],
+        ),
+        (
+            "cargo build --relase",
+            "error: unexpected argument '--relase' found\n\n  tip: a similar argument exists: '--release'\n",
+            &["cargo build --release"],
+        ),
+        (
+            "npm isntall left-pad",
+            "Unknown command: \"isntall\"\n\nDid you mean this?\n    npm install # Install a package\n",
+            &["npm install left-pad"],
+        ),
+        // set is listed too, but isn't similar enough to gte to place
+        (
+            "helm gte values web",
+            "Error: unknown command \"gte\" for \"helm\"\n\nDid you mean this?\n\tget\n\tset\n",
+            &["helm get values web"],
+        ),
+        // git_not_command lifts git's own list
+        (
+            "git pus",
+            "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush",
+            &[],
+        ),
+        (
+            "hugo sever -D",
+            "Error: unknown command \"sever\" for \"hugo\"\n\nDid you mean this?\n\tserver\n",
+            &["hugo server -D"],
+        ),
+        // The quoted token wins over a more similar one
+        (
+            "tool serv servr",
+            "tool: error: invalid choice: 'servr', maybe you meant 'serve'?",
+            &["tool serv serve"],
+        ),
+        // Tokens equally similar and unquoted: no confident placement
+        ("tool ab ac", "Did you mean 'ad'?", &[]),
+        // Already a token, so not a typo of one
+        ("tool build biuld", "Did you mean 'build'?", &[]),
+        // Nothing similar enough
+        ("tool xyz", "Did you mean 'install'?", &[]),
+        // The executable itself isn't replaced
+        ("instal", "Did you mean 'install'?", &[]),
+    ];
+
+    #[test]
+    fn test_harvest_suggestions() {
+        for (tool, output, expected) in HARVESTS {
+            assert_eq!(harvest_suggestions(output), *expected, "{}", tool);
+        }
+    }
+
+    #[test]
+    fn test_inline_phrasings_compile() {
+        assert_eq!(inline_patterns().len(), INLINE_PHRASINGS.len());
+    }
+
+    #[test]
+    fn test_locate_typo() {
+        let script = Script::parse("gh pr craete --fill");
+        assert_eq!(locate_typo(&script, "unknown command \"craete\"", "create"), Some(2));
+        // Unquoted, but clearly the closest
+        assert_eq!(locate_typo(&script, "", "create"), Some(2));
+        assert_eq!(locate_typo(&script, "", "--fill"), None);
+        assert_eq!(locate_typo(&Script::parse("tool ab ac"), "", "ad"), None);
+        assert_eq!(locate_typo(&Script::parse("tool ab ac"), "'ac' isn't a command", "ad"), Some(2));
+    }
+
+    #[test]
+    fn test_is_quoted_in() {
+        assert!(is_quoted_in("no such command: `biuld`", "biuld"));
+        assert!(is_quoted_in("unknown command \"gte\"", "gte"));
+        assert!(is_quoted_in("invalid choice: ‘serv’", "serv"));
+        assert!(!is_quoted_in("unknown command gte", "gte"));
+    }
+
+    #[test]
+    fn test_did_you_mean_corrections() {
+        for (script, output, expected) in CORRECTIONS {
+            let cmd = Command::new(*script, *output, 1);
+            assert_eq!(DidYouMeanRule.get_new_commands(&cmd), *expected, "{}", script);
+            assert_eq!(DidYouMeanRule.matches(&cmd), !expected.is_empty(), "{}", script);
+        }
+    }
+}
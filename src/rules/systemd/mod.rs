@@ -0,0 +1,422 @@
+//! systemctl and journalctl command correction rules.
+//!
+//! This module contains rules for common systemd mistakes:
+//! - Missing sudo for commands that change unit state
+//! - Mistyped unit names, matched against the installed unit files
+//! - Unit names that need an explicit `.service` suffix
+//! - The unit written before the verb (`systemctl nginx restart`)
+//!
+//! `systemctl status` exiting with 3 only reports an inactive unit; none of
+//! these rules treat it as something to correct.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use crate::fuzzy::similarity;
+use crate::privilege;
+use crate::shell::PROBE_TIMEOUT;
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// systemctl verbs, used to spot a unit written where the verb belongs.
+const VERBS: &[&str] = &[
+    "start", "stop", "restart", "reload", "try-restart", "reload-or-restart",
+    "status", "show", "cat", "enable", "disable", "reenable", "mask", "unmask",
+    "is-active", "is-enabled", "is-failed", "kill", "edit", "daemon-reload",
+    "list-units", "list-unit-files", "list-timers", "list-sockets",
+    "reset-failed", "isolate", "preset", "link", "revert",
+];
+
+/// Unit type suffixes; names without one are assumed to be services.
+const UNIT_SUFFIXES: &[&str] = &[
+    ".service", ".socket", ".timer", ".target", ".mount", ".automount",
+    ".path", ".slice", ".scope", ".device", ".swap",
+];
+
+/// Minimum similarity for an installed unit to be suggested.
+const MIN_UNIT_SIMILARITY: f64 = 0.6;
+
+/// Maximum number of unit suggestions.
+const MAX_UNIT_SUGGESTIONS: usize = 3;
+
+/// Executables every systemd rule requires.
+pub const EXECUTABLES: &[&str] = &["systemctl", "journalctl"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("systemctl_sudo", 100),
+    ("journalctl_sudo", 100),
+    ("systemctl_unit_typo", 300),
+    ("systemctl_service_suffix", 500),
+    ("systemctl_swapped_args", 200),
+];
+
+/// Creates all systemd rules.
+///
+/// The unit typo rule lists installed units through the context's shell.
+pub fn systemd_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    vec![
+        // systemctl_sudo: Add sudo when changing unit state is denied
+        create_systemctl_sudo(),
+        // journalctl_sudo: Add sudo when the journal can't be read
+        create_journalctl_sudo(),
+        // systemctl_unit_typo: Fix a mistyped unit name
+        Box::new(SystemctlUnitTypoRule::new(context)),
+        // systemctl_service_suffix: Add .service to a unit that wasn't found
+        create_systemctl_service_suffix(),
+        // systemctl_swapped_args: Put the verb before the unit
+        create_systemctl_swapped_args(),
+    ]
+}
+
+/// Strips a known unit type suffix from `unit`.
+fn unit_stem(unit: &str) -> &str {
+    UNIT_SUFFIXES
+        .iter()
+        .find_map(|suffix| unit.strip_suffix(suffix))
+        .unwrap_or(unit)
+}
+
+/// systemctl_sudo: `systemctl restart nginx` → `sudo systemctl restart nginx`
+fn create_systemctl_sudo() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("systemctl_sudo")
+        .match_command_static(static_regex!(
+            r"^systemctl\s+(start|stop|restart|reload|try-restart|reload-or-restart|enable|disable|reenable|mask|unmask|kill|daemon-reload|isolate|reset-failed)\b",
+        ))
+        .match_output_static(static_regex!(r"Interactive authentication required|Access denied"))
+        .priority(100)
+        .describe("Run systemctl with sudo when changing a unit's state is denied")
+        .example(Example::new(
+            "systemctl restart nginx",
+            "Failed to restart nginx.service: Access denied",
+            1,
+            "sudo systemctl restart nginx",
+        ))
+        .replace_with(|original, _captures| {
+            vec![privilege::escalate(Script::parse(original)).to_string()]
+        })
+        .build_builtin()
+}
+
+/// journalctl_sudo: `journalctl -u nginx` → `sudo journalctl -u nginx`
+fn create_journalctl_sudo() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("journalctl_sudo")
+        .match_command_static(static_regex!(r"^journalctl\b"))
+        .match_output_static(static_regex!(r"No journal files were (opened|found) due to insufficient permissions"))
+        .priority(100)
+        .describe("Read the journal with sudo")
+        .example(Example::new(
+            "journalctl -u nginx",
+            "No journal files were opened due to insufficient permissions.",
+            1,
+            "sudo journalctl -u nginx",
+        ))
+        .replace_with(|original, _captures| {
+            vec![privilege::escalate(Script::parse(original)).to_string()]
+        })
+        .build_builtin()
+}
+
+/// systemctl_service_suffix: `systemctl status php8.1-fpm` →
+/// `systemctl status php8.1-fpm.service`
+fn create_systemctl_service_suffix() -> Box<dyn Rule> {
+    // The unit comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("systemctl_service_suffix")
+        .match_output_static(static_regex!(r"Unit (\S+) could not be found"))
+        .priority(500)
+        .describe("Add `.service` to a unit systemctl couldn't find")
+        .example(Example::new(
+            "systemctl status php8.1-fpm",
+            "Unit php8.1-fpm could not be found.",
+            4,
+            "systemctl status php8.1-fpm.service",
+        ))
+        .replace_with(|original, captures| {
+            let Some(unit) = captures.get(1).map(|m| m.as_str()) else {
+                return vec![];
+            };
+            if unit_stem(unit) != unit {
+                return vec![];
+            }
+
+            let script = Script::parse(original);
+            let typed = script.tokens().iter().any(|token| token == unit);
+            if script.executable() != Some("systemctl") || !typed {
+                return vec![];
+            }
+            vec![script.replace_token(unit, format!("{}.service", unit)).to_string()]
+        })
+        .build_builtin()
+}
+
+/// systemctl_swapped_args: `systemctl nginx restart` → `systemctl restart nginx`
+fn create_systemctl_swapped_args() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("systemctl_swapped_args")
+        .match_command_static(static_regex!(r"^systemctl\s"))
+        .match_output_static(static_regex!(r"Unknown (command verb|operation)"))
+        .priority(200)
+        .describe("Put the verb before the unit")
+        .example(Example::new("systemctl nginx restart", "Unknown command verb nginx.", 1, "systemctl restart nginx"))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let tokens = script.tokens();
+            let Some(first) = tokens.iter().skip(1).position(|token| !token.starts_with('-')) else {
+                return vec![];
+            };
+            let first = first + 1;
+            if VERBS.contains(&tokens[first].as_str()) {
+                return vec![];
+            }
+            let Some(verb) = tokens
+                .iter()
+                .skip(first + 1)
+                .position(|token| VERBS.contains(&token.as_str()))
+            else {
+                return vec![];
+            };
+
+            let mut tokens = tokens.to_vec();
+            let verb = tokens.remove(first + 1 + verb);
+            tokens.insert(first, verb);
+            vec![Script::from_tokens(tokens).to_string()]
+        })
+        .build_builtin()
+}
+
+/// systemctl_unit_typo: `systemctl restart ngnix` → `systemctl restart nginx`
+pub struct SystemctlUnitTypoRule {
+    context: Arc<RuleContext>,
+}
+
+impl SystemctlUnitTypoRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the missing unit from systemctl's error.
+    fn missing_unit(output: &str) -> Option<&str> {
+        let rest = output
+            .split("Unit ")
+            .skip(1)
+            .find(|rest| rest.contains(" not found") || rest.contains(" could not be found"))?;
+        rest.split_whitespace().next()
+    }
+
+    /// Lists installed unit files via `systemctl list-unit-files --no-pager`.
+    fn installed_units(&self) -> Vec<String> {
+        let Some(shell) = self.context.shell() else {
+            return Vec::new();
+        };
+        match shell.execute_with_timeout("systemctl list-unit-files --no-pager", PROBE_TIMEOUT) {
+            Ok(output) if output.success => output
+                .stdout
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_whitespace().next())
+                .filter(|unit| unit_stem(unit) != *unit)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Rule for SystemctlUnitTypoRule {
+    fn name(&self) -> &str {
+        "systemctl_unit_typo"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Fix a mistyped unit name from the installed unit files")
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        Script::parse(&command.script).executable() == Some("systemctl")
+            && Self::missing_unit(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(missing) = Self::missing_unit(&command.output) else {
+            return vec![];
+        };
+
+        // systemctl reports `ngnix.service` even when `ngnix` was typed
+        let script = Script::parse(&command.script);
+        let Some(typed) = script
+            .tokens()
+            .iter()
+            .skip(1)
+            .find(|token| token.as_str() == missing || unit_stem(missing) == token.as_str())
+        else {
+            return vec![];
+        };
+        let with_suffix = unit_stem(typed) != typed;
+
+        let mut units: Vec<(String, f64)> = self
+            .installed_units()
+            .into_iter()
+            .filter(|unit| unit != missing)
+            .map(|unit| {
+                let score = similarity(unit_stem(&unit), unit_stem(missing));
+                (unit, score)
+            })
+            .filter(|(_, score)| *score >= MIN_UNIT_SIMILARITY)
+            .collect();
+        units.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        units
+            .into_iter()
+            .take(MAX_UNIT_SUGGESTIONS)
+            .map(|(unit, _)| {
+                let replacement = match unit.strip_suffix(".service") {
+                    Some(stem) if !with_suffix => stem.to_string(),
+                    _ => unit,
+                };
+                script.clone().replace_token(typed, replacement).to_string()
+            })
+            .collect()
+    }
+
+    fn priority(&self) -> i32 {
+        300
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::permissions::permission_rules;
+    use crate::testing::MockShell;
+
+    const UNIT_FILES: &str = "UNIT FILE                              STATE           VENDOR PRESET\nnginx.service                          enabled         enabled\nnginx-debug.service                    disabled        enabled\npostgresql.service                     enabled         enabled\nsshd.service                           enabled         enabled\nlogrotate.timer                        enabled         enabled\n\n5 unit files listed.\n";
+
+    const STATUS_INACTIVE: &str = "\u{25cb} nginx.service - A high performance web server and a reverse proxy server\n     Loaded: loaded (/lib/systemd/system/nginx.service; disabled; vendor preset: enabled)\n     Active: inactive (dead)\n       Docs: man:nginx(8)\n";
+
+    fn context_with_units() -> Arc<RuleContext> {
+        let shell = MockShell::new().with_output("systemctl list-unit-files --no-pager", UNIT_FILES, 0);
+        Arc::new(RuleContext::new(Box::new(shell)))
+    }
+
+    #[test]
+    fn test_systemctl_sudo() {
+        let rule = create_systemctl_sudo();
+        let cmd = Command::new(
+            "systemctl restart nginx",
+            "Failed to restart nginx.service: Interactive authentication required.\nSee system logs and 'systemctl status nginx.service' for details.\n",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo systemctl restart nginx"]);
+    }
+
+    #[test]
+    fn test_systemctl_sudo_access_denied() {
+        let rule = create_systemctl_sudo();
+        let cmd = Command::new("systemctl stop sshd", "Failed to stop sshd.service: Access denied\n", 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo systemctl stop sshd"]);
+    }
+
+    #[test]
+    fn test_journalctl_sudo() {
+        let rule = create_journalctl_sudo();
+        let cmd = Command::new(
+            "journalctl -u nginx",
+            "No journal files were opened due to insufficient permissions.\n",
+            1,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sudo journalctl -u nginx"]);
+    }
+
+    #[test]
+    fn test_systemctl_unit_typo() {
+        let rule = SystemctlUnitTypoRule::new(context_with_units());
+        let cmd = Command::new(
+            "systemctl restart ngnix",
+            "Failed to restart ngnix.service: Unit ngnix.service not found.\n",
+            5,
+        );
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd)[0], "systemctl restart nginx");
+    }
+
+    #[test]
+    fn test_systemctl_unit_typo_keeps_suffix() {
+        let rule = SystemctlUnitTypoRule::new(context_with_units());
+        let cmd = Command::new(
+            "systemctl status postgresq.service",
+            "Unit postgresq.service could not be found.\n",
+            4,
+        );
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["systemctl status postgresql.service"]);
+    }
+
+    #[test]
+    fn test_systemctl_unit_typo_without_shell() {
+        let rule = SystemctlUnitTypoRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new(
+            "systemctl restart ngnix",
+            "Failed to restart ngnix.service: Unit ngnix.service not found.\n",
+            5,
+        );
+
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_systemctl_service_suffix() {
+        let rule = create_systemctl_service_suffix();
+        let cmd = Command::new("systemctl status php8.1-fpm", "Unit php8.1-fpm could not be found.\n", 4);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["systemctl status php8.1-fpm.service"]);
+
+        let cmd = Command::new("systemctl status web", "Unit web.service could not be found.\n", 4);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_systemctl_swapped_args() {
+        let rule = create_systemctl_swapped_args();
+        let cmd = Command::new("systemctl nginx restart", "Unknown command verb 'nginx'.\n", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["systemctl restart nginx"]);
+
+        let cmd = Command::new("systemctl --user redshift status", "Unknown command verb redshift.\n", 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["systemctl --user status redshift"]);
+    }
+
+    #[test]
+    fn test_systemctl_status_inactive_is_not_corrected() {
+        // Exit code 3 only means the unit isn't running
+        let cmd = Command::new("systemctl status nginx", STATUS_INACTIVE, 3);
+
+        let mut rules = systemd_rules(context_with_units());
+        rules.extend(permission_rules(context_with_units()));
+        for rule in rules {
+            assert!(!rule.matches(&cmd), "{} should not match", rule.name());
+        }
+    }
+
+    #[test]
+    fn test_systemd_rules_exist() {
+        let rules = systemd_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "systemctl_sudo",
+                "journalctl_sudo",
+                "systemctl_unit_typo",
+                "systemctl_service_suffix",
+                "systemctl_swapped_args",
+            ]
+        );
+    }
+}
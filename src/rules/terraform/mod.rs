@@ -0,0 +1,323 @@
+//! Terraform command correction rules.
+//!
+//! This module contains rules for common terraform mistakes:
+//! - Running commands before `terraform init`
+//! - Applying a saved plan that has gone stale
+//! - Selecting a workspace that doesn't exist
+//! - Provider versions that no longer match the lock file
+//!
+//! Most corrections chain a setup step in front of the original command.
+//! The rules are only relevant in directories with `*.tf` files.
+
+use super::{Example, RuleInfo, RuleMetadata};
+use super::util::{prefix_then_retry, relevant_to};
+use crate::fuzzy::similarity;
+use crate::shell::PROBE_TIMEOUT;
+use crate::{static_regex, Command, ProjectType, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::sync::Arc;
+
+/// Plan file used when the original command didn't name one.
+const DEFAULT_PLAN_FILE: &str = "tfplan";
+
+/// Minimum similarity for an existing workspace to be suggested.
+const MIN_WORKSPACE_SIMILARITY: f64 = 0.5;
+
+/// Executables every terraform rule requires.
+pub const EXECUTABLES: &[&str] = &["terraform"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("terraform_init", 300),
+    ("terraform_stale_plan", 300),
+    ("terraform_workspace", 400),
+    ("terraform_init_upgrade", 350),
+];
+
+/// Creates all terraform rules.
+///
+/// The workspace rule lists existing workspaces through the context's shell.
+pub fn terraform_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    let rules: Vec<Box<dyn Rule>> = vec![
+        // terraform_init: Initialize the backend or modules first
+        create_terraform_init(),
+        // terraform_stale_plan: Re-plan before applying
+        create_terraform_stale_plan(),
+        // terraform_workspace: Select a similar workspace or create it
+        Box::new(TerraformWorkspaceRule::new(context)),
+        // terraform_init_upgrade: Upgrade providers to match the constraints
+        create_terraform_init_upgrade(),
+    ];
+    relevant_to(rules, &[ProjectType::Terraform])
+}
+
+/// terraform_init: Run `terraform init` when the backend or modules are missing
+fn create_terraform_init() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("terraform_init")
+        .match_command_static(static_regex!(r"^terraform\s"))
+        .match_output_static(static_regex!(r"Error: (Backend initialization required|Module not installed)"))
+        .priority(300)
+        .describe("Run `terraform init` first when the backend or modules aren't installed")
+        .example(Example::new(
+            "terraform plan",
+            "Error: Backend initialization required, please run \"terraform init\"",
+            1,
+            "terraform init && terraform plan",
+        ))
+        .replace_with(|original, _captures| vec![prefix_then_retry("terraform init", original)])
+        .build_builtin()
+}
+
+/// terraform_stale_plan: Re-create the plan file, then apply it
+fn create_terraform_stale_plan() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("terraform_stale_plan")
+        .match_command_static(static_regex!(r"^terraform\s+apply\b"))
+        .match_output_static(static_regex!(r"Error: Saved plan is stale"))
+        .priority(300)
+        .describe("Re-create a stale plan file before applying it")
+        .example(Example::new(
+            "terraform apply tfplan",
+            "Error: Saved plan is stale",
+            1,
+            "terraform plan -out=tfplan && terraform apply tfplan",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let plan_file = script
+                .tokens()
+                .iter()
+                .skip(2)
+                .rfind(|token| !token.starts_with('-'))
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_PLAN_FILE);
+
+            let out = format!("-out={}", plan_file);
+            let plan = Script::from_tokens(["terraform", "plan", out.as_str()]);
+            let apply = Script::from_tokens(["terraform", "apply", plan_file]);
+            vec![plan.append(" && ", apply).to_string()]
+        })
+        .build_builtin()
+}
+
+/// terraform_init_upgrade: Allow newer provider versions than the lock file pins
+fn create_terraform_init_upgrade() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("terraform_init_upgrade")
+        .match_command_static(static_regex!(r"^terraform\s"))
+        .match_output_static(static_regex!(r"terraform init -upgrade|Error: Inconsistent dependency lock file"))
+        .priority(350)
+        .describe("Upgrade providers that no longer match the lock file")
+        .example(Example::new(
+            "terraform plan",
+            "Error: Inconsistent dependency lock file",
+            1,
+            "terraform init -upgrade && terraform plan",
+        ))
+        .replace_with(|original, _captures| {
+            if Script::parse(original).tokens().get(1).map(String::as_str) == Some("init") {
+                vec![Script::parse(original).insert_flag_after("init", "-upgrade").to_string()]
+            } else {
+                vec![prefix_then_retry("terraform init -upgrade", original)]
+            }
+        })
+        .build_builtin()
+}
+
+/// terraform_workspace: `terraform workspace select stagin` → an existing
+/// workspace with a similar name, or `terraform workspace new stagin`
+pub struct TerraformWorkspaceRule {
+    context: Arc<RuleContext>,
+}
+
+impl TerraformWorkspaceRule {
+    /// Creates the rule.
+    pub fn new(context: Arc<RuleContext>) -> Self {
+        Self { context }
+    }
+
+    /// Gets the missing workspace from terraform's error.
+    fn missing_workspace(output: &str) -> Option<&str> {
+        let rest = output.split("Workspace \"").nth(1)?;
+        let (name, rest) = rest.split_once('"')?;
+        rest.trim_start().starts_with("doesn't exist").then_some(name)
+    }
+
+    /// Lists existing workspaces via `terraform workspace list`.
+    fn existing_workspaces(&self) -> Vec<String> {
+        let Some(shell) = self.context.shell() else {
+            return Vec::new();
+        };
+        match shell.execute_with_timeout("terraform workspace list", PROBE_TIMEOUT) {
+            Ok(output) if output.success => output
+                .stdout
+                .lines()
+                .map(|line| line.trim_start_matches('*').trim())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Rule for TerraformWorkspaceRule {
+    fn name(&self) -> &str {
+        "terraform_workspace"
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+            .describe("Select a workspace with a similar name, or create the missing one")
+            .example(Example::new(
+                "terraform workspace select stagin",
+                "Workspace \"stagin\" doesn't exist.",
+                1,
+                "terraform workspace new stagin",
+            ))
+    }
+
+    fn matches(&self, command: &Command) -> bool {
+        command.script.trim_start().starts_with("terraform workspace")
+            && Self::missing_workspace(&command.output).is_some()
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        let Some(missing) = Self::missing_workspace(&command.output) else {
+            return vec![];
+        };
+
+        let mut existing: Vec<(String, f64)> = self
+            .existing_workspaces()
+            .into_iter()
+            .map(|name| {
+                let score = similarity(&name, missing);
+                (name, score)
+            })
+            .filter(|(_, score)| *score >= MIN_WORKSPACE_SIMILARITY)
+            .collect();
+        existing.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut corrections: Vec<String> = existing
+            .into_iter()
+            .map(|(name, _)| {
+                Script::from_tokens(["terraform", "workspace", "select", name.as_str()]).to_string()
+            })
+            .collect();
+        corrections.push(Script::from_tokens(["terraform", "workspace", "new", missing]).to_string());
+        corrections
+    }
+
+    fn priority(&self) -> i32 {
+        400
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+
+    const BACKEND_INIT: &str = "\u{2577}\n\u{2502} Error: Backend initialization required, please run \"terraform init\"\n\u{2502} \n\u{2502} Reason: Initial configuration of the requested backend \"s3\"\n\u{2575}\n";
+
+    const MODULE_NOT_INSTALLED: &str = "\u{2577}\n\u{2502} Error: Module not installed\n\u{2502} \n\u{2502}   on main.tf line 12:\n\u{2502}   12: module \"vpc\" {\n\u{2502} \n\u{2502} This module is not yet installed. Run \"terraform init\" to install all modules\n\u{2502} required by this configuration.\n\u{2575}\n";
+
+    const STALE_PLAN: &str = "\u{2577}\n\u{2502} Error: Saved plan is stale\n\u{2502} \n\u{2502} The given plan file can no longer be applied because the state was changed by\n\u{2502} another operation after the plan was created.\n\u{2575}\n";
+
+    const LOCKED_PROVIDER: &str = "\u{2577}\n\u{2502} Error: Failed to query available provider packages\n\u{2502} \n\u{2502} Could not retrieve the list of available versions for provider hashicorp/aws:\n\u{2502} locked provider registry.terraform.io/hashicorp/aws 4.67.0 does not match\n\u{2502} configured version constraint ~> 5.0; must use terraform init -upgrade to\n\u{2502} allow selection of new versions\n\u{2575}\n";
+
+    const MISSING_WORKSPACE: &str = "Workspace \"stagin\" doesn't exist.\n\nYou can create this workspace with the \"new\" subcommand \nor include the \"-or-create\" flag with the \"select\" subcommand.\n";
+
+    #[test]
+    fn test_terraform_init_backend() {
+        let rule = create_terraform_init();
+        let cmd = Command::new("terraform plan", BACKEND_INIT, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["terraform init && terraform plan"]);
+    }
+
+    #[test]
+    fn test_terraform_init_module() {
+        let rule = create_terraform_init();
+        let cmd = Command::new("terraform validate", MODULE_NOT_INSTALLED, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["terraform init && terraform validate"]);
+    }
+
+    #[test]
+    fn test_terraform_stale_plan() {
+        let rule = create_terraform_stale_plan();
+        let cmd = Command::new("terraform apply prod.tfplan", STALE_PLAN, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["terraform plan -out=prod.tfplan && terraform apply prod.tfplan"]
+        );
+    }
+
+    #[test]
+    fn test_terraform_stale_plan_default_file() {
+        let rule = create_terraform_stale_plan();
+        let cmd = Command::new("terraform apply -auto-approve", STALE_PLAN, 1);
+
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["terraform plan -out=tfplan && terraform apply tfplan"]
+        );
+    }
+
+    #[test]
+    fn test_terraform_init_upgrade() {
+        let rule = create_terraform_init_upgrade();
+        let cmd = Command::new("terraform plan", LOCKED_PROVIDER, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["terraform init -upgrade && terraform plan"]
+        );
+
+        let cmd = Command::new("terraform init", LOCKED_PROVIDER, 1);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["terraform init -upgrade"]);
+    }
+
+    #[test]
+    fn test_terraform_workspace_fuzzy_select() {
+        let shell = MockShell::new().with_output(
+            "terraform workspace list",
+            "  default\n* dev\n  staging\n",
+            0,
+        );
+        let rule = TerraformWorkspaceRule::new(Arc::new(RuleContext::new(Box::new(shell))));
+        let cmd = Command::new("terraform workspace select stagin", MISSING_WORKSPACE, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["terraform workspace select staging", "terraform workspace new stagin"]
+        );
+    }
+
+    #[test]
+    fn test_terraform_workspace_without_shell() {
+        let rule = TerraformWorkspaceRule::new(Arc::new(RuleContext::default()));
+        let cmd = Command::new("terraform workspace select stagin", MISSING_WORKSPACE, 1);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["terraform workspace new stagin"]);
+    }
+
+    #[test]
+    fn test_terraform_rules_do_not_match_other_errors() {
+        let cmd = Command::new("terraform plan", "Error: Invalid reference", 1);
+        for rule in terraform_rules(Arc::new(RuleContext::default())) {
+            assert!(!rule.matches(&cmd), "{} should not match", rule.name());
+        }
+    }
+
+    #[test]
+    fn test_terraform_rules_relevant_to_terraform_projects() {
+        for rule in terraform_rules(Arc::new(RuleContext::default())) {
+            assert_eq!(rule.relevant_projects(), &[ProjectType::Terraform], "{}", rule.name());
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! grep, sed and awk command correction rules.
+//!
+//! This module contains rules for common text utility mistakes:
+//! - grep on a directory without `-r`
+//! - grep with a pattern that starts with `-` and is taken for an option
+//! - `sed -i` without a backup suffix on BSD sed (macOS)
+//! - sed `s` commands whose pattern or replacement contains `/`
+//! - awk programs left unquoted, which the shell splits into several words
+//!
+//! The sed rewrite changes only the delimiters of the `s` command, in the
+//! text as it was typed, so its quoting (and whatever expands inside double
+//! quotes) is kept.
+
+use super::{Example, RuleInfo};
+use crate::{static_regex, RegexRuleBuilder, Rule, Script};
+
+/// Characters tried, in order, as the new delimiter of a sed `s` command.
+const SED_DELIMITERS: &[char] = &['|', '#', ':', ',', '@', '%'];
+
+/// Characters the flags of a sed `s` command are made of.
+const SED_FLAGS: &str = "gpiImMe0123456789";
+
+/// Maximum number of ways of splitting a sed `s` command suggested.
+const MAX_SED_SPLITS: usize = 3;
+
+/// Executables every text utility rule requires.
+pub const EXECUTABLES: &[&str] = &["grep", "egrep", "fgrep", "sed", "gsed", "awk", "gawk", "mawk", "nawk"];
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("grep_recursive", 300),
+    ("grep_dash_pattern", 400),
+    ("sed_in_place_bsd", 300),
+    ("sed_delimiter", 300),
+    ("awk_quote_program", 300),
+];
+
+/// Creates all grep, sed and awk rules.
+pub fn textutils_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        // grep_recursive: Search the directory recursively
+        create_grep_recursive(),
+        // grep_dash_pattern: Pass a pattern starting with - after -e or --
+        create_grep_dash_pattern(),
+        // sed_in_place_bsd: Give BSD sed -i an empty backup suffix
+        create_sed_in_place_bsd(),
+        // sed_delimiter: Use another delimiter when the s command contains /
+        create_sed_delimiter(),
+        // awk_quote_program: Quote the awk program in single quotes
+        create_awk_quote_program(),
+    ]
+}
+
+/// grep_recursive: `grep TODO src` → `grep -r TODO src`
+fn create_grep_recursive() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("grep_recursive")
+        .match_command_static(static_regex!(r"^[ef]?grep\s"))
+        .match_output_static(static_regex!(r"grep: .+: Is a directory"))
+        .priority(300)
+        .describe("Search a directory recursively with grep")
+        .example(Example::new("grep TODO src", "grep: src: Is a directory", 2, "grep -r TODO src"))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let Some(executable) = script.executable().map(str::to_string) else {
+                return vec![];
+            };
+            vec![script.insert_flag_after(&executable, "-r").to_string()]
+        })
+        .build_builtin()
+}
+
+/// grep_dash_pattern: `grep --verbose app.log` → `grep -e --verbose app.log`
+/// or `grep -- --verbose app.log`
+fn create_grep_dash_pattern() -> Box<dyn Rule> {
+    // The rejected option comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("grep_dash_pattern")
+        .match_output_static(static_regex!(r"(?:invalid option -- '?([^'\s])'?|unrecognized option [`']([^'\s]+)')"))
+        .priority(400)
+        .describe("Pass a grep pattern that starts with - after -e")
+        .example(Example::new(
+            "grep --verbose app.log",
+            "grep: unrecognized option '--verbose'\nUsage: grep [OPTION]... PATTERNS [FILE]...",
+            2,
+            "grep -e --verbose app.log",
+        ))
+        .replace_with(|original, captures| {
+            let script = Script::parse(original);
+            if !script.executable().is_some_and(|executable| executable.ends_with("grep")) {
+                return vec![];
+            }
+            let tokens = script.tokens();
+            let options = tokens.iter().enumerate().skip(1).take_while(|(_, token)| *token != "--");
+            let pattern = match (captures.get(1), captures.get(2)) {
+                (_, Some(long)) => options
+                    .filter(|(_, token)| token.starts_with("--"))
+                    .find(|(_, token)| token.split('=').next() == Some(long.as_str())),
+                (Some(short), _) => options
+                    .filter(|(_, token)| token.starts_with('-') && !token.starts_with("--"))
+                    .find(|(_, token)| token.contains(short.as_str())),
+                _ => None,
+            };
+            let Some((index, _)) = pattern else {
+                return vec![];
+            };
+
+            let mut corrections = vec![script.clone().insert_script_at(index, Script::from_tokens(["-e"])).to_string()];
+            // -- would turn the options after the pattern into file names
+            if !tokens[index + 1..].iter().any(|token| token.starts_with('-') && token != "-") {
+                corrections.push(script.insert_script_at(index, Script::from_tokens(["--"])).to_string());
+            }
+            corrections
+        })
+        .build_builtin()
+}
+
+/// sed_in_place_bsd: `sed -i 's/a/b/' notes.txt` → `sed -i '' 's/a/b/' notes.txt`
+fn create_sed_in_place_bsd() -> Box<dyn Rule> {
+    // BSD sed takes the script for the backup suffix and the file for the script
+    RegexRuleBuilder::new("sed_in_place_bsd")
+        .match_command_static(static_regex!(r"^g?sed\s(?:.*\s)?-i\s"))
+        .match_output_static(static_regex!(r"invalid command code"))
+        .priority(300)
+        .describe("Give BSD sed -i an empty backup suffix")
+        .example(Example::new(
+            "sed -i 's/foo/bar/' notes.txt",
+            "sed: 1: \"notes.txt\": invalid command code n",
+            1,
+            "sed -i '' 's/foo/bar/' notes.txt",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let Some(index) = script.tokens().iter().position(|token| token == "-i") else {
+                return vec![];
+            };
+            if script.tokens().get(index + 1).is_some_and(String::is_empty) {
+                return vec![];
+            }
+            vec![script.insert_script_at(index + 1, Script::from_tokens([""])).to_string()]
+        })
+        .build_builtin()
+}
+
+/// sed_delimiter: `sed 's/usr/local/opt/' paths.txt` →
+/// `sed 's|usr|local/opt|' paths.txt` or `sed 's|usr/local|opt|' paths.txt`
+fn create_sed_delimiter() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("sed_delimiter")
+        .match_command_static(static_regex!(r"^g?sed\s"))
+        .match_output_static(static_regex!(
+            r"unknown option to `s'|unterminated `s' command|bad flag in substitute command|unterminated substitute"
+        ))
+        .priority(300)
+        .describe("Use another delimiter for a sed s command that contains /")
+        .example(Example::new(
+            "sed 's/usr/local/opt/' paths.txt",
+            "sed: -e expression #1, char 16: unknown option to `s'",
+            1,
+            "sed 's|usr|local/opt|' paths.txt",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let Some((index, splits)) = script
+                .tokens()
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(index, token)| (index, sed_splits(token)))
+                .find(|(_, splits)| !splits.is_empty())
+            else {
+                return vec![];
+            };
+            splits
+                .iter()
+                .filter_map(|split| rewrite_sed_token(&script, index, split))
+                .map(|rewritten| script.clone().replace_tokens(index..index + 1, rewritten).to_string())
+                .collect()
+        })
+        .build_builtin()
+}
+
+/// awk_quote_program: `awk {print $1} access.log` → `awk '{print $1}' access.log`
+fn create_awk_quote_program() -> Box<dyn Rule> {
+    RegexRuleBuilder::new("awk_quote_program")
+        .match_command_static(static_regex!(r"^[gmn]?awk\s"))
+        .match_output_static(static_regex!(r"unexpected newline or end of string|missing \}|syntax error"))
+        .priority(300)
+        .describe("Quote an awk program in single quotes")
+        .example(Example::new(
+            "awk {print $1} access.log",
+            "awk: cmd. line:1: {print\nawk: cmd. line:1:       ^ unexpected newline or end of string",
+            2,
+            "awk '{print $1}' access.log",
+        ))
+        .replace_with(|original, _captures| {
+            let script = Script::parse(original);
+            let tokens = script.tokens();
+            // The program is split where its words were, its braces unbalanced until the last one
+            let Some(start) = tokens.iter().skip(1).position(|token| brace_depth(token) > 0).map(|i| i + 1) else {
+                return vec![];
+            };
+            let mut depth = 0;
+            let Some(end) = tokens[start..].iter().position(|token| {
+                depth += brace_depth(token);
+                depth <= 0
+            }) else {
+                return vec![];
+            };
+            let program = tokens[start..=start + end].join(" ");
+            vec![script.clone().replace_tokens(start..start + end + 1, Script::from_tokens([program])).to_string()]
+        })
+        .build_builtin()
+}
+
+/// Gets how many more braces `token` opens than it closes.
+fn brace_depth(token: &str) -> i32 {
+    token
+        .chars()
+        .map(|c| match c {
+            '{' => 1,
+            '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// One way of splitting a sed `s` command: the char indexes of its three
+/// delimiters. The last is the length of the command when the delimiter
+/// closing it is missing.
+type SedSplit = [usize; 3];
+
+/// Gets the ways a `s/…/…/` command whose pattern or replacement contains
+/// unescaped slashes could be split into the two, most balanced first. A
+/// command with no extra slashes gives none.
+fn sed_splits(expression: &str) -> Vec<SedSplit> {
+    let Some(body) = expression.strip_prefix("s/") else {
+        return vec![];
+    };
+    let mut slashes = vec![1];
+    let mut escaped = false;
+    for (i, c) in body.chars().enumerate() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '/' => slashes.push(i + 2),
+            _ => {}
+        }
+    }
+
+    // Whatever follows the last slash is either the flags or the end of the replacement
+    let length = expression.chars().count();
+    let flags: String = expression.chars().skip(slashes[slashes.len() - 1] + 1).collect();
+    let (middle, close) = if flags.chars().all(|c| SED_FLAGS.contains(c)) {
+        (&slashes[1..slashes.len() - 1], slashes[slashes.len() - 1])
+    } else {
+        (&slashes[1..], length)
+    };
+    if middle.len() < 2 {
+        return vec![];
+    }
+
+    let mut splits: Vec<(usize, SedSplit)> = middle
+        .iter()
+        .enumerate()
+        .map(|(i, &split)| ((2 * i).abs_diff(middle.len() - 1), [1, split, close]))
+        .collect();
+    splits.sort_by_key(|(imbalance, _)| *imbalance);
+    splits.into_iter().take(MAX_SED_SPLITS).map(|(_, split)| split).collect()
+}
+
+/// Rewrites the token at `index`, a sed `s` command, to be split at `split`
+/// with another delimiter. The token is edited as it was written when it was
+/// parsed from the script, so its quoting is kept.
+fn rewrite_sed_token(script: &Script, index: usize, split: &SedSplit) -> Option<Script> {
+    let token = script.tokens().get(index)?;
+    let sources = script.raw_token(index).and_then(|word| char_sources(word, token));
+
+    // `|` is a pipe outside quotes
+    let unquoted = sources.as_ref().is_some_and(|sources| {
+        split.iter().any(|&at| sources.get(at).or(sources.last()).is_some_and(|(_, quoted)| !quoted))
+    });
+    let delimiter = SED_DELIMITERS
+        .iter()
+        .copied()
+        .filter(|&delimiter| !(unquoted && delimiter == '|'))
+        .find(|&delimiter| !token.contains(delimiter))?;
+
+    let Some((word, sources)) = script.raw_token(index).zip(sources) else {
+        return Some(Script::from_tokens([set_delimiters(token, split, delimiter)]));
+    };
+    // A missing closing delimiter goes after the last character, inside any quotes
+    let end = sources.last().map_or(0, |&(offset, _)| offset + word[offset..].chars().next().map_or(0, char::len_utf8));
+    let mut rewritten = word.to_string();
+    for &at in split.iter().rev() {
+        let range = match sources.get(at) {
+            Some(&(offset, _)) => offset..offset + 1,
+            None => end..end,
+        };
+        rewritten.replace_range(range, delimiter.encode_utf8(&mut [0; 4]));
+    }
+    Some(Script::parse(&rewritten))
+}
+
+/// Sets the characters of `expression` at `split` to `delimiter`, adding
+/// the closing one if it's missing.
+fn set_delimiters(expression: &str, split: &SedSplit, delimiter: char) -> String {
+    let mut rewritten: String = expression
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if split.contains(&i) { delimiter } else { c })
+        .collect();
+    if split[2] == expression.chars().count() {
+        rewritten.push(delimiter);
+    }
+    rewritten
+}
+
+/// Gets where each character of `token` comes from in `word`, the text it
+/// was parsed from: its byte offset, and whether it was quoted or escaped.
+/// `None` if the word doesn't spell the token that way.
+fn char_sources(word: &str, token: &str) -> Option<Vec<(usize, bool)>> {
+    let mut sources = Vec::new();
+    let mut quote = None;
+    let mut chars = word.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\\') => sources.push((chars.next()?.0, true)),
+            (None, '\'' | '"') => quote = Some(c),
+            (None, _) => sources.push((offset, false)),
+            (Some(open), _) if c == open => quote = None,
+            (Some('"'), '\\') if chars.peek().is_some_and(|(_, next)| "$`\"\\\n".contains(*next)) => {
+                sources.push((chars.next()?.0, true));
+            }
+            (Some(_), _) => sources.push((offset, true)),
+        }
+    }
+
+    let spelled: String = sources.iter().filter_map(|(offset, _)| word[*offset..].chars().next()).collect();
+    (quote.is_none() && spelled == token).then_some(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Command;
+
+    const SED_UNKNOWN_OPTION: &str = "sed: -e expression #1, char 16: unknown option to `s'";
+
+    const SED_UNTERMINATED: &str = "sed: -e expression #1, char 14: unterminated `s' command";
+
+    const AWK_UNBALANCED: &str =
+        "awk: cmd. line:1: {print\nawk: cmd. line:1:       ^ unexpected newline or end of string";
+
+    fn sed_fixes(script: &str) -> Vec<String> {
+        create_sed_delimiter().get_new_commands(&Command::new(script, SED_UNKNOWN_OPTION, 1))
+    }
+
+    #[test]
+    fn test_grep_recursive() {
+        let rule = create_grep_recursive();
+        let cmd = Command::new("grep -n TODO src", "grep: src: Is a directory", 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["grep -r -n TODO src"]);
+        assert!(!rule.matches(&Command::new("rm src", "rm: cannot remove 'src': Is a directory", 1)));
+    }
+
+    #[test]
+    fn test_grep_dash_pattern() {
+        let rule = create_grep_dash_pattern();
+        let cmd = Command::new("grep '-[0-9]' data.txt", "grep: invalid option -- '['\nUsage: grep [OPTION]...", 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["grep -e '-[0-9]' data.txt", "grep -- '-[0-9]' data.txt"]);
+
+        // BSD grep quotes with a backtick; -- would make -n a file name
+        let cmd = Command::new("grep --verbose -n app.log", "grep: unrecognized option `--verbose'", 2);
+        assert_eq!(rule.get_new_commands(&cmd), vec!["grep -e --verbose -n app.log"]);
+    }
+
+    #[test]
+    fn test_grep_dash_pattern_needs_grep() {
+        let rule = create_grep_dash_pattern();
+        let cmd = Command::new("ls --verbose", "ls: unrecognized option '--verbose'", 2);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_sed_in_place_bsd() {
+        let rule = create_sed_in_place_bsd();
+        let cmd = Command::new("sed -i -E \"s/a+/b/\" notes.txt", "sed: 1: \"notes.txt\": invalid command code n", 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["sed -i '' -E \"s/a+/b/\" notes.txt"]);
+        let cmd = Command::new("sed -i.bak 's/a/b/' notes.txt", "sed: 1: \"notes.txt\": invalid command code n", 1);
+        assert!(!rule.matches(&cmd));
+    }
+
+    #[test]
+    fn test_sed_splits() {
+        // s/usr/local/opt/ has its delimiters at 1 and 15, and slashes at 5 and 11 in between
+        assert_eq!(sed_splits("s/usr/local/opt/"), [[1, 5, 15], [1, 11, 15]]);
+        assert_eq!(sed_splits("s/a/b/c/d/g"), [[1, 5, 9], [1, 3, 9], [1, 7, 9]]);
+        // Without its closing delimiter the rest is the replacement
+        assert_eq!(sed_splits("s/a/b/c"), [[1, 3, 7], [1, 5, 7]]);
+        assert!(sed_splits("s/a/b/").is_empty());
+        assert!(sed_splits("s/a\\/b/c/").is_empty());
+        assert!(sed_splits("y/abc/xyz/").is_empty());
+    }
+
+    #[test]
+    fn test_sed_delimiter() {
+        let rule = create_sed_delimiter();
+        let cmd = Command::new("sed 's/usr/local/opt/' paths.txt", SED_UNKNOWN_OPTION, 1);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["sed 's|usr|local/opt|' paths.txt", "sed 's|usr/local|opt|' paths.txt"]
+        );
+        let cmd = Command::new("sed -e 's/a/b/c/d/g' -n f", SED_UNTERMINATED, 1);
+        assert_eq!(
+            rule.get_new_commands(&cmd),
+            vec!["sed -e 's|a/b|c/d|g' -n f", "sed -e 's|a|b/c/d|g' -n f", "sed -e 's|a/b/c|d|g' -n f"]
+        );
+    }
+
+    #[test]
+    fn test_sed_delimiter_keeps_quoting() {
+        // Double quotes keep expanding, escapes stay where they were
+        assert_eq!(sed_fixes("sed \"s/$OLD/new/dir/g\" f")[0], "sed \"s|$OLD|new/dir|g\" f");
+        assert_eq!(sed_fixes(r#"sed "s/\"a\"/b/c/" f"#)[0], r#"sed "s|\"a\"|b/c|" f"#);
+        assert_eq!(sed_fixes(r"sed 's/a\/b/c/d/' f"), [r"sed 's|a\/b|c/d|' f", r"sed 's|a\/b/c|d|' f"]);
+        // Quoting that changes partway through the command
+        assert_eq!(sed_fixes(r##"sed s/a/'b c'/"$d"/ f"##)[0], r##"sed s#a#'b c'/"$d"# f"##);
+        // Unquoted, so | would be a pipe
+        assert_eq!(sed_fixes("sed s/a/b/c/ f")[0], "sed s#a#b/c# f");
+        // A missing closing delimiter goes inside the quotes
+        let cmd = Command::new("sed 's/a/b/c' f", SED_UNTERMINATED, 1);
+        assert_eq!(create_sed_delimiter().get_new_commands(&cmd)[0], "sed 's|a|b/c|' f");
+    }
+
+    #[test]
+    fn test_sed_delimiter_avoids_characters_in_the_command() {
+        assert_eq!(sed_fixes("sed 's/a|b/c/d/' f")[0], "sed 's#a|b#c/d#' f");
+        // Unbalanced quotes leave nothing to rewrite
+        assert!(sed_fixes("sed 's/a/b/c/ f").is_empty());
+    }
+
+    #[test]
+    fn test_char_sources() {
+        assert_eq!(char_sources("a'b'", "ab"), Some(vec![(0, false), (2, true)]));
+        assert_eq!(char_sources(r#""\$x\y""#, r"$x\y"), Some(vec![(2, true), (3, true), (4, true), (5, true)]));
+        assert_eq!(char_sources(r"\é", "é"), Some(vec![(1, true)]));
+        assert_eq!(char_sources("'ab", "ab"), None);
+        assert_eq!(char_sources("ab", "ba"), None);
+    }
+
+    #[test]
+    fn test_awk_quote_program() {
+        let rule = create_awk_quote_program();
+        let cmd = Command::new("awk -F: {print $1, $3} /etc/passwd", AWK_UNBALANCED, 2);
+
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["awk -F: '{print $1, $3}' /etc/passwd"]);
+
+        let cmd = Command::new("awk 'NR>1{print $1}' f", "awk: line 1: syntax error at or near }", 2);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+        let cmd = Command::new("awk {print $1 f", AWK_UNBALANCED, 2);
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+}
@@ -0,0 +1,441 @@
+//! Helpers shared by rule modules.
+//!
+//! Many CLIs already print their own guess after a typo ("Did you mean this?",
+//! "Maybe you meant:", "The most similar command is"). Lifting that list into
+//! corrections is more accurate than fuzzy-matching against a dictionary we
+//! would have to keep in sync with the tool.
+//!
+//! Cloud CLIs also fail in the same few ways (expired login, unset project),
+//! where the fix is a setup step chained before the original command.
+//!
+//! Translation rules (wget → curl, docker → podman, ...) are only useful when
+//! the replacement is installed; [`requires_binary`] filters them by `PATH`.
+//! Groups tied to one toolchain declare it with [`relevant_to`].
+//!
+//! Rules that fix user names match them against the local accounts, read
+//! from the passwd file by [`passwd_users`].
+
+use super::adapter::{adapt_all, Adapt, Adapted};
+use crate::privilege;
+use crate::{Command, ProjectType, Rule, RuleContext, Script, SideEffect};
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Where the local user accounts are listed.
+pub const PASSWD_PATH: &str = "/etc/passwd";
+
+/// Parses the indented suggestion list a CLI prints after the line containing
+/// `header`.
+///
+/// Blank lines directly after the header are skipped, and bullet markers
+/// (`* `, `- `) are stripped. The list ends at the first blank or unindented
+/// line.
+pub fn listed_suggestions<'a>(output: &'a str, header: &str) -> Vec<&'a str> {
+    output
+        .lines()
+        .skip_while(|line| !line.contains(header))
+        .skip(1)
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| line.starts_with('\t') || line.starts_with("  "))
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix("* ")
+                .or_else(|| line.strip_prefix("- "))
+                .unwrap_or(line)
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Builds one correction per suggestion by replacing the `typo` token in
+/// `script`.
+///
+/// Some CLIs suggest a full command path (`gcloud compute instances`); only
+/// its last word replaces the typo.
+pub fn replace_with_suggestions<'a, I>(script: &str, typo: &str, suggestions: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let script = Script::parse(script);
+    if !script.tokens().iter().any(|token| token == typo) {
+        return Vec::new();
+    }
+
+    suggestions
+        .into_iter()
+        .filter_map(|suggestion| suggestion.split_whitespace().last())
+        .map(|replacement| script.clone().replace_token(typo, replacement).to_string())
+        .collect()
+}
+
+/// Lifts the command a CLI tells the user to run, e.g. `gh auth login` from
+/// "please run:  gh auth login".
+///
+/// The command is the rest of the line containing `marker`, or the next
+/// non-blank line if nothing follows it. A command in backticks ends at the
+/// closing backtick.
+pub fn command_after<'a>(output: &'a str, marker: &str) -> Option<&'a str> {
+    let mut lines = output.lines().skip_while(|line| !line.contains(marker));
+    let rest = lines.next()?.split_once(marker)?.1.trim();
+    let rest = if rest.is_empty() {
+        lines.map(str::trim).find(|line| !line.is_empty())?
+    } else {
+        rest
+    };
+
+    let command = match rest.strip_prefix('`') {
+        Some(quoted) => quoted.split('`').next()?,
+        None => rest.trim_end_matches('.'),
+    };
+    Some(command.trim()).filter(|command| !command.is_empty())
+}
+
+/// Chains `prefix` before `original`, e.g. a login step before a retry.
+///
+/// Both are re-rendered from their tokens, so arguments the user quoted stay
+/// quoted.
+pub fn prefix_then_retry(prefix: &str, original: &str) -> String {
+    Script::parse(prefix)
+        .append(" && ", Script::parse(original))
+        .to_string()
+}
+
+/// Wraps `rule` so it only suggests corrections whose executable is
+/// installed.
+///
+/// Lookups go through the context's shell and are cached there. Without a
+/// shell there is nothing to check against, so every correction is kept.
+pub fn requires_binary(rule: Box<dyn Rule>, context: Arc<RuleContext>) -> Box<dyn Rule> {
+    Box::new(Adapted::wrap(rule, RequiresBinary { context }))
+}
+
+/// Adapter behind [`requires_binary`]: drops corrections whose executable
+/// isn't installed.
+struct RequiresBinary {
+    context: Arc<RuleContext>,
+}
+
+impl Adapt for RequiresBinary {
+    fn get_new_commands<'a>(&'a self, inner: &'a dyn Rule, command: &'a Command) -> Vec<Cow<'a, str>> {
+        inner
+            .get_new_commands_cow(command)
+            .into_iter()
+            .filter(|correction| {
+                let script = Script::parse(correction);
+                script
+                    .executable()
+                    .and_then(|binary| self.context.command_exists(binary))
+                    .unwrap_or(true)
+            })
+            .collect()
+    }
+}
+
+/// Marks every rule in a group as only relevant to `projects`.
+///
+/// Elsewhere their corrections are ranked lower by the corrector.
+pub fn relevant_to(rules: Vec<Box<dyn Rule>>, projects: &'static [ProjectType]) -> Vec<Box<dyn Rule>> {
+    adapt_all(rules, RelevantTo(projects))
+}
+
+/// Adapter behind [`relevant_to`].
+#[derive(Clone)]
+struct RelevantTo(&'static [ProjectType]);
+
+impl Adapt for RelevantTo {
+    fn relevant_projects<'a>(&'a self, _inner: &'a dyn Rule) -> &'a [ProjectType] {
+        self.0
+    }
+}
+
+/// Marks every rule in a group as installing something before retrying, so
+/// their corrections refresh the shell's command cache afterwards.
+///
+/// Corrections that already have a side effect keep it.
+pub fn rehash_after_install(rules: Vec<Box<dyn Rule>>) -> Vec<Box<dyn Rule>> {
+    adapt_all(rules, RehashAfterInstall)
+}
+
+/// Adapter behind [`rehash_after_install`].
+#[derive(Clone)]
+struct RehashAfterInstall;
+
+impl Adapt for RehashAfterInstall {
+    fn side_effect_for(&self, inner: &dyn Rule, command: &Command, correction: &str) -> Option<SideEffect> {
+        inner
+            .side_effect_for(command, correction)
+            .or(Some(SideEffect::Rehash))
+    }
+}
+
+/// Makes every rule in a group use the system's privilege escalation tool
+/// instead of the `sudo` its corrections are written with.
+///
+/// `configured` forces a tool; otherwise it's looked up through the context's
+/// shell on first use.
+pub fn escalate_with(
+    rules: Vec<Box<dyn Rule>>,
+    context: Arc<RuleContext>,
+    configured: Option<String>,
+) -> Vec<Box<dyn Rule>> {
+    adapt_all(rules, EscalateWith { context, configured })
+}
+
+/// Adapter behind [`escalate_with`].
+#[derive(Clone)]
+struct EscalateWith {
+    context: Arc<RuleContext>,
+    configured: Option<String>,
+}
+
+impl Adapt for EscalateWith {
+    fn get_new_commands<'a>(&'a self, inner: &'a dyn Rule, command: &'a Command) -> Vec<Cow<'a, str>> {
+        let corrections = inner.get_new_commands_cow(command);
+        if !corrections.iter().any(|correction| correction.contains(privilege::DEFAULT_TOOL)) {
+            return corrections;
+        }
+
+        let tool = match &self.configured {
+            Some(tool) => tool.clone(),
+            None => self.context.escalation_command().to_string(),
+        };
+        corrections
+            .iter()
+            .map(|correction| Cow::Owned(privilege::use_tool(correction, &tool)))
+            .collect()
+    }
+}
+
+/// Gets the user names in the contents of a passwd file, in file order.
+///
+/// Comments, blank lines and NIS `+`/`-` entries are skipped.
+pub fn parse_passwd(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(['#', '+', '-']))
+        .filter_map(|line| line.split(':').next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Gets the user names in the passwd file at `path`, usually
+/// [`PASSWD_PATH`], or nothing if it can't be read.
+///
+/// The file is read once per context. Accounts only known to a directory
+/// service (LDAP, SSSD) aren't listed.
+pub fn passwd_users(context: &RuleContext, path: &Path) -> Arc<Vec<String>> {
+    context.cached(&format!("passwd_users:{}", path.display()), || {
+        std::fs::read_to_string(path).map(|contents| parse_passwd(&contents)).unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+    use crate::{RegexRuleBuilder, SimpleRuleBuilder};
+
+    fn python3_rule() -> Box<dyn Rule> {
+        SimpleRuleBuilder::new("python_python3")
+            .match_command("python ")
+            .replace("python ", "python3 ")
+    }
+
+    #[test]
+    fn test_listed_suggestions_tab_indented() {
+        let output = "error: unknown command \"gte\" for \"kubectl\"\n\nDid you mean this?\n\tget\n\tset\n";
+        assert_eq!(listed_suggestions(output, "Did you mean this?"), vec!["get", "set"]);
+    }
+
+    #[test]
+    fn test_listed_suggestions_bullets_after_blank_line() {
+        let output = "Invalid choice: 'ec3', maybe you meant:\n\n  * ec2\n  * ecs\n";
+        assert_eq!(listed_suggestions(output, "maybe you meant:"), vec!["ec2", "ecs"]);
+    }
+
+    #[test]
+    fn test_listed_suggestions_stop_at_blank_line() {
+        let output = "Maybe you meant:\n  gcloud compute\n\nTo search the help text of gcloud commands, run:\n  gcloud help -- SEARCH_TERMS\n";
+        assert_eq!(listed_suggestions(output, "Maybe you meant:"), vec!["gcloud compute"]);
+    }
+
+    #[test]
+    fn test_listed_suggestions_missing_header() {
+        assert!(listed_suggestions("command not found", "Did you mean this?").is_empty());
+    }
+
+    #[test]
+    fn test_replace_with_suggestions_uses_last_word() {
+        let corrections = replace_with_suggestions(
+            "gcloud compute instnces list",
+            "instnces",
+            ["gcloud compute instances", "gcloud compute instance-groups"],
+        );
+        assert_eq!(
+            corrections,
+            vec![
+                "gcloud compute instances list",
+                "gcloud compute instance-groups list",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_after_same_line() {
+        let output = "To get started with GitHub CLI, please run:  gh auth login\nAlternatively, populate the GH_TOKEN environment variable.\n";
+        assert_eq!(command_after(output, "please run:"), Some("gh auth login"));
+    }
+
+    #[test]
+    fn test_command_after_next_line_and_backticks() {
+        let output = "Interactive authentication is needed. Please run:\naz login\n";
+        assert_eq!(command_after(output, "Please run:"), Some("az login"));
+
+        let output = "Error: No existing credentials found. Please run `vercel login` or pass \"--token\"\n";
+        assert_eq!(command_after(output, "Please run"), Some("vercel login"));
+    }
+
+    #[test]
+    fn test_command_after_missing() {
+        assert_eq!(command_after("permission denied", "please run:"), None);
+        assert_eq!(command_after("please run:\n\n", "please run:"), None);
+    }
+
+    #[test]
+    fn test_prefix_then_retry() {
+        assert_eq!(prefix_then_retry("az login", "az vm list"), "az login && az vm list");
+        assert_eq!(
+            prefix_then_retry("heroku login", "heroku run 'rake db:migrate' -a web"),
+            "heroku login && heroku run 'rake db:migrate' -a web"
+        );
+    }
+
+    #[test]
+    fn test_replace_with_suggestions_typo_not_in_script() {
+        assert!(replace_with_suggestions("git status", "comit", ["commit"]).is_empty());
+    }
+
+    #[test]
+    fn test_requires_binary_present() {
+        let shell = MockShell::new().with_output("python3 --version", "Python 3.12.3", 0);
+        let rule = requires_binary(python3_rule(), Arc::new(RuleContext::new(Box::new(shell))));
+        let cmd = Command::new("python manage.py runserver", "bash: python: command not found", 127);
+
+        assert_eq!(rule.name(), "python_python3");
+        assert!(rule.matches(&cmd));
+        assert_eq!(rule.get_new_commands(&cmd), vec!["python3 manage.py runserver"]);
+    }
+
+    #[test]
+    fn test_requires_binary_absent() {
+        let rule = requires_binary(python3_rule(), Arc::new(RuleContext::new(Box::new(MockShell::new()))));
+        let cmd = Command::new("python manage.py runserver", "bash: python: command not found", 127);
+
+        assert!(rule.matches(&cmd));
+        assert!(rule.get_new_commands(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_requires_binary_without_shell_keeps_corrections() {
+        let rule = requires_binary(python3_rule(), Arc::new(RuleContext::default()));
+        let cmd = Command::new("python manage.py runserver", "bash: python: command not found", 127);
+
+        assert_eq!(rule.get_new_commands(&cmd), vec!["python3 manage.py runserver"]);
+    }
+
+    #[test]
+    fn test_relevant_to_only_changes_projects() {
+        let rules = relevant_to(vec![python3_rule()], &[ProjectType::Python]);
+        let cmd = Command::new("python manage.py runserver", "bash: python: command not found", 127);
+
+        assert_eq!(rules[0].name(), "python_python3");
+        assert_eq!(rules[0].relevant_projects(), &[ProjectType::Python]);
+        assert_eq!(rules[0].get_new_commands(&cmd), vec!["python3 manage.py runserver"]);
+        assert_eq!(python3_rule().relevant_projects(), ProjectType::ALL);
+    }
+
+    #[test]
+    fn test_rehash_after_install_keeps_existing_effect() {
+        let cmd = Command::new("python manage.py runserver", "bash: python: command not found", 127);
+
+        let rules = rehash_after_install(vec![python3_rule()]);
+        let corrections = rules[0].get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].side_effect, Some(SideEffect::Rehash));
+
+        let touch = RegexRuleBuilder::new("touch_parent_dirs")
+            .match_command_regex(r"^touch (\S+)")
+            .unwrap()
+            .replace_with(|original, _captures| vec![original.to_string()])
+            .side_effect(|_original, captures| Some(SideEffect::CreateParentDirs(captures[1].into())))
+            .build()
+            .unwrap();
+        let rules = rehash_after_install(vec![touch]);
+        let corrections = rules[0].get_corrected_commands(&Command::new("touch a/b", "", 1));
+        assert_eq!(corrections[0].side_effect, Some(SideEffect::CreateParentDirs("a/b".into())));
+    }
+
+    fn sudo_rule() -> Box<dyn Rule> {
+        SimpleRuleBuilder::new("sudo_permission_denied")
+            .match_output("Permission denied")
+            .prepend("sudo")
+    }
+
+    #[test]
+    fn test_escalate_with_detected_tool() {
+        let cmd = Command::new("apt install vim", "E: Permission denied", 100);
+        let with_tools = |tools: &[&str]| {
+            let shell = tools
+                .iter()
+                .fold(MockShell::new(), |shell, tool| shell.with_output(&format!("{} --version", tool), "", 0));
+            let rules = escalate_with(vec![sudo_rule()], Arc::new(RuleContext::new(Box::new(shell))), None);
+            rules[0].get_new_commands(&cmd)
+        };
+
+        assert_eq!(with_tools(&["sudo", "doas"]), vec!["sudo apt install vim"]);
+        assert_eq!(with_tools(&["doas"]), vec!["doas apt install vim"]);
+        assert_eq!(with_tools(&["run0"]), vec!["run0 apt install vim"]);
+        assert_eq!(with_tools(&[]), vec!["sudo apt install vim"]);
+    }
+
+    #[test]
+    fn test_escalate_with_configured_tool() {
+        let shell = MockShell::new().with_output("sudo --version", "", 0);
+        let context = Arc::new(RuleContext::new(Box::new(shell)));
+        let rules = escalate_with(vec![sudo_rule()], context, Some("doas".to_string()));
+        let cmd = Command::new("cat /etc/shadow", "cat: /etc/shadow: Permission denied", 1);
+
+        assert_eq!(rules[0].get_new_commands(&cmd), vec!["doas cat /etc/shadow"]);
+    }
+
+    fn passwd_fixture() -> &'static Path {
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/passwd/passwd"))
+    }
+
+    #[test]
+    fn test_parse_passwd_fixture() {
+        let contents = std::fs::read_to_string(passwd_fixture()).unwrap();
+        assert_eq!(parse_passwd(&contents), vec!["root", "daemon", "www-data", "postgres", "deploy", "alice"]);
+    }
+
+    #[test]
+    fn test_parse_passwd_skips_malformed_lines() {
+        assert_eq!(parse_passwd("\n:x:0:0::/:/bin/sh\nbob\n+@admins\n"), vec!["bob"]);
+    }
+
+    #[test]
+    fn test_passwd_users_are_read_once() {
+        let context = RuleContext::default();
+        let users = passwd_users(&context, passwd_fixture());
+        assert!(users.contains(&"deploy".to_string()));
+        assert!(Arc::ptr_eq(&users, &passwd_users(&context, passwd_fixture())));
+    }
+
+    #[test]
+    fn test_passwd_users_missing_file() {
+        let context = RuleContext::default();
+        assert!(passwd_users(&context, Path::new("/nonexistent/passwd")).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! Language runtime version-manager rules.
+//!
+//! This module contains rules for projects pinned to a runtime version that
+//! isn't installed yet, fixed by installing it and retrying:
+//! - Node versions managed by nvm
+//! - Python versions managed by pyenv
+//! - Rust toolchains managed by rustup
+//! - SDKs (Java, Gradle, ...) managed by SDKMAN!
+//!
+//! The install step is only suggested when the manager is available: pyenv
+//! and rustup are binaries looked up through [`requires_binary`]. nvm and sdk
+//! are shell functions no child process can find, so they are detected by
+//! the script that defines them, `$NVM_DIR/nvm.sh` and
+//! `$SDKMAN_DIR/bin/sdkman-init.sh`. The corrections refresh the shell's
+//! command cache through [`rehash_after_install`], so newly installed
+//! binaries are found.
+
+use super::{Example, RuleInfo};
+use super::adapter::{Adapt, Adapted};
+use super::util::{prefix_then_retry, rehash_after_install, requires_binary};
+use crate::{static_regex, Command, RegexRuleBuilder, Rule, RuleContext, Script};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Rule names and base priorities, for listing the rules without building them.
+pub const RULES: &[RuleInfo] = &[
+    ("nvm_install", 200),
+    ("pyenv_install", 200),
+    ("rustup_toolchain_install", 200),
+    ("sdkman_install", 200),
+];
+
+/// Creates all version-manager rules.
+///
+/// The managers are looked up through the context's shell.
+pub fn version_manager_rules(context: Arc<RuleContext>) -> Vec<Box<dyn Rule>> {
+    rehash_after_install(vec![
+        // nvm_install: Install the required Node version first
+        requires_shell_function(create_nvm_install(), ShellFunction::NVM, Arc::clone(&context)),
+        // pyenv_install: Install the pinned Python version first
+        requires_binary(create_pyenv_install(), Arc::clone(&context)),
+        // rustup_toolchain_install: Install the pinned toolchain first
+        requires_binary(create_rustup_toolchain_install(), Arc::clone(&context)),
+        // sdkman_install: Install the pinned SDK version first
+        requires_shell_function(create_sdkman_install(), ShellFunction::SDKMAN, context),
+    ])
+}
+
+/// A manager that's a shell function, and where the script defining it lives.
+struct ShellFunction {
+    /// Variable naming the manager's directory
+    dir_var: &'static str,
+    /// The directory under `$HOME` when the variable is unset
+    default_dir: &'static str,
+    /// The script defining the function, relative to the directory
+    init_script: &'static str,
+}
+
+impl ShellFunction {
+    const NVM: Self = Self {
+        dir_var: "NVM_DIR",
+        default_dir: ".nvm",
+        init_script: "nvm.sh",
+    };
+
+    const SDKMAN: Self = Self {
+        dir_var: "SDKMAN_DIR",
+        default_dir: ".sdkman",
+        init_script: "bin/sdkman-init.sh",
+    };
+}
+
+/// Wraps `rule` so it only suggests corrections when `function`'s init
+/// script exists. Without a shell there is nothing to check against, so every
+/// correction is kept.
+fn requires_shell_function(rule: Box<dyn Rule>, function: ShellFunction, context: Arc<RuleContext>) -> Box<dyn Rule> {
+    Box::new(Adapted::wrap(rule, RequiresShellFunction { function, context }))
+}
+
+/// Adapter behind [`requires_shell_function`].
+struct RequiresShellFunction {
+    function: ShellFunction,
+    context: Arc<RuleContext>,
+}
+
+impl RequiresShellFunction {
+    /// Gets whether the init script exists, or `None` without a shell or home.
+    fn installed(&self) -> Option<bool> {
+        let shell = self.context.shell()?;
+        let dir = match shell.env(self.function.dir_var) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(shell.env("HOME")?).join(self.function.default_dir),
+        };
+        Some(dir.join(self.function.init_script).is_file())
+    }
+}
+
+impl Adapt for RequiresShellFunction {
+    fn get_new_commands<'a>(&'a self, inner: &'a dyn Rule, command: &'a Command) -> Vec<Cow<'a, str>> {
+        if self.installed() == Some(false) {
+            return Vec::new();
+        }
+        inner.get_new_commands_cow(command)
+    }
+}
+
+/// nvm_install: `npm ci` → `nvm install 20 && npm ci`
+fn create_nvm_install() -> Box<dyn Rule> {
+    // The version comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("nvm_install")
+        .match_output_static(static_regex!(
+            r#"(?i:requires node(?:\.js)?(?: version)?)\s+v?(\d+(?:\.\d+){0,2})|version "v?(\d+(?:\.\d+){0,2})(?: -> N/A)?" is not yet installed"#,
+        ))
+        .priority(200)
+        .describe("Install the Node version a project requires with nvm, then retry")
+        .example(Example::new(
+            "npm ci",
+            "error: This project requires node v20. You are running v18.19.0.",
+            1,
+            "nvm install 20 && npm ci",
+        ))
+        .replace_with(|original, captures| {
+            let Some(version) = captures.iter().skip(1).flatten().next() else {
+                return vec![];
+            };
+            let install = Script::from_tokens(["nvm", "install", version.as_str()]);
+            vec![prefix_then_retry(&install.to_string(), original)]
+        })
+        .build_builtin()
+}
+
+/// pyenv_install: `python manage.py test` → `pyenv install 3.12.1 && python manage.py test`
+fn create_pyenv_install() -> Box<dyn Rule> {
+    // The version comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("pyenv_install")
+        .match_output_static(static_regex!(r#"pyenv: version [`'"]v?([\w.-]+)['"`] is not installed"#))
+        .priority(200)
+        .describe("Install the Python version pinned for pyenv, then retry")
+        .example(Example::new(
+            "python -m pytest",
+            "pyenv: version `3.12.1' is not installed (set by .python-version)",
+            1,
+            "pyenv install 3.12.1 && python -m pytest",
+        ))
+        .replace_with(|original, captures| {
+            let Some(version) = captures.get(1) else {
+                return vec![];
+            };
+            let install = Script::from_tokens(["pyenv", "install", version.as_str()]);
+            vec![prefix_then_retry(&install.to_string(), original)]
+        })
+        .build_builtin()
+}
+
+/// rustup_toolchain_install: `cargo build` →
+/// `rustup toolchain install nightly-2024-01-01 && cargo build`
+fn create_rustup_toolchain_install() -> Box<dyn Rule> {
+    // The toolchain comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("rustup_toolchain_install")
+        .match_output_static(static_regex!(r"toolchain '([\w.-]+)' is not installed"))
+        .priority(200)
+        .describe("Install the Rust toolchain a project pins with rustup, then retry")
+        .example(Example::new(
+            "cargo build",
+            "error: toolchain 'nightly-2024-01-01' is not installed",
+            1,
+            "rustup toolchain install nightly-2024-01-01 && cargo build",
+        ))
+        .replace_with(|original, captures| {
+            let Some(toolchain) = captures.get(1) else {
+                return vec![];
+            };
+            let install = Script::from_tokens(["rustup", "toolchain", "install", toolchain.as_str()]);
+            vec![prefix_then_retry(&install.to_string(), original)]
+        })
+        .build_builtin()
+}
+
+/// sdkman_install: `sdk use java 21.0.1-tem` →
+/// `sdk install java 21.0.1-tem && sdk use java 21.0.1-tem`
+fn create_sdkman_install() -> Box<dyn Rule> {
+    // The SDK comes from the output, so only the output pattern is set
+    RegexRuleBuilder::new("sdkman_install")
+        .match_output_static(static_regex!(r"Stop! (\w+) ([\w.-]+) is not installed"))
+        .priority(200)
+        .describe("Install the SDK version SDKMAN! says is missing, then retry")
+        .example(Example::new(
+            "sdk use java 21.0.1-tem",
+            "Stop! java 21.0.1-tem is not installed.",
+            1,
+            "sdk install java 21.0.1-tem && sdk use java 21.0.1-tem",
+        ))
+        .replace_with(|original, captures| {
+            let (Some(candidate), Some(version)) = (captures.get(1), captures.get(2)) else {
+                return vec![];
+            };
+            let install = Script::from_tokens(["sdk", "install", candidate.as_str(), version.as_str()]);
+            vec![prefix_then_retry(&install.to_string(), original)]
+        })
+        .build_builtin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockShell;
+    use crate::Command;
+
+    const NODE_ENGINE: &str = "error: This project requires node v20. You are running v18.19.0.\n";
+
+    const NVM_NOT_INSTALLED: &str = "N/A: version \"v20.11.0 -> N/A\" is not yet installed.\n\nYou need to run \"nvm install 20.11.0\" to install it before using it.\n";
+
+    const NVM_ALIAS_NOT_INSTALLED: &str = "N/A: version \"20 -> N/A\" is not yet installed.\n\nYou need to run \"nvm install 20\" to install it before using it.\n";
+
+    const PYENV_NOT_INSTALLED: &str = "pyenv: version `3.12.1' is not installed (set by /home/dev/src/app/.python-version)\n";
+
+    const RUSTUP_NOT_INSTALLED: &str = "error: toolchain 'nightly-2024-01-01-x86_64-unknown-linux-gnu' is not installed\n";
+
+    const SDKMAN_NOT_INSTALLED: &str = "\nStop! java 21.0.1-tem is not installed.\n";
+
+    fn rules_with(manager: &str) -> Vec<Box<dyn Rule>> {
+        let shell = MockShell::new().with_output(&format!("{} --version", manager), "", 0);
+        version_manager_rules(Arc::new(RuleContext::new(Box::new(shell))))
+    }
+
+    /// Gets the rules for a shell whose `$HOME` contains `init_script`.
+    fn rules_with_home(name: &str, init_script: &str) -> (Vec<Box<dyn Rule>>, PathBuf) {
+        let home = std::env::temp_dir().join(format!("ftf_version_managers_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        let script = home.join(init_script);
+        std::fs::create_dir_all(script.parent().unwrap()).unwrap();
+        std::fs::write(&script, "").unwrap();
+
+        let shell = MockShell::new().with_env("HOME", home.to_str().unwrap());
+        (version_manager_rules(Arc::new(RuleContext::new(Box::new(shell)))), home)
+    }
+
+    fn corrections(rules: &[Box<dyn Rule>], cmd: &Command) -> Vec<String> {
+        rules
+            .iter()
+            .filter(|rule| rule.matches(cmd))
+            .flat_map(|rule| rule.get_new_commands(cmd))
+            .collect()
+    }
+
+    #[test]
+    fn test_nvm_install_major_version() {
+        let (rules, home) = rules_with_home("nvm_major", ".nvm/nvm.sh");
+        let cmd = Command::new("npm ci", NODE_ENGINE, 1);
+        assert_eq!(corrections(&rules, &cmd), vec!["nvm install 20 && npm ci"]);
+
+        let cmd = Command::new("nvm use 20", NVM_ALIAS_NOT_INSTALLED, 3);
+        assert_eq!(corrections(&rules, &cmd), vec!["nvm install 20 && nvm use 20"]);
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_nvm_install_full_version() {
+        let (rules, home) = rules_with_home("nvm_full", ".nvm/nvm.sh");
+        let cmd = Command::new("nvm use", NVM_NOT_INSTALLED, 3);
+        assert_eq!(corrections(&rules, &cmd), vec!["nvm install 20.11.0 && nvm use"]);
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_nvm_found_through_nvm_dir() {
+        let (_, dir) = rules_with_home("nvm_dir", "nvm.sh");
+        let shell = MockShell::new()
+            .with_env("HOME", "/nonexistent")
+            .with_env("NVM_DIR", dir.to_str().unwrap());
+        let rules = version_manager_rules(Arc::new(RuleContext::new(Box::new(shell))));
+
+        let cmd = Command::new("npm ci", NODE_ENGINE, 1);
+        assert_eq!(corrections(&rules, &cmd), vec!["nvm install 20 && npm ci"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shell_function_managers_missing() {
+        // `command -v` can't see shell functions, so a binary lookup doesn't count
+        let shell = MockShell::new()
+            .with_env("HOME", "/nonexistent")
+            .with_output("nvm --version", "", 0)
+            .with_output("sdk --version", "", 0);
+        let rules = version_manager_rules(Arc::new(RuleContext::new(Box::new(shell))));
+
+        assert!(corrections(&rules, &Command::new("npm ci", NODE_ENGINE, 1)).is_empty());
+        assert!(corrections(&rules, &Command::new("sdk use java 21.0.1-tem", SDKMAN_NOT_INSTALLED, 1)).is_empty());
+    }
+
+    #[test]
+    fn test_pyenv_install() {
+        let cmd = Command::new("python -m pytest", PYENV_NOT_INSTALLED, 1);
+        assert_eq!(
+            corrections(&rules_with("pyenv"), &cmd),
+            vec!["pyenv install 3.12.1 && python -m pytest"]
+        );
+    }
+
+    #[test]
+    fn test_rustup_toolchain_install() {
+        let cmd = Command::new("cargo +nightly-2024-01-01 build", RUSTUP_NOT_INSTALLED, 1);
+        assert_eq!(
+            corrections(&rules_with("rustup"), &cmd),
+            vec!["rustup toolchain install nightly-2024-01-01-x86_64-unknown-linux-gnu && cargo +nightly-2024-01-01 build"]
+        );
+    }
+
+    #[test]
+    fn test_sdkman_install() {
+        let (rules, home) = rules_with_home("sdkman", ".sdkman/bin/sdkman-init.sh");
+        let cmd = Command::new("sdk use java 21.0.1-tem", SDKMAN_NOT_INSTALLED, 1);
+        assert_eq!(
+            corrections(&rules, &cmd),
+            vec!["sdk install java 21.0.1-tem && sdk use java 21.0.1-tem"]
+        );
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_version_manager_corrections_rehash() {
+        let cmd = Command::new("python -m pytest", PYENV_NOT_INSTALLED, 1);
+        let rule = rules_with("pyenv").remove(1);
+
+        let corrections = rule.get_corrected_commands(&cmd);
+        assert_eq!(corrections[0].side_effect, Some(crate::SideEffect::Rehash));
+    }
+
+    #[test]
+    fn test_version_managers_require_manager() {
+        let cmd = Command::new("python -m pytest", PYENV_NOT_INSTALLED, 1);
+        assert!(corrections(&rules_with("rustup"), &cmd).is_empty());
+    }
+
+    #[test]
+    fn test_version_manager_rules_exist() {
+        let rules = version_manager_rules(Arc::new(RuleContext::default()));
+        let names: Vec<_> = rules.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["nvm_install", "pyenv_install", "rustup_toolchain_install", "sdkman_install"]
+        );
+    }
+}
@@ -0,0 +1,522 @@
+//! Typed shell script manipulation.
+//!
+//! Rules that build corrections with raw string concatenation or `str::replace`
+//! easily produce double spaces, rewrite substrings inside arguments, or break
+//! quoting. `Script` works on the shlex-parsed token list instead. Parsed
+//! tokens render as they were written, so `*.o` still globs and `'$HOME'`
+//! stays literal; tokens a rule adds or replaces are quoted unless they only
+//! contain characters the shell never interprets.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+/// Characters a token can consist of without being quoted.
+const SAFE_CHARS: &str = "_@%+=:,./-";
+
+/// A quote a script opens and never closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnterminatedQuote {
+    /// The quote character: `'`, `"` or `` ` ``
+    pub quote: char,
+    /// Byte offset of the opening quote in the script
+    pub position: usize,
+}
+
+/// A shell script represented as a list of parsed tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Script {
+    tokens: Vec<String>,
+    /// Source text of each token, for tokens parsed from a script
+    raw: Vec<Option<String>>,
+}
+
+impl Script {
+    /// Parses a script into tokens using POSIX shell quoting rules.
+    ///
+    /// Falls back to whitespace splitting when the script has unbalanced quotes.
+    pub fn parse(script: &str) -> Self {
+        let Some(tokens) = shlex::split(script) else {
+            let tokens: Vec<String> = script.split_whitespace().map(str::to_string).collect();
+            let raw = tokens.iter().cloned().map(Some).collect();
+            return Self { tokens, raw };
+        };
+
+        // Comments and the like make words and tokens disagree; such scripts
+        // are rendered from their tokens alone
+        let words = Self::split_words(script);
+        let raw = if words.len() == tokens.len()
+            && words.iter().zip(&tokens).all(|(word, token)| {
+                shlex::split(word).is_some_and(|parsed| parsed.len() == 1 && parsed[0] == *token)
+            }) {
+            words.into_iter().map(|word| Some(word.to_string())).collect()
+        } else {
+            vec![None; tokens.len()]
+        };
+        Self { tokens, raw }
+    }
+
+    /// Creates a script from already-split tokens.
+    pub fn from_tokens<I, S>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let tokens: Vec<String> = tokens.into_iter().map(Into::into).collect();
+        let raw = vec![None; tokens.len()];
+        Self { tokens, raw }
+    }
+
+    /// Splits a script at unquoted whitespace, keeping each word's quotes.
+    fn split_words(script: &str) -> Vec<&str> {
+        let mut words = Vec::new();
+        let mut quote = None;
+        let mut escaped = false;
+        let mut start = None;
+
+        for (i, c) in script.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' && quote != Some('\'') {
+                escaped = true;
+            } else if let Some(open) = quote {
+                if c == open {
+                    quote = None;
+                }
+            } else if c == '\'' || c == '"' {
+                quote = Some(c);
+            } else if c.is_whitespace() {
+                if let Some(word_start) = start.take() {
+                    words.push(&script[word_start..i]);
+                }
+                continue;
+            }
+            start.get_or_insert(i);
+        }
+        if let Some(word_start) = start {
+            words.push(&script[word_start..]);
+        }
+        words
+    }
+
+    /// Finds the quote `script` leaves open, which the shell reports as an
+    /// unexpected end of file, or `None` if its quotes are balanced.
+    ///
+    /// This follows the same rules as [`Script::parse`]: backslashes escape
+    /// outside single quotes (and inside `$'...'`), and quotes in a comment
+    /// don't count. Backticks count as quotes too.
+    pub fn unterminated_quote(script: &str) -> Option<UnterminatedQuote> {
+        // The open quote, where it opened, and whether backslashes escape in it
+        let mut open: Option<(char, usize, bool)> = None;
+        let mut escaped = false;
+        let mut previous: Option<char> = None;
+        let mut chars = script.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            if escaped {
+                escaped = false;
+            } else if let Some((quote, _, escapes)) = open {
+                if c == '\\' && escapes {
+                    escaped = true;
+                } else if c == quote {
+                    open = None;
+                }
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '#' && previous.is_none_or(|p| p.is_whitespace() || ";&|(".contains(p)) {
+                // A comment runs to the end of the line
+                previous = chars.by_ref().map(|(_, c)| c).find(|c| *c == '\n');
+                continue;
+            } else if matches!(c, '\'' | '"' | '`') {
+                let escapes = c != '\'' || previous == Some('$');
+                open = Some((c, i, escapes));
+            }
+            previous = Some(c);
+        }
+        open.map(|(quote, position, _)| UnterminatedQuote { quote, position })
+    }
+
+    /// Gets the parsed tokens.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// Gets the first token (the executable), if any.
+    pub fn executable(&self) -> Option<&str> {
+        self.tokens.first().map(String::as_str)
+    }
+
+    /// Gets the token at `index` as it was written, quotes included. Tokens a
+    /// rule added, and those of scripts rendered from their tokens alone,
+    /// have none.
+    pub fn raw_token(&self, index: usize) -> Option<&str> {
+        self.raw.get(index)?.as_deref()
+    }
+
+    /// Returns true if the script has no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Inserts a token at the start of the script (e.g. `sudo`).
+    pub fn prepend_token(mut self, token: impl Into<String>) -> Self {
+        self.tokens.insert(0, token.into());
+        self.raw.insert(0, None);
+        self
+    }
+
+    /// Replaces the token at `index`. Out-of-range indices leave the script unchanged.
+    pub fn replace_token_at(mut self, index: usize, token: impl Into<String>) -> Self {
+        if let Some(slot) = self.tokens.get_mut(index) {
+            *slot = token.into();
+            self.raw[index] = None;
+        }
+        self
+    }
+
+    /// Replaces the first token equal to `old`. The script is left unchanged if
+    /// no token matches.
+    pub fn replace_token(self, old: &str, new: impl Into<String>) -> Self {
+        match self.tokens.iter().position(|t| t == old) {
+            Some(index) => self.replace_token_at(index, new),
+            None => self,
+        }
+    }
+
+    /// Appends a token to the end of the script (e.g. a missing flag or value).
+    pub fn push_token(mut self, token: impl Into<String>) -> Self {
+        self.tokens.push(token.into());
+        self.raw.push(None);
+        self
+    }
+
+    /// Inserts `flag` directly after the first token equal to `executable`.
+    ///
+    /// `executable` may also be a subcommand such as `push` in `git push`.
+    /// The script is left unchanged if the token is missing or the flag is
+    /// already present.
+    pub fn insert_flag_after(mut self, executable: &str, flag: impl Into<String>) -> Self {
+        let flag = flag.into();
+        if self.tokens.contains(&flag) {
+            return self;
+        }
+        if let Some(index) = self.tokens.iter().position(|t| t == executable) {
+            self.tokens.insert(index + 1, flag);
+            self.raw.insert(index + 1, None);
+        }
+        self
+    }
+
+    /// Inserts the tokens of `other` before the token at `index`, or at the
+    /// end if `index` is out of range. Tokens parsed from `other` keep their
+    /// quoting, so `~/.ssh/id_ed25519` still expands.
+    pub fn insert_script_at(mut self, index: usize, other: Script) -> Self {
+        let index = index.min(self.tokens.len());
+        self.tokens.splice(index..index, other.tokens);
+        self.raw.splice(index..index, other.raw);
+        self
+    }
+
+    /// Replaces the tokens in `range` with those of `other`, which keep their
+    /// quoting as with [`Script::insert_script_at`]. The range is clamped to
+    /// the script.
+    pub fn replace_tokens(mut self, range: Range<usize>, other: Script) -> Self {
+        let end = range.end.min(self.tokens.len());
+        let start = range.start.min(end);
+        self.tokens.splice(start..end, other.tokens);
+        self.raw.splice(start..end, other.raw);
+        self
+    }
+
+    /// Rebuilds the script from the tokens at `order`, in that order. The
+    /// tokens keep their quoting; out-of-range indices are skipped.
+    pub fn reordered(&self, order: &[usize]) -> Self {
+        let order = order.iter().filter(|&&index| index < self.tokens.len());
+        let (tokens, raw) = order.map(|&index| (self.tokens[index].clone(), self.raw[index].clone())).unzip();
+        Self { tokens, raw }
+    }
+
+    /// Chains another script after this one using a shell operator such as `" && "`.
+    pub fn append(mut self, separator: &str, other: Script) -> Self {
+        let separator = separator.trim();
+        if !separator.is_empty() {
+            self.tokens.push(separator.to_string());
+            self.raw.push(None);
+        }
+        self.tokens.extend(other.tokens);
+        self.raw.extend(other.raw);
+        self
+    }
+
+    /// Splits a script into the commands of its `&&` chain, keeping each
+    /// command's original text.
+    ///
+    /// `&&` inside quotes or after a backslash doesn't split, so
+    /// `echo 'a && b' && ls` is two commands.
+    pub fn split_chain(script: &str) -> Vec<&str> {
+        let bytes = script.as_bytes();
+        let mut segments = Vec::new();
+        let mut quote = None;
+        let mut escaped = false;
+        let mut start = 0;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let c = bytes[i];
+            if escaped {
+                escaped = false;
+            } else if c == b'\\' && quote != Some(b'\'') {
+                escaped = true;
+            } else if let Some(open) = quote {
+                if c == open {
+                    quote = None;
+                }
+            } else if c == b'\'' || c == b'"' {
+                quote = Some(c);
+            } else if c == b'&' && bytes.get(i + 1) == Some(&b'&') {
+                segments.push(script[start..i].trim());
+                i += 2;
+                start = i;
+                continue;
+            }
+            i += 1;
+        }
+        segments.push(script[start..].trim());
+
+        segments.retain(|segment| !segment.is_empty());
+        segments
+    }
+
+    /// Returns true for shell operators such as `&&`, `|` or `2>&1`, which
+    /// must be rendered as-is.
+    pub(crate) fn is_operator(token: &str) -> bool {
+        token.chars().any(|c| "&|;<>".contains(c))
+            && token.chars().all(|c| "&|;<>".contains(c) || c.is_ascii_digit())
+    }
+
+    /// Returns true for variable assignments such as `RUST_LOG=debug`.
+    pub(crate) fn is_assignment(token: &str) -> bool {
+        token.split_once('=').is_some_and(|(name, _)| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+    }
+
+    /// Quotes a token for rendering unless it only contains characters the
+    /// shell never interprets. Operators are rendered as-is.
+    ///
+    /// This also quotes `$`, `~` and glob characters, so a token a rule adds
+    /// can't expand or run anything.
+    fn quote_token(token: &str) -> Cow<'_, str> {
+        let safe = !token.is_empty()
+            && (Self::is_operator(token)
+                || token.chars().all(|c| c.is_ascii_alphanumeric() || SAFE_CHARS.contains(c)));
+        if safe {
+            Cow::Borrowed(token)
+        } else {
+            shlex::try_quote(token).unwrap_or(Cow::Borrowed(token))
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (token, raw)) in self.tokens.iter().zip(&self.raw).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match raw {
+                Some(raw) => f.write_str(raw)?,
+                None => f.write_str(&Self::quote_token(token))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for Script {
+    fn from(script: &str) -> Self {
+        Self::parse(script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quoted_argument() {
+        let script = Script::parse("cp \"my dir\" /backup");
+        assert_eq!(script.tokens(), &["cp", "my dir", "/backup"]);
+        assert_eq!(script.executable(), Some("cp"));
+    }
+
+    #[test]
+    fn test_parse_unbalanced_quotes_fallback() {
+        let script = Script::parse("echo \"unterminated");
+        assert_eq!(script.tokens(), &["echo", "\"unterminated"]);
+    }
+
+    #[test]
+    fn test_unterminated_quote() {
+        let quote = |script| Script::unterminated_quote(script).map(|open| (open.quote, open.position));
+        assert_eq!(quote("echo \"unterminated"), Some(('"', 5)));
+        assert_eq!(quote("echo it's done"), Some(('\'', 7)));
+        assert_eq!(quote("echo 'a \"b' \"c"), Some(('"', 12)));
+        assert_eq!(quote("ls `pwd"), Some(('`', 3)));
+        assert_eq!(quote("echo \"a \\\" b"), Some(('"', 5)));
+    }
+
+    #[test]
+    fn test_balanced_quotes() {
+        for script in [
+            "echo \"a 'b' c\"",
+            "echo 'a \\' b",
+            "echo it\\'s",
+            "echo $'it\\'s'",
+            "echo \"a `pwd` b\"",
+            "ls # don't",
+            "echo a#'b'",
+            "echo \"\\\"\"",
+        ] {
+            assert_eq!(Script::unterminated_quote(script), None, "{}", script);
+        }
+        assert!(Script::unterminated_quote("echo a#b'c").is_some());
+    }
+
+    #[test]
+    fn test_prepend_token() {
+        let script = Script::parse("apt update").prepend_token("sudo");
+        assert_eq!(script.to_string(), "sudo apt update");
+    }
+
+    #[test]
+    fn test_replace_token_at() {
+        let script = Script::parse("git branch -d feature").replace_token_at(2, "-D");
+        assert_eq!(script.to_string(), "git branch -D feature");
+
+        let unchanged = Script::parse("ls").replace_token_at(5, "-la");
+        assert_eq!(unchanged.to_string(), "ls");
+    }
+
+    #[test]
+    fn test_replace_token() {
+        let script = Script::parse("kubectl get podz web").replace_token("podz", "pods");
+        assert_eq!(script.to_string(), "kubectl get pods web");
+
+        let script = Script::parse("kubectl get pods").replace_token("svc", "services");
+        assert_eq!(script.to_string(), "kubectl get pods");
+    }
+
+    #[test]
+    fn test_push_token() {
+        let script = Script::parse("aws s3 ls").push_token("--region").push_token("eu-west-1");
+        assert_eq!(script.to_string(), "aws s3 ls --region eu-west-1");
+    }
+
+    #[test]
+    fn test_insert_flag_preserves_spaces_round_trip() {
+        let script = Script::parse("cp \"my dir\" /backup").insert_flag_after("cp", "-r");
+        let rendered = script.to_string();
+        assert_eq!(rendered, "cp -r \"my dir\" /backup");
+
+        let reparsed = Script::parse(&rendered);
+        assert_eq!(reparsed.tokens(), &["cp", "-r", "my dir", "/backup"]);
+    }
+
+    #[test]
+    fn test_insert_flag_does_not_touch_arguments() {
+        let script = Script::parse("rm harm.txt").insert_flag_after("rm", "-r");
+        assert_eq!(script.to_string(), "rm -r harm.txt");
+    }
+
+    #[test]
+    fn test_insert_flag_missing_executable_or_duplicate() {
+        let missing = Script::parse("ls dir").insert_flag_after("rm", "-r");
+        assert_eq!(missing.to_string(), "ls dir");
+
+        let duplicate = Script::parse("rm -r dir").insert_flag_after("rm", "-r");
+        assert_eq!(duplicate.to_string(), "rm -r dir");
+    }
+
+    #[test]
+    fn test_append() {
+        let script = Script::parse("mkdir -p backup")
+            .append(" && ", Script::parse("mv 'a file' backup/"));
+        assert_eq!(script.to_string(), "mkdir -p backup && mv 'a file' backup/");
+    }
+
+    #[test]
+    fn test_control_characters_are_quoted() {
+        let script = Script::parse("curl 'https://example.com/search?q=a&page=2'");
+        assert_eq!(script.to_string(), "curl 'https://example.com/search?q=a&page=2'");
+
+        let script = Script::parse("make build 2>&1 && ./run.sh | tee log");
+        assert_eq!(script.to_string(), "make build 2>&1 && ./run.sh | tee log");
+    }
+
+    #[test]
+    fn test_split_chain() {
+        assert_eq!(
+            Script::split_chain("git stash && git pull --rebase && git stash pop"),
+            vec!["git stash", "git pull --rebase", "git stash pop"]
+        );
+        assert_eq!(Script::split_chain("make 2>&1 | tee log"), vec!["make 2>&1 | tee log"]);
+        assert_eq!(Script::split_chain(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_split_chain_respects_quotes() {
+        assert_eq!(
+            Script::split_chain(r#"echo 'a && b' && git commit -m "x && y" && echo c\&\&d"#),
+            vec!["echo 'a && b'", r#"git commit -m "x && y""#, r"echo c\&\&d"]
+        );
+    }
+
+    #[test]
+    fn test_parsed_tokens_keep_their_quoting() {
+        let script = Script::parse("rm -f *.o '$HOME/x' ~/tmp").prepend_token("sudo");
+        assert_eq!(script.to_string(), "sudo rm -f *.o '$HOME/x' ~/tmp");
+    }
+
+    #[test]
+    fn test_added_tokens_never_expand() {
+        for token in ["*.o", "$HOME", "~/x", "$(reboot)", "`id`", "{a,b}", "!!", "a?b", "[ab]"] {
+            let rendered = Script::parse("echo").push_token(token).to_string();
+            assert_eq!(Script::parse(&rendered).tokens(), &["echo", token]);
+            assert!(rendered.contains('\''), "{} is unquoted", rendered);
+        }
+        assert_eq!(Script::parse("ls").push_token("src/main.rs").to_string(), "ls src/main.rs");
+    }
+
+    #[test]
+    fn test_insert_script_at() {
+        let script = Script::parse("ssh deploy@prod").insert_script_at(1, Script::parse("-i ~/.ssh/id_ed25519"));
+        assert_eq!(script.to_string(), "ssh -i ~/.ssh/id_ed25519 deploy@prod");
+    }
+
+    #[test]
+    fn test_replace_tokens() {
+        let script = Script::parse("awk {print $1} \"my file\"");
+        assert_eq!(script.raw_token(3), Some("\"my file\""));
+        let script = script.replace_tokens(1..3, Script::from_tokens(["{print $1}"]));
+        assert_eq!(script.to_string(), "awk '{print $1}' \"my file\"");
+        assert_eq!(script.raw_token(1), None);
+
+        let script = Script::parse("sed 's/a/b/' f").replace_tokens(1..2, Script::parse("\"s|$x|y|\""));
+        assert_eq!(script.to_string(), "sed \"s|$x|y|\" f");
+        assert_eq!(Script::parse("ls").replace_tokens(3..5, Script::parse("-l")).to_string(), "ls -l");
+    }
+
+    #[test]
+    fn test_reordered() {
+        let script = Script::parse("ffmpeg \"my clip.mp4\" -i $IN");
+        assert_eq!(script.reordered(&[0, 2, 3, 1]).to_string(), "ffmpeg -i $IN \"my clip.mp4\"");
+        assert_eq!(script.reordered(&[0, 7]).to_string(), "ffmpeg");
+    }
+
+    #[test]
+    fn test_empty_token_is_quoted() {
+        let script = Script::from_tokens(["echo", ""]);
+        assert_eq!(script.to_string(), "echo ''");
+    }
+}
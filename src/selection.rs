@@ -0,0 +1,145 @@
+//! The candidate list last shown in the selector.
+//!
+//! Each time the selector is shown, its corrections are saved in the session
+//! cache in the order they were numbered. `ftf --apply-nth N` then applies
+//! the Nth of them without correcting the command again, which would number
+//! them differently once the passed-over ones are suppressed. The list is
+//! tied to a hash of the command it was made for.
+
+use crate::stats::script_hash;
+use crate::{CorrectedCommand, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File in the session directory holding the last candidate list.
+const CANDIDATES_FILE: &str = "candidates.json";
+
+/// Corrections shown in the selector, numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CandidateList {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Hash of the failed command the corrections were offered for
+    pub original_hash: String,
+    /// The corrections, in the order they were numbered
+    pub corrections: Vec<CorrectedCommand>,
+}
+
+impl CandidateList {
+    /// Creates the list of `corrections` offered for `original`.
+    pub fn new(original: &str, corrections: &[CorrectedCommand], timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            original_hash: script_hash(original),
+            corrections: corrections.to_vec(),
+        }
+    }
+
+    /// Saves the list to `session_dir`, replacing the previous one.
+    pub fn save(&self, session_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(session_dir)?;
+        let json = serde_json::to_string(self).map_err(|e| Error::Other(e.to_string()))?;
+        // Written aside and renamed, so a concurrent --apply-nth never reads
+        // half a list
+        let tmp = session_dir.join(format!("{}.tmp", CANDIDATES_FILE));
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, session_dir.join(CANDIDATES_FILE))?;
+        Ok(())
+    }
+
+    /// Loads the list from `session_dir`, or `None` if none was saved.
+    pub fn load(session_dir: &Path) -> Result<Option<Self>> {
+        let json = match std::fs::read_to_string(session_dir.join(CANDIDATES_FILE)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| Error::Other(format!("unreadable candidate list: {}", e)))
+    }
+
+    /// Gets the correction numbered `n` for `original`, failing if the list
+    /// was made for another command or has no such number.
+    pub fn nth(&self, original: &str, n: usize) -> Result<&CorrectedCommand> {
+        if self.original_hash != script_hash(original) {
+            return Err(Error::Other(format!(
+                "the last candidate list is for another command than '{}'; run ftf on it first",
+                original
+            )));
+        }
+        n.checked_sub(1)
+            .and_then(|index| self.corrections.get(index))
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "no candidate {}; the last list has 1 to {}",
+                    n,
+                    self.corrections.len()
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn corrections() -> Vec<CorrectedCommand> {
+        vec![
+            CorrectedCommand::new("git push", 100).with_rule("git_not_command"),
+            CorrectedCommand::new("git pull", 200).with_rule("git_not_command"),
+            CorrectedCommand::new("git push --force", 300).with_rule("git_push_force"),
+        ]
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ftf_selection_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(CandidateList::load(&dir).unwrap(), None);
+        let list = CandidateList::new("git psuh", &corrections(), NOW);
+        list.save(&dir).unwrap();
+        let loaded = CandidateList::load(&dir).unwrap().unwrap();
+        assert_eq!(loaded, list);
+        assert_eq!(loaded.nth("git psuh", 3).unwrap().rule.as_deref(), Some("git_push_force"));
+
+        // A newer list replaces it
+        CandidateList::new("sl", &[CorrectedCommand::new("ls", 100)], NOW + 1).save(&dir).unwrap();
+        assert_eq!(CandidateList::load(&dir).unwrap().unwrap().corrections.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_nth_is_numbered_from_one() {
+        let list = CandidateList::new("git psuh", &corrections(), NOW);
+        assert_eq!(list.nth("git psuh", 1).unwrap().script, "git push");
+        assert_eq!(list.nth("git psuh", 2).unwrap().script, "git pull");
+    }
+
+    #[test]
+    fn test_nth_rejects_stale_list() {
+        let list = CandidateList::new("git psuh", &corrections(), NOW);
+        let error = list.nth("git cmomit", 1).unwrap_err().to_string();
+        assert!(error.contains("another command than 'git cmomit'"), "{}", error);
+    }
+
+    #[test]
+    fn test_nth_rejects_out_of_range() {
+        let list = CandidateList::new("git psuh", &corrections(), NOW);
+        let error = list.nth("git psuh", 4).unwrap_err().to_string();
+        assert!(error.contains("no candidate 4; the last list has 1 to 3"), "{}", error);
+        assert!(list.nth("git psuh", 0).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_list() {
+        let dir = std::env::temp_dir().join(format!("ftf_selection_corrupt_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(CANDIDATES_FILE), "{").unwrap();
+        assert!(CandidateList::load(&dir).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
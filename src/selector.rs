@@ -0,0 +1,207 @@
+//! Layout of the correction selector when candidates are grouped.
+//!
+//! One failure can get corrections from many rule categories, and a flat
+//! list of them is hard to read. In a terminal the selector groups them
+//! under a header per category, in the order of each category's best
+//! candidate. One group is expanded at a time, at first that of the top
+//! candidate, and a group too long for the terminal is shown a page at a
+//! time. Candidates keep the numbers of the flat list, so any of them can be
+//! picked whichever group is expanded.
+
+/// Category of corrections whose rule has none.
+pub const OTHER_CATEGORY: &str = "other";
+
+/// Lines the selector takes besides groups and candidates: a blank line,
+/// the title, the failed command, the key hint, another blank line and the
+/// prompt.
+const FIXED_LINES: usize = 6;
+
+/// Candidates of one category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// The category
+    pub category: String,
+    /// Indexes of the candidates in the flat list, best first
+    pub members: Vec<usize>,
+}
+
+/// Groups candidates by category, given each one's category in ranking
+/// order. Groups are in the order of their best candidate.
+pub fn group<'a>(categories: impl IntoIterator<Item = Option<&'a str>>) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for (index, category) in categories.into_iter().enumerate() {
+        let category = category.unwrap_or(OTHER_CATEGORY);
+        match groups.iter_mut().find(|group| group.category == category) {
+            Some(group) => group.members.push(index),
+            None => groups.push(Group {
+                category: category.to_string(),
+                members: vec![index],
+            }),
+        }
+    }
+    groups
+}
+
+/// Gets how many candidates of the expanded group fit in a terminal
+/// `height` lines high, with `groups` headers and `lines_per_candidate`
+/// lines each. At least one always does.
+pub fn page_size(height: usize, groups: usize, lines_per_candidate: usize) -> usize {
+    let available = height.saturating_sub(FIXED_LINES + groups);
+    (available / lines_per_candidate.max(1)).max(1)
+}
+
+/// Which group is expanded and which page of it is shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    groups: Vec<Group>,
+    page_size: usize,
+    expanded: usize,
+    page: usize,
+}
+
+impl Layout {
+    /// Lays out `groups` with `page_size` candidates to a page, the first
+    /// group expanded.
+    pub fn new(groups: Vec<Group>, page_size: usize) -> Self {
+        Self {
+            groups,
+            page_size: page_size.max(1),
+            expanded: 0,
+            page: 0,
+        }
+    }
+
+    /// Whether grouping helps: there's more than one group, or the one
+    /// group needs paging.
+    pub fn is_useful(&self) -> bool {
+        self.groups.len() > 1 || self.pages() > 1
+    }
+
+    /// Gets the groups.
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Gets the index of the expanded group.
+    pub fn expanded(&self) -> usize {
+        self.expanded
+    }
+
+    /// Gets the shown page of the expanded group, from 0.
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// Gets the number of pages of the expanded group.
+    pub fn pages(&self) -> usize {
+        let members = self.groups.get(self.expanded).map_or(0, |group| group.members.len());
+        members.div_ceil(self.page_size).max(1)
+    }
+
+    /// Gets the indexes of the candidates shown: the page of the expanded
+    /// group.
+    pub fn visible(&self) -> &[usize] {
+        let Some(group) = self.groups.get(self.expanded) else {
+            return &[];
+        };
+        let start = (self.page * self.page_size).min(group.members.len());
+        let end = (start + self.page_size).min(group.members.len());
+        &group.members[start..end]
+    }
+
+    /// Expands the next group, wrapping around, at its first page.
+    pub fn next_group(&mut self) {
+        if !self.groups.is_empty() {
+            self.expanded = (self.expanded + 1) % self.groups.len();
+            self.page = 0;
+        }
+    }
+
+    /// Expands the previous group, wrapping around, at its first page.
+    pub fn previous_group(&mut self) {
+        if !self.groups.is_empty() {
+            self.expanded = (self.expanded + self.groups.len() - 1) % self.groups.len();
+            self.page = 0;
+        }
+    }
+
+    /// Shows the next page of the expanded group, if there is one.
+    pub fn next_page(&mut self) {
+        self.page = (self.page + 1).min(self.pages() - 1);
+    }
+
+    /// Shows the previous page of the expanded group, if there is one.
+    pub fn previous_page(&mut self) {
+        self.page = self.page.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(categories: &[Option<&str>], page_size: usize) -> Layout {
+        Layout::new(group(categories.iter().copied()), page_size)
+    }
+
+    #[test]
+    fn test_groups_follow_their_best_candidate() {
+        let groups = group([Some("git"), Some("filesystem"), Some("git"), None, Some("filesystem")]);
+        let summary: Vec<_> = groups.iter().map(|group| (group.category.as_str(), group.members.clone())).collect();
+        assert_eq!(summary, [("git", vec![0, 2]), ("filesystem", vec![1, 4]), (OTHER_CATEGORY, vec![3])]);
+        assert!(group([]).is_empty());
+    }
+
+    #[test]
+    fn test_top_candidates_group_is_expanded() {
+        let layout = layout(&[Some("history_fallback"), Some("git"), Some("git")], 10);
+        assert_eq!(layout.expanded(), 0);
+        assert_eq!(layout.visible(), [0]);
+        assert!(layout.is_useful());
+    }
+
+    #[test]
+    fn test_switching_groups_wraps_and_resets_the_page() {
+        let mut layout = layout(&[Some("git"), Some("git"), Some("git"), Some("filesystem")], 2);
+        layout.next_page();
+        assert_eq!(layout.visible(), [2]);
+        layout.next_group();
+        assert_eq!((layout.expanded(), layout.page()), (1, 0));
+        assert_eq!(layout.visible(), [3]);
+        layout.next_group();
+        assert_eq!((layout.expanded(), layout.page()), (0, 0));
+        layout.previous_group();
+        assert_eq!(layout.expanded(), 1);
+    }
+
+    #[test]
+    fn test_paging_is_clamped() {
+        let mut layout = layout(&[Some("git"); 7], 3);
+        assert_eq!(layout.pages(), 3);
+        assert_eq!(layout.visible(), [0, 1, 2]);
+        layout.previous_page();
+        assert_eq!(layout.page(), 0);
+        layout.next_page();
+        layout.next_page();
+        assert_eq!(layout.visible(), [6]);
+        layout.next_page();
+        assert_eq!(layout.page(), 2);
+        assert!(layout.is_useful());
+    }
+
+    #[test]
+    fn test_single_group_that_fits_is_not_grouped() {
+        let layout = layout(&[Some("git"), Some("git")], 5);
+        assert_eq!(layout.pages(), 1);
+        assert!(!layout.is_useful());
+    }
+
+    #[test]
+    fn test_page_size() {
+        // 24 lines, less 6 fixed and 3 headers
+        assert_eq!(page_size(24, 3, 1), 15);
+        assert_eq!(page_size(24, 3, 2), 7);
+        assert_eq!(page_size(6, 3, 1), 1);
+        assert_eq!(page_size(24, 3, 0), 15);
+    }
+}
@@ -1,12 +1,27 @@
-/// Shell abstraction layer for executing commands and capturing output.
-///
-/// This module provides a trait-based abstraction over shell implementations,
-/// allowing the correction engine to work with different shells (Bash, Zsh, etc.)
-/// while maintaining a consistent interface.
+//! Shell abstraction layer for executing commands and capturing output.
+//!
+//! This module provides a trait-based abstraction over shell implementations,
+//! allowing the correction engine to work with different shells (Bash, Zsh, etc.)
+//! while maintaining a consistent interface.
 
+use crate::history::{history_file_path, parse_history_file_entries, HistoryEntry, HistoryFilter};
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::process::{Command as StdCommand, Stdio};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command as StdCommand, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long a probe, a command a rule runs to look something up such as
+/// the git branches, may take before it's killed.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a command run with a timeout is checked on.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long the output of a killed command is waited for. A process that
+/// left the command's group can keep its pipes open.
+const KILLED_OUTPUT_GRACE: Duration = Duration::from_millis(100);
 
 /// Result of executing a command in the shell.
 #[derive(Debug, Clone)]
@@ -21,6 +36,9 @@ pub struct ShellOutput {
     pub exit_code: i32,
     /// Whether the command succeeded
     pub success: bool,
+    /// Whether the command was killed for running past its timeout. The
+    /// output is what it printed until then.
+    pub timed_out: bool,
 }
 
 impl ShellOutput {
@@ -38,6 +56,7 @@ impl ShellOutput {
             stderr,
             exit_code,
             success,
+            timed_out: false,
         }
     }
 }
@@ -53,6 +72,16 @@ pub trait Shell: Send + Sync {
     /// Executes a command and captures its output.
     fn execute(&self, command: &str) -> crate::Result<ShellOutput>;
 
+    /// Executes a command with stdin closed, for at most `timeout`. Past
+    /// it, the command and everything it started are killed, and what it
+    /// printed so far comes back with `timed_out` set. Probes should run
+    /// with [`PROBE_TIMEOUT`].
+    ///
+    /// Shells that can't stop a command run it to the end.
+    fn execute_with_timeout(&self, command: &str, _timeout: Duration) -> crate::Result<ShellOutput> {
+        self.execute(command)
+    }
+
     /// Gets the current working directory
     fn cwd(&self) -> crate::Result<PathBuf>;
 
@@ -68,6 +97,16 @@ pub trait Shell: Send + Sync {
     /// Gets the user's shell history (if available)
     /// Returns commands in reverse chronological order (most recent first)
     fn history(&self) -> crate::Result<Vec<String>> {
+        Ok(self
+            .history_entries()?
+            .into_iter()
+            .map(|entry| entry.command)
+            .collect())
+    }
+
+    /// Gets the user's shell history with timestamps where the history file
+    /// records them, most recent first.
+    fn history_entries(&self) -> crate::Result<Vec<HistoryEntry>> {
         Ok(Vec::new())
     }
 
@@ -75,25 +114,386 @@ pub trait Shell: Send + Sync {
     fn command_exists(&self, command: &str) -> crate::Result<bool>;
 }
 
+/// Parsed shell history with lookup indexes, built once per invocation.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryIndex {
+    /// All entries, most recent first
+    recent: Vec<String>,
+    /// Number of times each command appears
+    frequency: HashMap<String, usize>,
+    /// Indexes into `recent` of each distinct command, keyed by first token
+    by_first_token: HashMap<String, Vec<usize>>,
+}
+
+impl HistoryIndex {
+    /// Builds the index from a shell's history.
+    pub fn build(shell: &dyn Shell) -> Self {
+        Self::from_entries(shell.history().unwrap_or_default())
+    }
+
+    /// Builds the index from the merged history of several shells.
+    pub fn build_merged(shells: &[Box<dyn Shell>]) -> Self {
+        Self::from_entries(merged_history(shells))
+    }
+
+    /// Builds the index from history entries ordered most recent first.
+    pub fn from_entries(entries: Vec<String>) -> Self {
+        let mut frequency: HashMap<String, usize> = HashMap::new();
+        let mut by_first_token: HashMap<String, Vec<usize>> = HashMap::new();
+
+        for (i, entry) in entries.iter().enumerate() {
+            let count = frequency.entry(entry.clone()).or_insert(0);
+            *count += 1;
+
+            // Only index the most recent occurrence of each command
+            if *count == 1 {
+                if let Some(token) = entry.split_whitespace().next() {
+                    by_first_token.entry(token.to_string()).or_default().push(i);
+                }
+            }
+        }
+
+        Self {
+            recent: entries,
+            frequency,
+            by_first_token,
+        }
+    }
+
+    /// Gets all entries, most recent first.
+    pub fn entries(&self) -> &[String] {
+        &self.recent
+    }
+
+    /// Gets up to `n` most recent entries, most recent first.
+    pub fn most_recent(&self, n: usize) -> &[String] {
+        &self.recent[..self.recent.len().min(n)]
+    }
+
+    /// Gets how many times `command` appears in history.
+    pub fn frequency(&self, command: &str) -> usize {
+        self.frequency.get(command).copied().unwrap_or(0)
+    }
+
+    /// Gets distinct commands whose first token is `token`, most recent first.
+    pub fn commands_starting_with<'a>(&'a self, token: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.by_first_token
+            .get(token)
+            .into_iter()
+            .flatten()
+            .map(|&i| self.recent[i].as_str())
+    }
+
+    /// Gets the total number of entries.
+    pub fn len(&self) -> usize {
+        self.recent.len()
+    }
+
+    /// Returns true if the history is empty.
+    pub fn is_empty(&self) -> bool {
+        self.recent.is_empty()
+    }
+}
+
+/// A history entry tagged with the shell it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcedHistoryEntry {
+    /// Name of the shell whose history contained the entry
+    pub source: String,
+    /// The entry itself
+    pub entry: HistoryEntry,
+}
+
+/// Merges the histories of several shells into one stream, most recent first.
+///
+/// See `merge_histories` for how entries are interleaved.
+pub fn merged_history(shells: &[Box<dyn Shell>]) -> Vec<String> {
+    merged_history_entries(shells)
+        .into_iter()
+        .map(|sourced| sourced.entry.command)
+        .collect()
+}
+
+/// Merges the histories of several shells, keeping each entry's source.
+pub fn merged_history_entries(shells: &[Box<dyn Shell>]) -> Vec<SourcedHistoryEntry> {
+    merge_histories(
+        shells
+            .iter()
+            .map(|shell| {
+                let entries = shell.history_entries().unwrap_or_default();
+                (shell.name().to_string(), entries)
+            })
+            .collect(),
+    )
+}
+
+/// Merges per-source histories (each most recent first) into one stream.
+///
+/// While every source's next entry has a timestamp, the most recent one is
+/// taken. Otherwise sources are visited round-robin, so histories without
+/// timestamps still interleave instead of one shadowing the other.
+pub fn merge_histories(sources: Vec<(String, Vec<HistoryEntry>)>) -> Vec<SourcedHistoryEntry> {
+    let total = sources.iter().map(|(_, entries)| entries.len()).sum();
+    let mut merged = Vec::with_capacity(total);
+    let mut queues: Vec<(String, std::vec::IntoIter<HistoryEntry>)> = sources
+        .into_iter()
+        .map(|(source, entries)| (source, entries.into_iter()))
+        .collect();
+    let mut heads: Vec<Option<HistoryEntry>> = queues.iter_mut().map(|(_, q)| q.next()).collect();
+    let mut turn = 0;
+
+    while heads.iter().any(Option::is_some) {
+        let live: Vec<usize> = (0..heads.len()).filter(|&i| heads[i].is_some()).collect();
+        let all_timestamped = live
+            .iter()
+            .all(|&i| heads[i].as_ref().is_some_and(|entry| entry.timestamp.is_some()));
+
+        let pick = if all_timestamped {
+            // Earliest source wins ties
+            *live
+                .iter()
+                .max_by_key(|&&i| (heads[i].as_ref().and_then(|e| e.timestamp), std::cmp::Reverse(i)))
+                .expect("at least one live source")
+        } else {
+            let next = live
+                .iter()
+                .copied()
+                .find(|&i| i >= turn)
+                .unwrap_or(live[0]);
+            turn = next + 1;
+            next
+        };
+
+        let entry = heads[pick].take().expect("picked a live source");
+        heads[pick] = queues[pick].1.next();
+        merged.push(SourcedHistoryEntry {
+            source: queues[pick].0.clone(),
+            entry,
+        });
+    }
+
+    merged
+}
+
+/// Number of commands the shell hook keeps in the session cache.
+pub const SESSION_CAPACITY: usize = 10;
+
+/// A command recorded in the session cache by the shell hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEntry {
+    /// The command line as typed
+    pub command: String,
+    /// Captured output (empty if the hook could not capture it)
+    pub output: String,
+    /// Exit code of the command
+    pub exit_code: i32,
+}
+
+impl SessionEntry {
+    /// Creates a new session entry.
+    pub fn new(command: impl Into<String>, output: impl Into<String>, exit_code: i32) -> Self {
+        Self {
+            command: command.into(),
+            output: output.into(),
+            exit_code,
+        }
+    }
+}
+
+/// Checks that `dir` is missing, or owned by the current user and writable
+/// by no one else.
+fn is_private_or_missing(dir: &Path) -> bool {
+    let metadata = match std::fs::symlink_metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(e) => return e.kind() == io::ErrorKind::NotFound,
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.is_dir() && metadata.uid() == nix::unistd::getuid().as_raw() && metadata.mode() & 0o022 == 0
+    }
+    #[cfg(not(unix))]
+    metadata.is_dir()
+}
+
+/// The last few commands run in the current terminal, as recorded by the hook.
+///
+/// The cache is a directory per terminal containing one numbered
+/// subdirectory per command, each holding `command`, `output` and
+/// `exit_code` files. Higher numbers are more recent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionContext {
+    /// Recorded commands, most recent first
+    entries: Vec<SessionEntry>,
+}
+
+impl SessionContext {
+    /// Creates a session from entries ordered most recent first.
+    pub fn from_entries(entries: Vec<SessionEntry>) -> Self {
+        Self { entries }
+    }
+
+    /// Gets the session directory for the current terminal.
+    ///
+    /// Uses `FTF_SESSION_DIR` if the hook exported it, otherwise
+    /// `$XDG_RUNTIME_DIR/fasterthefuck/<tty>`. A directory that another user
+    /// owns or can write to is refused, as `--apply-nth` runs the candidates
+    /// saved in it.
+    pub fn session_dir(env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+        let dir = match env("FTF_SESSION_DIR").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let runtime_dir = env("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())?;
+                let tty = env("TTY").filter(|tty| !tty.is_empty())?;
+                let key: String = tty
+                    .trim_start_matches('/')
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                PathBuf::from(runtime_dir).join("fasterthefuck").join(key)
+            }
+        };
+        is_private_or_missing(&dir).then_some(dir)
+    }
+
+    /// Creates the session directory `dir`, readable only by its owner.
+    pub fn create_dir(dir: &Path) -> io::Result<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(dir)
+    }
+
+    /// Loads the session cache from `dir`. A missing or unreadable cache is empty.
+    pub fn load(dir: &Path) -> Self {
+        let mut numbered: Vec<(u64, PathBuf)> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let seq = entry.file_name().to_str()?.parse().ok()?;
+                Some((seq, entry.path()))
+            })
+            .collect();
+        numbered.sort_by_key(|(seq, _)| std::cmp::Reverse(*seq));
+
+        let entries = numbered
+            .iter()
+            .take(SESSION_CAPACITY)
+            .filter_map(|(_, path)| read_session_entry(path))
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Records `entry` as the most recent command in the cache at `dir`,
+    /// dropping the oldest entries beyond `SESSION_CAPACITY`.
+    pub fn record(dir: &Path, entry: &SessionEntry) -> crate::Result<()> {
+        Self::create_dir(dir)?;
+
+        let mut existing: Vec<u64> = std::fs::read_dir(dir)?
+            .flatten()
+            .filter_map(|e| e.file_name().to_str()?.parse().ok())
+            .collect();
+        existing.sort_unstable();
+
+        let next = existing.last().map_or(1, |last| last + 1);
+        let entry_dir = dir.join(next.to_string());
+        std::fs::create_dir_all(&entry_dir)?;
+        std::fs::write(entry_dir.join("command"), &entry.command)?;
+        std::fs::write(entry_dir.join("output"), &entry.output)?;
+        std::fs::write(entry_dir.join("exit_code"), entry.exit_code.to_string())?;
+
+        let excess = (existing.len() + 1).saturating_sub(SESSION_CAPACITY);
+        for seq in existing.iter().take(excess) {
+            let _ = std::fs::remove_dir_all(dir.join(seq.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Gets all recorded commands, most recent first.
+    pub fn entries(&self) -> &[SessionEntry] {
+        &self.entries
+    }
+
+    /// Gets the commands recorded before the failed `script`, most recent first.
+    ///
+    /// The hook may or may not have recorded the failed command itself yet,
+    /// so a leading entry equal to `script` is skipped.
+    pub fn before(&self, script: &str) -> &[SessionEntry] {
+        match self.entries.first() {
+            Some(first) if first.command.trim() == script.trim() => &self.entries[1..],
+            _ => &self.entries,
+        }
+    }
+
+    /// Returns true if nothing was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Reads one numbered entry directory of the session cache.
+fn read_session_entry(path: &Path) -> Option<SessionEntry> {
+    let command = std::fs::read_to_string(path.join("command")).ok()?;
+    let output = std::fs::read(path.join("output"))
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+    let exit_code = std::fs::read_to_string(path.join("exit_code"))
+        .ok()
+        .and_then(|code| code.trim().parse().ok())
+        .unwrap_or(0);
+
+    Some(SessionEntry {
+        command: command.trim_end_matches('\n').to_string(),
+        output,
+        exit_code,
+    })
+}
+
+/// The interpreter `BashShell` runs commands with, looked up in `PATH`.
+const BASH: &str = "bash";
+
 /// Bash shell implementation.
 pub struct BashShell {
     cwd: PathBuf,
     env: HashMap<String, String>,
+    interpreter: PathBuf,
 }
 
 impl BashShell {
-    /// Creates a new Bash shell instance.
+    /// Creates a Bash shell running commands in the current directory, with
+    /// this process's environment. Fails if the current directory can't be
+    /// read, as when it was deleted.
     pub fn new() -> crate::Result<Self> {
-        let cwd = std::env::current_dir()?;
+        let cwd = std::env::current_dir().map_err(|e| crate::Error::shell_init(BASH, e))?;
+        Ok(Self::in_dir(cwd))
+    }
+
+    /// Creates a Bash shell running commands in `cwd`, with this process's
+    /// environment.
+    pub fn in_dir(cwd: PathBuf) -> Self {
         let env = std::env::vars().collect();
+        Self {
+            cwd,
+            env,
+            interpreter: PathBuf::from(BASH),
+        }
+    }
 
-        Ok(Self { cwd, env })
+    /// Uses `env` as the environment instead of this process's, as for a
+    /// command that ran in another process.
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
     }
-}
 
-impl Default for BashShell {
-    fn default() -> Self {
-        Self::new().expect("Failed to initialize BashShell")
+    /// Runs commands with `interpreter` instead of the `bash` in `PATH`.
+    pub fn with_interpreter(mut self, interpreter: impl Into<PathBuf>) -> Self {
+        self.interpreter = interpreter.into();
+        self
     }
 }
 
@@ -103,25 +503,11 @@ impl Shell for BashShell {
     }
 
     fn execute(&self, command: &str) -> crate::Result<ShellOutput> {
-        let output = StdCommand::new("bash")
-            .arg("-c")
-            .arg(command)
-            .current_dir(&self.cwd)
-            .envs(&self.env)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let exit_code = output.status.code().unwrap_or(1);
+        self.run(command, None)
+    }
 
-        Ok(ShellOutput::new(
-            command.to_string(),
-            stdout,
-            stderr,
-            exit_code,
-        ))
+    fn execute_with_timeout(&self, command: &str, timeout: Duration) -> crate::Result<ShellOutput> {
+        self.run(command, Some(timeout))
     }
 
     fn cwd(&self) -> crate::Result<PathBuf> {
@@ -144,34 +530,149 @@ impl Shell for BashShell {
         Ok(())
     }
 
-    fn history(&self) -> crate::Result<Vec<String>> {
+    fn history_entries(&self) -> crate::Result<Vec<HistoryEntry>> {
         // Try to read from HISTFILE if set, otherwise use ~/.bash_history
-        let hist_file = self
-            .env("HISTFILE")
-            .unwrap_or_else(|| {
-                format!(
-                    "{}/.bash_history",
-                    self.env("HOME").unwrap_or_else(|| ".".to_string())
-                )
-            });
-
-        if let Ok(contents) = std::fs::read_to_string(&hist_file) {
-            let lines: Vec<String> = contents
-                .lines()
-                .map(|s| s.to_string())
-                .collect();
-            Ok(lines.into_iter().rev().collect())
+        let hist_file = history_file_path(|key| self.env(key));
+
+        if let Ok(contents) = std::fs::read(&hist_file) {
+            let filter = HistoryFilter::from_env(|key| self.env(key));
+            let entries = filter.apply_entries(parse_history_file_entries(&contents));
+            Ok(entries.into_iter().rev().collect())
         } else {
             Ok(Vec::new())
         }
     }
 
     fn command_exists(&self, command: &str) -> crate::Result<bool> {
-        let output = self.execute(&format!("command -v {}", command))?;
+        let output = self.execute_with_timeout(&format!("command -v {}", command), PROBE_TIMEOUT)?;
         Ok(output.success)
     }
 }
 
+impl BashShell {
+    /// Runs `command` with stdin closed, for at most `timeout` if there's
+    /// one. A command with a timeout leads its own process group, so that
+    /// killing the group reaches whatever it started.
+    fn run(&self, command: &str, timeout: Option<Duration>) -> crate::Result<ShellOutput> {
+        let span = tracing::debug_span!(
+            "shell",
+            command,
+            exit_code = tracing::field::Empty,
+            timed_out = tracing::field::Empty
+        );
+        let _entered = span.enter();
+        let mut process = StdCommand::new(&self.interpreter);
+        process
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.cwd)
+            .env_clear()
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        if timeout.is_some() {
+            std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        }
+        let child = process.spawn().map_err(|e| self.spawn_error(e))?;
+        let (status, stdout, stderr, timed_out) = wait_for(child, timeout)?;
+
+        let exit_code = status.code().unwrap_or(1);
+        span.record("exit_code", exit_code);
+        span.record("timed_out", timed_out);
+        let mut output = ShellOutput::new(
+            command.to_string(),
+            String::from_utf8_lossy(&stdout).into_owned(),
+            String::from_utf8_lossy(&stderr).into_owned(),
+            exit_code,
+        );
+        if timed_out {
+            output.success = false;
+            output.timed_out = true;
+        }
+        Ok(output)
+    }
+
+    /// Explains why the interpreter couldn't be started. Spawning fails with
+    /// the same error whether the interpreter or the working directory is
+    /// missing, so the directory is checked to tell which.
+    fn spawn_error(&self, error: std::io::Error) -> crate::Error {
+        if error.kind() == std::io::ErrorKind::NotFound && !self.cwd.is_dir() {
+            return crate::Error::shell(format!("the working directory {} no longer exists", self.cwd.display()));
+        }
+        crate::Error::shell(format!("could not run {}: {}", self.interpreter.display(), error))
+    }
+}
+
+/// Waits for `child` to exit, or past `timeout` kills its process group
+/// and reaps it, so it leaves no zombie. Gets its exit status, stdout and
+/// stderr, and whether it was killed.
+pub(crate) fn wait_for(
+    mut child: Child,
+    timeout: Option<Duration>,
+) -> io::Result<(ExitStatus, Vec<u8>, Vec<u8>, bool)> {
+    // Read as the command runs, so it never blocks on a full pipe
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let (status, timed_out) = loop {
+        let Some(deadline) = deadline else {
+            break (child.wait()?, false);
+        };
+        if let Some(status) = child.try_wait()? {
+            break (status, false);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            kill_group(&mut child);
+            break (child.wait()?, true);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    };
+
+    let grace = timed_out.then(|| Instant::now() + KILLED_OUTPUT_GRACE);
+    Ok((status, collect(stdout, grace), collect(stderr, grace), timed_out))
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        // Whatever was read before an error is still the output
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+/// Gets what a pipe reader read, giving up at `grace` if there's one.
+fn collect(reader: Option<JoinHandle<Vec<u8>>>, grace: Option<Instant>) -> Vec<u8> {
+    let Some(reader) = reader else {
+        return Vec::new();
+    };
+    if let Some(grace) = grace {
+        while !reader.is_finished() && Instant::now() < grace {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if !reader.is_finished() {
+            return Vec::new();
+        }
+    }
+    reader.join().unwrap_or_default()
+}
+
+/// Kills `child` and, on unix, the rest of the process group it leads.
+fn kill_group(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+        if killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL).is_ok() {
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,6 +697,18 @@ mod tests {
         assert!(output.stdout.contains("hello"));
     }
 
+    #[test]
+    fn test_missing_interpreter_is_a_shell_error() {
+        let shell = BashShell::in_dir(std::env::temp_dir()).with_interpreter("/nonexistent/ftf/bash");
+        let error = shell.execute("echo 'hello'").unwrap_err();
+        assert!(matches!(error, crate::Error::Shell(_)), "{:?}", error);
+        assert!(error.to_string().starts_with("Shell error: could not run /nonexistent/ftf/bash: "), "{}", error);
+
+        let shell = BashShell::in_dir(PathBuf::from("/nonexistent/ftf/cwd"));
+        let error = shell.execute("echo 'hello'").unwrap_err();
+        assert_eq!(error.to_string(), "Shell error: the working directory /nonexistent/ftf/cwd no longer exists");
+    }
+
     #[test]
     fn test_bash_shell_execute_failure() {
         let shell = BashShell::new().unwrap();
@@ -204,6 +717,54 @@ mod tests {
         assert_eq!(output.exit_code, 1);
     }
 
+    #[test]
+    fn test_commands_cannot_wait_for_input() {
+        let shell = BashShell::in_dir(std::env::temp_dir());
+        let output = shell.execute("read line; echo $?").unwrap();
+        assert_eq!(output.stdout, "1\n");
+        assert!(!output.timed_out);
+    }
+
+    #[test]
+    fn test_command_within_timeout_finishes() {
+        let shell = BashShell::in_dir(std::env::temp_dir());
+        let output = shell.execute_with_timeout("echo hello; exit 3", Duration::from_secs(10)).unwrap();
+        assert_eq!((output.stdout.as_str(), output.exit_code, output.timed_out), ("hello\n", 3, false));
+    }
+
+    /// Whether `pid` is a live process, and not one waiting to be reaped.
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .is_ok_and(|stat| stat.rsplit_once(") ").is_some_and(|(_, fields)| !fields.starts_with('Z')))
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_timeout_kills_the_command_and_what_it_started() {
+        let shell = BashShell::in_dir(std::env::temp_dir());
+        let started = Instant::now();
+        let command = "echo $$; sleep 10 & echo $!; wait";
+        let output = shell.execute_with_timeout(command, Duration::from_millis(200)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+        assert!(output.timed_out);
+        assert!(!output.success);
+
+        // What was printed before the timeout is kept
+        let pids: Vec<&str> = output.stdout.lines().collect();
+        let [shell_pid, sleep_pid] = pids.as_slice() else {
+            panic!("expected two pids, got {:?}", output.stdout);
+        };
+        // Bash was reaped, so nothing is left of it
+        assert!(!Path::new(&format!("/proc/{}", shell_pid)).exists());
+        // The orphaned sleep is reaped by init, in its own time
+        let gone_by = Instant::now() + Duration::from_secs(1);
+        while is_running(sleep_pid) && Instant::now() < gone_by {
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(!is_running(sleep_pid), "sleep {} is still running", sleep_pid);
+    }
+
     #[test]
     fn test_bash_shell_cwd() {
         let shell = BashShell::new().unwrap();
@@ -218,6 +779,21 @@ mod tests {
         assert_eq!(shell.env("TEST_VAR"), Some("test_value".to_string()));
     }
 
+    #[test]
+    fn test_bash_shell_with_env_replaces_the_environment() {
+        let env = HashMap::from([
+            ("PATH".to_string(), std::env::var("PATH").unwrap()),
+            ("FTF_CLIENT".to_string(), "client".to_string()),
+        ]);
+        let shell = BashShell::in_dir(std::env::temp_dir()).with_env(env);
+        assert_eq!(shell.env("FTF_CLIENT").as_deref(), Some("client"));
+        assert_eq!(shell.env("HOME"), None);
+
+        // Commands don't inherit this process's variables either
+        let output = shell.execute("echo \"$FTF_CLIENT:${HOME-unset}\"").unwrap();
+        assert_eq!(output.stdout, "client:unset\n");
+    }
+
     #[test]
     fn test_bash_shell_command_exists_true() {
         let shell = BashShell::new().unwrap();
@@ -232,6 +808,231 @@ mod tests {
         assert!(!exists);
     }
 
+    #[test]
+    fn test_bash_shell_history_parses_zsh_file() {
+        let mut shell = BashShell::new().unwrap();
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/history/zsh_history");
+        shell.set_env("HISTFILE".to_string(), fixture.to_string()).unwrap();
+        shell.set_env("HISTCONTROL".to_string(), "ignoredups".to_string()).unwrap();
+
+        let history = shell.history().unwrap();
+        assert_eq!(history[0], "plain line without metadata");
+        assert_eq!(history[2], "echo 日本");
+        assert_eq!(history[3], "for f in *.txt; do\n  echo $f\ndone");
+    }
+
+    #[test]
+    fn test_history_index_accessors() {
+        let index = HistoryIndex::from_entries(vec![
+            "git status".to_string(),
+            "ls -la".to_string(),
+            "git status".to_string(),
+            "git push".to_string(),
+        ]);
+
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.most_recent(2), &["git status", "ls -la"]);
+        assert_eq!(index.most_recent(10).len(), 4);
+        assert_eq!(index.frequency("git status"), 2);
+        assert_eq!(index.frequency("cargo build"), 0);
+
+        let git: Vec<_> = index.commands_starting_with("git").collect();
+        assert_eq!(git, vec!["git status", "git push"]);
+        assert_eq!(index.commands_starting_with("cargo").count(), 0);
+    }
+
+    /// A shell whose history is read from a fixture file.
+    struct FixtureShell {
+        name: &'static str,
+        fixture: &'static str,
+    }
+
+    impl Shell for FixtureShell {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn execute(&self, command: &str) -> crate::Result<ShellOutput> {
+            Ok(ShellOutput::new(command.to_string(), String::new(), String::new(), 0))
+        }
+
+        fn cwd(&self) -> crate::Result<PathBuf> {
+            Ok(PathBuf::from("/"))
+        }
+
+        fn set_cwd(&mut self, _path: PathBuf) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn env(&self, _key: &str) -> Option<String> {
+            None
+        }
+
+        fn set_env(&mut self, _key: String, _value: String) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn history_entries(&self) -> crate::Result<Vec<HistoryEntry>> {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/history")
+                .join(self.fixture);
+            let entries = parse_history_file_entries(&std::fs::read(path)?);
+            Ok(entries.into_iter().rev().collect())
+        }
+
+        fn command_exists(&self, _command: &str) -> crate::Result<bool> {
+            Ok(false)
+        }
+    }
+
+    fn fixture_shells() -> Vec<Box<dyn Shell>> {
+        vec![
+            Box::new(FixtureShell {
+                name: "bash",
+                fixture: "merge_bash_history",
+            }),
+            Box::new(FixtureShell {
+                name: "zsh",
+                fixture: "merge_zsh_history",
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_merged_history_interleaves_by_timestamp() {
+        let merged = merged_history_entries(&fixture_shells());
+        let tagged: Vec<_> = merged
+            .iter()
+            .map(|sourced| (sourced.source.as_str(), sourced.entry.command.as_str()))
+            .collect();
+
+        assert_eq!(
+            tagged,
+            vec![
+                ("zsh", "ls"),
+                ("bash", "git push"),
+                ("zsh", "vim src/main.rs"),
+                ("bash", "cargo test"),
+                ("zsh", "cd project"),
+                ("bash", "git pull"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_histories_round_robin_without_timestamps() {
+        let untimed = |commands: &[&str]| commands.iter().map(|c| HistoryEntry::new(*c, None)).collect();
+        let merged = merge_histories(vec![
+            ("bash".to_string(), untimed(&["b1", "b2", "b3"])),
+            ("zsh".to_string(), untimed(&["z1"])),
+        ]);
+        let commands: Vec<_> = merged.iter().map(|s| s.entry.command.as_str()).collect();
+
+        assert_eq!(commands, vec!["b1", "z1", "b2", "b3"]);
+    }
+
+    #[test]
+    fn test_history_index_build_merged() {
+        let index = HistoryIndex::build_merged(&fixture_shells());
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.most_recent(2), &["ls", "git push"]);
+        assert_eq!(index.commands_starting_with("git").collect::<Vec<_>>(), vec!["git push", "git pull"]);
+    }
+
+    #[test]
+    fn test_history_index_empty() {
+        let index = HistoryIndex::default();
+        assert!(index.is_empty());
+        assert!(index.most_recent(5).is_empty());
+    }
+
+    fn session_fixture() -> PathBuf {
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/session"))
+    }
+
+    #[test]
+    fn test_session_load_fixture() {
+        let session = SessionContext::load(&session_fixture());
+        let commands: Vec<_> = session.entries().iter().map(|e| e.command.as_str()).collect();
+
+        assert_eq!(
+            commands,
+            vec!["cd fasterthefuk", "git clone https://github.com/GeorgePearse/fasterthefuck.git", "ls"]
+        );
+        assert_eq!(session.entries()[0].exit_code, 1);
+        assert!(session.entries()[1].output.contains("Cloning into 'fasterthefuck'"));
+    }
+
+    #[test]
+    fn test_session_load_missing_dir() {
+        let session = SessionContext::load(Path::new("/nonexistent/ftf/session"));
+        assert!(session.is_empty());
+    }
+
+    #[test]
+    fn test_session_before_skips_failed_command() {
+        let session = SessionContext::load(&session_fixture());
+        assert_eq!(session.before("cd fasterthefuk").len(), 2);
+        assert_eq!(session.before("cd elsewhere").len(), 3);
+    }
+
+    #[test]
+    fn test_session_dir_from_env() {
+        let dir = SessionContext::session_dir(|key| match key {
+            "XDG_RUNTIME_DIR" => Some("/run/user/1000".to_string()),
+            "TTY" => Some("/dev/pts/3".to_string()),
+            _ => None,
+        });
+        assert_eq!(dir, Some(PathBuf::from("/run/user/1000/fasterthefuck/dev_pts_3")));
+
+        let dir = SessionContext::session_dir(|key| match key {
+            "FTF_SESSION_DIR" => Some("/nonexistent/ftf".to_string()),
+            "XDG_RUNTIME_DIR" => Some("/run/user/1000".to_string()),
+            _ => None,
+        });
+        assert_eq!(dir, Some(PathBuf::from("/nonexistent/ftf")));
+
+        assert_eq!(SessionContext::session_dir(|_| None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_session_dir_others_can_write_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ftf_session_shared_{}", std::process::id()));
+        SessionContext::create_dir(&dir).unwrap();
+        let session_dir = |dir: &Path| {
+            let exported = dir.display().to_string();
+            SessionContext::session_dir(|key| (key == "FTF_SESSION_DIR").then(|| exported.clone()))
+        };
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        assert_eq!(session_dir(&dir), Some(dir.clone()));
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert_eq!(session_dir(&dir), None);
+        // Nor is anything but a directory
+        assert_eq!(session_dir(Path::new("/etc/hostname")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_session_record_keeps_capacity() {
+        let dir = std::env::temp_dir().join(format!("ftf_session_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for i in 0..SESSION_CAPACITY + 3 {
+            let entry = SessionEntry::new(format!("echo {}", i), format!("{}\n", i), 0);
+            SessionContext::record(&dir, &entry).unwrap();
+        }
+
+        let session = SessionContext::load(&dir);
+        assert_eq!(session.entries().len(), SESSION_CAPACITY);
+        assert_eq!(session.entries()[0].command, format!("echo {}", SESSION_CAPACITY + 2));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), SESSION_CAPACITY);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_shell_output_success() {
         let output = ShellOutput::new("test".to_string(), "output".to_string(), "".to_string(), 0);
@@ -0,0 +1,283 @@
+//! Correction log and usage statistics.
+//!
+//! When `[global] log_corrections` is enabled every invocation is appended to a
+//! JSONL log under the data directory. Logging is off by default. Each entry
+//! holds a timestamp, a hash of the failed script, the rule, the outcome and
+//! the accepted correction in plaintext. The correction usually repeats the
+//! failed command's arguments (paths, hosts, branch names), so the log is as
+//! private as shell history. `ftf stats` aggregates it into per-rule
+//! acceptance counts and the most common corrections.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What happened to the suggested corrections for one invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// A correction was selected and printed
+    Accepted,
+    /// Corrections were offered but none was selected
+    Cancelled,
+    /// No rule produced a correction
+    NoCorrection,
+}
+
+/// A single line of the correction log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Stable hash of the original script
+    pub script_hash: String,
+    /// Rule that produced the chosen (or first offered) correction
+    pub rule: Option<String>,
+    /// The chosen corrected script in plaintext, if any
+    pub correction: Option<String>,
+    /// Whether the correction was accepted
+    pub outcome: Outcome,
+}
+
+impl LogEntry {
+    /// Creates an entry for `script` timestamped now.
+    pub fn new(
+        script: &str,
+        rule: Option<String>,
+        correction: Option<String>,
+        outcome: Outcome,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            timestamp,
+            script_hash: script_hash(script),
+            rule,
+            correction,
+            outcome,
+        }
+    }
+}
+
+/// Hashes a script with 64-bit FNV-1a so log hashes are stable across builds.
+pub fn script_hash(script: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in script.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Gets the default log path: <data dir>/fasterthefuck/corrections.jsonl
+pub fn default_log_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("fasterthefuck").join("corrections.jsonl"))
+}
+
+/// Appends an entry to the log, creating the file and its parent directory.
+pub fn append_entry(path: &Path, entry: &LogEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let line = serde_json::to_string(entry).map_err(|e| Error::Other(e.to_string()))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Parses log contents, skipping blank or malformed lines.
+pub fn parse_log(contents: &str) -> Vec<LogEntry> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Reads the log at `path`. A missing log yields no entries.
+pub fn read_log(path: &Path) -> Result<Vec<LogEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_log(&std::fs::read_to_string(path)?))
+}
+
+/// Acceptance counts for a single rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    /// Times a correction from this rule was selected
+    pub accepted: usize,
+    /// Times this rule's correction was offered first but cancelled
+    pub cancelled: usize,
+}
+
+/// Aggregated statistics over the correction log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// Total invocations logged
+    pub invocations: usize,
+    /// Per-rule acceptance counts, keyed by rule name
+    pub rules: BTreeMap<String, RuleStats>,
+    /// Accepted corrections with their counts, most frequent first
+    pub top_corrections: Vec<(String, usize)>,
+    /// Registered rules that never appear in the log
+    pub never_fired: Vec<String>,
+}
+
+impl Stats {
+    /// Aggregates log entries, cross-referencing the names of registered rules.
+    pub fn from_entries<'a>(
+        entries: &[LogEntry],
+        registered_rules: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let mut rules: BTreeMap<String, RuleStats> = BTreeMap::new();
+        let mut corrections: HashMap<&str, usize> = HashMap::new();
+
+        for entry in entries {
+            if let Some(rule) = &entry.rule {
+                let stats = rules.entry(rule.clone()).or_default();
+                match entry.outcome {
+                    Outcome::Accepted => stats.accepted += 1,
+                    Outcome::Cancelled => stats.cancelled += 1,
+                    Outcome::NoCorrection => {}
+                }
+            }
+
+            if entry.outcome == Outcome::Accepted {
+                if let Some(correction) = &entry.correction {
+                    *corrections.entry(correction.as_str()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut top_corrections: Vec<(String, usize)> = corrections
+            .into_iter()
+            .map(|(correction, count)| (correction.to_string(), count))
+            .collect();
+        top_corrections.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut never_fired: Vec<String> = registered_rules
+            .into_iter()
+            .filter(|name| !rules.contains_key(*name))
+            .map(str::to_string)
+            .collect();
+        never_fired.sort();
+        never_fired.dedup();
+
+        Self {
+            invocations: entries.len(),
+            rules,
+            top_corrections,
+            never_fired,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"
+{"timestamp":1,"script_hash":"a","rule":"git_push_force","correction":"git push --force-with-lease","outcome":"accepted"}
+{"timestamp":2,"script_hash":"b","rule":"git_push_force","correction":"git push --force-with-lease","outcome":"accepted"}
+{"timestamp":3,"script_hash":"c","rule":"mkdir_p","correction":"mkdir -p a/b","outcome":"accepted"}
+{"timestamp":4,"script_hash":"d","rule":"mkdir_p","correction":null,"outcome":"cancelled"}
+not json at all
+{"timestamp":5,"script_hash":"e","rule":null,"correction":null,"outcome":"no_correction"}
+"#;
+
+    #[test]
+    fn test_parse_log_skips_malformed_lines() {
+        let entries = parse_log(FIXTURE);
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[4].outcome, Outcome::NoCorrection);
+    }
+
+    #[test]
+    fn test_stats_per_rule_counts() {
+        let entries = parse_log(FIXTURE);
+        let stats = Stats::from_entries(&entries, ["git_push_force", "mkdir_p"]);
+
+        assert_eq!(stats.invocations, 5);
+        assert_eq!(
+            stats.rules["git_push_force"],
+            RuleStats {
+                accepted: 2,
+                cancelled: 0
+            }
+        );
+        assert_eq!(
+            stats.rules["mkdir_p"],
+            RuleStats {
+                accepted: 1,
+                cancelled: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_stats_top_corrections() {
+        let entries = parse_log(FIXTURE);
+        let stats = Stats::from_entries(&entries, []);
+
+        assert_eq!(
+            stats.top_corrections,
+            vec![
+                ("git push --force-with-lease".to_string(), 2),
+                ("mkdir -p a/b".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_never_fired() {
+        let entries = parse_log(FIXTURE);
+        let stats = Stats::from_entries(&entries, ["mkdir_p", "rm_recursive", "cp_recursive"]);
+
+        assert_eq!(stats.never_fired, vec!["cp_recursive", "rm_recursive"]);
+    }
+
+    #[test]
+    fn test_script_hash_is_stable() {
+        assert_eq!(script_hash(""), "cbf29ce484222325");
+        assert_eq!(script_hash("git push"), script_hash("git push"));
+        assert_ne!(script_hash("git push"), script_hash("git pull"));
+    }
+
+    #[test]
+    fn test_append_and_read_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("ftf_stats_{}", std::process::id()))
+            .join("corrections.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let entry = LogEntry::new(
+            "git psuh",
+            Some("git_push".to_string()),
+            Some("git push".to_string()),
+            Outcome::Accepted,
+        );
+        append_entry(&path, &entry).unwrap();
+        append_entry(&path, &entry).unwrap();
+
+        let entries = read_log(&path).unwrap();
+        assert_eq!(entries, vec![entry.clone(), entry]);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_read_missing_log() {
+        let entries = read_log(Path::new("/nonexistent/ftf/corrections.jsonl")).unwrap();
+        assert!(entries.is_empty());
+    }
+}
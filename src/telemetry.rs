@@ -0,0 +1,424 @@
+//! Opt-in usage metrics.
+//!
+//! With `[telemetry] enabled = true` and an `endpoint`, every invocation
+//! appends an [`Event`] to a spool file under the data directory: the rule
+//! that offered the chosen or first correction, the outcome and how long
+//! finding corrections took, bucketed. The command, its output and the
+//! corrections are never recorded. Once `batch_size` events are spooled they
+//! are POSTed to the endpoint as one JSON document, shaped like an
+//! OpenTelemetry log export:
+//!
+//! ```text
+//! {"resource":{"service.name":"fasterthefuck","service.version":"0.1.0"},
+//!  "events":[{"name":"ftf.correction","time_unix":1700000000,"rule":"git_not_command",
+//!             "outcome":"accepted","latency":"lt_10ms"}]}
+//! ```
+//!
+//! Sending is best effort, and happens in a detached `ftf telemetry flush`
+//! started after the correction is printed, so it never delays one. A batch
+//! is moved aside while it's sent and only deleted once sending succeeds; a
+//! failed send puts it back in the spool, and a batch left aside by a flush
+//! that died is put back by the next one.
+
+use crate::stats::Outcome;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command as StdCommand, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of every event.
+const EVENT_NAME: &str = "ftf.correction";
+
+/// Seconds curl may take to send a batch.
+const SEND_TIMEOUT_SECS: &str = "5";
+
+/// How long a batch may stay aside being sent before a flush takes its
+/// sender for dead and puts it back in the spool. Well past the send timeout.
+const ABANDONED_AFTER: Duration = Duration::from_secs(60);
+
+/// How long finding corrections took, coarsely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LatencyBucket {
+    #[serde(rename = "lt_10ms")]
+    Under10Ms,
+    #[serde(rename = "lt_50ms")]
+    Under50Ms,
+    #[serde(rename = "lt_100ms")]
+    Under100Ms,
+    #[serde(rename = "lt_500ms")]
+    Under500Ms,
+    #[serde(rename = "ge_500ms")]
+    Slower,
+}
+
+impl LatencyBucket {
+    /// Gets the bucket `elapsed` falls in.
+    pub fn of(elapsed: Duration) -> Self {
+        match elapsed.as_millis() {
+            0..10 => Self::Under10Ms,
+            10..50 => Self::Under50Ms,
+            50..100 => Self::Under100Ms,
+            100..500 => Self::Under500Ms,
+            _ => Self::Slower,
+        }
+    }
+}
+
+/// One invocation, as sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// Always `ftf.correction`
+    pub name: String,
+    /// Seconds since the Unix epoch
+    pub time_unix: u64,
+    /// Rule that offered the chosen correction, or the first one offered
+    pub rule: Option<String>,
+    /// Whether a correction was offered and accepted
+    pub outcome: Outcome,
+    /// How long finding corrections took
+    pub latency: LatencyBucket,
+}
+
+impl Event {
+    /// Creates an event timestamped now.
+    pub fn new(rule: Option<String>, outcome: Outcome, elapsed: Duration) -> Self {
+        let time_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            name: EVENT_NAME.to_string(),
+            time_unix,
+            rule,
+            outcome,
+            latency: LatencyBucket::of(elapsed),
+        }
+    }
+}
+
+/// Encodes `events` as the document sent to the endpoint.
+pub fn batch_json(events: &[Event]) -> String {
+    serde_json::json!({
+        "resource": {
+            "service.name": env!("CARGO_PKG_NAME"),
+            "service.version": env!("CARGO_PKG_VERSION"),
+        },
+        "events": events,
+    })
+    .to_string()
+}
+
+/// Sends a batch of events somewhere.
+pub trait Transport: Send {
+    /// Sends `body`, a document from [`batch_json`].
+    fn send(&self, body: &str) -> Result<()>;
+}
+
+/// POSTs batches to an HTTP endpoint with `curl`.
+pub struct HttpTransport {
+    endpoint: String,
+}
+
+impl HttpTransport {
+    /// Creates a transport POSTing to `endpoint`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, body: &str) -> Result<()> {
+        let mut child = StdCommand::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--max-time", SEND_TIMEOUT_SECS])
+            .args(["--header", "Content-Type: application/json", "--data-binary", "@-"])
+            .arg(&self.endpoint)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::Other(format!(
+                "cannot send metrics to {}: {}",
+                self.endpoint,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Gets the default spool path: <data dir>/fasterthefuck/telemetry.jsonl
+pub fn default_spool_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("fasterthefuck").join("telemetry.jsonl"))
+}
+
+/// Events waiting to be sent, one JSON object per line.
+#[derive(Debug, Clone)]
+pub struct Spool {
+    path: PathBuf,
+}
+
+impl Spool {
+    /// Creates a spool kept at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Gets where the spool is kept.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `event`, creating the file and its parent directory. Returns
+    /// the number of events spooled.
+    pub fn record(&self, event: &Event) -> Result<usize> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let line = serde_json::to_string(event).map_err(|e| Error::Other(e.to_string()))?;
+        self.append(&format!("{}\n", line), 1)
+    }
+
+    /// Appends `lines`, `count` events, and gets the number spooled.
+    ///
+    /// The number is kept in a file beside the spool, so the spool needn't
+    /// be read on every invocation. Invocations racing may miscount by one,
+    /// which only moves the next batch.
+    fn append(&self, lines: &str, count: usize) -> Result<usize> {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        let spooled = self.count() + count;
+        std::fs::write(self.count_path(), spooled.to_string())?;
+        Ok(spooled)
+    }
+
+    /// Gets the number of events spooled, as kept by [`Spool::append`].
+    fn count(&self) -> usize {
+        let count = std::fs::read_to_string(self.count_path()).ok();
+        count.and_then(|count| count.trim().parse().ok()).unwrap_or(0)
+    }
+
+    fn count_path(&self) -> PathBuf {
+        self.path.with_extension("count")
+    }
+
+    /// Gets the spooled events, skipping malformed lines. A missing spool
+    /// has none.
+    pub fn events(&self) -> Result<Vec<Event>> {
+        Ok(parse(&self.contents()?))
+    }
+
+    /// Sends every spooled event through `transport` as one batch. Returns
+    /// the number sent.
+    ///
+    /// The events are moved aside while they're sent, and only deleted once
+    /// the send succeeds; a failed send puts them back. Events recorded
+    /// meanwhile go to a new spool. While another flush is sending, this one
+    /// sends nothing, unless that batch has been aside for so long its flush
+    /// must have died, when it's put back and sent with the rest.
+    pub fn flush(&self, transport: &dyn Transport) -> Result<usize> {
+        let sending = self.sending_path();
+        self.requeue_abandoned()?;
+        if sending.exists() {
+            return Ok(0);
+        }
+        match std::fs::rename(&self.path, &sending) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            renamed => renamed?,
+        }
+        std::fs::write(self.count_path(), "0")?;
+        let taken = std::fs::read_to_string(&sending)?;
+
+        let events = parse(&taken);
+        let sent = if events.is_empty() {
+            Ok(())
+        } else {
+            transport.send(&batch_json(&events))
+        };
+        if let Err(e) = sent {
+            self.append(&taken, events.len())?;
+            std::fs::remove_file(&sending)?;
+            return Err(e);
+        }
+        std::fs::remove_file(&sending)?;
+        Ok(events.len())
+    }
+
+    /// Puts a batch back in the spool if it's been aside for longer than any
+    /// send takes.
+    fn requeue_abandoned(&self) -> Result<()> {
+        let sending = self.sending_path();
+        let modified = match std::fs::metadata(&sending).and_then(|metadata| metadata.modified()) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            modified => modified?,
+        };
+        if modified.elapsed().unwrap_or_default() < ABANDONED_AFTER {
+            return Ok(());
+        }
+        let taken = std::fs::read_to_string(&sending)?;
+        self.append(&taken, parse(&taken).len())?;
+        std::fs::remove_file(&sending)?;
+        Ok(())
+    }
+
+    /// Where a batch is kept while it's sent.
+    fn sending_path(&self) -> PathBuf {
+        self.path.with_extension("sending")
+    }
+
+    /// Deletes every spooled event.
+    pub fn purge(&self) -> Result<()> {
+        for path in [&self.path, &self.count_path(), &self.sending_path()] {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the spool file, empty if there's none.
+    fn contents(&self) -> Result<String> {
+        match std::fs::read_to_string(&self.path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            contents => Ok(contents?),
+        }
+    }
+}
+
+/// Parses spool contents, skipping blank or malformed lines.
+fn parse(contents: &str) -> Vec<Event> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Keeps what's sent in memory, or fails every send.
+    #[derive(Clone, Default)]
+    struct MemoryTransport {
+        sent: Arc<Mutex<Vec<String>>>,
+        failing: bool,
+    }
+
+    impl Transport for MemoryTransport {
+        fn send(&self, body: &str) -> Result<()> {
+            if self.failing {
+                return Err(Error::Other("endpoint unreachable".to_string()));
+            }
+            self.sent.lock().unwrap().push(body.to_string());
+            Ok(())
+        }
+    }
+
+    fn spool(name: &str) -> Spool {
+        let path = std::env::temp_dir().join(format!("ftf_telemetry_{}_{}.jsonl", name, std::process::id()));
+        let spool = Spool::new(path);
+        spool.purge().unwrap();
+        spool
+    }
+
+    fn event(rule: &str) -> Event {
+        Event::new(Some(rule.to_string()), Outcome::Accepted, Duration::from_millis(3))
+    }
+
+    #[test]
+    fn test_latency_buckets() {
+        assert_eq!(LatencyBucket::of(Duration::from_millis(9)), LatencyBucket::Under10Ms);
+        assert_eq!(LatencyBucket::of(Duration::from_millis(10)), LatencyBucket::Under50Ms);
+        assert_eq!(LatencyBucket::of(Duration::from_millis(99)), LatencyBucket::Under100Ms);
+        assert_eq!(LatencyBucket::of(Duration::from_millis(250)), LatencyBucket::Under500Ms);
+        assert_eq!(LatencyBucket::of(Duration::from_secs(2)), LatencyBucket::Slower);
+    }
+
+    #[test]
+    fn test_flush_sends_one_batch_and_empties_spool() {
+        let spool = spool("flush");
+        assert_eq!(spool.record(&event("git_not_command")).unwrap(), 1);
+        assert_eq!(spool.record(&event("mkdir_p")).unwrap(), 2);
+
+        let transport = MemoryTransport::default();
+        assert_eq!(spool.flush(&transport).unwrap(), 2);
+        let sent = transport.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        let batch: serde_json::Value = serde_json::from_str(&sent[0]).unwrap();
+        assert_eq!(batch["resource"]["service.name"], "fasterthefuck");
+        assert_eq!(batch["events"][1]["rule"], "mkdir_p");
+        assert_eq!(batch["events"][0]["latency"], "lt_10ms");
+        assert_eq!(batch["events"][0]["name"], "ftf.correction");
+        assert!(spool.events().unwrap().is_empty());
+        assert!(!spool.path().exists());
+    }
+
+    #[test]
+    fn test_failed_send_keeps_events() {
+        let spool = spool("failed");
+        spool.record(&event("git_not_command")).unwrap();
+
+        let transport = MemoryTransport {
+            failing: true,
+            ..MemoryTransport::default()
+        };
+        assert!(spool.flush(&transport).is_err());
+        assert_eq!(spool.events().unwrap().len(), 1);
+        assert_eq!(spool.record(&event("mkdir_p")).unwrap(), 2);
+
+        spool.purge().unwrap();
+        assert!(spool.events().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_flush_in_progress_is_left_alone() {
+        let spool = spool("in_progress");
+        spool.record(&event("git_not_command")).unwrap();
+        std::fs::write(spool.sending_path(), "").unwrap();
+
+        let transport = MemoryTransport::default();
+        assert_eq!(spool.flush(&transport).unwrap(), 0);
+        assert!(transport.sent.lock().unwrap().is_empty());
+        assert_eq!(spool.events().unwrap().len(), 1);
+
+        spool.purge().unwrap();
+        assert!(!spool.sending_path().exists());
+    }
+
+    #[test]
+    fn test_abandoned_batch_is_sent_again() {
+        let spool = spool("abandoned");
+        spool.record(&event("git_not_command")).unwrap();
+        std::fs::rename(spool.path(), spool.sending_path()).unwrap();
+        let file = std::fs::File::options().write(true).open(spool.sending_path()).unwrap();
+        file.set_modified(SystemTime::now() - ABANDONED_AFTER * 2).unwrap();
+        spool.record(&event("mkdir_p")).unwrap();
+
+        let transport = MemoryTransport::default();
+        assert_eq!(spool.flush(&transport).unwrap(), 2);
+        let sent = transport.sent.lock().unwrap().clone();
+        assert!(sent[0].contains("mkdir_p") && sent[0].contains("git_not_command"), "{}", sent[0]);
+        assert!(!spool.sending_path().exists());
+    }
+
+    #[test]
+    fn test_record_counts_without_reading_the_spool() {
+        let spool = spool("count");
+        assert_eq!(spool.record(&event("git_not_command")).unwrap(), 1);
+        assert_eq!(spool.record(&event("mkdir_p")).unwrap(), 2);
+        spool.purge().unwrap();
+        assert_eq!(spool.record(&event("mkdir_p")).unwrap(), 1);
+        spool.purge().unwrap();
+    }
+}
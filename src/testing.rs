@@ -0,0 +1,588 @@
+//! Helpers for testing rules.
+//!
+//! The crate's own tests use these, and the `test-utils` feature exposes
+//! them to rule packs that depend on the crate:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! fasterthefuck = { version = "0.1", features = ["test-utils"] }
+//! ```
+//!
+//! - [`RuleTester`] runs a rule against commands and checks what it suggests
+//! - [`MockShell`] answers a rule's shell lookups with canned responses and
+//!   records the commands it was asked to run
+//! - [`fake_context`] builds a [`RuleContext`] over a temporary directory
+//! - [`fixture_output!`](crate::fixture_output) loads a command's output
+//!   from the calling crate's `tests/fixtures/`
+//!
+//! ```ignore
+//! use fasterthefuck::fixture_output;
+//! use fasterthefuck::testing::RuleTester;
+//!
+//! #[test]
+//! fn test_push_set_upstream() {
+//!     RuleTester::new(MyPushRule)
+//!         .given("git push", fixture_output!("git/push_no_upstream"), 128)
+//!         .expect_correction_containing("-u origin")
+//!         .expect_priority_lt(800)
+//!         .given("git push", "Everything up-to-date", 0)
+//!         .expect_no_match()
+//!         .run();
+//! }
+//! ```
+
+use crate::shell::{Shell, ShellOutput};
+use crate::{Command, Rule, RuleContext};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Loads the file at `tests/fixtures/<name>` of the calling crate as a
+/// `&'static str`, e.g. `fixture_output!("git/push_no_upstream")`.
+///
+/// The file is embedded at compile time, so a missing fixture fails the
+/// build rather than the test.
+#[macro_export]
+macro_rules! fixture_output {
+    ($name:literal $(,)?) => {
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/", $name))
+    };
+}
+
+/// The commands a [`MockShell`] was asked to run, in order.
+///
+/// Clones share the recording, so a test can keep one after handing the
+/// shell to a [`RuleContext`].
+#[derive(Debug, Clone, Default)]
+pub struct CallLog(Arc<Mutex<Vec<String>>>);
+
+impl CallLog {
+    /// Gets the commands run so far.
+    pub fn commands(&self) -> Vec<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Asserts that every command run so far starts with one of `allowed`,
+    /// so rules don't run probes a test didn't expect.
+    ///
+    /// # Panics
+    ///
+    /// Panics listing the commands that don't.
+    pub fn assert_only(&self, allowed: &[&str]) {
+        let unexpected: Vec<String> = self
+            .commands()
+            .into_iter()
+            .filter(|command| !allowed.iter().any(|prefix| command.starts_with(prefix)))
+            .collect();
+        assert!(unexpected.is_empty(), "unexpected commands were run: {:?}", unexpected);
+    }
+
+    fn record(&self, command: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(command.to_string());
+    }
+}
+
+/// A shell that returns canned output for known commands instead of running
+/// them, and records what it was asked to run.
+#[derive(Default)]
+pub struct MockShell {
+    outputs: HashMap<String, (String, i32)>,
+    env: HashMap<String, String>,
+    cwd: Option<PathBuf>,
+    history: Vec<String>,
+    commands: HashMap<String, bool>,
+    calls: CallLog,
+}
+
+impl MockShell {
+    /// Creates a mock where every command fails with exit code 127.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `command`, and any command line starting with it followed by
+    /// more arguments, print `stdout` and exit with `exit_code`.
+    ///
+    /// The longest matching command wins.
+    pub fn with_output(mut self, command: &str, stdout: &str, exit_code: i32) -> Self {
+        self.outputs
+            .insert(command.to_string(), (stdout.to_string(), exit_code));
+        self
+    }
+
+    /// Sets an environment variable.
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the working directory (`/` by default).
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Sets the history, oldest entry first. [`Shell::history`] gives it
+    /// back most recent first, as a real shell's.
+    pub fn with_history<I, S>(mut self, entries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.history = entries.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Makes [`Shell::command_exists`] answer `exists` for `command`.
+    ///
+    /// Commands without an answer exist if an output was set for a command
+    /// line starting with them.
+    pub fn with_command(mut self, command: &str, exists: bool) -> Self {
+        self.commands.insert(command.to_string(), exists);
+        self
+    }
+
+    /// Gets the recording of the commands this shell runs.
+    pub fn calls(&self) -> CallLog {
+        self.calls.clone()
+    }
+
+    /// Gets the output set for the longest command that `command` is or
+    /// starts with.
+    fn output_for(&self, command: &str) -> Option<&(String, i32)> {
+        self.outputs
+            .iter()
+            .filter(|(known, _)| {
+                command
+                    .strip_prefix(known.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+            })
+            .max_by_key(|(known, _)| known.len())
+            .map(|(_, output)| output)
+    }
+}
+
+impl Shell for MockShell {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn execute(&self, command: &str) -> crate::Result<ShellOutput> {
+        self.calls.record(command);
+        let (stdout, exit_code) = self
+            .output_for(command)
+            .cloned()
+            .unwrap_or_else(|| (String::new(), 127));
+        Ok(ShellOutput::new(command.to_string(), stdout, String::new(), exit_code))
+    }
+
+    fn cwd(&self) -> crate::Result<PathBuf> {
+        Ok(self.cwd.clone().unwrap_or_else(|| PathBuf::from("/")))
+    }
+
+    fn set_cwd(&mut self, _path: PathBuf) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn env(&self, key: &str) -> Option<String> {
+        self.env.get(key).cloned()
+    }
+
+    fn set_env(&mut self, key: String, value: String) -> crate::Result<()> {
+        self.env.insert(key, value);
+        Ok(())
+    }
+
+    fn history(&self) -> crate::Result<Vec<String>> {
+        Ok(self.history.iter().rev().cloned().collect())
+    }
+
+    fn command_exists(&self, command: &str) -> crate::Result<bool> {
+        if let Some(&exists) = self.commands.get(command) {
+            return Ok(exists);
+        }
+        Ok(self.outputs.keys().any(|known| known.split_whitespace().next() == Some(command)))
+    }
+}
+
+/// Gets a builder for a [`RuleContext`] over a new temporary directory,
+/// backed by a [`MockShell`] whose working directory is in it.
+pub fn fake_context() -> FakeContextBuilder {
+    FakeContextBuilder::default()
+}
+
+/// Builds a [`FakeContext`]; see [`fake_context`].
+#[derive(Default)]
+pub struct FakeContextBuilder {
+    shell: MockShell,
+    cwd: PathBuf,
+    entries: Vec<String>,
+}
+
+impl FakeContextBuilder {
+    /// Sets the working directory, relative to the temporary directory,
+    /// creating it.
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = cwd.into();
+        self
+    }
+
+    /// Sets an environment variable of the shell.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.shell = self.shell.with_env(key, value);
+        self
+    }
+
+    /// Sets the shell history, oldest entry first, so the last entry is the
+    /// most recent command.
+    pub fn history<I, S>(mut self, entries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.shell = self.shell.with_history(entries);
+        self
+    }
+
+    /// Creates files, relative to the working directory, along with their
+    /// parent directories. Paths ending in `/` are created as directories.
+    pub fn dir_entries<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.entries.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Makes `command` print `stdout` and exit with `exit_code`, as with
+    /// [`MockShell::with_output`].
+    pub fn output(mut self, command: &str, stdout: &str, exit_code: i32) -> Self {
+        self.shell = self.shell.with_output(command, stdout, exit_code);
+        self
+    }
+
+    /// Creates the temporary directory and its entries, and the context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory or an entry can't be created.
+    pub fn build(self) -> FakeContext {
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "ftf_fake_context_{}_{}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        let cwd = root.join(&self.cwd);
+        std::fs::create_dir_all(&cwd).expect("fake context directory not created");
+        for entry in &self.entries {
+            let path = cwd.join(entry);
+            if entry.ends_with('/') {
+                std::fs::create_dir_all(&path).expect("fake context directory not created");
+            } else {
+                std::fs::create_dir_all(path.parent().unwrap_or(&cwd)).expect("fake context directory not created");
+                std::fs::write(&path, "").expect("fake context file not created");
+            }
+        }
+
+        let shell = self.shell.with_cwd(&cwd);
+        let calls = shell.calls();
+        FakeContext {
+            context: Arc::new(RuleContext::new(Box::new(shell))),
+            calls,
+            root,
+        }
+    }
+}
+
+/// A [`RuleContext`] over a temporary directory, which is removed when this
+/// is dropped.
+pub struct FakeContext {
+    context: Arc<RuleContext>,
+    calls: CallLog,
+    root: PathBuf,
+}
+
+impl FakeContext {
+    /// Gets the context, to hand to rules.
+    pub fn context(&self) -> Arc<RuleContext> {
+        Arc::clone(&self.context)
+    }
+
+    /// Gets the recording of the commands the context's shell ran.
+    pub fn calls(&self) -> &CallLog {
+        &self.calls
+    }
+
+    /// Gets the temporary directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for FakeContext {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// What a [`RuleTester`] case checks.
+#[derive(Debug)]
+enum Expectation {
+    NoMatch,
+    Corrections(Vec<String>),
+    CorrectionContaining(String),
+    PriorityLt(i32),
+}
+
+/// A command and what the rule should make of it.
+struct Case {
+    command: Command,
+    expectations: Vec<Expectation>,
+}
+
+/// Runs a rule against commands and panics, naming the command, when it
+/// doesn't behave as expected.
+///
+/// Each [`given`](Self::given) starts a case; the expectations after it
+/// apply to that case. Every expectation but
+/// [`expect_no_match`](Self::expect_no_match) also expects the rule to
+/// match. Nothing is checked until [`run`](Self::run).
+pub struct RuleTester {
+    rule: Box<dyn Rule>,
+    cases: Vec<Case>,
+}
+
+impl RuleTester {
+    /// Creates a tester for `rule`.
+    pub fn new(rule: impl Rule + 'static) -> Self {
+        Self::boxed(Box::new(rule))
+    }
+
+    /// Creates a tester for a rule that is already boxed, such as one built
+    /// with [`RegexRuleBuilder`](crate::RegexRuleBuilder).
+    pub fn boxed(rule: Box<dyn Rule>) -> Self {
+        Self { rule, cases: Vec::new() }
+    }
+
+    /// Starts a case for `script` failing with `output` and `exit_code`.
+    pub fn given(mut self, script: &str, output: &str, exit_code: i32) -> Self {
+        self.cases.push(Case {
+            command: Command::new(script, output, exit_code),
+            expectations: Vec::new(),
+        });
+        self
+    }
+
+    /// Expects the rule not to match.
+    pub fn expect_no_match(self) -> Self {
+        self.expect(Expectation::NoMatch)
+    }
+
+    /// Expects exactly these corrections, in order.
+    pub fn expect_corrections<I, S>(self, corrections: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.expect(Expectation::Corrections(corrections.into_iter().map(Into::into).collect()))
+    }
+
+    /// Expects the first correction to contain `text`.
+    pub fn expect_correction_containing(self, text: &str) -> Self {
+        self.expect(Expectation::CorrectionContaining(text.to_string()))
+    }
+
+    /// Expects the first correction to rank above `priority` (lower is
+    /// better).
+    pub fn expect_priority_lt(self, priority: i32) -> Self {
+        self.expect(Expectation::PriorityLt(priority))
+    }
+
+    /// Adds an expectation to the current case.
+    fn expect(mut self, expectation: Expectation) -> Self {
+        let Some(case) = self.cases.last_mut() else {
+            panic!("{}: call given() before {:?}", self.rule.name(), expectation);
+        };
+        case.expectations.push(expectation);
+        self
+    }
+
+    /// Checks every case.
+    ///
+    /// # Panics
+    ///
+    /// Panics at the first expectation that doesn't hold.
+    pub fn run(self) {
+        let name = self.rule.name();
+        for case in &self.cases {
+            let command = &case.command;
+            let matches = self.rule.matches(command);
+            let corrections = self.rule.get_corrected_commands(command);
+            let scripts: Vec<&str> = corrections.iter().map(|correction| correction.script.as_str()).collect();
+
+            for expectation in &case.expectations {
+                if let Expectation::NoMatch = expectation {
+                    assert!(!matches, "{} matched `{}`, suggesting {:?}", name, command.script, scripts);
+                    continue;
+                }
+                assert!(matches, "{} didn't match `{}`", name, command.script);
+                match expectation {
+                    Expectation::NoMatch => {}
+                    Expectation::Corrections(expected) => {
+                        assert_eq!(&scripts, expected, "{} corrections of `{}`", name, command.script);
+                    }
+                    Expectation::CorrectionContaining(text) => {
+                        let first = scripts.first().copied().unwrap_or_default();
+                        assert!(
+                            first.contains(text.as_str()),
+                            "{}: first correction of `{}` doesn't contain {:?}: {:?}",
+                            name,
+                            command.script,
+                            text,
+                            scripts
+                        );
+                    }
+                    Expectation::PriorityLt(priority) => {
+                        let first = corrections.first().map(|correction| correction.priority);
+                        assert!(
+                            first.is_some_and(|first| first < *priority),
+                            "{}: first correction of `{}` has priority {:?}, expected below {}",
+                            name,
+                            command.script,
+                            first,
+                            priority
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimpleRuleBuilder;
+
+    fn mkdir_p() -> Box<dyn Rule> {
+        SimpleRuleBuilder::new("mkdir_p")
+            .match_command("mkdir")
+            .match_output("No such file or directory")
+            .priority(300)
+            .replace("mkdir", "mkdir -p")
+    }
+
+    #[test]
+    fn test_rule_tester_passes() {
+        RuleTester::boxed(mkdir_p())
+            .given("mkdir a/b", "mkdir: cannot create directory 'a/b': No such file or directory", 1)
+            .expect_corrections(["mkdir -p a/b"])
+            .expect_correction_containing("-p")
+            .expect_priority_lt(301)
+            .given("mkdir a", "", 0)
+            .expect_no_match()
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "mkdir_p didn't match `mkdir a`")]
+    fn test_rule_tester_reports_no_match() {
+        RuleTester::boxed(mkdir_p()).given("mkdir a", "", 0).expect_correction_containing("-p").run();
+    }
+
+    #[test]
+    #[should_panic(expected = "has priority Some(300), expected below 300")]
+    fn test_rule_tester_reports_priority() {
+        RuleTester::boxed(mkdir_p())
+            .given("mkdir a/b", "No such file or directory", 1)
+            .expect_priority_lt(300)
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "call given() before")]
+    fn test_rule_tester_needs_a_case() {
+        let _ = RuleTester::boxed(mkdir_p()).expect_no_match();
+    }
+
+    #[test]
+    fn test_mock_shell_command_exists() {
+        let shell = MockShell::new().with_output("podman --version", "podman 5.0", 0).with_command("docker", false);
+
+        assert!(shell.command_exists("podman").unwrap());
+        assert!(!shell.command_exists("docker").unwrap());
+        assert!(shell.with_command("docker", true).command_exists("docker").unwrap());
+    }
+
+    #[test]
+    fn test_mock_shell_history() {
+        let shell = MockShell::new().with_history(["git status", "git add .", "git push"]);
+        assert_eq!(shell.history().unwrap(), vec!["git push", "git add .", "git status"]);
+
+        let context = RuleContext::new(Box::new(shell));
+        assert_eq!(context.history().most_recent(1), &["git push"]);
+    }
+
+    #[test]
+    fn test_mock_shell_matches_command_prefixes() {
+        let shell = MockShell::new()
+            .with_output("git branch", "main\n", 0)
+            .with_output("git branch -r", "origin/main\n", 0);
+
+        assert_eq!(shell.execute("git branch").unwrap().stdout, "main\n");
+        assert_eq!(shell.execute("git branch -a").unwrap().stdout, "main\n");
+        assert_eq!(shell.execute("git branch -r -v").unwrap().stdout, "origin/main\n");
+        assert_eq!(shell.execute("git branches").unwrap().exit_code, 127);
+    }
+
+    #[test]
+    fn test_mock_shell_records_calls() {
+        let shell = MockShell::new().with_output("git status", "", 0);
+        let calls = shell.calls();
+        shell.execute("git status --short").unwrap();
+        shell.execute("make").unwrap();
+
+        assert_eq!(calls.commands(), vec!["git status --short", "make"]);
+        calls.assert_only(&["git ", "make"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected commands were run: [\"make\"]")]
+    fn test_call_log_reports_unexpected_commands() {
+        let shell = MockShell::new();
+        shell.execute("make").unwrap();
+        shell.calls().assert_only(&["git "]);
+    }
+
+    #[test]
+    fn test_fake_context() {
+        let fake = fake_context()
+            .cwd("project")
+            .dir_entries(["src/main.rs", "target/", "Cargo.toml"])
+            .env("EDITOR", "vi")
+            .history(["cargo test", "cargo build"])
+            .build();
+        let context = fake.context();
+        let root = fake.root().to_path_buf();
+
+        assert_eq!(context.shell().unwrap().cwd().unwrap(), root.join("project"));
+        assert_eq!(*context.dir_entries(Path::new(".")), vec!["Cargo.toml", "src", "target"]);
+        assert!(root.join("project/target").is_dir());
+        assert_eq!(context.shell().unwrap().env("EDITOR").as_deref(), Some("vi"));
+        assert_eq!(context.history().most_recent(1), &["cargo build"]);
+        assert_eq!(context.history().most_recent(2), &["cargo build", "cargo test"]);
+        assert!(fake.calls().commands().is_empty());
+
+        drop(fake);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_fixture_output() {
+        assert!(crate::fixture_output!("git/push_no_upstream").contains("has no upstream branch"));
+    }
+}
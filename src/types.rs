@@ -1,6 +1,13 @@
-/// Core types for the fasterthefuck command correction engine.
+//! Core types for the fasterthefuck command correction engine.
 
+use crate::context::{ProjectType, RuleContext};
+use crate::effects::SideEffect;
+use crate::rules::RuleMetadata;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fmt;
+use std::path::Path;
 
 /// Represents a shell command that needs correction.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,6 +18,12 @@ pub struct Command {
     pub output: String,
     /// Exit code from the command execution
     pub exit_code: i32,
+    /// Whether the middle of a long output was dropped, keeping its head
+    /// and tail (see [`crate::output`])
+    pub output_truncated: bool,
+    /// Whether the output had bytes that aren't UTF-8, replaced by U+FFFD
+    /// (see [`crate::output`])
+    pub output_had_invalid_utf8: bool,
 }
 
 impl Command {
@@ -20,6 +33,8 @@ impl Command {
             script: script.into(),
             output: output.into(),
             exit_code,
+            output_truncated: false,
+            output_had_invalid_utf8: false,
         }
     }
 
@@ -27,6 +42,80 @@ impl Command {
     pub fn script_parts(&self) -> Vec<&str> {
         self.script.split_whitespace().collect()
     }
+
+    /// Gets the first word of the script, the executable it runs unless it
+    /// starts with an assignment or a wrapper such as `sudo`.
+    pub fn executable(&self) -> Option<&str> {
+        self.script.split_whitespace().next()
+    }
+
+    /// Classifies why the command failed based on its exit code.
+    ///
+    /// Exit codes without a conventional meaning are refined from the output
+    /// text (e.g. "command not found" from shells that exit with 1).
+    pub fn failure_kind(&self) -> FailureKind {
+        match self.exit_code {
+            0 => FailureKind::Success,
+            127 => FailureKind::NotFound,
+            126 => FailureKind::NotExecutable,
+            130 => FailureKind::Interrupted,
+            137 => FailureKind::Killed,
+            2 => FailureKind::UsageError,
+            _ => self.failure_kind_from_output(),
+        }
+    }
+
+    fn failure_kind_from_output(&self) -> FailureKind {
+        if self.output.contains("command not found") {
+            return FailureKind::NotFound;
+        }
+
+        // Only the executable itself being denied means it lacks +x; a denied
+        // argument path is an ordinary failure.
+        if let Some(executable) = self.script_parts().first() {
+            if self.output.contains(&format!("{}: Permission denied", executable)) {
+                return FailureKind::NotExecutable;
+            }
+        }
+
+        FailureKind::GenericFailure
+    }
+}
+
+/// Broad classification of a command failure, derived from its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The command exited successfully (0)
+    Success,
+    /// The command was not found (127)
+    NotFound,
+    /// The command was found but could not be executed (126)
+    NotExecutable,
+    /// The command was interrupted with Ctrl-C (130)
+    Interrupted,
+    /// The command was killed with SIGKILL (137)
+    Killed,
+    /// Incorrect usage, as reported by many tools (2)
+    UsageError,
+    /// Any other non-zero exit code
+    GenericFailure,
+}
+
+impl FailureKind {
+    /// Gets an exit code that classifies as this kind, without help from
+    /// the output.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Success => 0,
+            FailureKind::NotFound => 127,
+            FailureKind::NotExecutable => 126,
+            FailureKind::Interrupted => 130,
+            FailureKind::Killed => 137,
+            FailureKind::UsageError => 2,
+            FailureKind::GenericFailure => 1,
+        }
+    }
 }
 
 impl fmt::Display for Command {
@@ -42,14 +131,21 @@ impl fmt::Display for Command {
 }
 
 /// A corrected command with metadata about which rule suggested it.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CorrectedCommand {
     /// The corrected shell command/script
     pub script: String,
     /// Priority for sorting results (lower = higher priority)
     pub priority: i32,
-    /// Optional side effect function name (for executing pre/post hooks)
-    pub side_effect: Option<String>,
+    /// Optional side effect to run before the corrected script
+    pub side_effect: Option<SideEffect>,
+    /// Commands that revert a segment of an `&&` chain, by 0-based segment
+    pub rollbacks: Vec<(usize, String)>,
+    /// Name of the rule that suggested this correction
+    pub rule: Option<String>,
+    /// Group of the rule that suggested this correction, such as `git`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
 }
 
 impl CorrectedCommand {
@@ -59,6 +155,9 @@ impl CorrectedCommand {
             script: script.into(),
             priority,
             side_effect: None,
+            rollbacks: Vec::new(),
+            rule: None,
+            category: None,
         }
     }
 
@@ -66,14 +165,35 @@ impl CorrectedCommand {
     pub fn with_side_effect(
         script: impl Into<String>,
         priority: i32,
-        side_effect: impl Into<String>,
+        side_effect: SideEffect,
     ) -> Self {
         Self {
             script: script.into(),
             priority,
-            side_effect: Some(side_effect.into()),
+            side_effect: Some(side_effect),
+            rollbacks: Vec::new(),
+            rule: None,
+            category: None,
         }
     }
+
+    /// Attaches rollback commands for the segments of an `&&` chain.
+    pub fn with_rollbacks(mut self, rollbacks: Vec<(usize, String)>) -> Self {
+        self.rollbacks = rollbacks;
+        self
+    }
+
+    /// Records the name of the rule that suggested this correction.
+    pub fn with_rule(mut self, rule: impl Into<String>) -> Self {
+        self.rule = Some(rule.into());
+        self
+    }
+
+    /// Records the group of the rule that suggested this correction.
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category.into());
+        self
+    }
 }
 
 impl PartialEq for CorrectedCommand {
@@ -96,6 +216,17 @@ impl Ord for CorrectedCommand {
     }
 }
 
+/// What a rule's check of one of its corrections decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The correction is likely to work
+    Keep,
+    /// The correction may work, but is ranked lower
+    Demote,
+    /// The correction would fail and is not offered
+    Drop,
+}
+
 /// Trait that all rules must implement.
 pub trait Rule: Send + Sync {
     /// The name of the rule (e.g., "git_branch_delete")
@@ -107,6 +238,15 @@ pub trait Rule: Send + Sync {
     /// Returns a list of corrected commands for the given command.
     fn get_new_commands(&self, command: &Command) -> Vec<String>;
 
+    /// Returns the corrected commands, borrowing them from the rule or the
+    /// command where it can instead of allocating each one.
+    ///
+    /// The correction pipeline calls this and only allocates the corrections
+    /// it keeps. The default wraps `get_new_commands`.
+    fn get_new_commands_cow<'a>(&'a self, command: &'a Command) -> Vec<Cow<'a, str>> {
+        self.get_new_commands(command).into_iter().map(Cow::Owned).collect()
+    }
+
     /// Whether this rule is enabled by default.
     fn enabled_by_default(&self) -> bool {
         true
@@ -122,15 +262,146 @@ pub trait Rule: Send + Sync {
         true
     }
 
+    /// Whether this rule needs the whole output rather than its head and
+    /// tail. When any enabled rule does, long outputs aren't capped.
+    fn requires_full_output(&self) -> bool {
+        false
+    }
+
+    /// Whether this rule is a fallback, such as offering `man`, that is
+    /// only tried when no other rule corrects the command.
+    ///
+    /// Fallbacks never outrank a real correction, whatever their priority.
+    /// The default is false.
+    fn is_fallback(&self) -> bool {
+        false
+    }
+
+    /// Gets a substring the script must contain for this rule to match.
+    ///
+    /// Rules that declare one are skipped without calling `matches` when
+    /// the script doesn't contain it. The default is none.
+    fn required_script(&self) -> Option<&str> {
+        None
+    }
+
+    /// Gets the word every script this rule matches starts with, such as
+    /// `git`.
+    ///
+    /// Rules with a leading token are only consulted for commands whose
+    /// [`executable`](Command::executable) it is, so `matches` must be false
+    /// for any other script. The default is none: the rule is consulted for
+    /// every command.
+    fn leading_token(&self) -> Option<&str> {
+        None
+    }
+
+    /// Gets a substring the output must contain for this rule to match.
+    ///
+    /// Like `required_script`, this lets rules be skipped before `matches`
+    /// is called. The default is none.
+    fn required_output(&self) -> Option<&str> {
+        None
+    }
+
+    /// Gets a command that reverts `correction` once it has been executed
+    /// in `cwd`. Called before it runs.
+    ///
+    /// Rules only return an inverse when it is unambiguous and safe; the
+    /// default is no inverse.
+    fn undo_for(&self, _correction: &str, _cwd: &Path) -> Option<String> {
+        None
+    }
+
+    /// Gets the project types this rule is useful in.
+    ///
+    /// Corrections from rules that don't fit the detected project are ranked
+    /// lower, never dropped. The default is every project type.
+    fn relevant_projects(&self) -> &[ProjectType] {
+        ProjectType::ALL
+    }
+
+    /// Gets the side effect to run before `correction`.
+    ///
+    /// The default is no side effect.
+    fn side_effect_for(&self, _command: &Command, _correction: &str) -> Option<SideEffect> {
+        None
+    }
+
+    /// Gets commands that revert segments of `correction`, an `&&` chain,
+    /// as (0-based segment, command) pairs.
+    ///
+    /// If a later segment fails, the completed segments are rolled back in
+    /// reverse order. The default is no rollbacks.
+    fn rollbacks_for(&self, _correction: &str) -> Vec<(usize, String)> {
+        Vec::new()
+    }
+
+    /// Gets a command this rule matches, made from its stored patterns, so
+    /// property tests can check its corrections without a hand-written
+    /// failure.
+    ///
+    /// Rules built with the builders make one when their patterns allow it;
+    /// the default is none.
+    fn sample_matching_command(&self) -> Option<Command> {
+        None
+    }
+
+    /// Describes what this rule matches and corrects, for `ftf rules export`.
+    ///
+    /// The default reports the name, priority and required substrings; rules
+    /// add a description and an example.
+    fn metadata(&self) -> RuleMetadata {
+        RuleMetadata::of(self)
+    }
+
+    /// Gets the group the rule was built in, such as `git`.
+    ///
+    /// Set for rules built by a [`RuleFactory`](crate::rules::RuleFactory);
+    /// the default is none.
+    fn category(&self) -> Option<&str> {
+        None
+    }
+
+    /// Checks that `correction` is likely to succeed, e.g. that a directory
+    /// it changes into exists.
+    ///
+    /// Called after matching, and only when a context is available. Checks
+    /// should be cheap; the default keeps every correction.
+    fn verify(&self, _correction: &CorrectedCommand, _context: &RuleContext) -> Verdict {
+        Verdict::Keep
+    }
+
     /// Gets corrected commands with priority and metadata.
+    ///
+    /// A script suggested more than once keeps its first priority. Blank
+    /// scripts, and the failed script itself unless a side effect runs
+    /// first, are left out, as running them would change nothing.
     fn get_corrected_commands(&self, command: &Command) -> Vec<CorrectedCommand> {
-        self.get_new_commands(command)
-            .into_iter()
-            .enumerate()
-            .map(|(i, script)| {
-                CorrectedCommand::new(script, (i as i32 + 1) * self.priority())
-            })
-            .collect()
+        let candidates = self.get_new_commands_cow(command);
+        // Repeats are found while the candidates are borrowed, so the kept
+        // ones can still be moved into their corrections
+        let mut seen = HashSet::with_capacity(candidates.len());
+        let first: Vec<bool> = candidates.iter().map(|script| seen.insert(script.as_ref())).collect();
+        let mut corrected: Vec<CorrectedCommand> = Vec::with_capacity(candidates.len());
+        for ((i, script), first) in candidates.into_iter().enumerate().zip(first) {
+            if !first || script.trim().is_empty() {
+                continue;
+            }
+            let priority = (i as i32 + 1) * self.priority();
+            let rollbacks = self.rollbacks_for(&script);
+            let correction = match self.side_effect_for(command, &script) {
+                Some(effect) => CorrectedCommand::with_side_effect(script, priority, effect),
+                None if script == command.script => continue,
+                None => CorrectedCommand::new(script, priority),
+            };
+            let correction = correction.with_rollbacks(rollbacks).with_rule(self.name());
+            corrected.push(match self.category() {
+                Some(category) => correction.with_category(category),
+                None => correction,
+            });
+        }
+        corrected
     }
 }
 
@@ -155,10 +426,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_failure_kind_success() {
+        let cmd = Command::new("ls", "", 0);
+        assert_eq!(cmd.failure_kind(), FailureKind::Success);
+    }
+
+    #[test]
+    fn test_failure_kind_not_found() {
+        let cmd = Command::new("gti status", "bash: gti: command not found", 127);
+        assert_eq!(cmd.failure_kind(), FailureKind::NotFound);
+
+        // Some shells exit with 1 but still report the missing command
+        let cmd = Command::new("gti status", "zsh: command not found: gti", 1);
+        assert_eq!(cmd.failure_kind(), FailureKind::NotFound);
+    }
+
+    #[test]
+    fn test_failure_kind_not_executable() {
+        let cmd = Command::new("./run.sh", "bash: ./run.sh: Permission denied", 126);
+        assert_eq!(cmd.failure_kind(), FailureKind::NotExecutable);
+
+        let cmd = Command::new("./run.sh", "sh: ./run.sh: Permission denied", 1);
+        assert_eq!(cmd.failure_kind(), FailureKind::NotExecutable);
+    }
+
+    #[test]
+    fn test_failure_kind_exit_codes_round_trip() {
+        let kinds = [
+            FailureKind::Success,
+            FailureKind::NotFound,
+            FailureKind::NotExecutable,
+            FailureKind::Interrupted,
+            FailureKind::Killed,
+            FailureKind::UsageError,
+            FailureKind::GenericFailure,
+        ];
+        for kind in kinds {
+            assert_eq!(Command::new("make", "", kind.exit_code()).failure_kind(), kind);
+        }
+    }
+
+    #[test]
+    fn test_failure_kind_permission_denied_on_argument() {
+        let cmd = Command::new("cat /etc/shadow", "cat: /etc/shadow: Permission denied", 1);
+        assert_eq!(cmd.failure_kind(), FailureKind::GenericFailure);
+    }
+
+    #[test]
+    fn test_failure_kind_interrupted() {
+        let cmd = Command::new("sleep 100", "", 130);
+        assert_eq!(cmd.failure_kind(), FailureKind::Interrupted);
+    }
+
+    #[test]
+    fn test_failure_kind_killed() {
+        let cmd = Command::new("cargo build", "Killed", 137);
+        assert_eq!(cmd.failure_kind(), FailureKind::Killed);
+    }
+
+    #[test]
+    fn test_failure_kind_usage_error() {
+        let cmd = Command::new("ls --bogus", "ls: unrecognized option '--bogus'", 2);
+        assert_eq!(cmd.failure_kind(), FailureKind::UsageError);
+    }
+
+    #[test]
+    fn test_failure_kind_generic_failure() {
+        let cmd = Command::new("false", "", 1);
+        assert_eq!(cmd.failure_kind(), FailureKind::GenericFailure);
+    }
+
     #[test]
     fn test_corrected_command_priority() {
         let cmd1 = CorrectedCommand::new("cmd1", 100);
         let cmd2 = CorrectedCommand::new("cmd2", 50);
         assert!(cmd2 < cmd1);
     }
+
+    #[test]
+    fn test_corrected_command_with_rule() {
+        let cmd = CorrectedCommand::new("git push --force-with-lease", 700).with_rule("git_push_force");
+        assert_eq!(cmd.rule.as_deref(), Some("git_push_force"));
+        // The originating rule does not affect equality
+        assert_eq!(cmd, CorrectedCommand::new("git push --force-with-lease", 100));
+    }
 }
@@ -0,0 +1,251 @@
+//! Undoing corrections executed with `--execute`.
+//!
+//! Rules may declare an inverse for the corrections they produce via
+//! `Rule::undo_for`. When ftf executes such a correction, the inverse is
+//! stored in the session cache and `ftf undo` can print or run it later.
+//! Only a small set of commands with an unambiguous, safe inverse is covered.
+
+use crate::{Error, Result, Script, SessionContext};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File in the session directory holding the most recent undo record.
+const UNDO_FILE: &str = "undo.json";
+
+/// Prints the newest stash, or nothing if there is none.
+pub const STASH_PROBE: &str = "git rev-parse -q --verify refs/stash";
+
+/// An executed correction together with the command that reverts it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoRecord {
+    /// The correction ftf executed
+    pub command: String,
+    /// The command that reverts it
+    pub inverse: String,
+}
+
+impl UndoRecord {
+    /// Creates a new undo record.
+    pub fn new(command: impl Into<String>, inverse: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            inverse: inverse.into(),
+        }
+    }
+
+    /// Stores this record as the most recent undoable command in `session_dir`.
+    pub fn save(&self, session_dir: &Path) -> Result<()> {
+        SessionContext::create_dir(session_dir)?;
+        let json = serde_json::to_string(self).map_err(|e| Error::Other(e.to_string()))?;
+        std::fs::write(session_dir.join(UNDO_FILE), json)?;
+        Ok(())
+    }
+
+    /// Loads the most recent undo record from `session_dir`, if any.
+    pub fn load(session_dir: &Path) -> Option<Self> {
+        let json = std::fs::read_to_string(session_dir.join(UNDO_FILE)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Removes any stored undo record, e.g. after executing a command without an inverse.
+    pub fn clear(session_dir: &Path) -> Result<()> {
+        match std::fs::remove_file(session_dir.join(UNDO_FILE)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Computes the inverse of `script`, about to run in `cwd`, if every part of
+/// it can be safely reverted.
+///
+/// `a && b` chains are inverted part by part in reverse order, leaving out
+/// parts that change nothing. Call this before `script` runs, as what it
+/// does can depend on the files in `cwd`.
+pub fn inverse_command(script: &str, cwd: &Path) -> Option<String> {
+    let mut inverses = Script::split_chain(script)
+        .into_iter()
+        .map(|part| inverse_single(part, cwd))
+        .collect::<Option<Vec<_>>>()?;
+    inverses.retain(|inverse| !inverse.is_empty());
+    if inverses.is_empty() {
+        return None;
+    }
+    inverses.reverse();
+
+    Some(inverses.join(" && "))
+}
+
+/// Gets a command whose output changes when `segment` changes something,
+/// for commands that succeed even when they do nothing. `git stash` with no
+/// local changes saves nothing, and popping after it would apply an older,
+/// unrelated stash, so its inverse must only run if the probe changed.
+pub fn change_probe(segment: &str) -> Option<&'static str> {
+    let parsed = Script::parse(segment);
+    let tokens: Vec<&str> = parsed.tokens().iter().map(String::as_str).collect();
+    match tokens.as_slice() {
+        ["git", "stash"] | ["git", "stash", "push"] => Some(STASH_PROBE),
+        _ => None,
+    }
+}
+
+/// Inverts a single command without `&&`, or gives an empty inverse if it
+/// changes nothing.
+fn inverse_single(script: &str, cwd: &Path) -> Option<String> {
+    let parsed = Script::parse(script);
+    let tokens: Vec<&str> = parsed.tokens().iter().map(String::as_str).collect();
+
+    match tokens.as_slice() {
+        // Only recorded when the stash saved something, see `change_probe`
+        ["git", "stash"] | ["git", "stash", "push"] => Some("git stash pop".to_string()),
+        ["git", "checkout" | "switch", target] if !target.starts_with('-') => {
+            Some("git checkout -".to_string())
+        }
+        ["mkdir", "-p", dir] | ["mkdir", dir] if !dir.starts_with('-') => {
+            // Only the directories it creates are removed again
+            let created = missing_dirs(dir, cwd)?;
+            if created.is_empty() {
+                Some(String::new())
+            } else {
+                Some(Script::from_tokens(std::iter::once("rmdir").chain(created)).to_string())
+            }
+        }
+        ["mv", source, target] if !source.starts_with('-') && !target.starts_with('-') => {
+            // `mv a backup` moves into `backup` if it's a directory already
+            let moved_to = if target.ends_with('/') || is_dir(&cwd.join(target))? {
+                let name = source.trim_end_matches('/').rsplit('/').next()?;
+                format!("{}/{}", target.trim_end_matches('/'), name)
+            } else {
+                target.to_string()
+            };
+            Some(Script::from_tokens(["mv", moved_to.as_str(), *source]).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Gets `dir` and those of its parents that don't exist in `cwd` yet,
+/// deepest first, or None if that can't be told.
+fn missing_dirs<'a>(dir: &'a str, cwd: &Path) -> Option<Vec<&'a str>> {
+    let mut missing = Vec::new();
+    let mut path = dir.trim_end_matches('/');
+    while !path.is_empty() && !is_dir(&cwd.join(path))? {
+        missing.push(path);
+        match path.rsplit_once('/') {
+            Some((parent, _)) => path = parent.trim_end_matches('/'),
+            None => break,
+        }
+    }
+    Some(missing)
+}
+
+/// Checks whether `path` is a directory, or None if that can't be told,
+/// such as for a path the shell would still expand.
+fn is_dir(path: &Path) -> Option<bool> {
+    if path.to_str().is_none_or(|path| path.contains(['~', '$', '*', '?', '['])) {
+        return None;
+    }
+    match std::fs::metadata(path) {
+        Ok(metadata) => Some(metadata.is_dir()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Some(false),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory that doesn't exist, so no target is a directory in it.
+    fn nowhere() -> &'static Path {
+        Path::new("/nonexistent/ftf")
+    }
+
+    #[test]
+    fn test_inverse_mv() {
+        assert_eq!(inverse_command("mv a.txt b.txt", nowhere()).as_deref(), Some("mv b.txt a.txt"));
+        assert_eq!(
+            inverse_command("mv notes.md backup/", nowhere()).as_deref(),
+            Some("mv backup/notes.md notes.md")
+        );
+        assert_eq!(inverse_command("mv -f a b", nowhere()), None);
+    }
+
+    #[test]
+    fn test_inverse_mv_into_existing_directory() {
+        let dir = std::env::temp_dir().join(format!("ftf_undo_mv_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("backup")).unwrap();
+
+        // Renaming `backup` over `a.txt` would lose the directory
+        assert_eq!(inverse_command("mv a.txt backup", &dir).as_deref(), Some("mv backup/a.txt a.txt"));
+        assert_eq!(inverse_command("mv a.txt other", &dir).as_deref(), Some("mv other a.txt"));
+        // Paths the shell expands can't be checked
+        assert_eq!(inverse_command("mv a.txt ~/backup", &dir), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_inverse_mkdir_removes_only_what_it_created() {
+        let dir = std::env::temp_dir().join(format!("ftf_undo_mkdir_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+
+        assert_eq!(inverse_command("mkdir build", &dir).as_deref(), Some("rmdir build"));
+        assert_eq!(
+            inverse_command("mkdir -p logs/2024/jan/", &dir).as_deref(),
+            Some("rmdir logs/2024/jan logs/2024")
+        );
+        // Directories that were there already are kept
+        assert_eq!(inverse_command("mkdir -p logs", &dir), None);
+        assert_eq!(inverse_command("mkdir -p logs && mv a.txt logs", &dir).as_deref(), Some("mv logs/a.txt a.txt"));
+        assert_eq!(inverse_command("mkdir -p ~/logs", &dir), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_inverse_git_stash() {
+        assert_eq!(inverse_command("git stash", nowhere()).as_deref(), Some("git stash pop"));
+        assert_eq!(
+            inverse_command("git stash && git checkout main", nowhere()).as_deref(),
+            Some("git checkout - && git stash pop")
+        );
+    }
+
+    #[test]
+    fn test_change_probe() {
+        assert_eq!(change_probe("git stash"), Some(STASH_PROBE));
+        assert_eq!(change_probe("git stash push"), Some(STASH_PROBE));
+        assert_eq!(change_probe("git stash pop"), None);
+        assert_eq!(change_probe("mv a b"), None);
+    }
+
+    #[test]
+    fn test_no_inverse() {
+        assert_eq!(inverse_command("git branch -D feature", nowhere()), None);
+        assert_eq!(inverse_command("git commit --amend --no-edit", nowhere()), None);
+        // One part without an inverse makes the whole chain irreversible
+        assert_eq!(inverse_command("git stash && git push --force-with-lease", nowhere()), None);
+    }
+
+    #[test]
+    fn test_undo_record_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ftf_undo_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let record = UndoRecord::new("mv a b", "mv b a");
+        record.save(&dir).unwrap();
+        assert_eq!(UndoRecord::load(&dir), Some(record));
+
+        UndoRecord::clear(&dir).unwrap();
+        assert_eq!(UndoRecord::load(&dir), None);
+        // Clearing twice is fine
+        UndoRecord::clear(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_nothing_to_undo() {
+        assert_eq!(UndoRecord::load(Path::new("/nonexistent/ftf/session")), None);
+    }
+}
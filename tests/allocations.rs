@@ -0,0 +1,118 @@
+//! Counts the allocations made while turning a rule's suggestions into
+//! corrections, with a global allocator that counts on this thread only.
+
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Rule, RuleContext, RuleRegistry};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::sync::Arc;
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Counts the allocations `f` makes on this thread.
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    std::hint::black_box(f());
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+const SUGGESTIONS: &[&str] = &["git push", "git pull", "git push"];
+const REPEATS: usize = 100;
+
+/// Suggests the same few commands many times over, borrowing each.
+struct Borrowing;
+
+impl Rule for Borrowing {
+    fn name(&self) -> &str {
+        "borrowing"
+    }
+
+    fn matches(&self, _command: &Command) -> bool {
+        true
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        self.get_new_commands_cow(command).into_iter().map(Cow::into_owned).collect()
+    }
+
+    fn get_new_commands_cow<'a>(&'a self, _command: &'a Command) -> Vec<Cow<'a, str>> {
+        SUGGESTIONS.iter().cycle().take(SUGGESTIONS.len() * REPEATS).map(|&s| Cow::Borrowed(s)).collect()
+    }
+}
+
+/// The same suggestions, allocated one by one.
+struct Owning;
+
+impl Rule for Owning {
+    fn name(&self) -> &str {
+        "owning"
+    }
+
+    fn matches(&self, _command: &Command) -> bool {
+        true
+    }
+
+    fn get_new_commands(&self, command: &Command) -> Vec<String> {
+        Borrowing.get_new_commands(command)
+    }
+}
+
+#[test]
+fn test_only_kept_corrections_are_allocated() {
+    let cmd = Command::new("git psuh", "", 1);
+    assert_eq!(Borrowing.get_corrected_commands(&cmd), Owning.get_corrected_commands(&cmd));
+    assert_eq!(Borrowing.get_corrected_commands(&cmd).len(), 2);
+
+    let borrowed = allocations(|| Borrowing.get_corrected_commands(&cmd));
+    let owned = allocations(|| Owning.get_corrected_commands(&cmd));
+    // Two kept corrections: their scripts, rule names and the vectors
+    assert!(borrowed <= 10, "{} allocations", borrowed);
+    assert!(owned >= SUGGESTIONS.len() * REPEATS, "{} allocations", owned);
+}
+
+/// Builds the built-in rules and keeps only the one called `name`.
+fn builtin_rule(name: &str) -> RuleRegistry {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    registry.retain(|rule| rule.name() == name);
+    assert_eq!(registry.len(), 1, "no built-in rule {}", name);
+    registry
+}
+
+#[test]
+fn test_builtin_retry_is_borrowed() {
+    let registry = builtin_rule("mv_to_directory");
+    let rule = &registry.rules()[0];
+    let cmd = Command::new(
+        "mv notes.txt archive/2024/",
+        "mv: cannot move 'notes.txt' to 'archive/2024/': No such file or directory",
+        1,
+    );
+    let suggestions = rule.get_new_commands_cow(&cmd);
+    assert!(matches!(suggestions.as_slice(), [Cow::Borrowed(script)] if *script == cmd.script));
+
+    let borrowed = allocations(|| rule.get_new_commands_cow(&cmd));
+    let owned = allocations(|| rule.get_new_commands(&cmd));
+    // Matching allocates either way; only the owned suggestion copies the script
+    assert_eq!(owned, borrowed + 1, "{} allocations borrowed, {} owned", borrowed, owned);
+}
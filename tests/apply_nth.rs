@@ -0,0 +1,56 @@
+//! Integration tests for `ftf --apply-nth`.
+
+use fasterthefuck::selection::CandidateList;
+use fasterthefuck::CorrectedCommand;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Runs `ftf --apply-nth n` for `script`, with `dir` as home and session
+/// directory.
+fn apply_nth(dir: &Path, script: &str, n: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["--apply-nth", n, "--command", script, "--output", "", "--exit-code", "1"])
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_DATA_HOME", dir)
+        .env("XDG_RUNTIME_DIR", dir)
+        .env("FTF_SESSION_DIR", dir.join("session"))
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+/// Creates a home whose session has a list for `git psuh`.
+fn home_with_list(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ftf_apply_nth_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let corrections = [
+        CorrectedCommand::new("git push", 100).with_rule("git_not_command"),
+        CorrectedCommand::new("git pull", 200).with_rule("git_not_command"),
+    ];
+    CandidateList::new("git psuh", &corrections, 1_700_000_000).save(&dir.join("session")).unwrap();
+    dir
+}
+
+#[test]
+fn test_applies_the_nth_cached_candidate() {
+    let dir = home_with_list("applies");
+    let output = apply_nth(&dir, "git psuh", "2");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "git pull");
+}
+
+#[test]
+fn test_stale_list_and_out_of_range_fail() {
+    let dir = home_with_list("fails");
+    let stale = apply_nth(&dir, "git cmomit", "1");
+    let out_of_range = apply_nth(&dir, "git psuh", "3");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!stale.status.success());
+    assert!(String::from_utf8_lossy(&stale.stderr).contains("another command than 'git cmomit'"));
+    assert!(!out_of_range.status.success());
+    assert!(String::from_utf8_lossy(&out_of_range.stderr).contains("no candidate 3"));
+}
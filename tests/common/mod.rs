@@ -0,0 +1,37 @@
+//! Helpers shared by the integration tests.
+
+// Every test crate includes this module, and most use only part of it
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Gets a command running ftf in `home`, with every directory it reads or
+/// writes inside it, so tests never see the user's config, data or session.
+pub fn ftf(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"));
+    command
+        .current_dir(home)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home)
+        .env("XDG_DATA_HOME", home)
+        .env("XDG_RUNTIME_DIR", home)
+        .env_remove("FTF_SESSION_DIR")
+        .stdin(Stdio::null());
+    command
+}
+
+/// Creates an empty home for the test `name` of the test crate `prefix`,
+/// deleting what an earlier run with the same process id left there.
+pub fn temp_home(prefix: &str, name: &str) -> PathBuf {
+    let home = std::env::temp_dir().join(format!("ftf_{}_{}_{}", prefix, name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    home
+}
+
+/// Writes `config` as ftf's config file in `home`.
+pub fn write_config(home: &Path, config: &str) {
+    std::fs::create_dir_all(home.join("fasterthefuck")).unwrap();
+    std::fs::write(home.join("fasterthefuck/config.toml"), config).unwrap();
+}
@@ -0,0 +1,61 @@
+//! Integration tests for config diagnostics and `ftf config validate`.
+
+mod common;
+
+use assert_cmd::Command;
+
+const TYPO: &str = "[rules.git_push_setupstream]\npriority = 100\n";
+const CORRECT: [&str; 7] = ["--command", "true", "--output", "", "--exit-code", "1", "--no-interaction"];
+const WARNING: &str = "ftf: warning: [rules.git_push_setupstream] names no rule; did you mean git_push_set_upstream?\n";
+
+#[test]
+fn test_clean_config_validates() {
+    let home = common::temp_home("config_validate", "clean");
+    common::write_config(&home, "[rules.mkdir_p]\nenabled = false\n");
+    Command::from_std(common::ftf(&home))
+        .args(["--no-interaction", "config", "validate"])
+        .assert()
+        .code(0)
+        .stdout("no problems found in the configuration\n")
+        .stderr("");
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_typo_is_warned_about_once() {
+    let home = common::temp_home("config_validate", "typo");
+    common::write_config(&home, TYPO);
+    let output = Command::from_std(common::ftf(&home)).args(CORRECT).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).matches(WARNING).count(), 1);
+    let validate = ["--no-interaction", "config", "validate"];
+    Command::from_std(common::ftf(&home)).args(validate).assert().code(0).stdout("").stderr(WARNING);
+
+    common::write_config(&home, &format!("[global]\nquiet_config_warnings = true\n\n{}", TYPO));
+    let output = Command::from_std(common::ftf(&home)).args(CORRECT).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("names no rule"));
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_invalid_pack_fails_validation() {
+    let home = common::temp_home("config_validate", "pack");
+    common::write_config(&home, "[rule_packs.team]\nurl = \"file:///team.toml\"\n");
+    std::fs::create_dir_all(home.join("fasterthefuck/packs")).unwrap();
+    std::fs::write(
+        home.join("fasterthefuck/packs/team.toml"),
+        "[[rules]]\nname = \"deploy\"\ncommand = '^deplyo ('\nreplace = \"deploy\"\n",
+    )
+    .unwrap();
+    let output = Command::from_std(common::ftf(&home)).args(["--no-interaction", "config", "validate"]).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(
+        stderr.starts_with("ftf: error: rule pack 'team': rule 'deploy' ([[rules]] table 1): invalid command pattern"),
+        "stderr: {}",
+        stderr
+    );
+    std::fs::remove_dir_all(&home).unwrap();
+}
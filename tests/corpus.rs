@@ -0,0 +1,97 @@
+//! Integration tests for the recorded failures in tests/corpus/.
+//!
+//! Every case runs through the full default registry. Adding a case is a
+//! change to the TOML files alone.
+
+use fasterthefuck::corpus::{self, CorpusCase};
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, RuleContext, RuleRegistry};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Minimum number of cases, so a corpus that fails to load isn't mistaken
+/// for one that passes.
+const MIN_CASES: usize = 40;
+
+fn corrector() -> Corrector {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    Corrector::new(registry)
+}
+
+fn cases() -> Vec<(String, CorpusCase)> {
+    corpus::read_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")).expect("corpus not readable")
+}
+
+#[test]
+fn test_corpus_cases() {
+    let corrector = corrector();
+    let cases = cases();
+    assert!(cases.len() >= MIN_CASES, "only {} corpus cases", cases.len());
+
+    let mut failures = Vec::new();
+    for (file, case) in &cases {
+        let corrections = corrector.get_corrections(&Command::new(&case.script, &case.output, case.exit_code));
+        let scripts: Vec<&str> = corrections.iter().map(|correction| correction.script.as_str()).collect();
+        if let Err(mismatch) = case.check(&scripts) {
+            failures.push(format!("{}: `{}`: {}", file, case.script, mismatch));
+        }
+    }
+    assert!(failures.is_empty(), "{} corpus cases failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn test_corpus_cases_expect_something() {
+    for (file, case) in cases() {
+        assert!(
+            case.expected_top.is_some() || case.expected_contains.is_some() || !case.must_not_suggest.is_empty(),
+            "{}: `{}` expects nothing",
+            file,
+            case.script
+        );
+    }
+}
+
+const NOT_A_GIT_COMMAND: &str = "git: 'pus' is not a git command.\n\nThe most similar command is\n\tpush\n";
+
+#[test]
+fn test_corpus_record_appends_the_top_correction() {
+    let home = std::env::temp_dir().join(format!("ftf_corpus_record_{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let file = home.join("corpus.toml");
+    let record = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+            .args(["--command", "git pus", "--output", NOT_A_GIT_COMMAND, "--exit-code", "1"])
+            .args(["corpus", "record", "--file"])
+            .arg(&file)
+            .args(extra)
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", &home)
+            .output()
+            .unwrap()
+    };
+
+    let output = record(&[]);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let output = record(&["--accepted", "git push --dry-run"]);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+
+    let cases = corpus::parse(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(cases.len(), 2);
+    assert_eq!(cases[0].script, "git pus");
+    assert_eq!(cases[0].exit_code, 1);
+    assert_eq!(cases[0].expected_top.as_deref(), Some("git push"));
+    assert_eq!(cases[1].expected_top.as_deref(), Some("git push --dry-run"));
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_corpus_record_needs_a_command() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["corpus", "record", "--file", "/nonexistent/corpus.toml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs --command"));
+}
@@ -0,0 +1,130 @@
+//! Integration tests for `ftf daemon` and forwarding corrections to it.
+
+mod common;
+
+use common::ftf;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+const GIT_OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+fn socket(dir: &Path) -> PathBuf {
+    dir.join("fasterthefuck").join("daemon.sock")
+}
+
+/// Starts the daemon and waits for its socket.
+fn start_daemon(dir: &Path) -> Child {
+    let child = ftf(dir).arg("daemon").stderr(Stdio::null()).spawn().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while UnixStream::connect(socket(dir)).is_err() {
+        assert!(Instant::now() < deadline, "daemon didn't start");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    child
+}
+
+/// Terminates the daemon the way a service manager would.
+fn stop_daemon(mut child: Child) {
+    Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+fn correct(dir: &Path, extra: &[&str]) -> Output {
+    ftf(dir)
+        .args(["--no-interaction", "--command", "git pus", "--output", GIT_OUTPUT, "--exit-code", "1"])
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+/// Sends one request line and reads the response line.
+fn send(stream: &mut UnixStream, request: &str) -> serde_json::Value {
+    stream.write_all(format!("{}\n", request).as_bytes()).unwrap();
+    let mut line = String::new();
+    BufReader::new(&*stream).read_line(&mut line).unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+#[test]
+fn test_daemon_answers_requests_and_cleans_up() {
+    let dir = std::env::temp_dir().join(format!("ftf_daemon_it_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let daemon = start_daemon(&dir);
+
+    // Several requests on one connection
+    let mut stream = UnixStream::connect(socket(&dir)).unwrap();
+    let request = serde_json::json!({
+        "version": 2,
+        "script": "git pus",
+        "output": GIT_OUTPUT,
+        "exit_code": 1,
+        "cwd": dir,
+    });
+    for _ in 0..2 {
+        let response = send(&mut stream, &request.to_string());
+        assert_eq!(response["version"], 2);
+        assert_eq!(response["corrections"][0]["script"], "git push");
+    }
+    let response = send(&mut stream, r#"{"version":99}"#);
+    assert_eq!(response["error"], "unsupported protocol version 99, expected 2");
+    drop(stream);
+
+    // The CLI forwards to it
+    let output = correct(&dir, &["--use-daemon"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "git push");
+    assert!(!stderr.contains("daemon unavailable"), "stderr: {}", stderr);
+
+    stop_daemon(daemon);
+    assert!(!socket(&dir).exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unreachable_daemon_falls_back_to_in_process() {
+    let dir = std::env::temp_dir().join(format!("ftf_daemon_fallback_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = correct(&dir, &["--use-daemon"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "git push");
+    assert!(stderr.contains("daemon unavailable"), "stderr: {}", stderr);
+
+    // Without --use-daemon a missing socket isn't worth mentioning
+    let output = correct(&dir, &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "git push");
+    assert!(output.stderr.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_daemon_is_skipped_when_the_config_differs() {
+    let dir = std::env::temp_dir().join(format!("ftf_daemon_config_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("fasterthefuck")).unwrap();
+    let daemon = start_daemon(&dir);
+    let disabled = "[rules.git_not_command]\nenabled = false\n";
+
+    // A config file given on the command line never reaches the daemon
+    let config = dir.join("other.toml");
+    std::fs::write(&config, disabled).unwrap();
+    let output = correct(&dir, &["--use-daemon", "--config", config.to_str().unwrap()]);
+    assert!(output.stdout.is_empty(), "stdout: {}", String::from_utf8_lossy(&output.stdout));
+    assert!(output.stderr.is_empty(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+
+    // Nor does one changed since the daemon started
+    std::fs::write(dir.join("fasterthefuck").join("config.toml"), disabled).unwrap();
+    let output = correct(&dir, &[]);
+    assert!(output.stdout.is_empty(), "stdout: {}", String::from_utf8_lossy(&output.stdout));
+    let output = correct(&dir, &["--use-daemon"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("runs with another configuration"), "stderr: {}", stderr);
+
+    stop_daemon(daemon);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,192 @@
+//! Integration tests for running chained corrections step by step.
+
+use fasterthefuck::execution::Step;
+use fasterthefuck::rules::git;
+use fasterthefuck::undo::STASH_PROBE;
+use fasterthefuck::{BashShell, Command, CorrectedCommand, ExecutionPlan, Shell, ShellOutput, SideEffect};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A shell that records every command it runs and fails the ones it was told to.
+///
+/// `git stash` saves local changes unless there are none, which the stash
+/// probe shows; probes aren't recorded.
+#[derive(Default)]
+struct RecordingShell {
+    failing: Vec<&'static str>,
+    no_local_changes: bool,
+    executed: Mutex<Vec<String>>,
+    stashes: Mutex<usize>,
+}
+
+impl RecordingShell {
+    fn failing(commands: &[&'static str]) -> Self {
+        Self {
+            failing: commands.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    fn without_local_changes(mut self) -> Self {
+        self.no_local_changes = true;
+        self
+    }
+
+    fn executed(&self) -> Vec<String> {
+        self.executed.lock().unwrap().clone()
+    }
+}
+
+impl Shell for RecordingShell {
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    fn execute(&self, command: &str) -> fasterthefuck::Result<ShellOutput> {
+        let mut stashes = self.stashes.lock().unwrap();
+        if command == STASH_PROBE {
+            let stdout = format!("stash{}\n", stashes);
+            return Ok(ShellOutput::new(command.to_string(), stdout, String::new(), 0));
+        }
+        if command == "git stash" && !self.no_local_changes {
+            *stashes += 1;
+        }
+        self.executed.lock().unwrap().push(command.to_string());
+        if self.failing.contains(&command) {
+            let stderr = "error: cannot pull with rebase: You have unstaged changes.\n";
+            Ok(ShellOutput::new(command.to_string(), String::new(), stderr.to_string(), 128))
+        } else {
+            Ok(ShellOutput::new(command.to_string(), format!("ran {}\n", command), String::new(), 0))
+        }
+    }
+
+    fn cwd(&self) -> fasterthefuck::Result<PathBuf> {
+        Ok(std::env::temp_dir())
+    }
+
+    fn set_cwd(&mut self, _path: PathBuf) -> fasterthefuck::Result<()> {
+        Ok(())
+    }
+
+    fn env(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    fn set_env(&mut self, _key: String, _value: String) -> fasterthefuck::Result<()> {
+        Ok(())
+    }
+
+    fn command_exists(&self, _command: &str) -> fasterthefuck::Result<bool> {
+        Ok(true)
+    }
+}
+
+#[test]
+fn test_chain_stops_at_failing_step() {
+    let shell = RecordingShell::failing(&["git pull --rebase"]);
+    let plan = ExecutionPlan::from_script("git stash && git pull --rebase && git stash pop");
+
+    let report = plan.run(&shell);
+
+    assert_eq!(shell.executed(), vec!["git stash", "git pull --rebase"]);
+    let failure = report.failure.as_ref().unwrap();
+    assert_eq!(failure.index, 1);
+    assert_eq!(failure.step, Step::Command("git pull --rebase".to_string()));
+    assert!(report.outputs[1].stderr.contains("unstaged changes"));
+    assert_eq!(report.exit_code(), 128);
+}
+
+#[test]
+fn test_chain_runs_every_step() {
+    let shell = RecordingShell::default();
+    let plan = ExecutionPlan::from_script("git stash && git pull --rebase && git stash pop");
+
+    let report = plan.run(&shell);
+
+    assert!(report.success());
+    assert_eq!(shell.executed(), vec!["git stash", "git pull --rebase", "git stash pop"]);
+    assert_eq!(report.outputs.len(), 3);
+}
+
+#[test]
+fn test_failed_side_effect_skips_commands() {
+    let shell = RecordingShell::default();
+    let missing = std::env::temp_dir().join(format!("ftf_execution_missing_{}.conf", std::process::id()));
+    let correction =
+        CorrectedCommand::with_side_effect("nginx -t", 100, SideEffect::BackupFile(missing));
+
+    let report = ExecutionPlan::new(&correction).run(&shell);
+
+    assert!(shell.executed().is_empty());
+    assert!(matches!(report.failure.unwrap().step, Step::SideEffect(_)));
+}
+
+#[test]
+fn test_stash_pull_pop_rolls_back_stash() {
+    let rule = git::git_staging_rules()
+        .into_iter()
+        .find(|rule| rule.name() == "git_stash")
+        .unwrap();
+    let cmd = Command::new(
+        "git pull",
+        "error: Your local changes to the following files would be overwritten by merge:\n\tCargo.lock\nPlease commit your changes or stash them before you merge.\nAborting\n",
+        1,
+    );
+    let correction = rule.get_corrected_commands(&cmd).remove(0);
+    let plan = ExecutionPlan::new(&correction);
+    let shell = RecordingShell::failing(&["git pull"]);
+
+    let report = plan.run(&shell);
+
+    // The chain's own `git stash pop` never ran, so the rollback restores the changes
+    assert_eq!(shell.executed(), vec!["git stash", "git pull"]);
+    let rollbacks = plan.rollbacks_after(&report);
+    assert_eq!(rollbacks, vec!["git stash pop"]);
+
+    assert!(ExecutionPlan::from_commands(&rollbacks).run(&shell).success());
+    assert_eq!(shell.executed(), vec!["git stash", "git pull", "git stash pop"]);
+}
+
+#[test]
+fn test_stash_that_saved_nothing_is_not_popped() {
+    let rule = git::git_staging_rules()
+        .into_iter()
+        .find(|rule| rule.name() == "git_stash")
+        .unwrap();
+    let cmd = Command::new("git pull", "Please commit your changes or stash them before you merge.\n", 1);
+    let correction = rule.get_corrected_commands(&cmd).remove(0);
+    let plan = ExecutionPlan::new(&correction);
+
+    // Popping would apply an older stash, so the chain's own pop is skipped
+    let shell = RecordingShell::default().without_local_changes();
+    let report = plan.run(&shell);
+    assert!(report.success());
+    assert_eq!(shell.executed(), vec!["git stash", "git pull"]);
+    assert_eq!((report.unchanged, report.skipped), (vec![0], vec![2]));
+
+    // And so is the rollback
+    let shell = RecordingShell::failing(&["git pull"]).without_local_changes();
+    let report = plan.run(&shell);
+    assert!(plan.rollbacks_after(&report).is_empty());
+}
+
+#[test]
+fn test_chain_with_cd_runs_in_one_shell() {
+    let recording = RecordingShell::default();
+    let plan = ExecutionPlan::from_script("cd api && cargo build");
+
+    assert!(!plan.is_chained());
+    assert!(plan.run(&recording).success());
+    assert_eq!(recording.executed(), vec!["cd api && cargo build"]);
+
+    // Split into separate shells, `touch` would run in the original directory
+    let dir = std::env::temp_dir().join(format!("ftf_execution_cd_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("api")).unwrap();
+    let script = format!("cd '{}' && cd api && touch built", dir.display());
+
+    let report = ExecutionPlan::from_script(&script).run(&BashShell::new().unwrap());
+
+    assert!(report.success());
+    assert!(dir.join("api/built").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,70 @@
+//! Integration tests for `--quiet` and ftf's exit codes.
+
+mod common;
+
+use assert_cmd::Command;
+
+const SCRIPT: &str = "git pus";
+const OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+#[test]
+fn test_printed_correction_exits_0() {
+    let home = common::temp_home("exit_codes", "printed");
+    Command::from_std(common::ftf(&home))
+        .args(["--quiet", "--command", SCRIPT, "--output", OUTPUT, "--exit-code", "1"])
+        .assert()
+        .code(0)
+        .stdout("git push\n")
+        .stderr("");
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_no_correction_exits_1() {
+    let home = common::temp_home("exit_codes", "none");
+    Command::from_std(common::ftf(&home))
+        .args(["--quiet", "--command", "true", "--output", "", "--exit-code", "1"])
+        .assert()
+        .code(1)
+        .stdout("");
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_usage_and_config_errors_exit_2() {
+    let home = common::temp_home("exit_codes", "usage");
+    Command::from_std(common::ftf(&home)).args(["--quiet", "--command", SCRIPT]).assert().code(2).stdout("");
+
+    let config = home.join("broken.toml");
+    std::fs::write(&config, "[global\n").unwrap();
+    Command::from_std(common::ftf(&home))
+        .args(["--quiet", "--config", config.to_str().unwrap()])
+        .args(["--command", SCRIPT, "--output", OUTPUT, "--exit-code", "1"])
+        .assert()
+        .code(2)
+        .stdout("");
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_dangerous_correction_exits_3() {
+    let home = common::temp_home("exit_codes", "dangerous");
+    let config = home.join("config.toml");
+    std::fs::write(&config, "[global]\ndangerous_patterns = [\"git push\"]\n").unwrap();
+    Command::from_std(common::ftf(&home))
+        .args(["--quiet", "--config", config.to_str().unwrap()])
+        .args(["--command", SCRIPT, "--output", OUTPUT, "--exit-code", "1"])
+        .assert()
+        .code(3)
+        .stdout("")
+        .stderr("");
+
+    // Without --quiet the reason is given
+    let assert = Command::from_std(common::ftf(&home))
+        .args(["--no-interaction", "--config", config.to_str().unwrap()])
+        .args(["--command", SCRIPT, "--output", OUTPUT, "--exit-code", "1"])
+        .assert()
+        .code(3);
+    assert!(String::from_utf8_lossy(&assert.get_output().stderr).contains("needs confirmation"));
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,8 @@
+#!/bin/sh
+# Corrects `dokcer` to `docker`. Reads the failed command as JSON on stdin
+# and prints the corrections as a JSON array.
+script=$(sed -n 's/.*"script":"\([^"]*\)".*/\1/p')
+case "$script" in
+    dokcer*) printf '[{"script":"%s","priority":900}]\n' "docker${script#dokcer}" ;;
+    *) echo '[]' ;;
+esac
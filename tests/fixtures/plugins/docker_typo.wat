@@ -0,0 +1,52 @@
+;; WASM plugin fixture: corrects `dokcer ps` to `docker ps`.
+;; See `src/plugins/wasm.rs` for the ABI, and `examples/wasm-plugin` for the
+;; same rule written in Rust.
+(module
+  (memory (export "memory") 1)
+
+  ;; Length-prefixed corrections
+  (data (i32.const 16) "\27\00\00\00[{\22script\22:\22docker ps\22,\22priority\22:900}]")
+  (data (i32.const 64) "\02\00\00\00[]")
+  ;; What the command JSON contains when the rule matches (20 bytes)
+  (data (i32.const 128) "\22script\22:\22dokcer ps\22")
+
+  ;; The input buffer starts at 1024 and is reused by every call
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $pages i32)
+    (local.set $pages
+      (i32.sub
+        (i32.shr_u (i32.add (i32.add (i32.const 1024) (local.get $len)) (i32.const 65535)) (i32.const 16))
+        (memory.size)))
+    (if (i32.gt_s (local.get $pages) (i32.const 0))
+      (then (drop (memory.grow (local.get $pages)))))
+    (i32.const 1024))
+
+  ;; Returns 1 if the 20-byte needle at 128 occurs in the input
+  (func $contains (param $ptr i32) (param $len i32) (result i32)
+    (local $i i32)
+    (local $j i32)
+    (block $not_found
+      (loop $outer
+        (br_if $not_found (i32.gt_s (i32.add (local.get $i) (i32.const 20)) (local.get $len)))
+        (local.set $j (i32.const 0))
+        (block $mismatch
+          (loop $inner
+            (if (i32.eq (local.get $j) (i32.const 20))
+              (then (return (i32.const 1))))
+            (br_if $mismatch
+              (i32.ne
+                (i32.load8_u (i32.add (i32.add (local.get $ptr) (local.get $i)) (local.get $j)))
+                (i32.load8_u (i32.add (i32.const 128) (local.get $j)))))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $inner)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $outer)))
+    (i32.const 0))
+
+  (func (export "matches") (param $ptr i32) (param $len i32) (result i32)
+    (call $contains (local.get $ptr) (local.get $len)))
+
+  (func (export "corrections") (param $ptr i32) (param $len i32) (result i32)
+    (if (result i32) (call $contains (local.get $ptr) (local.get $len))
+      (then (i32.const 16))
+      (else (i32.const 64)))))
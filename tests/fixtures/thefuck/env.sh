@@ -0,0 +1,5 @@
+# thefuck configured through the environment, from a shell profile
+export THEFUCK_EXCLUDE_RULES='git_pull:tmux'
+export THEFUCK_PRIORITY="sudo=50"
+export THEFUCK_REQUIRE_CONFIRMATION=true
+export THEFUCK_WAIT_SLOW_COMMAND=20
@@ -0,0 +1 @@
+rules = ['git_not_command', 'mkdir_p']
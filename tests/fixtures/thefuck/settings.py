@@ -0,0 +1,14 @@
+# thefuck settings, see https://github.com/nvbn/thefuck#settings
+import os
+
+rules = DEFAULT_RULES + ['git_push_force']
+exclude_rules = [
+    'git_pull',
+    'sudo',  # prefer typing it
+    'fix_file',
+]
+wait_command = 3
+require_confirmation = False
+no_colors = True
+priority = {'git_push': 100, "no_command": 9999}
+history_limit = 2000
@@ -0,0 +1,57 @@
+//! Integration tests for `ftf import-thefuck`.
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/thefuck");
+
+fn import(home: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .arg("import-thefuck")
+        .args(args)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home)
+        .env_remove("THEFUCK_RULES")
+        .env_remove("THEFUCK_EXCLUDE_RULES")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_import_prints_config_and_warnings() {
+    let home = std::env::temp_dir().join(format!("ftf_import_thefuck_{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+
+    let settings = format!("{}/settings.py", FIXTURES);
+    let output = import(&home, &[&settings, "--output", "-"], &[("THEFUCK_HISTORY_LIMIT", "50")]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(stdout.contains("[rules.git_pull_rebase]\nenabled = false"), "stdout: {}", stdout);
+    assert!(stdout.contains("history_scan_limit = 50"), "stdout: {}", stdout);
+    assert!(stderr.contains("ftf: thefuck rule 'fix_file' has no ftf equivalent"), "stderr: {}", stderr);
+    assert!(stderr.contains("ftf: thefuck setting 'wait_command' has no ftf equivalent"), "stderr: {}", stderr);
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_import_writes_config_without_replacing_it() {
+    let home = std::env::temp_dir().join(format!("ftf_import_thefuck_write_{}", std::process::id()));
+    std::fs::create_dir_all(home.join("thefuck")).unwrap();
+    std::fs::copy(format!("{}/env.sh", FIXTURES), home.join("thefuck/settings.py")).unwrap();
+
+    let output = import(&home, &[], &[]);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let written = std::fs::read_to_string(home.join("fasterthefuck/config.toml")).unwrap();
+    assert!(written.contains("[rules.tmux_unknown_command]\nenabled = false"), "{}", written);
+
+    let again = import(&home, &[], &[]);
+    assert!(!again.status.success());
+    assert!(String::from_utf8_lossy(&again.stderr).contains("--force"));
+    assert!(import(&home, &["--force"], &[]).status.success());
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
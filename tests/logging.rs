@@ -0,0 +1,49 @@
+//! Integration tests for `--log-file`.
+
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+fn correct(dir: &Path, config: &str) -> Output {
+    let config_path = dir.join("config.toml");
+    std::fs::write(&config_path, config).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["--command", "git pus", "--output", OUTPUT, "--exit-code", "1"])
+        .args(["--no-interaction", "--config"])
+        .arg(&config_path)
+        .arg("--log-file")
+        .arg(dir.join("ftf.log"))
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_RUNTIME_DIR", dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_debug_logs_rules_to_the_file_only() {
+    let dir = std::env::temp_dir().join(format!("ftf_logging_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = correct(&dir, "[global]\ndebug = true\n");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    // Debug adds the summary footer, and nothing of the log
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.lines().count(), 1, "stderr: {}", stderr);
+    assert!(stderr.starts_with("ftf: evaluated "), "stderr: {}", stderr);
+    let log = std::fs::read_to_string(dir.join("ftf.log")).unwrap();
+    assert!(log.contains("rule{rule=\"git_not_command\" matched=true"), "log: {}", log);
+
+    std::fs::remove_file(dir.join("ftf.log")).unwrap();
+    let output = correct(&dir, "[global]\ndebug = true\nlog_level = \"warn\"\n");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(dir.join("ftf.log")).unwrap(), "");
+
+    let output = correct(&dir, "[global]\nlog_level = \"loud\"\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown log_level 'loud'"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
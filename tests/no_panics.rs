@@ -0,0 +1,254 @@
+//! Integration tests for the corrector never panicking: built-in rules run
+//! over mangled versions of real failures and every correction they offer
+//! must be usable.
+//!
+//! Each rule is also run over commands shaped to trigger it, from
+//! [`Rule::sample_matching_command`] or its example, and the corrections it
+//! offers for them must differ from the command.
+//!
+//! The inputs are proptest strategies, so a failing case is shrunk to a
+//! minimal one before it's reported.
+
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, Rule, RuleContext, RuleRegistry};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::{select, Index};
+use proptest::test_runner::TestRunner;
+use std::sync::{Arc, OnceLock};
+
+const CORPUS: &str = include_str!("fixtures/corpus/commands.jsonl");
+
+/// Cases run per test.
+const CASES: u32 = 2000;
+
+/// Characters that trip up code slicing by byte or expecting ASCII.
+const AWKWARD: &[char] = &[
+    '\u{fffd}', 'é', '→', '✓', '日', '🦀', '\u{200b}', '\0', '\t', '\n', '\r', '\x1b', ' ', '$', '\\', '\'', '"',
+    '(', ')', '[', ']', '{', '}', '*', '?', '|', '&', ';', '-', '=', ':', '/', '~', '%',
+];
+
+/// Exit codes failures are given when their script and output are mixed up.
+const EXIT_CODES: &[i32] = &[1, 2, 126, 127, 128, 130];
+
+/// An awkward character a third of the time, printable ASCII otherwise.
+fn any_char() -> impl Strategy<Value = char> {
+    prop_oneof![1 => select(AWKWARD), 2 => (b' '..=b'~').prop_map(char::from)]
+}
+
+/// Up to `max` characters from [`any_char`].
+fn printable(max: usize) -> impl Strategy<Value = String> {
+    vec(any_char(), 0..=max).prop_map(String::from_iter)
+}
+
+/// One change to a piece of text, at a position taken modulo its length.
+#[derive(Debug, Clone)]
+enum Edit {
+    Insert(Index, char),
+    Remove(Index),
+    Replace(Index, char),
+    Truncate(Index),
+}
+
+impl Edit {
+    fn apply(&self, chars: &mut Vec<char>) {
+        match self {
+            Edit::Insert(at, c) => chars.insert(at.index(chars.len() + 1), *c),
+            Edit::Remove(at) if !chars.is_empty() => {
+                chars.remove(at.index(chars.len()));
+            }
+            Edit::Replace(at, c) if !chars.is_empty() => {
+                let at = at.index(chars.len());
+                chars[at] = *c;
+            }
+            Edit::Truncate(at) => chars.truncate(at.index(chars.len() + 1)),
+            _ => {}
+        }
+    }
+}
+
+/// A few inserted, deleted or replaced characters, or a truncation. Inserts
+/// are likelier, as they're what trips rules up most.
+fn edits() -> impl Strategy<Value = Vec<Edit>> {
+    let edit = prop_oneof![
+        2 => (any::<Index>(), any_char()).prop_map(|(at, c)| Edit::Insert(at, c)),
+        1 => any::<Index>().prop_map(Edit::Remove),
+        1 => (any::<Index>(), any_char()).prop_map(|(at, c)| Edit::Replace(at, c)),
+        1 => any::<Index>().prop_map(Edit::Truncate),
+    ];
+    vec(edit, 1..=5)
+}
+
+/// Applies `edits` to `text`.
+fn mangle(text: &str, edits: &[Edit]) -> String {
+    let mut chars: Vec<char> = text.chars().collect();
+    for edit in edits {
+        edit.apply(&mut chars);
+    }
+    chars.into_iter().collect()
+}
+
+/// Arguments often appended to a rule's sample command, so corrections are
+/// checked on more than the bare trigger.
+const EXTRA_ARGS: &[&str] = &["-v", "--force", "foo", "./some/path.txt", "'a b'", "\"x\" 2>&1", "é→日"];
+
+/// Up to three arguments to append to a command, each with its leading space.
+fn extra_args() -> impl Strategy<Value = String> {
+    let arg = prop_oneof![
+        select(EXTRA_ARGS).prop_map(String::from),
+        "-{1,2}[a-z]{1,8}",
+        "[a-zA-Z0-9._/é→日-]{1,12}",
+    ];
+    vec(arg, 0..=3).prop_map(|args| args.iter().map(|arg| format!(" {}", arg)).collect())
+}
+
+/// Where unrelated text goes around a trigger's output.
+#[derive(Debug, Clone, Copy)]
+enum Noise {
+    None,
+    Before,
+    After,
+}
+
+/// A line of unrelated text, without control characters, and where it goes.
+fn noise() -> impl Strategy<Value = (Noise, String)> {
+    let line = printable(20).prop_map(|line| line.replace(char::is_control, " "));
+    (prop_oneof![Just(Noise::None), Just(Noise::Before), Just(Noise::After)], line)
+}
+
+fn registry() -> RuleRegistry {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    registry
+}
+
+/// Gets the failures to start from: the corpus and every rule's example.
+fn load_seeds(registry: &RuleRegistry) -> Vec<Command> {
+    let corpus = CORPUS.lines().filter(|line| !line.trim().is_empty()).map(|line| {
+        let entry: serde_json::Value = serde_json::from_str(line).expect("invalid corpus line");
+        Command::new(
+            entry["script"].as_str().unwrap(),
+            entry["output"].as_str().unwrap(),
+            entry["exit_code"].as_i64().unwrap() as i32,
+        )
+    });
+    let examples = registry.rules().iter().filter_map(|rule| rule.metadata().example).map(|example| {
+        Command::new(example.script, example.output, example.exit_code)
+    });
+    corpus.chain(examples).collect()
+}
+
+/// Gets the corrector with every built-in rule and the failures mangled,
+/// built once for all cases.
+fn fixture() -> &'static (Corrector, Vec<Command>) {
+    static FIXTURE: OnceLock<(Corrector, Vec<Command>)> = OnceLock::new();
+    FIXTURE.get_or_init(|| {
+        let registry = registry();
+        let seeds = load_seeds(&registry);
+        (Corrector::new(registry), seeds)
+    })
+}
+
+/// Picks one of the failures to start from.
+fn seed() -> impl Strategy<Value = Command> {
+    select(fixture().1.as_slice())
+}
+
+/// Runs every built-in rule over `command` and checks none panicked and
+/// every correction is non-empty and, unless a side effect runs first,
+/// changes the command.
+fn check(command: &Command) -> Result<(), TestCaseError> {
+    let set = fixture().0.correct(command);
+    prop_assert!(set.failures.is_empty(), "{:?} panicked: {:?}", command, set.failures);
+    for correction in &set.corrections {
+        prop_assert!(!correction.script.trim().is_empty(), "empty correction for {:?}", command);
+        if correction.side_effect.is_none() {
+            prop_assert_ne!(&correction.script, &command.script, "{:?} unchanged", correction.rule);
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn test_mangled_scripts(seed in seed(), edits in edits()) {
+        check(&Command::new(mangle(&seed.script, &edits), seed.output, seed.exit_code))?;
+    }
+
+    #[test]
+    fn test_mangled_outputs(seed in seed(), edits in edits()) {
+        check(&Command::new(seed.script.clone(), mangle(&seed.output, &edits), seed.exit_code))?;
+    }
+
+    #[test]
+    fn test_mixed_up_failures(
+        (first, script_edits) in (seed(), edits()),
+        (second, output_edits) in (seed(), edits()),
+        exit_code in select(EXIT_CODES),
+    ) {
+        let (script, output) = (mangle(&first.script, &script_edits), mangle(&second.output, &output_edits));
+        check(&Command::new(script, output, exit_code))?;
+    }
+
+    #[test]
+    fn test_random_printable_failures(script in printable(40), output in printable(120), exit_code in 1..256) {
+        check(&Command::new(script, output, exit_code))?;
+    }
+}
+
+/// Gets the commands shaped to trigger `rule`: its sample, then its example.
+fn triggers(rule: &dyn Rule) -> Vec<Command> {
+    let example = rule.metadata().example.map(|example| Command::new(example.script, example.output, example.exit_code));
+    rule.sample_matching_command().into_iter().chain(example).collect()
+}
+
+/// Checks every correction `rule` offers for `command`, if it matches, is
+/// usable and isn't `command` itself.
+fn check_suggestions(rule: &dyn Rule, command: &Command) -> Result<(), TestCaseError> {
+    if !rule.matches(command) {
+        return Ok(());
+    }
+    let name = rule.name();
+    for correction in rule.get_corrected_commands(command) {
+        prop_assert!(!correction.script.trim().is_empty(), "{} suggested nothing", name);
+        prop_assert!(
+            !correction.script.contains(|c: char| c.is_control() && c != '\n'),
+            "{} suggested {:?}, with a control character",
+            name,
+            correction.script
+        );
+        if correction.side_effect.is_none() {
+            prop_assert_ne!(&correction.script, &command.script, "{} suggested its input", name);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rules_never_suggest_their_input() {
+    let registry = registry();
+    let mut sampled = 0;
+    for rule in registry.rules() {
+        let triggers = triggers(rule.as_ref());
+        if triggers.is_empty() {
+            continue;
+        }
+        sampled += 1;
+        let commands = (select(triggers), extra_args(), noise()).prop_map(|(trigger, args, (noise, line))| {
+            let output = match noise {
+                Noise::None => trigger.output,
+                Noise::Before => format!("{}\n{}", line, trigger.output),
+                Noise::After => format!("{}\n{}", trigger.output, line),
+            };
+            Command::new(format!("{}{}", trigger.script, args), output, trigger.exit_code)
+        });
+        let mut runner = TestRunner::new(ProptestConfig::with_cases(CASES / 10));
+        if let Err(e) = runner.run(&commands, |command| check_suggestions(rule.as_ref(), &command)) {
+            panic!("{}: {}", rule.name(), e);
+        }
+    }
+    assert!(sampled >= 100, "only {} rules could be sampled", sampled);
+}
@@ -0,0 +1,95 @@
+//! Integration tests for running ftf where nobody can answer a prompt.
+
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_ci_prints_instead_of_executing() {
+    let dir = std::env::temp_dir().join(format!("ftf_non_interactive_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args([
+            "--command",
+            "mkdir build/out",
+            "--output",
+            "mkdir: cannot create directory 'build/out': No such file or directory",
+            "--exit-code",
+            "1",
+            "--execute",
+        ])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .env("CI", "true")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert_eq!(stdout.lines().next(), Some("mkdir -p build/out"));
+    assert!(stderr.contains("non-interactive (CI is set)"), "stderr: {}", stderr);
+    assert!(!dir.join("build").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_piped_stdin_never_prompts() {
+    let dir = std::env::temp_dir().join(format!("ftf_piped_stdin_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // Without the guard a selector would wait on the pipe
+    let output = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["--command", "cat /etc/shadow", "--output", "cat: /etc/shadow: Permission denied", "--exit-code", "1"])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .env_remove("CI")
+        .stdin(Stdio::piped())
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(stderr.contains("non-interactive (stdin or stderr is not a terminal)"), "stderr: {}", stderr);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_side_effect_is_never_run_unasked() {
+    let dir = std::env::temp_dir().join(format!("ftf_unasked_effect_{}", std::process::id()));
+    let ssh_dir = dir.join(".ssh");
+    std::fs::create_dir_all(&ssh_dir).unwrap();
+    let known_hosts = ssh_dir.join("known_hosts");
+    let entry = "build.example.com ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBHkP\n";
+    std::fs::write(&known_hosts, entry).unwrap();
+
+    let ssh_output = format!(
+        "@    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @\n\
+         Offending ECDSA key in {}:1\n\
+         Host key verification failed.\n",
+        known_hosts.display()
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["--no-interaction", "--command", "ssh build.example.com", "--output", &ssh_output, "--exit-code", "255"])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .env_remove("CI")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("pass --yes"), "stderr: {}", stderr);
+    assert_eq!(std::fs::read_to_string(&known_hosts).unwrap(), entry);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,77 @@
+//! Integration tests for reading a long output from stdin, and outputs
+//! that aren't UTF-8.
+
+mod common;
+
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::Stdio;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/invalid_utf8");
+
+#[test]
+fn test_error_at_the_end_of_a_long_piped_output_matches() {
+    let dir = common::temp_home("output", "long");
+    let mut child = common::ftf(&dir)
+        .args(["--no-interaction", "--command", "mkdir build/out", "--output", "-", "--exit-code", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // 10 MiB of noise before the error
+    let mut stdin = child.stdin.take().unwrap();
+    let noise = "   Compiling some-crate v1.0.0 (/src/some-crate)\n".repeat(1024);
+    for _ in 0..(10 * 1024 * 1024 / noise.len()) {
+        stdin.write_all(noise.as_bytes()).unwrap();
+    }
+    stdin
+        .write_all(b"mkdir: cannot create directory 'build/out': No such file or directory\n")
+        .unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(stdout.lines().next(), Some("mkdir -p build/out"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_output_file_with_invalid_bytes_is_corrected() {
+    let home = common::temp_home("output", "file");
+    let output = common::ftf(&home)
+        .args(["--no-interaction", "--command", "git pus", "--exit-code", "1", "--output-file"])
+        .arg(Path::new(FIXTURES).join("git_output"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().next(), Some("git push"));
+
+    let output = common::ftf(&home)
+        .args(["--no-interaction", "--command", "git pus", "--exit-code", "1", "--output-file"])
+        .arg(home.join("missing"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&home.join("missing").display().to_string()));
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_output_argument_with_invalid_bytes_is_corrected() {
+    let home = common::temp_home("output", "argument");
+    let mut bytes = b"git: 'pus' is not a git command. See 'git --help'.\n\n".to_vec();
+    bytes.extend_from_slice(b"The most similar command is\n\tpush\n\xff\xfe\xc3\x28\n");
+    let output = common::ftf(&home)
+        .args(["--no-interaction", "--command", "git pus", "--exit-code", "1", "--output"])
+        .arg(std::ffi::OsStr::from_bytes(&bytes))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().next(), Some("git push"));
+    std::fs::remove_dir_all(&home).unwrap();
+}
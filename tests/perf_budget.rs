@@ -0,0 +1,66 @@
+//! End-to-end latency budget for a canned correction.
+//!
+//! Ignored unless built with `--features perf-budget`, since timings depend
+//! on the machine. Run it on a release build:
+//!
+//! ```sh
+//! cargo test --release --features perf-budget --test perf_budget
+//! ```
+//!
+//! The budget defaults to 100ms and can be changed with
+//! `FTF_PERF_BUDGET_MS`.
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const DEFAULT_BUDGET_MS: u64 = 100;
+
+/// Runs per measurement; the median is compared against the budget.
+const RUNS: usize = 11;
+
+fn budget() -> Duration {
+    let millis = std::env::var("FTF_PERF_BUDGET_MS")
+        .ok()
+        .map(|value| value.parse().expect("FTF_PERF_BUDGET_MS must be a number of milliseconds"))
+        .unwrap_or(DEFAULT_BUDGET_MS);
+    Duration::from_millis(millis)
+}
+
+#[test]
+#[cfg_attr(not(feature = "perf-budget"), ignore = "run with --features perf-budget")]
+fn test_git_correction_within_budget() {
+    let dir = std::env::temp_dir().join(format!("ftf_perf_budget_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let output = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+                .args([
+                    "--no-interaction",
+                    "--command",
+                    "git pus",
+                    "--output",
+                    "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush",
+                    "--exit-code",
+                    "1",
+                ])
+                .current_dir(&dir)
+                .env("HOME", &dir)
+                .env("XDG_CONFIG_HOME", &dir)
+                .env("XDG_DATA_HOME", &dir)
+                .env_remove("XDG_RUNTIME_DIR")
+                .stdin(Stdio::null())
+                .output()
+                .unwrap();
+            let elapsed = start.elapsed();
+            assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "git push");
+            elapsed
+        })
+        .collect();
+    times.sort();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let median = times[RUNS / 2];
+    assert!(median <= budget(), "median correction took {:?}, over the {:?} budget", median, budget());
+}
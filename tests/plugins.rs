@@ -0,0 +1,89 @@
+//! Integration tests for rules loaded from executables in the config
+//! directory.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const DOCKER_TYPO: &str = include_str!("fixtures/plugins/docker_typo.sh");
+
+/// Creates a config directory with the fixture plugin, plus `extra` plugins
+/// given as (file name, shell body).
+fn setup(name: &str, extra: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ftf_plugins_it_{}_{}", name, std::process::id()));
+    let rules = dir.join("fasterthefuck").join("rules");
+    std::fs::create_dir_all(&rules).unwrap();
+    // Plugins are only run from a directory no one else can write to
+    std::fs::set_permissions(&rules, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut plugins = vec![("docker_typo.sh", DOCKER_TYPO.to_string())];
+    plugins.extend(extra.iter().map(|(name, body)| (*name, format!("#!/bin/sh\n{}\n", body))));
+    for (name, contents) in plugins {
+        let path = rules.join(name);
+        std::fs::write(&path, contents).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    dir
+}
+
+fn correct(dir: &Path, script: &str, output: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["--no-interaction", "--command", script, "--output", output, "--exit-code", "127"])
+        .arg("--log-file")
+        .arg(dir.join("ftf.log"))
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_DATA_HOME", dir)
+        .env_remove("XDG_RUNTIME_DIR")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_plugin_corrects_command() {
+    let dir = setup("corrects", &[]);
+
+    let output = correct(&dir, "dokcer ps -a", "sh: dokcer: command not found");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "docker ps -a");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_broken_plugins_never_break_builtin_rules() {
+    let dir = setup("broken", &[("crashes", "exit 3"), ("garbage.py", "echo nope")]);
+    std::fs::write(
+        dir.join("fasterthefuck").join("config.toml"),
+        "[global]\nplugin_timeout_ms = 200\n",
+    )
+    .unwrap();
+
+    let output = correct(&dir, "mkdir a/b", "mkdir: cannot create directory 'a/b': No such file or directory");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "mkdir -p a/b", "stderr: {}", stderr);
+    assert!(!stderr.contains("plugin"), "stderr: {}", stderr);
+    let log = std::fs::read_to_string(dir.join("ftf.log")).unwrap();
+    assert!(log.contains("plugin failed plugin=\"crashes\" error=Rule error: exited with"), "log: {}", log);
+    assert!(log.contains("plugin failed plugin=\"garbage\""), "log: {}", log);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_plugin_disabled_by_file_name() {
+    let dir = setup("disabled", &[]);
+    std::fs::write(
+        dir.join("fasterthefuck").join("config.toml"),
+        "[rules.docker_typo]\nenabled = false\n",
+    )
+    .unwrap();
+
+    let output = correct(&dir, "dokcer ps -a", "sh: dokcer: command not found");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("docker ps -a"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,66 @@
+//! Differential test for the substring pre-filter: every fixture command
+//! must get exactly the same corrections with and without it.
+
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, RuleContext, RuleRegistry};
+use std::sync::Arc;
+
+const CORPUS: &str = include_str!("fixtures/corpus/commands.jsonl");
+
+fn registry() -> RuleRegistry {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    registry
+}
+
+/// Gets the corrections for `cmd` with everything that tells them apart.
+fn corrections(corrector: &Corrector, cmd: &Command) -> Vec<(String, i32, Option<String>)> {
+    corrector
+        .get_corrections(cmd)
+        .into_iter()
+        .map(|correction| (correction.script, correction.priority, correction.rule))
+        .collect()
+}
+
+fn corpus() -> Vec<Command> {
+    CORPUS
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let entry: serde_json::Value = serde_json::from_str(line).expect("invalid corpus line");
+            Command::new(
+                entry["script"].as_str().unwrap(),
+                entry["output"].as_str().unwrap(),
+                entry["exit_code"].as_i64().unwrap() as i32,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_prefilter_is_transparent() {
+    let filtered = Corrector::new(registry());
+    let unfiltered = Corrector::new(registry()).without_prefilter();
+
+    let mut corrected = 0;
+    for cmd in corpus() {
+        let expected = corrections(&unfiltered, &cmd);
+        assert_eq!(corrections(&filtered, &cmd), expected, "corrections differ for {:?}", cmd.script);
+        corrected += usize::from(!expected.is_empty());
+    }
+    // The corpus has to exercise the rules for the comparison to mean anything
+    assert!(corrected >= 20, "only {} fixture commands were corrected", corrected);
+}
+
+#[test]
+fn test_prefilter_is_transparent_on_long_output() {
+    let filtered = Corrector::new(registry());
+    let unfiltered = Corrector::new(registry()).without_prefilter();
+
+    let padding = "npm WARN deprecated some-package@1.0.0: use another-package\n".repeat(1000);
+    for cmd in corpus() {
+        let cmd = Command::new(cmd.script, format!("{}{}", padding, cmd.output), cmd.exit_code);
+        assert_eq!(corrections(&filtered, &cmd), corrections(&unfiltered, &cmd));
+    }
+}
@@ -0,0 +1,111 @@
+//! Integration tests for `ftf record` and `ftf replay`.
+
+mod common;
+
+use fasterthefuck::replay::Recording;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const GCLOUD_OUTPUT: &str =
+    "ERROR: (gcloud.compute.instances.list) The required property [project] is not currently set.";
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay").join(name)
+}
+
+/// Runs ftf without the variables the gcloud rule reads, so only a
+/// recording can supply them.
+fn ftf(home: &Path) -> Command {
+    let mut command = common::ftf(home);
+    command.env("LANG", "C").env_remove("GOOGLE_CLOUD_PROJECT").env_remove("CLOUDSDK_CORE_PROJECT");
+    command
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_record_then_replay_without_the_environment() {
+    let home = common::temp_home("replay", "round_trip");
+    let file = home.join("failure.ftf");
+    let recorded = ftf(&home)
+        .args(["--command", "gcloud compute instances list", "--output", GCLOUD_OUTPUT, "--exit-code", "1"])
+        .arg("record")
+        .arg("--out")
+        .arg(&file)
+        .env("GOOGLE_CLOUD_PROJECT", "my-project")
+        .output()
+        .unwrap();
+    let recorded = stdout(&recorded);
+    assert_eq!(recorded.trim(), "gcloud config set project my-project && gcloud compute instances list");
+
+    let recording = Recording::read(&file).unwrap();
+    assert_eq!(recording.script, "gcloud compute instances list");
+    assert_eq!(recording.exit_code, 1);
+    assert_eq!(recording.probes.env.get("GOOGLE_CLOUD_PROJECT").map(String::as_str), Some("my-project"));
+    assert!(!recording.probes.env.contains_key("HOME"), "{:?}", recording.probes.env);
+
+    // The variable is gone now, so only the recording can supply it
+    let replayed = ftf(&home).arg("replay").arg(&file).output().unwrap();
+    let stderr = String::from_utf8_lossy(&replayed.stderr).to_string();
+    assert_eq!(stdout(&replayed), recorded);
+    assert!(!stderr.contains("config differs"), "stderr: {}", stderr);
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_replay_answers_probes_from_the_recording() {
+    let home = common::temp_home("replay", "probes");
+    let output = ftf(&home).arg("replay").arg(fixture("git_checkout.ftf")).output().unwrap();
+    // `feature` was among the recorded branches, so creating it is dropped
+    assert_eq!(output.status.code(), Some(1), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("git checkout -b feature"));
+
+    let contents = std::fs::read_to_string(fixture("git_checkout.ftf")).unwrap();
+    let without_branch = home.join("without_branch.ftf");
+    std::fs::write(&without_branch, contents.replace("main\\nfeature\\n", "main\\n")).unwrap();
+    let output = ftf(&home).arg("replay").arg(&without_branch).output().unwrap();
+    assert_eq!(stdout(&output).trim(), "git checkout -b feature");
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_replay_warns_when_the_config_differs() {
+    let home = common::temp_home("replay", "config");
+    let output = ftf(&home).arg("replay").arg(fixture("git_checkout.ftf")).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the config differs from the one the failure was recorded with"), "stderr: {}", stderr);
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_replay_explain() {
+    let home = common::temp_home("replay", "explain");
+    let file = home.join("failure.ftf");
+    let mut recording = Recording::read(&fixture("git_checkout.ftf")).unwrap();
+    recording.probes.runs.clear();
+    recording.write(&file).unwrap();
+
+    let output = ftf(&home).arg("replay").arg(&file).arg("--explain").output().unwrap();
+    let stdout = stdout(&output);
+    assert!(stdout.contains("$ git checkout feature"), "stdout: {}", stdout);
+    assert!(stdout.contains("git checkout -b feature"), "stdout: {}", stdout);
+    assert!(stdout.contains("git_branch_exists"), "stdout: {}", stdout);
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_replay_refuses_unreadable_recordings() {
+    let home = common::temp_home("replay", "unreadable");
+    let file = home.join("failure.ftf");
+    std::fs::write(&file, "version = 99\n").unwrap();
+    let output = ftf(&home).arg("replay").arg(&file).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,69 @@
+//! Integration test driving `ftf serve --stdio` through a pipe.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{ChildStdin, Command, Stdio};
+
+const GIT_OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+fn send(stdin: &mut ChildStdin, stdout: &mut impl BufRead, line: &str) -> Value {
+    writeln!(stdin, "{}", line).unwrap();
+    let mut response = String::new();
+    stdout.read_line(&mut response).unwrap();
+    serde_json::from_str(&response).unwrap()
+}
+
+#[test]
+fn test_serve_stdio() {
+    let dir = std::env::temp_dir().join(format!("ftf_rpc_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("fasterthefuck")).unwrap();
+    std::fs::write(dir.join("fasterthefuck/config.toml"), "[rules.git_not_command]\npriority = 42\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["serve", "--stdio"])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let correct = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "correct",
+        "params": {"script": "git pus", "output": GIT_OUTPUT, "exit_code": 1, "cwd": dir},
+    });
+    let response = send(&mut stdin, &mut stdout, &correct.to_string());
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"][0]["script"], "git push");
+    // The loaded config applies
+    assert_eq!(response["result"][0]["priority"], 42);
+
+    // A malformed line gets an error, and the server keeps answering
+    let response = send(&mut stdin, &mut stdout, "{\"jsonrpc\":");
+    assert_eq!(response["error"]["code"], -32700);
+
+    let response = send(&mut stdin, &mut stdout, r#"{"jsonrpc":"2.0","id":"rules","method":"listRules"}"#);
+    assert!(response["result"].as_array().unwrap().iter().any(|rule| rule["name"] == "git_push_force"));
+
+    let mut explain = correct.clone();
+    explain["id"] = json!(3);
+    explain["method"] = json!("explain");
+    let response = send(&mut stdin, &mut stdout, &explain.to_string());
+    assert_eq!(response["result"][0]["rule"], "git_not_command");
+    assert_eq!(response["result"][0]["configured_priority"], 42);
+
+    let response = send(&mut stdin, &mut stdout, r#"{"jsonrpc":"2.0","id":4,"method":"correct","params":{}}"#);
+    assert_eq!(response["error"]["code"], -32602);
+
+    // The server exits when its input closes
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
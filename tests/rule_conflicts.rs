@@ -0,0 +1,98 @@
+//! Integration tests for rules that put different corrections first for the
+//! same failure, and `ftf rules conflicts`.
+//!
+//! Every conflict between built-in rules must be listed as accepted in a
+//! committed file, so a new rule that fights an existing one is noticed.
+//! After deciding a conflict is fine, regenerate the list with
+//! `FTF_UPDATE_GOLDEN=1 cargo test --test rule_conflicts`.
+
+use fasterthefuck::corpus::{self, CorpusCase};
+use fasterthefuck::rules::{builtin_factories, Overlap};
+use fasterthefuck::{Command, Config, RuleContext, RuleRegistry};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Arc;
+
+const ACCEPTED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rules/accepted_overlaps.txt");
+
+const HEADER: &str = "# Pairs of built-in rules that put different corrections first for the same\n\
+                      # failure, where either can be right and priorities decide which is offered\n\
+                      # first. One pair per line, by name; see tests/rule_conflicts.rs.\n";
+
+fn corpus_dir() -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")
+}
+
+fn conflicts() -> Vec<Overlap> {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    let cases = corpus::read_dir(&corpus_dir()).expect("corpus not readable");
+    let corpus: Vec<Command> = cases.iter().map(|(_, case)| CorpusCase::command(case)).collect();
+    registry.find_overlaps(&corpus).into_iter().filter(Overlap::is_conflict).collect()
+}
+
+/// Describes `overlap`, with the failure its rules first disagreed on.
+fn describe(overlap: &Overlap) -> String {
+    let mut text = format!("{} {} ({} of {})", overlap.first, overlap.second, overlap.disagreed, overlap.co_fired);
+    if let Some(example) = &overlap.example {
+        text += &format!(
+            ": `{}` (exit {}) gives `{}` and `{}`",
+            example.command.script, example.command.exit_code, example.first_suggests, example.second_suggests
+        );
+    }
+    text
+}
+
+#[test]
+fn test_conflicts_are_accepted() {
+    let conflicts = conflicts();
+    let pair = |overlap: &Overlap| format!("{} {}", overlap.first, overlap.second);
+    let found: BTreeSet<String> = conflicts.iter().map(pair).collect();
+
+    if std::env::var_os("FTF_UPDATE_GOLDEN").is_some() {
+        let lines: String = found.iter().map(|pair| format!("{}\n", pair)).collect();
+        std::fs::write(ACCEPTED, format!("{}{}", HEADER, lines)).unwrap();
+        return;
+    }
+
+    let accepted: BTreeSet<String> = std::fs::read_to_string(ACCEPTED)
+        .expect("accepted overlaps not readable")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+
+    let new: Vec<String> = conflicts
+        .iter()
+        .filter(|overlap| !accepted.contains(&pair(overlap)))
+        .map(describe)
+        .collect();
+    assert!(new.is_empty(), "rules newly conflict:\n{}", new.join("\n"));
+    let gone: Vec<&String> = accepted.difference(&found).collect();
+    assert!(gone.is_empty(), "accepted conflicts no longer happen, remove them: {:?}", gone);
+}
+
+#[test]
+fn test_conflicts_subcommand() {
+    let home = std::env::temp_dir().join(format!("ftf_rule_conflicts_{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["rules", "conflicts", "--min-disagreements", "2", "--corpus"])
+        .arg(corpus_dir())
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", &home)
+        .env("LANG", "C")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("chmod_execute and sudo_permission_denied: corrected"), "stdout: {}", stdout);
+    assert!(stdout.contains("    chmod_execute: chmod +x "), "stdout: {}", stdout);
+    for overlap in conflicts().iter().filter(|overlap| overlap.disagreed < 2) {
+        assert!(!stdout.contains(&format!("{} and {}:", overlap.first, overlap.second)), "stdout: {}", stdout);
+    }
+}
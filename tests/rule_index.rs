@@ -0,0 +1,63 @@
+//! Differential test for the leading-token index: every fixture command must
+//! get exactly the same corrections with and without it.
+
+use fasterthefuck::rules::builtin_factories;
+use fasterthefuck::{Command, Config, Corrector, RuleContext, RuleRegistry};
+use std::sync::Arc;
+
+const CORPUS: &str = include_str!("fixtures/corpus/commands.jsonl");
+
+fn registry() -> RuleRegistry {
+    let context = Arc::new(RuleContext::default());
+    let mut registry = RuleRegistry::new();
+    registry.add_factories(builtin_factories(&Config::default(), &context), None);
+    registry
+}
+
+/// Gets the corrections for `cmd` with everything that tells them apart.
+fn corrections(corrector: &Corrector, cmd: &Command) -> Vec<(String, i32, Option<String>)> {
+    corrector
+        .get_corrections(cmd)
+        .into_iter()
+        .map(|correction| (correction.script, correction.priority, correction.rule))
+        .collect()
+}
+
+fn corpus() -> Vec<Command> {
+    CORPUS
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let entry: serde_json::Value = serde_json::from_str(line).expect("invalid corpus line");
+            Command::new(
+                entry["script"].as_str().unwrap(),
+                entry["output"].as_str().unwrap(),
+                entry["exit_code"].as_i64().unwrap() as i32,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_index_is_transparent() {
+    let indexed = Corrector::new(registry());
+    let unindexed = Corrector::new(registry()).without_index();
+
+    for cmd in corpus() {
+        assert_eq!(corrections(&indexed, &cmd), corrections(&unindexed, &cmd), "corrections differ for {:?}", cmd.script);
+
+        // The same command run another way must not lose its corrections
+        for prefix in ["sudo ", "  ", "FOO=1 "] {
+            let cmd = Command::new(format!("{}{}", prefix, cmd.script), cmd.output.clone(), cmd.exit_code);
+            assert_eq!(corrections(&indexed, &cmd), corrections(&unindexed, &cmd), "corrections differ for {:?}", cmd.script);
+        }
+    }
+}
+
+#[test]
+fn test_builtin_rules_are_indexed() {
+    let registry = registry();
+    let indexed = registry.rules().iter().filter(|rule| rule.leading_token().is_some()).count();
+    // The comparison above only means something if the index skips rules
+    assert!(indexed >= 10, "only {} built-in rules have a leading token", indexed);
+}
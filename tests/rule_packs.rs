@@ -0,0 +1,59 @@
+//! Integration tests for `ftf rules sync` and loading synced rule packs.
+
+mod common;
+
+use fasterthefuck::plugins::packs::sha256_hex;
+use std::path::Path;
+use std::process::Output;
+
+const PACK: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/packs/team.toml");
+
+fn ftf(home: &Path, args: &[&str]) -> Output {
+    common::ftf(home).args(args).output().unwrap()
+}
+
+/// Writes a config listing the fixture pack, pinned to `sha256`.
+fn configure(home: &Path, sha256: &str) {
+    common::write_config(home, &format!("[rule_packs.team]\nurl = \"file://{}\"\nsha256 = \"{}\"\n", PACK, sha256));
+}
+
+fn correct(home: &Path) -> Output {
+    ftf(home, &["--command", "deplyo prod", "--output", "deplyo: command not found", "--exit-code", "127", "--no-interaction"])
+}
+
+#[test]
+fn test_synced_pack_corrects() {
+    let home = common::temp_home("rule_packs", "synced");
+    configure(&home, &sha256_hex(&std::fs::read(PACK).unwrap()));
+
+    // Nothing is fetched while correcting
+    assert!(!correct(&home).status.success());
+    assert!(!home.join("fasterthefuck/packs").exists());
+
+    let url = format!("file://{}", PACK);
+    let output = ftf(&home, &["rules", "sync", &url]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(stderr.contains("synced rule pack 'team' (1 rules)"), "stderr: {}", stderr);
+
+    let output = correct(&home);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "deploy prod");
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_tampered_pack_is_not_synced() {
+    let home = common::temp_home("rule_packs", "tampered");
+    configure(&home, &sha256_hex(b"what the team reviewed"));
+
+    let output = ftf(&home, &["rules", "sync"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("not the pinned"), "stderr: {}", stderr);
+    assert!(!home.join("fasterthefuck/packs/team.toml").exists());
+    assert!(!correct(&home).status.success());
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
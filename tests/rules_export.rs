@@ -0,0 +1,69 @@
+//! Integration tests for `ftf rules export` and `ftf rules show`.
+//!
+//! The JSON export is a published schema, so it's compared against a
+//! committed copy. After an intended change, regenerate it with
+//! `FTF_UPDATE_GOLDEN=1 cargo test --test rules_export`.
+
+mod common;
+
+use fasterthefuck::rules::Catalog;
+use std::path::Path;
+
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rules/export.json");
+
+/// Runs `ftf rules export` with `args` and no config or plugins.
+fn export(home: &Path, args: &[&str]) -> String {
+    let output = common::ftf(home).args(["rules", "export"]).args(args).output().unwrap();
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_json_export_matches_golden_file() {
+    let home = common::temp_home("rules_export", "json");
+    let json = export(&home, &[]);
+    std::fs::remove_dir_all(&home).unwrap();
+
+    if std::env::var_os("FTF_UPDATE_GOLDEN").is_some() {
+        std::fs::write(GOLDEN, &json).unwrap();
+        return;
+    }
+    let golden = std::fs::read_to_string(GOLDEN).unwrap();
+    assert!(
+        json == golden,
+        "the export no longer matches {}; if that's intended, regenerate it with FTF_UPDATE_GOLDEN=1",
+        GOLDEN
+    );
+}
+
+#[test]
+fn test_toml_export_has_the_same_rules() {
+    let home = common::temp_home("rules_export", "toml");
+    let json: Catalog = serde_json::from_str(&export(&home, &["--format", "json"])).unwrap();
+    let toml: Catalog = toml::from_str(&export(&home, &["--format", "toml"])).unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+
+    assert_eq!(toml, json);
+    assert!(json.rules.iter().any(|rule| rule.name == "git_not_command" && rule.example.is_some()));
+}
+
+#[test]
+fn test_show_prints_a_worked_example() {
+    let home = common::temp_home("rules_export", "show");
+    let show = |name: &str| {
+        common::ftf(&home).args(["rules", "show", name]).env("LANG", "C").output().unwrap()
+    };
+    let output = show("git_not_command");
+    let unknown = show("git_not_comand");
+    std::fs::remove_dir_all(&home).unwrap();
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("git_not_command\n"), "stdout: {}", stdout);
+    assert!(stdout.contains("Group:"), "stdout: {}", stdout);
+    assert!(stdout.contains("\n    $ git "), "stdout: {}", stdout);
+    assert!(stdout.contains("\n    → git "), "stdout: {}", stdout);
+
+    assert_eq!(unknown.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("did you mean git_not_command?"));
+}
@@ -0,0 +1,35 @@
+//! Integration tests for starting a shell in a deleted directory.
+//!
+//! The working directory is process-wide, so this file holds a single test
+//! and no other test sees it change.
+
+use assert_cmd::Command;
+use fasterthefuck::{BashShell, Error};
+
+#[test]
+fn test_deleted_working_directory_is_an_error() {
+    let dir = std::env::temp_dir().join(format!("ftf_shell_init_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+    std::fs::remove_dir(&dir).unwrap();
+
+    let error = BashShell::new().err().unwrap();
+    assert!(matches!(&error, Error::ShellInit { shell, .. } if shell == "bash"), "{:?}", error);
+
+    // ftf inherits the deleted directory, and says so instead of panicking
+    let home = std::env::temp_dir().join(format!("ftf_shell_init_home_{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let assert = Command::cargo_bin("fasterthefuck")
+        .unwrap()
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", &home)
+        .env("XDG_DATA_HOME", &home)
+        .env("XDG_RUNTIME_DIR", &home)
+        .env_remove("FTF_SESSION_DIR")
+        .args(["--command", "git pus", "--output", "", "--exit-code", "1"])
+        .assert()
+        .code(2);
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.contains("ftf: Could not start bash: the current directory no longer exists"), "{}", stderr);
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,76 @@
+//! Tests for the bash hook in `ftf_shell_integration.sh`.
+
+use std::path::Path;
+use std::process::Command;
+
+/// A stub ftf that writes the arguments it got to `args` beside it.
+const RECORD_ARGS: &str = "#!/bin/sh\nprintf '%s\\n' \"$@\" > \"$(dirname \"$0\")/args\"\n";
+
+/// Runs `script` in bash after sourcing the hook, with `ftf` calling a stub
+/// binary with the body `stub`.
+fn run_hook(dir: &Path, stub: &str, script: &str) -> std::process::Output {
+    let path = dir.join("stub");
+    std::fs::write(&path, stub).unwrap();
+    std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    let hook = Path::new(env!("CARGO_MANIFEST_DIR")).join("ftf_shell_integration.sh");
+    Command::new("bash")
+        .arg("-c")
+        .arg(format!("source '{}'\n{}", hook.display(), script))
+        .env("FTF_BINARY", &path)
+        .env("FTF_SESSION_DIR", dir.join("session"))
+        .env_remove("PROMPT_COMMAND")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_prompt_keeps_the_last_command_for_ftf() {
+    let dir = std::env::temp_dir().join(format!("ftf_hook_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // Each prompt runs the recorder from PROMPT_COMMAND before the user can
+    // type `ftf`
+    let script = "ls /nonexistent-hook-dir\n_fasterthefuck_record\n_fasterthefuck_record\nftf";
+    let output = run_hook(&dir, RECORD_ARGS, script);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    let args: Vec<&str> = args.lines().collect();
+    let command = args.iter().position(|arg| *arg == "--command").unwrap();
+    assert_eq!(args[command + 1], "ls /nonexistent-hook-dir");
+    assert_eq!(args[args.len() - 2..], ["--exit-code", "2"]);
+
+    // The second prompt didn't record the command again
+    let session = dir.join("session");
+    assert_eq!(std::fs::read_to_string(session.join("1/command")).unwrap(), "ls /nonexistent-hook-dir");
+    assert!(!session.join("2").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_rehash_runs_before_the_correction() {
+    let dir = std::env::temp_dir().join(format!("ftf_hook_rehash_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("old")).unwrap();
+    std::fs::create_dir_all(dir.join("new")).unwrap();
+    let tool = dir.join("old/tool");
+    std::fs::write(&tool, "#!/bin/sh\necho old\n").unwrap();
+    std::fs::set_permissions(&tool, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    // The correction installs a tool earlier in PATH than the one bash has
+    // cached, then retries it
+    let new = dir.join("new/tool");
+    let stub = format!(
+        "#!/bin/sh\necho \"printf '#!/bin/sh\\\\necho new\\\\n' > {new} && chmod +x {new} && tool\"\necho '# ftf-effect: rehash'\n",
+        new = new.display()
+    );
+    let path = format!("{}:{}:{}", dir.join("new").display(), tool.parent().unwrap().display(), std::env::var("PATH").unwrap());
+    let output = run_hook(&dir, &stub, &format!("export PATH='{}'\ntool\n_fasterthefuck_record\nftf", path));
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "old\nnew\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,104 @@
+//! Snapshot tests for what the CLI prints in each output format.
+//!
+//! ftf runs with `--deterministic`, a fixed config and an empty home, so a
+//! snapshot only changes when the output does. Review changes with
+//! `cargo insta review`, or accept them with
+//! `INSTA_UPDATE=always cargo test --test snapshots`.
+
+mod common;
+
+use assert_cmd::Command;
+use serde_json::{json, Value};
+use std::path::Path;
+
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/snapshots/config.toml");
+
+/// (name, script, output, exit code) of each failed command.
+const INPUTS: &[(&str, &str, &str, i32)] = &[
+    (
+        "git_typo",
+        "git stauts",
+        "git: 'stauts' is not a git command. See 'git --help'.\n\nThe most similar commands are\n\tstatus\n\tstage",
+        1,
+    ),
+    ("git_push", "git push", include_str!("fixtures/git/push_no_upstream"), 128),
+    ("mkdir", "mkdir logs/2024/jan", "mkdir: cannot create directory 'logs/2024/jan': No such file or directory", 1),
+    (
+        "apt_lock",
+        "apt install vim",
+        "E: Could not open lock file /var/lib/dpkg/lock-frontend - open (13: Permission denied)",
+        100,
+    ),
+    ("no_correction", "ls", "", 0),
+];
+
+/// (name, flags) of each output format.
+const FORMATS: &[(&str, &[&str])] = &[
+    ("plain", &[]),
+    ("verbose", &["--verbose"]),
+    ("explain", &["--explain"]),
+    ("diff", &["--diff"]),
+    ("explain_diff", &["--explain", "--diff"]),
+];
+
+/// Builds an ftf invocation isolated from the user's config, daemon and
+/// locale, which `--deterministic` should ignore anyway.
+fn ftf(home: &Path) -> Command {
+    let mut command = Command::from_std(common::ftf(home));
+    command
+        .args(["--deterministic", "--config", CONFIG])
+        .env("LANG", "es_ES.UTF-8")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES");
+    command
+}
+
+#[test]
+fn test_output_formats() {
+    let home = common::temp_home("snapshots", "formats");
+    for (format, flags) in FORMATS {
+        for (input, script, output, exit_code) in INPUTS {
+            let result = ftf(&home)
+                .arg("--no-interaction")
+                .args(*flags)
+                .args(["--command", script, "--output", output, "--exit-code", &exit_code.to_string()])
+                .output()
+                .unwrap();
+            let printed = format!(
+                "{}\n--- stdout\n{}--- stderr\n{}",
+                result.status,
+                String::from_utf8_lossy(&result.stdout),
+                String::from_utf8_lossy(&result.stderr)
+            );
+            insta::assert_snapshot!(format!("{}__{}", format, input), printed);
+        }
+    }
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_json_output() {
+    let home = common::temp_home("snapshots", "json");
+    let (_, script, output, exit_code) = INPUTS[0];
+    let params = json!({"script": script, "output": output, "exit_code": exit_code, "cwd": home});
+    let mut verbose_diff = params.clone();
+    verbose_diff["verbose"] = json!(true);
+    verbose_diff["diff"] = json!(true);
+    let requests = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "correct", "params": params}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "correct", "params": verbose_diff}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "explain", "params": params}),
+    ];
+    let stdin: String = requests.iter().map(|request| format!("{}\n", request)).collect();
+
+    let result = ftf(&home).args(["serve", "--stdio"]).write_stdin(stdin).output().unwrap();
+    assert!(result.status.success(), "stderr: {}", String::from_utf8_lossy(&result.stderr));
+    let responses: Vec<Value> = String::from_utf8_lossy(&result.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    insta::assert_json_snapshot!("json__correct", responses[0]);
+    insta::assert_json_snapshot!("json__correct_verbose_diff", responses[1], {".result.elapsed_ms" => "[elapsed]"});
+    insta::assert_json_snapshot!("json__explain", responses[2]);
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,58 @@
+//! Integration tests for the opt-in usage metrics and `ftf telemetry`.
+
+mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+const OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+fn ftf(home: &Path, args: &[&str]) -> Output {
+    common::ftf(home).args(args).output().unwrap()
+}
+
+fn correct(home: &Path) {
+    let output = ftf(home, &["--command", "git pus", "--output", OUTPUT, "--exit-code", "1", "--no-interaction"]);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+fn status(home: &Path) -> String {
+    let output = ftf(home, &["telemetry", "status"]);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_disabled_by_default() {
+    let home = common::temp_home("telemetry", "disabled");
+    common::write_config(&home, "[telemetry]\nendpoint = \"https://127.0.0.1:9/metrics\"\n");
+    correct(&home);
+    let spool = home.join("fasterthefuck/telemetry.jsonl");
+    assert_eq!(status(&home), format!("Telemetry: disabled\nSpooled events: 0 ({})\n", spool.display()));
+    assert!(!home.join("fasterthefuck/telemetry.jsonl").exists());
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn test_events_are_spooled_without_the_command() {
+    let home = common::temp_home("telemetry", "spooled");
+    let config = "[telemetry]\nenabled = true\nendpoint = \"https://127.0.0.1:9/metrics\"\nbatch_size = 100\n";
+    common::write_config(&home, config);
+    correct(&home);
+    correct(&home);
+
+    let status = status(&home);
+    assert!(status.starts_with("Telemetry: enabled, sending to https://127.0.0.1:9/metrics\n"), "{}", status);
+    assert!(status.contains("Spooled events: 2 "), "{}", status);
+    let spooled = std::fs::read_to_string(home.join("fasterthefuck/telemetry.jsonl")).unwrap();
+    assert!(spooled.contains("\"rule\":\"git_not_command\""), "{}", spooled);
+    assert!(spooled.contains("\"outcome\":\"accepted\""), "{}", spooled);
+    assert!(!spooled.contains("pus"), "{}", spooled);
+
+    let output = ftf(&home, &["telemetry", "purge"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Deleted 2 spooled events\n");
+    assert!(!home.join("fasterthefuck/telemetry.jsonl").exists());
+
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,103 @@
+//! Integration tests for invoking ftf with thefuck's command line.
+
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const SCRIPT: &str = "git pus";
+const OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+/// Runs `program` with `args` and `env`, isolated from the user's config,
+/// daemon and thefuck variables.
+fn run(program: &Path, dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(program);
+    for key in ["TF_ALIAS", "TF_HISTORY", "TF_SHELL", "TF_OUTPUT", "TF_EXIT_CODE", "THEFUCK_REQUIRE_CONFIRMATION"] {
+        command.env_remove(key);
+    }
+    command
+        .args(args)
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_DATA_HOME", dir)
+        .env("XDG_RUNTIME_DIR", dir)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+fn binary() -> &'static Path {
+    Path::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+}
+
+fn native(dir: &Path) -> String {
+    let output = run(
+        binary(),
+        dir,
+        &["--command", SCRIPT, "--output", OUTPUT, "--exit-code", "1", "--no-interaction"],
+        &[],
+    );
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("ftf_thefuck_compat_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_trailing_command_corrects_like_native() {
+    let dir = temp_dir("trailing");
+    let output = run(
+        binary(),
+        &dir,
+        &["--thefuck-compat", "-y", "git", "pus"],
+        &[("TF_OUTPUT", OUTPUT), ("TF_EXIT_CODE", "1")],
+    );
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), native(&dir));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_thefuck_symlink_reads_history() {
+    let dir = temp_dir("symlink");
+    let thefuck = dir.join("thefuck");
+    std::os::unix::fs::symlink(binary(), &thefuck).unwrap();
+
+    let output = run(
+        &thefuck,
+        &dir,
+        &["THEFUCK_ARGUMENT_PLACEHOLDER", "-y"],
+        &[
+            ("TF_ALIAS", "oops"),
+            ("TF_SHELL", "bash"),
+            ("TF_HISTORY", "ls\ngit pus\noops"),
+            ("TF_OUTPUT", OUTPUT),
+        ],
+    );
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), native(&dir));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_require_confirmation_false_never_prompts() {
+    let dir = temp_dir("confirmation");
+    let output = run(
+        binary(),
+        &dir,
+        &["--thefuck-compat", "--output", OUTPUT, "git", "pus"],
+        &[("THEFUCK_REQUIRE_CONFIRMATION", "False"), ("THEFUCK_NO_COLORS", "true")],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(!stderr.contains("not prompting"), "stderr: {}", stderr);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), native(&dir));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
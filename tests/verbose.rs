@@ -0,0 +1,35 @@
+//! Integration tests for the summary footer of `--verbose`.
+
+use std::process::{Command, Stdio};
+
+const OUTPUT: &str = "git: 'pus' is not a git command. See 'git --help'.\n\nThe most similar command is\n\tpush";
+
+#[test]
+fn test_footer_goes_to_stderr() {
+    let dir = std::env::temp_dir().join(format!("ftf_verbose_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fasterthefuck"))
+        .args(["--command", "git pus", "--output", OUTPUT, "--exit-code", "1"])
+        .args(["--no-interaction", "--verbose"])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .env("XDG_RUNTIME_DIR", &dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert_eq!(stdout.trim(), "git push");
+    let footer = stderr.lines().last().unwrap_or_default();
+    assert!(footer.starts_with("ftf: evaluated "), "stderr: {}", stderr);
+    assert!(footer.contains(" rules (1 matched) in "), "stderr: {}", stderr);
+    assert!(footer.ends_with(", 1 suggestions (0 suppressed)"), "stderr: {}", stderr);
+    assert!(!stdout.contains("evaluated"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}